@@ -0,0 +1,85 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use qlib_rs::data::codec::{decode_frame, rkyv::{self as wire, StoreCommand, StoreResponse}, FRAME_HEADER_SIZE};
+use qlib_rs::data::resp::{ReadResponse, RespDecode, RespEncode, RespFromBytes, RespToBytes, RespValue, WriteCommand};
+use qlib_rs::*;
+
+fn write_command() -> WriteCommand<'static> {
+    WriteCommand {
+        entity_id: EntityId::new(EntityType(3), 123_456),
+        field_path: vec![FieldType(17), FieldType(42)],
+        value: Value::Int(1_234_567_890),
+        writer_id: Some(EntityId::new(EntityType(1), 9)),
+        write_time: Some(now()),
+        push_condition: Some(PushCondition::Changes),
+        adjust_behavior: None,
+        _marker: std::marker::PhantomData,
+    }
+}
+
+fn read_response() -> ReadResponse {
+    ReadResponse {
+        value: Value::EntityList((0..32).map(|i| EntityId::new(EntityType(3), i)).collect()),
+        timestamp: now(),
+        writer_id: Some(EntityId::new(EntityType(1), 9)),
+    }
+}
+
+fn bench_command_codecs(c: &mut Criterion) {
+    let mut group = c.benchmark_group("codec_write_command");
+    let command = write_command();
+
+    group.bench_function("resp_encode", |b| {
+        b.iter(|| black_box(command.encode().to_bytes()))
+    });
+    group.bench_function("rkyv_encode", |b| {
+        b.iter(|| black_box(wire::encode_command(&StoreCommand::from(&command)).unwrap()))
+    });
+
+    let resp_bytes = command.encode().to_bytes();
+    group.bench_function("resp_decode", |b| {
+        b.iter(|| {
+            let (value, _) = RespValue::from_bytes(black_box(&resp_bytes)).unwrap();
+            black_box(WriteCommand::decode(value).unwrap())
+        })
+    });
+
+    // Servers read the payload into an aligned buffer and access it in place
+    let frame = wire::encode_command(&StoreCommand::from(&command)).unwrap();
+    let mut payload = rkyv::util::AlignedVec::<{ wire::FRAME_ALIGNMENT }>::new();
+    payload.extend_from_slice(&frame[FRAME_HEADER_SIZE..]);
+    group.bench_function("rkyv_access", |b| {
+        b.iter(|| black_box(wire::access_command(black_box(&payload)).unwrap().entity_id()))
+    });
+    group.bench_function("rkyv_decode", |b| {
+        b.iter(|| {
+            let (payload, _) = decode_frame(black_box(&frame), &ProtocolLimits::default()).unwrap().unwrap();
+            black_box(wire::decode_command(payload).unwrap())
+        })
+    });
+    group.finish();
+}
+
+fn bench_response_codecs(c: &mut Criterion) {
+    let mut group = c.benchmark_group("codec_read_response");
+    let response = read_response();
+
+    let resp_bytes = response.encode().to_bytes();
+    group.bench_function("resp_decode", |b| {
+        b.iter(|| {
+            let (value, _) = RespValue::from_bytes(black_box(&resp_bytes)).unwrap();
+            black_box(ReadResponse::decode(value).unwrap())
+        })
+    });
+
+    let frame = wire::encode_response(&StoreResponse::from(&response)).unwrap();
+    group.bench_function("rkyv_decode", |b| {
+        b.iter(|| {
+            let (payload, _) = decode_frame(black_box(&frame), &ProtocolLimits::default()).unwrap().unwrap();
+            black_box(wire::decode_response(payload).unwrap().into_read_response().unwrap())
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_command_codecs, bench_response_codecs);
+criterion_main!(benches);
@@ -0,0 +1,46 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use qlib_rs::data::resp::{parse_i64_bytes, OwnedRespValue, RespFromBytes, RespToBytes, RespValue};
+
+fn array_of(elements: Vec<OwnedRespValue>) -> Vec<u8> {
+    OwnedRespValue::Array(elements).to_bytes()
+}
+
+fn bench_integer_parsing(c: &mut Criterion) {
+    let mut group = c.benchmark_group("resp_integer_parsing");
+    for input in ["42", "1234567890", "-9223372036854775808"] {
+        group.bench_with_input(BenchmarkId::new("parse_i64_bytes", input), input.as_bytes(), |b, bytes| {
+            b.iter(|| black_box(parse_i64_bytes(black_box(bytes))))
+        });
+        group.bench_with_input(BenchmarkId::new("str_parse", input), input.as_bytes(), |b, bytes| {
+            b.iter(|| black_box(std::str::from_utf8(black_box(bytes)).ok().and_then(|s| s.parse::<i64>().ok())))
+        });
+    }
+    group.finish();
+}
+
+fn bench_array_decoding(c: &mut Criterion) {
+    let mut group = c.benchmark_group("resp_array_decoding");
+    for size in [100, 10_000] {
+        let integers = array_of((0..size).map(|i| OwnedRespValue::Integer(i * 1_000_003)).collect());
+        group.throughput(Throughput::Bytes(integers.len() as u64));
+        group.bench_with_input(BenchmarkId::new("integers", size), &integers, |b, bytes| {
+            b.iter(|| black_box(RespValue::from_bytes(black_box(bytes)).unwrap()))
+        });
+
+        let strings = array_of((0..size).map(|i| OwnedRespValue::BulkString(format!("entity-name-{:08}", i).into_bytes())).collect());
+        group.throughput(Throughput::Bytes(strings.len() as u64));
+        group.bench_with_input(BenchmarkId::new("bulk_strings", size), &strings, |b, bytes| {
+            b.iter(|| black_box(RespValue::from_bytes(black_box(bytes)).unwrap()))
+        });
+
+        let lines = array_of((0..size).map(|i| OwnedRespValue::SimpleString(format!("status line number {} with some padding text", i))).collect());
+        group.throughput(Throughput::Bytes(lines.len() as u64));
+        group.bench_with_input(BenchmarkId::new("simple_strings", size), &lines, |b, bytes| {
+            b.iter(|| black_box(RespValue::from_bytes(black_box(bytes)).unwrap()))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_integer_parsing, bench_array_decoding);
+criterion_main!(benches);
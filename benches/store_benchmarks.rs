@@ -16,6 +16,8 @@ fn create_entity_schema_with_name(store: &mut Store, entity_type_name: &str) ->
             default_value: String::new(),
             rank: 0,
             storage_scope: StorageScope::Runtime,
+            validator: None,
+            metadata: Default::default(),
         },
     );
 
@@ -26,6 +28,8 @@ fn create_entity_schema_with_name(store: &mut Store, entity_type_name: &str) ->
             default_value: None,
             rank: 1,
             storage_scope: StorageScope::Runtime,
+            validator: None,
+            metadata: Default::default(),
         },
     );
 
@@ -36,6 +40,9 @@ fn create_entity_schema_with_name(store: &mut Store, entity_type_name: &str) ->
             default_value: Vec::new(),
             rank: 2,
             storage_scope: StorageScope::Runtime,
+            merge_policy: MergePolicy::LastWriterWins,
+            validator: None,
+            metadata: Default::default(),
         },
     );
 
@@ -46,6 +53,9 @@ fn create_entity_schema_with_name(store: &mut Store, entity_type_name: &str) ->
             default_value: 0,
             rank: 3,
             storage_scope: StorageScope::Runtime,
+            merge_policy: MergePolicy::LastWriterWins,
+            validator: None,
+            metadata: Default::default(),
         },
     );
 
@@ -56,6 +66,8 @@ fn create_entity_schema_with_name(store: &mut Store, entity_type_name: &str) ->
             default_value: true,
             rank: 4,
             storage_scope: StorageScope::Runtime,
+            validator: None,
+            metadata: Default::default(),
         },
     );
 
@@ -205,6 +217,8 @@ fn bench_inheritance_operations(c: &mut Criterion) {
                 default_value: String::new(),
                 rank: 10,
                 storage_scope: StorageScope::Runtime,
+                validator: None,
+                metadata: Default::default(),
             }
         );
         store.update_schema(user_schema).unwrap();
@@ -218,6 +232,9 @@ fn bench_inheritance_operations(c: &mut Criterion) {
                 default_value: 1,
                 rank: 20,
                 storage_scope: StorageScope::Runtime,
+                merge_policy: MergePolicy::LastWriterWins,
+                validator: None,
+                metadata: Default::default(),
             }
         );
         store.update_schema(admin_schema).unwrap();
@@ -0,0 +1,15 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use qlib_rs::data::codec::{decode_frame, rkyv};
+use qlib_rs::ProtocolLimits;
+
+fuzz_target!(|data: &[u8]| {
+    let limits = ProtocolLimits::default();
+    if let Ok(Some((payload, _))) = decode_frame(data, &limits) {
+        let _ = rkyv::decode_command(payload);
+        let _ = rkyv::decode_response(payload);
+    }
+    // Payloads that skip the frame header reach the decoders too
+    let _ = rkyv::decode_command(data);
+});
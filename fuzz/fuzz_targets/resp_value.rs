@@ -0,0 +1,17 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use qlib_rs::data::resp::{RespFromBytes, RespToBytes, RespValue};
+
+fuzz_target!(|data: &[u8]| {
+    let Ok((value, _)) = RespValue::from_bytes(data) else {
+        return;
+    };
+
+    // Numbers may be written differently ("+007"), but once encoded a
+    // frame parses back to the same encoding
+    let bytes = value.to_bytes();
+    let (again, rest) = RespValue::from_bytes(&bytes).expect("encoded frame parses");
+    assert!(rest.is_empty());
+    assert_eq!(again.to_bytes(), bytes);
+});
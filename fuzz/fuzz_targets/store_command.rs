@@ -0,0 +1,10 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use qlib_rs::data::resp::{RespDecode, RespFromBytes, RespValue, StoreCommand};
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok((value, _)) = RespValue::from_bytes(data) {
+        let _ = StoreCommand::decode(value);
+    }
+});
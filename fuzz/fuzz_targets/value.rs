@@ -0,0 +1,21 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use qlib_rs::data::resp::{RespDecode, RespEncode, RespFromBytes, RespToBytes, RespValue};
+use qlib_rs::Value;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok((frame, _)) = RespValue::from_bytes(data) else {
+        return;
+    };
+    let Ok(value) = Value::decode(frame) else {
+        return;
+    };
+
+    // A decoded value survives a round trip. Encodings are compared since
+    // a NaN never equals itself.
+    let bytes = value.encode().to_bytes();
+    let (frame, _) = RespValue::from_bytes(&bytes).expect("encoded value parses");
+    let again = Value::decode(frame).expect("encoded value decodes");
+    assert_eq!(again.encode().to_bytes(), bytes);
+});
@@ -0,0 +1,248 @@
+#ifndef QLIB_H
+#define QLIB_H
+
+/* Generated from src/capi.rs by cbindgen. Do not edit. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * Outcome of a call. On anything but `Ok`, see `qlib_last_error`.
+ */
+typedef enum QlibStatus {
+  QLIB_STATUS_OK = 0,
+  /**
+   * A required pointer was NULL, a string wasn't UTF-8, or a value was
+   * read as the wrong kind
+   */
+  QLIB_STATUS_INVALID_ARGUMENT = 1,
+  /**
+   * The store rejected the request or the connection failed
+   */
+  QLIB_STATUS_ERROR = 2,
+} QlibStatus;
+
+/**
+ * The variant of a `QlibValue`
+ */
+typedef enum QlibValueKind {
+  QLIB_VALUE_KIND_BLOB,
+  QLIB_VALUE_KIND_BOOL,
+  QLIB_VALUE_KIND_CHOICE,
+  QLIB_VALUE_KIND_ENTITY_LIST,
+  QLIB_VALUE_KIND_ENTITY_REFERENCE,
+  QLIB_VALUE_KIND_FLOAT,
+  QLIB_VALUE_KIND_INT,
+  QLIB_VALUE_KIND_STRING,
+  QLIB_VALUE_KIND_TIMESTAMP,
+  QLIB_VALUE_KIND_DECIMAL,
+  QLIB_VALUE_KIND_DURATION,
+  QLIB_VALUE_KIND_STRING_LIST,
+  QLIB_VALUE_KIND_MAP,
+} QlibValueKind;
+
+/**
+ * A write to a subscribed field
+ */
+typedef struct QlibNotification QlibNotification;
+
+/**
+ * A connection to a qlib server
+ */
+typedef struct QlibStore QlibStore;
+
+/**
+ * Notifications for one `NotifyConfig`, queued by
+ * `qlib_process_notifications`
+ */
+typedef struct QlibSubscription QlibSubscription;
+
+/**
+ * A field value
+ */
+typedef struct QlibValue QlibValue;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Describes the last failure on this thread, or NULL if nothing failed
+ */
+const char *qlib_last_error(void);
+
+/**
+ * Connect to the server at `address`, e.g. "127.0.0.1:8080"
+ */
+struct QlibStore *qlib_connect(const char *address);
+
+/**
+ * Close the connection and release the store
+ */
+void qlib_disconnect(struct QlibStore *store);
+
+/**
+ * The entity at a path such as "Root/Plant/Pump1"
+ */
+enum QlibStatus qlib_resolve_path(const struct QlibStore *store,
+                                  const char *path,
+                                  uint64_t *out_entity_id);
+
+/**
+ * Read a field, following indirection such as "Parent->Name". The value
+ * is stored in `out_value` and released with `qlib_value_free`.
+ */
+enum QlibStatus qlib_read(const struct QlibStore *store,
+                          uint64_t entity_id,
+                          const char *field,
+                          struct QlibValue **out_value);
+
+/**
+ * Write a field. `value` is borrowed.
+ */
+enum QlibStatus qlib_write(const struct QlibStore *store,
+                           uint64_t entity_id,
+                           const char *field,
+                           const struct QlibValue *value);
+
+/**
+ * The entities of a type, optionally matching a CEL `filter` (may be
+ * NULL). The ids are stored in `out_entity_ids` and released with
+ * `qlib_entity_ids_free`.
+ */
+enum QlibStatus qlib_find_entities(const struct QlibStore *store,
+                                   const char *entity_type,
+                                   const char *filter,
+                                   uint64_t **out_entity_ids,
+                                   size_t *out_len);
+
+void qlib_entity_ids_free(uint64_t *entity_ids, size_t len);
+
+/**
+ * Subscribe to writes of `field` on one entity. With `trigger_on_change`,
+ * writes that don't change the value aren't notified.
+ */
+struct QlibSubscription *qlib_subscribe(const struct QlibStore *store,
+                                        uint64_t entity_id,
+                                        const char *field,
+                                        bool trigger_on_change);
+
+/**
+ * Subscribe to writes of `field` on every entity of a type
+ */
+struct QlibSubscription *qlib_subscribe_type(const struct QlibStore *store,
+                                             const char *entity_type,
+                                             const char *field,
+                                             bool trigger_on_change);
+
+/**
+ * Unregister and release a subscription. `store` must be the store it
+ * was created on.
+ */
+void qlib_unsubscribe(const struct QlibStore *store, struct QlibSubscription *subscription);
+
+/**
+ * Read notifications that arrived from the server into their
+ * subscriptions
+ */
+enum QlibStatus qlib_process_notifications(const struct QlibStore *store);
+
+/**
+ * The oldest queued notification, or NULL if there is none. Release it
+ * with `qlib_notification_free`.
+ */
+struct QlibNotification *qlib_subscription_next(const struct QlibSubscription *subscription);
+
+uint64_t qlib_notification_entity_id(const struct QlibNotification *notification);
+
+/**
+ * The written value, or NULL if it couldn't be read
+ */
+const struct QlibValue *qlib_notification_value(const struct QlibNotification *notification);
+
+/**
+ * The value before the write, or NULL if there was none
+ */
+const struct QlibValue *qlib_notification_previous(const struct QlibNotification *notification);
+
+void qlib_notification_free(struct QlibNotification *notification);
+
+struct QlibValue *qlib_value_bool(bool value);
+
+struct QlibValue *qlib_value_int(int64_t value);
+
+struct QlibValue *qlib_value_float(double value);
+
+struct QlibValue *qlib_value_choice(int64_t value);
+
+/**
+ * A reference to `entity_id`, or an empty reference if it is 0
+ */
+struct QlibValue *qlib_value_entity_reference(uint64_t entity_id);
+
+/**
+ * A timestamp, in nanoseconds since the Unix epoch
+ */
+struct QlibValue *qlib_value_timestamp(int64_t unix_nanos);
+
+/**
+ * NULL if `value` is NULL or isn't UTF-8
+ */
+struct QlibValue *qlib_value_string(const char *value);
+
+/**
+ * Copies `len` bytes from `data`
+ */
+struct QlibValue *qlib_value_blob(const uint8_t *data, size_t len);
+
+void qlib_value_free(struct QlibValue *value);
+
+/**
+ * `value` must not be NULL
+ */
+enum QlibValueKind qlib_value_kind(const struct QlibValue *value);
+
+enum QlibStatus qlib_value_get_bool(const struct QlibValue *value, bool *out);
+
+/**
+ * Accepts Int and Choice values
+ */
+enum QlibStatus qlib_value_get_int(const struct QlibValue *value, int64_t *out);
+
+/**
+ * Accepts Float and Int values
+ */
+enum QlibStatus qlib_value_get_float(const struct QlibValue *value, double *out);
+
+/**
+ * Stores 0 for an empty reference
+ */
+enum QlibStatus qlib_value_get_entity_reference(const struct QlibValue *value, uint64_t *out);
+
+/**
+ * Nanoseconds since the Unix epoch
+ */
+enum QlibStatus qlib_value_get_timestamp(const struct QlibValue *value, int64_t *out);
+
+/**
+ * A copy of a String value, released with `qlib_string_free`. NULL if the
+ * value isn't a String.
+ */
+char *qlib_value_get_string(const struct QlibValue *value);
+
+/**
+ * The bytes of a Blob value, borrowed from `value`. NULL if the value
+ * isn't a Blob.
+ */
+const uint8_t *qlib_value_get_blob(const struct QlibValue *value, size_t *out_len);
+
+void qlib_string_free(char *value);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* QLIB_H */
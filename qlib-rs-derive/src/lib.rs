@@ -12,8 +12,70 @@ fn is_phantom_data(ty: &Type) -> bool {
     false
 }
 
+/// Options set with `#[resp(...)]` on a struct field
+#[derive(Default)]
+struct FieldOptions {
+    /// Name sent on the wire instead of the field name
+    rename: Option<String>,
+    /// Use `Default::default()` when the peer doesn't send the field
+    default: bool,
+    /// Never sent; always `Default::default()` when decoded
+    skip: bool,
+}
+
+impl FieldOptions {
+    fn parse(field: &syn::Field) -> syn::Result<Self> {
+        let mut options = FieldOptions {
+            skip: is_phantom_data(&field.ty),
+            ..Default::default()
+        };
+        for attr in field.attrs.iter().filter(|attr| attr.path().is_ident("resp")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("rename") {
+                    if field.ident.is_none() {
+                        return Err(meta.error("rename needs a named field"));
+                    }
+                    options.rename = Some(meta.value()?.parse::<LitStr>()?.value());
+                } else if meta.path.is_ident("default") {
+                    options.default = true;
+                } else if meta.path.is_ident("skip") {
+                    options.skip = true;
+                } else {
+                    return Err(meta.error("expected `rename = \"...\"`, `default` or `skip`"));
+                }
+                Ok(())
+            })?;
+        }
+        Ok(options)
+    }
+
+    /// Key a named field is sent under
+    fn wire_name(&self, field: &syn::Field) -> String {
+        match (&self.rename, &field.ident) {
+            (Some(rename), _) => rename.clone(),
+            (None, Some(ident)) => ident.to_string(),
+            (None, None) => String::new(),
+        }
+    }
+}
+
+/// Variants are encoded by position, so their fields can't be renamed or
+/// skipped; `default` is allowed, for fields appended to a variant
+fn reject_variant_field_options(data: &syn::DataEnum) -> Option<syn::Error> {
+    data.variants.iter()
+        .flat_map(|variant| variant.fields.iter())
+        .find_map(|field| match FieldOptions::parse(field) {
+            Ok(options) if options.rename.is_some() || options.skip => Some(syn::Error::new_spanned(
+                field.attrs.iter().find(|attr| attr.path().is_ident("resp")),
+                "only #[resp(default)] is supported on variant fields",
+            )),
+            Ok(_) => None,
+            Err(e) => Some(e),
+        })
+}
+
 /// Derive macro for `RespEncode` trait
-#[proc_macro_derive(RespEncode)]
+#[proc_macro_derive(RespEncode, attributes(resp))]
 pub fn derive_resp_encode(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
     let name = &input.ident;
@@ -24,19 +86,24 @@ pub fn derive_resp_encode(input: TokenStream) -> TokenStream {
         Data::Struct(data) => {
             match &data.fields {
                 Fields::Named(fields) => {
-                    let non_phantom_fields: Vec<_> = fields.named.iter()
-                        .filter(|field| !is_phantom_data(&field.ty))
-                        .collect();
-                    
-                    let field_encodes: Vec<_> = non_phantom_fields.iter().map(|field| {
+                    let mut field_encodes = Vec::new();
+                    for field in &fields.named {
+                        let options = match FieldOptions::parse(field) {
+                            Ok(options) => options,
+                            Err(e) => return e.to_compile_error().into(),
+                        };
+                        if options.skip {
+                            continue;
+                        }
                         let field_name = &field.ident;
-                        quote! {
+                        let wire_name = options.wire_name(field);
+                        field_encodes.push(quote! {
                             elements.push(crate::data::resp::OwnedRespValue::BulkString(
-                                stringify!(#field_name).as_bytes().to_vec()
+                                #wire_name.as_bytes().to_vec()
                             ));
                             elements.push(crate::data::resp::RespEncode::encode(&self.#field_name));
-                        }
-                    }).collect();
+                        });
+                    }
                     
                     quote! {
                         let mut elements: Vec<crate::data::resp::OwnedRespValue> = Vec::new();
@@ -45,17 +112,20 @@ pub fn derive_resp_encode(input: TokenStream) -> TokenStream {
                     }
                 }
                 Fields::Unnamed(fields) => {
-                    let non_phantom_fields: Vec<_> = fields.unnamed.iter()
-                        .enumerate()
-                        .filter(|(_, field)| !is_phantom_data(&field.ty))
-                        .collect();
-                    
-                    let field_encodes: Vec<_> = non_phantom_fields.iter().map(|(i, _)| {
-                        let index = Index::from(*i);
-                        quote! {
-                            elements.push(crate::data::resp::RespEncode::encode(&self.#index));
+                    let mut field_encodes = Vec::new();
+                    for (i, field) in fields.unnamed.iter().enumerate() {
+                        let options = match FieldOptions::parse(field) {
+                            Ok(options) => options,
+                            Err(e) => return e.to_compile_error().into(),
+                        };
+                        if options.skip {
+                            continue;
                         }
-                    }).collect();
+                        let index = Index::from(i);
+                        field_encodes.push(quote! {
+                            elements.push(crate::data::resp::RespEncode::encode(&self.#index));
+                        });
+                    }
                     
                     quote! {
                         let mut elements: Vec<crate::data::resp::OwnedRespValue> = Vec::new();
@@ -71,6 +141,9 @@ pub fn derive_resp_encode(input: TokenStream) -> TokenStream {
             }
         }
         Data::Enum(data) => {
+            if let Some(e) = reject_variant_field_options(data) {
+                return e.to_compile_error().into();
+            }
             let variant_arms: Vec<_> = data.variants.iter().enumerate().map(|(i, variant)| {
                 let variant_name = &variant.ident;
                 let variant_index = i as u32;
@@ -152,7 +225,7 @@ pub fn derive_resp_encode(input: TokenStream) -> TokenStream {
 }
 
 /// Derive macro for `RespDecode` trait
-#[proc_macro_derive(RespDecode)]
+#[proc_macro_derive(RespDecode, attributes(resp))]
 pub fn derive_resp_decode(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
     let name = &input.ident;
@@ -163,65 +236,101 @@ pub fn derive_resp_decode(input: TokenStream) -> TokenStream {
         Data::Struct(data) => {
             match &data.fields {
                 Fields::Named(fields) => {
-                    let non_phantom_fields: Vec<_> = fields.named.iter()
-                        .filter(|field| !is_phantom_data(&field.ty))
-                        .collect();
-                    let field_count = non_phantom_fields.len();
-                    let field_count_lit = syn::LitInt::new(&field_count.to_string(), proc_macro2::Span::call_site());
-                    
-                    let field_decodes: Vec<_> = non_phantom_fields.iter().enumerate().map(|(i, field)| {
+                    let mut slots = Vec::new();
+                    let mut key_arms = Vec::new();
+                    let mut field_values = Vec::new();
+                    for (i, field) in fields.named.iter().enumerate() {
+                        let options = match FieldOptions::parse(field) {
+                            Ok(options) => options,
+                            Err(e) => return e.to_compile_error().into(),
+                        };
                         let field_name = &field.ident;
-                        let field_index = i * 2 + 1; // Skip field name, get value
-                        quote! {
-                            let #field_name = if let crate::data::resp::RespValue::BulkString(key) = &elements[#field_index - 1] {
-                                if key == &stringify!(#field_name).as_bytes().to_vec() {
-                                    <_ as crate::data::resp::RespDecode>::decode(elements[#field_index].clone())?
-                                } else {
-                                    return Err(crate::Error::InvalidRequest(format!("Expected field '{}', got '{}'", stringify!(#field_name), String::from_utf8_lossy(key))));
-                                }
-                            } else {
-                                return Err(crate::Error::InvalidRequest("Expected bulk string for field name".to_string()));
-                            };
+                        if options.skip {
+                            field_values.push(quote! { #field_name: ::core::default::Default::default() });
+                            continue;
                         }
-                    }).collect();
-                    
-                    // Generate phantom data assignments
-                    let phantom_assignments: Vec<_> = fields.named.iter().filter_map(|field| {
-                        let field_name = &field.ident;
-                        if is_phantom_data(&field.ty) {
-                            Some(quote! { #field_name: std::marker::PhantomData })
+                        // Decoded values are held in slots so field names can't shadow locals
+                        let slot = format_ident!("slot_{}", i);
+                        let field_type = &field.ty;
+                        let wire_name = options.wire_name(field);
+                        let wire_key = syn::LitByteStr::new(wire_name.as_bytes(), proc_macro2::Span::call_site());
+                        slots.push(quote! { let mut #slot: Option<#field_type> = None; });
+                        key_arms.push(quote! {
+                            #wire_key => #slot = Some(<#field_type as crate::data::resp::RespDecode>::decode(value)?),
+                        });
+                        let missing = if options.default {
+                            quote! { ::core::default::Default::default() }
                         } else {
-                            Some(quote! { #field_name })
-                        }
-                    }).collect();
+                            quote! {
+                                return Err(crate::Error::InvalidRequest(format!("Missing field '{}' for struct {}", #wire_name, stringify!(#name))))
+                            }
+                        };
+                        field_values.push(quote! {
+                            #field_name: match #slot {
+                                Some(value) => value,
+                                None => #missing,
+                            }
+                        });
+                    }
+                    
+                    // Every field may be skipped, leaving nothing to decode
+                    let value = if key_arms.is_empty() { quote! { _ } } else { quote! { value } };
                     
                     quote! {
                         match input {
                             crate::data::resp::RespValue::Array(elements) => {
-                                if elements.len() != #field_count_lit * 2 {
+                                if elements.len() % 2 != 0 {
                                     return Err(crate::Error::InvalidRequest(format!(
-                                        "Expected exactly {} elements for struct {}, got {}",
-                                        #field_count_lit * 2, stringify!(#name), elements.len()
+                                        "Expected field name/value pairs for struct {}, got {} elements",
+                                        stringify!(#name), elements.len()
                                     )));
                                 }
-                                #(#field_decodes)*
-                                Ok(Self { #(#phantom_assignments),* })
+                                #(#slots)*
+                                let mut elements = elements.into_iter();
+                                while let (Some(key), Some(#value)) = (elements.next(), elements.next()) {
+                                    let crate::data::resp::RespValue::BulkString(key) = key else {
+                                        return Err(crate::Error::InvalidRequest("Expected bulk string for field name".to_string()));
+                                    };
+                                    // Fields added by newer peers are ignored
+                                    #[allow(clippy::match_single_binding)]
+                                    match key {
+                                        #(#key_arms)*
+                                        _ => {}
+                                    }
+                                }
+                                Ok(Self { #(#field_values),* })
                             }
                             _ => Err(crate::Error::InvalidRequest("Expected array for struct".to_string())),
                         }
                     }
                 }
                 Fields::Unnamed(fields) => {
-                    let field_decodes: Vec<_> = (0..fields.unnamed.len()).map(|i| {
+                    let mut field_decodes = Vec::new();
+                    let mut position = 0usize;
+                    for (i, field) in fields.unnamed.iter().enumerate() {
+                        let options = match FieldOptions::parse(field) {
+                            Ok(options) => options,
+                            Err(e) => return e.to_compile_error().into(),
+                        };
                         let field_name = format_ident!("field_{}", i);
-                        quote! {
-                            let #field_name = if elements.len() > #i {
-                                <_ as crate::data::resp::RespDecode>::decode(elements[#i].clone())?
+                        if options.skip {
+                            field_decodes.push(quote! { let #field_name = ::core::default::Default::default(); });
+                            continue;
+                        }
+                        let missing = if options.default {
+                            quote! { ::core::default::Default::default() }
+                        } else {
+                            quote! { return Err(crate::Error::InvalidRequest(format!("Missing field {}", #i))) }
+                        };
+                        field_decodes.push(quote! {
+                            let #field_name = if elements.len() > #position {
+                                <_ as crate::data::resp::RespDecode>::decode(elements[#position].clone())?
                             } else {
-                                return Err(crate::Error::InvalidRequest(format!("Missing field {}", #i)));
+                                #missing
                             };
-                        }
-                    }).collect();
+                        });
+                        position += 1;
+                    }
                     
                     let field_names: Vec<_> = (0..fields.unnamed.len())
                         .map(|i| format_ident!("field_{}", i))
@@ -248,6 +357,9 @@ pub fn derive_resp_decode(input: TokenStream) -> TokenStream {
             }
         }
         Data::Enum(data) => {
+            if let Some(e) = reject_variant_field_options(data) {
+                return e.to_compile_error().into();
+            }
             let variant_arms: Vec<_> = data.variants.iter().enumerate().map(|(i, variant)| {
                 let variant_name = &variant.ident;
                 let variant_index = i as i64;
@@ -257,11 +369,17 @@ pub fn derive_resp_decode(input: TokenStream) -> TokenStream {
                         let field_decodes: Vec<_> = fields.named.iter().enumerate().map(|(field_i, field)| {
                             let field_name = &field.ident;
                             let element_index = field_i + 1; // Skip variant discriminant
+                            // Options were checked by reject_variant_field_options
+                            let missing = if FieldOptions::parse(field).is_ok_and(|options| options.default) {
+                                quote! { ::core::default::Default::default() }
+                            } else {
+                                quote! { return Err(crate::Error::InvalidRequest(format!("Missing field {} for variant {}", stringify!(#field_name), stringify!(#variant_name)))) }
+                            };
                             quote! {
                                 let #field_name = if elements.len() > #element_index {
                                     <_ as crate::data::resp::RespDecode>::decode(elements[#element_index].clone())?
                                 } else {
-                                    return Err(crate::Error::InvalidRequest(format!("Missing field {} for variant {}", stringify!(#field_name), stringify!(#variant_name))));
+                                    #missing
                                 };
                             }
                         }).collect();
@@ -364,15 +482,50 @@ pub fn derive_resp_decode(input: TokenStream) -> TokenStream {
 ///     _marker: std::marker::PhantomData<&'a ()>,
 /// }
 /// ```
+///
+/// On an enum it generates one dispatcher for several commands. A variant
+/// that wraps a single command is encoded and decoded by that command; any
+/// other variant names its own command and its fields follow the name:
+/// ```rust,ignore
+/// #[respc]
+/// #[derive(Debug, Clone)]
+/// pub enum Command<'a> {
+///     Read(ReadCommand<'a>),
+///     #[respc(name = "PING")]
+///     Ping,
+///     #[respc(name = "ECHO")]
+///     Echo { message: String },
+/// }
+/// ```
 #[proc_macro_attribute]
 pub fn respc(args: TokenStream, input: TokenStream) -> TokenStream {
-    let input = parse_macro_input!(input as DeriveInput);
+    let mut input = parse_macro_input!(input as DeriveInput);
     let args = parse_macro_input!(args as RespCommandArgs);
+
+    if let Data::Enum(_) = &input.data {
+        if args.name.is_some() {
+            return syn::Error::new_spanned(&input, "respc on an enum takes no name; name the variants instead")
+                .to_compile_error()
+                .into();
+        }
+        return respc_enum(input);
+    }
     
+    // Arguments may be left off the end of a command for the fields marked
+    // #[resp(default)], e.g. ones added after older peers were deployed
+    let defaults = match command_field_defaults(&mut input) {
+        Ok(defaults) => defaults,
+        Err(e) => return e.to_compile_error().into(),
+    };
+
     let name = &input.ident;
     let generics = &input.generics;
     let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
-    let command_name = args.name;
+    let Some(command_name) = args.name else {
+        return syn::Error::new_spanned(&input, "respc on a struct needs name = \"...\"")
+            .to_compile_error()
+            .into();
+    };
 
     // Generate field encoding for the RespEncode override
     let encode_fields = match &input.data {
@@ -407,7 +560,7 @@ pub fn respc(args: TokenStream, input: TokenStream) -> TokenStream {
             }
         }
         _ => {
-            return syn::Error::new_spanned(&input, "respc can only be used with structs")
+            return syn::Error::new_spanned(&input, "respc can only be used with structs and enums")
                 .to_compile_error()
                 .into();
         }
@@ -423,15 +576,22 @@ pub fn respc(args: TokenStream, input: TokenStream) -> TokenStream {
                         .collect();
                     let field_count = non_phantom_fields.len();
                     let field_count_lit = syn::LitInt::new(&field_count.to_string(), proc_macro2::Span::call_site());
+                    let required_count = defaults.iter().take_while(|default| !**default).count();
+                    let required_count_lit = syn::LitInt::new(&required_count.to_string(), proc_macro2::Span::call_site());
                     
                     let field_decodes: Vec<_> = non_phantom_fields.iter().enumerate().map(|(i, field)| {
                         let field_name = &field.ident;
                         let field_index = i + 1; // Skip command name
+                        let missing = if defaults[i] {
+                            quote! { ::core::default::Default::default() }
+                        } else {
+                            quote! { return Err(crate::Error::InvalidRequest(format!("Missing field {}", stringify!(#field_name)))) }
+                        };
                         quote! {
                             let #field_name = if elements.len() > #field_index {
                                 <_ as crate::data::resp::RespDecode>::decode(elements[#field_index].clone())?
                             } else {
-                                return Err(crate::Error::InvalidRequest(format!("Missing field {}", stringify!(#field_name))));
+                                #missing
                             };
                         }
                     }).collect();
@@ -451,7 +611,7 @@ pub fn respc(args: TokenStream, input: TokenStream) -> TokenStream {
                             crate::data::resp::RespValue::Array(elements) => {
                                 // Check if we have the expected command name
                                 if elements.is_empty() {
-                                    return Err(crate::Error::InvalidRequest("Empty array for command".to_string()));
+                                    return Err(crate::Error::from(crate::data::resp::ProtocolError::MalformedFrame("Empty array for command".to_string())));
                                 }
                                 
                                 // Verify command name matches
@@ -459,7 +619,7 @@ pub fn respc(args: TokenStream, input: TokenStream) -> TokenStream {
                                 match &elements[0] {
                                     crate::data::resp::RespValue::BulkString(cmd_bytes) => {
                                         let cmd_str = std::str::from_utf8(cmd_bytes)
-                                            .map_err(|_| crate::Error::InvalidRequest("Invalid UTF-8 in command name".to_string()))?;
+                                            .map_err(|_| crate::Error::from(crate::data::resp::ProtocolError::MalformedFrame("Invalid UTF-8 in command name".to_string())))?;
                                         if cmd_str != expected_cmd {
                                             return Err(crate::Error::InvalidRequest(format!("Expected command '{}', got '{}'", expected_cmd, cmd_str)));
                                         }
@@ -469,19 +629,18 @@ pub fn respc(args: TokenStream, input: TokenStream) -> TokenStream {
                                             return Err(crate::Error::InvalidRequest(format!("Expected command '{}', got '{}'", expected_cmd, cmd_str)));
                                         }
                                     }
-                                    _ => return Err(crate::Error::InvalidRequest("Expected command name as first element".to_string())),
+                                    _ => return Err(crate::Error::from(crate::data::resp::ProtocolError::MalformedFrame("Expected command name as first element".to_string()))),
                                 }
                                 
-                                if elements.len() != 1 + #field_count_lit {
-                                    return Err(crate::Error::InvalidRequest(format!(
-                                        "Expected exactly {} elements for command {}, got {}",
-                                        1 + #field_count_lit, stringify!(#name), elements.len()
-                                    )));
+                                if elements.len() < 1 + #required_count_lit || elements.len() > 1 + #field_count_lit {
+                                    return Err(crate::data::resp::ProtocolError::ArityMismatch(
+                                        expected_cmd.to_string(), 1 + #field_count_lit, elements.len()
+                                    ).into());
                                 }
                                 #(#field_decodes)*
                                 Ok(Self { #(#phantom_assignments),* })
                             }
-                            _ => Err(crate::Error::InvalidRequest("Expected array for command".to_string())),
+                            _ => Err(crate::Error::from(crate::data::resp::ProtocolError::MalformedFrame("Expected array for command".to_string()))),
                         }
                     }
                 }
@@ -520,7 +679,7 @@ pub fn respc(args: TokenStream, input: TokenStream) -> TokenStream {
                             crate::data::resp::RespValue::Array(elements) => {
                                 // Check if we have the expected command name
                                 if elements.is_empty() {
-                                    return Err(crate::Error::InvalidRequest("Empty array for command".to_string()));
+                                    return Err(crate::Error::from(crate::data::resp::ProtocolError::MalformedFrame("Empty array for command".to_string())));
                                 }
                                 
                                 // Verify command name matches
@@ -528,7 +687,7 @@ pub fn respc(args: TokenStream, input: TokenStream) -> TokenStream {
                                 match &elements[0] {
                                     crate::data::resp::RespValue::BulkString(cmd_bytes) => {
                                         let cmd_str = std::str::from_utf8(cmd_bytes)
-                                            .map_err(|_| crate::Error::InvalidRequest("Invalid UTF-8 in command name".to_string()))?;
+                                            .map_err(|_| crate::Error::from(crate::data::resp::ProtocolError::MalformedFrame("Invalid UTF-8 in command name".to_string())))?;
                                         if cmd_str != expected_cmd {
                                             return Err(crate::Error::InvalidRequest(format!("Expected command '{}', got '{}'", expected_cmd, cmd_str)));
                                         }
@@ -538,20 +697,19 @@ pub fn respc(args: TokenStream, input: TokenStream) -> TokenStream {
                                             return Err(crate::Error::InvalidRequest(format!("Expected command '{}', got '{}'", expected_cmd, cmd_str)));
                                         }
                                     }
-                                    _ => return Err(crate::Error::InvalidRequest("Expected command name as first element".to_string())),
+                                    _ => return Err(crate::Error::from(crate::data::resp::ProtocolError::MalformedFrame("Expected command name as first element".to_string()))),
                                 }
                                 
                                 if elements.len() != 1 + #field_count_lit {
-                                    return Err(crate::Error::InvalidRequest(format!(
-                                        "Expected exactly {} elements for command {}, got {}",
-                                        1 + #field_count_lit, stringify!(#name), elements.len()
-                                    )));
+                                    return Err(crate::data::resp::ProtocolError::ArityMismatch(
+                                        expected_cmd.to_string(), 1 + #field_count_lit, elements.len()
+                                    ).into());
                                 }
                                 
                                 #(#field_decodes)*
                                 Ok(Self(#(#field_assignments),*))
                             }
-                            _ => Err(crate::Error::InvalidRequest("Expected array for command".to_string())),
+                            _ => Err(crate::Error::from(crate::data::resp::ProtocolError::MalformedFrame("Expected array for command".to_string()))),
                         }
                     }
                 }
@@ -561,7 +719,7 @@ pub fn respc(args: TokenStream, input: TokenStream) -> TokenStream {
                             crate::data::resp::RespValue::Array(elements) => {
                                 // Check if we have the expected command name
                                 if elements.is_empty() {
-                                    return Err(crate::Error::InvalidRequest("Empty array for command".to_string()));
+                                    return Err(crate::Error::from(crate::data::resp::ProtocolError::MalformedFrame("Empty array for command".to_string())));
                                 }
                                 
                                 // Verify command name matches
@@ -569,7 +727,7 @@ pub fn respc(args: TokenStream, input: TokenStream) -> TokenStream {
                                 match &elements[0] {
                                     crate::data::resp::RespValue::BulkString(cmd_bytes) => {
                                         let cmd_str = std::str::from_utf8(cmd_bytes)
-                                            .map_err(|_| crate::Error::InvalidRequest("Invalid UTF-8 in command name".to_string()))?;
+                                            .map_err(|_| crate::Error::from(crate::data::resp::ProtocolError::MalformedFrame("Invalid UTF-8 in command name".to_string())))?;
                                         if cmd_str != expected_cmd {
                                             return Err(crate::Error::InvalidRequest(format!("Expected command '{}', got '{}'", expected_cmd, cmd_str)));
                                         }
@@ -579,26 +737,25 @@ pub fn respc(args: TokenStream, input: TokenStream) -> TokenStream {
                                             return Err(crate::Error::InvalidRequest(format!("Expected command '{}', got '{}'", expected_cmd, cmd_str)));
                                         }
                                     }
-                                    _ => return Err(crate::Error::InvalidRequest("Expected command name as first element".to_string())),
+                                    _ => return Err(crate::Error::from(crate::data::resp::ProtocolError::MalformedFrame("Expected command name as first element".to_string()))),
                                 }
                                 
                                 if elements.len() != 1 {
-                                    return Err(crate::Error::InvalidRequest(format!(
-                                        "Expected exactly 1 element for command {}, got {}",
-                                        stringify!(#name), elements.len()
-                                    )));
+                                    return Err(crate::data::resp::ProtocolError::ArityMismatch(
+                                        expected_cmd.to_string(), 1, elements.len()
+                                    ).into());
                                 }
                                 
                                 Ok(Self)
                             }
-                            _ => Err(crate::Error::InvalidRequest("Expected array for command".to_string())),
+                            _ => Err(crate::Error::from(crate::data::resp::ProtocolError::MalformedFrame("Expected array for command".to_string()))),
                         }
                     }
                 }
             }
         }
         _ => {
-            return syn::Error::new_spanned(&input, "respc can only be used with structs")
+            return syn::Error::new_spanned(&input, "respc can only be used with structs and enums")
                 .to_compile_error()
                 .into();
         }
@@ -640,13 +797,215 @@ pub fn respc(args: TokenStream, input: TokenStream) -> TokenStream {
     TokenStream::from(expanded)
 }
 
+/// Whether each non-phantom named field of a command is `#[resp(default)]`,
+/// with the `resp` attributes removed since `respc` isn't a derive. Only
+/// trailing fields can have defaults, as arguments are positional.
+fn command_field_defaults(input: &mut DeriveInput) -> syn::Result<Vec<bool>> {
+    let Data::Struct(data) = &mut input.data else {
+        return Ok(Vec::new());
+    };
+    let Fields::Named(fields) = &mut data.fields else {
+        return Ok(Vec::new());
+    };
+
+    let mut defaults = Vec::new();
+    for field in fields.named.iter_mut().filter(|field| !is_phantom_data(&field.ty)) {
+        let options = FieldOptions::parse(field)?;
+        let attr = field.attrs.iter().find(|attr| attr.path().is_ident("resp"));
+        if options.rename.is_some() || options.skip {
+            return Err(syn::Error::new_spanned(attr, "only #[resp(default)] is supported on command fields"));
+        }
+        if !options.default && defaults.last() == Some(&true) {
+            return Err(syn::Error::new_spanned(&field.ident, "fields after a #[resp(default)] field need one too"));
+        }
+        defaults.push(options.default);
+        field.attrs.retain(|attr| !attr.path().is_ident("resp"));
+    }
+    Ok(defaults)
+}
+
+/// Expand `#[respc]` on an enum into a dispatcher over its variants' commands
+fn respc_enum(mut input: DeriveInput) -> TokenStream {
+    let name = input.ident.clone();
+    let generics = input.generics.clone();
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let Data::Enum(data) = &mut input.data else {
+        unreachable!("respc_enum is only called for enums");
+    };
+
+    let mut encode_arms = Vec::new();
+    let mut decode_arms = Vec::new();
+    let mut name_arms = Vec::new();
+    for variant in data.variants.iter_mut() {
+        let variant_name = variant.ident.clone();
+
+        // The variant attribute is ours, so it is removed from the output
+        let mut command_name = None;
+        let mut error = None;
+        variant.attrs.retain(|attr| {
+            if !attr.path().is_ident("respc") {
+                return true;
+            }
+            match attr.parse_args::<RespCommandArgs>() {
+                Ok(args) => command_name = args.name,
+                Err(e) => error = Some(e),
+            }
+            false
+        });
+        if let Some(e) = error {
+            return e.to_compile_error().into();
+        }
+
+        let Some(command_name) = command_name else {
+            // A variant wrapping a command is handled by that command
+            let ty = match &variant.fields {
+                Fields::Unnamed(fields) if fields.unnamed.len() == 1 => &fields.unnamed[0].ty,
+                _ => {
+                    return syn::Error::new_spanned(&variant.ident, "respc variants need #[respc(name = \"...\")] unless they wrap a single command")
+                        .to_compile_error()
+                        .into();
+                }
+            };
+            encode_arms.push(quote! {
+                Self::#variant_name(command) => crate::data::resp::RespEncode::encode(command),
+            });
+            name_arms.push(quote! {
+                Self::#variant_name(_) => <#ty as crate::data::resp::RespCommand>::COMMAND_NAME,
+            });
+            decode_arms.push(quote! {
+                if command_name == <#ty as crate::data::resp::RespCommand>::COMMAND_NAME {
+                    return <#ty as crate::data::resp::RespDecode>::decode(crate::data::resp::RespValue::Array(elements))
+                        .map(Self::#variant_name);
+                }
+            });
+            continue;
+        };
+
+        // Bindings for every field, with PhantomData fields left out of the frame
+        let (pattern, constructor, bindings) = match &variant.fields {
+            Fields::Named(fields) => {
+                let bindings: Vec<_> = fields.named.iter()
+                    .filter(|field| !is_phantom_data(&field.ty))
+                    .map(|field| field.ident.clone().unwrap())
+                    .collect();
+                let values: Vec<_> = fields.named.iter().map(|field| {
+                    let field_name = &field.ident;
+                    if is_phantom_data(&field.ty) {
+                        quote! { #field_name: std::marker::PhantomData }
+                    } else {
+                        quote! { #field_name }
+                    }
+                }).collect();
+                (
+                    quote! { Self::#variant_name { #(#bindings,)* .. } },
+                    quote! { Self::#variant_name { #(#values),* } },
+                    bindings,
+                )
+            }
+            Fields::Unnamed(fields) => {
+                let names: Vec<_> = (0..fields.unnamed.len()).map(|i| format_ident!("field_{}", i)).collect();
+                let patterns: Vec<_> = fields.unnamed.iter().zip(&names).map(|(field, field_name)| {
+                    if is_phantom_data(&field.ty) { quote! { _ } } else { quote! { #field_name } }
+                }).collect();
+                let values: Vec<_> = fields.unnamed.iter().zip(&names).map(|(field, field_name)| {
+                    if is_phantom_data(&field.ty) { quote! { std::marker::PhantomData } } else { quote! { #field_name } }
+                }).collect();
+                let bindings = fields.unnamed.iter().zip(names)
+                    .filter(|(field, _)| !is_phantom_data(&field.ty))
+                    .map(|(_, field_name)| field_name)
+                    .collect();
+                (
+                    quote! { Self::#variant_name(#(#patterns),*) },
+                    quote! { Self::#variant_name(#(#values),*) },
+                    bindings,
+                )
+            }
+            Fields::Unit => (quote! { Self::#variant_name }, quote! { Self::#variant_name }, Vec::new()),
+        };
+
+        let arity = 1 + bindings.len();
+        let field_decodes: Vec<_> = bindings.iter().enumerate().map(|(i, binding)| {
+            let element_index = i + 1; // Skip command name
+            quote! {
+                let #binding = <_ as crate::data::resp::RespDecode>::decode(elements[#element_index].clone())?;
+            }
+        }).collect();
+
+        encode_arms.push(quote! {
+            #pattern => {
+                let mut elements: Vec<crate::data::resp::OwnedRespValue> = vec![crate::data::resp::OwnedRespValue::BulkString(#command_name.as_bytes().to_vec())];
+                #(elements.push(crate::data::resp::RespEncode::encode(#bindings));)*
+                crate::data::resp::OwnedRespValue::Array(elements)
+            }
+        });
+        name_arms.push(quote! {
+            #pattern => #command_name,
+        });
+        decode_arms.push(quote! {
+            if command_name == #command_name {
+                if elements.len() != #arity {
+                    return Err(crate::data::resp::ProtocolError::ArityMismatch(
+                        command_name.to_string(), #arity, elements.len()
+                    ).into());
+                }
+                #(#field_decodes)*
+                return Ok(#constructor);
+            }
+        });
+    }
+
+    let expanded = quote! {
+        #input
+
+        impl #impl_generics #name #ty_generics #where_clause {
+            /// Name of the command this value is sent as
+            pub fn command_name(&self) -> &'static str {
+                #[allow(unused_variables)]
+                match self {
+                    #(#name_arms)*
+                }
+            }
+        }
+
+        impl #impl_generics crate::data::resp::RespEncode for #name #ty_generics #where_clause {
+            fn encode(&self) -> crate::data::resp::OwnedRespValue {
+                match self {
+                    #(#encode_arms)*
+                }
+            }
+        }
+
+        impl #impl_generics crate::data::resp::RespDecode<'_> for #name #ty_generics #where_clause {
+            fn decode(input: crate::data::resp::RespValue<'_>) -> crate::Result<Self> {
+                let crate::data::resp::RespValue::Array(elements) = input else {
+                    return Err(crate::Error::from(crate::data::resp::ProtocolError::MalformedFrame("Expected array for command".to_string())));
+                };
+                let command_name = match elements.first() {
+                    Some(crate::data::resp::RespValue::BulkString(cmd_bytes)) => std::str::from_utf8(*cmd_bytes)
+                        .map_err(|_| crate::Error::from(crate::data::resp::ProtocolError::MalformedFrame("Invalid UTF-8 in command name".to_string())))?,
+                    Some(crate::data::resp::RespValue::SimpleString(cmd_str)) => *cmd_str,
+                    Some(_) => return Err(crate::Error::from(crate::data::resp::ProtocolError::MalformedFrame("Expected command name as first element".to_string()))),
+                    None => return Err(crate::Error::from(crate::data::resp::ProtocolError::MalformedFrame("Empty array for command".to_string()))),
+                };
+                #(#decode_arms)*
+                Err(crate::data::resp::ProtocolError::UnknownCommand(command_name.to_string()).into())
+            }
+        }
+    };
+
+    TokenStream::from(expanded)
+}
+
 /// Parser for the respc attribute arguments
 struct RespCommandArgs {
-    name: String,
+    name: Option<String>,
 }
 
 impl Parse for RespCommandArgs {
     fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        if input.is_empty() {
+            return Ok(RespCommandArgs { name: None });
+        }
         let name_ident: Ident = input.parse()?;
         if name_ident != "name" {
             return Err(syn::Error::new_spanned(name_ident, "Expected 'name'"));
@@ -656,7 +1015,118 @@ impl Parse for RespCommandArgs {
         let name_lit: LitStr = input.parse()?;
         
         Ok(RespCommandArgs {
-            name: name_lit.value(),
+            name: Some(name_lit.value()),
         })
     }
 }
+
+/// Helper function to check if a type is an Option
+fn is_option(ty: &Type) -> bool {
+    if let Type::Path(type_path) = ty {
+        if let Some(segment) = type_path.path.segments.last() {
+            return segment.ident == "Option";
+        }
+    }
+    false
+}
+
+/// Convert a snake_case field name to the PascalCase store name
+fn to_pascal_case(name: &str) -> String {
+    name.split('_')
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            let mut chars = part.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect::<String>(),
+                None => String::new(),
+            }
+        })
+        .collect()
+}
+
+/// Derive macro for the `FieldTypes` trait
+///
+/// Every field must be a `FieldType` or an `Option<FieldType>`. The store name
+/// is the field name in PascalCase unless overridden with
+/// `#[field_type(name = "...")]`. Required fields fail resolution with
+/// `Error::FieldTypeStrNotFound` when the store doesn't know them; optional
+/// fields are left `None`.
+///
+/// Unlike the RESP derives, the generated code refers to `::qlib_rs`, so it
+/// can be used from other crates.
+///
+/// # Example
+/// ```ignore
+/// #[derive(FieldTypes)]
+/// pub struct PumpFields {
+///     pub speed: FieldType,
+///     #[field_type(name = "RPMSetpoint")]
+///     pub setpoint: Option<FieldType>,
+/// }
+/// ```
+#[proc_macro_derive(FieldTypes, attributes(field_type))]
+pub fn derive_field_types(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return syn::Error::new_spanned(&input, "FieldTypes can only be derived for structs with named fields")
+                    .to_compile_error()
+                    .into();
+            }
+        },
+        _ => {
+            return syn::Error::new_spanned(&input, "FieldTypes can only be derived for structs")
+                .to_compile_error()
+                .into();
+        }
+    };
+
+    let mut names = Vec::new();
+    let mut field_inits = Vec::new();
+    for (index, field) in fields.iter().enumerate() {
+        let field_name = field.ident.as_ref().expect("named field");
+
+        let mut store_name = to_pascal_case(&field_name.to_string());
+        for attr in field.attrs.iter().filter(|attr| attr.path().is_ident("field_type")) {
+            let result = attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("name") {
+                    let value: LitStr = meta.value()?.parse()?;
+                    store_name = value.value();
+                    Ok(())
+                } else {
+                    Err(meta.error("Expected 'name'"))
+                }
+            });
+            if let Err(e) = result {
+                return e.to_compile_error().into();
+            }
+        }
+
+        let init = if is_option(&field.ty) {
+            quote! { #field_name: ids.get(#index).copied().flatten() }
+        } else {
+            quote! { #field_name: ::qlib_rs::data::type_registry::required_field_type(ids, #index, #store_name)? }
+        };
+        names.push(store_name);
+        field_inits.push(init);
+    }
+
+    let expanded = quote! {
+        impl #impl_generics ::qlib_rs::FieldTypes for #name #ty_generics #where_clause {
+            const NAMES: &'static [&'static str] = &[#(#names),*];
+
+            fn from_field_types(ids: &[Option<::qlib_rs::FieldType>]) -> ::qlib_rs::Result<Self> {
+                Ok(Self {
+                    #(#field_inits),*
+                })
+            }
+        }
+    };
+
+    TokenStream::from(expanded)
+}
@@ -0,0 +1,551 @@
+//! Python bindings for qlib-rs.
+//!
+//! Builds the `qlib` extension module (`maturin develop` from this
+//! directory). Field and entity type names are used wherever the Rust API
+//! takes a `FieldType` or `EntityType`, and values are converted to and
+//! from native Python objects using the schema of the field being written:
+//!
+//! | Value           | Python                          |
+//! |-----------------|---------------------------------|
+//! | Blob            | `bytes`                         |
+//! | Bool            | `bool`                          |
+//! | Choice          | `int` (a choice name on write)  |
+//! | EntityList      | `list[EntityId]`                |
+//! | EntityReference | `EntityId` or `None`            |
+//! | Float / Int     | `float` / `int`                 |
+//! | String          | `str`                           |
+//! | Timestamp       | `datetime` (UTC)                |
+//! | Decimal         | `decimal.Decimal`               |
+//! | Duration        | `timedelta`                     |
+//! | StringList      | `list[str]`                     |
+//! | Map             | `dict`                          |
+//!
+//! Wrap a value in `qlib.Value(value, kind)` to pick the variant explicitly.
+
+// Triggered by the code #[pymethods] generates for PyResult returns
+#![allow(clippy::useless_conversion)]
+
+use std::collections::HashMap;
+
+use crossbeam::channel::{unbounded, Receiver, Sender};
+use pyo3::create_exception;
+use pyo3::exceptions::{PyException, PyTypeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{
+    timezone_utc_bound, PyBool, PyBytes, PyDateTime, PyDelta, PyDeltaAccess, PyDict, PyFloat, PyInt, PyList, PyString,
+};
+use qlib_rs::expr::cel_value_to_value;
+use qlib_rs::{
+    parse_field_path, CelExecutor, Decimal, Duration, EntityId, FieldSchema, FieldType, Notification, NotifyConfig, StoreProxy,
+    Timestamp, Value,
+};
+
+create_exception!(qlib, QlibError, PyException);
+
+fn to_py_err(error: qlib_rs::Error) -> PyErr {
+    QlibError::new_err(error.to_string())
+}
+
+const MICROS_PER_DAY: i64 = 86_400_000_000;
+
+/// An entity id. Equal ids compare and hash equal, and `int(id)` gives the
+/// raw 64-bit value.
+#[pyclass(name = "EntityId", module = "qlib", frozen, eq, ord, hash)]
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Hash)]
+struct PyEntityId(EntityId);
+
+#[pymethods]
+impl PyEntityId {
+    #[new]
+    fn new(id: u64) -> Self {
+        PyEntityId(EntityId(id))
+    }
+
+    /// The id of the entity's type
+    #[getter]
+    fn entity_type(&self) -> u32 {
+        self.0.extract_type().0
+    }
+
+    /// The id within the entity's type
+    #[getter]
+    fn id(&self) -> u32 {
+        self.0.extract_id()
+    }
+
+    fn __int__(&self) -> u64 {
+        self.0 .0
+    }
+
+    fn __repr__(&self) -> String {
+        format!("EntityId({})", self.0 .0)
+    }
+}
+
+/// A store value with an explicit variant, for when the native Python
+/// object is ambiguous (e.g. a `Choice` or a `Decimal` inside a `Map`)
+#[pyclass(name = "Value", module = "qlib", frozen, eq)]
+#[derive(Clone, PartialEq)]
+struct PyValue(Value);
+
+#[pymethods]
+impl PyValue {
+    /// `kind` is a variant name such as "Int" or "Choice". Without it the
+    /// variant is inferred from the Python type.
+    #[new]
+    #[pyo3(signature = (value, kind=None))]
+    fn new(value: &Bound<'_, PyAny>, kind: Option<&str>) -> PyResult<Self> {
+        match kind {
+            Some(kind) => value_from_py(value, &template(kind)?, &[]),
+            None => infer_value(value),
+        }
+        .map(PyValue)
+    }
+
+    #[getter]
+    fn kind(&self) -> &'static str {
+        kind(&self.0)
+    }
+
+    #[getter]
+    fn value(&self, py: Python<'_>) -> PyResult<PyObject> {
+        value_to_py(py, &self.0)
+    }
+
+    fn __repr__(&self, py: Python<'_>) -> PyResult<String> {
+        Ok(format!("Value({}, '{}')", value_to_py(py, &self.0)?.bind(py).repr()?, kind(&self.0)))
+    }
+}
+
+/// A change delivered to a `Subscription`. `context` maps the paths given
+/// at registration to the values they resolved to.
+#[pyclass(name = "Notification", module = "qlib", frozen, get_all)]
+struct PyNotification {
+    entity_id: PyObject,
+    field: String,
+    value: PyObject,
+    previous: PyObject,
+    timestamp: PyObject,
+    writer_id: PyObject,
+    context: PyObject,
+}
+
+#[pymethods]
+impl PyNotification {
+    fn __repr__(&self, py: Python<'_>) -> PyResult<String> {
+        Ok(format!("Notification({}, '{}', {})", self.entity_id.bind(py).repr()?, self.field, self.value.bind(py).repr()?))
+    }
+}
+
+/// Notifications registered with `StoreProxy.register_notification`.
+///
+/// They are queued by `StoreProxy.process_notifications` and taken with
+/// `drain`.
+#[pyclass(name = "Subscription", module = "qlib", unsendable)]
+struct PySubscription {
+    config: NotifyConfig,
+    sender: Sender<Notification>,
+    receiver: Receiver<Notification>,
+    field: String,
+    context: Vec<(String, Vec<FieldType>)>,
+}
+
+#[pymethods]
+impl PySubscription {
+    /// Take the queued notifications, oldest first
+    fn drain(&self, py: Python<'_>) -> PyResult<Vec<PyNotification>> {
+        self.receiver.try_iter().map(|notification| self.convert(py, notification)).collect()
+    }
+
+    fn __len__(&self) -> usize {
+        self.receiver.len()
+    }
+}
+
+impl PySubscription {
+    fn convert(&self, py: Python<'_>, notification: Notification) -> PyResult<PyNotification> {
+        let context = PyDict::new_bound(py);
+        for (name, field_path) in &self.context {
+            context.set_item(name, optional_value_to_py(py, notification.context_value(field_path))?)?;
+        }
+        let current = notification.current;
+        Ok(PyNotification {
+            entity_id: PyEntityId(current.entity_id).into_py(py),
+            field: self.field.clone(),
+            value: optional_value_to_py(py, current.value.as_ref())?,
+            previous: optional_value_to_py(py, notification.previous.value.as_ref())?,
+            timestamp: match current.timestamp {
+                Some(timestamp) => timestamp_to_py(py, timestamp)?,
+                None => py.None(),
+            },
+            writer_id: current.writer_id.map(PyEntityId).into_py(py),
+            context: context.into_any().unbind(),
+        })
+    }
+}
+
+/// A connection to a qlib server
+#[pyclass(name = "StoreProxy", module = "qlib", unsendable)]
+struct PyStoreProxy(StoreProxy);
+
+#[pymethods]
+impl PyStoreProxy {
+    /// Connect to the server at `address`, e.g. "localhost:9100"
+    #[staticmethod]
+    fn connect(address: &str) -> PyResult<Self> {
+        StoreProxy::connect(address).map(PyStoreProxy).map_err(to_py_err)
+    }
+
+    /// The entity at a path such as "Root/Plant/Pump1"
+    fn resolve_path(&self, path: &str) -> PyResult<PyEntityId> {
+        self.0.resolve_path(path).map(PyEntityId).map_err(to_py_err)
+    }
+
+    /// The path of an entity, the inverse of `resolve_path`
+    fn path(&self, entity_id: PyEntityId) -> PyResult<String> {
+        qlib_rs::path(&self.0, entity_id.0).map_err(to_py_err)
+    }
+
+    /// The name of an entity's type
+    fn entity_type(&self, entity_id: PyEntityId) -> PyResult<String> {
+        self.0.resolve_entity_type(entity_id.0.extract_type()).map_err(to_py_err)
+    }
+
+    fn entity_exists(&self, entity_id: PyEntityId) -> bool {
+        self.0.entity_exists(entity_id.0)
+    }
+
+    #[pyo3(signature = (entity_type, filter=None))]
+    fn find_entities(&self, entity_type: &str, filter: Option<&str>) -> PyResult<Vec<PyEntityId>> {
+        let entity_type = self.0.get_entity_type(entity_type).map_err(to_py_err)?;
+        let entity_ids = self.0.find_entities(entity_type, filter).map_err(to_py_err)?;
+        Ok(entity_ids.into_iter().map(PyEntityId).collect())
+    }
+
+    #[pyo3(signature = (entity_type, parent, name))]
+    fn create_entity(&self, entity_type: &str, parent: Option<PyEntityId>, name: &str) -> PyResult<PyEntityId> {
+        let entity_type = self.0.get_entity_type(entity_type).map_err(to_py_err)?;
+        self.0.create_entity(entity_type, parent.map(|parent| parent.0), name).map(PyEntityId).map_err(to_py_err)
+    }
+
+    fn delete_entity(&self, entity_id: PyEntityId) -> PyResult<()> {
+        self.0.delete_entity(entity_id.0).map_err(to_py_err)
+    }
+
+    /// Read a field, following indirection such as "Parent->Name"
+    fn read(&self, py: Python<'_>, entity_id: PyEntityId, field: &str) -> PyResult<PyObject> {
+        let field_path = parse_field_path(&self.0, field).map_err(to_py_err)?;
+        let (value, _, _) = self.0.read(entity_id.0, &field_path).map_err(to_py_err)?;
+        value_to_py(py, &value)
+    }
+
+    /// Like `read`, returning `(value, write_time, writer_id)`
+    fn read_with_metadata(&self, py: Python<'_>, entity_id: PyEntityId, field: &str) -> PyResult<(PyObject, PyObject, PyObject)> {
+        let field_path = parse_field_path(&self.0, field).map_err(to_py_err)?;
+        let (value, write_time, writer_id) = self.0.read(entity_id.0, &field_path).map_err(to_py_err)?;
+        Ok((value_to_py(py, &value)?, timestamp_to_py(py, write_time)?, writer_id.map(PyEntityId).into_py(py)))
+    }
+
+    /// Write a field, converting `value` to the variant of the field's schema
+    #[pyo3(signature = (entity_id, field, value, writer_id=None))]
+    fn write(&self, entity_id: PyEntityId, field: &str, value: &Bound<'_, PyAny>, writer_id: Option<PyEntityId>) -> PyResult<()> {
+        let field_path = parse_field_path(&self.0, field).map_err(to_py_err)?;
+        let (target, field_type) = self.0.resolve_indirection(entity_id.0, &field_path).map_err(to_py_err)?;
+        let schema = self.0.get_field_schema(target.extract_type(), field_type).map_err(to_py_err)?;
+        let choices = match &schema {
+            FieldSchema::Choice { choices, .. } => choices.as_slice(),
+            _ => &[],
+        };
+        let value = value_from_py(value, &schema.default_value(), choices)?;
+        self.0
+            .write(entity_id.0, &field_path, value, writer_id.map(|writer_id| writer_id.0), None, None, None)
+            .map_err(to_py_err)
+    }
+
+    /// Subscribe to writes of `field` on one entity (`entity_id`) or on
+    /// every entity of a type (`entity_type`). `context` lists field paths
+    /// read alongside each notification.
+    #[pyo3(signature = (field, *, entity_id=None, entity_type=None, trigger_on_change=true, context=Vec::new(), filter=None))]
+    fn register_notification(
+        &self,
+        field: &str,
+        entity_id: Option<PyEntityId>,
+        entity_type: Option<&str>,
+        trigger_on_change: bool,
+        context: Vec<String>,
+        filter: Option<String>,
+    ) -> PyResult<PySubscription> {
+        let field_type = self.0.get_field_type(field).map_err(to_py_err)?;
+        let context = context
+            .into_iter()
+            .map(|name| parse_field_path(&self.0, &name).map(|field_path| (name, field_path)))
+            .collect::<qlib_rs::Result<Vec<_>>>()
+            .map_err(to_py_err)?;
+        let context_paths = context.iter().map(|(_, field_path)| field_path.clone()).collect();
+        let config = match (entity_id, entity_type) {
+            (Some(entity_id), None) => NotifyConfig::EntityId { entity_id: entity_id.0, field_type, trigger_on_change, context: context_paths, filter },
+            (None, Some(entity_type)) => NotifyConfig::EntityType {
+                entity_type: self.0.get_entity_type(entity_type).map_err(to_py_err)?,
+                field_type,
+                trigger_on_change,
+                context: context_paths,
+                filter,
+            },
+            _ => return Err(PyValueError::new_err("Exactly one of entity_id and entity_type is required")),
+        };
+        let (sender, receiver) = unbounded();
+        self.0.register_notification(config.clone(), sender.clone()).map_err(to_py_err)?;
+        Ok(PySubscription { config, sender, receiver, field: field.to_string(), context })
+    }
+
+    /// Returns whether the subscription was registered
+    fn unregister_notification(&self, subscription: PyRef<'_, PySubscription>) -> bool {
+        self.0.unregister_notification(&subscription.config, &subscription.sender)
+    }
+
+    /// Read pending notifications from the server into their subscriptions
+    fn process_notifications(&self) -> PyResult<()> {
+        self.0.process_notifications().map_err(to_py_err)
+    }
+}
+
+/// Evaluates CEL expressions against entities, caching compiled programs
+#[pyclass(name = "CelExecutor", module = "qlib", unsendable)]
+struct PyCelExecutor(CelExecutor);
+
+#[pymethods]
+impl PyCelExecutor {
+    #[new]
+    fn new() -> Self {
+        PyCelExecutor(CelExecutor::new())
+    }
+
+    /// Evaluate `source` relative to `entity_id`. `params` are made
+    /// available to the expression by name.
+    #[pyo3(signature = (store, source, entity_id, params=None))]
+    fn execute(
+        &mut self,
+        py: Python<'_>,
+        store: PyRef<'_, PyStoreProxy>,
+        source: &str,
+        entity_id: PyEntityId,
+        params: Option<&Bound<'_, PyDict>>,
+    ) -> PyResult<PyObject> {
+        let params = match params {
+            Some(params) => params
+                .iter()
+                .map(|(name, value)| Ok((name.extract::<String>()?, infer_value(&value)?)))
+                .collect::<PyResult<HashMap<_, _>>>()?,
+            None => HashMap::new(),
+        };
+        match self.0.execute_with_params(source, entity_id.0, &params, &store.0).map_err(to_py_err)? {
+            cel::Value::Null => Ok(py.None()),
+            result => value_to_py(py, &cel_value_to_value(result).map_err(to_py_err)?),
+        }
+    }
+}
+
+fn kind(value: &Value) -> &'static str {
+    match value {
+        Value::Blob(_) => "Blob",
+        Value::Bool(_) => "Bool",
+        Value::Choice(_) => "Choice",
+        Value::EntityList(_) => "EntityList",
+        Value::EntityReference(_) => "EntityReference",
+        Value::Float(_) => "Float",
+        Value::Int(_) => "Int",
+        Value::String(_) => "String",
+        Value::Timestamp(_) => "Timestamp",
+        Value::Decimal(_) => "Decimal",
+        Value::Duration(_) => "Duration",
+        Value::StringList(_) => "StringList",
+        Value::Map(_) => "Map",
+    }
+}
+
+/// A value of the named variant, used to pick the conversion
+fn template(kind: &str) -> PyResult<Value> {
+    Ok(match kind {
+        "Blob" => Value::Blob(Vec::new()),
+        "Bool" => Value::Bool(false),
+        "Choice" => Value::Choice(0),
+        "EntityList" => Value::EntityList(Vec::new()),
+        "EntityReference" => Value::EntityReference(None),
+        "Float" => Value::Float(0.0),
+        "Int" => Value::Int(0),
+        "String" => Value::String(String::new()),
+        "Timestamp" => Value::Timestamp(qlib_rs::epoch()),
+        "Decimal" => Value::Decimal(Decimal::default()),
+        "Duration" => Value::Duration(Duration::ZERO),
+        "StringList" => Value::StringList(Vec::new()),
+        "Map" => Value::Map(Default::default()),
+        other => return Err(PyValueError::new_err(format!("Unknown value kind '{}'", other))),
+    })
+}
+
+fn value_to_py(py: Python<'_>, value: &Value) -> PyResult<PyObject> {
+    Ok(match value {
+        Value::Blob(v) => PyBytes::new_bound(py, v).into_any().unbind(),
+        Value::Bool(v) => v.into_py(py),
+        Value::Choice(v) | Value::Int(v) => v.into_py(py),
+        Value::EntityList(v) => v.iter().copied().map(PyEntityId).collect::<Vec<_>>().into_py(py),
+        Value::EntityReference(v) => v.map(PyEntityId).into_py(py),
+        Value::Float(v) => v.into_py(py),
+        Value::String(v) => v.into_py(py),
+        Value::Timestamp(v) => timestamp_to_py(py, *v)?,
+        Value::Decimal(v) => py.import_bound("decimal")?.getattr("Decimal")?.call1((v.to_string(),))?.unbind(),
+        Value::Duration(v) => duration_to_py(py, *v)?,
+        Value::StringList(v) => PyList::new_bound(py, v).into_any().unbind(),
+        Value::Map(v) => {
+            let dict = PyDict::new_bound(py);
+            for (key, value) in v {
+                dict.set_item(key, value_to_py(py, value)?)?;
+            }
+            dict.into_any().unbind()
+        }
+    })
+}
+
+fn optional_value_to_py(py: Python<'_>, value: Option<&Value>) -> PyResult<PyObject> {
+    match value {
+        Some(value) => value_to_py(py, value),
+        None => Ok(py.None()),
+    }
+}
+
+/// Convert `obj` to the same variant as `template`. `choices` resolves
+/// choice names for Choice fields.
+fn value_from_py(obj: &Bound<'_, PyAny>, template: &Value, choices: &[String]) -> PyResult<Value> {
+    if let Ok(value) = obj.downcast::<PyValue>() {
+        return Ok(value.get().0.clone());
+    }
+    Ok(match template {
+        Value::Blob(_) => Value::Blob(obj.extract()?),
+        Value::Bool(_) => Value::Bool(obj.extract()?),
+        Value::Choice(_) => match obj.extract::<String>() {
+            Ok(name) => Value::Choice(
+                choices
+                    .iter()
+                    .position(|choice| *choice == name)
+                    .ok_or_else(|| PyValueError::new_err(format!("Unknown choice '{}'", name)))? as i64,
+            ),
+            Err(_) => Value::Choice(obj.extract()?),
+        },
+        Value::EntityList(_) => Value::EntityList(obj.iter()?.map(|item| entity_id_from_py(&item?)).collect::<PyResult<_>>()?),
+        Value::EntityReference(_) => Value::EntityReference(match obj.is_none() {
+            true => None,
+            false => Some(entity_id_from_py(obj)?),
+        }),
+        Value::Float(_) => Value::Float(obj.extract()?),
+        Value::Int(_) => Value::Int(obj.extract()?),
+        Value::String(_) => Value::String(obj.extract()?),
+        Value::Timestamp(_) => Value::Timestamp(timestamp_from_py(obj)?),
+        Value::Decimal(_) => Value::Decimal(obj.str()?.to_str()?.parse().map_err(to_py_err)?),
+        Value::Duration(_) => Value::Duration(duration_from_py(obj)?),
+        Value::StringList(_) => Value::StringList(obj.extract()?),
+        Value::Map(_) => Value::Map(
+            obj.downcast::<PyDict>()?
+                .iter()
+                .map(|(key, value)| Ok((key.extract::<String>()?, infer_value(&value)?)))
+                .collect::<PyResult<_>>()?,
+        ),
+    })
+}
+
+/// Convert `obj` to the variant its Python type corresponds to
+fn infer_value(obj: &Bound<'_, PyAny>) -> PyResult<Value> {
+    let py = obj.py();
+    let template = if let Ok(value) = obj.downcast::<PyValue>() {
+        return Ok(value.get().0.clone());
+    } else if obj.is_instance_of::<PyBool>() {
+        Value::Bool(false)
+    } else if obj.is_instance_of::<PyInt>() {
+        Value::Int(0)
+    } else if obj.is_instance_of::<PyFloat>() {
+        Value::Float(0.0)
+    } else if obj.is_instance_of::<PyString>() {
+        Value::String(String::new())
+    } else if obj.is_instance_of::<PyBytes>() {
+        Value::Blob(Vec::new())
+    } else if obj.is_instance_of::<PyEntityId>() {
+        Value::EntityReference(None)
+    } else if obj.is_instance_of::<PyDateTime>() {
+        Value::Timestamp(qlib_rs::epoch())
+    } else if obj.is_instance_of::<PyDelta>() {
+        Value::Duration(Duration::ZERO)
+    } else if obj.is_instance(&py.import_bound("decimal")?.getattr("Decimal")?)? {
+        Value::Decimal(Decimal::default())
+    } else if obj.is_instance_of::<PyDict>() {
+        Value::Map(Default::default())
+    } else if let Ok(strings) = obj.extract::<Vec<String>>() {
+        return Ok(Value::StringList(strings));
+    } else if obj.is_instance_of::<PyList>() {
+        Value::EntityList(Vec::new())
+    } else {
+        return Err(PyTypeError::new_err(format!("Can't convert {} to a value", obj.get_type().name()?)));
+    };
+    value_from_py(obj, &template, &[])
+}
+
+fn entity_id_from_py(obj: &Bound<'_, PyAny>) -> PyResult<EntityId> {
+    match obj.downcast::<PyEntityId>() {
+        Ok(entity_id) => Ok(entity_id.get().0),
+        Err(_) => obj.extract::<u64>().map(EntityId),
+    }
+}
+
+fn unix_epoch(py: Python<'_>) -> PyResult<Bound<'_, PyDateTime>> {
+    PyDateTime::new_bound(py, 1970, 1, 1, 0, 0, 0, 0, Some(&timezone_utc_bound(py)))
+}
+
+fn micros_to_delta(py: Python<'_>, micros: i64) -> PyResult<Bound<'_, PyDelta>> {
+    let (days, micros) = (micros.div_euclid(MICROS_PER_DAY), micros.rem_euclid(MICROS_PER_DAY));
+    PyDelta::new_bound(py, days as i32, (micros / 1_000_000) as i32, (micros % 1_000_000) as i32, false)
+}
+
+fn delta_to_micros(delta: &Bound<'_, PyDelta>) -> i64 {
+    delta.get_days() as i64 * MICROS_PER_DAY + delta.get_seconds() as i64 * 1_000_000 + delta.get_microseconds() as i64
+}
+
+fn timestamp_to_py(py: Python<'_>, timestamp: Timestamp) -> PyResult<PyObject> {
+    let micros = timestamp.unix_timestamp_nanos().div_euclid(1000) as i64;
+    Ok(unix_epoch(py)?.call_method1("__add__", (micros_to_delta(py, micros)?,))?.unbind())
+}
+
+/// Naive datetimes are taken to be UTC
+fn timestamp_from_py(obj: &Bound<'_, PyAny>) -> PyResult<Timestamp> {
+    let py = obj.py();
+    let mut datetime = obj.downcast::<PyDateTime>()?.clone().into_any();
+    if datetime.getattr("tzinfo")?.is_none() {
+        let kwargs = PyDict::new_bound(py);
+        kwargs.set_item("tzinfo", timezone_utc_bound(py))?;
+        datetime = datetime.call_method("replace", (), Some(&kwargs))?;
+    }
+    let since_epoch = datetime.call_method1("__sub__", (unix_epoch(py)?,))?;
+    let micros = delta_to_micros(since_epoch.downcast::<PyDelta>()?);
+    Timestamp::from_unix_timestamp_nanos(micros as i128 * 1000).map_err(|e| PyValueError::new_err(e.to_string()))
+}
+
+fn duration_to_py(py: Python<'_>, duration: Duration) -> PyResult<PyObject> {
+    Ok(micros_to_delta(py, duration.whole_microseconds() as i64)?.into_any().unbind())
+}
+
+/// Accepts a timedelta or a number of seconds
+fn duration_from_py(obj: &Bound<'_, PyAny>) -> PyResult<Duration> {
+    match obj.downcast::<PyDelta>() {
+        Ok(delta) => Ok(Duration::microseconds(delta_to_micros(delta))),
+        Err(_) => Ok(Duration::seconds_f64(obj.extract()?)),
+    }
+}
+
+#[pymodule]
+fn qlib(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add("QlibError", m.py().get_type_bound::<QlibError>())?;
+    m.add_class::<PyEntityId>()?;
+    m.add_class::<PyValue>()?;
+    m.add_class::<PyNotification>()?;
+    m.add_class::<PySubscription>()?;
+    m.add_class::<PyStoreProxy>()?;
+    m.add_class::<PyCelExecutor>()?;
+    Ok(())
+}
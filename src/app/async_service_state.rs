@@ -0,0 +1,182 @@
+use std::future::Future;
+use std::time::Duration;
+
+use tokio::sync::watch;
+
+use crate::{et, ft, data::AsyncStoreProxy, EntityId, FieldType, PushCondition, Result, Value};
+
+/// Async counterpart of `ServiceState` for tokio based services.
+///
+/// Instead of calling `tick` from a hand-written loop, spawn `run` with a
+/// shutdown future:
+///
+/// # Example Usage
+/// ```ignore
+/// let store = AsyncStoreProxy::connect("127.0.0.1:9100").await?;
+/// let mut service = AsyncServiceState::new(&store, "my-service".to_string(), true, 1000).await?;
+/// let mut leadership = service.subscribe_leadership();
+///
+/// tokio::spawn(async move {
+///     while leadership.changed().await.is_ok() {
+///         println!("leader: {}", *leadership.borrow());
+///     }
+/// });
+///
+/// service.run(&store, token.cancelled()).await?;
+/// ```
+///
+/// `AsyncStoreProxy` doesn't forward notifications yet, so leadership is
+/// refreshed by reading `CurrentLeader` on every heartbeat rather than by
+/// subscribing to changes.
+pub struct AsyncServiceState {
+    /// The machine this service is running on
+    pub machine_id: String,
+
+    /// The EntityId of the service instance
+    pub service_id: EntityId,
+
+    /// Whether this service participates in leader election
+    pub fault_tolerant: bool,
+
+    /// Heartbeat interval in milliseconds
+    pub heartbeat_interval_msecs: u64,
+
+    /// FaultTolerance entity listing this service as a candidate (only present if fault_tolerant is true)
+    fault_tolerance_id: Option<EntityId>,
+
+    leadership: watch::Sender<bool>,
+
+    ft_heartbeat: FieldType,
+    ft_make_me: FieldType,
+    ft_current_leader: FieldType,
+}
+
+impl AsyncServiceState {
+    pub async fn new(store: &AsyncStoreProxy, service_name: String, fault_tolerant: bool, heartbeat_interval_msecs: u64) -> Result<Self> {
+        let machine_id = store.machine_info().await?;
+
+        let et_service = store.get_entity_type(et::SERVICE).await?;
+        let ft_heartbeat = store.get_field_type(ft::HEARTBEAT).await?;
+        let ft_make_me = store.get_field_type(ft::MAKE_ME).await?;
+        let ft_current_leader = store.get_field_type(ft::CURRENT_LEADER).await?;
+
+        let service_id = {
+            let query = format!("Parent->Name == '{}' && Name == '{}'", machine_id, service_name);
+            let entities = store.find_entities(et_service, Some(query.as_str())).await?;
+            *entities.first().expect("Service entity instance to exist")
+        };
+
+        let fault_tolerance_id = if fault_tolerant {
+            let et_fault_tolerance = store.get_entity_type(et::FAULT_TOLERANCE).await?;
+            let query = format!("CandidateList.contains({})", String::from(service_id));
+            let entities = store.find_entities(et_fault_tolerance, Some(query.as_str())).await?;
+            Some(*entities.first().expect("FaultTolerance entity instance to exist"))
+        } else {
+            None
+        };
+
+        Ok(AsyncServiceState {
+            machine_id,
+            service_id,
+            fault_tolerant,
+            heartbeat_interval_msecs,
+            fault_tolerance_id,
+            leadership: watch::channel(false).0,
+            ft_heartbeat,
+            ft_make_me,
+            ft_current_leader,
+        })
+    }
+
+    /// Returns true if this service is currently the leader (only meaningful if fault_tolerant is true)
+    pub fn is_leader(&self) -> bool {
+        *self.leadership.borrow()
+    }
+
+    /// Watch for leadership changes of this service
+    pub fn subscribe_leadership(&self) -> watch::Receiver<bool> {
+        self.leadership.subscribe()
+    }
+
+    /// Heartbeat and leadership loop.
+    ///
+    /// Marks the service as available for election, then writes a heartbeat
+    /// and refreshes the leadership status every `heartbeat_interval_msecs`
+    /// until `shutdown` completes (e.g. `CancellationToken::cancelled()`).
+    /// On shutdown the service marks itself unavailable so another candidate
+    /// can take over.
+    pub async fn run(&mut self, store: &AsyncStoreProxy, shutdown: impl Future<Output = ()>) -> Result<()> {
+        tokio::pin!(shutdown);
+
+        let mut interval = tokio::time::interval(Duration::from_millis(self.heartbeat_interval_msecs.max(1)));
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+
+        self.make_me_available(store).await?;
+
+        loop {
+            tokio::select! {
+                _ = &mut shutdown => break,
+                _ = interval.tick() => self.tick(store).await?,
+            }
+        }
+
+        self.make_me_unavailable(store).await?;
+        self.leadership.send_replace(false);
+
+        Ok(())
+    }
+
+    /// Write a heartbeat and refresh the leadership status once
+    pub async fn tick(&mut self, store: &AsyncStoreProxy) -> Result<()> {
+        // Write a heartbeat value (Choice(0) is a common convention for "alive")
+        store.write(
+            self.service_id,
+            &[self.ft_heartbeat],
+            Value::Choice(0),
+            Some(self.service_id), // writer_id
+            None, // write_time
+            None, // push_condition
+            None, // adjust_behavior
+        ).await?;
+
+        if let Some(fault_tolerance_id) = self.fault_tolerance_id {
+            let (current_leader, _, _) = store.read(fault_tolerance_id, &[self.ft_current_leader]).await?;
+            let is_leader = current_leader.as_entity_reference() == Some(&Some(self.service_id));
+
+            let was_leader = self.leadership.send_replace(is_leader);
+            if was_leader != is_leader {
+                if is_leader {
+                    log::info!("Candidate {:?} became leader", self.service_id);
+                } else {
+                    log::info!("Candidate {:?} lost leadership", self.service_id);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub async fn make_me_available(&mut self, store: &AsyncStoreProxy) -> Result<()> {
+        self.write_make_me(store, 1).await // 1 = Available
+    }
+
+    pub async fn make_me_unavailable(&mut self, store: &AsyncStoreProxy) -> Result<()> {
+        self.write_make_me(store, 0).await // 0 = Unavailable
+    }
+
+    async fn write_make_me(&mut self, store: &AsyncStoreProxy, choice: i64) -> Result<()> {
+        if !self.fault_tolerant {
+            return Ok(());
+        }
+
+        store.write(
+            self.service_id,
+            &[self.ft_make_me],
+            Value::Choice(choice),
+            Some(self.service_id), // writer_id
+            None, // write_time
+            Some(PushCondition::Changes),
+            None, // adjust_behavior
+        ).await
+    }
+}
@@ -0,0 +1,333 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use crate::data::snapshot_sink::format_compact_time;
+use crate::{
+    app::CronSchedule, epoch, et::ET, ft::FT, now, restore_json_snapshot, take_json_schemas, take_json_snapshot,
+    verify_json_snapshot, verify_snapshot, Duration, EntityId, Error, FieldType, JsonSnapshot, Result, Snapshot, SnapshotChecksum,
+    Store, StoreTrait, Timestamp, Value,
+};
+
+/// Name prefix of the backup directories made by `backup_to`
+pub const BACKUP_PREFIX: &str = "backup-";
+
+const MANIFEST_FILE: &str = "manifest.json";
+const SCHEMA_FILE: &str = "schema.json";
+
+/// How the snapshot in a backup is encoded.
+///
+/// Stored as a Choice on the `Format` field of a `BackupSchedule`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum BackupFormat {
+    /// `Snapshot` encoded with bincode, as the server writes its snapshots.
+    /// Restores exactly, including entity ids, series and schema ranks.
+    #[default]
+    Binary,
+    /// `JsonSnapshot`, readable and editable, but restored entity by entity
+    /// under new ids
+    Json,
+}
+
+impl From<i64> for BackupFormat {
+    fn from(value: i64) -> Self {
+        match value {
+            1 => BackupFormat::Json,
+            _ => BackupFormat::Binary,
+        }
+    }
+}
+
+/// How the snapshot in a backup is compressed. The schema is always stored
+/// as plain JSON so it can be read without restoring.
+///
+/// Stored as a Choice on the `Compression` field of a `BackupSchedule`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Compression {
+    #[default]
+    None,
+    /// zlib-wrapped deflate
+    Deflate,
+}
+
+impl From<i64> for Compression {
+    fn from(value: i64) -> Self {
+        match value {
+            1 => Compression::Deflate,
+            _ => Compression::None,
+        }
+    }
+}
+
+/// Describes a backup; stored as `manifest.json` next to the snapshot
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BackupManifest {
+    pub created: Timestamp,
+    pub format: BackupFormat,
+    pub compression: Compression,
+    /// Checksum of the snapshot's content, see `verify_snapshot`
+    pub checksum: Option<SnapshotChecksum>,
+}
+
+impl BackupManifest {
+    fn snapshot_file(&self) -> &'static str {
+        match (self.format, self.compression) {
+            (BackupFormat::Binary, Compression::None) => "snapshot.bin",
+            (BackupFormat::Binary, Compression::Deflate) => "snapshot.bin.z",
+            (BackupFormat::Json, Compression::None) => "snapshot.json",
+            (BackupFormat::Json, Compression::Deflate) => "snapshot.json.z",
+        }
+    }
+}
+
+/// Which backups `rotate` keeps
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Retention {
+    /// Keep at most this many of the newest backups; 0 keeps them all
+    pub keep_last: usize,
+    /// Remove backups older than this. The newest backup is kept no matter
+    /// its age, so a stalled schedule doesn't end up with none.
+    pub max_age: Option<Duration>,
+}
+
+impl Retention {
+    pub fn keep_last(keep_last: usize) -> Self {
+        Self { keep_last, max_age: None }
+    }
+}
+
+fn io_error(action: &str, path: &Path, e: impl std::fmt::Display) -> Error {
+    Error::InvalidRequest(format!("Failed to {} {}: {}", action, path.display(), e))
+}
+
+/// Directory name of a backup taken at `created`, e.g.
+/// `backup-20240310T142501.123456789Z`. Names sort in time order.
+fn backup_name(created: Timestamp) -> String {
+    format!("{}{}", BACKUP_PREFIX, format_compact_time(created))
+}
+
+/// Back `store` up into a new timestamped directory under `dir` (created if
+/// missing) and return its path.
+///
+/// A backup holds the snapshot in `format`, the schemas as `schema.json` and
+/// a `manifest.json` describing both. It is written under a temporary name
+/// and renamed when complete, so `restore_from` and `rotate` never see a
+/// partial backup.
+pub fn backup_to(store: &mut impl StoreTrait, dir: impl AsRef<Path>, format: BackupFormat, compression: Compression) -> Result<PathBuf> {
+    let dir = dir.as_ref();
+    let created = now();
+
+    let (snapshot, checksum) = match format {
+        BackupFormat::Binary => {
+            let snapshot = store.take_snapshot();
+            let bytes = bincode::serialize(&snapshot).map_err(|e| Error::InvalidRequest(format!("Failed to serialize snapshot: {}", e)))?;
+            (bytes, snapshot.checksum)
+        }
+        BackupFormat::Json => {
+            let snapshot = take_json_snapshot(store)?;
+            let bytes = serde_json::to_vec(&snapshot).map_err(|e| Error::InvalidRequest(format!("Failed to serialize snapshot: {}", e)))?;
+            (bytes, snapshot.checksum)
+        }
+    };
+    let snapshot = match compression {
+        Compression::None => snapshot,
+        Compression::Deflate => miniz_oxide::deflate::compress_to_vec_zlib(&snapshot, 6),
+    };
+    let schemas = serde_json::to_vec_pretty(&take_json_schemas(store)?).map_err(|e| Error::InvalidRequest(format!("Failed to serialize schemas: {}", e)))?;
+    let manifest = BackupManifest { created, format, compression, checksum };
+    let manifest_json = serde_json::to_vec_pretty(&manifest).map_err(|e| Error::InvalidRequest(format!("Failed to serialize manifest: {}", e)))?;
+
+    let name = backup_name(created);
+    let path = dir.join(&name);
+    if path.exists() {
+        return Err(Error::InvalidRequest(format!("Backup {} already exists", path.display())));
+    }
+    let partial = dir.join(format!(".{}.partial", name));
+    fs::create_dir_all(&partial).map_err(|e| io_error("create", &partial, e))?;
+
+    let written = [(manifest.snapshot_file(), &snapshot), (SCHEMA_FILE, &schemas), (MANIFEST_FILE, &manifest_json)]
+        .into_iter()
+        .try_for_each(|(file, bytes)| {
+            let file = partial.join(file);
+            fs::write(&file, bytes).map_err(|e| io_error("write", &file, e))
+        })
+        .and_then(|_| fs::rename(&partial, &path).map_err(|e| io_error("rename", &partial, e)));
+    if let Err(e) = written {
+        let _ = fs::remove_dir_all(&partial);
+        return Err(e);
+    }
+
+    Ok(path)
+}
+
+/// Read the manifest of the backup at `path`
+pub fn read_manifest(path: impl AsRef<Path>) -> Result<BackupManifest> {
+    let file = path.as_ref().join(MANIFEST_FILE);
+    let json = fs::read(&file).map_err(|e| io_error("read", &file, e))?;
+    serde_json::from_slice(&json).map_err(|e| io_error("parse", &file, e))
+}
+
+/// Replace the content of `store` with the backup at `path`, a directory
+/// made by `backup_to`. The snapshot is checked against its checksum first,
+/// so a damaged backup fails with `Error::ChecksumMismatch` and leaves the
+/// store as it was. Returns the backup's manifest.
+pub fn restore_from(store: &mut Store, path: impl AsRef<Path>) -> Result<BackupManifest> {
+    let path = path.as_ref();
+    let manifest = read_manifest(path)?;
+
+    let file = path.join(manifest.snapshot_file());
+    let bytes = fs::read(&file).map_err(|e| io_error("read", &file, e))?;
+    let bytes = match manifest.compression {
+        Compression::None => bytes,
+        Compression::Deflate => miniz_oxide::inflate::decompress_to_vec_zlib(&bytes).map_err(|e| io_error("decompress", &file, e))?,
+    };
+
+    match manifest.format {
+        BackupFormat::Binary => {
+            let snapshot: Snapshot = bincode::deserialize(&bytes).map_err(|e| io_error("parse", &file, e))?;
+            verify_snapshot(&snapshot)?;
+            store.restore_snapshot(snapshot);
+        }
+        BackupFormat::Json => {
+            let snapshot: JsonSnapshot = serde_json::from_slice(&bytes).map_err(|e| io_error("parse", &file, e))?;
+            verify_json_snapshot(&snapshot)?;
+            // Rebuilt from scratch rather than merged into what's there
+            let mut restored = Store::new();
+            restore_json_snapshot(&mut restored, &snapshot)?;
+            store.restore_snapshot(restored.take_snapshot());
+        }
+    }
+
+    Ok(manifest)
+}
+
+/// The backups under `dir` with their manifests, oldest first. Directories
+/// that aren't complete backups are left out.
+pub fn list_backups(dir: impl AsRef<Path>) -> Result<Vec<(PathBuf, BackupManifest)>> {
+    let dir = dir.as_ref();
+    let entries = fs::read_dir(dir).map_err(|e| io_error("read", dir, e))?;
+
+    let mut backups = Vec::new();
+    for entry in entries {
+        let path = entry.map_err(|e| io_error("read", dir, e))?.path();
+        let is_backup = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name.starts_with(BACKUP_PREFIX));
+        if !is_backup || !path.is_dir() {
+            continue;
+        }
+        if let Ok(manifest) = read_manifest(&path) {
+            backups.push((path, manifest));
+        }
+    }
+
+    backups.sort_by(|(a, _), (b, _)| a.cmp(b));
+    Ok(backups)
+}
+
+/// Remove the backups under `dir` that `retention` doesn't keep, as of
+/// `now`. Returns the removed paths.
+pub fn rotate(dir: impl AsRef<Path>, retention: &Retention, now: Timestamp) -> Result<Vec<PathBuf>> {
+    let backups = list_backups(dir)?;
+    let newest = backups.len().saturating_sub(1);
+
+    let mut removed = Vec::new();
+    for (index, (path, manifest)) in backups.into_iter().enumerate() {
+        let too_many = retention.keep_last > 0 && index + retention.keep_last <= newest;
+        let too_old = index < newest && retention.max_age.is_some_and(|max_age| now - manifest.created > max_age);
+        if too_many || too_old {
+            fs::remove_dir_all(&path).map_err(|e| io_error("remove", &path, e))?;
+            removed.push(path);
+        }
+    }
+
+    Ok(removed)
+}
+
+/// Takes backups as configured by `BackupSchedule` entities, so the
+/// schedule can be changed at runtime like any other configuration.
+///
+/// A backup schedule has the following fields:
+/// - `Schedule`: cron expression (see `CronSchedule`)
+/// - `Directory`: where the backups are written
+/// - `Format`: Choice of Binary or Json
+/// - `Compression`: Choice of None or Deflate
+/// - `Retention`: number of backups kept, 0 keeps them all
+/// - `LastRun`: time of the last occurrence that was handled
+///
+/// Occurrences missed while no scheduler was running are made up with a
+/// single backup. As with `Scheduler`, only one should be active at a time;
+/// `ServiceState` runs it while the service is leader.
+#[derive(Debug, Default)]
+pub struct BackupScheduler;
+
+impl BackupScheduler {
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Take the backups that are due at `now`. Returns the paths written.
+    pub fn tick(&mut self, store: &mut impl StoreTrait, now: Timestamp) -> Result<Vec<PathBuf>> {
+        let et = ET::new(store);
+        let ft = FT::new(store);
+
+        let Some(et_backup_schedule) = et.backup_schedule else {
+            return Ok(Vec::new());
+        };
+
+        let mut written = Vec::new();
+        for schedule_id in store.find_entities(et_backup_schedule, None)? {
+            match self.run_schedule(store, &ft, schedule_id, now) {
+                Ok(path) => written.extend(path),
+                Err(e) => log::warn!("Backup schedule {:?} failed: {}", schedule_id, e),
+            }
+        }
+
+        Ok(written)
+    }
+
+    fn run_schedule(&mut self, store: &mut impl StoreTrait, ft: &FT, schedule_id: EntityId, now: Timestamp) -> Result<Option<PathBuf>> {
+        let ft_schedule = ft.schedule.ok_or_else(|| Error::FieldTypeStrNotFound(crate::ft::SCHEDULE.to_string()))?;
+        let ft_last_run = ft.last_run.ok_or_else(|| Error::FieldTypeStrNotFound(crate::ft::LAST_RUN.to_string()))?;
+        let ft_directory = ft.directory.ok_or_else(|| Error::FieldTypeStrNotFound(crate::ft::DIRECTORY.to_string()))?;
+
+        let (schedule, _, _) = store.read(schedule_id, &[ft_schedule])?;
+        let schedule = CronSchedule::parse(schedule.as_string().unwrap_or_default())?;
+        let (last_run, _, _) = store.read(schedule_id, &[ft_last_run])?;
+        let last_run = last_run.as_timestamp().unwrap_or_else(epoch);
+
+        // A schedule that never ran starts counting from now
+        if last_run == epoch() {
+            store.write(schedule_id, &[ft_last_run], Value::Timestamp(now), None, None, None, None)?;
+            return Ok(None);
+        }
+
+        if schedule.next_after(last_run).is_none_or(|next| next > now) {
+            return Ok(None);
+        }
+
+        let choice = |field_type: Option<FieldType>| {
+            field_type
+                .and_then(|field_type| store.read(schedule_id, &[field_type]).ok())
+                .and_then(|(value, _, _)| value.as_choice())
+                .unwrap_or_default()
+        };
+        let format = BackupFormat::from(choice(ft.format));
+        let compression = Compression::from(choice(ft.compression));
+        let keep_last = ft
+            .retention
+            .and_then(|ft_retention| store.read(schedule_id, &[ft_retention]).ok())
+            .and_then(|(retention, _, _)| retention.as_int())
+            .unwrap_or_default();
+        let (directory, _, _) = store.read(schedule_id, &[ft_directory])?;
+        let directory = PathBuf::from(directory.as_string().unwrap_or_default());
+
+        // Record progress first so a failing backup isn't retried every tick
+        store.write(schedule_id, &[ft_last_run], Value::Timestamp(now), None, None, None, None)?;
+
+        let path = backup_to(store, &directory, format, compression)?;
+        rotate(&directory, &Retention::keep_last(keep_last.max(0) as usize), now)?;
+        Ok(Some(path))
+    }
+}
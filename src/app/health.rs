@@ -0,0 +1,94 @@
+use crate::{ft::FT, EntityId, PushCondition, Result, StoreTrait, Value};
+
+/// Health of a service or of a subtree of the entity tree.
+///
+/// Stored as a Choice on the `Health` field, ordered from best to worst so
+/// that rolling up a subtree is just taking the maximum.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum HealthStatus {
+    /// Nothing has been reported yet
+    #[default]
+    Unknown,
+    Healthy,
+    /// Working, but with reduced capacity or functionality
+    Degraded,
+    Unhealthy,
+}
+
+impl HealthStatus {
+    pub fn as_choice(self) -> i64 {
+        match self {
+            HealthStatus::Unknown => 0,
+            HealthStatus::Healthy => 1,
+            HealthStatus::Degraded => 2,
+            HealthStatus::Unhealthy => 3,
+        }
+    }
+
+    pub fn from_choice(choice: i64) -> Self {
+        match choice {
+            1 => HealthStatus::Healthy,
+            2 => HealthStatus::Degraded,
+            3 => HealthStatus::Unhealthy,
+            _ => HealthStatus::Unknown,
+        }
+    }
+}
+
+/// Write a health status and message to an entity's `Health` and `HealthMessage` fields
+pub fn set_health(store: &mut impl StoreTrait, entity_id: EntityId, status: HealthStatus, message: &str) -> Result<()> {
+    let ft = FT::new(store);
+    let ft_health = ft.health.expect("Health field type should be defined");
+
+    store.write(entity_id, &[ft_health], Value::Choice(status.as_choice()), Some(entity_id), None, Some(PushCondition::Changes), None)?;
+
+    if let Some(ft_health_message) = ft.health_message {
+        store.write(entity_id, &[ft_health_message], Value::String(message.to_string()), Some(entity_id), None, Some(PushCondition::Changes), None)?;
+    }
+
+    Ok(())
+}
+
+/// Roll the health of a subtree up to its root.
+///
+/// Returns the worst status reported by the leaves of the subtree, ignoring
+/// those that haven't reported (`Unknown`) unless none of them has. Every entity with children and a `Health`
+/// field is updated with the status of its own subtree along the way, so
+/// dashboards can show health at any level of the tree. Leaves keep the
+/// status they reported themselves.
+pub fn aggregate_health(store: &mut impl StoreTrait, parent_id: EntityId) -> Result<HealthStatus> {
+    let ft = FT::new(store);
+    let ft_health = ft.health.expect("Health field type should be defined");
+    let ft_children = ft.children.expect("Children field type should be defined");
+
+    aggregate_subtree(store, parent_id, ft_health, ft_children)
+}
+
+fn aggregate_subtree(store: &mut impl StoreTrait, entity_id: EntityId, ft_health: crate::FieldType, ft_children: crate::FieldType) -> Result<HealthStatus> {
+    let has_health = store.field_exists(entity_id.extract_type(), ft_health);
+    let children = store
+        .read(entity_id, &[ft_children])
+        .ok()
+        .and_then(|(children, _, _)| children.as_entity_list().cloned())
+        .unwrap_or_default();
+
+    if children.is_empty() {
+        if !has_health {
+            return Ok(HealthStatus::Unknown);
+        }
+
+        let (health, _, _) = store.read(entity_id, &[ft_health])?;
+        return Ok(HealthStatus::from_choice(health.as_choice().unwrap_or_default()));
+    }
+
+    let mut status = HealthStatus::Unknown;
+    for child_id in children {
+        status = status.max(aggregate_subtree(store, child_id, ft_health, ft_children)?);
+    }
+
+    if has_health {
+        store.write(entity_id, &[ft_health], Value::Choice(status.as_choice()), None, None, Some(PushCondition::Changes), None)?;
+    }
+
+    Ok(status)
+}
@@ -0,0 +1,189 @@
+use crate::data::StoreTrait;
+use crate::{
+    AdjustBehavior, AggregateOp, Complete, EntityId, EntitySchema, EntityType, Error, FieldSchema, FieldType, PageOpts,
+    PageResult, PushCondition, Result, Single, StoreProxy, Timestamp, TypesBulk, Value,
+};
+
+use super::CandidateState;
+
+/// A `StoreProxy` that only writes while a candidate is the leader.
+///
+/// Every write (including creating, deleting and renaming entities and
+/// schema changes) first applies the leadership notifications the candidate
+/// has received, then fails locally with `Error::NotLeader` unless the
+/// candidate is the leader. Nothing is sent to the server in that case, so
+/// an instance that has just lost leadership can't write alongside the new
+/// leader. Reads are passed through unchanged.
+///
+/// Leadership notifications arrive with the replies to other commands, or
+/// when `StoreProxy::process_notifications` is called, so services should
+/// keep ticking as usual. The check is local: for writes that must be
+/// rejected by the store itself, see `ServiceState::fenced_write`.
+///
+/// # Example Usage
+/// ```ignore
+/// let candidate = service.candidate_state.as_mut().expect("fault tolerant service");
+/// let mut guard = LeaderGuard::new(&mut store, candidate);
+/// guard.write(output, &[ft_value], Value::Int(1), None, None, None, None)?;
+/// ```
+pub struct LeaderGuard<'a> {
+    store: &'a mut StoreProxy,
+    candidate: &'a mut CandidateState,
+}
+
+impl<'a> LeaderGuard<'a> {
+    pub fn new(store: &'a mut StoreProxy, candidate: &'a mut CandidateState) -> Self {
+        LeaderGuard { store, candidate }
+    }
+
+    /// Whether the candidate is the leader, after applying any leadership
+    /// notifications received so far
+    pub fn is_leader(&mut self) -> Result<bool> {
+        self.candidate.tick(self.store)?;
+        Ok(self.candidate.is_leader())
+    }
+
+    /// The proxy being guarded, e.g. for reads that need `&StoreProxy`
+    pub fn store(&self) -> &StoreProxy {
+        self.store
+    }
+
+    /// Fail with `Error::NotLeader` unless the candidate is the leader
+    fn check_leader(&mut self) -> Result<()> {
+        if self.is_leader()? {
+            Ok(())
+        } else {
+            Err(Error::NotLeader(self.candidate.candidate_id))
+        }
+    }
+}
+
+impl StoreTrait for LeaderGuard<'_> {
+    fn get_entity_type(&self, name: &str) -> Result<EntityType> {
+        self.store.get_entity_type(name)
+    }
+
+    fn resolve_entity_type(&self, entity_type: EntityType) -> Result<String> {
+        self.store.resolve_entity_type(entity_type)
+    }
+
+    fn get_field_type(&self, name: &str) -> Result<FieldType> {
+        self.store.get_field_type(name)
+    }
+
+    fn resolve_field_type(&self, field_type: FieldType) -> Result<String> {
+        self.store.resolve_field_type(field_type)
+    }
+
+    fn get_types_bulk(&self, entity_types: &[&str], field_types: &[&str]) -> Result<TypesBulk> {
+        self.store.get_types_bulk(entity_types, field_types)
+    }
+
+    fn get_entity_schema(&self, entity_type: EntityType) -> Result<EntitySchema<Single>> {
+        self.store.get_entity_schema(entity_type)
+    }
+
+    fn get_complete_entity_schema(&self, entity_type: EntityType) -> Result<&EntitySchema<Complete>> {
+        StoreTrait::get_complete_entity_schema(&*self.store, entity_type)
+    }
+
+    fn get_complete_entity_schema_owned(&self, entity_type: EntityType) -> Result<EntitySchema<Complete>> {
+        self.store.get_complete_entity_schema_owned(entity_type)
+    }
+
+    fn get_field_schema(&self, entity_type: EntityType, field_type: FieldType) -> Result<FieldSchema> {
+        self.store.get_field_schema(entity_type, field_type)
+    }
+
+    fn set_field_schema(&mut self, entity_type: EntityType, field_type: FieldType, schema: FieldSchema) -> Result<()> {
+        self.check_leader()?;
+        self.store.set_field_schema(entity_type, field_type, schema)
+    }
+
+    fn entity_exists(&self, entity_id: EntityId) -> bool {
+        self.store.entity_exists(entity_id)
+    }
+
+    fn field_exists(&self, entity_type: EntityType, field_type: FieldType) -> bool {
+        self.store.field_exists(entity_type, field_type)
+    }
+
+    fn resolve_indirection(&self, entity_id: EntityId, fields: &[FieldType]) -> Result<(EntityId, FieldType)> {
+        self.store.resolve_indirection(entity_id, fields)
+    }
+
+    fn read(&self, entity_id: EntityId, field_path: &[FieldType]) -> Result<(Value, Timestamp, Option<EntityId>)> {
+        self.store.read(entity_id, field_path)
+    }
+
+    fn write(&mut self, entity_id: EntityId, field_path: &[FieldType], value: Value, writer_id: Option<EntityId>, write_time: Option<Timestamp>, push_condition: Option<PushCondition>, adjust_behavior: Option<AdjustBehavior>) -> Result<()> {
+        self.check_leader()?;
+        self.store.write(entity_id, field_path, value, writer_id, write_time, push_condition, adjust_behavior)
+    }
+
+    fn create_entity(&mut self, entity_type: EntityType, parent_id: Option<EntityId>, name: &str) -> Result<EntityId> {
+        self.check_leader()?;
+        self.store.create_entity(entity_type, parent_id, name)
+    }
+
+    fn delete_entity(&mut self, entity_id: EntityId) -> Result<()> {
+        self.check_leader()?;
+        self.store.delete_entity(entity_id)
+    }
+
+    fn get_or_create(&mut self, entity_type: EntityType, parent_id: Option<EntityId>, name: &str) -> Result<(EntityId, bool)> {
+        self.check_leader()?;
+        self.store.get_or_create(entity_type, parent_id, name)
+    }
+
+    fn upsert_field(&mut self, entity_id: EntityId, field_type: FieldType, value: Value) -> Result<bool> {
+        self.check_leader()?;
+        self.store.upsert_field(entity_id, field_type, value)
+    }
+
+    fn rename_entity(&mut self, entity_id: EntityId, new_name: &str) -> Result<()> {
+        self.check_leader()?;
+        self.store.rename_entity(entity_id, new_name)
+    }
+
+    fn update_schema(&mut self, schema: EntitySchema<Single, String, String>) -> Result<()> {
+        self.check_leader()?;
+        self.store.update_schema(schema)
+    }
+
+    fn take_snapshot(&self) -> crate::data::Snapshot {
+        self.store.take_snapshot()
+    }
+
+    fn stats(&self) -> Result<crate::StoreStats> {
+        self.store.stats()
+    }
+
+    fn find_entities_paginated(&self, entity_type: EntityType, page_opts: Option<&PageOpts>, filter: Option<&str>) -> Result<PageResult<EntityId>> {
+        self.store.find_entities_paginated(entity_type, page_opts, filter)
+    }
+
+    fn find_entities_exact(&self, entity_type: EntityType, page_opts: Option<&PageOpts>, filter: Option<&str>) -> Result<PageResult<EntityId>> {
+        self.store.find_entities_exact(entity_type, page_opts, filter)
+    }
+
+    fn find_entities(&self, entity_type: EntityType, filter: Option<&str>) -> Result<Vec<EntityId>> {
+        self.store.find_entities(entity_type, filter)
+    }
+
+    fn find_referencing(&self, entity_id: EntityId) -> Result<Vec<(EntityId, FieldType)>> {
+        self.store.find_referencing(entity_id)
+    }
+
+    fn aggregate(&self, entity_type: EntityType, field_type: FieldType, op: AggregateOp, filter: Option<&str>) -> Result<Option<Value>> {
+        self.store.aggregate(entity_type, field_type, op, filter)
+    }
+
+    fn get_entity_types(&self) -> Result<Vec<EntityType>> {
+        self.store.get_entity_types()
+    }
+
+    fn get_entity_types_paginated(&self, page_opts: Option<&PageOpts>) -> Result<PageResult<EntityType>> {
+        self.store.get_entity_types_paginated(page_opts)
+    }
+}
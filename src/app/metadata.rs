@@ -0,0 +1,137 @@
+use std::net::UdpSocket;
+
+use crate::{ft::FT, secs_to_timestamp, EntityId, FieldType, PushCondition, Result, StoreTrait, Timestamp, Value};
+
+/// Version details a service reports on its Service entity, see
+/// `ServiceState::new_with_metadata`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ServiceMetadata {
+    /// Version of the service, usually `env!("CARGO_PKG_VERSION")` of its crate
+    pub version: String,
+    /// Free-form build details, e.g. the commit and build date
+    pub build_info: String,
+}
+
+impl ServiceMetadata {
+    pub fn new(version: impl Into<String>) -> Self {
+        ServiceMetadata {
+            version: version.into(),
+            build_info: String::new(),
+        }
+    }
+
+    pub fn with_build_info(mut self, build_info: impl Into<String>) -> Self {
+        self.build_info = build_info.into();
+        self
+    }
+}
+
+/// Descriptive details of the host a service runs on, reported on its
+/// Machine entity
+#[derive(Debug, Clone, PartialEq)]
+pub struct HostInfo {
+    pub hostname: String,
+    /// Operating system and architecture, e.g. "Debian GNU/Linux 12 (bookworm) (x86_64)"
+    pub operating_system: String,
+    /// Addresses this host uses for outbound traffic, IPv4 first
+    pub ip_addresses: Vec<String>,
+    /// When the host booted, where the OS reports it
+    pub boot_time: Option<Timestamp>,
+}
+
+impl HostInfo {
+    /// Find out what can be found about this host without extra
+    /// dependencies. Anything that can't be found is left empty.
+    pub fn detect() -> Self {
+        HostInfo {
+            hostname: detect_hostname(),
+            operating_system: detect_operating_system(),
+            ip_addresses: detect_ip_addresses(),
+            boot_time: detect_boot_time(),
+        }
+    }
+}
+
+fn detect_hostname() -> String {
+    std::env::var("HOSTNAME")
+        .or_else(|_| std::env::var("COMPUTERNAME"))
+        .ok()
+        .or_else(|| std::fs::read_to_string("/proc/sys/kernel/hostname").ok())
+        .or_else(|| std::fs::read_to_string("/etc/hostname").ok())
+        .map(|hostname| hostname.trim().to_string())
+        .unwrap_or_default()
+}
+
+fn detect_operating_system() -> String {
+    let name = std::fs::read_to_string("/etc/os-release")
+        .ok()
+        .and_then(|release| {
+            release
+                .lines()
+                .find_map(|line| line.strip_prefix("PRETTY_NAME="))
+                .map(|name| name.trim_matches('"').to_string())
+        })
+        .unwrap_or_else(|| std::env::consts::OS.to_string());
+    format!("{} ({})", name, std::env::consts::ARCH)
+}
+
+fn detect_ip_addresses() -> Vec<String> {
+    // Connecting a UDP socket picks the outbound address without sending
+    // anything; the targets are documentation addresses
+    [("0.0.0.0:0", "192.0.2.1:9"), ("[::]:0", "[2001:db8::1]:9")]
+        .into_iter()
+        .filter_map(|(local, target)| {
+            let socket = UdpSocket::bind(local).ok()?;
+            socket.connect(target).ok()?;
+            let ip = socket.local_addr().ok()?.ip();
+            (!ip.is_unspecified() && !ip.is_loopback()).then(|| ip.to_string())
+        })
+        .collect()
+}
+
+fn detect_boot_time() -> Option<Timestamp> {
+    // Linux reports it in seconds since the epoch as `btime`
+    let stat = std::fs::read_to_string("/proc/stat").ok()?;
+    let secs = stat.lines().find_map(|line| line.strip_prefix("btime "))?.trim().parse().ok()?;
+    Some(secs_to_timestamp(secs))
+}
+
+/// Report host details on a Machine entity (`Hostname`, `OperatingSystem`,
+/// `QlibVersion`, `IpAddresses` and the boot time as `StartTime`) and
+/// version details on a Service entity (`Version`, `BuildInfo` and the
+/// time it started as `StartTime`).
+///
+/// Fields the entity types don't define are skipped, and unchanged values
+/// are written with `PushCondition::Changes`, so this is cheap to call on
+/// every start.
+pub fn register_metadata(store: &mut impl StoreTrait, machine_id: EntityId, service_id: EntityId, host: &HostInfo, service: &ServiceMetadata) -> Result<()> {
+    let ft = FT::new(store);
+
+    let mut machine_fields = vec![
+        (ft.hostname, Value::String(host.hostname.clone())),
+        (ft.operating_system, Value::String(host.operating_system.clone())),
+        (ft.qlib_version, Value::String(env!("CARGO_PKG_VERSION").to_string())),
+        (ft.ip_addresses, Value::StringList(host.ip_addresses.clone())),
+    ];
+    if let Some(boot_time) = host.boot_time {
+        machine_fields.push((ft.start_time, Value::Timestamp(boot_time)));
+    }
+    write_defined(store, machine_id, machine_fields)?;
+
+    write_defined(store, service_id, vec![
+        (ft.version, Value::String(service.version.clone())),
+        (ft.build_info, Value::String(service.build_info.clone())),
+        (ft.start_time, Value::Timestamp(crate::now())),
+    ])
+}
+
+/// Write each value whose field exists on the entity's type
+fn write_defined(store: &mut impl StoreTrait, entity_id: EntityId, fields: Vec<(Option<FieldType>, Value)>) -> Result<()> {
+    for (field_type, value) in fields {
+        let Some(field_type) = field_type.filter(|field_type| store.field_exists(entity_id.extract_type(), *field_type)) else {
+            continue;
+        };
+        store.write(entity_id, &[field_type], value, Some(entity_id), None, Some(PushCondition::Changes), None)?;
+    }
+    Ok(())
+}
@@ -1,6 +1,22 @@
+pub mod backup;
+pub mod health;
+pub mod metadata;
+pub mod scheduler;
+mod async_service_state;
+mod leader_guard;
+mod service_group;
+
 use crossbeam::channel::{Receiver, Sender};
 
-use crate::{et::ET, ft::FT, EntityId, Notification, NotifyConfig, Result, StoreProxy, Value};
+use crate::{et::ET, ft::FT, EntityId, Error, FieldType, Notification, NotifyConfig, Result, StoreProxy, Value};
+
+pub use backup::{backup_to, list_backups, read_manifest, restore_from, rotate, BackupFormat, BackupManifest, BackupScheduler, Compression, Retention, BACKUP_PREFIX};
+pub use health::{aggregate_health, HealthStatus};
+pub use metadata::{register_metadata, HostInfo, ServiceMetadata};
+pub use scheduler::{CronSchedule, MissedRunPolicy, Scheduler};
+pub use async_service_state::AsyncServiceState;
+pub use leader_guard::LeaderGuard;
+pub use service_group::ServiceGroup;
 
 /// Represents a logical component that can act as a candidate for leadership
 /// in a fault-tolerant setup. Typically this would be a Service, but could
@@ -12,7 +28,12 @@ pub struct CandidateState {
     pub candidate_id: EntityId,
     pub is_leader: bool,
 
-    notify_ch: (Sender<Notification>, Receiver<Notification>),
+    /// LeaderToken of the fault tolerance entity at the time this candidate
+    /// became leader. Attached to fenced writes so they are rejected once
+    /// another candidate has taken over.
+    pub fencing_token: Option<(EntityId, i64)>,
+
+    pub(crate) notify_ch: (Sender<Notification>, Receiver<Notification>),
 
     ft: FT
 }
@@ -56,6 +77,14 @@ pub struct ServiceState {
     pub heartbeat_interval_msecs: u64,
     last_heartbeat: std::time::Instant,
 
+    /// Runs ScheduledTask entities while this service is leader (or always,
+    /// if the service is not fault tolerant). Disabled unless set.
+    pub scheduler: Option<Scheduler>,
+
+    /// Takes the backups configured by BackupSchedule entities, under the
+    /// same conditions as `scheduler`. Disabled unless set.
+    pub backup_scheduler: Option<BackupScheduler>,
+
     ft: FT
 }
 
@@ -93,7 +122,10 @@ impl ServiceState {
                 entity_id: fault_tolerance_id,
                 field_type: ft_current_leader,
                 trigger_on_change: true,
-                context: vec![]
+                // The token issued with the assignment, read under the same
+                // write, so a later change of leader can't slip in between
+                context: ft.leader_token.map(|ft_leader_token| vec![vec![ft_leader_token]]).unwrap_or_default(),
+                filter: None,
             }, notify_ch.0.clone())?;
         }
 
@@ -101,6 +133,7 @@ impl ServiceState {
             Some(CandidateState {
                 candidate_id: service_id,
                 is_leader: false,
+                fencing_token: None,
                 notify_ch,
                 ft: ft.clone(),
             })
@@ -115,10 +148,28 @@ impl ServiceState {
             candidate_state,
             heartbeat_interval_msecs,
             last_heartbeat: std::time::Instant::now(),
+            scheduler: None,
+            backup_scheduler: None,
             ft,
         })
     }
 
+    /// Like `new`, and also reports this host on the service's Machine entity
+    /// (its parent) and `metadata` on the Service entity, see
+    /// `register_metadata`, so inventory dashboards are filled in without
+    /// code in every service
+    pub fn new_with_metadata(store: &mut StoreProxy, service_name: String, fault_tolerant: bool, heartbeat_interval_msecs: u64, metadata: &ServiceMetadata) -> Result<Self> {
+        let service = Self::new(store, service_name, fault_tolerant, heartbeat_interval_msecs)?;
+
+        let ft_parent = service.ft.parent.expect("Parent field type should be defined");
+        let (machine, _, _) = store.read(service.service_id, &[ft_parent])?;
+        if let Some(machine_id) = machine.as_entity_reference().copied().flatten() {
+            register_metadata(store, machine_id, service.service_id, &HostInfo::detect(), metadata)?;
+        }
+
+        Ok(service)
+    }
+
     // Called back the main loop tick
     pub fn tick(&mut self, store: &mut StoreProxy) -> Result<()> {
         // Heartbeat update
@@ -128,11 +179,26 @@ impl ServiceState {
             self.last_heartbeat = now;
         }
 
+        self.tick_leadership(store)
+    }
+
+    /// The part of `tick` that doesn't depend on the heartbeat timer, so
+    /// a `ServiceGroup` can drive the heartbeats of all its services at once
+    fn tick_leadership(&mut self, store: &mut StoreProxy) -> Result<()> {
         // Candidate tick - process notifications and update leadership status
         if let Some(ref mut candidate) = self.candidate_state {
             candidate.tick(store)?;
         }
 
+        // Scheduled tasks must only run on one instance
+        let should_schedule = !self.fault_tolerant || self.is_leader();
+        if let (true, Some(scheduler)) = (should_schedule, self.scheduler.as_mut()) {
+            scheduler.tick(store, crate::now())?;
+        }
+        if let (true, Some(backup_scheduler)) = (should_schedule, self.backup_scheduler.as_mut()) {
+            backup_scheduler.tick(store, crate::now())?;
+        }
+
         Ok(())
     }
 
@@ -175,6 +241,40 @@ impl ServiceState {
         }
         Ok(())
     }
+
+    /// Report the health of this service on its `Health` and `HealthMessage` fields
+    pub fn set_health(&self, store: &mut StoreProxy, status: HealthStatus, message: &str) -> Result<()> {
+        health::set_health(store, self.service_id, status, message)
+    }
+
+    /// Write a field only while this service is still the leader.
+    ///
+    /// The write carries the fencing token issued when this service became
+    /// leader, so the store rejects it with `Error::StaleFencingToken` if
+    /// leadership has moved on in the meantime (e.g. after a network
+    /// partition this instance hasn't noticed yet). Services that aren't
+    /// fault tolerant write unconditionally.
+    pub fn fenced_write(&self, store: &mut StoreProxy, entity_id: EntityId, field_path: &[FieldType], value: Value) -> Result<()> {
+        let Some(candidate) = self.candidate_state.as_ref() else {
+            return store.write(entity_id, field_path, value, Some(self.service_id), None, None, None);
+        };
+
+        let (fault_tolerance_id, fencing_token) = candidate
+            .fencing_token
+            .filter(|_| candidate.is_leader())
+            .ok_or_else(|| Error::InvalidRequest(format!("Service {:?} is not the leader", self.service_id)))?;
+
+        store.fenced_write(fault_tolerance_id, fencing_token, entity_id, field_path, value, Some(self.service_id), None, None, None)
+    }
+
+    /// Hand leadership over to another candidate before shutting down.
+    /// See `CandidateState::resign`. Always succeeds for services that aren't fault tolerant.
+    pub fn resign(&mut self, store: &mut StoreProxy, timeout: std::time::Duration) -> Result<bool> {
+        match self.candidate_state {
+            Some(ref mut candidate) => candidate.resign(store, timeout),
+            None => Ok(true),
+        }
+    }
 }
 
 impl CandidateState {
@@ -185,6 +285,7 @@ impl CandidateState {
         CandidateState {
             candidate_id,
             is_leader: false,
+            fencing_token: None,
             notify_ch,
             ft,
         }
@@ -193,22 +294,75 @@ impl CandidateState {
     pub fn tick(&mut self, _store: &mut StoreProxy) -> Result<()> {
         // Check for notifications about leadership changes
         while let Some(notification) = self.notify_ch.1.try_recv().ok() {
-            // The notification is for CurrentLeader field changes
-            if let Some(Value::EntityReference(leader_ref)) = notification.current.value {
-                let was_leader = self.is_leader;
-                self.is_leader = leader_ref == Some(self.candidate_id);
-                
-                if was_leader != self.is_leader {
-                    if self.is_leader {
-                        log::info!("Candidate {:?} became leader", self.candidate_id);
-                    } else {
-                        log::info!("Candidate {:?} lost leadership", self.candidate_id);
+            self.handle_leader_notification(notification);
+        }
+
+        Ok(())
+    }
+
+    /// Update the leadership status from a CurrentLeader notification,
+    /// returning the new leader if the notification carried one
+    fn handle_leader_notification(&mut self, notification: Notification) -> Option<Option<EntityId>> {
+        // The notification is for CurrentLeader field changes
+        if let Some(Value::EntityReference(leader_ref)) = notification.current.value {
+            let was_leader = self.is_leader;
+            self.is_leader = leader_ref == Some(self.candidate_id);
+
+            if was_leader != self.is_leader {
+                if self.is_leader {
+                    log::info!("Candidate {:?} became leader", self.candidate_id);
+                    self.fencing_token = self.fencing_token_of(&notification);
+                } else {
+                    log::info!("Candidate {:?} lost leadership", self.candidate_id);
+                    self.fencing_token = None;
+                }
+            }
+
+            return Some(leader_ref);
+        }
+
+        None
+    }
+
+    /// The LeaderToken that was issued along with the CurrentLeader
+    /// assignment, as carried in the notification's context. Reading it from
+    /// the store afterwards could return a successor's token.
+    fn fencing_token_of(&self, notification: &Notification) -> Option<(EntityId, i64)> {
+        let token = notification.context_value(&[self.ft.leader_token?])?.as_int()?;
+        Some((notification.current.entity_id, token))
+    }
+
+    /// Hand leadership over to another candidate.
+    ///
+    /// Marks this candidate unavailable and waits for the CurrentLeader
+    /// notification naming a different candidate. Returns true once another
+    /// candidate has taken over (or immediately if this candidate wasn't the
+    /// leader), and false if `timeout` elapsed first, in which case the
+    /// candidate stays unavailable but may still be recorded as leader.
+    ///
+    /// Relies on the CurrentLeader notification registered by `ServiceState`.
+    pub fn resign(&mut self, store: &mut StoreProxy, timeout: std::time::Duration) -> Result<bool> {
+        self.make_me_unavailable(store)?;
+
+        if !self.is_leader {
+            return Ok(true);
+        }
+
+        let deadline = std::time::Instant::now() + timeout;
+        while std::time::Instant::now() < deadline {
+            store.process_notifications()?;
+
+            while let Ok(notification) = self.notify_ch.1.try_recv() {
+                if let Some(Some(leader)) = self.handle_leader_notification(notification) {
+                    if leader != self.candidate_id {
+                        log::info!("Candidate {:?} handed leadership to {:?}", self.candidate_id, leader);
+                        return Ok(true);
                     }
                 }
             }
         }
 
-        Ok(())
+        Ok(false)
     }
 
     pub fn make_me_available(&mut self, store: &mut StoreProxy) -> Result<()> {
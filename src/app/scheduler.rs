@@ -0,0 +1,306 @@
+use std::collections::HashMap;
+
+use time::{Date, Duration, Month, OffsetDateTime, Time, UtcOffset};
+
+use crate::{
+    epoch, et::ET, expr::{cel_value_to_value, CelExecutor}, ft::FT, EntityId, Error, IndirectFieldType, Result,
+    StoreTrait, Timestamp, Value, INDIRECTION_DELIMITER,
+};
+
+/// Upper bound on the runs executed for a single task in one tick when
+/// catching up with `MissedRunPolicy::RunAll`
+pub const MAX_CATCH_UP_RUNS: usize = 64;
+
+/// What to do with occurrences that passed while no scheduler was running
+/// (e.g. the leader was down or leadership was changing hands).
+///
+/// Stored as a Choice on the `MissedRunPolicy` field of a `ScheduledTask`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MissedRunPolicy {
+    /// Drop missed occurrences; the task only runs if exactly one occurrence
+    /// is due, i.e. nothing was missed
+    Skip,
+    /// Run once no matter how many occurrences were missed
+    #[default]
+    RunOnce,
+    /// Run once per missed occurrence, up to `MAX_CATCH_UP_RUNS` per tick
+    RunAll,
+}
+
+impl From<i64> for MissedRunPolicy {
+    fn from(value: i64) -> Self {
+        match value {
+            0 => MissedRunPolicy::Skip,
+            2 => MissedRunPolicy::RunAll,
+            _ => MissedRunPolicy::RunOnce,
+        }
+    }
+}
+
+/// A parsed 5-field cron expression: `minute hour day-of-month month day-of-week`.
+///
+/// Each field accepts `*`, single values, ranges (`a-b`), steps (`*/n`,
+/// `a-b/n`) and comma separated lists. Day-of-week uses 0-6 starting on
+/// Sunday (7 is also accepted as Sunday). As with standard cron, if both
+/// day-of-month and day-of-week are restricted a day matches if either does.
+/// All times are evaluated in UTC.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CronSchedule {
+    minutes: u64,
+    hours: u64,
+    days_of_month: u64,
+    months: u64,
+    days_of_week: u64,
+    day_of_month_restricted: bool,
+    day_of_week_restricted: bool,
+}
+
+impl CronSchedule {
+    pub fn parse(expression: &str) -> Result<Self> {
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        if fields.len() != 5 {
+            return Err(Error::InvalidFieldValue(format!(
+                "Cron expression '{}' must have 5 fields",
+                expression
+            )));
+        }
+
+        let mut days_of_week = parse_cron_field(fields[4], 0, 7)?;
+        // Both 0 and 7 mean Sunday
+        if days_of_week & (1 << 7) != 0 {
+            days_of_week = (days_of_week | 1) & !(1 << 7);
+        }
+
+        Ok(CronSchedule {
+            minutes: parse_cron_field(fields[0], 0, 59)?,
+            hours: parse_cron_field(fields[1], 0, 23)?,
+            days_of_month: parse_cron_field(fields[2], 1, 31)?,
+            months: parse_cron_field(fields[3], 1, 12)?,
+            days_of_week,
+            day_of_month_restricted: fields[2] != "*",
+            day_of_week_restricted: fields[4] != "*",
+        })
+    }
+
+    /// The first occurrence strictly after the given time, if there is one
+    /// within the next few years
+    pub fn next_after(&self, after: Timestamp) -> Option<Timestamp> {
+        let after = after.to_offset(UtcOffset::UTC);
+        let mut t = after.replace_time(Time::from_hms(after.hour(), after.minute(), 0).ok()?) + Duration::minutes(1);
+        let limit = after + Duration::days(366 * 5);
+
+        while t <= limit {
+            if !has_bit(self.months, t.month() as u8) {
+                let (year, month) = match t.month() {
+                    Month::December => (t.year() + 1, Month::January),
+                    month => (t.year(), month.next()),
+                };
+                t = Date::from_calendar_date(year, month, 1).ok()?.midnight().assume_utc();
+                continue;
+            }
+
+            if !self.day_matches(t) {
+                t = t.date().next_day()?.midnight().assume_utc();
+                continue;
+            }
+
+            if !has_bit(self.hours, t.hour()) {
+                t = t.replace_time(Time::from_hms(t.hour(), 0, 0).ok()?) + Duration::hours(1);
+                continue;
+            }
+
+            if !has_bit(self.minutes, t.minute()) {
+                t += Duration::minutes(1);
+                continue;
+            }
+
+            return Some(t);
+        }
+
+        None
+    }
+
+    fn day_matches(&self, t: OffsetDateTime) -> bool {
+        let day_of_month = has_bit(self.days_of_month, t.day());
+        let day_of_week = has_bit(self.days_of_week, t.weekday().number_days_from_sunday());
+
+        match (self.day_of_month_restricted, self.day_of_week_restricted) {
+            (true, true) => day_of_month || day_of_week,
+            _ => day_of_month && day_of_week,
+        }
+    }
+}
+
+fn has_bit(mask: u64, bit: u8) -> bool {
+    mask & (1 << bit) != 0
+}
+
+fn parse_cron_field(field: &str, min: u8, max: u8) -> Result<u64> {
+    let invalid = || Error::InvalidFieldValue(format!("Invalid cron field '{}'", field));
+    let mut mask = 0u64;
+
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u8>().map_err(|_| invalid())?),
+            None => (part, 1),
+        };
+        if step == 0 {
+            return Err(invalid());
+        }
+
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if let Some((start, end)) = range.split_once('-') {
+            (start.parse::<u8>().map_err(|_| invalid())?, end.parse::<u8>().map_err(|_| invalid())?)
+        } else {
+            let value = range.parse::<u8>().map_err(|_| invalid())?;
+            // `a/n` means every n starting at a
+            (value, if part.contains('/') { max } else { value })
+        };
+
+        if start < min || end > max || start > end {
+            return Err(invalid());
+        }
+
+        for value in (start..=end).step_by(step as usize) {
+            mask |= 1 << value;
+        }
+    }
+
+    Ok(mask)
+}
+
+/// Runs `ScheduledTask` entities when their cron schedule is due.
+///
+/// A scheduled task has the following fields:
+/// - `Schedule`: cron expression (see `CronSchedule`)
+/// - `Action`: CEL expression evaluated relative to the target entity. The
+///   time of the occurrence being run is available as `ScheduledTime`.
+/// - `Target`: entity the action runs against (the task itself if unset)
+/// - `TargetField`: field path (e.g. `Parent->Counter`) the result is written to
+/// - `LastRun`: time of the last occurrence that was handled
+/// - `MissedRunPolicy`: Choice of Skip, RunOnce or RunAll
+///
+/// Only one scheduler should be active at a time; `ServiceState` runs it
+/// while the service is leader. The progress is kept in `LastRun`, so a new
+/// leader continues where the previous one stopped.
+#[derive(Debug)]
+pub struct Scheduler {
+    executor: CelExecutor,
+}
+
+impl Default for Scheduler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Scheduler {
+    pub fn new() -> Self {
+        Self {
+            executor: CelExecutor::new(),
+        }
+    }
+
+    /// Run every scheduled task that is due at `now`
+    pub fn tick(&mut self, store: &mut impl StoreTrait, now: Timestamp) -> Result<()> {
+        let et = ET::new(store);
+        let ft = FT::new(store);
+
+        let Some(et_scheduled_task) = et.scheduled_task else {
+            return Ok(());
+        };
+
+        for task_id in store.find_entities(et_scheduled_task, None)? {
+            if let Err(e) = self.run_task(store, &ft, task_id, now) {
+                log::warn!("Scheduled task {:?} failed: {}", task_id, e);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn run_task(&mut self, store: &mut impl StoreTrait, ft: &FT, task_id: EntityId, now: Timestamp) -> Result<()> {
+        let ft_schedule = ft.schedule.ok_or_else(|| Error::FieldTypeStrNotFound(crate::ft::SCHEDULE.to_string()))?;
+        let ft_last_run = ft.last_run.ok_or_else(|| Error::FieldTypeStrNotFound(crate::ft::LAST_RUN.to_string()))?;
+
+        let (schedule, _, _) = store.read(task_id, &[ft_schedule])?;
+        let schedule = CronSchedule::parse(schedule.as_string().unwrap_or_default())?;
+        let (last_run, _, _) = store.read(task_id, &[ft_last_run])?;
+        let last_run = last_run.as_timestamp().unwrap_or_else(epoch);
+
+        // A task that never ran starts counting from now instead of catching
+        // up on everything since the epoch
+        if last_run == epoch() {
+            store.write(task_id, &[ft_last_run], Value::Timestamp(now), None, None, None, None)?;
+            return Ok(());
+        }
+
+        let mut due = Vec::new();
+        let mut next = schedule.next_after(last_run);
+        while let Some(occurrence) = next {
+            if occurrence > now || due.len() > MAX_CATCH_UP_RUNS {
+                break;
+            }
+            due.push(occurrence);
+            next = schedule.next_after(occurrence);
+        }
+
+        let Some(latest) = due.last().copied() else {
+            return Ok(());
+        };
+
+        let policy = ft
+            .missed_run_policy
+            .and_then(|ft_policy| store.read(task_id, &[ft_policy]).ok())
+            .and_then(|(policy, _, _)| policy.as_choice())
+            .map(MissedRunPolicy::from)
+            .unwrap_or_default();
+
+        let runs: Vec<Timestamp> = match policy {
+            MissedRunPolicy::Skip if due.len() > 1 => Vec::new(),
+            MissedRunPolicy::Skip | MissedRunPolicy::RunOnce => vec![latest],
+            MissedRunPolicy::RunAll => due.into_iter().take(MAX_CATCH_UP_RUNS).collect(),
+        };
+
+        // Record progress first so a failing action isn't retried every tick
+        let handled = runs.last().copied().unwrap_or(latest);
+        store.write(task_id, &[ft_last_run], Value::Timestamp(handled), None, None, None, None)?;
+
+        for scheduled_time in runs {
+            self.run_action(store, ft, task_id, scheduled_time)?;
+        }
+
+        Ok(())
+    }
+
+    fn run_action(&mut self, store: &mut impl StoreTrait, ft: &FT, task_id: EntityId, scheduled_time: Timestamp) -> Result<()> {
+        let ft_action = ft.action.ok_or_else(|| Error::FieldTypeStrNotFound(crate::ft::ACTION.to_string()))?;
+        let ft_target_field = ft.target_field.ok_or_else(|| Error::FieldTypeStrNotFound(crate::ft::TARGET_FIELD.to_string()))?;
+
+        let target_id = ft
+            .target
+            .and_then(|ft_target| store.read(task_id, &[ft_target]).ok())
+            .and_then(|(target, _, _)| target.as_entity_reference().copied().flatten())
+            .unwrap_or(task_id);
+
+        let (action, _, _) = store.read(task_id, &[ft_action])?;
+        let action = action.as_string().unwrap_or_default().to_string();
+        let (target_field, _, _) = store.read(task_id, &[ft_target_field])?;
+        let target_field: IndirectFieldType = target_field
+            .as_string()
+            .unwrap_or_default()
+            .split(INDIRECTION_DELIMITER)
+            .map(|field_name| store.get_field_type(field_name))
+            .collect::<Result<_>>()?;
+
+        let mut params = HashMap::new();
+        params.insert("ScheduledTime".to_string(), Value::Timestamp(scheduled_time));
+
+        let result = self.executor.execute_with_params(&action, target_id, &params, store)?;
+        let value = cel_value_to_value(result)?;
+        store.write(target_id, &target_field, value, Some(task_id), None, None, None)?;
+
+        Ok(())
+    }
+}
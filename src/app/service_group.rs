@@ -0,0 +1,196 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use crate::{Error, Result, StoreProxy};
+
+use super::ServiceState;
+
+/// A service declared in a `ServiceGroup` and the services it needs
+#[derive(Debug, Clone)]
+struct ServiceSpec {
+    name: String,
+    fault_tolerant: bool,
+    depends_on: Vec<String>,
+}
+
+/// Several services running in one process, e.g. on an edge box that hosts
+/// many small services.
+///
+/// Each service keeps its own `ServiceState`, so leadership is decided per
+/// service, but the group writes all heartbeats on one timer and ticks every
+/// service from one call. Services may depend on each other: `start` brings
+/// a service up only after everything it depends on, and `shutdown` stops
+/// it before them.
+///
+/// # Example Usage
+/// ```ignore
+/// let mut group = ServiceGroup::new(1000);
+/// group.add_service("modbus-poller", true, &[])?;
+/// group.add_service("alarm-engine", true, &["modbus-poller"])?;
+/// group.start(&mut store)?;
+///
+/// loop {
+///     group.tick(&mut store)?;
+///     if group.is_leader("alarm-engine") {
+///         // ...
+///     }
+/// }
+///
+/// group.shutdown(&mut store, Duration::from_secs(5))?;
+/// ```
+pub struct ServiceGroup {
+    specs: Vec<ServiceSpec>,
+
+    /// Running services by name, in the order they were started
+    services: Vec<(String, ServiceState)>,
+
+    /// Heartbeat interval in milliseconds, shared by all services
+    pub heartbeat_interval_msecs: u64,
+    last_heartbeat: Instant,
+}
+
+impl ServiceGroup {
+    pub fn new(heartbeat_interval_msecs: u64) -> Self {
+        ServiceGroup {
+            specs: Vec::new(),
+            services: Vec::new(),
+            heartbeat_interval_msecs,
+            last_heartbeat: Instant::now(),
+        }
+    }
+
+    /// Declare a service to run in this group. `depends_on` names other
+    /// services of the group, which may be declared later. Services can't be
+    /// added once the group is started.
+    pub fn add_service(&mut self, name: &str, fault_tolerant: bool, depends_on: &[&str]) -> Result<()> {
+        if !self.services.is_empty() {
+            return Err(Error::InvalidRequest(format!("Can't add service '{}' to a started group", name)));
+        }
+        if self.specs.iter().any(|spec| spec.name == name) {
+            return Err(Error::InvalidRequest(format!("Service '{}' is already in the group", name)));
+        }
+
+        self.specs.push(ServiceSpec {
+            name: name.to_string(),
+            fault_tolerant,
+            depends_on: depends_on.iter().map(|dependency| dependency.to_string()).collect(),
+        });
+        Ok(())
+    }
+
+    /// The order services are started in: every service after the services
+    /// it depends on, otherwise in the order they were added. Shutdown goes
+    /// the other way. Fails if a dependency isn't in the group or services
+    /// depend on each other in a cycle.
+    pub fn startup_order(&self) -> Result<Vec<&str>> {
+        let index: HashMap<&str, usize> = self.specs.iter().enumerate().map(|(i, spec)| (spec.name.as_str(), i)).collect();
+        for spec in &self.specs {
+            if let Some(missing) = spec.depends_on.iter().find(|dependency| !index.contains_key(dependency.as_str())) {
+                return Err(Error::InvalidRequest(format!("Service '{}' depends on '{}', which is not in the group", spec.name, missing)));
+            }
+        }
+
+        let mut started = vec![false; self.specs.len()];
+        let mut order = Vec::with_capacity(self.specs.len());
+        while order.len() < self.specs.len() {
+            // The first service, in the order added, whose dependencies are all up
+            let next = self.specs.iter().enumerate().position(|(i, spec)| {
+                !started[i] && spec.depends_on.iter().all(|dependency| started[index[dependency.as_str()]])
+            });
+            let Some(next) = next else {
+                let stuck: Vec<&str> = self.specs.iter().zip(&started).filter(|(_, started)| !**started).map(|(spec, _)| spec.name.as_str()).collect();
+                return Err(Error::InvalidRequest(format!("Services depend on each other in a cycle: {}", stuck.join(", "))));
+            };
+            started[next] = true;
+            order.push(self.specs[next].name.as_str());
+        }
+        Ok(order)
+    }
+
+    /// Start every service in `startup_order`: set up its `ServiceState`,
+    /// write a first heartbeat and, for fault tolerant services, make it
+    /// available for election. If a service fails to start, the ones already
+    /// started are shut down again.
+    pub fn start(&mut self, store: &mut StoreProxy) -> Result<()> {
+        if !self.services.is_empty() {
+            return Err(Error::InvalidRequest("Service group is already started".to_string()));
+        }
+        let order: Vec<ServiceSpec> = self
+            .startup_order()?
+            .into_iter()
+            .map(|name| self.specs.iter().find(|spec| spec.name == name).cloned().unwrap())
+            .collect();
+
+        for spec in order {
+            let started = ServiceState::new(store, spec.name.clone(), spec.fault_tolerant, self.heartbeat_interval_msecs)
+                .and_then(|mut service| {
+                    service.write_heartbeat(store)?;
+                    service.make_me_available(store)?;
+                    Ok(service)
+                });
+            match started {
+                Ok(service) => self.services.push((spec.name, service)),
+                Err(e) => {
+                    log::error!("Service '{}' failed to start: {}", spec.name, e);
+                    let _ = self.shutdown(store, Duration::ZERO);
+                    return Err(e);
+                }
+            }
+        }
+
+        self.last_heartbeat = Instant::now();
+        Ok(())
+    }
+
+    /// Call from the main loop: writes every service's heartbeat when the
+    /// shared interval has passed, then ticks each service's leadership and
+    /// scheduler
+    pub fn tick(&mut self, store: &mut StoreProxy) -> Result<()> {
+        let now = Instant::now();
+        if now.duration_since(self.last_heartbeat).as_millis() >= self.heartbeat_interval_msecs as u128 {
+            for (_, service) in &mut self.services {
+                service.write_heartbeat(store)?;
+            }
+            self.last_heartbeat = now;
+        }
+
+        for (_, service) in &mut self.services {
+            service.tick_leadership(store)?;
+        }
+        Ok(())
+    }
+
+    /// Stop every service, in the reverse of the startup order, handing
+    /// leadership over as with `ServiceState::resign`. `timeout` covers the
+    /// whole shutdown. Returns false if some service was still leader when
+    /// its share of the time ran out.
+    pub fn shutdown(&mut self, store: &mut StoreProxy, timeout: Duration) -> Result<bool> {
+        let deadline = Instant::now() + timeout;
+        let mut handed_over = true;
+        while let Some((_, mut service)) = self.services.pop() {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            handed_over &= service.resign(store, remaining)?;
+        }
+        Ok(handed_over)
+    }
+
+    /// A running service by name
+    pub fn service(&self, name: &str) -> Option<&ServiceState> {
+        self.services.iter().find(|(service_name, _)| service_name == name).map(|(_, service)| service)
+    }
+
+    /// A running service by name, e.g. to set its scheduler
+    pub fn service_mut(&mut self, name: &str) -> Option<&mut ServiceState> {
+        self.services.iter_mut().find(|(service_name, _)| service_name == name).map(|(_, service)| service)
+    }
+
+    /// Whether the named service is running and is currently the leader
+    pub fn is_leader(&self, name: &str) -> bool {
+        self.service(name).is_some_and(|service| service.is_leader())
+    }
+
+    /// Running services and their names, in the order they were started
+    pub fn services(&self) -> impl Iterator<Item = (&str, &ServiceState)> {
+        self.services.iter().map(|(name, service)| (name.as_str(), service))
+    }
+}
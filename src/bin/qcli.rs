@@ -0,0 +1,769 @@
+//! `qcli`: interactive shell for a qlib store server.
+//!
+//! ```text
+//! cargo run --features cli --bin qcli -- 127.0.0.1:8080
+//! ```
+//!
+//! Entities are addressed by path (`Root/Machines/M1`) or by numeric id, and
+//! fields by appending the field path (`Root/Machines/M1/Parent->Name`).
+//! Type `help` for the list of commands. Tab completes commands, entity
+//! types, field names and entity names along a path.
+//!
+//! The proxy sits in a `RefCell` so the completer can query it while the
+//! shell holds on to it; the two never run at the same time.
+
+use std::cell::{Ref, RefCell};
+use std::time::{Duration, Instant};
+
+use base64::Engine;
+use crossbeam::channel::{unbounded, Receiver, Sender};
+use qlib_rs::{
+    et, path, EntityId, EntityType, Error, FieldSchema, FieldType, Notification, NotifyConfig, Result, StoreProxy, StoreTrait,
+    StorageScope, Value, INDIRECTION_DELIMITER, json_value_to_value, value_to_json_value,
+};
+use rustyline::completion::Completer;
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::Validator;
+use rustyline::{Context, Editor, Helper};
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+
+const DEFAULT_ADDRESS: &str = "127.0.0.1:8080";
+
+const DEFAULT_LISTEN_SECS: u64 = 10;
+
+/// (name, usage, description)
+const COMMANDS: &[(&str, &str, &str)] = &[
+    ("help", "help", "show this message"),
+    ("ls", "ls [entity]", "list the children of an entity (root entities if omitted)"),
+    ("get", "get <entity>/<field>", "read a field"),
+    ("set", "set <entity>/<field> <value>", "write a field, parsed according to the field's type"),
+    ("resolve", "resolve <path>", "resolve an entity path to its id"),
+    ("path", "path <id>", "show the path of an entity"),
+    ("create", "create <type> <parent|-> <name>", "create an entity"),
+    ("rm", "rm <entity>", "delete an entity and its children"),
+    ("find", "find <type> [filter]", "find entities of a type, optionally filtered by a CEL expression"),
+    ("types", "types", "list entity types"),
+    ("schema", "schema <type>", "show the complete schema of an entity type"),
+    ("watch", "watch <entity>/<field> | watch <type> <field>", "print notifications when a field is written"),
+    ("unwatch", "unwatch <entity>/<field> | unwatch <type> <field>", "stop a watch"),
+    ("listen", "listen [seconds]", "wait for notifications"),
+    ("export", "export <entity> [file]", "export an entity and its descendants as JSON"),
+    ("import", "import <file> <parent|-> [--dry-run]", "validate and import a JSON tree written by export"),
+    ("bootstrap", "bootstrap <manifest>... [--dry-run]", "apply TOML/YAML manifests layered in order, base first, or only list the changes"),
+    ("check-refs", "check-refs", "list reference fields pointing at entities that don't exist"),
+    ("stats", "stats", "show entity counts, memory use and other store statistics"),
+    ("slowlog", "slowlog [count] | slowlog reset", "show the slowest recent commands, or clear the log"),
+    ("quit", "quit", "exit"),
+];
+
+fn main() {
+    let address = std::env::args().nth(1).unwrap_or_else(|| DEFAULT_ADDRESS.to_string());
+
+    let proxy = match StoreProxy::connect(&address) {
+        Ok(proxy) => RefCell::new(proxy),
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    println!("Connected to {}. Type 'help' for a list of commands.", address);
+
+    let helper = QcliHelper::new(&proxy);
+    let mut editor = match Editor::<QcliHelper, DefaultHistory>::new() {
+        Ok(editor) => editor,
+        Err(e) => {
+            eprintln!("Failed to start line editor: {}", e);
+            std::process::exit(1);
+        }
+    };
+    editor.set_helper(Some(helper));
+
+    let mut shell = Shell::new(&proxy);
+
+    loop {
+        shell.print_notifications();
+
+        let line = match editor.readline("qcli> ") {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(e) => {
+                eprintln!("{}", e);
+                break;
+            }
+        };
+
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let _ = editor.add_history_entry(line);
+
+        match shell.execute(line) {
+            Ok(true) => {}
+            Ok(false) => break,
+            Err(e) => eprintln!("error: {}", e),
+        }
+    }
+
+    // Unregister the shell's watches and say goodbye to the server
+    drop(shell);
+    drop(editor);
+    if let Err(e) = proxy.into_inner().close() {
+        eprintln!("error: {}", e);
+    }
+}
+
+struct Shell<'a> {
+    proxy: &'a RefCell<StoreProxy>,
+    sender: Sender<Notification>,
+    receiver: Receiver<Notification>,
+    watches: Vec<NotifyConfig>,
+}
+
+impl<'a> Shell<'a> {
+    fn new(proxy: &'a RefCell<StoreProxy>) -> Self {
+        let (sender, receiver) = unbounded();
+        Self {
+            proxy,
+            sender,
+            receiver,
+            watches: Vec::new(),
+        }
+    }
+
+    fn proxy(&self) -> Ref<'_, StoreProxy> {
+        self.proxy.borrow()
+    }
+
+    /// Run a command line, returns false if the shell should exit
+    fn execute(&mut self, line: &str) -> Result<bool> {
+        let (command, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let rest = rest.trim();
+        let args: Vec<&str> = rest.split_whitespace().collect();
+
+        match command {
+            "help" => print_help(),
+            "quit" | "exit" => return Ok(false),
+            "ls" => self.ls(args.first().copied())?,
+            "get" => {
+                let target = self.field_target(required(&args, 0, "get <entity>/<field>")?)?;
+                let (value, timestamp, writer_id) = self.proxy().read(target.entity_id, &target.field_path)?;
+                let choices = self.choices(&target);
+                println!("{}", format_value(&self.proxy(), &value, &choices));
+                println!("  written {} by {}", format_timestamp(timestamp), writer_id.map(|id| format_entity(&self.proxy(), id)).unwrap_or_else(|| "-".to_string()));
+            }
+            "set" => {
+                let (target, input) = rest
+                    .split_once(char::is_whitespace)
+                    .ok_or_else(|| usage("set <entity>/<field> <value>"))?;
+                let target = self.field_target(target)?;
+                let (current, _, _) = self.proxy().read(target.entity_id, &target.field_path)?;
+                let choices = self.choices(&target);
+                let value = parse_value(&self.proxy(), &current, input.trim(), &choices)?;
+                self.proxy().write(target.entity_id, &target.field_path, value, None, None, None, None)?;
+            }
+            "resolve" => {
+                let entity_id = self.proxy().resolve_path(required(&args, 0, "resolve <path>")?)?;
+                println!("{}", String::from(entity_id));
+            }
+            "path" => {
+                let entity_id = self.entity(required(&args, 0, "path <id>")?)?;
+                println!("{}", path(&*self.proxy(), entity_id)?);
+            }
+            "create" => {
+                let usage_text = "create <type> <parent|-> <name>";
+                let entity_type = self.proxy().get_entity_type(required(&args, 0, usage_text)?)?;
+                let parent_id = match required(&args, 1, usage_text)? {
+                    "-" => None,
+                    parent => Some(self.entity(parent)?),
+                };
+                let name = args.get(2..).filter(|name| !name.is_empty()).ok_or_else(|| usage(usage_text))?.join(" ");
+                let entity_id = self.proxy().create_entity(entity_type, parent_id, &name)?;
+                println!("{}", format_entity(&self.proxy(), entity_id));
+            }
+            "rm" => {
+                let entity_id = self.entity(required(&args, 0, "rm <entity>")?)?;
+                self.proxy().delete_entity(entity_id)?;
+            }
+            "find" => {
+                let (entity_type, filter) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+                if entity_type.is_empty() {
+                    return Err(usage("find <type> [filter]"));
+                }
+                let entity_type = self.proxy().get_entity_type(entity_type)?;
+                let filter = filter.trim();
+                let filter = if filter.is_empty() { None } else { Some(filter) };
+                for entity_id in self.proxy().find_entities(entity_type, filter)? {
+                    println!("{}", format_entity(&self.proxy(), entity_id));
+                }
+            }
+            "types" => {
+                let mut names = self.entity_type_names()?;
+                names.sort();
+                for name in names {
+                    println!("{}", name);
+                }
+            }
+            "schema" => {
+                let entity_type = self.proxy().get_entity_type(required(&args, 0, "schema <type>")?)?;
+                self.print_schema(entity_type)?;
+            }
+            "watch" => {
+                let config = self.notify_config(&args, "watch <entity>/<field> | watch <type> <field>")?;
+                if !self.watches.contains(&config) {
+                    self.proxy().register_notification(config.clone(), self.sender.clone())?;
+                    self.watches.push(config);
+                }
+            }
+            "unwatch" => {
+                let config = self.notify_config(&args, "unwatch <entity>/<field> | unwatch <type> <field>")?;
+                self.proxy().unregister_notification(&config, &self.sender);
+                self.watches.retain(|watch| *watch != config);
+            }
+            "listen" => {
+                let secs = match args.first() {
+                    Some(secs) => secs.parse::<u64>().map_err(|_| usage("listen [seconds]"))?,
+                    None => DEFAULT_LISTEN_SECS,
+                };
+                let deadline = Instant::now() + Duration::from_secs(secs);
+                while Instant::now() < deadline {
+                    self.print_notifications();
+                }
+            }
+            "export" => {
+                let entity_id = self.entity(required(&args, 0, "export <entity> [file]")?)?;
+                let json = qlib_rs::export_subtree(&mut *self.proxy.borrow_mut(), entity_id)?;
+                let json = serde_json::to_string_pretty(&json).map_err(|e| Error::InvalidRequest(e.to_string()))?;
+                match args.get(1) {
+                    Some(file) => std::fs::write(file, json).map_err(|e| Error::InvalidRequest(format!("Failed to write {}: {}", file, e)))?,
+                    None => println!("{}", json),
+                }
+            }
+            "stats" => {
+                let stats = self.proxy().stats()?;
+                for (name, count) in &stats.entity_counts {
+                    println!("{:<24} {}", name, count);
+                }
+                println!("{:<24} {}", "entities", stats.entity_count());
+                println!("{:<24} {}", "fields", stats.field_count);
+                println!("{:<24} {} bytes", "memory estimate", stats.memory_estimate);
+                println!("{:<24} {} entity, {} type", "notifications", stats.entity_notifications, stats.type_notifications);
+                println!("{:<24} {} entity types, {} field types", "interned names", stats.entity_type_names, stats.field_type_names);
+            }
+            "slowlog" => match args.first() {
+                Some(&"reset") => self.proxy().slowlog_reset()?,
+                count => {
+                    let count = count.map(|count| count.parse::<usize>().map_err(|_| usage("slowlog [count] | slowlog reset"))).transpose()?;
+                    for entry in self.proxy().slowlog_get(count)? {
+                        println!("#{} {} {}us {}", entry.id, entry.timestamp, entry.duration_micros, entry.args.join(" "));
+                    }
+                }
+            },
+            "check-refs" => {
+                let dangling = self.proxy().find_dangling_references()?;
+                for reference in &dangling {
+                    let field = self.proxy().resolve_field_type(reference.field_type)?;
+                    println!("{} {} -> {}", format_entity(&self.proxy(), reference.entity_id), field, String::from(reference.target));
+                }
+                println!("{} dangling references", dangling.len());
+            }
+            "import" => {
+                let usage_text = "import <file> <parent|-> [--dry-run]";
+                let file = required(&args, 0, usage_text)?;
+                let parent_id = match required(&args, 1, usage_text)? {
+                    "-" => None,
+                    parent => Some(self.entity(parent)?),
+                };
+                let dry_run = match args.get(2) {
+                    Some(&"--dry-run") => true,
+                    Some(_) => return Err(usage(usage_text)),
+                    None => false,
+                };
+
+                let json = std::fs::read_to_string(file).map_err(|e| Error::InvalidRequest(format!("Failed to read {}: {}", file, e)))?;
+                let json: qlib_rs::JsonEntity = serde_json::from_str(&json).map_err(|e| Error::InvalidRequest(format!("Invalid JSON in {}: {}", file, e)))?;
+
+                let report = qlib_rs::import_subtree(&mut *self.proxy.borrow_mut(), &json, parent_id, dry_run)?;
+                for error in &report.errors {
+                    eprintln!("  {}", error);
+                }
+                match report.root_id {
+                    Some(root_id) => println!("Imported {} entities as {}", report.entity_count, format_entity(&self.proxy(), root_id)),
+                    None if report.is_valid() => println!("{} entities are valid", report.entity_count),
+                    None => println!("Nothing imported, {} errors", report.errors.len()),
+                }
+            }
+            "bootstrap" => {
+                let dry_run = args.last() == Some(&"--dry-run");
+                let files = if dry_run { &args[..args.len() - 1] } else { &args[..] };
+                if files.is_empty() {
+                    return Err(usage("bootstrap <manifest>... [--dry-run]"));
+                }
+
+                let manifest = qlib_rs::bootstrap::Manifest::from_files(files)?;
+                let plan = if dry_run {
+                    qlib_rs::bootstrap::plan(&mut *self.proxy.borrow_mut(), &manifest)?
+                } else {
+                    qlib_rs::bootstrap::apply(&mut *self.proxy.borrow_mut(), &manifest)?
+                };
+                print!("{}", plan);
+                match (plan.is_empty(), dry_run) {
+                    (true, _) => println!("Up to date"),
+                    (false, true) => println!("{} changes to make", plan.changes.len()),
+                    (false, false) => println!("{} changes made", plan.changes.len()),
+                }
+            }
+            _ => return Err(Error::InvalidRequest(format!("Unknown command '{}', type 'help' for a list of commands", command))),
+        }
+
+        Ok(true)
+    }
+
+    fn print_notifications(&self) {
+        if self.watches.is_empty() {
+            return;
+        }
+
+        if let Err(e) = self.proxy().process_notifications() {
+            eprintln!("error: {}", e);
+        }
+
+        for notification in self.receiver.try_iter() {
+            let field = format_field_path(&self.proxy(), &notification.current.field_path);
+            let previous = notification.previous.value.as_ref().map(|value| format_value(&self.proxy(), value, &[])).unwrap_or_else(|| "-".to_string());
+            let current = notification.current.value.as_ref().map(|value| format_value(&self.proxy(), value, &[])).unwrap_or_else(|| "-".to_string());
+            println!("[{}] {} {}: {} -> {}", format_timestamp(notification.current.timestamp.unwrap_or_else(OffsetDateTime::now_utc)), format_entity(&self.proxy(), notification.current.entity_id), field, previous, current);
+        }
+    }
+
+    fn ls(&self, entity: Option<&str>) -> Result<()> {
+        let entities = match entity {
+            Some(entity) => {
+                let entity_id = self.entity(entity)?;
+                let ft_children = self.proxy().get_field_type(qlib_rs::ft::CHILDREN)?;
+                let (children, _, _) = self.proxy().read(entity_id, &[ft_children])?;
+                children.as_entity_list().cloned().unwrap_or_default()
+            }
+            None => self.proxy().find_entities(self.proxy().get_entity_type(et::ROOT)?, None)?,
+        };
+
+        let ft_name = self.proxy().get_field_type(qlib_rs::ft::NAME)?;
+        for entity_id in entities {
+            let name = self
+                .proxy()
+                .read(entity_id, &[ft_name])
+                .ok()
+                .and_then(|(name, _, _)| name.as_string().map(str::to_string))
+                .unwrap_or_default();
+            let entity_type = self.proxy().resolve_entity_type(entity_id.extract_type()).unwrap_or_default();
+            println!("{:<32} {:<24} {}", name, entity_type, String::from(entity_id));
+        }
+
+        Ok(())
+    }
+
+    fn print_schema(&self, entity_type: EntityType) -> Result<()> {
+        let schema = self.proxy().get_complete_entity_schema(entity_type)?;
+        let mut fields: Vec<&FieldSchema> = schema.fields.values().collect();
+        fields.sort_by_key(|field| field.rank());
+
+        for field in fields {
+            let name = self.proxy().resolve_field_type(field.field_type())?;
+            let mut details = Vec::new();
+            if let Some(expression) = field.expression() {
+                details.push(format!("= {}", expression));
+            }
+            if let Some(choices_source) = field.choices_source() {
+                details.push(format!("[choices from {}]", choices_source));
+            }
+            let choices = field.choices();
+            if !choices.is_empty() {
+                details.push(format!("[{}]", choices.join(", ")));
+            }
+            if let Some(validator) = field.validator() {
+                details.push(format!("validator: {}", validator));
+            }
+            let metadata = field.metadata();
+            if let Some(unit) = &metadata.unit {
+                details.push(format!("unit: {}", unit));
+            }
+            if metadata.min.is_some() || metadata.max.is_some() {
+                details.push(format!(
+                    "range: {}..{}",
+                    metadata.min.map(|min| min.to_string()).unwrap_or_default(),
+                    metadata.max.map(|max| max.to_string()).unwrap_or_default()
+                ));
+            }
+            if let Some(description) = &metadata.description {
+                details.push(format!("-- {}", description));
+            }
+            println!(
+                "{:<32} {:<16} {:<14} {}",
+                name,
+                if field.is_computed() { "Computed" } else { value_type_name(&field.default_value()) },
+                format!("{:?}", field.storage_scope()),
+                details.join("  ")
+            );
+        }
+
+        Ok(())
+    }
+
+    fn entity_type_names(&self) -> Result<Vec<String>> {
+        let proxy = self.proxy();
+        proxy
+            .get_entity_types()?
+            .into_iter()
+            .map(|entity_type| proxy.resolve_entity_type(entity_type))
+            .collect()
+    }
+
+    /// An entity given either as a path or as a numeric id
+    fn entity(&self, entity: &str) -> Result<EntityId> {
+        resolve_entity(&self.proxy(), entity)
+    }
+
+    /// An entity followed by a field path, e.g. `Root/Machines/M1/Parent->Name`
+    fn field_target(&self, target: &str) -> Result<FieldTarget> {
+        let (entity, field_path) = target
+            .rsplit_once('/')
+            .ok_or_else(|| Error::InvalidRequest(format!("Expected <entity>/<field>, got '{}'", target)))?;
+
+        Ok(FieldTarget {
+            entity_id: self.entity(entity)?,
+            field_path: field_path
+                .split(INDIRECTION_DELIMITER)
+                .map(|field_name| self.proxy().get_field_type(field_name))
+                .collect::<Result<_>>()?,
+        })
+    }
+
+    /// Schema of the field a target ends up at after indirection
+    /// Choice names of the field a target ends up at after indirection (empty if it isn't a Choice)
+    fn choices(&self, target: &FieldTarget) -> Vec<String> {
+        let proxy = self.proxy();
+        proxy
+            .resolve_indirection(target.entity_id, &target.field_path)
+            .and_then(|(entity_id, field_type)| StoreTrait::get_choices(&*proxy, entity_id.extract_type(), field_type))
+            .unwrap_or_default()
+    }
+
+    fn notify_config(&self, args: &[&str], usage_text: &str) -> Result<NotifyConfig> {
+        match args {
+            [target] => {
+                let target = self.field_target(target)?;
+                let [field_type] = target.field_path[..] else {
+                    return Err(Error::InvalidRequest("Watches don't support indirection".to_string()));
+                };
+                Ok(NotifyConfig::EntityId {
+                    entity_id: target.entity_id,
+                    field_type,
+                    trigger_on_change: false,
+                    context: Vec::new(),
+                    filter: None,
+                })
+            }
+            [entity_type, field] => Ok(NotifyConfig::EntityType {
+                entity_type: self.proxy().get_entity_type(entity_type)?,
+                field_type: self.proxy().get_field_type(field)?,
+                trigger_on_change: false,
+                context: Vec::new(),
+                filter: None,
+            }),
+            _ => Err(usage(usage_text)),
+        }
+    }
+}
+
+struct FieldTarget {
+    entity_id: EntityId,
+    field_path: Vec<FieldType>,
+}
+
+fn print_help() {
+    for (_, usage, description) in COMMANDS {
+        println!("  {:<48} {}", usage, description);
+    }
+    println!();
+    println!("  <entity> is a path such as Root/Machines/M1 or a numeric entity id.");
+    println!("  <field> may use indirection, e.g. Parent->Name.");
+}
+
+fn usage(usage_text: &str) -> Error {
+    Error::InvalidRequest(format!("usage: {}", usage_text))
+}
+
+fn required<'b>(args: &[&'b str], index: usize, usage_text: &str) -> Result<&'b str> {
+    args.get(index).copied().ok_or_else(|| usage(usage_text))
+}
+
+fn resolve_entity(proxy: &StoreProxy, entity: &str) -> Result<EntityId> {
+    match entity.parse::<u64>() {
+        Ok(id) => {
+            let entity_id = EntityId::from(id);
+            if !proxy.entity_exists(entity_id) {
+                return Err(Error::EntityNotFound(entity_id));
+            }
+            Ok(entity_id)
+        }
+        Err(_) => proxy.resolve_path(entity.trim_end_matches('/')),
+    }
+}
+
+fn format_entity(proxy: &StoreProxy, entity_id: EntityId) -> String {
+    match path(proxy, entity_id) {
+        Ok(path) => format!("{} ({})", path, String::from(entity_id)),
+        Err(_) => String::from(entity_id),
+    }
+}
+
+fn format_field_path(proxy: &StoreProxy, field_path: &[FieldType]) -> String {
+    field_path
+        .iter()
+        .map(|field_type| proxy.resolve_field_type(*field_type).unwrap_or_else(|_| field_type.0.to_string()))
+        .collect::<Vec<_>>()
+        .join(INDIRECTION_DELIMITER)
+}
+
+fn format_timestamp(timestamp: OffsetDateTime) -> String {
+    timestamp.format(&Rfc3339).unwrap_or_else(|_| timestamp.to_string())
+}
+
+fn format_value(proxy: &StoreProxy, value: &Value, choices: &[String]) -> String {
+    match value {
+        Value::Blob(blob) => format!("<{} bytes> {}", blob.len(), base64::engine::general_purpose::STANDARD.encode(blob)),
+        Value::Bool(b) => b.to_string(),
+        Value::Choice(choice) => match usize::try_from(*choice).ok().and_then(|index| choices.get(index)) {
+            Some(name) => format!("{} ({})", name, choice),
+            None => choice.to_string(),
+        },
+        Value::EntityList(entities) => {
+            let entities: Vec<String> = entities.iter().map(|entity_id| format_entity(proxy, *entity_id)).collect();
+            format!("[{}]", entities.join(", "))
+        }
+        Value::EntityReference(Some(entity_id)) => format_entity(proxy, *entity_id),
+        Value::EntityReference(None) => "null".to_string(),
+        Value::Float(f) => f.to_string(),
+        Value::Int(i) => i.to_string(),
+        Value::String(s) => format!("{:?}", s),
+        Value::Timestamp(timestamp) => format_timestamp(*timestamp),
+        Value::Decimal(decimal) => decimal.to_string(),
+        Value::Duration(duration) => format!("{}s", duration.as_seconds_f64()),
+        Value::StringList(list) => format!("{:?}", list),
+        Value::Map(_) => value_to_json_value(value, None).to_string(),
+    }
+}
+
+fn value_type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Blob(_) => "Blob",
+        Value::Bool(_) => "Bool",
+        Value::Choice(_) => "Choice",
+        Value::EntityList(_) => "EntityList",
+        Value::EntityReference(_) => "EntityReference",
+        Value::Float(_) => "Float",
+        Value::Int(_) => "Int",
+        Value::String(_) => "String",
+        Value::Timestamp(_) => "Timestamp",
+        Value::Decimal(_) => "Decimal",
+        Value::Duration(_) => "Duration",
+        Value::StringList(_) => "StringList",
+        Value::Map(_) => "Map",
+    }
+}
+
+/// Parse user input into a value of the same type as `current`
+fn parse_value(proxy: &StoreProxy, current: &Value, input: &str, choices: &[String]) -> Result<Value> {
+    let invalid = || Error::InvalidFieldValue(format!("Cannot parse '{}' as {}", input, value_type_name(current)));
+
+    Ok(match current {
+        Value::Blob(_) => Value::Blob(base64::engine::general_purpose::STANDARD.decode(input).map_err(|_| invalid())?),
+        Value::Bool(_) => Value::Bool(input.parse().map_err(|_| invalid())?),
+        Value::Choice(_) => match choices.iter().position(|choice| choice == input) {
+            Some(index) => Value::Choice(index as i64),
+            None => Value::Choice(input.parse().map_err(|_| invalid())?),
+        },
+        Value::EntityList(_) => Value::EntityList(
+            input
+                .trim_start_matches('[')
+                .trim_end_matches(']')
+                .split(',')
+                .map(str::trim)
+                .filter(|entity| !entity.is_empty())
+                .map(|entity| resolve_entity(proxy, entity))
+                .collect::<Result<_>>()?,
+        ),
+        Value::EntityReference(_) => match input {
+            "null" | "-" => Value::EntityReference(None),
+            entity => Value::EntityReference(Some(resolve_entity(proxy, entity)?)),
+        },
+        Value::Float(_) => Value::Float(input.parse().map_err(|_| invalid())?),
+        Value::Int(_) => Value::Int(input.parse().map_err(|_| invalid())?),
+        Value::String(_) => {
+            let unquoted = input
+                .strip_prefix('"')
+                .and_then(|input| input.strip_suffix('"'))
+                .unwrap_or(input);
+            Value::String(unquoted.to_string())
+        }
+        Value::Timestamp(_) => Value::Timestamp(OffsetDateTime::parse(input, &Rfc3339).map_err(|_| invalid())?),
+        Value::Decimal(_) => Value::Decimal(input.parse().map_err(|_| invalid())?),
+        // Seconds, optionally suffixed with "s"
+        Value::Duration(_) => {
+            let secs: f64 = input.trim_end_matches('s').parse().map_err(|_| invalid())?;
+            Value::Duration(qlib_rs::Duration::checked_seconds_f64(secs).ok_or_else(invalid)?)
+        }
+        Value::StringList(_) => Value::StringList(
+            input
+                .trim_start_matches('[')
+                .trim_end_matches(']')
+                .split(',')
+                .map(|item| item.trim().trim_matches('"'))
+                .filter(|item| !item.is_empty())
+                .map(str::to_string)
+                .collect(),
+        ),
+        // Maps are entered as JSON objects, in the same format `get` prints them
+        Value::Map(_) => {
+            let json: serde_json::Value = serde_json::from_str(input).map_err(|_| invalid())?;
+            let schema = FieldSchema::Map {
+                field_type: FieldType(0),
+                default_value: Default::default(),
+                rank: 0,
+                storage_scope: StorageScope::Runtime,
+                validator: None,
+                metadata: Default::default(),
+            };
+            json_value_to_value(&json, &schema)?
+        }
+    })
+}
+
+/// Tab completion for commands, entity types, field names and entity paths
+struct QcliHelper<'a> {
+    proxy: &'a RefCell<StoreProxy>,
+    entity_types: Vec<String>,
+    fields: Vec<String>,
+}
+
+impl<'a> QcliHelper<'a> {
+    fn new(proxy_cell: &'a RefCell<StoreProxy>) -> Self {
+        let proxy = proxy_cell.borrow();
+        let mut entity_types = Vec::new();
+        let mut fields = Vec::new();
+
+        for entity_type in proxy.get_entity_types().unwrap_or_default() {
+            if let Ok(name) = proxy.resolve_entity_type(entity_type) {
+                entity_types.push(name);
+            }
+            if let Ok(schema) = proxy.get_entity_schema(entity_type) {
+                for field_type in schema.fields.keys() {
+                    if let Ok(name) = proxy.resolve_field_type(*field_type) {
+                        fields.push(name);
+                    }
+                }
+            }
+        }
+
+        entity_types.sort();
+        entity_types.dedup();
+        fields.sort();
+        fields.dedup();
+
+        Self {
+            proxy: proxy_cell,
+            entity_types,
+            fields,
+        }
+    }
+
+    fn proxy(&self) -> Ref<'_, StoreProxy> {
+        self.proxy.borrow()
+    }
+
+    fn entity_names(&self, entity_ids: &[EntityId]) -> Vec<String> {
+        let Ok(ft_name) = self.proxy().get_field_type(qlib_rs::ft::NAME) else {
+            return Vec::new();
+        };
+
+        entity_ids
+            .iter()
+            .filter_map(|entity_id| self.proxy().read(*entity_id, &[ft_name]).ok())
+            .filter_map(|(name, _, _)| name.as_string().map(str::to_string))
+            .collect()
+    }
+
+    fn candidates(&self, word: &str, first_word: bool) -> (usize, Vec<String>) {
+        if first_word {
+            return (0, COMMANDS.iter().map(|(name, _, _)| name.to_string()).collect());
+        }
+
+        // Within a field path, only fields make sense after `->`
+        if let Some(index) = word.rfind(INDIRECTION_DELIMITER) {
+            return (index + INDIRECTION_DELIMITER.len(), self.fields.clone());
+        }
+
+        match word.rfind('/') {
+            Some(index) => {
+                let children = resolve_entity(&self.proxy(), &word[..index])
+                    .ok()
+                    .and_then(|entity_id| {
+                        let ft_children = self.proxy().get_field_type(qlib_rs::ft::CHILDREN).ok()?;
+                        self.proxy().read(entity_id, &[ft_children]).ok()
+                    })
+                    .and_then(|(children, _, _)| children.as_entity_list().cloned())
+                    .unwrap_or_default();
+
+                let mut candidates = self.entity_names(&children);
+                candidates.extend(self.fields.iter().cloned());
+                (index + 1, candidates)
+            }
+            None => {
+                let roots = self
+                    .proxy()
+                    .get_entity_type(et::ROOT)
+                    .and_then(|et_root| self.proxy().find_entities(et_root, None))
+                    .unwrap_or_default();
+
+                let mut candidates = self.entity_names(&roots);
+                candidates.extend(self.entity_types.iter().cloned());
+                candidates.extend(self.fields.iter().cloned());
+                (0, candidates)
+            }
+        }
+    }
+}
+
+impl Completer for QcliHelper<'_> {
+    type Candidate = String;
+
+    fn complete(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> rustyline::Result<(usize, Vec<String>)> {
+        let line = &line[..pos];
+        let word_start = line.rfind(char::is_whitespace).map(|index| index + 1).unwrap_or(0);
+        let word = &line[word_start..];
+
+        let (offset, candidates) = self.candidates(word, word_start == 0);
+        let prefix = &word[offset..];
+
+        let mut candidates: Vec<String> = candidates.into_iter().filter(|candidate| candidate.starts_with(prefix)).collect();
+        candidates.sort();
+        candidates.dedup();
+
+        Ok((word_start + offset, candidates))
+    }
+}
+
+impl Hinter for QcliHelper<'_> {
+    type Hint = String;
+}
+
+impl Highlighter for QcliHelper<'_> {}
+
+impl Validator for QcliHelper<'_> {}
+
+impl Helper for QcliHelper<'_> {}
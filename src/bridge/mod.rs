@@ -0,0 +1,10 @@
+//! Glue between the store and the protocols found on the plant floor.
+//!
+//! Each bridge is behind a feature of the same name.
+
+#[cfg(feature = "mqtt")]
+pub mod mqtt;
+#[cfg(feature = "modbus")]
+pub mod modbus;
+#[cfg(feature = "opcua")]
+pub mod opcua;
@@ -0,0 +1,564 @@
+//! Polls Modbus devices and writes the values read into entity fields.
+//!
+//! What is polled is configured with entities, so it can be changed at
+//! runtime like any other configuration. A `ModbusDevice` has the fields:
+//! - `Address`: `tcp://host:port` (or just `host:port`) for Modbus TCP, or
+//!   `rtu:/dev/ttyUSB0` for Modbus RTU over a serial port, which has to be
+//!   set up (baud rate, parity) beforehand, e.g. with `stty`
+//! - `UnitId`: unit (slave) id of the device
+//! - `Health`, `HealthMessage` and `FailedAttempts` (optional): the
+//!   communication status, see `ModbusPoller`
+//!
+//! Its children of type `ModbusPoint` each read one value:
+//! - `RegisterType`: Choice of Coil, DiscreteInput, HoldingRegister or
+//!   InputRegister
+//! - `RegisterAddress`: zero-based address of the (first) register
+//! - `DataType`: Choice of Bool, U16, I16, U32, I32 or F32. 32-bit values
+//!   span two registers, high word first.
+//! - `Scale`: the value written is `raw * Scale`; 0 counts as 1
+//! - `PollInterval`: Duration, or Int milliseconds, between reads
+//! - `Target` and `TargetField`: the entity and field (path) written
+
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration as StdDuration;
+
+use crate::app::health::{set_health, HealthStatus};
+use crate::{
+    ft, Duration, EntityId, Error, FieldSchema, IndirectFieldType, PushCondition, Result, StoreTrait, Timestamp, Value,
+    INDIRECTION_DELIMITER,
+};
+
+pub const MODBUS_DEVICE: &str = "ModbusDevice";
+pub const MODBUS_POINT: &str = "ModbusPoint";
+
+pub const ADDRESS: &str = "Address";
+pub const UNIT_ID: &str = "UnitId";
+pub const REGISTER_TYPE: &str = "RegisterType";
+pub const REGISTER_ADDRESS: &str = "RegisterAddress";
+pub const DATA_TYPE: &str = "DataType";
+pub const SCALE: &str = "Scale";
+pub const POLL_INTERVAL: &str = "PollInterval";
+
+/// Polling interval of points that don't set one
+const DEFAULT_POLL_INTERVAL_MILLIS: i64 = 1000;
+
+/// The four Modbus data tables, stored as a Choice on `RegisterType`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RegisterType {
+    Coil,
+    DiscreteInput,
+    #[default]
+    HoldingRegister,
+    InputRegister,
+}
+
+impl From<i64> for RegisterType {
+    fn from(value: i64) -> Self {
+        match value {
+            0 => RegisterType::Coil,
+            1 => RegisterType::DiscreteInput,
+            3 => RegisterType::InputRegister,
+            _ => RegisterType::HoldingRegister,
+        }
+    }
+}
+
+impl RegisterType {
+    /// The function code reading this table
+    pub fn function_code(self) -> u8 {
+        match self {
+            RegisterType::Coil => 0x01,
+            RegisterType::DiscreteInput => 0x02,
+            RegisterType::HoldingRegister => 0x03,
+            RegisterType::InputRegister => 0x04,
+        }
+    }
+
+    fn is_bits(self) -> bool {
+        matches!(self, RegisterType::Coil | RegisterType::DiscreteInput)
+    }
+}
+
+/// How registers are decoded, stored as a Choice on `DataType`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DataType {
+    Bool,
+    #[default]
+    U16,
+    I16,
+    U32,
+    I32,
+    F32,
+}
+
+impl From<i64> for DataType {
+    fn from(value: i64) -> Self {
+        match value {
+            0 => DataType::Bool,
+            2 => DataType::I16,
+            3 => DataType::U32,
+            4 => DataType::I32,
+            5 => DataType::F32,
+            _ => DataType::U16,
+        }
+    }
+}
+
+impl DataType {
+    /// Number of registers (or bits) read
+    pub fn quantity(self) -> u16 {
+        match self {
+            DataType::Bool | DataType::U16 | DataType::I16 => 1,
+            DataType::U32 | DataType::I32 | DataType::F32 => 2,
+        }
+    }
+}
+
+/// Sends request PDUs (function code and data) to a unit and returns the
+/// response PDU. Exception responses are returned, not turned into errors;
+/// an error means the link failed and the transport is dropped.
+pub trait ModbusTransport {
+    fn request(&mut self, unit_id: u8, pdu: &[u8]) -> Result<Vec<u8>>;
+}
+
+fn modbus_error(what: &str, e: impl std::fmt::Display) -> Error {
+    Error::InvalidRequest(format!("Modbus {} failed: {}", what, e))
+}
+
+/// Modbus TCP: the PDU behind an MBAP header
+#[derive(Debug)]
+pub struct ModbusTcp {
+    stream: TcpStream,
+    transaction_id: u16,
+}
+
+impl ModbusTcp {
+    pub fn connect(address: &str, timeout: StdDuration) -> Result<Self> {
+        let socket_address = address
+            .to_socket_addrs()
+            .map_err(|e| modbus_error("connect", e))?
+            .next()
+            .ok_or_else(|| modbus_error("connect", format!("{} doesn't resolve", address)))?;
+        let stream = TcpStream::connect_timeout(&socket_address, timeout).map_err(|e| modbus_error("connect", e))?;
+        stream.set_read_timeout(Some(timeout)).map_err(|e| modbus_error("connect", e))?;
+        stream.set_write_timeout(Some(timeout)).map_err(|e| modbus_error("connect", e))?;
+        Ok(Self { stream, transaction_id: 0 })
+    }
+}
+
+impl ModbusTransport for ModbusTcp {
+    fn request(&mut self, unit_id: u8, pdu: &[u8]) -> Result<Vec<u8>> {
+        self.transaction_id = self.transaction_id.wrapping_add(1);
+        let mut frame = Vec::with_capacity(7 + pdu.len());
+        frame.extend_from_slice(&self.transaction_id.to_be_bytes());
+        frame.extend_from_slice(&[0, 0]);
+        frame.extend_from_slice(&(pdu.len() as u16 + 1).to_be_bytes());
+        frame.push(unit_id);
+        frame.extend_from_slice(pdu);
+        self.stream.write_all(&frame).map_err(|e| modbus_error("write", e))?;
+
+        let mut header = [0u8; 7];
+        self.stream.read_exact(&mut header).map_err(|e| modbus_error("read", e))?;
+        let length = u16::from_be_bytes([header[4], header[5]]) as usize;
+        if u16::from_be_bytes([header[0], header[1]]) != self.transaction_id || header[2..4] != [0, 0] || length < 2 {
+            return Err(modbus_error("read", "unexpected MBAP header"));
+        }
+        let mut response = vec![0u8; length - 1];
+        self.stream.read_exact(&mut response).map_err(|e| modbus_error("read", e))?;
+        Ok(response)
+    }
+}
+
+/// CRC-16/MODBUS, sent low byte first
+pub fn crc16(data: &[u8]) -> u16 {
+    let mut crc = 0xffffu16;
+    for byte in data {
+        crc ^= *byte as u16;
+        for _ in 0..8 {
+            crc = match crc & 1 {
+                1 => (crc >> 1) ^ 0xa001,
+                _ => crc >> 1,
+            };
+        }
+    }
+    crc
+}
+
+/// Modbus RTU: unit id, PDU and CRC over a serial line
+#[derive(Debug)]
+pub struct ModbusRtu<S> {
+    port: S,
+}
+
+impl ModbusRtu<File> {
+    /// Open a serial port that has already been configured
+    pub fn open(path: &str) -> Result<Self> {
+        let port = OpenOptions::new().read(true).write(true).open(path).map_err(|e| modbus_error("open", format!("{}: {}", path, e)))?;
+        Ok(Self::new(port))
+    }
+}
+
+impl<S: Read + Write> ModbusRtu<S> {
+    pub fn new(port: S) -> Self {
+        Self { port }
+    }
+
+    pub fn into_port(self) -> S {
+        self.port
+    }
+}
+
+impl<S: Read + Write> ModbusTransport for ModbusRtu<S> {
+    fn request(&mut self, unit_id: u8, pdu: &[u8]) -> Result<Vec<u8>> {
+        let mut frame = vec![unit_id];
+        frame.extend_from_slice(pdu);
+        frame.extend_from_slice(&crc16(&frame).to_le_bytes());
+        self.port.write_all(&frame).map_err(|e| modbus_error("write", e))?;
+        self.port.flush().map_err(|e| modbus_error("write", e))?;
+
+        // RTU frames carry no length, so it follows from the function code
+        let mut response = vec![0u8; 3];
+        self.port.read_exact(&mut response).map_err(|e| modbus_error("read", e))?;
+        let rest = match response[1] {
+            code if code & 0x80 != 0 => 2,
+            0x01..=0x04 => response[2] as usize + 2,
+            code => return Err(modbus_error("read", format!("unexpected function code {:#04x}", code))),
+        };
+        let start = response.len();
+        response.resize(start + rest, 0);
+        self.port.read_exact(&mut response[start..]).map_err(|e| modbus_error("read", e))?;
+
+        let (body, crc) = response.split_at(response.len() - 2);
+        if crc16(body).to_le_bytes() != crc {
+            return Err(modbus_error("read", "CRC mismatch"));
+        }
+        if body[0] != unit_id {
+            return Err(modbus_error("read", format!("response from unit {} instead of {}", body[0], unit_id)));
+        }
+        Ok(body[1..].to_vec())
+    }
+}
+
+/// Open the transport for a device's `Address`
+pub fn connect(address: &str, timeout: StdDuration) -> Result<Box<dyn ModbusTransport>> {
+    if let Some(path) = address.strip_prefix("rtu:") {
+        return Ok(Box::new(ModbusRtu::open(path)?));
+    }
+    let address = address.strip_prefix("tcp://").unwrap_or(address);
+    Ok(Box::new(ModbusTcp::connect(address, timeout)?))
+}
+
+/// Read a point's raw value. The outer error is a link failure, the inner
+/// one an exception or malformed response from the device.
+pub fn read_point(
+    transport: &mut dyn ModbusTransport,
+    unit_id: u8,
+    register_type: RegisterType,
+    address: u16,
+    data_type: DataType,
+) -> Result<Result<f64>> {
+    if register_type.is_bits() && data_type != DataType::Bool {
+        return Ok(Err(Error::InvalidFieldValue(format!("{:?} holds bits, not {:?}", register_type, data_type))));
+    }
+    let function_code = register_type.function_code();
+    let mut pdu = vec![function_code];
+    pdu.extend_from_slice(&address.to_be_bytes());
+    pdu.extend_from_slice(&data_type.quantity().to_be_bytes());
+
+    let response = transport.request(unit_id, &pdu)?;
+    let invalid = |message: String| Ok(Err(Error::InvalidRequest(message)));
+    match response.first() {
+        Some(code) if *code == function_code | 0x80 => {
+            return invalid(format!("device answered with exception {}", response.get(1).copied().unwrap_or_default()));
+        }
+        Some(code) if *code == function_code => {}
+        _ => return invalid("unexpected response".to_string()),
+    }
+    let data = match response.get(2..2 + *response.get(1).unwrap_or(&0) as usize) {
+        Some(data) if !data.is_empty() => data,
+        _ => return invalid("short response".to_string()),
+    };
+
+    if register_type.is_bits() {
+        return Ok(Ok((data[0] & 1) as f64));
+    }
+    if data.len() < 2 * data_type.quantity() as usize {
+        return invalid("short response".to_string());
+    }
+    let word = |index: usize| u16::from_be_bytes([data[2 * index], data[2 * index + 1]]);
+    let long = || ((word(0) as u32) << 16) | word(1) as u32;
+    Ok(Ok(match data_type {
+        DataType::Bool => (word(0) != 0) as u8 as f64,
+        DataType::U16 => word(0) as f64,
+        DataType::I16 => word(0) as i16 as f64,
+        DataType::U32 => long() as f64,
+        DataType::I32 => long() as i32 as f64,
+        DataType::F32 => f32::from_bits(long()) as f64,
+    }))
+}
+
+/// Opens the transport for a device address
+pub type Connector = Box<dyn FnMut(&str) -> Result<Box<dyn ModbusTransport>> + Send>;
+
+#[derive(Default)]
+struct DeviceState {
+    transport: Option<Box<dyn ModbusTransport>>,
+    /// Consecutive failed polls
+    failures: u32,
+    retry_at: Option<Timestamp>,
+}
+
+/// A point as read from its entity
+struct Point {
+    entity_id: EntityId,
+    register_type: RegisterType,
+    address: u16,
+    data_type: DataType,
+    scale: f64,
+    interval: Duration,
+    target: EntityId,
+    target_field: IndirectFieldType,
+}
+
+/// Polls the points of the `ModbusDevice` entities when they are due.
+///
+/// Each device gets one connection, opened on first use and dropped when a
+/// request fails. After a failure the device isn't polled again until a
+/// backoff has passed, doubling with each consecutive failure up to a
+/// maximum. Its `FailedAttempts` counts the failures, and `Health` is
+/// Unhealthy until a poll succeeds again. Exceptions from the device (e.g.
+/// an illegal address) only affect their point, and mark the device
+/// Degraded.
+///
+/// ```rust,ignore
+/// let mut poller = ModbusPoller::new();
+/// loop {
+///     poller.tick(&mut store, now())?;
+///     std::thread::sleep(std::time::Duration::from_millis(100));
+/// }
+/// ```
+pub struct ModbusPoller {
+    connector: Connector,
+    backoff: Duration,
+    max_backoff: Duration,
+    devices: HashMap<EntityId, DeviceState>,
+    next_poll: HashMap<EntityId, Timestamp>,
+}
+
+impl std::fmt::Debug for ModbusPoller {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ModbusPoller")
+            .field("backoff", &self.backoff)
+            .field("max_backoff", &self.max_backoff)
+            .field("devices", &self.devices.len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Default for ModbusPoller {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ModbusPoller {
+    /// A poller connecting with `connect` and a 3 second timeout
+    pub fn new() -> Self {
+        Self::with_connector(Box::new(|address: &str| connect(address, StdDuration::from_secs(3))))
+    }
+
+    pub fn with_connector(connector: Connector) -> Self {
+        Self {
+            connector,
+            backoff: Duration::seconds(1),
+            max_backoff: Duration::minutes(5),
+            devices: HashMap::new(),
+            next_poll: HashMap::new(),
+        }
+    }
+
+    /// Wait `backoff` after the first failure, doubling up to `max_backoff`
+    pub fn with_backoff(mut self, backoff: Duration, max_backoff: Duration) -> Self {
+        self.backoff = backoff;
+        self.max_backoff = max_backoff;
+        self
+    }
+
+    /// When a device that failed will be polled again, if it is backing off
+    pub fn retry_at(&self, device_id: EntityId) -> Option<Timestamp> {
+        self.devices.get(&device_id).and_then(|state| state.retry_at)
+    }
+
+    /// Poll the points that are due at `now`. Returns the number of fields
+    /// written.
+    pub fn tick(&mut self, store: &mut impl StoreTrait, now: Timestamp) -> Result<usize> {
+        let Ok(et_device) = store.get_entity_type(MODBUS_DEVICE) else {
+            return Ok(0);
+        };
+
+        let devices = store.find_entities(et_device, None)?;
+        self.devices.retain(|device_id, _| devices.contains(device_id));
+        let mut written = 0;
+        for device_id in devices {
+            match self.poll_device(store, device_id, now) {
+                Ok(count) => written += count,
+                Err(e) => log::warn!("Modbus device {:?} failed: {}", device_id, e),
+            }
+        }
+        Ok(written)
+    }
+
+    fn poll_device(&mut self, store: &mut impl StoreTrait, device_id: EntityId, now: Timestamp) -> Result<usize> {
+        let state = self.devices.entry(device_id).or_default();
+        if state.retry_at.is_some_and(|retry_at| now < retry_at) {
+            return Ok(0);
+        }
+
+        let points: Vec<Point> = device_points(store, device_id)?
+            .into_iter()
+            .filter(|point| self.next_poll.get(&point.entity_id).is_none_or(|next| *next <= now))
+            .collect();
+        if points.is_empty() {
+            return Ok(0);
+        }
+
+        let address = read_string(store, device_id, ADDRESS)?;
+        let unit_id = read_int(store, device_id, UNIT_ID).unwrap_or(1) as u8;
+
+        let state = self.devices.get_mut(&device_id).expect("device state was just inserted");
+        let mut written = 0;
+        let mut exceptions = Vec::new();
+        let mut failure = None;
+        for point in &points {
+            if state.transport.is_none() {
+                match (self.connector)(&address) {
+                    Ok(transport) => state.transport = Some(transport),
+                    Err(e) => {
+                        failure = Some(e);
+                        break;
+                    }
+                }
+            }
+            let transport = state.transport.as_mut().expect("transport was just opened");
+            let raw = match read_point(transport.as_mut(), unit_id, point.register_type, point.address, point.data_type) {
+                Ok(raw) => raw,
+                Err(e) => {
+                    state.transport = None;
+                    failure = Some(e);
+                    break;
+                }
+            };
+            self.next_poll.insert(point.entity_id, now + point.interval);
+            match raw.and_then(|raw| write_point(store, point, raw * point.scale, now)) {
+                Ok(()) => written += 1,
+                Err(e) => exceptions.push(format!("point {:?}: {}", point.entity_id, e)),
+            }
+        }
+
+        match failure {
+            Some(e) => {
+                state.failures += 1;
+                let backoff = self.backoff * 2i32.saturating_pow(state.failures.min(30) - 1);
+                let backoff = backoff.min(self.max_backoff);
+                state.retry_at = Some(now + backoff);
+                let failures = state.failures;
+                report(store, device_id, HealthStatus::Unhealthy, &format!("{}: {}", address, e), failures)?;
+            }
+            None => {
+                state.failures = 0;
+                state.retry_at = None;
+                match exceptions.is_empty() {
+                    true => report(store, device_id, HealthStatus::Healthy, &format!("Polling {}", address), 0)?,
+                    false => report(store, device_id, HealthStatus::Degraded, &exceptions.join("; "), 0)?,
+                }
+            }
+        }
+        Ok(written)
+    }
+}
+
+fn read_value(store: &impl StoreTrait, entity_id: EntityId, name: &str) -> Option<Value> {
+    let field_type = store.get_field_type(name).ok()?;
+    store.read(entity_id, &[field_type]).ok().map(|(value, _, _)| value)
+}
+
+fn read_int(store: &impl StoreTrait, entity_id: EntityId, name: &str) -> Option<i64> {
+    read_value(store, entity_id, name).and_then(|value| value.as_int().or_else(|| value.as_choice()))
+}
+
+fn read_string(store: &impl StoreTrait, entity_id: EntityId, name: &str) -> Result<String> {
+    read_value(store, entity_id, name)
+        .and_then(|value| value.as_string().map(|text| text.to_string()))
+        .ok_or_else(|| Error::FieldTypeStrNotFound(name.to_string()))
+}
+
+/// The `ModbusPoint` children of a device
+fn device_points(store: &impl StoreTrait, device_id: EntityId) -> Result<Vec<Point>> {
+    let et_point = store.get_entity_type(MODBUS_POINT)?;
+    let children = read_value(store, device_id, ft::CHILDREN).and_then(|value| value.as_entity_list().cloned()).unwrap_or_default();
+
+    let mut points = Vec::new();
+    for entity_id in children.into_iter().filter(|child| child.extract_type() == et_point) {
+        let interval = match read_value(store, entity_id, POLL_INTERVAL) {
+            Some(Value::Duration(interval)) => interval,
+            Some(Value::Int(millis)) => Duration::milliseconds(millis),
+            _ => Duration::milliseconds(DEFAULT_POLL_INTERVAL_MILLIS),
+        };
+        let target = read_value(store, entity_id, ft::TARGET).and_then(|value| value.as_entity_reference().copied().flatten());
+        let target_field = read_string(store, entity_id, ft::TARGET_FIELD).unwrap_or_default();
+        let (Some(target), false) = (target, target_field.is_empty()) else {
+            log::warn!("Modbus point {:?} has no Target or TargetField", entity_id);
+            continue;
+        };
+        let target_field = target_field
+            .split(INDIRECTION_DELIMITER)
+            .map(|name| store.get_field_type(name))
+            .collect::<Result<_>>()?;
+        let scale = read_value(store, entity_id, SCALE).and_then(|value| value.as_float()).unwrap_or(1.0);
+
+        points.push(Point {
+            entity_id,
+            register_type: RegisterType::from(read_int(store, entity_id, REGISTER_TYPE).unwrap_or(2)),
+            address: read_int(store, entity_id, REGISTER_ADDRESS).unwrap_or_default() as u16,
+            data_type: DataType::from(read_int(store, entity_id, DATA_TYPE).unwrap_or(1)),
+            scale: if scale == 0.0 { 1.0 } else { scale },
+            interval: interval.max(Duration::ZERO),
+            target,
+            target_field,
+        });
+    }
+    Ok(points)
+}
+
+/// Write a scaled value, converted to the target field's type
+fn write_point(store: &mut impl StoreTrait, point: &Point, scaled: f64, now: Timestamp) -> Result<()> {
+    let (target, field_type) = store.resolve_indirection(point.target, &point.target_field)?;
+    let field_schema = store
+        .get_complete_entity_schema_owned(target.extract_type())?
+        .fields
+        .get(&field_type)
+        .cloned()
+        .ok_or(Error::FieldTypeNotFound(target, field_type))?;
+    let value = match field_schema {
+        FieldSchema::Int { .. } => Value::Int(scaled.round() as i64),
+        FieldSchema::Bool { .. } => Value::Bool(scaled != 0.0),
+        FieldSchema::Float { .. } => Value::Float(scaled),
+        _ => return Err(Error::InvalidFieldType(format!("{:?} isn't an Int, Float or Bool field", field_type))),
+    };
+    store.write(target, &[field_type], value, Some(point.entity_id), Some(now), Some(PushCondition::Changes), None)
+}
+
+/// Write a device's communication status to the fields it has
+fn report(store: &mut impl StoreTrait, device_id: EntityId, status: HealthStatus, message: &str, failures: u32) -> Result<()> {
+    if read_value(store, device_id, ft::HEALTH).is_some() {
+        set_health(store, device_id, status, message)?;
+    }
+    if read_value(store, device_id, ft::FAILED_ATTEMPTS).is_some() {
+        let ft_failed_attempts = store.get_field_type(ft::FAILED_ATTEMPTS)?;
+        store.write(device_id, &[ft_failed_attempts], Value::Int(failures as i64), None, None, Some(PushCondition::Changes), None)?;
+    }
+    Ok(())
+}
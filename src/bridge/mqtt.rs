@@ -0,0 +1,559 @@
+//! Publishes field values to MQTT and turns MQTT messages into writes.
+//!
+//! `MqttClient` is a small MQTT 3.1.1 client over plain TCP. It publishes
+//! and subscribes at QoS 0, which is what telemetry bridges use: the store
+//! keeps the state, and every value is published retained, so a missed
+//! message is made up by the next change or reconnect.
+//!
+//! `MqttBridge` decides what goes over it. Topics are derived from entity
+//! paths: the `Speed` field of `Plant/Area1/P1` is published to
+//! `<prefix>/Plant/Area1/P1/Speed`, and for writable fields a message to
+//! `<prefix>/Plant/Area1/P1/Speed/set` writes the field. Payloads are the
+//! JSON form of the value, as in JSON snapshots.
+
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::{Duration as StdDuration, Instant};
+
+use crate::{
+    json_value_to_value, path, path_to_entity_id, value_to_json_value, EntityId, EntityType, Error, FieldSchema, FieldType,
+    Notification, NotificationQueue, NotifyConfig, Result, StoreTrait,
+};
+
+/// Suffix of the topics that write a field
+pub const SET_SUFFIX: &str = "/set";
+
+const CONNECT: u8 = 0x10;
+const CONNACK: u8 = 0x20;
+const PUBLISH: u8 = 0x30;
+const PUBACK: u8 = 0x40;
+const SUBSCRIBE: u8 = 0x82;
+const SUBACK: u8 = 0x90;
+const PINGREQ: u8 = 0xc0;
+const PINGRESP: u8 = 0xd0;
+const DISCONNECT: u8 = 0xe0;
+
+/// How to reach and log in to the broker
+#[derive(Debug, Clone)]
+pub struct MqttOptions {
+    /// `host:port` of the broker
+    pub address: String,
+    pub client_id: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// The broker drops the connection if nothing is sent for 1.5 times
+    /// this; `poll` pings it in time
+    pub keep_alive: StdDuration,
+    /// Timeout of connecting and of waiting for acknowledgements
+    pub timeout: StdDuration,
+}
+
+impl MqttOptions {
+    pub fn new(address: &str, client_id: &str) -> Self {
+        Self {
+            address: address.to_string(),
+            client_id: client_id.to_string(),
+            username: None,
+            password: None,
+            keep_alive: StdDuration::from_secs(30),
+            timeout: StdDuration::from_secs(10),
+        }
+    }
+
+    pub fn with_credentials(mut self, username: &str, password: &str) -> Self {
+        self.username = Some(username.to_string());
+        self.password = Some(password.to_string());
+        self
+    }
+
+    pub fn with_keep_alive(mut self, keep_alive: StdDuration) -> Self {
+        self.keep_alive = keep_alive;
+        self
+    }
+}
+
+/// A message received on a subscribed topic
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MqttMessage {
+    pub topic: String,
+    pub payload: Vec<u8>,
+}
+
+fn mqtt_error(what: &str, e: impl std::fmt::Display) -> Error {
+    Error::InvalidRequest(format!("MQTT {} failed: {}", what, e))
+}
+
+fn put_string(packet: &mut Vec<u8>, text: &str) {
+    packet.extend_from_slice(&(text.len() as u16).to_be_bytes());
+    packet.extend_from_slice(text.as_bytes());
+}
+
+/// A packet with its fixed header
+fn frame(header: u8, body: &[u8]) -> Vec<u8> {
+    let mut packet = vec![header];
+    let mut length = body.len();
+    loop {
+        let mut byte = (length % 128) as u8;
+        length /= 128;
+        if length > 0 {
+            byte |= 0x80;
+        }
+        packet.push(byte);
+        if length == 0 {
+            break;
+        }
+    }
+    packet.extend_from_slice(body);
+    packet
+}
+
+/// The first complete packet in `buffer` as its header byte, body and
+/// total length
+fn parse_frame(buffer: &[u8]) -> Result<Option<(u8, &[u8], usize)>> {
+    let mut length = 0usize;
+    for (index, byte) in buffer.iter().enumerate().skip(1).take(4) {
+        length += ((byte & 0x7f) as usize) << (7 * (index - 1));
+        if byte & 0x80 == 0 {
+            let end = index + 1 + length;
+            return Ok((buffer.len() >= end).then(|| (buffer[0], &buffer[index + 1..end], end)));
+        }
+    }
+    match buffer.len() > 4 {
+        true => Err(mqtt_error("read", "malformed remaining length")),
+        false => Ok(None),
+    }
+}
+
+fn read_string(body: &[u8]) -> Option<(String, &[u8])> {
+    let length = u16::from_be_bytes([*body.first()?, *body.get(1)?]) as usize;
+    let text = String::from_utf8(body.get(2..2 + length)?.to_vec()).ok()?;
+    Some((text, &body[2 + length..]))
+}
+
+/// A connection to an MQTT broker
+#[derive(Debug)]
+pub struct MqttClient {
+    stream: TcpStream,
+    buffer: Vec<u8>,
+    keep_alive: StdDuration,
+    timeout: StdDuration,
+    last_sent: Instant,
+    next_packet_id: u16,
+    /// Messages that arrived while waiting for an acknowledgement
+    pending: Vec<MqttMessage>,
+}
+
+impl MqttClient {
+    /// Connect with a clean session
+    pub fn connect(options: &MqttOptions) -> Result<Self> {
+        let address = options
+            .address
+            .to_socket_addrs()
+            .map_err(|e| mqtt_error("connect", e))?
+            .next()
+            .ok_or_else(|| mqtt_error("connect", format!("{} doesn't resolve", options.address)))?;
+        let stream = TcpStream::connect_timeout(&address, options.timeout).map_err(|e| mqtt_error("connect", e))?;
+        stream.set_nodelay(true).map_err(|e| mqtt_error("connect", e))?;
+
+        let mut client = Self {
+            stream,
+            buffer: Vec::new(),
+            keep_alive: options.keep_alive,
+            timeout: options.timeout,
+            last_sent: Instant::now(),
+            next_packet_id: 1,
+            pending: Vec::new(),
+        };
+
+        let mut flags = 0x02;
+        let mut payload = Vec::new();
+        put_string(&mut payload, &options.client_id);
+        if let Some(username) = &options.username {
+            flags |= 0x80;
+            put_string(&mut payload, username);
+        }
+        if let Some(password) = &options.password {
+            flags |= 0x40;
+            put_string(&mut payload, password);
+        }
+        let mut body = Vec::new();
+        put_string(&mut body, "MQTT");
+        body.push(4);
+        body.push(flags);
+        body.extend_from_slice(&(options.keep_alive.as_secs().min(u16::MAX as u64) as u16).to_be_bytes());
+        body.extend_from_slice(&payload);
+        client.send(&frame(CONNECT, &body))?;
+
+        let connack = client.wait_for(CONNACK, "connect")?;
+        match connack.get(1) {
+            Some(0) => Ok(client),
+            Some(code) => Err(mqtt_error("connect", format!("broker refused the connection with code {}", code))),
+            None => Err(mqtt_error("connect", "malformed CONNACK")),
+        }
+    }
+
+    fn send(&mut self, packet: &[u8]) -> Result<()> {
+        self.stream.write_all(packet).map_err(|e| mqtt_error("write", e))?;
+        self.last_sent = Instant::now();
+        Ok(())
+    }
+
+    /// Read the next packet, or None if none arrives before `deadline`
+    fn read_packet(&mut self, deadline: Instant) -> Result<Option<(u8, Vec<u8>)>> {
+        loop {
+            if let Some((header, body, end)) = parse_frame(&self.buffer)? {
+                let packet = (header, body.to_vec());
+                self.buffer.drain(..end);
+                return Ok(Some(packet));
+            }
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Ok(None);
+            }
+            self.stream.set_read_timeout(Some(remaining)).map_err(|e| mqtt_error("read", e))?;
+            let mut chunk = [0u8; 4096];
+            match self.stream.read(&mut chunk) {
+                Ok(0) => return Err(mqtt_error("read", "broker closed the connection")),
+                Ok(n) => self.buffer.extend_from_slice(&chunk[..n]),
+                Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => return Ok(None),
+                Err(e) => return Err(mqtt_error("read", e)),
+            }
+        }
+    }
+
+    /// Handle a packet the broker sent on its own. Returns the message if
+    /// it was a PUBLISH.
+    fn handle(&mut self, header: u8, body: &[u8]) -> Result<Option<MqttMessage>> {
+        if header & 0xf0 != PUBLISH {
+            return Ok(None);
+        }
+        let qos = (header >> 1) & 0x03;
+        let (topic, mut rest) = read_string(body).ok_or_else(|| mqtt_error("read", "malformed PUBLISH"))?;
+        if qos > 0 {
+            let packet_id = rest.get(..2).ok_or_else(|| mqtt_error("read", "malformed PUBLISH"))?.to_vec();
+            rest = &rest[2..];
+            // QoS 2 isn't requested, so brokers only send QoS 0 and 1
+            self.send(&frame(PUBACK, &packet_id))?;
+        }
+        Ok(Some(MqttMessage { topic, payload: rest.to_vec() }))
+    }
+
+    /// Wait for a packet of type `expected`, keeping messages that arrive
+    /// meanwhile for `poll`
+    fn wait_for(&mut self, expected: u8, what: &str) -> Result<Vec<u8>> {
+        let deadline = Instant::now() + self.timeout;
+        loop {
+            let (header, body) = self.read_packet(deadline)?.ok_or_else(|| mqtt_error(what, "timed out"))?;
+            if header & 0xf0 == expected {
+                return Ok(body);
+            }
+            if let Some(message) = self.handle(header, &body)? {
+                self.pending.push(message);
+            }
+        }
+    }
+
+    /// Publish at QoS 0
+    pub fn publish(&mut self, topic: &str, payload: &[u8], retain: bool) -> Result<()> {
+        let mut body = Vec::with_capacity(topic.len() + payload.len() + 2);
+        put_string(&mut body, topic);
+        body.extend_from_slice(payload);
+        self.send(&frame(PUBLISH | retain as u8, &body))
+    }
+
+    /// Subscribe to topic filters at QoS 0, waiting for the broker to
+    /// accept them
+    pub fn subscribe(&mut self, filters: &[&str]) -> Result<()> {
+        let packet_id = self.next_packet_id;
+        self.next_packet_id = self.next_packet_id.checked_add(1).unwrap_or(1);
+        let mut body = packet_id.to_be_bytes().to_vec();
+        for filter in filters {
+            put_string(&mut body, filter);
+            body.push(0);
+        }
+        self.send(&frame(SUBSCRIBE, &body))?;
+
+        let suback = self.wait_for(SUBACK, "subscribe")?;
+        if let Some(index) = suback.iter().skip(2).position(|code| *code == 0x80) {
+            return Err(mqtt_error("subscribe", format!("broker rejected '{}'", filters.get(index).unwrap_or(&""))));
+        }
+        Ok(())
+    }
+
+    /// The next message on a subscribed topic, waiting up to `timeout`.
+    /// Pings the broker when the connection would otherwise go idle, so
+    /// call it at least every `keep_alive`.
+    pub fn poll(&mut self, timeout: StdDuration) -> Result<Option<MqttMessage>> {
+        if !self.pending.is_empty() {
+            return Ok(Some(self.pending.remove(0)));
+        }
+        if !self.keep_alive.is_zero() && self.last_sent.elapsed() >= self.keep_alive / 2 {
+            self.send(&frame(PINGREQ, &[]))?;
+        }
+
+        let deadline = Instant::now() + timeout;
+        while let Some((header, body)) = self.read_packet(deadline)? {
+            if header & 0xf0 == PINGRESP {
+                continue;
+            }
+            if let Some(message) = self.handle(header, &body)? {
+                return Ok(Some(message));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Close the connection cleanly
+    pub fn disconnect(mut self) -> Result<()> {
+        self.send(&frame(DISCONNECT, &[]))
+    }
+}
+
+/// What a bridged field is read from: every entity of a type (subtypes
+/// included), or one entity
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BridgeTarget {
+    EntityType(EntityType),
+    EntityId(EntityId),
+}
+
+/// A field published by an `MqttBridge`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BridgedField {
+    pub target: BridgeTarget,
+    pub field_type: FieldType,
+    /// Whether messages on its `/set` topic write it
+    pub writable: bool,
+}
+
+impl BridgedField {
+    pub fn of_type(entity_type: EntityType, field_type: FieldType) -> Self {
+        Self {
+            target: BridgeTarget::EntityType(entity_type),
+            field_type,
+            writable: false,
+        }
+    }
+
+    pub fn of_entity(entity_id: EntityId, field_type: FieldType) -> Self {
+        Self {
+            target: BridgeTarget::EntityId(entity_id),
+            field_type,
+            writable: false,
+        }
+    }
+
+    pub fn writable(mut self) -> Self {
+        self.writable = true;
+        self
+    }
+}
+
+/// Whether `entity_type` is `ancestor` or inherits from it
+fn inherits(store: &impl StoreTrait, entity_type: EntityType, ancestor: EntityType) -> bool {
+    let mut pending = vec![entity_type];
+    while let Some(entity_type) = pending.pop() {
+        if entity_type == ancestor {
+            return true;
+        }
+        if let Ok(schema) = store.get_entity_schema(entity_type) {
+            pending.extend(schema.inherit);
+        }
+    }
+    false
+}
+
+/// Publishes fields to MQTT on change and writes fields from MQTT.
+///
+/// The bridge itself holds no connection; pass the `MqttClient` to each
+/// call so reconnecting is up to the caller.
+///
+/// ```rust,ignore
+/// let bridge = MqttBridge::new("plant")
+///     .field(BridgedField::of_type(et_pump, ft_speed))
+///     .field(BridgedField::of_type(et_pump, ft_setpoint).writable());
+/// let queue = NotificationQueue::new();
+/// for config in bridge.notify_configs() {
+///     store.register_notification(config, queue.clone())?;
+/// }
+/// let mut client = MqttClient::connect(&MqttOptions::new("broker:1883", "qlib-bridge"))?;
+/// bridge.start(&mut store, &mut client)?;
+/// loop {
+///     bridge.run_once(&mut store, &mut client, &queue, std::time::Duration::from_millis(100))?;
+/// }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct MqttBridge {
+    prefix: String,
+    fields: Vec<BridgedField>,
+}
+
+impl MqttBridge {
+    /// A bridge publishing under `prefix`, which may be empty
+    pub fn new(prefix: &str) -> Self {
+        Self {
+            prefix: prefix.trim_end_matches('/').to_string(),
+            fields: Vec::new(),
+        }
+    }
+
+    pub fn field(mut self, field: BridgedField) -> Self {
+        self.fields.push(field);
+        self
+    }
+
+    pub fn fields(&self) -> &[BridgedField] {
+        &self.fields
+    }
+
+    /// Notifications to register for the bridged fields, all delivered to
+    /// the queue passed to `run_once`
+    pub fn notify_configs(&self) -> Vec<NotifyConfig> {
+        self.fields
+            .iter()
+            .map(|field| match field.target {
+                BridgeTarget::EntityType(entity_type) => NotifyConfig::EntityType {
+                    entity_type,
+                    field_type: field.field_type,
+                    trigger_on_change: true,
+                    context: vec![],
+                    filter: None,
+                },
+                BridgeTarget::EntityId(entity_id) => NotifyConfig::EntityId {
+                    entity_id,
+                    field_type: field.field_type,
+                    trigger_on_change: true,
+                    context: vec![],
+                    filter: None,
+                },
+            })
+            .collect()
+    }
+
+    /// The topic the field of an entity is published to
+    pub fn topic(&self, store: &impl StoreTrait, entity_id: EntityId, field_type: FieldType) -> Result<String> {
+        let entity_path = path(store, entity_id)?;
+        let field_name = store.resolve_field_type(field_type)?;
+        let topic = match self.prefix.is_empty() {
+            true => format!("{}/{}", entity_path, field_name),
+            false => format!("{}/{}/{}", self.prefix, entity_path, field_name),
+        };
+        // Wildcards can't be published to, and would make the set topic ambiguous
+        if topic.contains(['+', '#', '\0']) {
+            return Err(Error::InvalidRequest(format!("'{}' is not a valid MQTT topic", topic)));
+        }
+        Ok(topic)
+    }
+
+    fn bridged(&self, store: &impl StoreTrait, entity_id: EntityId, field_type: FieldType) -> Option<&BridgedField> {
+        self.fields.iter().find(|field| {
+            field.field_type == field_type
+                && match field.target {
+                    BridgeTarget::EntityId(target) => target == entity_id,
+                    BridgeTarget::EntityType(entity_type) => inherits(store, entity_id.extract_type(), entity_type),
+                }
+        })
+    }
+
+    /// The topic and payload publishing a field's current value
+    fn message(&self, store: &impl StoreTrait, entity_id: EntityId, field_type: FieldType) -> Result<MqttMessage> {
+        let topic = self.topic(store, entity_id, field_type)?;
+        let (value, _, _) = store.read(entity_id, &[field_type])?;
+        let choices = store.get_choices(entity_id.extract_type(), field_type).ok();
+        let payload = value_to_json_value(&value, choices.as_ref()).to_string().into_bytes();
+        Ok(MqttMessage { topic, payload })
+    }
+
+    /// Publish the current value of a field, retained
+    pub fn publish(&self, store: &impl StoreTrait, client: &mut MqttClient, entity_id: EntityId, field_type: FieldType) -> Result<()> {
+        let message = self.message(store, entity_id, field_type)?;
+        client.publish(&message.topic, &message.payload, true)
+    }
+
+    /// Publish every bridged field as it is now, and subscribe to the set
+    /// topics if any field is writable. Call after (re)connecting.
+    pub fn start(&self, store: &impl StoreTrait, client: &mut MqttClient) -> Result<()> {
+        for field in &self.fields {
+            let entities = match field.target {
+                BridgeTarget::EntityType(entity_type) => store.find_entities(entity_type, None)?,
+                BridgeTarget::EntityId(entity_id) => vec![entity_id],
+            };
+            for entity_id in entities {
+                match self.message(store, entity_id, field.field_type) {
+                    Ok(message) => client.publish(&message.topic, &message.payload, true)?,
+                    Err(e) => log::warn!("Not publishing {:?} of {:?} to MQTT: {}", field.field_type, entity_id, e),
+                }
+            }
+        }
+
+        if self.fields.iter().any(|field| field.writable) {
+            let filter = match self.prefix.is_empty() {
+                true => "#".to_string(),
+                false => format!("{}/#", self.prefix),
+            };
+            client.subscribe(&[&filter])?;
+        }
+        Ok(())
+    }
+
+    /// The bridged field a notification is about, if any. Notifications
+    /// from other registrations sharing the queue give None.
+    pub fn notified_field(&self, store: &impl StoreTrait, notification: &Notification) -> Option<(EntityId, FieldType)> {
+        let entity_id = notification.current.entity_id;
+        let field_type = *notification.current.field_path.first()?;
+        self.bridged(store, entity_id, field_type).map(|_| (entity_id, field_type))
+    }
+
+    /// Write the field a message's set topic names. Returns the field
+    /// written, or None if the topic isn't a set topic of this bridge.
+    /// Messages for fields that aren't bridged as writable are rejected.
+    pub fn handle_message(&self, store: &mut impl StoreTrait, message: &MqttMessage) -> Result<Option<(EntityId, FieldType)>> {
+        let topic = match self.prefix.is_empty() {
+            true => Some(message.topic.as_str()),
+            false => message.topic.strip_prefix(&self.prefix).and_then(|topic| topic.strip_prefix('/')),
+        };
+        let Some((entity_path, field_name)) = topic.and_then(|topic| topic.strip_suffix(SET_SUFFIX)).and_then(|topic| topic.rsplit_once('/')) else {
+            return Ok(None);
+        };
+
+        let field_type = store.get_field_type(field_name)?;
+        let entity_id = path_to_entity_id(store, entity_path)?;
+        if !self.bridged(store, entity_id, field_type).is_some_and(|field| field.writable) {
+            return Err(Error::InvalidRequest(format!("{} isn't writable over MQTT", message.topic)));
+        }
+
+        let field_schema: FieldSchema = store
+            .get_complete_entity_schema_owned(entity_id.extract_type())?
+            .fields
+            .get(&field_type)
+            .cloned()
+            .ok_or(Error::FieldTypeNotFound(entity_id, field_type))?;
+        let payload: serde_json::Value = serde_json::from_slice(&message.payload)
+            .map_err(|e| Error::InvalidFieldValue(format!("Payload on {} isn't JSON: {}", message.topic, e)))?;
+        let value = json_value_to_value(&payload, &field_schema)?;
+        store.write(entity_id, &[field_type], value, None, None, None, None)?;
+        Ok(Some((entity_id, field_type)))
+    }
+
+    /// Publish the queued notifications, then apply at most one message,
+    /// waiting up to `timeout` for it. Failures of single fields are logged;
+    /// connection failures are returned so the caller can reconnect.
+    pub fn run_once(&self, store: &mut impl StoreTrait, client: &mut MqttClient, queue: &NotificationQueue, timeout: StdDuration) -> Result<()> {
+        while let Some(notification) = queue.pop() {
+            let Some((entity_id, field_type)) = self.notified_field(store, &notification) else {
+                continue;
+            };
+            match self.message(store, entity_id, field_type) {
+                Ok(message) => client.publish(&message.topic, &message.payload, true)?,
+                Err(e) => log::warn!("Not publishing {:?} of {:?} to MQTT: {}", field_type, entity_id, e),
+            }
+        }
+
+        if let Some(message) = client.poll(timeout)? {
+            if let Err(e) = self.handle_message(store, &message) {
+                log::warn!("Ignoring MQTT message on {}: {}", message.topic, e);
+            }
+        }
+        Ok(())
+    }
+}
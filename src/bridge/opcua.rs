@@ -0,0 +1,249 @@
+//! Writes OPC UA node value changes into entity fields.
+//!
+//! `OpcUaIngest` owns the mapping from nodes to fields, the scaling and
+//! deadband applied on the way in, and the health reported for the
+//! connection. The OPC UA session itself is behind `OpcUaConnection`:
+//! this crate doesn't include an OPC UA stack, so implement the trait on
+//! top of the client library the deployment uses, creating a subscription
+//! with one monitored item per node and returning its data change
+//! notifications from `poll`.
+
+use std::collections::HashMap;
+use std::time::Duration as StdDuration;
+
+use crate::app::health::{set_health, HealthStatus};
+use crate::{EntityId, FieldSchema, FieldType, PushCondition, Result, StoreTrait, Timestamp, Value};
+
+/// A change of a monitored node's value
+#[derive(Debug, Clone, PartialEq)]
+pub struct DataChange {
+    /// Node id in its string form, e.g. `ns=2;s=Line1.Pump1.Speed`
+    pub node_id: String,
+    pub value: Value,
+    /// OPC UA StatusCode of the value
+    pub status_code: u32,
+    pub source_timestamp: Option<Timestamp>,
+}
+
+impl DataChange {
+    /// Good and Good_* codes; Uncertain and Bad values aren't written
+    pub fn is_good(&self) -> bool {
+        self.status_code >> 30 == 0
+    }
+}
+
+/// An OPC UA session with a subscription, provided by the client library
+pub trait OpcUaConnection {
+    /// Open a session with `endpoint`, e.g. `opc.tcp://plc1:4840`
+    fn connect(&mut self, endpoint: &str) -> Result<()>;
+
+    /// Monitor the value of the nodes, replacing earlier monitored items.
+    /// Servers send the current value of each node first.
+    fn monitor(&mut self, node_ids: &[String]) -> Result<()>;
+
+    /// Data changes received within `timeout`. An error means the session
+    /// is gone and `connect` is called again.
+    fn poll(&mut self, timeout: StdDuration) -> Result<Vec<DataChange>>;
+}
+
+/// Where a node's value is written, and how it is converted
+#[derive(Debug, Clone, PartialEq)]
+pub struct OpcUaMapping {
+    pub node_id: String,
+    pub entity_id: EntityId,
+    pub field_type: FieldType,
+    /// Numeric values are written as `raw * scale + offset`
+    pub scale: f64,
+    pub offset: f64,
+    /// Numeric changes smaller than this (after scaling) since the last
+    /// written value aren't written; 0 writes every change
+    pub deadband: f64,
+}
+
+impl OpcUaMapping {
+    pub fn new(node_id: &str, entity_id: EntityId, field_type: FieldType) -> Self {
+        Self {
+            node_id: node_id.to_string(),
+            entity_id,
+            field_type,
+            scale: 1.0,
+            offset: 0.0,
+            deadband: 0.0,
+        }
+    }
+
+    pub fn with_scaling(mut self, scale: f64, offset: f64) -> Self {
+        self.scale = scale;
+        self.offset = offset;
+        self
+    }
+
+    pub fn with_deadband(mut self, deadband: f64) -> Self {
+        self.deadband = deadband;
+        self
+    }
+}
+
+fn as_number(value: &Value) -> Option<f64> {
+    match value {
+        Value::Int(value) => Some(*value as f64),
+        Value::Float(value) => Some(*value),
+        Value::Bool(value) => Some(*value as u8 as f64),
+        _ => None,
+    }
+}
+
+/// Subscribes to OPC UA nodes and writes their values into mapped fields.
+///
+/// With a health entity, the connection state is reported on its `Health`
+/// and `HealthMessage` fields: Healthy while connected, Degraded while a
+/// node reports a bad status, Unhealthy while disconnected.
+///
+/// ```rust,ignore
+/// let mut ingest = OpcUaIngest::new("opc.tcp://plc1:4840")
+///     .with_health_entity(plc_id)
+///     .map(OpcUaMapping::new("ns=2;s=Pump1.Speed", pump_id, ft_speed).with_scaling(0.1, 0.0).with_deadband(0.5));
+/// loop {
+///     ingest.tick(&mut store, &mut connection, std::time::Duration::from_millis(200))?;
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct OpcUaIngest {
+    endpoint: String,
+    health_entity: Option<EntityId>,
+    mappings: Vec<OpcUaMapping>,
+    connected: bool,
+    /// Last numeric value written per mapping, for the deadband
+    last_written: HashMap<usize, f64>,
+    /// Nodes whose last value had a bad status
+    bad_nodes: Vec<String>,
+}
+
+impl OpcUaIngest {
+    pub fn new(endpoint: &str) -> Self {
+        Self {
+            endpoint: endpoint.to_string(),
+            health_entity: None,
+            mappings: Vec::new(),
+            connected: false,
+            last_written: HashMap::new(),
+            bad_nodes: Vec::new(),
+        }
+    }
+
+    /// Report the connection state on this entity's health fields
+    pub fn with_health_entity(mut self, entity_id: EntityId) -> Self {
+        self.health_entity = Some(entity_id);
+        self
+    }
+
+    pub fn map(mut self, mapping: OpcUaMapping) -> Self {
+        self.mappings.push(mapping);
+        self
+    }
+
+    pub fn mappings(&self) -> &[OpcUaMapping] {
+        &self.mappings
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.connected
+    }
+
+    fn report(&self, store: &mut impl StoreTrait, status: HealthStatus, message: &str) -> Result<()> {
+        match self.health_entity {
+            Some(entity_id) => set_health(store, entity_id, status, message),
+            None => Ok(()),
+        }
+    }
+
+    /// Connect if needed, then write the data changes received within
+    /// `timeout`. Returns the number of fields written. Connection failures
+    /// are reported on the health entity rather than returned; the next
+    /// tick connects again.
+    pub fn tick(&mut self, store: &mut impl StoreTrait, connection: &mut impl OpcUaConnection, timeout: StdDuration) -> Result<usize> {
+        if !self.connected {
+            let node_ids: Vec<String> = self.mappings.iter().map(|mapping| mapping.node_id.clone()).collect();
+            if let Err(e) = connection.connect(&self.endpoint).and_then(|_| connection.monitor(&node_ids)) {
+                log::warn!("Failed to connect to {}: {}", self.endpoint, e);
+                self.report(store, HealthStatus::Unhealthy, &format!("Failed to connect to {}: {}", self.endpoint, e))?;
+                return Ok(0);
+            }
+            self.connected = true;
+            self.bad_nodes.clear();
+            self.report(store, HealthStatus::Healthy, &format!("Connected to {}", self.endpoint))?;
+        }
+
+        let changes = match connection.poll(timeout) {
+            Ok(changes) => changes,
+            Err(e) => {
+                log::warn!("Lost connection to {}: {}", self.endpoint, e);
+                self.connected = false;
+                self.report(store, HealthStatus::Unhealthy, &format!("Lost connection to {}: {}", self.endpoint, e))?;
+                return Ok(0);
+            }
+        };
+
+        let had_bad_nodes = !self.bad_nodes.is_empty();
+        let mut written = 0;
+        for change in &changes {
+            self.bad_nodes.retain(|node_id| *node_id != change.node_id);
+            if !change.is_good() {
+                self.bad_nodes.push(change.node_id.clone());
+                continue;
+            }
+            for index in 0..self.mappings.len() {
+                if self.mappings[index].node_id != change.node_id {
+                    continue;
+                }
+                match self.apply(store, index, change) {
+                    Ok(true) => written += 1,
+                    Ok(false) => {}
+                    Err(e) => log::warn!("Failed to write {} to {:?}: {}", change.node_id, self.mappings[index].entity_id, e),
+                }
+            }
+        }
+
+        if !self.bad_nodes.is_empty() {
+            let message = format!("Bad status from {}", self.bad_nodes.join(", "));
+            self.report(store, HealthStatus::Degraded, &message)?;
+        } else if had_bad_nodes {
+            self.report(store, HealthStatus::Healthy, &format!("Connected to {}", self.endpoint))?;
+        }
+        Ok(written)
+    }
+
+    /// Write one change through one mapping. Returns false if the deadband
+    /// held it back.
+    fn apply(&mut self, store: &mut impl StoreTrait, index: usize, change: &DataChange) -> Result<bool> {
+        let mapping = &self.mappings[index];
+        let field_schema = store
+            .get_complete_entity_schema_owned(mapping.entity_id.extract_type())?
+            .fields
+            .get(&mapping.field_type)
+            .cloned()
+            .ok_or(crate::Error::FieldTypeNotFound(mapping.entity_id, mapping.field_type))?;
+
+        let scaled = as_number(&change.value).map(|raw| raw * mapping.scale + mapping.offset);
+        let value = match (&field_schema, scaled) {
+            (FieldSchema::Float { .. }, Some(scaled)) => Value::Float(scaled),
+            (FieldSchema::Int { .. }, Some(scaled)) => Value::Int(scaled.round() as i64),
+            (FieldSchema::Bool { .. }, Some(scaled)) => Value::Bool(scaled != 0.0),
+            _ => change.value.clone(),
+        };
+
+        if let Some(scaled) = scaled {
+            let within_deadband = self
+                .last_written
+                .get(&index)
+                .is_some_and(|last| (scaled - last).abs() < mapping.deadband);
+            if within_deadband {
+                return Ok(false);
+            }
+            self.last_written.insert(index, scaled);
+        }
+
+        store.write(mapping.entity_id, &[mapping.field_type], value, None, change.source_timestamp, Some(PushCondition::Changes), None)?;
+        Ok(true)
+    }
+}
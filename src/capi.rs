@@ -0,0 +1,519 @@
+//! C interface to `StoreProxy`, for controllers that aren't written in Rust.
+//!
+//! Build the library with
+//! `cargo rustc --release --features capi --crate-type staticlib` (or
+//! `cdylib`) and include `include/qlib.h`, which cbindgen generates from
+//! this module (`cbindgen --config cbindgen.toml -o include/qlib.h`).
+//!
+//! Handles are opaque. Ownership follows these rules:
+//!
+//! - A non-const pointer returned by a `qlib_*` function is owned by the
+//!   caller and released with the matching `_free` (`qlib_disconnect` for
+//!   stores, `qlib_unsubscribe` for subscriptions).
+//! - A const pointer is borrowed from the handle it came from and is valid
+//!   until that handle is released.
+//! - Pointer arguments are only borrowed for the duration of the call.
+//!   Strings are NUL-terminated UTF-8.
+//! - A handle may be used from any thread, but only one at a time.
+//!
+//! Functions that can fail return a `QlibStatus`, or NULL for those that
+//! return a handle. `qlib_last_error` then describes the failure; it
+//! belongs to the calling thread and is valid until its next failed call.
+#![allow(clippy::missing_safety_doc)]
+
+use std::cell::RefCell;
+use std::ffi::{c_char, CStr, CString};
+use std::ptr;
+
+use crossbeam::channel::{unbounded, Receiver, Sender};
+
+use crate::{parse_field_path, EntityId, Notification, NotifyConfig, Result, StoreProxy, Value};
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(message: String) {
+    let message = CString::new(message).unwrap_or_else(|e| {
+        let nul = e.nul_position();
+        CString::new(&e.into_vec()[..nul]).unwrap_or_default()
+    });
+    LAST_ERROR.with(|last_error| *last_error.borrow_mut() = Some(message));
+}
+
+/// Outcome of a call. On anything but `Ok`, see `qlib_last_error`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QlibStatus {
+    Ok = 0,
+    /// A required pointer was NULL, a string wasn't UTF-8, or a value was
+    /// read as the wrong kind
+    InvalidArgument = 1,
+    /// The store rejected the request or the connection failed
+    Error = 2,
+}
+
+/// The variant of a `QlibValue`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QlibValueKind {
+    Blob,
+    Bool,
+    Choice,
+    EntityList,
+    EntityReference,
+    Float,
+    Int,
+    String,
+    Timestamp,
+    Decimal,
+    Duration,
+    StringList,
+    Map,
+}
+
+/// A connection to a qlib server
+pub struct QlibStore(StoreProxy);
+
+/// A field value
+pub struct QlibValue(Value);
+
+/// Notifications for one `NotifyConfig`, queued by
+/// `qlib_process_notifications`
+pub struct QlibSubscription {
+    config: NotifyConfig,
+    sender: Sender<Notification>,
+    receiver: Receiver<Notification>,
+}
+
+/// A write to a subscribed field
+pub struct QlibNotification {
+    entity_id: EntityId,
+    value: Option<QlibValue>,
+    previous: Option<QlibValue>,
+}
+
+fn invalid_argument(message: &str) -> QlibStatus {
+    set_last_error(message.to_string());
+    QlibStatus::InvalidArgument
+}
+
+fn status(result: Result<()>) -> QlibStatus {
+    match result {
+        Ok(()) => QlibStatus::Ok,
+        Err(e) => {
+            set_last_error(e.to_string());
+            QlibStatus::Error
+        }
+    }
+}
+
+/// The string behind `ptr`, or an InvalidArgument error naming `argument`
+unsafe fn str_arg<'a>(ptr: *const c_char, argument: &str) -> std::result::Result<&'a str, QlibStatus> {
+    if ptr.is_null() {
+        return Err(invalid_argument(&format!("{} is NULL", argument)));
+    }
+    CStr::from_ptr(ptr).to_str().map_err(|_| invalid_argument(&format!("{} isn't UTF-8", argument)))
+}
+
+macro_rules! try_arg {
+    ($expr:expr) => {
+        match $expr {
+            Ok(value) => value,
+            Err(status) => return status,
+        }
+    };
+}
+
+macro_rules! non_null {
+    ($ptr:expr, $name:literal) => {
+        match $ptr.as_ref() {
+            Some(value) => value,
+            None => return invalid_argument(concat!($name, " is NULL")),
+        }
+    };
+}
+
+macro_rules! non_null_mut {
+    ($ptr:expr, $name:literal) => {
+        match $ptr.as_mut() {
+            Some(value) => value,
+            None => return invalid_argument(concat!($name, " is NULL")),
+        }
+    };
+}
+
+/// Describes the last failure on this thread, or NULL if nothing failed
+#[no_mangle]
+pub extern "C" fn qlib_last_error() -> *const c_char {
+    LAST_ERROR.with(|last_error| last_error.borrow().as_ref().map_or(ptr::null(), |message| message.as_ptr()))
+}
+
+/// Connect to the server at `address`, e.g. "127.0.0.1:8080"
+#[no_mangle]
+pub unsafe extern "C" fn qlib_connect(address: *const c_char) -> *mut QlibStore {
+    let address = match str_arg(address, "address") {
+        Ok(address) => address,
+        Err(_) => return ptr::null_mut(),
+    };
+    match StoreProxy::connect(address) {
+        Ok(proxy) => Box::into_raw(Box::new(QlibStore(proxy))),
+        Err(e) => {
+            set_last_error(e.to_string());
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Close the connection and release the store
+#[no_mangle]
+pub unsafe extern "C" fn qlib_disconnect(store: *mut QlibStore) {
+    if !store.is_null() {
+        let _ = Box::from_raw(store).0.close();
+    }
+}
+
+/// The entity at a path such as "Root/Plant/Pump1"
+#[no_mangle]
+pub unsafe extern "C" fn qlib_resolve_path(store: *const QlibStore, path: *const c_char, out_entity_id: *mut u64) -> QlibStatus {
+    let store = non_null!(store, "store");
+    let path = try_arg!(str_arg(path, "path"));
+    let out_entity_id = non_null_mut!(out_entity_id, "out_entity_id");
+    status(store.0.resolve_path(path).map(|entity_id| *out_entity_id = entity_id.0))
+}
+
+/// Read a field, following indirection such as "Parent->Name". The value
+/// is stored in `out_value` and released with `qlib_value_free`.
+#[no_mangle]
+pub unsafe extern "C" fn qlib_read(store: *const QlibStore, entity_id: u64, field: *const c_char, out_value: *mut *mut QlibValue) -> QlibStatus {
+    let store = non_null!(store, "store");
+    let field = try_arg!(str_arg(field, "field"));
+    let out_value = non_null_mut!(out_value, "out_value");
+    status(parse_field_path(&store.0, field).and_then(|field_path| store.0.read(EntityId(entity_id), &field_path)).map(|(value, _, _)| {
+        *out_value = Box::into_raw(Box::new(QlibValue(value)));
+    }))
+}
+
+/// Write a field. `value` is borrowed.
+#[no_mangle]
+pub unsafe extern "C" fn qlib_write(store: *const QlibStore, entity_id: u64, field: *const c_char, value: *const QlibValue) -> QlibStatus {
+    let store = non_null!(store, "store");
+    let field = try_arg!(str_arg(field, "field"));
+    let value = non_null!(value, "value");
+    status(parse_field_path(&store.0, field).and_then(|field_path| {
+        store.0.write(EntityId(entity_id), &field_path, value.0.clone(), None, None, None, None)
+    }))
+}
+
+/// The entities of a type, optionally matching a CEL `filter` (may be
+/// NULL). The ids are stored in `out_entity_ids` and released with
+/// `qlib_entity_ids_free`.
+#[no_mangle]
+pub unsafe extern "C" fn qlib_find_entities(
+    store: *const QlibStore,
+    entity_type: *const c_char,
+    filter: *const c_char,
+    out_entity_ids: *mut *mut u64,
+    out_len: *mut usize,
+) -> QlibStatus {
+    let store = non_null!(store, "store");
+    let entity_type = try_arg!(str_arg(entity_type, "entity_type"));
+    let filter = match filter.is_null() {
+        true => None,
+        false => Some(try_arg!(str_arg(filter, "filter"))),
+    };
+    let out_entity_ids = non_null_mut!(out_entity_ids, "out_entity_ids");
+    let out_len = non_null_mut!(out_len, "out_len");
+    let entity_ids = store.0.get_entity_type(entity_type).and_then(|entity_type| store.0.find_entities(entity_type, filter));
+    status(entity_ids.map(|entity_ids| {
+        let entity_ids: Box<[u64]> = entity_ids.into_iter().map(|entity_id| entity_id.0).collect();
+        *out_len = entity_ids.len();
+        *out_entity_ids = Box::into_raw(entity_ids) as *mut u64;
+    }))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn qlib_entity_ids_free(entity_ids: *mut u64, len: usize) {
+    if !entity_ids.is_null() {
+        drop(Box::from_raw(ptr::slice_from_raw_parts_mut(entity_ids, len)));
+    }
+}
+
+unsafe fn subscribe(store: *const QlibStore, config: impl FnOnce(&StoreProxy) -> Result<NotifyConfig>) -> *mut QlibSubscription {
+    let Some(store) = store.as_ref() else {
+        invalid_argument("store is NULL");
+        return ptr::null_mut();
+    };
+    let (sender, receiver) = unbounded();
+    let registered = config(&store.0).and_then(|config| store.0.register_notification(config.clone(), sender.clone()).map(|_| config));
+    match registered {
+        Ok(config) => Box::into_raw(Box::new(QlibSubscription { config, sender, receiver })),
+        Err(e) => {
+            set_last_error(e.to_string());
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Subscribe to writes of `field` on one entity. With `trigger_on_change`,
+/// writes that don't change the value aren't notified.
+#[no_mangle]
+pub unsafe extern "C" fn qlib_subscribe(store: *const QlibStore, entity_id: u64, field: *const c_char, trigger_on_change: bool) -> *mut QlibSubscription {
+    let Ok(field) = str_arg(field, "field") else {
+        return ptr::null_mut();
+    };
+    subscribe(store, |proxy| {
+        Ok(NotifyConfig::EntityId { entity_id: EntityId(entity_id), field_type: proxy.get_field_type(field)?, trigger_on_change, context: Vec::new(), filter: None })
+    })
+}
+
+/// Subscribe to writes of `field` on every entity of a type
+#[no_mangle]
+pub unsafe extern "C" fn qlib_subscribe_type(
+    store: *const QlibStore,
+    entity_type: *const c_char,
+    field: *const c_char,
+    trigger_on_change: bool,
+) -> *mut QlibSubscription {
+    let (Ok(entity_type), Ok(field)) = (str_arg(entity_type, "entity_type"), str_arg(field, "field")) else {
+        return ptr::null_mut();
+    };
+    subscribe(store, |proxy| {
+        Ok(NotifyConfig::EntityType {
+            entity_type: proxy.get_entity_type(entity_type)?,
+            field_type: proxy.get_field_type(field)?,
+            trigger_on_change,
+            context: Vec::new(),
+            filter: None,
+        })
+    })
+}
+
+/// Unregister and release a subscription. `store` must be the store it
+/// was created on.
+#[no_mangle]
+pub unsafe extern "C" fn qlib_unsubscribe(store: *const QlibStore, subscription: *mut QlibSubscription) {
+    if subscription.is_null() {
+        return;
+    }
+    let subscription = Box::from_raw(subscription);
+    if let Some(store) = store.as_ref() {
+        store.0.unregister_notification(&subscription.config, &subscription.sender);
+    }
+}
+
+/// Read notifications that arrived from the server into their
+/// subscriptions
+#[no_mangle]
+pub unsafe extern "C" fn qlib_process_notifications(store: *const QlibStore) -> QlibStatus {
+    let store = non_null!(store, "store");
+    status(store.0.process_notifications())
+}
+
+/// The oldest queued notification, or NULL if there is none. Release it
+/// with `qlib_notification_free`.
+#[no_mangle]
+pub unsafe extern "C" fn qlib_subscription_next(subscription: *const QlibSubscription) -> *mut QlibNotification {
+    let Some(subscription) = subscription.as_ref() else {
+        return ptr::null_mut();
+    };
+    match subscription.receiver.try_recv() {
+        Ok(notification) => Box::into_raw(Box::new(QlibNotification {
+            entity_id: notification.current.entity_id,
+            value: notification.current.value.map(QlibValue),
+            previous: notification.previous.value.map(QlibValue),
+        })),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn qlib_notification_entity_id(notification: *const QlibNotification) -> u64 {
+    notification.as_ref().map_or(0, |notification| notification.entity_id.0)
+}
+
+/// The written value, or NULL if it couldn't be read
+#[no_mangle]
+pub unsafe extern "C" fn qlib_notification_value(notification: *const QlibNotification) -> *const QlibValue {
+    notification.as_ref().and_then(|notification| notification.value.as_ref()).map_or(ptr::null(), |value| value as *const _)
+}
+
+/// The value before the write, or NULL if there was none
+#[no_mangle]
+pub unsafe extern "C" fn qlib_notification_previous(notification: *const QlibNotification) -> *const QlibValue {
+    notification.as_ref().and_then(|notification| notification.previous.as_ref()).map_or(ptr::null(), |value| value as *const _)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn qlib_notification_free(notification: *mut QlibNotification) {
+    if !notification.is_null() {
+        drop(Box::from_raw(notification));
+    }
+}
+
+fn new_value(value: Value) -> *mut QlibValue {
+    Box::into_raw(Box::new(QlibValue(value)))
+}
+
+#[no_mangle]
+pub extern "C" fn qlib_value_bool(value: bool) -> *mut QlibValue {
+    new_value(Value::Bool(value))
+}
+
+#[no_mangle]
+pub extern "C" fn qlib_value_int(value: i64) -> *mut QlibValue {
+    new_value(Value::Int(value))
+}
+
+#[no_mangle]
+pub extern "C" fn qlib_value_float(value: f64) -> *mut QlibValue {
+    new_value(Value::Float(value))
+}
+
+#[no_mangle]
+pub extern "C" fn qlib_value_choice(value: i64) -> *mut QlibValue {
+    new_value(Value::Choice(value))
+}
+
+/// A reference to `entity_id`, or an empty reference if it is 0
+#[no_mangle]
+pub extern "C" fn qlib_value_entity_reference(entity_id: u64) -> *mut QlibValue {
+    new_value(Value::EntityReference((entity_id != 0).then_some(EntityId(entity_id))))
+}
+
+/// A timestamp, in nanoseconds since the Unix epoch
+#[no_mangle]
+pub extern "C" fn qlib_value_timestamp(unix_nanos: i64) -> *mut QlibValue {
+    new_value(Value::Timestamp(crate::epoch() + crate::Duration::nanoseconds(unix_nanos)))
+}
+
+/// NULL if `value` is NULL or isn't UTF-8
+#[no_mangle]
+pub unsafe extern "C" fn qlib_value_string(value: *const c_char) -> *mut QlibValue {
+    match str_arg(value, "value") {
+        Ok(value) => new_value(Value::String(value.to_string())),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+/// Copies `len` bytes from `data`
+#[no_mangle]
+pub unsafe extern "C" fn qlib_value_blob(data: *const u8, len: usize) -> *mut QlibValue {
+    match (data.is_null(), len) {
+        (_, 0) => new_value(Value::Blob(Vec::new())),
+        (true, _) => {
+            invalid_argument("data is NULL");
+            ptr::null_mut()
+        }
+        (false, _) => new_value(Value::Blob(std::slice::from_raw_parts(data, len).to_vec())),
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn qlib_value_free(value: *mut QlibValue) {
+    if !value.is_null() {
+        drop(Box::from_raw(value));
+    }
+}
+
+/// `value` must not be NULL
+#[no_mangle]
+pub unsafe extern "C" fn qlib_value_kind(value: *const QlibValue) -> QlibValueKind {
+    match &value.as_ref().expect("value is NULL").0 {
+        Value::Blob(_) => QlibValueKind::Blob,
+        Value::Bool(_) => QlibValueKind::Bool,
+        Value::Choice(_) => QlibValueKind::Choice,
+        Value::EntityList(_) => QlibValueKind::EntityList,
+        Value::EntityReference(_) => QlibValueKind::EntityReference,
+        Value::Float(_) => QlibValueKind::Float,
+        Value::Int(_) => QlibValueKind::Int,
+        Value::String(_) => QlibValueKind::String,
+        Value::Timestamp(_) => QlibValueKind::Timestamp,
+        Value::Decimal(_) => QlibValueKind::Decimal,
+        Value::Duration(_) => QlibValueKind::Duration,
+        Value::StringList(_) => QlibValueKind::StringList,
+        Value::Map(_) => QlibValueKind::Map,
+    }
+}
+
+/// Store the value behind `value` in `out` if `get` accepts its kind
+unsafe fn get<T>(value: *const QlibValue, out: *mut T, kind: &str, get: impl FnOnce(&Value) -> Option<T>) -> QlibStatus {
+    let value = non_null!(value, "value");
+    let out = non_null_mut!(out, "out");
+    match get(&value.0) {
+        Some(v) => {
+            *out = v;
+            QlibStatus::Ok
+        }
+        None => invalid_argument(&format!("value isn't {}", kind)),
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn qlib_value_get_bool(value: *const QlibValue, out: *mut bool) -> QlibStatus {
+    get(value, out, "a Bool", Value::as_bool)
+}
+
+/// Accepts Int and Choice values
+#[no_mangle]
+pub unsafe extern "C" fn qlib_value_get_int(value: *const QlibValue, out: *mut i64) -> QlibStatus {
+    get(value, out, "an Int or Choice", |value| value.as_int().or(value.as_choice()))
+}
+
+/// Accepts Float and Int values
+#[no_mangle]
+pub unsafe extern "C" fn qlib_value_get_float(value: *const QlibValue, out: *mut f64) -> QlibStatus {
+    get(value, out, "a Float or Int", |value| value.as_float().or(value.as_int().map(|v| v as f64)))
+}
+
+/// Stores 0 for an empty reference
+#[no_mangle]
+pub unsafe extern "C" fn qlib_value_get_entity_reference(value: *const QlibValue, out: *mut u64) -> QlibStatus {
+    get(value, out, "an EntityReference", |value| value.as_entity_reference().map(|entity_id| entity_id.map_or(0, |entity_id| entity_id.0)))
+}
+
+/// Nanoseconds since the Unix epoch
+#[no_mangle]
+pub unsafe extern "C" fn qlib_value_get_timestamp(value: *const QlibValue, out: *mut i64) -> QlibStatus {
+    get(value, out, "a Timestamp", |value| value.as_timestamp().map(|timestamp| (timestamp - crate::epoch()).whole_nanoseconds() as i64))
+}
+
+/// A copy of a String value, released with `qlib_string_free`. NULL if the
+/// value isn't a String.
+#[no_mangle]
+pub unsafe extern "C" fn qlib_value_get_string(value: *const QlibValue) -> *mut c_char {
+    match value.as_ref().and_then(|value| value.0.as_string()).map(CString::new) {
+        Some(Ok(value)) => value.into_raw(),
+        Some(Err(_)) => {
+            invalid_argument("value contains a NUL byte");
+            ptr::null_mut()
+        }
+        None => {
+            invalid_argument("value isn't a String");
+            ptr::null_mut()
+        }
+    }
+}
+
+/// The bytes of a Blob value, borrowed from `value`. NULL if the value
+/// isn't a Blob.
+#[no_mangle]
+pub unsafe extern "C" fn qlib_value_get_blob(value: *const QlibValue, out_len: *mut usize) -> *const u8 {
+    match (value.as_ref().and_then(|value| value.0.as_blob()), out_len.as_mut()) {
+        (Some(blob), Some(out_len)) => {
+            *out_len = blob.len();
+            blob.as_ptr()
+        }
+        _ => {
+            invalid_argument("value isn't a Blob");
+            ptr::null()
+        }
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn qlib_string_free(value: *mut c_char) {
+    if !value.is_null() {
+        drop(CString::from_raw(value));
+    }
+}
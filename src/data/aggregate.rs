@@ -0,0 +1,146 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{Decimal, Duration, Error, Result, Value};
+
+/// How `StoreTrait::aggregate` combines a field across entities
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AggregateOp {
+    /// Number of entities matched, as an Int
+    Count,
+    /// Total of an Int, Float, Decimal or Duration field
+    Sum,
+    /// Smallest value of a numeric, Timestamp or String field
+    Min,
+    /// Largest value of a numeric, Timestamp or String field
+    Max,
+}
+
+impl AggregateOp {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AggregateOp::Count => "Count",
+            AggregateOp::Sum => "Sum",
+            AggregateOp::Min => "Min",
+            AggregateOp::Max => "Max",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "Count" => Some(AggregateOp::Count),
+            "Sum" => Some(AggregateOp::Sum),
+            "Min" => Some(AggregateOp::Min),
+            "Max" => Some(AggregateOp::Max),
+            _ => None,
+        }
+    }
+}
+
+/// Running result of an aggregation, fed one value per entity
+#[derive(Debug, Clone)]
+pub struct Aggregator {
+    op: AggregateOp,
+    count: i64,
+    result: Option<Value>,
+}
+
+impl Aggregator {
+    /// `zero` is what a Sum over no entities returns, normally the field's
+    /// default value reset to zero (see `zero_of`)
+    pub fn new(op: AggregateOp, zero: Option<Value>) -> Self {
+        let result = match op {
+            AggregateOp::Sum => zero,
+            _ => None,
+        };
+        Self { op, count: 0, result }
+    }
+
+    pub fn add(&mut self, value: Value) -> Result<()> {
+        self.count += 1;
+        let result = match (self.op, self.result.take()) {
+            (AggregateOp::Count, _) => None,
+            (_, None) => Some(Self::check_type(self.op, value)?),
+            (AggregateOp::Sum, Some(total)) => Some(sum(total, value)?),
+            (AggregateOp::Min, Some(min)) => Some(if less_than(&value, &min)? { value } else { min }),
+            (AggregateOp::Max, Some(max)) => Some(if less_than(&max, &value)? { value } else { max }),
+        };
+        self.result = result;
+        Ok(())
+    }
+
+    /// The aggregate, or None for a Min/Max over no entities
+    pub fn finish(self) -> Option<Value> {
+        match self.op {
+            AggregateOp::Count => Some(Value::Int(self.count)),
+            _ => self.result,
+        }
+    }
+
+    fn check_type(op: AggregateOp, value: Value) -> Result<Value> {
+        let supported = match op {
+            AggregateOp::Count => true,
+            AggregateOp::Sum => matches!(value, Value::Int(_) | Value::Float(_) | Value::Decimal(_) | Value::Duration(_)),
+            AggregateOp::Min | AggregateOp::Max => matches!(
+                value,
+                Value::Int(_) | Value::Float(_) | Value::Decimal(_) | Value::Duration(_) | Value::Timestamp(_) | Value::String(_)
+            ),
+        };
+        if supported {
+            Ok(value)
+        } else {
+            Err(Error::InvalidRequest(format!("Can't {} a {} field", op.as_str(), type_name(&value))))
+        }
+    }
+}
+
+/// The zero of a summable value's type, e.g. `Int(0)` for an Int field
+pub fn zero_of(value: &Value) -> Option<Value> {
+    match value {
+        Value::Int(_) => Some(Value::Int(0)),
+        Value::Float(_) => Some(Value::Float(0.0)),
+        Value::Decimal(_) => Some(Value::Decimal(Decimal::from_int(0))),
+        Value::Duration(_) => Some(Value::Duration(Duration::ZERO)),
+        _ => None,
+    }
+}
+
+fn sum(total: Value, value: Value) -> Result<Value> {
+    let overflow = || Error::InvalidRequest("Sum overflowed".to_string());
+    match (total, value) {
+        (Value::Int(a), Value::Int(b)) => a.checked_add(b).map(Value::Int).ok_or_else(overflow),
+        (Value::Float(a), Value::Float(b)) => Ok(Value::Float(a + b)),
+        (Value::Decimal(a), Value::Decimal(b)) => a.checked_add(&b).map(Value::Decimal).ok_or_else(overflow),
+        (Value::Duration(a), Value::Duration(b)) => a.checked_add(b).map(Value::Duration).ok_or_else(overflow),
+        (total, value) => Err(mismatch(&total, &value)),
+    }
+}
+
+fn less_than(a: &Value, b: &Value) -> Result<bool> {
+    if std::mem::discriminant(a) != std::mem::discriminant(b) {
+        return Err(mismatch(a, b));
+    }
+    // NaN compares as neither smaller nor larger
+    Ok(a.partial_cmp(b).is_some_and(|ordering| ordering.is_lt()))
+}
+
+fn mismatch(a: &Value, b: &Value) -> Error {
+    Error::InvalidRequest(format!("Can't aggregate a {} with a {}", type_name(a), type_name(b)))
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Blob(_) => "Blob",
+        Value::Bool(_) => "Bool",
+        Value::Choice(_) => "Choice",
+        Value::EntityList(_) => "EntityList",
+        Value::EntityReference(_) => "EntityReference",
+        Value::Float(_) => "Float",
+        Value::Int(_) => "Int",
+        Value::String(_) => "String",
+        Value::Timestamp(_) => "Timestamp",
+        Value::Decimal(_) => "Decimal",
+        Value::Duration(_) => "Duration",
+        Value::StringList(_) => "StringList",
+        Value::Map(_) => "Map",
+    }
+}
@@ -1,18 +1,43 @@
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use std::sync::Arc;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use std::task::{Context, Poll};
+use futures_util::{Stream, StreamExt};
+use rustc_hash::FxHashMap;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
 use tokio::net::TcpStream;
-use tokio::sync::Mutex;
+use tokio::sync::{mpsc, oneshot, Mutex, MutexGuard, Notify};
+use tokio::task::JoinHandle;
 
 use crate::{
-    Complete, EntityId, EntitySchema, EntityType, Error, FieldSchema, FieldType, PageOpts, PageResult, Result, Single, Value, Timestamp, PushCondition, AdjustBehavior
+    AggregateOp, AsyncStoreTrait, Complete, EntityId, EntitySchema, EntityType, Error, FieldSchema, FieldType, Notification, NotifyConfig, PageOpts, PageResult, Result, Single, TypesBulk, Value, Timestamp, PushCondition, AdjustBehavior
 };
-use crate::data::resp::{RespCommand, RespDecode, ReadCommand, WriteCommand, CreateEntityCommand, RespValue, RespToBytes, RespFromBytes, NotificationCommand};
+use crate::data::buffer_pool::take_buffer;
+use crate::data::codec::Codec;
+use crate::data::resp::{decode_tagged, encode_tagged, error_from_frame, with_binary_ids, CodecCommand, ProtocolLimits, RespCommand, RespDecode, ReadCommand, WriteCommand, CreateEntityCommand, RespValue, RespToBytes, RespFromBytes, DurableNotificationCommand, NotificationCommand, PushCommand};
+
+/// Chunk size used by callers of the blob streaming helpers; well under
+/// `MAX_MESSAGE_SIZE` so one chunk never dominates a connection
+pub const DEFAULT_BLOB_CHUNK_SIZE: usize = 1024 * 1024;
+
+/// Bytes read from the socket at a time
+const READ_CHUNK_SIZE: usize = 65536;
+
+/// How much longer than a request's timeout the client waits for the server
+/// to report the timeout itself before giving up on the reply
+pub const TIMEOUT_GRACE: std::time::Duration = std::time::Duration::from_millis(500);
+
+/// Notifications a `NotificationStream` buffers before newer ones are dropped
+pub const DEFAULT_NOTIFICATION_STREAM_CAPACITY: usize = 1024;
+
+/// How long `AsyncStoreProxy::shutdown` waits for requests still in flight
+pub const SHUTDOWN_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
 
 /// Expect an OK response from RESP
 fn expect_ok(resp_value: RespValue) -> Result<()> {
     match resp_value {
         RespValue::SimpleString(s) if s == "OK" => Ok(()),
-        RespValue::Error(msg) => Err(Error::StoreProxyError(format!("Server error: {}", msg))),
+        RespValue::Error(msg) => Err(error_from_frame(msg)),
         _ => Err(Error::StoreProxyError("Expected OK response".to_string())),
     }
 }
@@ -22,39 +47,175 @@ fn expect_ok(resp_value: RespValue) -> Result<()> {
 pub struct AsyncTcpConnection {
     stream: TcpStream,
     pub(crate) read_buffer: Vec<u8>,
+    pub(crate) limits: ProtocolLimits,
+    /// Replies the server still owes. Nonzero when the connection is free
+    /// means a command gave up waiting (e.g. timed out), and its reply must
+    /// be skipped when it arrives.
+    pub(crate) unanswered: usize,
 }
 
 impl AsyncTcpConnection {
-    pub fn new(stream: TcpStream) -> Self {
+    pub fn new(stream: TcpStream, limits: ProtocolLimits) -> Self {
         Self {
             stream,
             read_buffer: Vec::new(),
+            limits,
+            unanswered: 0,
         }
     }
     
-    pub async fn send_bytes(&mut self, data: &[u8]) -> anyhow::Result<()> {
-        self.stream.write_all(data).await?;
-        self.stream.flush().await?;
+    pub async fn send_bytes(&mut self, data: &[u8]) -> Result<()> {
+        let map_send_error = |e: std::io::Error| match e.kind() {
+            std::io::ErrorKind::ConnectionAborted |
+            std::io::ErrorKind::ConnectionReset |
+            std::io::ErrorKind::BrokenPipe => Error::ConnectionLost,
+            _ => Error::StoreProxyError(format!("Failed to send data: {}", e)),
+        };
+        self.stream.write_all(data).await.map_err(map_send_error)?;
+        self.stream.flush().await.map_err(map_send_error)?;
         Ok(())
     }
     
-    pub async fn read_bytes(&mut self) -> anyhow::Result<()> {
-        let mut buffer = [0u8; 65536];
-        match self.stream.read(&mut buffer).await {
-            Ok(0) => return Err(anyhow::anyhow!("Connection closed")),
-            Ok(bytes_read) => {
-                self.read_buffer.extend_from_slice(&buffer[..bytes_read]);
-                Ok(())
+    pub async fn read_bytes(&mut self) -> Result<()> {
+        // Read straight into the buffer so the future doesn't carry a copy of it
+        self.read_buffer.reserve(READ_CHUNK_SIZE);
+        match self.stream.read_buf(&mut self.read_buffer).await {
+            Ok(0) => Err(Error::ConnectionLost),
+            Ok(_) => {
+                // Only called while the frame at the head of the buffer is incomplete,
+                // so the buffer length is a lower bound on that frame's size
+                self.limits.check_frame(self.read_buffer.len())
             }
-            Err(e) => Err(anyhow::anyhow!("TCP read error: {}", e)),
+            Err(e) => Err(Error::StoreProxyError(format!("TCP read error: {}", e))),
+        }
+    }
+
+    /// Close the sending side of the socket, telling the server no more
+    /// commands follow
+    pub async fn shutdown(&mut self) -> Result<()> {
+        match self.stream.shutdown().await {
+            Err(e) if e.kind() != std::io::ErrorKind::NotConnected => Err(Error::StoreProxyError(format!("Failed to close connection: {}", e))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Wait until the socket has data to read
+    pub(crate) async fn readable(&self) -> Result<()> {
+        self.stream
+            .readable()
+            .await
+            .map_err(|e| Error::StoreProxyError(format!("TCP read error: {}", e)))
+    }
+
+    /// Read whatever the socket has without waiting for more
+    pub(crate) fn try_read_bytes(&mut self) -> Result<()> {
+        self.read_buffer.reserve(READ_CHUNK_SIZE);
+        match self.stream.try_read_buf(&mut self.read_buffer) {
+            Ok(0) => Err(Error::ConnectionLost),
+            Ok(_) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => Ok(()),
+            Err(e) => Err(Error::StoreProxyError(format!("TCP read error: {}", e))),
         }
     }
 }
 
+/// The sending side of one `NotificationStream`
+#[derive(Debug)]
+struct StreamSender {
+    id: u64,
+    sender: mpsc::Sender<Notification>,
+    dropped: Arc<AtomicU64>,
+}
+
+/// Notifications for one registration made with
+/// `AsyncStoreProxy::register_notification_stream`.
+///
+/// The stream ends when the connection is lost. If the consumer falls more
+/// than the stream's capacity behind, new notifications are dropped and
+/// counted by `dropped()`.
+#[derive(Debug)]
+pub struct NotificationStream {
+    id: u64,
+    config: NotifyConfig,
+    receiver: mpsc::Receiver<Notification>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl NotificationStream {
+    /// The registration this stream delivers
+    pub fn config(&self) -> &NotifyConfig {
+        &self.config
+    }
+
+    /// Notifications dropped because the stream was full
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Number of notifications waiting to be read
+    pub fn lag(&self) -> usize {
+        self.receiver.len()
+    }
+}
+
+impl Stream for NotificationStream {
+    type Item = Notification;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Notification>> {
+        self.receiver.poll_recv(cx)
+    }
+}
+
+/// Notification streams by config hash, along with the config itself
+type StreamMap = FxHashMap<u64, (NotifyConfig, Vec<StreamSender>)>;
+
+/// Durable subscription senders by subscription id, along with the sequence
+/// number of the last notification delivered
+type DurableMap = FxHashMap<String, (u64, mpsc::Sender<(u64, Notification)>)>;
+
+/// Requests sent tagged with a correlation id, waiting for their reply frame
+type InFlightMap = FxHashMap<u64, oneshot::Sender<Vec<u8>>>;
+
+/// Forgets a tagged request once its caller stops waiting, e.g. on timeout,
+/// so a late reply is dropped
+struct InFlightGuard<'p> {
+    in_flight: &'p std::sync::Mutex<InFlightMap>,
+    correlation_id: u64,
+}
+
+impl Drop for InFlightGuard<'_> {
+    fn drop(&mut self) {
+        self.in_flight.lock().unwrap().remove(&self.correlation_id);
+    }
+}
+
 /// Async version of StoreProxy
+///
+/// Notifications can be consumed as streams (see `register_notification_stream`).
+/// While any stream is open, a background task holds the connection between
+/// commands and hands pushed notifications to the streams; commands take the
+/// connection back from it as needed.
+///
+/// A proxy made with `connect_multiplexed` tags every command with a
+/// correlation id. Commands only hold the connection while sending, the
+/// background task reads the replies and hands each to its request, so many
+/// requests can be in flight at once and their replies may arrive in any order.
 #[derive(Debug, Clone)]
 pub struct AsyncStoreProxy {
     pub(crate) tcp_connection: Arc<Mutex<AsyncTcpConnection>>,
+    notification_streams: Arc<std::sync::Mutex<StreamMap>>,
+    durable_streams: Arc<std::sync::Mutex<DurableMap>>,
+    next_stream_id: Arc<AtomicU64>,
+    multiplexed: bool,
+    in_flight: Arc<std::sync::Mutex<InFlightMap>>,
+    next_correlation_id: Arc<AtomicU64>,
+    reader_task: Arc<std::sync::Mutex<Option<JoinHandle<()>>>>,
+    /// Commands waiting for the connection, and the signal that tells the
+    /// notification task to let go of it
+    waiting_commands: Arc<AtomicUsize>,
+    release_connection: Arc<Notify>,
+    /// Codec the server agreed to, see `negotiate_codec`
+    codec: Arc<std::sync::Mutex<Codec>>,
 }
 
 impl AsyncStoreProxy {
@@ -63,14 +224,201 @@ impl AsyncStoreProxy {
         crate::data::pipeline::AsyncPipeline::new(self)
     }
 
+    /// Check if commands are tagged with correlation ids
+    pub fn is_multiplexed(&self) -> bool {
+        self.multiplexed
+    }
+
+    /// Take the connection for a command, asking the reader task to
+    /// hand it over if it is waiting on the socket
+    pub(crate) async fn connection(&self) -> Result<MutexGuard<'_, AsyncTcpConnection>> {
+        self.waiting_commands.fetch_add(1, Ordering::AcqRel);
+        self.release_connection.notify_one();
+        let mut conn = self.tcp_connection.lock().await;
+        self.waiting_commands.fetch_sub(1, Ordering::AcqRel);
+        self.skip_abandoned_replies(&mut conn).await?;
+        Ok(conn)
+    }
+
+    /// Read and discard the replies of commands that stopped waiting for them
+    async fn skip_abandoned_replies(&self, conn: &mut AsyncTcpConnection) -> Result<()> {
+        while conn.unanswered > 0 {
+            match RespValue::from_bytes(&conn.read_buffer) {
+                Ok((resp_value, remaining)) => {
+                    let consumed = conn.read_buffer.len() - remaining.len();
+                    if self.route_tagged_reply(&resp_value) {
+                        // Not one of the untagged replies being skipped
+                    } else if let Ok(push) = PushCommand::decode(resp_value) {
+                        self.handle_push(push);
+                    } else {
+                        conn.unanswered -= 1;
+                    }
+                    conn.read_buffer.drain(..consumed);
+                }
+                Err(e @ Error::ProtocolError(_)) => return Err(e),
+                Err(_) => conn.read_bytes().await?,
+            }
+        }
+        Ok(())
+    }
+
     /// Handle a notification command received from the server
-    pub(crate) fn handle_notification(&self, _notification_cmd: NotificationCommand) {
-        // TODO: Implement notification handling for async proxy
-        // For now, notifications are ignored in async mode
+    pub(crate) fn handle_notification(&self, notification_cmd: NotificationCommand) {
+        let notification = notification_cmd.notification;
+
+        let mut streams = self.notification_streams.lock().unwrap();
+        if let Some((_config, senders)) = streams.get_mut(&notification.config_hash) {
+            senders.retain(|stream| !stream.sender.is_closed());
+            for stream in senders.iter() {
+                if stream.sender.try_send(notification.clone()).is_err() {
+                    stream.dropped.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+        }
+    }
+
+    /// Handle a notification for a durable subscription, skipping any the
+    /// receiver already got (a replay can overlap notifications received live)
+    pub(crate) fn handle_durable_notification(&self, command: DurableNotificationCommand) {
+        let mut streams = self.durable_streams.lock().unwrap();
+        if let Some((last_seq, sender)) = streams.get_mut(&command.subscription_id) {
+            if command.seq > *last_seq && sender.try_send((command.seq, command.notification)).is_ok() {
+                *last_seq = command.seq;
+            }
+        }
+    }
+
+    /// Handle a frame the server pushed between replies
+    pub(crate) fn handle_push(&self, push: PushCommand) {
+        match push {
+            PushCommand::Notification(command) => self.handle_notification(command),
+            PushCommand::DurableNotification(command) => self.handle_durable_notification(command),
+        }
+    }
+
+    /// Hand a reply to the tagged request waiting for it. Returns false if
+    /// `resp_value` isn't tagged.
+    pub(crate) fn route_tagged_reply(&self, resp_value: &RespValue) -> bool {
+        let Some((correlation_id, reply)) = decode_tagged(resp_value) else {
+            return false;
+        };
+        // Nobody is waiting if the request timed out
+        if let Some(sender) = self.in_flight.lock().unwrap().remove(&correlation_id) {
+            let _ = sender.send(reply.to_bytes());
+        }
+        true
+    }
+
+    /// Hand every complete frame in the read buffer to the tagged request
+    /// or the notification streams it belongs to
+    fn drain_pushed_frames(&self, conn: &mut AsyncTcpConnection) -> Result<()> {
+        loop {
+            let consumed = match RespValue::from_bytes(&conn.read_buffer) {
+                Ok((resp_value, remaining)) => {
+                    let consumed = conn.read_buffer.len() - remaining.len();
+                    // No command is waiting for an untagged reply, so anything else is stale
+                    if self.route_tagged_reply(&resp_value) {
+                        // Delivered
+                    } else if let Ok(push) = PushCommand::decode(resp_value) {
+                        self.handle_push(push);
+                    } else {
+                        conn.unanswered = conn.unanswered.saturating_sub(1);
+                    }
+                    consumed
+                }
+                Err(e @ Error::ProtocolError(_)) => return Err(e),
+                Err(_) => return conn.limits.check_frame(conn.read_buffer.len()),
+            };
+            conn.read_buffer.drain(..consumed);
+        }
+    }
+
+    /// Start the reader task unless it is already running
+    fn ensure_reader_task(&self) {
+        let mut task = self.reader_task.lock().unwrap();
+        if task.as_ref().is_none_or(|handle| handle.is_finished()) {
+            *task = Some(tokio::spawn(self.clone().run_reader()));
+        }
+    }
+
+    /// Check if the reader task has nothing left to do, and if so mark it
+    /// as stopped. Done under the task lock so that work added meanwhile
+    /// either keeps it running or starts a new one.
+    fn reader_finished(&self) -> bool {
+        let mut task = self.reader_task.lock().unwrap();
+        let mut streams = self.notification_streams.lock().unwrap();
+        streams.retain(|_, (_config, senders)| {
+            senders.retain(|stream| !stream.sender.is_closed());
+            !senders.is_empty()
+        });
+        let mut durable_streams = self.durable_streams.lock().unwrap();
+        durable_streams.retain(|_, (_last_seq, sender)| !sender.is_closed());
+        if streams.is_empty() && durable_streams.is_empty() && self.in_flight.lock().unwrap().is_empty() {
+            *task = None;
+            return true;
+        }
+        false
+    }
+
+    /// Read notifications and tagged replies between commands until no
+    /// stream is left open and no tagged request is waiting, or the
+    /// connection is lost
+    async fn run_reader(self) {
+        loop {
+            if self.reader_finished() {
+                return;
+            }
+
+            let mut conn = self.tcp_connection.lock().await;
+            let mut result = self.drain_pushed_frames(&mut conn);
+            if result.is_ok() && self.waiting_commands.load(Ordering::Acquire) == 0 {
+                let readable = tokio::select! {
+                    readable = conn.readable() => Some(readable),
+                    _ = self.release_connection.notified() => None,
+                };
+                if let Some(readable) = readable {
+                    result = readable.and_then(|_| conn.try_read_bytes());
+                }
+            }
+            drop(conn);
+
+            if result.is_err() {
+                // Ends every stream and fails every tagged request
+                let mut task = self.reader_task.lock().unwrap();
+                self.notification_streams.lock().unwrap().clear();
+                self.durable_streams.lock().unwrap().clear();
+                self.in_flight.lock().unwrap().clear();
+                *task = None;
+                return;
+            }
+            // Let a waiting command take the connection first
+            tokio::task::yield_now().await;
+        }
     }
 
     /// Connect to TCP server
     pub async fn connect(address: &str) -> Result<Self> {
+        Self::connect_with_limits(address, ProtocolLimits::default()).await
+    }
+
+    /// Connect to TCP server, rejecting frames larger than `limits` allows
+    pub async fn connect_with_limits(address: &str, limits: ProtocolLimits) -> Result<Self> {
+        Self::open(address, limits, false).await
+    }
+
+    /// Connect to TCP server and tag every command with a correlation id, so
+    /// concurrent requests share the connection instead of queueing for it.
+    /// The server must support tagged frames (see `resp::encode_tagged`).
+    pub async fn connect_multiplexed(address: &str) -> Result<Self> {
+        Self::connect_multiplexed_with_limits(address, ProtocolLimits::default()).await
+    }
+
+    /// Like `connect_multiplexed`, rejecting frames larger than `limits` allows
+    pub async fn connect_multiplexed_with_limits(address: &str, limits: ProtocolLimits) -> Result<Self> {
+        Self::open(address, limits, true).await
+    }
+
+    async fn open(address: &str, limits: ProtocolLimits, multiplexed: bool) -> Result<Self> {
         // Connect to TCP server
         let stream = TcpStream::connect(address)
             .await
@@ -80,45 +428,97 @@ impl AsyncStoreProxy {
         stream.set_nodelay(true)
             .map_err(|e| Error::StoreProxyError(format!("Failed to set TCP_NODELAY: {}", e)))?;
 
-        let tcp_connection = AsyncTcpConnection::new(stream);
+        let tcp_connection = AsyncTcpConnection::new(stream, limits);
 
         Ok(AsyncStoreProxy {
             tcp_connection: Arc::new(Mutex::new(tcp_connection)),
+            notification_streams: Arc::new(std::sync::Mutex::new(FxHashMap::default())),
+            durable_streams: Arc::new(std::sync::Mutex::new(FxHashMap::default())),
+            next_stream_id: Arc::new(AtomicU64::new(0)),
+            multiplexed,
+            in_flight: Arc::new(std::sync::Mutex::new(FxHashMap::default())),
+            next_correlation_id: Arc::new(AtomicU64::new(0)),
+            reader_task: Arc::new(std::sync::Mutex::new(None)),
+            waiting_commands: Arc::new(AtomicUsize::new(0)),
+            release_connection: Arc::new(Notify::new()),
+            codec: Arc::new(std::sync::Mutex::new(Codec::Resp)),
         })
     }
 
+    /// Ask the server to switch the connection to `codec` with the `CODEC`
+    /// command, and use it for the following commands once the server
+    /// agrees. The proxy only speaks codecs that keep RESP framing.
+    pub async fn negotiate_codec(&self, codec: Codec) -> Result<()> {
+        if !codec.is_resp() {
+            return Err(Error::StoreProxyError(format!("AsyncStoreProxy can't speak the {} codec", codec)));
+        }
+        self.send_command_ok(&CodecCommand {
+            codec: codec.name().to_string(),
+            _marker: std::marker::PhantomData,
+        }).await?;
+        *self.codec.lock().unwrap() = codec;
+        Ok(())
+    }
+
+    /// Codec the connection speaks, `Codec::Resp` until `negotiate_codec`
+    pub fn codec(&self) -> Codec {
+        *self.codec.lock().unwrap()
+    }
+
     async fn send_command_get_response<C, R>(&self, command: &C) -> Result<R>
     where
         C: RespCommand<'static>,
         R: for<'a> RespDecode<'a>,
     {
-        let encoded = command.encode();
-        let encoded_bytes = encoded.to_bytes();
+        let mut encoded_bytes = take_buffer();
+        with_binary_ids(self.codec() == Codec::RespBinaryIds, || command.encode_into(&mut encoded_bytes));
+
+        if self.multiplexed {
+            let reply = self.send_tagged(&encoded_bytes).await?;
+            let (resp_value, _) = RespValue::from_bytes(&reply)?;
+            if let RespValue::Error(error_msg) = &resp_value {
+                return Err(error_from_frame(error_msg));
+            }
+            return R::decode(resp_value);
+        }
         
-        let mut conn = self.tcp_connection.lock().await;
+        let mut conn = self.connection().await?;
+        conn.limits.check_frame(encoded_bytes.len())?;
         
-        conn.send_bytes(&encoded_bytes)
-            .await
-            .map_err(|e| Error::StoreProxyError(format!("Failed to send command: {}", e)))?;
+        conn.send_bytes(&encoded_bytes).await?;
+        conn.unanswered += 1;
 
         loop {
             // Try to parse and get the number of bytes consumed
             let consumed_opt = match RespValue::from_bytes(&conn.read_buffer) {
                 Ok((resp_value, remaining)) => {
                     let consumed = conn.read_buffer.len() - remaining.len();
+                    if let RespValue::Error(error_msg) = &resp_value {
+                        let error = error_from_frame(error_msg);
+                        conn.read_buffer.drain(..consumed);
+                        conn.unanswered -= 1;
+                        return Err(error);
+                    }
                     match R::decode(resp_value.clone()) {
-                        Ok(response_struct) => Some((consumed, Some(response_struct))),
+                        Ok(response_struct) => {
+                            conn.unanswered -= 1;
+                            Some((consumed, Some(response_struct)))
+                        }
                         Err(_) => {
                             // Try to decode as notification
-                            if let Ok(notification) = NotificationCommand::decode(resp_value.clone()) {
-                                self.handle_notification(notification);
+                            if let Ok(push) = PushCommand::decode(resp_value.clone()) {
+                                self.handle_push(push);
                                 Some((consumed, None))
                             } else {
+                                conn.read_buffer.drain(..consumed);
+                                conn.unanswered -= 1;
                                 return Err(Error::StoreProxyError(format!("Failed to decode response or notification")));
                             }
                         }
                     }
                 }
+                // Malformed data will never parse, so don't wait for more of it
+                Err(e @ Error::ProtocolError(_)) => return Err(e),
                 Err(_) => None
             };
             
@@ -134,9 +534,7 @@ impl AsyncStoreProxy {
             }
             
             // Need more data
-            conn.read_bytes()
-                .await
-                .map_err(|e| Error::StoreProxyError(format!("Failed to read bytes: {}", e)))?;
+            conn.read_bytes().await?;
         }
     }
 
@@ -144,23 +542,36 @@ impl AsyncStoreProxy {
     where
         C: RespCommand<'static>,
     {
-        let encoded = command.encode();
-        let encoded_bytes = encoded.to_bytes();
+        let mut encoded_bytes = take_buffer();
+        with_binary_ids(self.codec() == Codec::RespBinaryIds, || command.encode_into(&mut encoded_bytes));
+
+        if self.multiplexed {
+            let reply = self.send_tagged(&encoded_bytes).await?;
+            return expect_ok(RespValue::from_bytes(&reply)?.0);
+        }
         
-        let mut conn = self.tcp_connection.lock().await;
+        let mut conn = self.connection().await?;
+        conn.limits.check_frame(encoded_bytes.len())?;
         
-        conn.send_bytes(&encoded_bytes)
-            .await
-            .map_err(|e| Error::StoreProxyError(format!("Failed to send command: {}", e)))?;
+        conn.send_bytes(&encoded_bytes).await?;
+        conn.unanswered += 1;
 
         loop {
             // Try to parse and get the number of bytes consumed
             let result_opt = match RespValue::from_bytes(&conn.read_buffer) {
                 Ok((resp_value, remaining)) => {
                     let consumed = conn.read_buffer.len() - remaining.len();
+                    if let Ok(push) = PushCommand::decode(resp_value.clone()) {
+                        self.handle_push(push);
+                        conn.read_buffer.drain(..consumed);
+                        continue;
+                    }
                     let result = expect_ok(resp_value);
+                    conn.unanswered -= 1;
                     Some((consumed, result))
                 }
+                // Malformed data will never parse, so don't wait for more of it
+                Err(e @ Error::ProtocolError(_)) => return Err(e),
                 Err(_) => None
             };
             
@@ -171,10 +582,29 @@ impl AsyncStoreProxy {
             }
             
             // Need more data
-            conn.read_bytes()
-                .await
-                .map_err(|e| Error::StoreProxyError(format!("Failed to read bytes: {}", e)))?;
+            conn.read_bytes().await?;
+        }
+    }
+
+    /// Send an encoded command tagged with a new correlation id and wait
+    /// for the reader task to hand over its reply frame
+    async fn send_tagged(&self, encoded_bytes: &[u8]) -> Result<Vec<u8>> {
+        let correlation_id = self.next_correlation_id.fetch_add(1, Ordering::Relaxed);
+        let frame = encode_tagged(correlation_id, encoded_bytes);
+
+        let (sender, receiver) = oneshot::channel();
+        self.in_flight.lock().unwrap().insert(correlation_id, sender);
+        let _guard = InFlightGuard { in_flight: &self.in_flight, correlation_id };
+
+        {
+            let mut conn = self.connection().await?;
+            conn.limits.check_frame(frame.len())?;
+            conn.send_bytes(&frame).await?;
         }
+        self.ensure_reader_task();
+
+        // The sender is dropped if the connection is lost
+        receiver.await.map_err(|_| Error::ConnectionLost)
     }
 
     /// Get entity type by name
@@ -221,6 +651,27 @@ impl AsyncStoreProxy {
         Ok(string_response.value)
     }
 
+    /// Resolve many entity and field type names in one round trip.
+    /// Unknown names are `None`.
+    pub async fn get_types_bulk(&self, entity_types: &[&str], field_types: &[&str]) -> Result<TypesBulk> {
+        let command = crate::data::resp::GetTypesBulkCommand {
+            entity_types: entity_types.iter().map(|name| name.to_string()).collect(),
+            field_types: field_types.iter().map(|name| name.to_string()).collect(),
+            _marker: std::marker::PhantomData,
+        };
+
+        let response = self.send_command_get_response::<crate::data::resp::GetTypesBulkCommand, crate::data::resp::TypesBulkResponse>(&command).await?;
+        if response.entity_types.len() != entity_types.len() || response.field_types.len() != field_types.len() {
+            return Err(Error::StoreProxyError("GET_TYPES_BULK returned the wrong number of types".to_string()));
+        }
+        Ok((response.entity_types, response.field_types))
+    }
+
+    /// Resolve many field type names in one round trip
+    pub async fn get_field_types(&self, names: &[&str]) -> Result<Vec<Option<FieldType>>> {
+        Ok(self.get_types_bulk(&[], names).await?.1)
+    }
+
     /// Get entity schema
     pub async fn get_entity_schema(&self, entity_type: EntityType) -> Result<EntitySchema<Single>> {
         let command = crate::data::resp::GetEntitySchemaCommand {
@@ -230,6 +681,12 @@ impl AsyncStoreProxy {
         
         let schema_resp = self.send_command_get_response::<crate::data::resp::GetEntitySchemaCommand, crate::data::entity_schema::EntitySchemaResp>(&command).await?;
         
+        self.entity_schema_from_resp(schema_resp).await
+    }
+
+    /// Convert a schema received over the wire into a typed schema, looking up
+    /// each type name on the server
+    pub(crate) async fn entity_schema_from_resp(&self, schema_resp: crate::data::entity_schema::EntitySchemaResp) -> Result<EntitySchema<Single>> {
         // Convert EntitySchemaResp to EntitySchema<Single, String, String>
         let mut fields = rustc_hash::FxHashMap::default();
         for field_resp in schema_resp.fields {
@@ -272,68 +729,148 @@ impl AsyncStoreProxy {
     /// Helper method to convert FieldSchema<String> to FieldSchema<FieldType>
     pub(crate) async fn convert_field_schema_from_string(&self, schema: FieldSchema<String>) -> Result<FieldSchema<FieldType>> {
         Ok(match schema {
-            FieldSchema::Blob { field_type, default_value, rank, storage_scope } => FieldSchema::Blob {
+            FieldSchema::Blob { field_type, default_value, rank, storage_scope, validator, metadata } => FieldSchema::Blob {
                 field_type: self.get_field_type(&field_type).await?,
                 default_value,
                 rank,
                 storage_scope,
+                validator,
+                metadata,
             },
-            FieldSchema::Bool { field_type, default_value, rank, storage_scope } => FieldSchema::Bool {
+            FieldSchema::Bool { field_type, default_value, rank, storage_scope, validator, metadata } => FieldSchema::Bool {
                 field_type: self.get_field_type(&field_type).await?,
                 default_value,
                 rank,
                 storage_scope,
+                validator,
+                metadata,
             },
-            FieldSchema::Choice { field_type, default_value, rank, choices, storage_scope } => FieldSchema::Choice {
+            FieldSchema::Choice { field_type, default_value, rank, choices, choices_source, storage_scope, validator, metadata } => FieldSchema::Choice {
                 field_type: self.get_field_type(&field_type).await?,
                 default_value,
                 rank,
                 choices,
+                choices_source,
                 storage_scope,
+                validator,
+                metadata,
             },
-            FieldSchema::EntityList { field_type, default_value, rank, storage_scope } => FieldSchema::EntityList {
+            FieldSchema::EntityList { field_type, default_value, rank, storage_scope, merge_policy, validator, metadata } => FieldSchema::EntityList {
                 field_type: self.get_field_type(&field_type).await?,
                 default_value,
                 rank,
                 storage_scope,
+                merge_policy,
+                validator,
+                metadata,
             },
-            FieldSchema::EntityReference { field_type, default_value, rank, storage_scope } => FieldSchema::EntityReference {
+            FieldSchema::EntityReference { field_type, default_value, rank, storage_scope, validator, metadata } => FieldSchema::EntityReference {
                 field_type: self.get_field_type(&field_type).await?,
                 default_value,
                 rank,
                 storage_scope,
+                validator,
+                metadata,
             },
-            FieldSchema::Float { field_type, default_value, rank, storage_scope } => FieldSchema::Float {
+            FieldSchema::Float { field_type, default_value, rank, storage_scope, merge_policy, validator, metadata } => FieldSchema::Float {
                 field_type: self.get_field_type(&field_type).await?,
                 default_value,
                 rank,
                 storage_scope,
+                merge_policy,
+                validator,
+                metadata,
             },
-            FieldSchema::Int { field_type, default_value, rank, storage_scope } => FieldSchema::Int {
+            FieldSchema::Int { field_type, default_value, rank, storage_scope, merge_policy, validator, metadata } => FieldSchema::Int {
                 field_type: self.get_field_type(&field_type).await?,
                 default_value,
                 rank,
                 storage_scope,
+                merge_policy,
+                validator,
+                metadata,
             },
-            FieldSchema::String { field_type, default_value, rank, storage_scope } => FieldSchema::String {
+            FieldSchema::String { field_type, default_value, rank, storage_scope, validator, metadata } => FieldSchema::String {
                 field_type: self.get_field_type(&field_type).await?,
                 default_value,
                 rank,
                 storage_scope,
+                validator,
+                metadata,
             },
-            FieldSchema::Timestamp { field_type, default_value, rank, storage_scope } => FieldSchema::Timestamp {
+            FieldSchema::Timestamp { field_type, default_value, rank, storage_scope, validator, metadata } => FieldSchema::Timestamp {
                 field_type: self.get_field_type(&field_type).await?,
                 default_value,
                 rank,
                 storage_scope,
+                validator,
+                metadata,
+            },
+            FieldSchema::Computed { field_type, expression, rank, storage_scope, metadata } => FieldSchema::Computed {
+                field_type: self.get_field_type(&field_type).await?,
+                expression,
+                rank,
+                storage_scope,
+                metadata,
+            },
+            FieldSchema::Decimal { field_type, default_value, rank, storage_scope, merge_policy, validator, metadata } => FieldSchema::Decimal {
+                field_type: self.get_field_type(&field_type).await?,
+                default_value,
+                rank,
+                storage_scope,
+                merge_policy,
+                validator,
+                metadata,
+            },
+            FieldSchema::Duration { field_type, default_value, rank, storage_scope, merge_policy, validator, metadata } => FieldSchema::Duration {
+                field_type: self.get_field_type(&field_type).await?,
+                default_value,
+                rank,
+                storage_scope,
+                merge_policy,
+                validator,
+                metadata,
+            },
+            FieldSchema::StringList { field_type, default_value, rank, storage_scope, merge_policy, validator, metadata } => FieldSchema::StringList {
+                field_type: self.get_field_type(&field_type).await?,
+                default_value,
+                rank,
+                storage_scope,
+                merge_policy,
+                validator,
+                metadata,
+            },
+            FieldSchema::Map { field_type, default_value, rank, storage_scope, validator, metadata } => FieldSchema::Map {
+                field_type: self.get_field_type(&field_type).await?,
+                default_value,
+                rank,
+                storage_scope,
+                validator,
+                metadata,
+            },
+            FieldSchema::Series { field_type, sample_type, capacity, retention, rank, storage_scope, validator, metadata } => FieldSchema::Series {
+                field_type: self.get_field_type(&field_type).await?,
+                sample_type,
+                capacity,
+                retention,
+                rank,
+                storage_scope,
+                validator,
+                metadata,
             },
         })
     }
 
     /// Get complete entity schema
-    pub async fn get_complete_entity_schema(&self, _entity_type: EntityType) -> Result<EntitySchema<Complete>> {
-        // TODO: Implement RESP command for complete entity schema
-        unimplemented!("Complete entity schema not yet implemented with RESP")
+    pub async fn get_complete_entity_schema(&self, entity_type: EntityType) -> Result<EntitySchema<Complete>> {
+        let command = crate::data::resp::GetCompleteEntitySchemaCommand {
+            entity_type,
+            _marker: std::marker::PhantomData,
+        };
+
+        let schema_resp = self.send_command_get_response::<crate::data::resp::GetCompleteEntitySchemaCommand, crate::data::entity_schema::EntitySchemaResp>(&command).await?;
+
+        Ok(EntitySchema::<Complete>::from(self.entity_schema_from_resp(schema_resp).await?))
     }
 
     /// Set field schema
@@ -345,6 +882,12 @@ impl AsyncStoreProxy {
             rank: schema.rank(),
             default_value: schema.default_value(),
             choices: schema.choices(),
+            choices_source: schema.choices_source().map(|s| s.to_string()),
+            expression: schema.expression().map(|e| e.to_string()),
+            metadata: schema.metadata().clone(),
+            series: schema.series_limits(),
+            validator: schema.validator().map(|v| v.to_string()),
+            merge_policy: schema.merge_policy(),
         };
 
         let command = crate::data::resp::SetFieldSchemaCommand {
@@ -443,6 +986,141 @@ impl AsyncStoreProxy {
         self.send_command_ok(&command).await
     }
 
+    /// Write a field value, rejected with `Error::StaleFencingToken` unless
+    /// `fencing_token` is the current LeaderToken of `fault_tolerance_id`
+    #[allow(clippy::too_many_arguments)]
+    pub async fn fenced_write(&self, fault_tolerance_id: EntityId, fencing_token: i64, entity_id: EntityId, field_path: &[FieldType], value: Value, writer_id: Option<EntityId>, write_time: Option<Timestamp>, push_condition: Option<PushCondition>, adjust_behavior: Option<AdjustBehavior>) -> Result<()> {
+        let command = crate::data::resp::FencedWriteCommand {
+            fault_tolerance_id,
+            fencing_token,
+            entity_id,
+            field_path: field_path.to_vec(),
+            value,
+            writer_id,
+            write_time,
+            push_condition,
+            adjust_behavior,
+            _marker: std::marker::PhantomData,
+        };
+        self.send_command_ok(&command).await
+    }
+
+    /// Resolve an entity path such as `Root/Machines/M1` to its entity id on the server
+    pub async fn resolve_path(&self, path: &str) -> Result<EntityId> {
+        let command = crate::data::resp::ResolvePathCommand {
+            path: path.to_string(),
+            _marker: std::marker::PhantomData,
+        };
+
+        let response = self.send_command_get_response::<_, crate::data::resp::ResolvePathResponse>(&command).await?;
+        Ok(response.entity_id)
+    }
+
+    /// Read a field addressed by path, e.g. `Root/Machines/M1/Pump3/Speed`
+    pub async fn read_path(&self, path: &str) -> Result<(Value, Timestamp, Option<EntityId>)> {
+        let command = crate::data::resp::ReadPathCommand {
+            path: path.to_string(),
+            _marker: std::marker::PhantomData,
+        };
+
+        let read_response = self.send_command_get_response::<_, crate::data::resp::ReadResponse>(&command).await?;
+        Ok((read_response.value, read_response.timestamp, read_response.writer_id))
+    }
+
+    /// Write a field addressed by path, e.g. `Root/Machines/M1/Pump3/Speed`
+    pub async fn write_path(&self, path: &str, value: Value, writer_id: Option<EntityId>, write_time: Option<Timestamp>, push_condition: Option<PushCondition>, adjust_behavior: Option<AdjustBehavior>) -> Result<()> {
+        let command = crate::data::resp::WritePathCommand {
+            path: path.to_string(),
+            value,
+            writer_id,
+            write_time,
+            push_condition,
+            adjust_behavior,
+            _marker: std::marker::PhantomData,
+        };
+        self.send_command_ok(&command).await
+    }
+
+    /// Read `len` bytes of a blob field starting at `offset`, returning the
+    /// bytes and the blob's total length
+    pub async fn read_blob_range(&self, entity_id: EntityId, field_path: &[FieldType], offset: usize, len: usize) -> Result<(Vec<u8>, usize)> {
+        let command = crate::data::resp::ReadBlobRangeCommand {
+            entity_id,
+            field_path: field_path.to_vec(),
+            offset,
+            len,
+            _marker: std::marker::PhantomData,
+        };
+
+        let response = self.send_command_get_response::<_, crate::data::resp::BlobRangeResponse>(&command).await?;
+        Ok((response.data, response.total_len))
+    }
+
+    /// Append bytes to a blob field, returning the blob's new length
+    pub async fn write_blob_append(&self, entity_id: EntityId, field_path: &[FieldType], data: Vec<u8>, writer_id: Option<EntityId>) -> Result<usize> {
+        let command = crate::data::resp::WriteBlobAppendCommand {
+            entity_id,
+            field_path: field_path.to_vec(),
+            data,
+            writer_id,
+            _marker: std::marker::PhantomData,
+        };
+
+        let response = self.send_command_get_response::<_, crate::data::resp::IntegerResponse>(&command).await?;
+        Ok(response.value as usize)
+    }
+
+    /// Stream a blob field in chunks of at most `chunk_size` bytes, so blobs
+    /// larger than a single frame can be read without one huge response
+    pub fn read_blob_stream(&self, entity_id: EntityId, field_path: &[FieldType], chunk_size: usize) -> impl Stream<Item = Result<Vec<u8>>> + '_ {
+        let field_path = field_path.to_vec();
+        let chunk_size = chunk_size.max(1);
+        futures_util::stream::try_unfold(Some(0usize), move |offset| {
+            let field_path = field_path.clone();
+            async move {
+                let Some(offset) = offset else {
+                    return Ok(None);
+                };
+                let (chunk, total_len) = self.read_blob_range(entity_id, &field_path, offset, chunk_size).await?;
+                if chunk.is_empty() {
+                    return Ok(None);
+                }
+                let next_offset = offset + chunk.len();
+                Ok(Some((chunk, (next_offset < total_len).then_some(next_offset))))
+            }
+        })
+    }
+
+    /// Replace a blob field with the contents of `reader`, uploading it in
+    /// chunks of at most `chunk_size` bytes. Returns the number of bytes written.
+    ///
+    /// The field is cleared first, so readers may observe a partial blob until
+    /// the upload completes.
+    pub async fn write_blob_from_reader<R: AsyncRead + Unpin>(&self, entity_id: EntityId, field_path: &[FieldType], mut reader: R, chunk_size: usize, writer_id: Option<EntityId>) -> Result<usize> {
+        self.write(entity_id, field_path, Value::Blob(Vec::new()), writer_id, None, None, None).await?;
+
+        let mut buffer = vec![0u8; chunk_size.max(1)];
+        let mut written = 0;
+        loop {
+            // Fill a whole chunk before sending so short reads don't turn into tiny appends
+            let mut filled = 0;
+            while filled < buffer.len() {
+                let bytes_read = reader
+                    .read(&mut buffer[filled..])
+                    .await
+                    .map_err(|e| Error::StoreProxyError(format!("Failed to read blob source: {}", e)))?;
+                if bytes_read == 0 {
+                    break;
+                }
+                filled += bytes_read;
+            }
+            if filled == 0 {
+                return Ok(written);
+            }
+            written = self.write_blob_append(entity_id, field_path, buffer[..filled].to_vec(), writer_id).await?;
+        }
+    }
+
     /// Create a new entity
     pub async fn create_entity(&self, entity_type: EntityType, parent_id: Option<EntityId>, name: &str) -> Result<EntityId> {
         let command = CreateEntityCommand {
@@ -456,6 +1134,74 @@ impl AsyncStoreProxy {
         Ok(create_response.entity_id)
     }
 
+    /// Create an entity with initial field values, applied together with
+    /// the creation (`CREATE_ENTITY_FULL`)
+    pub async fn create_entity_with_fields(&self, entity_type: EntityType, parent_id: Option<EntityId>, name: &str, fields: Vec<(FieldType, Value)>) -> Result<EntityId> {
+        let command = crate::data::resp::CreateEntityFullCommand {
+            entity_type,
+            parent_id,
+            name: name.to_string(),
+            fields,
+            _marker: std::marker::PhantomData,
+        };
+
+        let create_response = self.send_command_get_response::<_, crate::data::resp::CreateEntityResponse>(&command).await?;
+        Ok(create_response.entity_id)
+    }
+
+    /// Create a copy of a template and its descendants (`INSTANTIATE`),
+    /// see `Store::instantiate_template`
+    pub async fn instantiate_template(&self, template_id: EntityId, parent_id: Option<EntityId>, name: &str, overrides: Vec<(FieldType, Value)>) -> Result<EntityId> {
+        let command = crate::data::resp::InstantiateTemplateCommand {
+            template_id,
+            parent_id,
+            name: name.to_string(),
+            overrides,
+            _marker: std::marker::PhantomData,
+        };
+
+        let create_response = self.send_command_get_response::<_, crate::data::resp::CreateEntityResponse>(&command).await?;
+        Ok(create_response.entity_id)
+    }
+
+    /// Find or create an entity by parent and name in one round trip, so
+    /// the server decides atomically (`GET_OR_CREATE`)
+    pub async fn get_or_create(&self, entity_type: EntityType, parent_id: Option<EntityId>, name: &str) -> Result<(EntityId, bool)> {
+        let command = crate::data::resp::GetOrCreateCommand {
+            entity_type,
+            parent_id,
+            name: name.to_string(),
+            _marker: std::marker::PhantomData,
+        };
+
+        let response = self.send_command_get_response::<_, crate::data::resp::GetOrCreateResponse>(&command).await?;
+        Ok((response.entity_id, response.created))
+    }
+
+    /// Write a field unless it already holds `value` (`UPSERT`), returning
+    /// whether it was written
+    pub async fn upsert_field(&self, entity_id: EntityId, field_type: FieldType, value: Value) -> Result<bool> {
+        let command = crate::data::resp::UpsertFieldCommand {
+            entity_id,
+            field_type,
+            value,
+            _marker: std::marker::PhantomData,
+        };
+
+        let response = self.send_command_get_response::<_, crate::data::resp::BooleanResponse>(&command).await?;
+        Ok(response.result)
+    }
+
+    /// Rename an entity (`RENAME`), enforcing name uniqueness on the server
+    pub async fn rename_entity(&self, entity_id: EntityId, new_name: &str) -> Result<()> {
+        let command = crate::data::resp::RenameEntityCommand {
+            entity_id,
+            new_name: new_name.to_string(),
+            _marker: std::marker::PhantomData,
+        };
+        self.send_command_ok(&command).await
+    }
+
     /// Delete an entity
     pub async fn delete_entity(&self, entity_id: EntityId) -> Result<()> {
         let command = crate::data::resp::DeleteEntityCommand {
@@ -467,26 +1213,8 @@ impl AsyncStoreProxy {
 
     /// Update entity schema
     pub async fn update_schema(&self, schema: EntitySchema<Single, String, String>) -> Result<()> {
-        // Convert EntitySchema to EntitySchemaResp
-        let fields_resp: Vec<crate::data::entity_schema::FieldSchemaResp> = schema
-            .fields
-            .into_iter()
-            .map(|(field_type_str, field_schema)| {
-                crate::data::entity_schema::FieldSchemaResp {
-                    field_type: field_type_str,
-                    rank: field_schema.rank(),
-                    default_value: field_schema.default_value(),
-                    choices: field_schema.choices(),
-                }
-            })
-            .collect();
+        let schema_resp = crate::data::entity_schema::EntitySchemaResp::from_string_schema(schema);
 
-        let schema_resp = crate::data::entity_schema::EntitySchemaResp {
-            entity_type: schema.entity_type,
-            inherit: schema.inherit,
-            fields: fields_resp,
-        };
-        
         let command = crate::data::resp::UpdateSchemaCommand {
             schema: schema_resp,
             _marker: std::marker::PhantomData,
@@ -520,38 +1248,56 @@ impl AsyncStoreProxy {
 
     /// Find entities with pagination (includes inherited types)
     pub async fn find_entities_paginated(&self, entity_type: EntityType, page_opts: Option<&PageOpts>, filter: Option<&str>) -> Result<PageResult<EntityId>> {
+        self.find_entities_paginated_with_timeout(entity_type, page_opts, filter, None).await
+    }
+
+    /// Like `find_entities_paginated`, but the server gives up once `timeout`
+    /// has passed and the call fails with `Error::Timeout`
+    pub async fn find_entities_paginated_with_timeout(&self, entity_type: EntityType, page_opts: Option<&PageOpts>, filter: Option<&str>, timeout: Option<std::time::Duration>) -> Result<PageResult<EntityId>> {
+        let request_id = rand::random::<u64>();
         let command = crate::data::resp::FindEntitiesPaginatedCommand {
             entity_type,
             page_opts: page_opts.cloned(),
             filter: filter.map(|s| s.to_string()),
+            timeout_ms: timeout.map(|timeout| timeout.as_millis() as u64),
+            request_id: timeout.map(|_| request_id),
             _marker: std::marker::PhantomData,
         };
         
-        let paginated_response = self.send_command_get_response::<crate::data::resp::FindEntitiesPaginatedCommand, crate::data::resp::PaginatedEntityResponse>(&command).await?;
+        let paginated_response = self.with_deadline(timeout, request_id, self.send_command_get_response::<crate::data::resp::FindEntitiesPaginatedCommand, crate::data::resp::PaginatedEntityResponse>(&command)).await?;
         
         Ok(PageResult::new(
             paginated_response.items,
             paginated_response.total,
             paginated_response.next_cursor,
-        ))
+        ).with_next_key(paginated_response.next_key))
     }
 
     /// Find entities exactly of the specified type (no inheritance) with pagination
     pub async fn find_entities_exact(&self, entity_type: EntityType, page_opts: Option<&PageOpts>, filter: Option<&str>) -> Result<PageResult<EntityId>> {
+        self.find_entities_exact_with_timeout(entity_type, page_opts, filter, None).await
+    }
+
+    /// Like `find_entities_exact`, but the server gives up once `timeout`
+    /// has passed and the call fails with `Error::Timeout`
+    pub async fn find_entities_exact_with_timeout(&self, entity_type: EntityType, page_opts: Option<&PageOpts>, filter: Option<&str>, timeout: Option<std::time::Duration>) -> Result<PageResult<EntityId>> {
+        let request_id = rand::random::<u64>();
         let command = crate::data::resp::FindEntitiesExactCommand {
             entity_type,
             page_opts: page_opts.cloned(),
             filter: filter.map(|s| s.to_string()),
+            timeout_ms: timeout.map(|timeout| timeout.as_millis() as u64),
+            request_id: timeout.map(|_| request_id),
             _marker: std::marker::PhantomData,
         };
         
-        let paginated_response = self.send_command_get_response::<crate::data::resp::FindEntitiesExactCommand, crate::data::resp::PaginatedEntityResponse>(&command).await?;
+        let paginated_response = self.with_deadline(timeout, request_id, self.send_command_get_response::<crate::data::resp::FindEntitiesExactCommand, crate::data::resp::PaginatedEntityResponse>(&command)).await?;
         
         Ok(PageResult::new(
             paginated_response.items,
             paginated_response.total,
             paginated_response.next_cursor,
-        ))
+        ).with_next_key(paginated_response.next_key))
     }
 
     /// Get all entity types with pagination
@@ -570,18 +1316,154 @@ impl AsyncStoreProxy {
         ))
     }
 
+    /// Get machine info (machine ID/name)
+    pub async fn machine_info(&self) -> Result<String> {
+        let command = crate::data::resp::MachineInfoCommand {
+            _marker: std::marker::PhantomData,
+        };
+
+        let string_response = self.send_command_get_response::<crate::data::resp::MachineInfoCommand, crate::data::resp::StringResponse>(&command).await?;
+        Ok(string_response.value)
+    }
+
+    /// Query the server's audit log. Fails if auditing isn't enabled there.
+    pub async fn audit_query(&self, query: &crate::AuditQuery) -> Result<Vec<crate::AuditRecord>> {
+        let command = crate::data::resp::AuditQueryCommand::from_query(query);
+        let response = self.send_command_get_response::<crate::data::resp::AuditQueryCommand, crate::data::resp::AuditQueryResponse>(&command).await?;
+        response.into_records()
+    }
+
+    /// The writes the server made to `entity_id` at or after `since`, oldest
+    /// first. Fails if auditing isn't enabled there or the log no longer
+    /// goes back that far.
+    pub async fn get_changes(&self, entity_id: EntityId, since: crate::Timestamp) -> Result<Vec<crate::WriteInfo>> {
+        let command = crate::data::resp::GetChangesCommand {
+            entity_id,
+            since,
+            _marker: std::marker::PhantomData,
+        };
+        let response = self.send_command_get_response::<crate::data::resp::GetChangesCommand, crate::data::resp::ChangesResponse>(&command).await?;
+        response.into_writes()
+    }
+
+    /// The server's `count` slowest recent commands (all of them for None),
+    /// newest first
+    pub async fn slowlog_get(&self, count: Option<usize>) -> Result<Vec<crate::SlowLogEntry>> {
+        let command = crate::data::resp::SlowLogCommand {
+            action: crate::SlowLogAction::Get,
+            count,
+            _marker: std::marker::PhantomData,
+        };
+        let response = self.send_command_get_response::<crate::data::resp::SlowLogCommand, crate::data::resp::SlowLogResponse>(&command).await?;
+        response.into_entries()
+    }
+
+    /// Clear the server's slow log
+    pub async fn slowlog_reset(&self) -> Result<()> {
+        let command = crate::data::resp::SlowLogCommand {
+            action: crate::SlowLogAction::Reset,
+            count: None,
+            _marker: std::marker::PhantomData,
+        };
+        self.send_command_ok(&command).await
+    }
+
     /// Find entities of a specific type (includes inherited types)
     pub async fn find_entities(&self, entity_type: EntityType, filter: Option<&str>) -> Result<Vec<EntityId>> {
+        self.find_entities_with_timeout(entity_type, filter, None).await
+    }
+
+    /// Like `find_entities`, but the server gives up once `timeout`
+    /// has passed and the call fails with `Error::Timeout`
+    pub async fn find_entities_with_timeout(&self, entity_type: EntityType, filter: Option<&str>, timeout: Option<std::time::Duration>) -> Result<Vec<EntityId>> {
+        let request_id = rand::random::<u64>();
         let command = crate::data::resp::FindEntitiesCommand {
             entity_type,
             filter: filter.map(|s| s.to_string()),
+            timeout_ms: timeout.map(|timeout| timeout.as_millis() as u64),
+            request_id: timeout.map(|_| request_id),
             _marker: std::marker::PhantomData,
         };
         
-        let entity_list_response = self.send_command_get_response::<crate::data::resp::FindEntitiesCommand, crate::data::resp::EntityListResponse>(&command).await?;
+        let entity_list_response = self.with_deadline(timeout, request_id, self.send_command_get_response::<crate::data::resp::FindEntitiesCommand, crate::data::resp::EntityListResponse>(&command)).await?;
         Ok(entity_list_response.entities)
     }
 
+    /// Entities and fields referring to an entity, answered by the server's index
+    pub async fn find_referencing(&self, entity_id: EntityId) -> Result<Vec<(EntityId, FieldType)>> {
+        let command = crate::data::resp::FindReferencingCommand {
+            entity_id,
+            _marker: std::marker::PhantomData,
+        };
+
+        let response = self.send_command_get_response::<crate::data::resp::FindReferencingCommand, crate::data::resp::ReferencingResponse>(&command).await?;
+        if response.entity_ids.len() != response.field_types.len() {
+            return Err(Error::StoreProxyError("FIND_REFERENCING returned mismatched lists".to_string()));
+        }
+        Ok(response.entity_ids.into_iter().zip(response.field_types).collect())
+    }
+
+    /// Full-text search on the server, see `Store::search`
+    pub async fn search(&self, entity_type: EntityType, query: &str) -> Result<Vec<EntityId>> {
+        let command = crate::data::resp::SearchCommand {
+            entity_type,
+            query: query.to_string(),
+            _marker: std::marker::PhantomData,
+        };
+
+        let response = self.send_command_get_response::<crate::data::resp::SearchCommand, crate::data::resp::EntityListResponse>(&command).await?;
+        Ok(response.entities)
+    }
+
+    /// Sample history of a Series field, see `Store::read_series`
+    pub async fn read_series(&self, entity_id: EntityId, field_type: FieldType, from: Option<Timestamp>, to: Option<Timestamp>, downsample: Option<crate::Duration>) -> Result<Vec<(Timestamp, Value)>> {
+        let command = crate::data::resp::ReadSeriesCommand {
+            entity_id,
+            field_type,
+            from,
+            to,
+            downsample,
+            _marker: std::marker::PhantomData,
+        };
+
+        let response = self.send_command_get_response::<crate::data::resp::ReadSeriesCommand, crate::data::resp::SeriesResponse>(&command).await?;
+        Ok(response.samples)
+    }
+
+    /// Content hashes of the server's state, see `Store::checksum`
+    pub async fn verify(&self) -> Result<crate::SnapshotChecksum> {
+        let command = crate::data::resp::VerifyCommand {
+            _marker: std::marker::PhantomData,
+        };
+
+        let response = self.send_command_get_response::<crate::data::resp::VerifyCommand, crate::data::resp::ChecksumResponse>(&command).await?;
+        response.into_checksum()
+    }
+
+    /// Size and usage figures of the server's store, see `StoreTrait::stats`
+    pub async fn stats(&self) -> Result<crate::StoreStats> {
+        let command = crate::data::resp::StatsCommand {
+            _marker: std::marker::PhantomData,
+        };
+
+        let response = self.send_command_get_response::<crate::data::resp::StatsCommand, crate::data::resp::StatsResponse>(&command).await?;
+        crate::StoreStats::from_map(&response.stats)
+    }
+
+    /// Aggregate a field on the server, see `StoreTrait::aggregate`
+    pub async fn aggregate(&self, entity_type: EntityType, field_type: FieldType, op: AggregateOp, filter: Option<&str>) -> Result<Option<Value>> {
+        let command = crate::data::resp::AggregateCommand {
+            entity_type,
+            field_type,
+            op,
+            filter: filter.map(|s| s.to_string()),
+            _marker: std::marker::PhantomData,
+        };
+
+        let response = self.send_command_get_response::<crate::data::resp::AggregateCommand, crate::data::resp::AggregateResponse>(&command).await?;
+        Ok(response.value)
+    }
+
     /// Get all entity types
     pub async fn get_entity_types(&self) -> Result<Vec<EntityType>> {
         let command = crate::data::resp::GetEntityTypesCommand {
@@ -592,9 +1474,36 @@ impl AsyncStoreProxy {
         Ok(entity_type_list_response.entity_types)
     }
 
-    /// Register notification with provided sender
-    /// Note: For proxy, this registers the notification on the remote server
-    /// and stores the sender locally to forward notifications
+    /// Cancel a request sent with a timeout, e.g. from another connection.
+    /// Succeeds whether or not the request was still running.
+    pub async fn cancel(&self, request_id: u64) -> Result<()> {
+        let command = crate::data::resp::CancelCommand {
+            request_id,
+            _marker: std::marker::PhantomData,
+        };
+        self.send_command_ok(&command).await
+    }
+
+    /// Run a request, giving up with `Error::Timeout` if the server hasn't
+    /// answered shortly after `timeout`
+    async fn with_deadline<T>(&self, timeout: Option<std::time::Duration>, request_id: u64, request: impl std::future::Future<Output = Result<T>>) -> Result<T> {
+        let Some(timeout) = timeout else {
+            return request.await;
+        };
+
+        match tokio::time::timeout(timeout + TIMEOUT_GRACE, request).await {
+            Ok(result) => result,
+            Err(_) => {
+                // Nobody will read the reply, so stop the server working on it
+                let _ = tokio::time::timeout(TIMEOUT_GRACE, self.cancel(request_id)).await;
+                Err(Error::Timeout(timeout))
+            }
+        }
+    }
+
+    /// Register a notification on the server
+    /// Note: the queue is not fed; use `register_notification_stream` to
+    /// receive the notifications
     pub async fn register_notification(
         &self,
         config: crate::NotifyConfig,
@@ -625,4 +1534,336 @@ impl AsyncStoreProxy {
             Err(_) => false,
         }
     }
+
+    /// Register a notification and receive it as a `Stream`, e.g. to
+    /// `select!` on in a tokio service
+    pub async fn register_notification_stream(&self, config: NotifyConfig) -> Result<NotificationStream> {
+        self.register_notification_stream_with_capacity(config, DEFAULT_NOTIFICATION_STREAM_CAPACITY).await
+    }
+
+    /// Like `register_notification_stream`, buffering up to `capacity`
+    /// notifications for a slow consumer
+    pub async fn register_notification_stream_with_capacity(&self, config: NotifyConfig, capacity: usize) -> Result<NotificationStream> {
+        let (sender, receiver) = mpsc::channel(capacity.max(1));
+        let id = self.next_stream_id.fetch_add(1, Ordering::Relaxed);
+        let dropped = Arc::new(AtomicU64::new(0));
+
+        // Add the stream first so a notification right after the reply isn't lost
+        let config_hash = crate::data::notifications::hash_notify_config(&config);
+        self.notification_streams
+            .lock()
+            .unwrap()
+            .entry(config_hash)
+            .or_insert_with(|| (config.clone(), Vec::new()))
+            .1
+            .push(StreamSender { id, sender, dropped: dropped.clone() });
+
+        let command = crate::data::resp::RegisterNotificationCommand {
+            config: config.clone(),
+            _marker: std::marker::PhantomData,
+        };
+        if let Err(e) = self.send_command_ok(&command).await {
+            self.remove_stream(config_hash, id);
+            return Err(e);
+        }
+
+        self.ensure_reader_task();
+        Ok(NotificationStream { id, config, receiver, dropped })
+    }
+
+    /// Close a notification stream. The registration is removed from the
+    /// server once no other stream for the same config is open.
+    pub async fn unregister_notification_stream(&self, stream: NotificationStream) -> Result<()> {
+        let config_hash = crate::data::notifications::hash_notify_config(&stream.config);
+        if !self.remove_stream(config_hash, stream.id) {
+            return Ok(());
+        }
+
+        let command = crate::data::resp::UnregisterNotificationCommand {
+            config: stream.config,
+            _marker: std::marker::PhantomData,
+        };
+        self.send_command_ok(&command).await
+    }
+
+    /// Subscribe to notifications that the server keeps for this
+    /// subscription while no client is connected, like
+    /// `StoreProxy::subscribe_durable`. Returns the sequence number of the
+    /// last notification the subscription had received before, and a
+    /// receiver for the ones after it, numbered.
+    pub async fn subscribe_durable(&self, subscription_id: &str, config: NotifyConfig) -> Result<(u64, mpsc::Receiver<(u64, Notification)>)> {
+        let receiver = self.add_durable_stream(subscription_id, 0);
+        let command = crate::data::resp::SubscribeDurableCommand {
+            subscription_id: subscription_id.to_string(),
+            config,
+            _marker: std::marker::PhantomData,
+        };
+        self.finish_durable_stream(subscription_id, receiver, &command).await
+    }
+
+    /// Attach to a durable subscription after connecting again, like
+    /// `StoreProxy::resume_durable`. The notifications after `last_seq` are
+    /// on the receiver by the time this returns, with new ones following.
+    pub async fn resume_durable(&self, subscription_id: &str, last_seq: u64) -> Result<(u64, mpsc::Receiver<(u64, Notification)>)> {
+        let receiver = self.add_durable_stream(subscription_id, last_seq);
+        let command = crate::data::resp::ResumeCommand {
+            subscription_id: subscription_id.to_string(),
+            last_seq,
+            _marker: std::marker::PhantomData,
+        };
+        self.finish_durable_stream(subscription_id, receiver, &command).await
+    }
+
+    /// Remove a durable subscription from the server, along with the
+    /// notifications it kept, and end its receiver
+    pub async fn unsubscribe_durable(&self, subscription_id: &str) -> Result<()> {
+        self.durable_streams.lock().unwrap().remove(subscription_id);
+        let command = crate::data::resp::UnsubscribeDurableCommand {
+            subscription_id: subscription_id.to_string(),
+            _marker: std::marker::PhantomData,
+        };
+        self.send_command_ok(&command).await
+    }
+
+    /// Add the receiver before sending, so a replay or a notification right
+    /// after the reply isn't lost
+    fn add_durable_stream(&self, subscription_id: &str, last_seq: u64) -> mpsc::Receiver<(u64, Notification)> {
+        let (sender, receiver) = mpsc::channel(DEFAULT_NOTIFICATION_STREAM_CAPACITY);
+        self.durable_streams.lock().unwrap().insert(subscription_id.to_string(), (last_seq, sender));
+        receiver
+    }
+
+    async fn finish_durable_stream<C>(&self, subscription_id: &str, receiver: mpsc::Receiver<(u64, Notification)>, command: &C) -> Result<(u64, mpsc::Receiver<(u64, Notification)>)>
+    where
+        C: RespCommand<'static>,
+    {
+        match self.send_command_get_response::<_, crate::data::resp::IntegerResponse>(command).await {
+            Ok(response) => {
+                self.ensure_reader_task();
+                Ok((response.value as u64, receiver))
+            }
+            Err(e) => {
+                self.durable_streams.lock().unwrap().remove(subscription_id);
+                Err(e)
+            }
+        }
+    }
+
+    /// Wait until a field satisfies `predicate` and return the value that
+    /// did, or fail with `Error::Timeout` once `timeout` has passed. Like
+    /// `StoreProxy::wait_for`, the notification stream is opened before the
+    /// field is read, so no write in between is missed.
+    pub async fn wait_for(&self, entity_id: EntityId, field_type: FieldType, predicate: impl Fn(&Value) -> bool, timeout: std::time::Duration) -> Result<Value> {
+        let config = NotifyConfig::EntityId {
+            entity_id,
+            field_type,
+            trigger_on_change: true,
+            context: vec![],
+            filter: None,
+        };
+        let mut stream = self.register_notification_stream(config).await?;
+
+        let result = tokio::time::timeout(timeout, async {
+            let (value, _, _) = self.read(entity_id, &[field_type]).await?;
+            if predicate(&value) {
+                return Ok(value);
+            }
+            while let Some(notification) = stream.next().await {
+                if let Some(value) = notification.current.value.filter(|value| predicate(value)) {
+                    return Ok(value);
+                }
+            }
+            // Streams only end when the connection is lost
+            Err(Error::ConnectionLost)
+        })
+        .await
+        .unwrap_or(Err(Error::Timeout(timeout)));
+
+        // The stream is closed locally even if the server can't be told
+        let _ = self.unregister_notification_stream(stream).await;
+        result
+    }
+
+    /// End the session cleanly instead of just dropping the socket: wait
+    /// (up to `SHUTDOWN_TIMEOUT`) for the replies to requests already sent,
+    /// including pipelines, end every notification stream and remove its
+    /// registration from the server, send `QUIT` and close the connection.
+    /// Every clone of the proxy shares the connection, so all of them are
+    /// closed.
+    pub async fn shutdown(&self) -> Result<()> {
+        let deadline = tokio::time::Instant::now() + SHUTDOWN_TIMEOUT;
+        while !self.in_flight.lock().unwrap().is_empty() && tokio::time::Instant::now() < deadline {
+            tokio::time::sleep(std::time::Duration::from_millis(1)).await;
+        }
+
+        let configs: Vec<NotifyConfig> = self
+            .notification_streams
+            .lock()
+            .unwrap()
+            .drain()
+            .map(|(_, (config, _senders))| config)
+            .collect();
+
+        let mut result = Ok(());
+        for config in configs {
+            let command = crate::data::resp::UnregisterNotificationCommand {
+                config,
+                _marker: std::marker::PhantomData,
+            };
+            // Keep going, so the connection is still closed
+            if let Err(e) = self.send_command_ok(&command).await {
+                result = result.and(Err(e));
+            }
+        }
+
+        let command = crate::data::resp::QuitCommand {
+            _marker: std::marker::PhantomData,
+        };
+        result = result.and(self.send_command_ok(&command).await);
+
+        // Stop reading before closing, so the reader doesn't hold the connection
+        if let Some(task) = self.reader_task.lock().unwrap().take() {
+            task.abort();
+        }
+        self.in_flight.lock().unwrap().clear();
+        let closed = self.tcp_connection.lock().await.shutdown().await;
+        result.and(closed)
+    }
+
+    /// Forget one stream. Returns true if it was the last one for its config.
+    fn remove_stream(&self, config_hash: u64, id: u64) -> bool {
+        let mut streams = self.notification_streams.lock().unwrap();
+        let Some((_config, senders)) = streams.get_mut(&config_hash) else {
+            return false;
+        };
+        senders.retain(|stream| stream.id != id && !stream.sender.is_closed());
+        if senders.is_empty() {
+            streams.remove(&config_hash);
+            true
+        } else {
+            false
+        }
+    }
+}
+
+impl AsyncStoreTrait for AsyncStoreProxy {
+    async fn get_entity_type(&self, name: &str) -> Result<EntityType> {
+        self.get_entity_type(name).await
+    }
+
+    async fn resolve_entity_type(&self, entity_type: EntityType) -> Result<String> {
+        self.resolve_entity_type(entity_type).await
+    }
+
+    async fn get_field_type(&self, name: &str) -> Result<FieldType> {
+        self.get_field_type(name).await
+    }
+
+    async fn resolve_field_type(&self, field_type: FieldType) -> Result<String> {
+        self.resolve_field_type(field_type).await
+    }
+
+    async fn get_types_bulk(&self, entity_types: &[&str], field_types: &[&str]) -> Result<TypesBulk> {
+        self.get_types_bulk(entity_types, field_types).await
+    }
+
+    async fn get_field_types(&self, names: &[&str]) -> Result<Vec<Option<FieldType>>> {
+        self.get_field_types(names).await
+    }
+
+    async fn get_entity_schema(&self, entity_type: EntityType) -> Result<EntitySchema<Single>> {
+        self.get_entity_schema(entity_type).await
+    }
+
+    async fn get_complete_entity_schema(&self, entity_type: EntityType) -> Result<EntitySchema<Complete>> {
+        self.get_complete_entity_schema(entity_type).await
+    }
+
+    async fn get_field_schema(&self, entity_type: EntityType, field_type: FieldType) -> Result<FieldSchema> {
+        self.get_field_schema(entity_type, field_type).await
+    }
+
+    async fn set_field_schema(&mut self, entity_type: EntityType, field_type: FieldType, schema: FieldSchema) -> Result<()> {
+        AsyncStoreProxy::set_field_schema(self, entity_type, field_type, schema).await
+    }
+
+    async fn entity_exists(&self, entity_id: EntityId) -> bool {
+        self.entity_exists(entity_id).await
+    }
+
+    async fn field_exists(&self, entity_type: EntityType, field_type: FieldType) -> bool {
+        self.field_exists(entity_type, field_type).await
+    }
+
+    async fn resolve_indirection(&self, entity_id: EntityId, fields: &[FieldType]) -> Result<(EntityId, FieldType)> {
+        self.resolve_indirection(entity_id, fields).await
+    }
+
+    async fn read(&self, entity_id: EntityId, field_path: &[FieldType]) -> Result<(Value, Timestamp, Option<EntityId>)> {
+        self.read(entity_id, field_path).await
+    }
+
+    async fn write(&mut self, entity_id: EntityId, field_path: &[FieldType], value: Value, writer_id: Option<EntityId>, write_time: Option<Timestamp>, push_condition: Option<PushCondition>, adjust_behavior: Option<AdjustBehavior>) -> Result<()> {
+        AsyncStoreProxy::write(self, entity_id, field_path, value, writer_id, write_time, push_condition, adjust_behavior).await
+    }
+
+    async fn create_entity(&mut self, entity_type: EntityType, parent_id: Option<EntityId>, name: &str) -> Result<EntityId> {
+        AsyncStoreProxy::create_entity(self, entity_type, parent_id, name).await
+    }
+
+    async fn delete_entity(&mut self, entity_id: EntityId) -> Result<()> {
+        AsyncStoreProxy::delete_entity(self, entity_id).await
+    }
+
+    async fn get_or_create(&mut self, entity_type: EntityType, parent_id: Option<EntityId>, name: &str) -> Result<(EntityId, bool)> {
+        AsyncStoreProxy::get_or_create(self, entity_type, parent_id, name).await
+    }
+
+    async fn upsert_field(&mut self, entity_id: EntityId, field_type: FieldType, value: Value) -> Result<bool> {
+        AsyncStoreProxy::upsert_field(self, entity_id, field_type, value).await
+    }
+
+    async fn rename_entity(&mut self, entity_id: EntityId, new_name: &str) -> Result<()> {
+        AsyncStoreProxy::rename_entity(self, entity_id, new_name).await
+    }
+
+    async fn update_schema(&mut self, schema: EntitySchema<Single, String, String>) -> Result<()> {
+        AsyncStoreProxy::update_schema(self, schema).await
+    }
+
+    async fn take_snapshot(&self) -> crate::data::Snapshot {
+        self.take_snapshot().await
+    }
+
+    async fn stats(&self) -> Result<crate::StoreStats> {
+        self.stats().await
+    }
+
+    async fn find_entities_paginated(&self, entity_type: EntityType, page_opts: Option<&PageOpts>, filter: Option<&str>) -> Result<PageResult<EntityId>> {
+        self.find_entities_paginated(entity_type, page_opts, filter).await
+    }
+
+    async fn find_entities_exact(&self, entity_type: EntityType, page_opts: Option<&PageOpts>, filter: Option<&str>) -> Result<PageResult<EntityId>> {
+        self.find_entities_exact(entity_type, page_opts, filter).await
+    }
+
+    async fn find_entities(&self, entity_type: EntityType, filter: Option<&str>) -> Result<Vec<EntityId>> {
+        self.find_entities(entity_type, filter).await
+    }
+
+    async fn find_referencing(&self, entity_id: EntityId) -> Result<Vec<(EntityId, FieldType)>> {
+        self.find_referencing(entity_id).await
+    }
+
+    async fn aggregate(&self, entity_type: EntityType, field_type: FieldType, op: AggregateOp, filter: Option<&str>) -> Result<Option<Value>> {
+        self.aggregate(entity_type, field_type, op, filter).await
+    }
+
+    async fn get_entity_types(&self) -> Result<Vec<EntityType>> {
+        self.get_entity_types().await
+    }
+
+    async fn get_entity_types_paginated(&self, page_opts: Option<&PageOpts>) -> Result<PageResult<EntityType>> {
+        self.get_entity_types_paginated(page_opts).await
+    }
 }
@@ -0,0 +1,260 @@
+use crate::{
+    AdjustBehavior, AggregateOp, Complete, EntityId, EntitySchema, EntityType, FieldSchema, FieldType, PageOpts, PageResult, PushCondition, Result, Single, StoreTrait, Timestamp, TypesBulk, Value
+};
+
+/// Async counterpart of `StoreTrait`, so code can be written once for
+/// `AsyncStoreProxy` and for local stores (through `AsyncStoreAdapter`).
+///
+/// The futures aren't required to be `Send`: a local `Store` isn't `Sync`,
+/// so code generic over this trait runs on a single task, e.g. under
+/// `tokio::task::LocalSet` or `block_on`.
+#[allow(async_fn_in_trait)]
+pub trait AsyncStoreTrait {
+    async fn get_entity_type(&self, name: &str) -> Result<EntityType>;
+
+    async fn resolve_entity_type(&self, entity_type: EntityType) -> Result<String>;
+
+    async fn get_field_type(&self, name: &str) -> Result<FieldType>;
+
+    async fn resolve_field_type(&self, field_type: FieldType) -> Result<String>;
+
+    /// Look up many entity and field type names at once, see `StoreTrait::get_types_bulk`
+    async fn get_types_bulk(&self, entity_types: &[&str], field_types: &[&str]) -> Result<TypesBulk> {
+        let mut entity_ids = Vec::with_capacity(entity_types.len());
+        for name in entity_types {
+            entity_ids.push(self.get_entity_type(name).await.ok());
+        }
+        let mut field_ids = Vec::with_capacity(field_types.len());
+        for name in field_types {
+            field_ids.push(self.get_field_type(name).await.ok());
+        }
+        Ok((entity_ids, field_ids))
+    }
+
+    /// Look up many field type names at once, see `get_types_bulk`
+    async fn get_field_types(&self, names: &[&str]) -> Result<Vec<Option<FieldType>>> {
+        Ok(self.get_types_bulk(&[], names).await?.1)
+    }
+
+    /// Get the schema for a specific entity type
+    async fn get_entity_schema(&self, entity_type: EntityType) -> Result<EntitySchema<Single>>;
+
+    /// Get the complete schema for a specific entity type (including
+    /// inherited fields). Unlike `StoreTrait` this returns an owned schema,
+    /// since a remote store has nothing to borrow from.
+    async fn get_complete_entity_schema(&self, entity_type: EntityType) -> Result<EntitySchema<Complete>>;
+
+    /// Get the schema for a specific field
+    async fn get_field_schema(&self, entity_type: EntityType, field_type: FieldType) -> Result<FieldSchema>;
+
+    /// Set or update the schema for a specific field
+    async fn set_field_schema(&mut self, entity_type: EntityType, field_type: FieldType, schema: FieldSchema) -> Result<()>;
+
+    /// Check if an entity exists
+    async fn entity_exists(&self, entity_id: EntityId) -> bool;
+
+    /// Check if a field type exists for an entity type
+    async fn field_exists(&self, entity_type: EntityType, field_type: FieldType) -> bool;
+
+    /// Resolve indirection for field paths
+    async fn resolve_indirection(&self, entity_id: EntityId, fields: &[FieldType]) -> Result<(EntityId, FieldType)>;
+
+    /// Read a field value
+    async fn read(&self, entity_id: EntityId, field_path: &[FieldType]) -> Result<(Value, Timestamp, Option<EntityId>)>;
+
+    /// Write a field value
+    #[allow(clippy::too_many_arguments)]
+    async fn write(&mut self, entity_id: EntityId, field_path: &[FieldType], value: Value, writer_id: Option<EntityId>, write_time: Option<Timestamp>, push_condition: Option<PushCondition>, adjust_behavior: Option<AdjustBehavior>) -> Result<()>;
+
+    /// Create a new entity
+    async fn create_entity(&mut self, entity_type: EntityType, parent_id: Option<EntityId>, name: &str) -> Result<EntityId>;
+
+    /// Delete an entity
+    async fn delete_entity(&mut self, entity_id: EntityId) -> Result<()>;
+
+    /// Find or create an entity by parent and name, see `StoreTrait::get_or_create`
+    async fn get_or_create(&mut self, entity_type: EntityType, parent_id: Option<EntityId>, name: &str) -> Result<(EntityId, bool)>;
+
+    /// Write a field unless it already holds `value`, see `StoreTrait::upsert_field`
+    async fn upsert_field(&mut self, entity_id: EntityId, field_type: FieldType, value: Value) -> Result<bool>;
+
+    /// Rename an entity, see `StoreTrait::rename_entity`
+    async fn rename_entity(&mut self, entity_id: EntityId, new_name: &str) -> Result<()>;
+
+    /// Update an entity schema
+    async fn update_schema(&mut self, schema: EntitySchema<Single, String, String>) -> Result<()>;
+
+    /// Take a snapshot of the current store state
+    async fn take_snapshot(&self) -> crate::data::Snapshot;
+
+    /// Size and usage figures, see `StoreTrait::stats`
+    async fn stats(&self) -> Result<crate::StoreStats>;
+
+    /// Find entities with pagination (includes derived types)
+    async fn find_entities_paginated(&self, entity_type: EntityType, page_opts: Option<&PageOpts>, filter: Option<&str>) -> Result<PageResult<EntityId>>;
+
+    /// Find entities of exactly the specified type (no inheritance) with pagination
+    async fn find_entities_exact(&self, entity_type: EntityType, page_opts: Option<&PageOpts>, filter: Option<&str>) -> Result<PageResult<EntityId>>;
+
+    /// Find all entities of a type (includes derived types)
+    async fn find_entities(&self, entity_type: EntityType, filter: Option<&str>) -> Result<Vec<EntityId>>;
+
+    /// Entities and fields referring to an entity, see `StoreTrait::find_referencing`
+    async fn find_referencing(&self, entity_id: EntityId) -> Result<Vec<(EntityId, FieldType)>>;
+
+    /// Count, sum or take the min/max of a field, see `StoreTrait::aggregate`
+    async fn aggregate(&self, entity_type: EntityType, field_type: FieldType, op: AggregateOp, filter: Option<&str>) -> Result<Option<Value>>;
+
+    /// Get all entity types
+    async fn get_entity_types(&self) -> Result<Vec<EntityType>>;
+
+    /// Get entity types with pagination
+    async fn get_entity_types_paginated(&self, page_opts: Option<&PageOpts>) -> Result<PageResult<EntityType>>;
+}
+
+/// Runs a synchronous store (a `Store`, `StoreProxy`, ...) behind
+/// `AsyncStoreTrait`. Each call completes immediately on the calling task.
+#[derive(Debug)]
+pub struct AsyncStoreAdapter<S> {
+    store: S,
+}
+
+impl<S: StoreTrait> AsyncStoreAdapter<S> {
+    pub fn new(store: S) -> Self {
+        Self { store }
+    }
+
+    pub fn inner(&self) -> &S {
+        &self.store
+    }
+
+    pub fn inner_mut(&mut self) -> &mut S {
+        &mut self.store
+    }
+
+    pub fn into_inner(self) -> S {
+        self.store
+    }
+}
+
+impl<S: StoreTrait> AsyncStoreTrait for AsyncStoreAdapter<S> {
+    async fn get_entity_type(&self, name: &str) -> Result<EntityType> {
+        self.store.get_entity_type(name)
+    }
+
+    async fn resolve_entity_type(&self, entity_type: EntityType) -> Result<String> {
+        self.store.resolve_entity_type(entity_type)
+    }
+
+    async fn get_field_type(&self, name: &str) -> Result<FieldType> {
+        self.store.get_field_type(name)
+    }
+
+    async fn resolve_field_type(&self, field_type: FieldType) -> Result<String> {
+        self.store.resolve_field_type(field_type)
+    }
+
+    async fn get_types_bulk(&self, entity_types: &[&str], field_types: &[&str]) -> Result<TypesBulk> {
+        self.store.get_types_bulk(entity_types, field_types)
+    }
+
+    async fn get_field_types(&self, names: &[&str]) -> Result<Vec<Option<FieldType>>> {
+        self.store.get_field_types(names)
+    }
+
+    async fn get_entity_schema(&self, entity_type: EntityType) -> Result<EntitySchema<Single>> {
+        self.store.get_entity_schema(entity_type)
+    }
+
+    async fn get_complete_entity_schema(&self, entity_type: EntityType) -> Result<EntitySchema<Complete>> {
+        self.store.get_complete_entity_schema_owned(entity_type)
+    }
+
+    async fn get_field_schema(&self, entity_type: EntityType, field_type: FieldType) -> Result<FieldSchema> {
+        self.store.get_field_schema(entity_type, field_type)
+    }
+
+    async fn set_field_schema(&mut self, entity_type: EntityType, field_type: FieldType, schema: FieldSchema) -> Result<()> {
+        self.store.set_field_schema(entity_type, field_type, schema)
+    }
+
+    async fn entity_exists(&self, entity_id: EntityId) -> bool {
+        self.store.entity_exists(entity_id)
+    }
+
+    async fn field_exists(&self, entity_type: EntityType, field_type: FieldType) -> bool {
+        self.store.field_exists(entity_type, field_type)
+    }
+
+    async fn resolve_indirection(&self, entity_id: EntityId, fields: &[FieldType]) -> Result<(EntityId, FieldType)> {
+        self.store.resolve_indirection(entity_id, fields)
+    }
+
+    async fn read(&self, entity_id: EntityId, field_path: &[FieldType]) -> Result<(Value, Timestamp, Option<EntityId>)> {
+        self.store.read(entity_id, field_path)
+    }
+
+    async fn write(&mut self, entity_id: EntityId, field_path: &[FieldType], value: Value, writer_id: Option<EntityId>, write_time: Option<Timestamp>, push_condition: Option<PushCondition>, adjust_behavior: Option<AdjustBehavior>) -> Result<()> {
+        self.store.write(entity_id, field_path, value, writer_id, write_time, push_condition, adjust_behavior)
+    }
+
+    async fn create_entity(&mut self, entity_type: EntityType, parent_id: Option<EntityId>, name: &str) -> Result<EntityId> {
+        self.store.create_entity(entity_type, parent_id, name)
+    }
+
+    async fn delete_entity(&mut self, entity_id: EntityId) -> Result<()> {
+        self.store.delete_entity(entity_id)
+    }
+
+    async fn get_or_create(&mut self, entity_type: EntityType, parent_id: Option<EntityId>, name: &str) -> Result<(EntityId, bool)> {
+        self.store.get_or_create(entity_type, parent_id, name)
+    }
+
+    async fn upsert_field(&mut self, entity_id: EntityId, field_type: FieldType, value: Value) -> Result<bool> {
+        self.store.upsert_field(entity_id, field_type, value)
+    }
+
+    async fn rename_entity(&mut self, entity_id: EntityId, new_name: &str) -> Result<()> {
+        self.store.rename_entity(entity_id, new_name)
+    }
+
+    async fn update_schema(&mut self, schema: EntitySchema<Single, String, String>) -> Result<()> {
+        self.store.update_schema(schema)
+    }
+
+    async fn take_snapshot(&self) -> crate::data::Snapshot {
+        self.store.take_snapshot()
+    }
+
+    async fn stats(&self) -> Result<crate::StoreStats> {
+        self.store.stats()
+    }
+
+    async fn find_entities_paginated(&self, entity_type: EntityType, page_opts: Option<&PageOpts>, filter: Option<&str>) -> Result<PageResult<EntityId>> {
+        self.store.find_entities_paginated(entity_type, page_opts, filter)
+    }
+
+    async fn find_entities_exact(&self, entity_type: EntityType, page_opts: Option<&PageOpts>, filter: Option<&str>) -> Result<PageResult<EntityId>> {
+        self.store.find_entities_exact(entity_type, page_opts, filter)
+    }
+
+    async fn find_entities(&self, entity_type: EntityType, filter: Option<&str>) -> Result<Vec<EntityId>> {
+        self.store.find_entities(entity_type, filter)
+    }
+
+    async fn find_referencing(&self, entity_id: EntityId) -> Result<Vec<(EntityId, FieldType)>> {
+        self.store.find_referencing(entity_id)
+    }
+
+    async fn aggregate(&self, entity_type: EntityType, field_type: FieldType, op: AggregateOp, filter: Option<&str>) -> Result<Option<Value>> {
+        self.store.aggregate(entity_type, field_type, op, filter)
+    }
+
+    async fn get_entity_types(&self) -> Result<Vec<EntityType>> {
+        self.store.get_entity_types()
+    }
+
+    async fn get_entity_types_paginated(&self, page_opts: Option<&PageOpts>) -> Result<PageResult<EntityType>> {
+        self.store.get_entity_types_paginated(page_opts)
+    }
+}
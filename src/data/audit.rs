@@ -0,0 +1,174 @@
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+use crossbeam::channel::Sender;
+use serde::{Deserialize, Serialize};
+
+use crate::{now, ClientContext, EntityId, Timestamp, WriteInfo};
+
+/// Default number of records kept in memory by `Store::enable_audit`
+pub const DEFAULT_AUDIT_CAPACITY: usize = 10_000;
+
+/// One audited change
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditRecord {
+    /// Sequence number, increasing by one per record
+    pub seq: u64,
+    /// When the store performed the change
+    pub timestamp: Timestamp,
+    /// The authenticated subject, or the write's writer if none was set
+    pub subject: Option<EntityId>,
+    pub connection: Option<String>,
+    pub write: WriteInfo,
+}
+
+impl AuditRecord {
+    /// The entity the change applies to, if any
+    pub fn entity_id(&self) -> Option<EntityId> {
+        match &self.write {
+            WriteInfo::FieldUpdate { entity_id, .. }
+            | WriteInfo::DeleteEntity { entity_id, .. }
+            | WriteInfo::SoftDeleteEntity { entity_id, .. }
+            | WriteInfo::RestoreEntity { entity_id, .. } => Some(*entity_id),
+            WriteInfo::CreateEntity { created_entity_id, .. } => Some(*created_entity_id),
+            WriteInfo::SchemaUpdate { .. } | WriteInfo::Snapshot { .. } => None,
+        }
+    }
+}
+
+/// Filters for `AuditLog::query`. Unset filters match everything.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AuditQuery {
+    /// Only records at or after this time
+    pub since: Option<Timestamp>,
+    /// Only records before this time
+    pub until: Option<Timestamp>,
+    pub entity_id: Option<EntityId>,
+    pub subject: Option<EntityId>,
+    /// Return at most this many records, the most recent ones
+    pub limit: Option<usize>,
+}
+
+impl AuditQuery {
+    pub fn matches(&self, record: &AuditRecord) -> bool {
+        self.since.is_none_or(|since| record.timestamp >= since)
+            && self.until.is_none_or(|until| record.timestamp < until)
+            && self.entity_id.is_none_or(|entity_id| record.entity_id() == Some(entity_id))
+            && self.subject.is_none_or(|subject| record.subject == Some(subject))
+    }
+}
+
+/// Somewhere audit records are sent as they are made, e.g. a file or an
+/// external log service. Sinks see every record, including the ones that
+/// have already dropped out of the in-memory buffer.
+pub trait AuditSink: Send {
+    fn record(&mut self, record: &AuditRecord);
+}
+
+/// Forward records to a channel, e.g. for a thread that writes them out
+impl AuditSink for Sender<AuditRecord> {
+    fn record(&mut self, record: &AuditRecord) {
+        let _ = self.send(record.clone());
+    }
+}
+
+/// The most recent audit records plus any sinks they are copied to
+pub struct AuditLog {
+    records: VecDeque<AuditRecord>,
+    capacity: usize,
+    next_seq: u64,
+    // Sinks are only used through `&mut self`; the mutex (never locked) just
+    // lets a `Store` holding the log be shared between threads
+    sinks: Vec<Mutex<Box<dyn AuditSink>>>,
+}
+
+impl std::fmt::Debug for AuditLog {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AuditLog")
+            .field("records", &self.records.len())
+            .field("capacity", &self.capacity)
+            .field("next_seq", &self.next_seq)
+            .field("sinks", &self.sinks.len())
+            .finish()
+    }
+}
+
+impl AuditLog {
+    /// Keep up to `capacity` records in memory; older ones are dropped
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            records: VecDeque::with_capacity(capacity.min(DEFAULT_AUDIT_CAPACITY)),
+            capacity,
+            next_seq: 0,
+            sinks: Vec::new(),
+        }
+    }
+
+    pub fn add_sink(&mut self, sink: Box<dyn AuditSink>) {
+        self.sinks.push(Mutex::new(sink));
+    }
+
+    /// Record a change made under `context`
+    pub fn record(&mut self, context: &ClientContext, write: &WriteInfo) {
+        let writer_id = match write {
+            WriteInfo::FieldUpdate { writer_id, .. } => *writer_id,
+            _ => None,
+        };
+
+        let record = AuditRecord {
+            seq: self.next_seq,
+            timestamp: now(),
+            subject: context.subject.or(writer_id),
+            connection: context.connection.clone(),
+            write: write.clone(),
+        };
+        self.next_seq += 1;
+
+        for sink in self.sinks.iter_mut() {
+            sink.get_mut().unwrap_or_else(|e| e.into_inner()).record(&record);
+        }
+
+        if self.capacity == 0 {
+            return;
+        }
+        if self.records.len() == self.capacity {
+            self.records.pop_front();
+        }
+        self.records.push_back(record);
+    }
+
+    /// Records in the buffer that match `query`, oldest first
+    pub fn query(&self, query: &AuditQuery) -> Vec<AuditRecord> {
+        let mut matching: Vec<AuditRecord> = self.records
+            .iter()
+            .rev()
+            .filter(|record| query.matches(record))
+            .take(query.limit.unwrap_or(usize::MAX))
+            .cloned()
+            .collect();
+        matching.reverse();
+        matching
+    }
+
+    /// Whether the buffer still has every record made at or after `since`,
+    /// i.e. nothing that recent has been dropped or cleared
+    pub fn covers(&self, since: Timestamp) -> bool {
+        let dropped = self.next_seq - self.records.len() as u64;
+        dropped == 0 || self.records.front().is_some_and(|record| record.timestamp < since)
+    }
+
+    /// Number of records in the buffer
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    /// Check if the buffer is empty
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    /// Drop the buffered records. Sequence numbers keep counting.
+    pub fn clear(&mut self) {
+        self.records.clear();
+    }
+}
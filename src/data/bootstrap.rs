@@ -0,0 +1,431 @@
+//! Declarative store bootstrap. A `Manifest` describes entity types and
+//! seed entity trees in TOML or YAML, using the same schema and entity
+//! format as a JSON snapshot. `apply` brings a store in line with it: new
+//! types and fields are added, changed ones updated, missing entities
+//! created and differing fields written. Entities are matched by path, so
+//! applying the same manifest twice changes nothing the second time.
+//! `plan` lists the same changes without making them.
+//!
+//! Manifests can be layered, e.g. a base shared by every deployment, then
+//! one per site, then one per machine. Later layers win: see
+//! `Manifest::merge`.
+//!
+//! ```toml
+//! [[schemas]]
+//! entityType = "Pump"
+//! inheritsFrom = ["Object"]
+//! fields = [{ name = "Speed", dataType = "Float", default = 0.0 }]
+//!
+//! [[entities]]
+//! entityType = "Root"
+//! Name = "Root"
+//!
+//! [[entities.Children]]
+//! entityType = "Pump"
+//! Name = "P1"
+//! Speed = 1200.0
+//! ```
+
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+
+use crate::data::json_snapshot::{json_schemas_to_string_schemas, json_value_to_value_with_resolution};
+use crate::data::store_trait::collect_field_schemas;
+use crate::{
+    ft, value_to_json_value, value_to_json_value_with_paths, EntityId, EntitySchema, Error, FieldSchema,
+    JsonEntity, JsonEntitySchema, Result, Single, StoreTrait, Value,
+};
+
+/// Entity types and seed entity trees a store should have
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Manifest {
+    #[serde(default)]
+    pub schemas: Vec<JsonEntitySchema>,
+    /// Top-level entities (usually just the Root), with their descendants
+    /// nested under `Children`
+    #[serde(default)]
+    pub entities: Vec<JsonEntity>,
+}
+
+impl Manifest {
+    pub fn from_toml(text: &str) -> Result<Self> {
+        toml::from_str(text).map_err(|e| Error::InvalidRequest(format!("Invalid manifest: {}", e)))
+    }
+
+    pub fn from_yaml(text: &str) -> Result<Self> {
+        serde_yaml::from_str(text).map_err(|e| Error::InvalidRequest(format!("Invalid manifest: {}", e)))
+    }
+
+    /// Read a manifest file, `.toml` or `.yaml`/`.yml`
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| Error::InvalidRequest(format!("Failed to read {}: {}", path.display(), e)))?;
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("toml") => Self::from_toml(&text),
+            Some("yaml") | Some("yml") => Self::from_yaml(&text),
+            _ => Err(Error::InvalidRequest(format!("Unknown manifest format: {}", path.display()))),
+        }
+    }
+
+    /// Read manifest files and merge them in order, base first
+    pub fn from_files<P: AsRef<Path>>(paths: impl IntoIterator<Item = P>) -> Result<Self> {
+        let mut manifest = Manifest::default();
+        for path in paths {
+            manifest.merge(Self::from_file(path)?)?;
+        }
+        Ok(manifest)
+    }
+
+    /// Layer `overlay` on top of this manifest. Where both say something,
+    /// the overlay wins:
+    ///
+    /// - Types are matched by name. The overlay's fields replace base fields
+    ///   of the same name whole and the rest are appended. A non-empty
+    ///   `inheritsFrom` replaces the base one.
+    /// - Entities are matched by name among their siblings. The overlay's
+    ///   type and field values replace the base ones and children are merged
+    ///   the same way. Unmatched entities are appended.
+    ///
+    /// Overlays only add and override; nothing in the base can be removed.
+    pub fn merge(&mut self, overlay: Manifest) -> Result<()> {
+        for schema in overlay.schemas {
+            match self.schemas.iter_mut().find(|base| base.entity_type == schema.entity_type) {
+                Some(base) => merge_schema(base, schema),
+                None => self.schemas.push(schema),
+            }
+        }
+        merge_entities(&mut self.entities, overlay.entities)
+    }
+}
+
+fn merge_schema(base: &mut JsonEntitySchema, overlay: JsonEntitySchema) {
+    if !overlay.inherits_from.is_empty() {
+        base.inherits_from = overlay.inherits_from;
+    }
+    for field in overlay.fields {
+        match base.fields.iter_mut().find(|base_field| base_field.name == field.name) {
+            Some(base_field) => *base_field = field,
+            None => base.fields.push(field),
+        }
+    }
+}
+
+fn entity_name(json_entity: &JsonEntity) -> Option<&str> {
+    json_entity.fields.get(ft::NAME).and_then(|name| name.as_str())
+}
+
+fn entity_children(json_entity: &JsonEntity) -> Result<Vec<JsonEntity>> {
+    match json_entity.fields.get(ft::CHILDREN) {
+        Some(children) => serde_json::from_value(children.clone()).map_err(|e| {
+            Error::InvalidRequest(format!("Invalid Children of {}: {}", entity_name(json_entity).unwrap_or_default(), e))
+        }),
+        None => Ok(Vec::new()),
+    }
+}
+
+fn merge_entities(base: &mut Vec<JsonEntity>, overlay: Vec<JsonEntity>) -> Result<()> {
+    for json_entity in overlay {
+        let name = entity_name(&json_entity).map(str::to_string);
+        match base.iter_mut().find(|base_entity| name.is_some() && entity_name(base_entity) == name.as_deref()) {
+            Some(base_entity) => merge_entity(base_entity, json_entity)?,
+            None => base.push(json_entity),
+        }
+    }
+    Ok(())
+}
+
+fn merge_entity(base: &mut JsonEntity, mut overlay: JsonEntity) -> Result<()> {
+    if overlay.fields.contains_key(ft::CHILDREN) {
+        let mut children = entity_children(base)?;
+        merge_entities(&mut children, entity_children(&overlay)?)?;
+        overlay.fields.remove(ft::CHILDREN);
+        let children = serde_json::to_value(children).map_err(|e| Error::InvalidRequest(e.to_string()))?;
+        base.fields.insert(ft::CHILDREN.to_string(), children);
+    }
+    base.entity_type = overlay.entity_type;
+    base.fields.extend(overlay.fields);
+    Ok(())
+}
+
+/// One difference between a manifest and a store
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    CreateType { entity_type: String },
+    /// An existing type whose inheritance or fields differ
+    UpdateType { entity_type: String, added: Vec<String>, changed: Vec<String>, removed: Vec<String> },
+    CreateEntity { path: String, entity_type: String },
+    /// A field to write; `from` is None when the entity or field is new
+    SetField { path: String, field: String, from: Option<JsonValue>, to: JsonValue },
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Change::CreateType { entity_type } => write!(f, "+ type {}", entity_type),
+            Change::UpdateType { entity_type, added, changed, removed } => {
+                let fields: Vec<String> = added
+                    .iter()
+                    .map(|name| format!("+{}", name))
+                    .chain(changed.iter().map(|name| format!("~{}", name)))
+                    .chain(removed.iter().map(|name| format!("-{}", name)))
+                    .collect();
+                if fields.is_empty() {
+                    write!(f, "~ type {} (inheritance)", entity_type)
+                } else {
+                    write!(f, "~ type {} ({})", entity_type, fields.join(", "))
+                }
+            }
+            Change::CreateEntity { path, entity_type } => write!(f, "+ {} ({})", path, entity_type),
+            Change::SetField { path, field, from: Some(from), to } => write!(f, "~ {}.{}: {} -> {}", path, field, from, to),
+            Change::SetField { path, field, from: None, to } => write!(f, "~ {}.{} = {}", path, field, to),
+        }
+    }
+}
+
+/// The changes `apply` makes (or `plan` would make), in order
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Plan {
+    pub changes: Vec<Change>,
+}
+
+impl Plan {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+impl fmt::Display for Plan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for change in &self.changes {
+            writeln!(f, "{}", change)?;
+        }
+        Ok(())
+    }
+}
+
+/// Bring the store in line with the manifest and return what was changed.
+///
+/// Schemas are updated first; fields are written once every entity exists,
+/// so references may point anywhere in the manifest. Types and entities the
+/// manifest doesn't mention are left alone, but fields dropped from a type
+/// listed in it are removed.
+pub fn apply<T: StoreTrait>(store: &mut T, manifest: &Manifest) -> Result<Plan> {
+    run(store, manifest, false)
+}
+
+/// The changes `apply` would make, without making them. Field values that
+/// refer to entities the manifest has yet to create show up as changes.
+pub fn plan<T: StoreTrait>(store: &mut T, manifest: &Manifest) -> Result<Plan> {
+    run(store, manifest, true)
+}
+
+struct PendingWrite {
+    entity_id: EntityId,
+    path: String,
+    field: String,
+    value: JsonValue,
+}
+
+fn run<T: StoreTrait>(store: &mut T, manifest: &Manifest, dry_run: bool) -> Result<Plan> {
+    let mut plan = Plan::default();
+
+    // Types the manifest inherits from without listing them must already
+    // exist; their fields come first
+    let mut max_ranks = HashMap::new();
+    for base in manifest.schemas.iter().flat_map(|schema| &schema.inherits_from) {
+        if manifest.schemas.iter().any(|schema| &schema.entity_type == base) || max_ranks.contains_key(base) {
+            continue;
+        }
+        let complete = store.get_complete_entity_schema_owned(store.get_entity_type(base)?)?;
+        let max_rank = complete.fields.values().map(|field_schema| field_schema.rank()).max().unwrap_or(-1);
+        max_ranks.insert(base.clone(), max_rank);
+    }
+
+    for schema in json_schemas_to_string_schemas(&manifest.schemas, max_ranks)? {
+        if let Some(change) = diff_schema(store, &schema)? {
+            if !dry_run {
+                store.update_schema(schema)?;
+            }
+            plan.changes.push(change);
+        }
+    }
+
+    let mut writes = Vec::new();
+    for json_entity in &manifest.entities {
+        sync_entity(store, json_entity, None, false, "", dry_run, &mut plan, &mut writes)?;
+    }
+
+    for write in writes {
+        let field_type = store.get_field_type(&write.field)?;
+        let field_schema = collect_field_schemas(store, write.entity_id.extract_type())?
+            .into_iter()
+            .find(|(schema_field_type, _)| *schema_field_type == field_type)
+            .map(|(_, field_schema)| field_schema)
+            .ok_or(Error::FieldTypeNotFound(write.entity_id, field_type))?;
+        let value = json_value_to_value_with_resolution(store, &write.value, &field_schema)
+            .map_err(|e| Error::InvalidFieldValue(format!("{}.{}: {}", write.path, write.field, e)))?;
+        store.write(write.entity_id, &[field_type], value, None, None, None, None)?;
+    }
+
+    Ok(plan)
+}
+
+fn diff_schema<T: StoreTrait>(store: &T, schema: &EntitySchema<Single, String, String>) -> Result<Option<Change>> {
+    let live = match store.get_entity_type(&schema.entity_type).and_then(|entity_type| store.get_entity_schema(entity_type)) {
+        Ok(live) => live.to_string_schema(store),
+        Err(Error::EntityTypeNotFound(_)) | Err(Error::EntityTypeStrNotFound(_)) => {
+            return Ok(Some(Change::CreateType { entity_type: schema.entity_type.clone() }));
+        }
+        Err(e) => return Err(e),
+    };
+
+    let mut added = Vec::new();
+    let mut changed = Vec::new();
+    for (name, field_schema) in &schema.fields {
+        match live.fields.get(name) {
+            None => added.push(name.clone()),
+            Some(live_field_schema) if live_field_schema != field_schema => changed.push(name.clone()),
+            Some(_) => {}
+        }
+    }
+    let mut removed: Vec<String> = live.fields.keys().filter(|name| !schema.fields.contains_key(*name)).cloned().collect();
+
+    if added.is_empty() && changed.is_empty() && removed.is_empty() && live.inherit == schema.inherit {
+        return Ok(None);
+    }
+    added.sort();
+    changed.sort();
+    removed.sort();
+    Ok(Some(Change::UpdateType { entity_type: schema.entity_type.clone(), added, changed, removed }))
+}
+
+/// Find the entity named `name` under `parent`, or at the top level
+fn find_entity<T: StoreTrait>(store: &T, parent: Option<EntityId>, name: &str, entity_type: &str) -> Result<Option<EntityId>> {
+    let name_ft = match store.get_field_type(ft::NAME) {
+        Ok(name_ft) => name_ft,
+        // Nothing has a name yet
+        Err(Error::FieldTypeStrNotFound(_)) => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    let candidates = match parent {
+        Some(parent) => match store.read(parent, &[store.get_field_type(ft::CHILDREN)?])?.0 {
+            Value::EntityList(children) => children,
+            _ => Vec::new(),
+        },
+        None => {
+            let entity_type = match store.get_entity_type(entity_type) {
+                Ok(entity_type) => entity_type,
+                Err(Error::EntityTypeStrNotFound(_)) => return Ok(None),
+                Err(e) => return Err(e),
+            };
+            let parent_ft = store.get_field_type(ft::PARENT)?;
+            let mut top_level = Vec::new();
+            for entity_id in store.find_entities(entity_type, None)? {
+                if store.read(entity_id, &[parent_ft])?.0 == Value::EntityReference(None) {
+                    top_level.push(entity_id);
+                }
+            }
+            top_level
+        }
+    };
+
+    for entity_id in candidates {
+        if let Value::String(candidate) = store.read(entity_id, &[name_ft])?.0 {
+            if candidate.as_str() == name {
+                let live_type = store.resolve_entity_type(entity_id.extract_type())?;
+                if live_type != entity_type {
+                    return Err(Error::InvalidRequest(format!("{} is a {}, not a {}", name, live_type, entity_type)));
+                }
+                return Ok(Some(entity_id));
+            }
+        }
+    }
+    Ok(None)
+}
+
+/// Diff one manifest entity and its children against the store. With
+/// `parent_planned` the parent doesn't exist yet (on a dry run), so neither
+/// does this entity.
+#[allow(clippy::too_many_arguments)]
+fn sync_entity<T: StoreTrait>(
+    store: &mut T,
+    json_entity: &JsonEntity,
+    parent: Option<EntityId>,
+    parent_planned: bool,
+    parent_path: &str,
+    dry_run: bool,
+    plan: &mut Plan,
+    writes: &mut Vec<PendingWrite>,
+) -> Result<()> {
+    let name = entity_name(json_entity).ok_or_else(|| {
+        Error::InvalidRequest(format!("Entity of type {} under '{}' has no Name", json_entity.entity_type, parent_path))
+    })?;
+    let path = if parent_path.is_empty() { name.to_string() } else { format!("{}/{}", parent_path, name) };
+
+    let existing = if parent_planned { None } else { find_entity(store, parent, name, &json_entity.entity_type)? };
+    let entity_id = match existing {
+        Some(entity_id) => Some(entity_id),
+        None => {
+            plan.changes.push(Change::CreateEntity { path: path.clone(), entity_type: json_entity.entity_type.clone() });
+            if dry_run {
+                None
+            } else {
+                let entity_type = store.get_entity_type(&json_entity.entity_type)?;
+                Some(store.create_entity(entity_type, parent, name)?)
+            }
+        }
+    };
+
+    // Every field given for a new entity is written, defaults or not
+    let schemas = match existing {
+        Some(entity_id) => collect_field_schemas(store, entity_id.extract_type())?,
+        None => Vec::new(),
+    };
+    for (field, wanted) in &json_entity.fields {
+        if field == ft::NAME || field == ft::CHILDREN {
+            continue;
+        }
+        // Fields the live schema doesn't have yet (dry run) count as different
+        let live = match (entity_id, store.get_field_type(field)) {
+            (Some(entity_id), Ok(field_type)) => match schemas.iter().find(|(schema_field_type, _)| *schema_field_type == field_type) {
+                Some((_, field_schema)) => Some((field_schema.clone(), store.read(entity_id, &[field_type])?.0)),
+                None => None,
+            },
+            _ => None,
+        };
+        let from = match &live {
+            Some((field_schema, value)) => match json_value_to_value_with_resolution(store, wanted, field_schema) {
+                Ok(wanted_value) if wanted_value == *value => continue,
+                _ => Some(live_json_value(store, value, field_schema)),
+            },
+            None => None,
+        };
+
+        plan.changes.push(Change::SetField { path: path.clone(), field: field.clone(), from, to: wanted.clone() });
+        if let Some(entity_id) = entity_id.filter(|_| !dry_run) {
+            writes.push(PendingWrite { entity_id, path: path.clone(), field: field.clone(), value: wanted.clone() });
+        }
+    }
+
+    for child in &entity_children(json_entity)? {
+        sync_entity(store, child, entity_id, entity_id.is_none(), &path, dry_run, plan, writes)?;
+    }
+    Ok(())
+}
+
+/// A live value the way a manifest would spell it
+fn live_json_value<T: StoreTrait>(store: &mut T, value: &Value, field_schema: &FieldSchema) -> JsonValue {
+    let choices = match field_schema {
+        FieldSchema::Choice { choices, choices_source: None, .. } => Some(choices),
+        _ => None,
+    };
+    match value {
+        Value::EntityReference(_) | Value::EntityList(_) => value_to_json_value_with_paths(store, value, choices),
+        _ => value_to_json_value(value, choices),
+    }
+}
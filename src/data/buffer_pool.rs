@@ -0,0 +1,72 @@
+use std::cell::RefCell;
+use std::ops::{Deref, DerefMut};
+
+/// Buffers each thread keeps for reuse
+pub const MAX_POOLED_BUFFERS: usize = 16;
+/// Buffers that grew past this are freed instead of pooled, so one large
+/// message doesn't pin its memory for the life of the thread
+pub const MAX_POOLED_CAPACITY: usize = 1024 * 1024;
+/// Capacity of a buffer when the pool has none to hand out
+const INITIAL_CAPACITY: usize = 512;
+
+thread_local! {
+    static POOL: RefCell<Vec<Vec<u8>>> = const { RefCell::new(Vec::new()) };
+}
+
+/// An empty buffer taken from the current thread's pool. It goes back to
+/// the pool of whichever thread drops it.
+#[derive(Debug)]
+pub struct PooledBuffer(Vec<u8>);
+
+impl PooledBuffer {
+    /// Keep the bytes instead of returning the buffer to the pool
+    pub fn into_vec(mut self) -> Vec<u8> {
+        std::mem::take(&mut self.0)
+    }
+}
+
+impl Deref for PooledBuffer {
+    type Target = Vec<u8>;
+
+    fn deref(&self) -> &Vec<u8> {
+        &self.0
+    }
+}
+
+impl DerefMut for PooledBuffer {
+    fn deref_mut(&mut self) -> &mut Vec<u8> {
+        &mut self.0
+    }
+}
+
+impl Drop for PooledBuffer {
+    fn drop(&mut self) {
+        let mut buffer = std::mem::take(&mut self.0);
+        if buffer.capacity() == 0 || buffer.capacity() > MAX_POOLED_CAPACITY {
+            return;
+        }
+        buffer.clear();
+        // The pool is gone if the thread is shutting down
+        let _ = POOL.try_with(|pool| {
+            let mut pool = pool.borrow_mut();
+            if pool.len() < MAX_POOLED_BUFFERS {
+                pool.push(buffer);
+            }
+        });
+    }
+}
+
+/// Take an empty buffer to encode a message into
+pub fn take_buffer() -> PooledBuffer {
+    let buffer = POOL
+        .try_with(|pool| pool.borrow_mut().pop())
+        .ok()
+        .flatten()
+        .unwrap_or_else(|| Vec::with_capacity(INITIAL_CAPACITY));
+    PooledBuffer(buffer)
+}
+
+/// Number of buffers waiting in the current thread's pool
+pub fn pooled_buffers() -> usize {
+    POOL.try_with(|pool| pool.borrow().len()).unwrap_or(0)
+}
@@ -4,7 +4,7 @@ use crossbeam::channel::{Receiver, Sender};
 use rustc_hash::FxHashMap;
 
 use crate::{
-    EntityId, EntityType, FieldType, Notification, NotifyConfig, NotifyInfo, StoreProxy, Value
+    AsyncStoreTrait, EntityId, EntityType, FieldType, Notification, NotifyConfig, NotifyInfo, PageOpts, StoreProxy, Timestamp, Value
 };
 
 #[derive(Debug)]
@@ -22,10 +22,16 @@ pub struct Cache {
     // For instance, an entity with a specific ID may have different values for the other fields.
     pub fields_by_entity_id: FxHashMap<EntityId, FxHashMap<FieldType, Value>>,
 
+    // When set, `write` forwards mutations to the store
+    write_through: bool,
+
     notify_receiver: Receiver<Notification>,
     notify_sender: Sender<Notification>,
 }
 
+/// Number of entities loaded per round trip by `Cache::preload`
+const PRELOAD_PAGE_SIZE: usize = 500;
+
 impl Cache {
     pub fn new(
         store: &StoreProxy,
@@ -43,6 +49,7 @@ impl Cache {
                     field_type: *field_type,
                     trigger_on_change: true,
                     context: vec![],
+                    filter: None,
                 },
                 sender.clone(),
             )?;
@@ -55,56 +62,108 @@ impl Cache {
                     field_type: *field_type,
                     trigger_on_change: true,
                     context: vec![],
+                    filter: None,
                 },
                 sender.clone(),
             )?;
         }
 
+        let mut cache = Cache {
+            entity_type,
+            index_fields,
+            other_fields,
+            entity_ids_by_index_fields: HashMap::new(),
+            fields_by_entity_id: FxHashMap::default(),
+            write_through: false,
+            notify_receiver: receiver.clone(),
+            notify_sender: sender.clone(),
+        };
+
         // Read initial values from the store
-        let mut entity_ids_by_index_fields = HashMap::new();
-        let mut fields_by_entity_id = FxHashMap::default();
-
-        let entity_ids = store.find_entities(entity_type, None)?;
-        for entity_id in entity_ids {
-            let mut index_values = Vec::new();
-            let mut other_values = Vec::new();
-
-            // Read index fields
-            for field in index_fields.iter() {
-                let (value, _, _) = store.read(entity_id, &[*field])?;
-                index_values.push(value);
+        let fields: Vec<FieldType> = cache.index_fields.iter().chain(cache.other_fields.iter()).copied().collect();
+        cache.preload(store, entity_type, &fields)?;
+
+        Ok((cache, receiver))
+    }
+
+    /// Bulk-load `fields` for every entity of `entity_type` (including
+    /// derived types), one page of entities per round trip. Usually
+    /// `entity_type` is the cache's own type; a derived type loads just those
+    /// entities. Returns the number of entities loaded.
+    pub fn preload(&mut self, store: &StoreProxy, entity_type: EntityType, fields: &[FieldType]) -> crate::Result<usize> {
+        if let Some(field) = fields.iter().find(|field| !self.tracks(**field)) {
+            return Err(crate::Error::CacheFieldNotFound(*field));
+        }
+
+        let mut page_opts = PageOpts::new(PRELOAD_PAGE_SIZE, None);
+        let mut loaded = 0;
+        loop {
+            let page = store.find_entities_paginated(entity_type, Some(&page_opts), None)?;
+
+            let mut pipeline = store.pipeline();
+            for entity_id in &page.items {
+                for field in fields {
+                    pipeline.read(*entity_id, &[*field])?;
+                }
+            }
+            let results = pipeline.execute()?;
+
+            let mut index = 0;
+            for entity_id in &page.items {
+                let mut values = Vec::with_capacity(fields.len());
+                for field in fields {
+                    let (value, _, _): (Value, Timestamp, Option<EntityId>) = results.get(index)?;
+                    values.push((*field, value));
+                    index += 1;
+                }
+                self.load_entity(*entity_id, values);
             }
-            
-            // Read other fields
-            for field in other_fields.iter() {
-                let (value, _, _) = store.read(entity_id, &[*field])?;
-                other_values.push(value);
+            loaded += page.items.len();
+
+            match page.next_cursor {
+                Some(cursor) => page_opts.cursor = Some(cursor),
+                None => break,
             }
+        }
 
-            let index_key = index_values.clone();
+        Ok(loaded)
+    }
 
-            let all_fields = index_fields.iter().cloned()
-                .zip(index_values.into_iter())
-                .chain(other_fields.iter().cloned().zip(other_values.into_iter()))
-                .collect::<FxHashMap<FieldType, Value>>();
+    /// `preload` from an async store. Fields are read one at a time rather
+    /// than pipelined.
+    pub async fn preload_async(&mut self, store: &impl AsyncStoreTrait, entity_type: EntityType, fields: &[FieldType]) -> crate::Result<usize> {
+        if let Some(field) = fields.iter().find(|field| !self.tracks(**field)) {
+            return Err(crate::Error::CacheFieldNotFound(*field));
+        }
 
-            entity_ids_by_index_fields
-                .entry(index_key)
-                .or_insert_with(Vec::new)
-                .push(entity_id);
+        let mut page_opts = PageOpts::new(PRELOAD_PAGE_SIZE, None);
+        let mut loaded = 0;
+        loop {
+            let page = store.find_entities_paginated(entity_type, Some(&page_opts), None).await?;
+            for entity_id in &page.items {
+                let mut values = Vec::with_capacity(fields.len());
+                for field in fields {
+                    let (value, _, _) = store.read(*entity_id, &[*field]).await?;
+                    values.push((*field, value));
+                }
+                self.load_entity(*entity_id, values);
+            }
+            loaded += page.items.len();
 
-            fields_by_entity_id.insert(entity_id, all_fields);
+            match page.next_cursor {
+                Some(cursor) => page_opts.cursor = Some(cursor),
+                None => break,
+            }
         }
 
-        Ok((Cache {
-            entity_type,
-            index_fields,
-            other_fields,
-            entity_ids_by_index_fields,
-            fields_by_entity_id,
-            notify_receiver: receiver.clone(),
-            notify_sender: sender.clone(),
-        }, receiver))
+        Ok(loaded)
+    }
+
+    /// Store freshly read field values for an entity and reindex it
+    fn load_entity(&mut self, entity_id: EntityId, values: Vec<(FieldType, Value)>) {
+        let old_index_key = self.index_key(entity_id);
+        self.fields_by_entity_id.entry(entity_id).or_default().extend(values);
+        self.reindex(entity_id, old_index_key);
     }
 }
 
@@ -189,6 +248,96 @@ impl Cache {
         });
     }
 
+    /// Whether `field_type` is one of the cached fields
+    pub fn tracks(&self, field_type: FieldType) -> bool {
+        self.index_fields.contains(&field_type) || self.other_fields.contains(&field_type)
+    }
+
+    /// The index key of a cached entity, if all of its index fields are known
+    fn index_key(&self, entity_id: EntityId) -> Option<Vec<Value>> {
+        let fields = self.fields_by_entity_id.get(&entity_id)?;
+        self.index_fields.iter().map(|field| fields.get(field).cloned()).collect()
+    }
+
+    /// Move an entity from its previous index key to its current one
+    fn reindex(&mut self, entity_id: EntityId, old_index_key: Option<Vec<Value>>) {
+        if let Some(old_index_key) = old_index_key {
+            if let Some(entity_ids) = self.entity_ids_by_index_fields.get_mut(&old_index_key) {
+                entity_ids.retain(|id| *id != entity_id);
+                if entity_ids.is_empty() {
+                    self.entity_ids_by_index_fields.remove(&old_index_key);
+                }
+            }
+        }
+
+        if let Some(new_index_key) = self.index_key(entity_id) {
+            let entity_ids = self.entity_ids_by_index_fields.entry(new_index_key).or_default();
+            if !entity_ids.contains(&entity_id) {
+                entity_ids.push(entity_id);
+            }
+        }
+    }
+
+    /// Enable or disable write-through mode, which lets `write` forward
+    /// mutations to the store. Caches are read-only views by default.
+    pub fn set_write_through(&mut self, enabled: bool) {
+        self.write_through = enabled;
+    }
+
+    pub fn is_write_through(&self) -> bool {
+        self.write_through
+    }
+
+    /// Write a field of an entity to the store and apply it to the cache
+    /// straight away instead of waiting for the notification.
+    /// Fails unless the cache is in write-through mode.
+    pub fn write(&mut self, store: &StoreProxy, entity_id: EntityId, field_type: FieldType, value: Value) -> crate::Result<()> {
+        self.check_write(field_type)?;
+        store.write(entity_id, &[field_type], value.clone(), None, None, None, None)?;
+        self.load_entity(entity_id, vec![(field_type, value)]);
+        Ok(())
+    }
+
+    /// `write` through an async store
+    pub async fn write_async(&mut self, store: &mut impl AsyncStoreTrait, entity_id: EntityId, field_type: FieldType, value: Value) -> crate::Result<()> {
+        self.check_write(field_type)?;
+        store.write(entity_id, &[field_type], value.clone(), None, None, None, None).await?;
+        self.load_entity(entity_id, vec![(field_type, value)]);
+        Ok(())
+    }
+
+    fn check_write(&self, field_type: FieldType) -> crate::Result<()> {
+        if !self.write_through {
+            return Err(crate::Error::InvalidRequest("Cache is not in write-through mode".to_string()));
+        }
+        if !self.tracks(field_type) {
+            return Err(crate::Error::CacheFieldNotFound(field_type));
+        }
+        Ok(())
+    }
+
+    /// Iterate over every cached entity and its field values
+    pub fn iter_entities(&self) -> impl Iterator<Item = (EntityId, &FxHashMap<FieldType, Value>)> {
+        self.fields_by_entity_id.iter().map(|(entity_id, fields)| (*entity_id, fields))
+    }
+
+    /// Iterate over the cached values of one field, with the entity each belongs to
+    pub fn values_for_field(&self, field_type: FieldType) -> impl Iterator<Item = (EntityId, &Value)> {
+        self.fields_by_entity_id
+            .iter()
+            .filter_map(move |(entity_id, fields)| fields.get(&field_type).map(|value| (*entity_id, value)))
+    }
+
+    /// Number of cached entities
+    pub fn len(&self) -> usize {
+        self.fields_by_entity_id.len()
+    }
+
+    /// Check if no entities are cached
+    pub fn is_empty(&self) -> bool {
+        self.fields_by_entity_id.is_empty()
+    }
+
     pub fn get_config_sender(&self) -> (Vec<NotifyConfig>, Sender<Notification>) {
         let mut configs = Vec::new();
 
@@ -199,6 +348,7 @@ impl Cache {
                 field_type: *field,
                 trigger_on_change: true,
                 context: vec![],
+                filter: None,
             };
             configs.push(config);
         }
@@ -210,6 +360,7 @@ impl Cache {
                 field_type: *field,
                 trigger_on_change: true,
                 context: vec![],
+                filter: None,
             };
             configs.push(config);
         }
@@ -0,0 +1,329 @@
+use std::cell::{Cell, RefCell};
+
+use crossbeam::channel::{Receiver, Sender};
+use rustc_hash::{FxHashMap, FxHashSet};
+
+use crate::data::StoreTrait;
+use crate::{
+    AdjustBehavior, AggregateOp, Complete, EntityId, EntitySchema, EntityType, FieldSchema, FieldType, Notification, NotifyConfig,
+    PageOpts, PageResult, PushCondition, Result, Single, StoreProxy, Timestamp, TypesBulk, Value,
+};
+
+/// A cached `read()` result: value, write time and writer
+type CachedRead = (Value, Timestamp, Option<EntityId>);
+
+/// Hit/miss counters for a `CachedStoreProxy`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    /// Reads answered from the cache
+    pub hits: u64,
+    /// Reads that went to the server
+    pub misses: u64,
+    /// Cached entries dropped because the field was written
+    pub invalidations: u64,
+}
+
+impl CacheStats {
+    /// Fraction of reads answered from the cache, 0.0 when nothing was read
+    pub fn hit_rate(&self) -> f64 {
+        let total = self.hits + self.misses;
+        if total == 0 {
+            0.0
+        } else {
+            self.hits as f64 / total as f64
+        }
+    }
+}
+
+/// A `StoreProxy` that remembers `read()` results.
+///
+/// The first read of a field subscribes to it (a `NotifyConfig::EntityId`
+/// that fires on every write) in the same round trip as the read itself.
+/// Later reads are answered locally until a notification for the field
+/// invalidates the entry.
+///
+/// Notifications are only taken off the socket while the proxy talks to the
+/// server, so tick-based services should call `process_notifications()` once
+/// per tick, the same way they would with a plain `StoreProxy`.
+///
+/// Only direct reads (a single field) are cached; reads through indirection
+/// always go to the server.
+#[derive(Debug)]
+pub struct CachedStoreProxy {
+    proxy: StoreProxy,
+    entries: RefCell<FxHashMap<(EntityId, FieldType), CachedRead>>,
+    subscribed: RefCell<FxHashSet<(EntityId, FieldType)>>,
+    notify_sender: Sender<Notification>,
+    notify_receiver: Receiver<Notification>,
+    stats: Cell<CacheStats>,
+}
+
+impl CachedStoreProxy {
+    /// Wrap an existing connection
+    pub fn new(proxy: StoreProxy) -> Self {
+        let (notify_sender, notify_receiver) = crossbeam::channel::unbounded();
+        Self {
+            proxy,
+            entries: RefCell::new(FxHashMap::default()),
+            subscribed: RefCell::new(FxHashSet::default()),
+            notify_sender,
+            notify_receiver,
+            stats: Cell::new(CacheStats::default()),
+        }
+    }
+
+    /// Connect to TCP server
+    pub fn connect(address: &str) -> Result<Self> {
+        Ok(Self::new(StoreProxy::connect(address)?))
+    }
+
+    /// The underlying proxy, e.g. for pipelines or notifications of your own
+    pub fn proxy(&self) -> &StoreProxy {
+        &self.proxy
+    }
+
+    /// Unwrap the underlying proxy. Its cache subscriptions stay registered.
+    pub fn into_inner(self) -> StoreProxy {
+        self.proxy
+    }
+
+    /// Current hit/miss counters
+    pub fn stats(&self) -> CacheStats {
+        self.stats.get()
+    }
+
+    /// Reset the hit/miss counters to zero
+    pub fn reset_stats(&self) {
+        self.stats.set(CacheStats::default());
+    }
+
+    /// Number of cached field values
+    pub fn len(&self) -> usize {
+        self.entries.borrow().len()
+    }
+
+    /// Check if nothing is cached
+    pub fn is_empty(&self) -> bool {
+        self.entries.borrow().is_empty()
+    }
+
+    /// Drop every cached value. Subscriptions are kept, so the next read of a
+    /// field only costs the read itself.
+    pub fn clear(&self) {
+        self.entries.borrow_mut().clear();
+    }
+
+    /// Read pending notifications from the server and invalidate the
+    /// entries they refer to
+    pub fn process_notifications(&self) -> Result<()> {
+        self.proxy.process_notifications()?;
+        self.apply_invalidations();
+        Ok(())
+    }
+
+    /// Drop the entries of fields written since the last call
+    fn apply_invalidations(&self) {
+        while let Ok(notification) = self.notify_receiver.try_recv() {
+            let entity_id = notification.current.entity_id;
+            if let Some(field_type) = notification.current.field_path.first() {
+                self.invalidate(entity_id, *field_type);
+            }
+        }
+    }
+
+    fn invalidate(&self, entity_id: EntityId, field_type: FieldType) {
+        if self.entries.borrow_mut().remove(&(entity_id, field_type)).is_some() {
+            let mut stats = self.stats.get();
+            stats.invalidations += 1;
+            self.stats.set(stats);
+        }
+    }
+
+    fn record(&self, hit: bool) {
+        let mut stats = self.stats.get();
+        if hit {
+            stats.hits += 1;
+        } else {
+            stats.misses += 1;
+        }
+        self.stats.set(stats);
+    }
+
+    /// Read a field, answering from the cache when possible
+    pub fn read(&self, entity_id: EntityId, field_path: &[FieldType]) -> Result<(Value, Timestamp, Option<EntityId>)> {
+        let &[field_type] = field_path else {
+            return self.proxy.read(entity_id, field_path);
+        };
+
+        self.apply_invalidations();
+        let key = (entity_id, field_type);
+        if let Some(cached) = self.entries.borrow().get(&key) {
+            self.record(true);
+            return Ok(cached.clone());
+        }
+        self.record(false);
+
+        let result = if self.subscribed.borrow().contains(&key) {
+            self.proxy.read(entity_id, field_path)?
+        } else {
+            // Subscribe before reading so no write can slip in between
+            let mut pipeline = self.proxy.pipeline();
+            pipeline.register_notification(
+                NotifyConfig::EntityId {
+                    entity_id,
+                    field_type,
+                    trigger_on_change: false,
+                    context: vec![],
+                    filter: None,
+                },
+                self.notify_sender.clone(),
+            )?;
+            pipeline.read(entity_id, field_path)?;
+            let results = pipeline.execute()?;
+            self.subscribed.borrow_mut().insert(key);
+            results.get(1)?
+        };
+
+        self.entries.borrow_mut().insert(key, result.clone());
+        Ok(result)
+    }
+
+    /// Write a field value, dropping any cached copy of it
+    #[allow(clippy::too_many_arguments)]
+    pub fn write(&self, entity_id: EntityId, field_path: &[FieldType], value: Value, writer_id: Option<EntityId>, write_time: Option<Timestamp>, push_condition: Option<PushCondition>, adjust_behavior: Option<AdjustBehavior>) -> Result<()> {
+        if let &[field_type] = field_path {
+            self.invalidate(entity_id, field_type);
+        }
+        self.proxy.write(entity_id, field_path, value, writer_id, write_time, push_condition, adjust_behavior)
+    }
+
+    /// Delete an entity, dropping every cached field of it
+    pub fn delete_entity(&self, entity_id: EntityId) -> Result<()> {
+        self.entries.borrow_mut().retain(|(cached_id, _), _| *cached_id != entity_id);
+        self.proxy.delete_entity(entity_id)
+    }
+}
+
+impl StoreTrait for CachedStoreProxy {
+    fn get_entity_type(&self, name: &str) -> Result<EntityType> {
+        self.proxy.get_entity_type(name)
+    }
+
+    fn resolve_entity_type(&self, entity_type: EntityType) -> Result<String> {
+        self.proxy.resolve_entity_type(entity_type)
+    }
+
+    fn get_field_type(&self, name: &str) -> Result<FieldType> {
+        self.proxy.get_field_type(name)
+    }
+
+    fn resolve_field_type(&self, field_type: FieldType) -> Result<String> {
+        self.proxy.resolve_field_type(field_type)
+    }
+
+    fn get_types_bulk(&self, entity_types: &[&str], field_types: &[&str]) -> Result<TypesBulk> {
+        self.proxy.get_types_bulk(entity_types, field_types)
+    }
+
+    fn get_entity_schema(&self, entity_type: EntityType) -> Result<EntitySchema<Single>> {
+        self.proxy.get_entity_schema(entity_type)
+    }
+
+    fn get_complete_entity_schema(&self, entity_type: EntityType) -> Result<&EntitySchema<Complete>> {
+        StoreTrait::get_complete_entity_schema(&self.proxy, entity_type)
+    }
+
+    fn get_complete_entity_schema_owned(&self, entity_type: EntityType) -> Result<EntitySchema<Complete>> {
+        self.proxy.get_complete_entity_schema(entity_type)
+    }
+
+    fn get_field_schema(&self, entity_type: EntityType, field_type: FieldType) -> Result<FieldSchema> {
+        self.proxy.get_field_schema(entity_type, field_type)
+    }
+
+    fn set_field_schema(&mut self, entity_type: EntityType, field_type: FieldType, schema: FieldSchema) -> Result<()> {
+        self.proxy.set_field_schema(entity_type, field_type, schema)
+    }
+
+    fn entity_exists(&self, entity_id: EntityId) -> bool {
+        self.proxy.entity_exists(entity_id)
+    }
+
+    fn field_exists(&self, entity_type: EntityType, field_type: FieldType) -> bool {
+        self.proxy.field_exists(entity_type, field_type)
+    }
+
+    fn resolve_indirection(&self, entity_id: EntityId, fields: &[FieldType]) -> Result<(EntityId, FieldType)> {
+        self.proxy.resolve_indirection(entity_id, fields)
+    }
+
+    fn read(&self, entity_id: EntityId, field_path: &[FieldType]) -> Result<(Value, Timestamp, Option<EntityId>)> {
+        CachedStoreProxy::read(self, entity_id, field_path)
+    }
+
+    fn write(&mut self, entity_id: EntityId, field_path: &[FieldType], value: Value, writer_id: Option<EntityId>, write_time: Option<Timestamp>, push_condition: Option<PushCondition>, adjust_behavior: Option<AdjustBehavior>) -> Result<()> {
+        CachedStoreProxy::write(self, entity_id, field_path, value, writer_id, write_time, push_condition, adjust_behavior)
+    }
+
+    fn create_entity(&mut self, entity_type: EntityType, parent_id: Option<EntityId>, name: &str) -> Result<EntityId> {
+        self.proxy.create_entity(entity_type, parent_id, name)
+    }
+
+    fn delete_entity(&mut self, entity_id: EntityId) -> Result<()> {
+        CachedStoreProxy::delete_entity(self, entity_id)
+    }
+
+    fn get_or_create(&mut self, entity_type: EntityType, parent_id: Option<EntityId>, name: &str) -> Result<(EntityId, bool)> {
+        self.proxy.get_or_create(entity_type, parent_id, name)
+    }
+
+    fn upsert_field(&mut self, entity_id: EntityId, field_type: FieldType, value: Value) -> Result<bool> {
+        self.invalidate(entity_id, field_type);
+        self.proxy.upsert_field(entity_id, field_type, value)
+    }
+
+    fn rename_entity(&mut self, entity_id: EntityId, new_name: &str) -> Result<()> {
+        self.invalidate(entity_id, self.proxy.get_field_type(crate::ft::NAME)?);
+        self.proxy.rename_entity(entity_id, new_name)
+    }
+
+    fn update_schema(&mut self, schema: EntitySchema<Single, String, String>) -> Result<()> {
+        self.proxy.update_schema(schema)
+    }
+
+    fn take_snapshot(&self) -> crate::data::Snapshot {
+        self.proxy.take_snapshot()
+    }
+
+    fn stats(&self) -> Result<crate::StoreStats> {
+        self.proxy.stats()
+    }
+
+    fn find_entities_paginated(&self, entity_type: EntityType, page_opts: Option<&PageOpts>, filter: Option<&str>) -> Result<PageResult<EntityId>> {
+        self.proxy.find_entities_paginated(entity_type, page_opts, filter)
+    }
+
+    fn find_entities_exact(&self, entity_type: EntityType, page_opts: Option<&PageOpts>, filter: Option<&str>) -> Result<PageResult<EntityId>> {
+        self.proxy.find_entities_exact(entity_type, page_opts, filter)
+    }
+
+    fn find_entities(&self, entity_type: EntityType, filter: Option<&str>) -> Result<Vec<EntityId>> {
+        self.proxy.find_entities(entity_type, filter)
+    }
+
+    fn find_referencing(&self, entity_id: EntityId) -> Result<Vec<(EntityId, FieldType)>> {
+        self.proxy.find_referencing(entity_id)
+    }
+
+    fn aggregate(&self, entity_type: EntityType, field_type: FieldType, op: AggregateOp, filter: Option<&str>) -> Result<Option<Value>> {
+        self.proxy.aggregate(entity_type, field_type, op, filter)
+    }
+
+    fn get_entity_types(&self) -> Result<Vec<EntityType>> {
+        self.proxy.get_entity_types()
+    }
+
+    fn get_entity_types_paginated(&self, page_opts: Option<&PageOpts>) -> Result<PageResult<EntityType>> {
+        self.proxy.get_entity_types_paginated(page_opts)
+    }
+}
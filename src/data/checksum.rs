@@ -0,0 +1,207 @@
+use std::collections::BTreeMap;
+
+use rustc_hash::FxHashMap;
+use serde::{Deserialize, Serialize};
+use sorted_vec::SortedVec;
+
+use crate::{EntityId, EntitySchema, EntityType, Error, Field, FieldType, JsonSnapshot, Result, Single, Snapshot};
+use crate::data::series::Samples;
+
+/// Content hashes of a store's state: one per entity type, covering its
+/// schema, entities, fields (value, write time and writer) and series
+/// history, and one over all of them. Two stores or snapshots with the same
+/// checksum hold the same state.
+///
+/// The hash is FNV-1a over a canonical encoding (ids in order, JSON with
+/// sorted keys), so it is stable across processes and platforms.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotChecksum {
+    pub entity_types: BTreeMap<String, u64>,
+    pub total: u64,
+}
+
+impl SnapshotChecksum {
+    fn from_entity_types(entity_types: BTreeMap<String, u64>) -> Self {
+        let mut hasher = Fnv64::new();
+        for (name, hash) in &entity_types {
+            hasher.write_str(name);
+            hasher.write(&hash.to_le_bytes());
+        }
+        Self {
+            total: hasher.finish(),
+            entity_types,
+        }
+    }
+
+    /// Entity types whose hash differs from `other`'s, including the ones
+    /// only one of them has
+    pub fn mismatches(&self, other: &SnapshotChecksum) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .entity_types
+            .iter()
+            .filter(|(name, hash)| other.entity_types.get(*name) != Some(*hash))
+            .map(|(name, _)| name.clone())
+            .collect();
+        names.extend(other.entity_types.keys().filter(|name| !self.entity_types.contains_key(*name)).cloned());
+        names.sort();
+        names
+    }
+}
+
+/// 64-bit FNV-1a
+struct Fnv64(u64);
+
+impl Fnv64 {
+    fn new() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    /// Length-prefixed, so consecutive strings can't run into each other
+    fn write_str(&mut self, s: &str) {
+        self.write(&(s.len() as u64).to_le_bytes());
+        self.write(s.as_bytes());
+    }
+
+    /// Serialized as JSON, whose maps come out with sorted keys
+    fn write_json<T: Serialize>(&mut self, value: &T) {
+        let json = serde_json::to_value(value).unwrap_or(serde_json::Value::Null);
+        self.write_str(&json.to_string());
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+/// Checksum of a store's state, from its parts. `fields` and `series` may
+/// come in any order.
+pub(crate) fn compute<'a>(
+    schemas: &FxHashMap<EntityType, EntitySchema<Single>>,
+    entities: &FxHashMap<EntityType, SortedVec<EntityId>>,
+    entity_type_name: impl Fn(EntityType) -> String,
+    fields: impl Iterator<Item = (EntityId, FieldType, &'a Field)>,
+    series: impl Iterator<Item = (EntityId, FieldType, &'a Samples)>,
+) -> SnapshotChecksum {
+    let fields: BTreeMap<(EntityId, FieldType), &Field> = fields.map(|(entity_id, field_type, field)| ((entity_id, field_type), field)).collect();
+    let series: BTreeMap<(EntityId, FieldType), &Samples> = series.map(|(entity_id, field_type, samples)| ((entity_id, field_type), samples)).collect();
+
+    let mut entity_types: Vec<EntityType> = schemas.keys().chain(entities.keys()).copied().collect();
+    entity_types.sort_by_key(|entity_type| entity_type.0);
+    entity_types.dedup();
+
+    let mut hashes = BTreeMap::new();
+    for entity_type in entity_types {
+        let mut hasher = Fnv64::new();
+        hasher.write_json(&schemas.get(&entity_type));
+
+        let ids = entities.get(&entity_type).map(|ids| ids.as_slice()).unwrap_or_default();
+        hasher.write(&(ids.len() as u64).to_le_bytes());
+        for entity_id in ids {
+            hasher.write(&entity_id.0.to_le_bytes());
+        }
+
+        // Entity ids start with their type, so each type's fields are one range
+        let first = (EntityId::new(entity_type, 0), FieldType(0));
+        let in_type = |(entity_id, _): &&(EntityId, FieldType)| entity_id.extract_type() == entity_type;
+        for ((entity_id, field_type), field) in fields.range(first..).take_while(|(key, _)| in_type(key)) {
+            hasher.write(&entity_id.0.to_le_bytes());
+            hasher.write(&field_type.0.to_le_bytes());
+            hasher.write_json(field);
+        }
+        for ((entity_id, field_type), samples) in series.range(first..).take_while(|(key, _)| in_type(key)) {
+            hasher.write(&entity_id.0.to_le_bytes());
+            hasher.write(&field_type.0.to_le_bytes());
+            hasher.write_json(samples);
+        }
+
+        hashes.insert(entity_type_name(entity_type), hasher.finish());
+    }
+
+    SnapshotChecksum::from_entity_types(hashes)
+}
+
+/// Checksum of a snapshot's content
+pub(crate) fn snapshot_checksum(snapshot: &Snapshot) -> SnapshotChecksum {
+    compute(
+        &snapshot.schemas,
+        &snapshot.entities,
+        |entity_type| {
+            snapshot
+                .entity_type_interner
+                .resolve(entity_type.0 as u64)
+                .cloned()
+                .unwrap_or_else(|| entity_type.0.to_string())
+        },
+        snapshot
+            .fields
+            .iter()
+            .flat_map(|(entity_id, fields)| fields.iter().map(move |(field_type, field)| (*entity_id, *field_type, field))),
+        snapshot
+            .series
+            .iter()
+            .flat_map(|(entity_id, series)| series.iter().map(move |(field_type, samples)| (*entity_id, *field_type, samples))),
+    )
+}
+
+/// Checksum of a JSON snapshot's content: per entity type, its schema and
+/// its entities in tree order. JSON snapshots carry no ids or write times,
+/// so this only compares against other JSON snapshots.
+pub(crate) fn json_snapshot_checksum(snapshot: &JsonSnapshot) -> SnapshotChecksum {
+    let mut hashers: BTreeMap<String, Fnv64> = BTreeMap::new();
+    for schema in &snapshot.schemas {
+        hashers.entry(schema.entity_type.clone()).or_insert_with(Fnv64::new).write_json(schema);
+    }
+
+    let tree = serde_json::to_value(&snapshot.tree).unwrap_or(serde_json::Value::Null);
+    hash_json_entity(&tree, &mut hashers);
+
+    SnapshotChecksum::from_entity_types(hashers.into_iter().map(|(name, hasher)| (name, hasher.finish())).collect())
+}
+
+/// Hash an entity of a JSON tree (without its children) into its type's
+/// hash, then its children depth first
+fn hash_json_entity(entity: &serde_json::Value, hashers: &mut BTreeMap<String, Fnv64>) {
+    let Some(fields) = entity.as_object() else {
+        return;
+    };
+    let entity_type = fields.get("entityType").and_then(|entity_type| entity_type.as_str()).unwrap_or_default();
+
+    let mut own_fields = fields.clone();
+    let children = own_fields.remove("Children");
+    hashers.entry(entity_type.to_string()).or_insert_with(Fnv64::new).write_json(&own_fields);
+
+    if let Some(serde_json::Value::Array(children)) = children {
+        for child in &children {
+            hash_json_entity(child, hashers);
+        }
+    }
+}
+
+fn verify(expected: Option<&SnapshotChecksum>, actual: SnapshotChecksum) -> Result<SnapshotChecksum> {
+    match expected {
+        Some(expected) if *expected != actual => Err(Error::ChecksumMismatch(expected.mismatches(&actual))),
+        _ => Ok(actual),
+    }
+}
+
+/// Check a snapshot's content against the checksum it was taken with, e.g.
+/// after reading it back from disk or receiving it from a peer. Returns the
+/// content's checksum, to compare with the restored store's
+/// `Store::checksum`. A snapshot without a checksum passes unchecked.
+pub fn verify_snapshot(snapshot: &Snapshot) -> Result<SnapshotChecksum> {
+    verify(snapshot.checksum.as_ref(), snapshot_checksum(snapshot))
+}
+
+/// Check a JSON snapshot's content against the checksum it was taken with.
+/// Returns the content's checksum, to compare with the checksum of a JSON
+/// snapshot taken from the restored store.
+pub fn verify_json_snapshot(snapshot: &JsonSnapshot) -> Result<SnapshotChecksum> {
+    verify(snapshot.checksum.as_ref(), json_snapshot_checksum(snapshot))
+}
@@ -0,0 +1,93 @@
+#[cfg(feature = "rkyv")]
+pub mod rkyv;
+
+use crate::data::resp::ProtocolLimits;
+use crate::Result;
+
+/// Size of the length prefix on a binary frame
+pub const FRAME_HEADER_SIZE: usize = 4;
+
+/// How commands and responses are encoded on a connection.
+///
+/// Every connection starts out speaking RESP. A client switches with the
+/// `CODEC` command (`resp::CodecCommand`); once the server has answered
+/// `OK`, both sides use the new codec for the rest of the connection. A
+/// server that doesn't support the codec answers `ERR_UNSUPPORTED` and the
+/// connection stays on RESP.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Codec {
+    #[default]
+    Resp,
+    /// RESP with entity ids, entity types and field types sent as fixed
+    /// width binary (`resp::BINARY_ID_MARKER`) instead of integers
+    RespBinaryIds,
+    /// `codec::rkyv` messages in binary frames. Needs the `rkyv` feature.
+    Rkyv,
+}
+
+impl Codec {
+    /// Name sent in the `CODEC` command
+    pub fn name(&self) -> &'static str {
+        match self {
+            Codec::Resp => "resp",
+            Codec::RespBinaryIds => "resp-binary-ids",
+            Codec::Rkyv => "rkyv",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "resp" => Some(Codec::Resp),
+            "resp-binary-ids" => Some(Codec::RespBinaryIds),
+            "rkyv" => Some(Codec::Rkyv),
+            _ => None,
+        }
+    }
+
+    /// Check if the codec keeps RESP framing, so a proxy can switch to it
+    pub fn is_resp(&self) -> bool {
+        matches!(self, Codec::Resp | Codec::RespBinaryIds)
+    }
+
+    /// Check if this build can speak the codec
+    pub fn is_supported(&self) -> bool {
+        match self {
+            Codec::Resp | Codec::RespBinaryIds => true,
+            Codec::Rkyv => cfg!(feature = "rkyv"),
+        }
+    }
+}
+
+impl std::fmt::Display for Codec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Prefix `payload` with its length (`u32`, little endian) to make a binary frame
+pub fn encode_frame(payload: &[u8]) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(FRAME_HEADER_SIZE + payload.len());
+    frame.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    frame.extend_from_slice(payload);
+    frame
+}
+
+/// Split the binary frame at the head of `input` into its payload and the
+/// bytes after it. Returns `None` until the whole frame has arrived, and
+/// fails with `Error::FrameTooLarge` as soon as the header announces a frame
+/// over the limit.
+pub fn decode_frame<'a>(input: &'a [u8], limits: &ProtocolLimits) -> Result<Option<(&'a [u8], &'a [u8])>> {
+    let Some(header) = input.get(..FRAME_HEADER_SIZE) else {
+        return Ok(None);
+    };
+    let mut len_bytes = [0u8; FRAME_HEADER_SIZE];
+    len_bytes.copy_from_slice(header);
+    let len = u32::from_le_bytes(len_bytes) as usize;
+    limits.check_frame(len)?;
+
+    let rest = &input[FRAME_HEADER_SIZE..];
+    if rest.len() < len {
+        return Ok(None);
+    }
+    Ok(Some(rest.split_at(len)))
+}
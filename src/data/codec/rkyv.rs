@@ -0,0 +1,439 @@
+//! Binary codec built on rkyv.
+//!
+//! Commands and responses are archived with rkyv and sent in length
+//! prefixed frames (`codec::encode_frame`). A server can check a frame once
+//! and then read the archived command in place with [`access_command`],
+//! without parsing numbers out of strings the way RESP requires.
+//!
+//! The wire types mirror the RESP commands with plain integers for ids and
+//! types. Commands without a native variant travel as `StoreCommand::Resp`,
+//! so every command keeps working on a connection that switched codecs.
+
+use ::rkyv::rancor;
+use ::rkyv::util::AlignedVec;
+use ::rkyv::{Archive, Deserialize, Serialize};
+
+use crate::data::codec::encode_frame;
+use crate::data::resp::{
+    BooleanResponse, CreateEntityCommand, CreateEntityResponse, DeleteEntityCommand, EntityExistsCommand,
+    EntityListResponse, FindEntitiesCommand, ProtocolError, ReadCommand, ReadResponse, RespEncode, RespToBytes,
+    WriteCommand,
+};
+use crate::{AdjustBehavior, Decimal, EntityId, EntityType, FieldType, PushCondition, Result, Timestamp, Value};
+
+/// Alignment rkyv needs for the buffer a frame is accessed in
+pub const FRAME_ALIGNMENT: usize = 16;
+
+/// `Value` as archived on the wire
+#[derive(Archive, Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[rkyv(serialize_bounds(
+    __S: ::rkyv::ser::Writer + ::rkyv::ser::Allocator,
+    __S::Error: rancor::Source,
+))]
+#[rkyv(deserialize_bounds(__D::Error: rancor::Source))]
+#[rkyv(bytecheck(bounds(__C: ::rkyv::validation::ArchiveContext)))]
+pub enum WireValue {
+    Blob(Vec<u8>),
+    Bool(bool),
+    Choice(i64),
+    EntityList(Vec<u64>),
+    EntityReference(Option<u64>),
+    Float(f64),
+    Int(i64),
+    String(String),
+    /// Nanoseconds since the unix epoch
+    Timestamp(i128),
+    Decimal { mantissa: i64, scale: u32 },
+    Duration { seconds: i64, nanoseconds: i32 },
+    StringList(Vec<String>),
+    Map(#[rkyv(omit_bounds)] Vec<(String, WireValue)>),
+}
+
+/// Command sent to the server
+#[derive(Archive, Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum StoreCommand {
+    Read {
+        entity_id: u64,
+        field_path: Vec<u64>,
+    },
+    Write {
+        entity_id: u64,
+        field_path: Vec<u64>,
+        value: WireValue,
+        writer_id: Option<u64>,
+        /// Nanoseconds since the unix epoch
+        write_time: Option<i128>,
+        push_condition: Option<u8>,
+        adjust_behavior: Option<u8>,
+    },
+    CreateEntity {
+        entity_type: u32,
+        parent_id: Option<u64>,
+        name: String,
+    },
+    DeleteEntity {
+        entity_id: u64,
+    },
+    EntityExists {
+        entity_id: u64,
+    },
+    FindEntities {
+        entity_type: u32,
+        filter: Option<String>,
+        timeout_ms: Option<u64>,
+        request_id: Option<u64>,
+    },
+    /// Any other command, RESP encoded
+    Resp(Vec<u8>),
+}
+
+/// Reply to a `StoreCommand`
+#[derive(Archive, Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum StoreResponse {
+    Ok,
+    Read {
+        value: WireValue,
+        /// Nanoseconds since the unix epoch
+        timestamp: i128,
+        writer_id: Option<u64>,
+    },
+    EntityId(u64),
+    EntityIds(Vec<u64>),
+    Bool(bool),
+    /// The text of the RESP error frame the command would have failed with,
+    /// e.g. `ERR_TIMEOUT after 50 ms`; see `resp::error_from_frame`
+    Error(String),
+    /// Reply to `StoreCommand::Resp`, RESP encoded
+    Resp(Vec<u8>),
+}
+
+fn malformed(e: rancor::Error) -> crate::Error {
+    ProtocolError::MalformedFrame(e.to_string()).into()
+}
+
+fn timestamp_to_wire(timestamp: &Timestamp) -> i128 {
+    timestamp.unix_timestamp_nanos()
+}
+
+fn timestamp_from_wire(nanos: i128) -> Result<Timestamp> {
+    Timestamp::from_unix_timestamp_nanos(nanos)
+        .map_err(|e| ProtocolError::MalformedFrame(format!("Timestamp out of range: {}", e)).into())
+}
+
+fn push_condition_to_wire(condition: &PushCondition) -> u8 {
+    match condition {
+        PushCondition::Always => 0,
+        PushCondition::Changes => 1,
+    }
+}
+
+fn push_condition_from_wire(condition: u8) -> Result<PushCondition> {
+    match condition {
+        0 => Ok(PushCondition::Always),
+        1 => Ok(PushCondition::Changes),
+        other => Err(ProtocolError::MalformedFrame(format!("Unknown push condition {}", other)).into()),
+    }
+}
+
+fn adjust_behavior_to_wire(behavior: &AdjustBehavior) -> u8 {
+    match behavior {
+        AdjustBehavior::Set => 0,
+        AdjustBehavior::Add => 1,
+        AdjustBehavior::Subtract => 2,
+    }
+}
+
+fn adjust_behavior_from_wire(behavior: u8) -> Result<AdjustBehavior> {
+    match behavior {
+        0 => Ok(AdjustBehavior::Set),
+        1 => Ok(AdjustBehavior::Add),
+        2 => Ok(AdjustBehavior::Subtract),
+        other => Err(ProtocolError::MalformedFrame(format!("Unknown adjust behavior {}", other)).into()),
+    }
+}
+
+impl From<&Value> for WireValue {
+    fn from(value: &Value) -> Self {
+        match value {
+            Value::Blob(b) => WireValue::Blob(b.clone()),
+            Value::Bool(b) => WireValue::Bool(*b),
+            Value::Choice(c) => WireValue::Choice(*c),
+            Value::EntityList(list) => WireValue::EntityList(list.iter().map(|id| id.0).collect()),
+            Value::EntityReference(reference) => WireValue::EntityReference(reference.map(|id| id.0)),
+            Value::Float(f) => WireValue::Float(*f),
+            Value::Int(i) => WireValue::Int(*i),
+            Value::String(s) => WireValue::String(s.clone()),
+            Value::Timestamp(t) => WireValue::Timestamp(timestamp_to_wire(t)),
+            Value::Decimal(d) => WireValue::Decimal { mantissa: d.mantissa(), scale: d.scale() },
+            Value::Duration(d) => WireValue::Duration { seconds: d.whole_seconds(), nanoseconds: d.subsec_nanoseconds() },
+            Value::StringList(list) => WireValue::StringList(list.clone()),
+            Value::Map(map) => WireValue::Map(map.iter().map(|(k, v)| (k.clone(), v.into())).collect()),
+        }
+    }
+}
+
+impl TryFrom<WireValue> for Value {
+    type Error = crate::Error;
+
+    fn try_from(value: WireValue) -> Result<Self> {
+        Ok(match value {
+            WireValue::Blob(b) => Value::Blob(b),
+            WireValue::Bool(b) => Value::Bool(b),
+            WireValue::Choice(c) => Value::Choice(c),
+            WireValue::EntityList(list) => Value::EntityList(list.into_iter().map(EntityId).collect()),
+            WireValue::EntityReference(reference) => Value::EntityReference(reference.map(EntityId)),
+            WireValue::Float(f) => Value::Float(f),
+            WireValue::Int(i) => Value::Int(i),
+            WireValue::String(s) => Value::String(s),
+            WireValue::Timestamp(nanos) => Value::Timestamp(timestamp_from_wire(nanos)?),
+            WireValue::Decimal { mantissa, scale } => Value::Decimal(Decimal::new(mantissa, scale)?),
+            WireValue::Duration { seconds, nanoseconds } => Value::Duration(crate::Duration::new(seconds, nanoseconds)),
+            WireValue::StringList(list) => Value::StringList(list),
+            WireValue::Map(map) => Value::Map(
+                map.into_iter()
+                    .map(|(k, v)| Ok((k, Value::try_from(v)?)))
+                    .collect::<Result<_>>()?,
+            ),
+        })
+    }
+}
+
+impl From<&ReadCommand<'_>> for StoreCommand {
+    fn from(command: &ReadCommand<'_>) -> Self {
+        StoreCommand::Read {
+            entity_id: command.entity_id.0,
+            field_path: command.field_path.iter().map(|ft| ft.0).collect(),
+        }
+    }
+}
+
+impl From<&WriteCommand<'_>> for StoreCommand {
+    fn from(command: &WriteCommand<'_>) -> Self {
+        StoreCommand::Write {
+            entity_id: command.entity_id.0,
+            field_path: command.field_path.iter().map(|ft| ft.0).collect(),
+            value: (&command.value).into(),
+            writer_id: command.writer_id.map(|id| id.0),
+            write_time: command.write_time.as_ref().map(timestamp_to_wire),
+            push_condition: command.push_condition.as_ref().map(push_condition_to_wire),
+            adjust_behavior: command.adjust_behavior.as_ref().map(adjust_behavior_to_wire),
+        }
+    }
+}
+
+impl From<&CreateEntityCommand<'_>> for StoreCommand {
+    fn from(command: &CreateEntityCommand<'_>) -> Self {
+        StoreCommand::CreateEntity {
+            entity_type: command.entity_type.0,
+            parent_id: command.parent_id.map(|id| id.0),
+            name: command.name.clone(),
+        }
+    }
+}
+
+impl From<&DeleteEntityCommand<'_>> for StoreCommand {
+    fn from(command: &DeleteEntityCommand<'_>) -> Self {
+        StoreCommand::DeleteEntity { entity_id: command.entity_id.0 }
+    }
+}
+
+impl From<&EntityExistsCommand<'_>> for StoreCommand {
+    fn from(command: &EntityExistsCommand<'_>) -> Self {
+        StoreCommand::EntityExists { entity_id: command.entity_id.0 }
+    }
+}
+
+impl From<&FindEntitiesCommand<'_>> for StoreCommand {
+    fn from(command: &FindEntitiesCommand<'_>) -> Self {
+        StoreCommand::FindEntities {
+            entity_type: command.entity_type.0,
+            filter: command.filter.clone(),
+            timeout_ms: command.timeout_ms,
+            request_id: command.request_id,
+        }
+    }
+}
+
+impl StoreCommand {
+    /// Encode as the RESP command this stands for, so a server can run it
+    /// through the same code that serves RESP connections
+    pub fn into_resp_command(self) -> Result<Vec<u8>> {
+        let marker = std::marker::PhantomData;
+        let encoded = match self {
+            StoreCommand::Read { entity_id, field_path } => ReadCommand {
+                entity_id: EntityId(entity_id),
+                field_path: field_path.into_iter().map(FieldType).collect(),
+                _marker: marker,
+            }.encode(),
+            StoreCommand::Write { entity_id, field_path, value, writer_id, write_time, push_condition, adjust_behavior } => WriteCommand {
+                entity_id: EntityId(entity_id),
+                field_path: field_path.into_iter().map(FieldType).collect(),
+                value: value.try_into()?,
+                writer_id: writer_id.map(EntityId),
+                write_time: write_time.map(timestamp_from_wire).transpose()?,
+                push_condition: push_condition.map(push_condition_from_wire).transpose()?,
+                adjust_behavior: adjust_behavior.map(adjust_behavior_from_wire).transpose()?,
+                _marker: marker,
+            }.encode(),
+            StoreCommand::CreateEntity { entity_type, parent_id, name } => CreateEntityCommand {
+                entity_type: EntityType(entity_type),
+                parent_id: parent_id.map(EntityId),
+                name,
+                _marker: marker,
+            }.encode(),
+            StoreCommand::DeleteEntity { entity_id } => DeleteEntityCommand {
+                entity_id: EntityId(entity_id),
+                _marker: marker,
+            }.encode(),
+            StoreCommand::EntityExists { entity_id } => EntityExistsCommand {
+                entity_id: EntityId(entity_id),
+                _marker: marker,
+            }.encode(),
+            StoreCommand::FindEntities { entity_type, filter, timeout_ms, request_id } => FindEntitiesCommand {
+                entity_type: EntityType(entity_type),
+                filter,
+                timeout_ms,
+                request_id,
+                _marker: marker,
+            }.encode(),
+            StoreCommand::Resp(bytes) => return Ok(bytes),
+        };
+        Ok(encoded.to_bytes())
+    }
+}
+
+impl From<&ReadResponse> for StoreResponse {
+    fn from(response: &ReadResponse) -> Self {
+        StoreResponse::Read {
+            value: (&response.value).into(),
+            timestamp: timestamp_to_wire(&response.timestamp),
+            writer_id: response.writer_id.map(|id| id.0),
+        }
+    }
+}
+
+impl From<&CreateEntityResponse> for StoreResponse {
+    fn from(response: &CreateEntityResponse) -> Self {
+        StoreResponse::EntityId(response.entity_id.0)
+    }
+}
+
+impl From<&BooleanResponse> for StoreResponse {
+    fn from(response: &BooleanResponse) -> Self {
+        StoreResponse::Bool(response.result)
+    }
+}
+
+impl From<&EntityListResponse> for StoreResponse {
+    fn from(response: &EntityListResponse) -> Self {
+        StoreResponse::EntityIds(response.entities.iter().map(|id| id.0).collect())
+    }
+}
+
+impl StoreResponse {
+    /// Turn an `Error` reply into the error it stands for
+    pub fn into_result(self) -> Result<StoreResponse> {
+        match self {
+            StoreResponse::Error(message) => Err(crate::data::resp::error_from_frame(&message)),
+            other => Ok(other),
+        }
+    }
+
+    pub fn into_read_response(self) -> Result<ReadResponse> {
+        match self.into_result()? {
+            StoreResponse::Read { value, timestamp, writer_id } => Ok(ReadResponse {
+                value: value.try_into()?,
+                timestamp: timestamp_from_wire(timestamp)?,
+                writer_id: writer_id.map(EntityId),
+            }),
+            other => Err(unexpected("a read", &other)),
+        }
+    }
+
+    pub fn into_entity_id(self) -> Result<EntityId> {
+        match self.into_result()? {
+            StoreResponse::EntityId(id) => Ok(EntityId(id)),
+            other => Err(unexpected("an entity id", &other)),
+        }
+    }
+
+    pub fn into_entity_ids(self) -> Result<Vec<EntityId>> {
+        match self.into_result()? {
+            StoreResponse::EntityIds(ids) => Ok(ids.into_iter().map(EntityId).collect()),
+            other => Err(unexpected("entity ids", &other)),
+        }
+    }
+
+    pub fn into_bool(self) -> Result<bool> {
+        match self.into_result()? {
+            StoreResponse::Bool(b) => Ok(b),
+            other => Err(unexpected("a boolean", &other)),
+        }
+    }
+}
+
+fn unexpected(expected: &str, got: &StoreResponse) -> crate::Error {
+    crate::Error::StoreProxyError(format!("Expected {} response, got {:?}", expected, got))
+}
+
+impl ArchivedStoreCommand {
+    /// Entity type of a create or find, read without deserializing
+    pub fn entity_type(&self) -> Option<EntityType> {
+        match self {
+            ArchivedStoreCommand::CreateEntity { entity_type, .. } | ArchivedStoreCommand::FindEntities { entity_type, .. } => {
+                Some(EntityType(entity_type.to_native()))
+            }
+            _ => None,
+        }
+    }
+
+    /// Entity a command acts on, read without deserializing
+    pub fn entity_id(&self) -> Option<EntityId> {
+        match self {
+            ArchivedStoreCommand::Read { entity_id, .. }
+            | ArchivedStoreCommand::Write { entity_id, .. }
+            | ArchivedStoreCommand::DeleteEntity { entity_id }
+            | ArchivedStoreCommand::EntityExists { entity_id } => Some(EntityId(entity_id.to_native())),
+            _ => None,
+        }
+    }
+}
+
+/// Archive a command into a binary frame
+pub fn encode_command(command: &StoreCommand) -> Result<Vec<u8>> {
+    let payload = ::rkyv::to_bytes::<rancor::Error>(command).map_err(malformed)?;
+    Ok(encode_frame(&payload))
+}
+
+/// Archive a response into a binary frame
+pub fn encode_response(response: &StoreResponse) -> Result<Vec<u8>> {
+    let payload = ::rkyv::to_bytes::<rancor::Error>(response).map_err(malformed)?;
+    Ok(encode_frame(&payload))
+}
+
+/// Check a frame's payload and read the command in place. The payload must
+/// start at a multiple of `FRAME_ALIGNMENT`, e.g. because it was read into
+/// an `AlignedVec`; use `decode_command` otherwise.
+pub fn access_command(payload: &[u8]) -> Result<&ArchivedStoreCommand> {
+    ::rkyv::access::<ArchivedStoreCommand, rancor::Error>(payload).map_err(malformed)
+}
+
+/// Check a frame's payload and read the response in place. The same
+/// alignment rules as `access_command` apply.
+pub fn access_response(payload: &[u8]) -> Result<&ArchivedStoreResponse> {
+    ::rkyv::access::<ArchivedStoreResponse, rancor::Error>(payload).map_err(malformed)
+}
+
+/// Copy a frame's payload out into a command, wherever it is in memory
+pub fn decode_command(payload: &[u8]) -> Result<StoreCommand> {
+    let mut aligned = AlignedVec::<FRAME_ALIGNMENT>::with_capacity(payload.len());
+    aligned.extend_from_slice(payload);
+    ::rkyv::from_bytes::<StoreCommand, rancor::Error>(&aligned).map_err(malformed)
+}
+
+/// Copy a frame's payload out into a response, wherever it is in memory
+pub fn decode_response(payload: &[u8]) -> Result<StoreResponse> {
+    let mut aligned = AlignedVec::<FRAME_ALIGNMENT>::with_capacity(payload.len());
+    aligned.extend_from_slice(payload);
+    ::rkyv::from_bytes::<StoreResponse, rancor::Error>(&aligned).map_err(malformed)
+}
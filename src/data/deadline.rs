@@ -0,0 +1,130 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use rustc_hash::FxHashMap;
+
+use crate::{Error, Result};
+
+/// Flag shared between a running request and whoever may cancel it
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Release);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Acquire)
+    }
+}
+
+/// When the request being served must give up. Long running store
+/// operations, like filtered `find_entities` scans, check it as they go and
+/// fail with `Error::Timeout` or `Error::Cancelled`.
+#[derive(Debug, Clone, Default)]
+pub struct Deadline {
+    expires: Option<(Instant, Duration)>,
+    cancel: Option<(u64, CancelToken)>,
+}
+
+impl Deadline {
+    /// No time limit and no way to cancel
+    pub fn none() -> Self {
+        Self::default()
+    }
+
+    /// Expire `timeout` from now
+    pub fn after(timeout: Duration) -> Self {
+        Self {
+            expires: Some((Instant::now() + timeout, timeout)),
+            cancel: None,
+        }
+    }
+
+    /// Also give up once `token` is cancelled. `request_id` is reported in
+    /// `Error::Cancelled`.
+    pub fn with_cancel(mut self, request_id: u64, token: CancelToken) -> Self {
+        self.cancel = Some((request_id, token));
+        self
+    }
+
+    /// Check if there is anything to enforce
+    pub fn is_none(&self) -> bool {
+        self.expires.is_none() && self.cancel.is_none()
+    }
+
+    /// Fail if the request was cancelled or has run out of time
+    pub fn check(&self) -> Result<()> {
+        if let Some((request_id, token)) = &self.cancel {
+            if token.is_cancelled() {
+                return Err(Error::Cancelled(*request_id));
+            }
+        }
+        if let Some((expires_at, timeout)) = self.expires {
+            if Instant::now() >= expires_at {
+                return Err(Error::Timeout(timeout));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Requests in progress by the id their client gave them, so a `CANCEL`
+/// arriving on any connection can reach them. The command dispatcher calls
+/// `begin` before running a command that carries an id and `finish` after.
+#[derive(Debug, Default)]
+pub struct CancelRegistry {
+    running: Mutex<FxHashMap<u64, CancelToken>>,
+}
+
+impl CancelRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The deadline for a command sent with the given timeout and request id
+    pub fn begin(&self, timeout_ms: Option<u64>, request_id: Option<u64>) -> Deadline {
+        let mut deadline = match timeout_ms {
+            Some(timeout_ms) => Deadline::after(Duration::from_millis(timeout_ms)),
+            None => Deadline::none(),
+        };
+        if let Some(request_id) = request_id {
+            let token = CancelToken::new();
+            self.running.lock().unwrap().insert(request_id, token.clone());
+            deadline = deadline.with_cancel(request_id, token);
+        }
+        deadline
+    }
+
+    /// Forget a finished request
+    pub fn finish(&self, request_id: u64) {
+        self.running.lock().unwrap().remove(&request_id);
+    }
+
+    /// Cancel a running request. Returns false if no request has that id,
+    /// e.g. because it already finished.
+    pub fn cancel(&self, request_id: u64) -> bool {
+        match self.running.lock().unwrap().get(&request_id) {
+            Some(token) => {
+                token.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Number of requests that can currently be cancelled
+    pub fn len(&self) -> usize {
+        self.running.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
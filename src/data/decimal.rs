@@ -0,0 +1,201 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{Error, Result};
+
+/// Largest scale a Decimal can carry; 10^18 is the largest power of ten in an i64
+pub const MAX_DECIMAL_SCALE: u32 = 18;
+
+/// Fixed-point decimal number: `mantissa * 10^-scale`.
+///
+/// Equality, ordering and hashing compare the numeric value, so `1.50 == 1.5`.
+/// Serialized as a string (e.g. `"12.50"`) so JSON never goes through f64.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Decimal {
+    mantissa: i64,
+    scale: u32,
+}
+
+impl Decimal {
+    pub const ZERO: Decimal = Decimal { mantissa: 0, scale: 0 };
+
+    pub fn new(mantissa: i64, scale: u32) -> Result<Self> {
+        if scale > MAX_DECIMAL_SCALE {
+            return Err(Error::InvalidRequest(format!(
+                "Decimal scale {} exceeds maximum of {}",
+                scale, MAX_DECIMAL_SCALE
+            )));
+        }
+        Ok(Decimal { mantissa, scale })
+    }
+
+    pub fn from_int(value: i64) -> Self {
+        Decimal { mantissa: value, scale: 0 }
+    }
+
+    pub fn mantissa(&self) -> i64 {
+        self.mantissa
+    }
+
+    pub fn scale(&self) -> u32 {
+        self.scale
+    }
+
+    /// Change the scale, rounding half away from zero when digits are dropped
+    pub fn rescale(&self, scale: u32) -> Result<Self> {
+        let mantissa = rescale_mantissa(self.mantissa as i128, self.scale, scale);
+        let mantissa = i64::try_from(mantissa)
+            .map_err(|_| Error::InvalidRequest(format!("Decimal {} overflows at scale {}", self, scale)))?;
+        Decimal::new(mantissa, scale)
+    }
+
+    /// Drop trailing fractional zeros
+    pub fn normalize(&self) -> Self {
+        let mut result = *self;
+        while result.scale > 0 && result.mantissa % 10 == 0 {
+            result.mantissa /= 10;
+            result.scale -= 1;
+        }
+        result
+    }
+
+    pub fn checked_add(&self, other: &Decimal) -> Option<Decimal> {
+        let scale = self.scale.max(other.scale);
+        let a = rescale_mantissa(self.mantissa as i128, self.scale, scale);
+        let b = rescale_mantissa(other.mantissa as i128, other.scale, scale);
+        i64::try_from(a + b).ok().map(|mantissa| Decimal { mantissa, scale })
+    }
+
+    pub fn checked_sub(&self, other: &Decimal) -> Option<Decimal> {
+        self.checked_add(&Decimal { mantissa: other.mantissa.checked_neg()?, scale: other.scale })
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        self.mantissa as f64 / 10f64.powi(self.scale as i32)
+    }
+
+    /// Convert from a float, rounding to the given scale
+    pub fn from_f64(value: f64, scale: u32) -> Result<Self> {
+        if !value.is_finite() || scale > MAX_DECIMAL_SCALE {
+            return Err(Error::InvalidRequest(format!("Cannot represent {} as a Decimal", value)));
+        }
+        let scaled = (value * 10f64.powi(scale as i32)).round();
+        if scaled < i64::MIN as f64 || scaled > i64::MAX as f64 {
+            return Err(Error::InvalidRequest(format!("Cannot represent {} as a Decimal", value)));
+        }
+        Decimal::new(scaled as i64, scale)
+    }
+}
+
+fn rescale_mantissa(mantissa: i128, from: u32, to: u32) -> i128 {
+    match from.cmp(&to) {
+        Ordering::Equal => mantissa,
+        Ordering::Less => mantissa * 10i128.pow(to - from),
+        Ordering::Greater => {
+            let divisor = 10i128.pow(from - to);
+            let quotient = mantissa / divisor;
+            let remainder = mantissa % divisor;
+            if remainder.abs() * 2 >= divisor {
+                quotient + mantissa.signum()
+            } else {
+                quotient
+            }
+        }
+    }
+}
+
+impl PartialEq for Decimal {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Decimal {}
+
+impl PartialOrd for Decimal {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Decimal {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let scale = self.scale.max(other.scale);
+        rescale_mantissa(self.mantissa as i128, self.scale, scale)
+            .cmp(&rescale_mantissa(other.mantissa as i128, other.scale, scale))
+    }
+}
+
+impl Hash for Decimal {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        let normalized = self.normalize();
+        normalized.mantissa.hash(state);
+        normalized.scale.hash(state);
+    }
+}
+
+impl fmt::Display for Decimal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.scale == 0 {
+            return write!(f, "{}", self.mantissa);
+        }
+        let digits = self.mantissa.unsigned_abs().to_string();
+        let scale = self.scale as usize;
+        let digits = if digits.len() <= scale {
+            format!("{}{}", "0".repeat(scale + 1 - digits.len()), digits)
+        } else {
+            digits
+        };
+        let (int_part, frac_part) = digits.split_at(digits.len() - scale);
+        let sign = if self.mantissa < 0 { "-" } else { "" };
+        write!(f, "{}{}.{}", sign, int_part, frac_part)
+    }
+}
+
+impl FromStr for Decimal {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || Error::InvalidRequest(format!("Invalid decimal: '{}'", s));
+        let trimmed = s.trim();
+        let (negative, unsigned) = match trimmed.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, trimmed.strip_prefix('+').unwrap_or(trimmed)),
+        };
+        let (int_part, frac_part) = unsigned.split_once('.').unwrap_or((unsigned, ""));
+        if (int_part.is_empty() && frac_part.is_empty())
+            || !int_part.bytes().all(|b| b.is_ascii_digit())
+            || !frac_part.bytes().all(|b| b.is_ascii_digit())
+        {
+            return Err(invalid());
+        }
+
+        let scale = frac_part.len() as u32;
+        let magnitude: i128 = format!("{}{}", int_part, frac_part).parse().map_err(|_| invalid())?;
+        let mantissa = i64::try_from(if negative { -magnitude } else { magnitude }).map_err(|_| invalid())?;
+        Decimal::new(mantissa, scale)
+    }
+}
+
+impl From<i64> for Decimal {
+    fn from(value: i64) -> Self {
+        Decimal::from_int(value)
+    }
+}
+
+impl Serialize for Decimal {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for Decimal {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
@@ -0,0 +1,99 @@
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::data::resp::ProtocolError;
+use crate::Result;
+
+#[derive(Debug, Default)]
+struct DrainState {
+    draining: bool,
+    in_flight: usize,
+}
+
+/// Lets a server shut down without cutting commands off halfway. Every
+/// connection handler calls `begin` before running a command and keeps the
+/// guard until the reply is sent. Once `start_draining` is called, new
+/// commands are refused with `ProtocolError::ShuttingDown` while the ones
+/// already running finish, and `wait_idle` returns when they have.
+///
+/// ```rust,ignore
+/// // Connection handler
+/// let _command = match drain.begin() {
+///     Ok(guard) => guard,
+///     Err(e) => return reply_error(e),
+/// };
+/// reply(dispatch(&mut store, &request));
+///
+/// // Shutdown
+/// drain.start_draining();
+/// listener.close();
+/// drain.wait_idle(Duration::from_secs(10));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Drain {
+    state: Arc<(Mutex<DrainState>, Condvar)>,
+}
+
+/// A command in progress, see `Drain::begin`
+#[derive(Debug)]
+pub struct DrainGuard {
+    state: Arc<(Mutex<DrainState>, Condvar)>,
+}
+
+impl Drain {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Count a command as in flight until the guard is dropped, or fail if
+    /// the server is draining
+    pub fn begin(&self) -> Result<DrainGuard> {
+        let mut state = self.state.0.lock().unwrap_or_else(|e| e.into_inner());
+        if state.draining {
+            return Err(ProtocolError::ShuttingDown.into());
+        }
+        state.in_flight += 1;
+        Ok(DrainGuard { state: self.state.clone() })
+    }
+
+    /// Refuse new commands from now on
+    pub fn start_draining(&self) {
+        self.state.0.lock().unwrap_or_else(|e| e.into_inner()).draining = true;
+    }
+
+    pub fn is_draining(&self) -> bool {
+        self.state.0.lock().unwrap_or_else(|e| e.into_inner()).draining
+    }
+
+    /// Commands currently running
+    pub fn in_flight(&self) -> usize {
+        self.state.0.lock().unwrap_or_else(|e| e.into_inner()).in_flight
+    }
+
+    /// Wait until no command is running, for at most `timeout`. Returns
+    /// false if some were still running when it expired.
+    pub fn wait_idle(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        let (lock, idle) = &*self.state;
+        let mut state = lock.lock().unwrap_or_else(|e| e.into_inner());
+        while state.in_flight > 0 {
+            let left = deadline.saturating_duration_since(Instant::now());
+            if left.is_zero() {
+                return false;
+            }
+            state = idle.wait_timeout(state, left).unwrap_or_else(|e| e.into_inner()).0;
+        }
+        true
+    }
+}
+
+impl Drop for DrainGuard {
+    fn drop(&mut self) {
+        let (lock, idle) = &*self.state;
+        let mut state = lock.lock().unwrap_or_else(|e| e.into_inner());
+        state.in_flight -= 1;
+        if state.in_flight == 0 {
+            idle.notify_all();
+        }
+    }
+}
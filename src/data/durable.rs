@@ -0,0 +1,207 @@
+use std::collections::VecDeque;
+
+use rustc_hash::FxHashMap;
+use serde::{Deserialize, Serialize};
+
+use crate::data::resp::DurableNotificationCommand;
+use crate::{Error, Notification, NotificationQueue, NotifyConfig, Result, Store};
+
+/// Notifications kept per durable subscription by default
+pub const DEFAULT_DURABLE_CAPACITY: usize = 10_000;
+
+/// One durable subscription and the notifications kept for replay
+#[derive(Debug, Serialize, Deserialize)]
+struct DurableSubscription {
+    config: NotifyConfig,
+    /// Sequence number of the last notification received, 0 before the first
+    last_seq: u64,
+    /// The most recent notifications, oldest first
+    kept: VecDeque<(u64, Notification)>,
+    /// Registered with the store; a new one is registered by `restore`
+    #[serde(skip, default = "NotificationQueue::new")]
+    queue: NotificationQueue,
+}
+
+impl DurableSubscription {
+    /// Number and keep whatever the store queued since the last call, and
+    /// return it
+    fn take_queued(&mut self, capacity: usize) -> Vec<(u64, Notification)> {
+        let mut taken = Vec::new();
+        while let Some(notification) = self.queue.pop() {
+            self.last_seq += 1;
+            self.kept.push_back((self.last_seq, notification.clone()));
+            taken.push((self.last_seq, notification));
+        }
+        while self.kept.len() > capacity {
+            self.kept.pop_front();
+        }
+        taken
+    }
+}
+
+/// Notification subscriptions that outlive the connection that made them,
+/// so a consuming service that restarts or reconnects doesn't silently miss
+/// the changes made meanwhile.
+///
+/// A subscription is named by the client (`DLISTEN <id> <config>`) and
+/// numbers its notifications from 1. The server keeps the last `capacity`
+/// of them; a client that comes back sends `RESUME <id> <last-seq>` with the
+/// last one it handled and gets the rest replayed before anything new.
+/// If some of them are no longer kept, resuming fails with
+/// `Error::NotificationsDiscarded` and the client has to read the state
+/// again instead.
+///
+/// Like `PeerReplicator` this owns no sockets: the server calls `poll`
+/// after applying writes and pushes the result as `DNOTIFY` to whichever
+/// connection is attached to each subscription. It serializes with serde so
+/// the server can persist it with its snapshots; call `restore` after
+/// loading it.
+///
+/// ```rust,ignore
+/// // DLISTEN
+/// let last_seq = durable.subscribe(&mut store, &id, config)?;
+/// // RESUME: replay, then answer
+/// for command in durable.resume(&id, last_seq)? {
+///     connection.push(command);
+/// }
+/// // After each batch of writes
+/// for command in durable.poll() {
+///     if let Some(connection) = attached.get(&command.subscription_id) {
+///         connection.push(command);
+///     }
+/// }
+/// ```
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DurableSubscriptions {
+    subscriptions: FxHashMap<String, DurableSubscription>,
+    capacity: usize,
+}
+
+impl Default for DurableSubscriptions {
+    fn default() -> Self {
+        Self::new(DEFAULT_DURABLE_CAPACITY)
+    }
+}
+
+impl DurableSubscriptions {
+    /// Keep up to `capacity` notifications per subscription for replay
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            subscriptions: FxHashMap::default(),
+            capacity,
+        }
+    }
+
+    /// Create a subscription, or attach to an existing one with the same
+    /// config. Returns the sequence number of the last notification it
+    /// received, so a new client knows where it starts.
+    pub fn subscribe(&mut self, store: &mut Store, subscription_id: &str, config: NotifyConfig) -> Result<u64> {
+        if let Some(subscription) = self.subscriptions.get(subscription_id) {
+            if subscription.config != config {
+                return Err(Error::InvalidRequest(format!("Subscription '{}' exists with a different config", subscription_id)));
+            }
+            return Ok(subscription.last_seq);
+        }
+
+        let queue = NotificationQueue::new();
+        store.register_notification(config.clone(), queue.clone())?;
+        self.subscriptions.insert(subscription_id.to_string(), DurableSubscription {
+            config,
+            last_seq: 0,
+            kept: VecDeque::new(),
+            queue,
+        });
+        Ok(0)
+    }
+
+    /// Remove a subscription and the notifications kept for it. Returns
+    /// false if there was no such subscription.
+    pub fn unsubscribe(&mut self, store: &mut Store, subscription_id: &str) -> bool {
+        let Some(subscription) = self.subscriptions.remove(subscription_id) else {
+            return false;
+        };
+        store.unregister_notification(&subscription.config, &subscription.queue);
+        true
+    }
+
+    /// Register every subscription with `store` again, after loading them
+    /// from a snapshot
+    pub fn restore(&mut self, store: &mut Store) -> Result<()> {
+        for subscription in self.subscriptions.values_mut() {
+            store.unregister_notification(&subscription.config, &subscription.queue);
+            subscription.queue = NotificationQueue::new();
+            store.register_notification(subscription.config.clone(), subscription.queue.clone())?;
+        }
+        Ok(())
+    }
+
+    /// The notifications every subscription received since the last call,
+    /// numbered and ready to push, in order per subscription
+    pub fn poll(&mut self) -> Vec<DurableNotificationCommand<'static>> {
+        let capacity = self.capacity;
+        let mut commands = Vec::new();
+        for (subscription_id, subscription) in self.subscriptions.iter_mut() {
+            commands.extend(subscription.take_queued(capacity).into_iter().map(|(seq, notification)| DurableNotificationCommand {
+                subscription_id: subscription_id.clone(),
+                seq,
+                notification,
+                _marker: std::marker::PhantomData,
+            }));
+        }
+        commands
+    }
+
+    /// The notifications a client that handled everything up to `last_seq`
+    /// missed, oldest first. Those up to `last_seq` are no longer needed and
+    /// are dropped. Fails with `Error::NotificationsDiscarded` if some of
+    /// the missed ones are no longer kept.
+    pub fn resume(&mut self, subscription_id: &str, last_seq: u64) -> Result<Vec<DurableNotificationCommand<'static>>> {
+        let capacity = self.capacity;
+        let subscription = self
+            .subscriptions
+            .get_mut(subscription_id)
+            .ok_or_else(|| Error::InvalidRequest(format!("No subscription '{}'", subscription_id)))?;
+        subscription.take_queued(capacity);
+
+        if last_seq > subscription.last_seq {
+            return Err(Error::InvalidRequest(format!(
+                "Subscription '{}' has only received {} notifications, can't resume after {}",
+                subscription_id, subscription.last_seq, last_seq
+            )));
+        }
+        let oldest = subscription.kept.front().map_or(subscription.last_seq + 1, |(seq, _)| *seq);
+        if oldest > last_seq + 1 {
+            return Err(Error::NotificationsDiscarded(subscription_id.to_string(), oldest));
+        }
+
+        subscription.kept.retain(|(seq, _)| *seq > last_seq);
+        Ok(subscription
+            .kept
+            .iter()
+            .map(|(seq, notification)| DurableNotificationCommand {
+                subscription_id: subscription_id.to_string(),
+                seq: *seq,
+                notification: notification.clone(),
+                _marker: std::marker::PhantomData,
+            })
+            .collect())
+    }
+
+    /// Sequence number of the last notification a subscription received
+    pub fn last_seq(&self, subscription_id: &str) -> Option<u64> {
+        self.subscriptions.get(subscription_id).map(|subscription| subscription.last_seq)
+    }
+
+    /// The config a subscription was made with
+    pub fn config(&self, subscription_id: &str) -> Option<&NotifyConfig> {
+        self.subscriptions.get(subscription_id).map(|subscription| &subscription.config)
+    }
+
+    pub fn subscription_ids(&self) -> impl Iterator<Item = &str> {
+        self.subscriptions.keys().map(String::as_str)
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+}
@@ -1,6 +1,18 @@
+use std::ops::Range;
+
 use crate::data::EntityType;
 use serde::{Deserialize, Serialize};
 
+/// Bits at the top of an entity's id (`extract_id`) that hold the node it
+/// was created on, see `IdAllocator::Node`. Ids minted without a node are on
+/// node 0, so existing ids keep their meaning.
+pub const NODE_BITS: u32 = 8;
+
+/// Largest node id that fits in `NODE_BITS`
+pub const MAX_NODE_ID: u32 = (1 << NODE_BITS) - 1;
+
+const LOCAL_BITS: u32 = 32 - NODE_BITS;
+const LOCAL_MASK: u32 = (1 << LOCAL_BITS) - 1;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Ord, PartialOrd)]
 pub struct EntityId(pub u64);
@@ -17,6 +29,58 @@ impl EntityId {
     pub fn extract_type(&self) -> EntityType {
         EntityType((self.0 >> 32) as u32)
     }
+
+    /// The `local_id`th id of a type minted on `node`. Bits of either that
+    /// don't fit are dropped.
+    pub fn on_node(entity_type: EntityType, node: u32, local_id: u32) -> Self {
+        Self::new(entity_type, ((node & MAX_NODE_ID) << LOCAL_BITS) | (local_id & LOCAL_MASK))
+    }
+
+    /// The node the entity was created on, 0 for stores without a node id
+    pub fn node_of(&self) -> u32 {
+        self.extract_id() >> LOCAL_BITS
+    }
+
+    /// The id without its node bits
+    pub fn local_id(&self) -> u32 {
+        self.extract_id() & LOCAL_MASK
+    }
+}
+
+/// How a store picks the id of a new entity. Ids are counted per type.
+///
+/// The default numbers entities one after another, which is fine for a
+/// single store but means two stores creating entities while disconnected
+/// will mint the same ids. Giving each store its own range, or its own
+/// node id, keeps their ids apart so their writes can be merged later.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum IdAllocator {
+    /// One more than the highest id of the type
+    #[default]
+    Sequential,
+    /// The next free id in this range, e.g. one range per site
+    Range(Range<u32>),
+    /// Ids of this node, with the node in the top `NODE_BITS` bits, see
+    /// `EntityId::node_of`
+    Node(u32),
+}
+
+impl IdAllocator {
+    /// The ids this allocator hands out, none for a node above
+    /// `MAX_NODE_ID`. Id 0 is never used, nor is local id 0 of a node.
+    pub fn range(&self) -> Range<u32> {
+        match self {
+            IdAllocator::Sequential => 1..u32::MAX,
+            IdAllocator::Range(range) => range.start.max(1)..range.end,
+            IdAllocator::Node(node) if *node > MAX_NODE_ID => 0..0,
+            IdAllocator::Node(node) => {
+                // Local ids count from 1 like sequential ones
+                let start = (node << LOCAL_BITS) | 1;
+                let end = ((*node as u64 + 1) << LOCAL_BITS).min(u32::MAX as u64) as u32;
+                start..end
+            }
+        }
+    }
 }
 
 impl From<u64> for EntityId {
@@ -4,7 +4,7 @@ use qlib_rs_derive::{RespDecode, RespEncode};
 use rustc_hash::FxHashMap;
 use serde::{Deserialize, Serialize};
 
-use crate::{data::{EntityType, FieldSchema, FieldType}, StoreTrait, Value};
+use crate::{data::{Duration, EntityType, FieldMetadata, FieldSchema, FieldType, MergePolicy, SampleType}, StoreTrait, Value};
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Single;
@@ -47,7 +47,7 @@ impl EntitySchema<Single, EntityType, FieldType> {
     pub fn diff(&self, other: &EntitySchema<Single, EntityType, FieldType>) -> Vec<FieldSchema> {
         self.fields
             .values()
-            .filter(|v| other.fields.contains_key(&v.field_type()))
+            .filter(|v| !other.fields.contains_key(&v.field_type()))
             .cloned()
             .collect()
     }
@@ -57,7 +57,7 @@ impl EntitySchema<Complete, EntityType, FieldType> {
     pub fn diff(&self, other: &EntitySchema<Complete, EntityType, FieldType>) -> Vec<FieldSchema> {
         self.fields
             .values()
-            .filter(|v| other.fields.contains_key(&v.field_type()))
+            .filter(|v| !other.fields.contains_key(&v.field_type()))
             .cloned()
             .collect()
     }
@@ -138,6 +138,18 @@ impl EntitySchemaResp {
         }
     }
 
+    /// Convert from a string-keyed EntitySchema, which needs no type lookups
+    pub fn from_string_schema(schema: EntitySchema<Single, String, String>) -> Self {
+        Self {
+            entity_type: schema.entity_type,
+            inherit: schema.inherit,
+            fields: schema.fields.into_iter().map(|(field_type, field_schema)| FieldSchemaResp {
+                field_type,
+                ..FieldSchemaResp::from_string_schema(&field_schema)
+            }).collect(),
+        }
+    }
+
     /// Convert from Complete EntitySchema to EntitySchemaResp
     pub fn from_complete_entity_schema(schema: &EntitySchema<Complete, EntityType, FieldType>, store: &impl StoreTrait) -> Self {
         Self {
@@ -154,11 +166,49 @@ pub struct FieldSchemaResp {
     pub rank: i64,
     pub default_value: Value,
     pub choices: Vec<String>,
+    pub choices_source: Option<String>,
+    pub expression: Option<String>,
+    pub metadata: FieldMetadata,
+    /// Capacity and retention of a Series field, whose samples have the
+    /// type of `default_value`
+    #[resp(default)]
+    pub series: Option<(usize, Option<Duration>)>,
+    /// CEL expression writes must satisfy, see `FieldSchema::validator`
+    #[resp(default)]
+    pub validator: Option<String>,
+    /// How diverged values are reconciled, see `FieldSchema::merge_policy`
+    #[resp(default)]
+    pub merge_policy: MergePolicy,
 }
 
 impl FieldSchemaResp {
     /// Convert from FieldSchemaResp to FieldSchema<String>
     pub fn to_field_schema(self) -> FieldSchema<String> {
+        if let Some(expression) = self.expression {
+            return FieldSchema::Computed {
+                field_type: self.field_type,
+                expression,
+                rank: self.rank,
+                storage_scope: crate::data::field_schema::StorageScope::Runtime,
+                metadata: self.metadata,
+            };
+        }
+
+        if let Some((capacity, retention)) = self.series {
+            if let Some(sample_type) = SampleType::of(&self.default_value) {
+                return FieldSchema::Series {
+                    field_type: self.field_type,
+                    sample_type,
+                    capacity,
+                    retention,
+                    rank: self.rank,
+                    storage_scope: crate::data::field_schema::StorageScope::Runtime,
+                    validator: self.validator,
+                    metadata: self.metadata,
+                };
+            }
+        }
+
         // Determine the field schema variant based on the default value type
         match self.default_value {
             Value::Blob(data) => FieldSchema::Blob {
@@ -166,56 +216,129 @@ impl FieldSchemaResp {
                 default_value: data,
                 rank: self.rank,
                 storage_scope: crate::data::field_schema::StorageScope::Runtime,
+                validator: self.validator,
+                metadata: self.metadata,
             },
             Value::Bool(val) => FieldSchema::Bool {
                 field_type: self.field_type,
                 default_value: val,
                 rank: self.rank,
                 storage_scope: crate::data::field_schema::StorageScope::Runtime,
+                validator: self.validator,
+                metadata: self.metadata,
             },
             Value::Choice(val) => FieldSchema::Choice {
                 field_type: self.field_type,
                 default_value: val,
                 rank: self.rank,
                 choices: self.choices,
+                choices_source: self.choices_source,
                 storage_scope: crate::data::field_schema::StorageScope::Runtime,
+                validator: self.validator,
+                metadata: self.metadata,
             },
             Value::EntityList(val) => FieldSchema::EntityList {
                 field_type: self.field_type,
                 default_value: val,
                 rank: self.rank,
                 storage_scope: crate::data::field_schema::StorageScope::Runtime,
+                merge_policy: self.merge_policy,
+                validator: self.validator,
+                metadata: self.metadata,
             },
             Value::EntityReference(val) => FieldSchema::EntityReference {
                 field_type: self.field_type,
                 default_value: val,
                 rank: self.rank,
                 storage_scope: crate::data::field_schema::StorageScope::Runtime,
+                validator: self.validator,
+                metadata: self.metadata,
             },
             Value::Float(val) => FieldSchema::Float {
                 field_type: self.field_type,
                 default_value: val,
                 rank: self.rank,
                 storage_scope: crate::data::field_schema::StorageScope::Runtime,
+                merge_policy: self.merge_policy,
+                validator: self.validator,
+                metadata: self.metadata,
             },
             Value::Int(val) => FieldSchema::Int {
                 field_type: self.field_type,
                 default_value: val,
                 rank: self.rank,
                 storage_scope: crate::data::field_schema::StorageScope::Runtime,
+                merge_policy: self.merge_policy,
+                validator: self.validator,
+                metadata: self.metadata,
             },
             Value::String(val) => FieldSchema::String {
                 field_type: self.field_type,
                 default_value: val,
                 rank: self.rank,
                 storage_scope: crate::data::field_schema::StorageScope::Runtime,
+                validator: self.validator,
+                metadata: self.metadata,
             },
             Value::Timestamp(val) => FieldSchema::Timestamp {
                 field_type: self.field_type,
                 default_value: val,
                 rank: self.rank,
                 storage_scope: crate::data::field_schema::StorageScope::Runtime,
+                validator: self.validator,
+                metadata: self.metadata,
             },
+            Value::Decimal(val) => FieldSchema::Decimal {
+                field_type: self.field_type,
+                default_value: val,
+                rank: self.rank,
+                storage_scope: crate::data::field_schema::StorageScope::Runtime,
+                merge_policy: self.merge_policy,
+                validator: self.validator,
+                metadata: self.metadata,
+            },
+            Value::Duration(val) => FieldSchema::Duration {
+                field_type: self.field_type,
+                default_value: val,
+                rank: self.rank,
+                storage_scope: crate::data::field_schema::StorageScope::Runtime,
+                merge_policy: self.merge_policy,
+                validator: self.validator,
+                metadata: self.metadata,
+            },
+            Value::StringList(val) => FieldSchema::StringList {
+                field_type: self.field_type,
+                default_value: val,
+                rank: self.rank,
+                storage_scope: crate::data::field_schema::StorageScope::Runtime,
+                merge_policy: self.merge_policy,
+                validator: self.validator,
+                metadata: self.metadata,
+            },
+            Value::Map(val) => FieldSchema::Map {
+                field_type: self.field_type,
+                default_value: val,
+                rank: self.rank,
+                storage_scope: crate::data::field_schema::StorageScope::Runtime,
+                validator: self.validator,
+                metadata: self.metadata,
+            },
+        }
+    }
+
+    /// Convert from a string-keyed FieldSchema to FieldSchemaResp
+    pub fn from_string_schema(schema: &FieldSchema<String>) -> Self {
+        Self {
+            field_type: schema.field_type(),
+            rank: schema.rank(),
+            default_value: schema.default_value(),
+            choices: schema.choices(),
+            choices_source: schema.choices_source().map(|s| s.to_string()),
+            expression: schema.expression().map(|e| e.to_string()),
+            metadata: schema.metadata().clone(),
+            series: schema.series_limits(),
+            validator: schema.validator().map(|v| v.to_string()),
+            merge_policy: schema.merge_policy(),
         }
     }
 
@@ -232,6 +355,12 @@ impl FieldSchemaResp {
             FieldSchema::Int { rank, default_value, .. } => (*rank, Value::Int(*default_value), Vec::new()),
             FieldSchema::String { rank, default_value, .. } => (*rank, Value::String(default_value.clone()), Vec::new()),
             FieldSchema::Timestamp { rank, default_value, .. } => (*rank, Value::Timestamp(*default_value), Vec::new()),
+            FieldSchema::Decimal { rank, default_value, .. } => (*rank, Value::Decimal(*default_value), Vec::new()),
+            FieldSchema::Duration { rank, default_value, .. } => (*rank, Value::Duration(*default_value), Vec::new()),
+            FieldSchema::StringList { rank, default_value, .. } => (*rank, Value::StringList(default_value.clone()), Vec::new()),
+            FieldSchema::Map { rank, default_value, .. } => (*rank, Value::Map(default_value.clone()), Vec::new()),
+            FieldSchema::Series { rank, .. } => (*rank, schema.default_value(), Vec::new()),
+            FieldSchema::Computed { rank, .. } => (*rank, schema.default_value(), Vec::new()),
         };
 
         Self {
@@ -239,6 +368,12 @@ impl FieldSchemaResp {
             rank,
             default_value,
             choices,
+            choices_source: schema.choices_source().map(|s| s.to_string()),
+            expression: schema.expression().map(|e| e.to_string()),
+            metadata: schema.metadata().clone(),
+            series: schema.series_limits(),
+            validator: schema.validator().map(|v| v.to_string()),
+            merge_policy: schema.merge_policy(),
         }
     }
 }
\ No newline at end of file
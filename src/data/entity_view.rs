@@ -0,0 +1,108 @@
+use serde::de::DeserializeOwned;
+use serde::ser::{Serialize, SerializeMap, Serializer};
+use serde_json::Value as JsonValue;
+
+use crate::data::{store_trait::collect_field_schemas, StoreTrait};
+use crate::{ft, json_value_to_value, value_to_json_value, EntityId, Error, FieldSchema, Result};
+
+/// An entity's fields by name, serializable to any serde format (TOML,
+/// YAML, MessagePack...).
+///
+/// Values are represented the same way as in a JSON snapshot (see
+/// `value_to_json_value`): choices by name, timestamps as unix seconds and
+/// entity ids as strings. `Parent` and `Children` describe where the entity
+/// is rather than what it holds, and computed fields can't be written back,
+/// so neither is included. Empty references are left out as well, since
+/// not every format has a null.
+#[derive(Debug, Clone)]
+pub struct EntityView {
+    entity_id: EntityId,
+    fields: Vec<(String, JsonValue)>,
+}
+
+impl EntityView {
+    /// Read the entity's fields, in rank order
+    pub fn new<T: StoreTrait + ?Sized>(store: &T, entity_id: EntityId) -> Result<Self> {
+        if !store.entity_exists(entity_id) {
+            return Err(Error::EntityNotFound(entity_id));
+        }
+        let mut schemas = collect_field_schemas(store, entity_id.extract_type())?;
+        schemas.sort_by_key(|(_, field_schema)| field_schema.rank());
+
+        let mut fields = Vec::new();
+        for (field_type, field_schema) in schemas {
+            let name = store.resolve_field_type(field_type)?;
+            if name == ft::PARENT || name == ft::CHILDREN || field_schema.is_computed() {
+                continue;
+            }
+            let (value, _, _) = store.read(entity_id, &[field_type])?;
+            let choices = match &field_schema {
+                FieldSchema::Choice { choices, choices_source: None, .. } => Some(choices),
+                _ => None,
+            };
+            match value_to_json_value(&value, choices) {
+                JsonValue::Null => {}
+                json_value => fields.push((name, json_value)),
+            }
+        }
+        Ok(EntityView { entity_id, fields })
+    }
+
+    pub fn entity_id(&self) -> EntityId {
+        self.entity_id
+    }
+
+    /// The value of a field, or None if it isn't in the view
+    pub fn get(&self, name: &str) -> Option<&JsonValue> {
+        self.fields.iter().find(|(field, _)| field == name).map(|(_, value)| value)
+    }
+}
+
+impl Serialize for EntityView {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.fields.len()))?;
+        for (name, value) in &self.fields {
+            map.serialize_entry(name, value)?;
+        }
+        map.end()
+    }
+}
+
+/// Read an entity into any type that deserializes from its `EntityView`,
+/// e.g. a struct with a member per field of interest
+pub fn entity_to_value<T: DeserializeOwned, S: StoreTrait + ?Sized>(store: &S, entity_id: EntityId) -> Result<T> {
+    let view = serde_json::to_value(EntityView::new(store, entity_id)?).map_err(|e| Error::InvalidFieldValue(e.to_string()))?;
+    serde_json::from_value(view).map_err(|e| Error::InvalidFieldValue(e.to_string()))
+}
+
+/// Write the fields of any value that serializes to a map of field names,
+/// such as a parsed TOML table or the struct `entity_to_value` produced.
+///
+/// Values are converted with the entity's field schemas before anything is
+/// written, so a bad value leaves the entity untouched. Returns the number
+/// of fields written.
+pub fn entity_from_value<T: Serialize + ?Sized, S: StoreTrait + ?Sized>(store: &mut S, entity_id: EntityId, value: &T) -> Result<usize> {
+    let fields = match serde_json::to_value(value).map_err(|e| Error::InvalidFieldValue(e.to_string()))? {
+        JsonValue::Object(fields) => fields,
+        other => return Err(Error::InvalidFieldValue(format!("Expected a map of fields, got {}", other))),
+    };
+    let schemas = collect_field_schemas(store, entity_id.extract_type())?;
+
+    let mut writes = Vec::with_capacity(fields.len());
+    for (name, json_value) in &fields {
+        if name == ft::PARENT || name == ft::CHILDREN {
+            return Err(Error::InvalidRequest(format!("{} can't be written from a value", name)));
+        }
+        let field_type = store.get_field_type(name)?;
+        let (_, field_schema) = schemas
+            .iter()
+            .find(|(schema_field_type, _)| *schema_field_type == field_type)
+            .ok_or(Error::FieldTypeNotFound(entity_id, field_type))?;
+        writes.push((field_type, json_value_to_value(json_value, field_schema)?));
+    }
+
+    for (field_type, value) in &writes {
+        store.write(entity_id, &[*field_type], value.clone(), None, None, None, None)?;
+    }
+    Ok(writes.len())
+}
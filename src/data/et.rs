@@ -6,10 +6,13 @@ pub const MACHINE: &str = "Machine";
 pub const OBJECT: &str = "Object";
 pub const PERMISSION: &str = "Permission";
 pub const ROOT: &str = "Root";
+pub const SCHEDULED_TASK: &str = "ScheduledTask";
 pub const SERVICE: &str = "Service";
 pub const SUBJECT: &str = "Subject";
 pub const USER: &str = "User";
 pub const CANDIDATE: &str = "Candidate";
+pub const BACKUP_SCHEDULE: &str = "BackupSchedule";
+pub const TRIGGER: &str = "Trigger";
 
 #[derive(Clone)]
 pub struct ET {
@@ -19,25 +22,52 @@ pub struct ET {
     pub object: Option<EntityType>,
     pub permission: Option<EntityType>,
     pub root: Option<EntityType>,
+    pub scheduled_task: Option<EntityType>,
     pub service: Option<EntityType>,
     pub subject: Option<EntityType>,
     pub user: Option<EntityType>,
     pub candidate: Option<EntityType>,
+    pub backup_schedule: Option<EntityType>,
+    pub trigger: Option<EntityType>,
 }
 
 impl ET {
     pub fn new(store: &impl StoreTrait) -> Self {
+        const NAMES: [&str; 13] = [
+            FAULT_TOLERANCE,
+            FOLDER,
+            MACHINE,
+            OBJECT,
+            PERMISSION,
+            ROOT,
+            SCHEDULED_TASK,
+            SERVICE,
+            SUBJECT,
+            USER,
+            CANDIDATE,
+            BACKUP_SCHEDULE,
+            TRIGGER,
+        ];
+
+        // One round trip; older servers without GET_TYPES_BULK get one lookup per name
+        let ids = store.get_types_bulk(&NAMES, &[]).map(|(entity_types, _)| entity_types)
+            .unwrap_or_else(|_| NAMES.iter().map(|name| store.get_entity_type(name).ok()).collect());
+        let mut ids = ids.into_iter();
+
         ET {
-            fault_tolerance: store.get_entity_type(FAULT_TOLERANCE).ok(),
-            folder: store.get_entity_type(FOLDER).ok(),
-            machine: store.get_entity_type(MACHINE).ok(),
-            object: store.get_entity_type(OBJECT).ok(),
-            permission: store.get_entity_type(PERMISSION).ok(),
-            root: store.get_entity_type(ROOT).ok(),
-            service: store.get_entity_type(SERVICE).ok(),
-            subject: store.get_entity_type(SUBJECT).ok(),
-            user: store.get_entity_type(USER).ok(),
-            candidate: store.get_entity_type(CANDIDATE).ok(),
+            fault_tolerance: ids.next().flatten(),
+            folder: ids.next().flatten(),
+            machine: ids.next().flatten(),
+            object: ids.next().flatten(),
+            permission: ids.next().flatten(),
+            root: ids.next().flatten(),
+            scheduled_task: ids.next().flatten(),
+            service: ids.next().flatten(),
+            subject: ids.next().flatten(),
+            user: ids.next().flatten(),
+            candidate: ids.next().flatten(),
+            backup_schedule: ids.next().flatten(),
+            trigger: ids.next().flatten(),
         }
     }
 }
\ No newline at end of file
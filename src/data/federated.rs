@@ -0,0 +1,398 @@
+use std::ops::Range;
+
+use crossbeam::channel::Sender;
+use rustc_hash::FxHashMap;
+
+use crate::data::StoreTrait;
+use crate::{
+    BadIndirectionReason, Complete, EntityId, EntitySchema, EntityType, Error, FieldSchema, FieldType, Notification, NotifyConfig,
+    PageOpts, PageResult, PushCondition, AdjustBehavior, Result, Single, StoreProxy, StoreStats, Timestamp, Value,
+};
+
+/// Several stores, e.g. one per site, behind one `StoreTrait`.
+///
+/// Each entity lives in exactly one backend, picked by its id: the first
+/// id range (`route_ids`, matched against `EntityId::extract_id`) that
+/// contains it, else the backend its type is routed to (`route_type`), else
+/// the first backend. New entities are created in their type's backend if
+/// it is routed, else in their parent's backend, else in the first one, so
+/// a backend with id ranges has to mint ids inside them. Creating an entity
+/// whose id would route it somewhere else is undone and fails.
+///
+/// The backends must agree on entity and field type ids, e.g. by loading
+/// one store's `export_type_ids` into the others with `import_type_ids`.
+/// Type lookups are answered by the first backend and schema changes are
+/// applied to all of them, in order. Finds are sent to every backend and
+/// the results merged, and indirection follows references from one backend
+/// into another.
+///
+/// ```rust,ignore
+/// let mut federated = FederatedStore::new("hq", StoreProxy::connect("hq:9100")?);
+/// federated.add_backend("north", StoreProxy::connect("north:9100")?)?;
+/// federated.route_type(et_pump, "north")?;
+/// // Every pump, wherever it lives
+/// let pumps = federated.find_entities(et_pump, Some("Power > 10"))?;
+/// ```
+#[derive(Debug)]
+pub struct FederatedStore<S = StoreProxy> {
+    backends: Vec<(String, S)>,
+    type_routes: FxHashMap<EntityType, usize>,
+    id_routes: Vec<(Range<u32>, usize)>,
+}
+
+impl<S: StoreTrait> FederatedStore<S> {
+    /// Start with one backend, which gets everything not routed elsewhere
+    pub fn new(name: &str, store: S) -> Self {
+        Self {
+            backends: vec![(name.to_string(), store)],
+            type_routes: FxHashMap::default(),
+            id_routes: Vec::new(),
+        }
+    }
+
+    pub fn add_backend(&mut self, name: &str, store: S) -> Result<()> {
+        if self.backends.iter().any(|(existing, _)| existing == name) {
+            return Err(Error::InvalidRequest(format!("Backend '{}' already exists", name)));
+        }
+        self.backends.push((name.to_string(), store));
+        Ok(())
+    }
+
+    /// Keep entities of `entity_type` in the backend named `backend`.
+    /// Derived types are routed on their own.
+    pub fn route_type(&mut self, entity_type: EntityType, backend: &str) -> Result<()> {
+        let index = self.index_of(backend)?;
+        self.type_routes.insert(entity_type, index);
+        Ok(())
+    }
+
+    /// Keep entities whose id is in `ids`, whatever their type, in the
+    /// backend named `backend`. Ranges are checked in the order they were
+    /// added, before the type routes.
+    pub fn route_ids(&mut self, ids: Range<u32>, backend: &str) -> Result<()> {
+        let index = self.index_of(backend)?;
+        self.id_routes.push((ids, index));
+        Ok(())
+    }
+
+    pub fn backend(&self, name: &str) -> Option<&S> {
+        self.backends.iter().find(|(existing, _)| existing == name).map(|(_, store)| store)
+    }
+
+    pub fn backend_mut(&mut self, name: &str) -> Option<&mut S> {
+        self.backends.iter_mut().find(|(existing, _)| existing == name).map(|(_, store)| store)
+    }
+
+    pub fn backend_names(&self) -> impl Iterator<Item = &str> {
+        self.backends.iter().map(|(name, _)| name.as_str())
+    }
+
+    /// Name of the backend an entity lives in
+    pub fn backend_of(&self, entity_id: EntityId) -> &str {
+        &self.backends[self.index_for(entity_id)].0
+    }
+
+    fn index_of(&self, name: &str) -> Result<usize> {
+        self.backends
+            .iter()
+            .position(|(existing, _)| existing == name)
+            .ok_or_else(|| Error::InvalidRequest(format!("No backend '{}'", name)))
+    }
+
+    fn index_for(&self, entity_id: EntityId) -> usize {
+        let id = entity_id.extract_id();
+        self.id_routes
+            .iter()
+            .find(|(ids, _)| ids.contains(&id))
+            .map(|(_, index)| *index)
+            .or_else(|| self.type_routes.get(&entity_id.extract_type()).copied())
+            .unwrap_or(0)
+    }
+
+    fn store_for(&self, entity_id: EntityId) -> &S {
+        &self.backends[self.index_for(entity_id)].1
+    }
+
+    fn store_for_mut(&mut self, entity_id: EntityId) -> &mut S {
+        let index = self.index_for(entity_id);
+        &mut self.backends[index].1
+    }
+
+    fn default_store(&self) -> &S {
+        &self.backends[0].1
+    }
+
+    /// Matching entities from every backend, sorted by id
+    fn find_everywhere(&self, entity_type: EntityType, filter: Option<&str>) -> Result<Vec<EntityId>> {
+        let mut found = Vec::new();
+        for (_, store) in &self.backends {
+            found.extend(store.find_entities(entity_type, filter)?);
+        }
+        found.sort();
+        found.dedup();
+        Ok(found)
+    }
+
+    /// Page through merged find results the way `Store` pages its own
+    fn paginate(&self, entity_ids: Vec<EntityId>, page_opts: Option<&PageOpts>) -> Result<PageResult<EntityId>> {
+        let opts = page_opts.cloned().unwrap_or_default();
+        let total = entity_ids.len();
+
+        let Some((field_type, direction)) = opts.order_by else {
+            let start_idx = opts.cursor.unwrap_or(0).min(total);
+            let end_idx = std::cmp::min(start_idx.saturating_add(opts.limit), total);
+            let next_cursor = (end_idx < total).then_some(end_idx);
+            return Ok(PageResult::new(entity_ids[start_idx..end_idx].to_vec(), total, next_cursor));
+        };
+
+        let mut keys = Vec::with_capacity(total);
+        for entity_id in entity_ids {
+            let (value, _, _) = self.read(entity_id, &[field_type])?;
+            keys.push((value, entity_id));
+        }
+        keys.sort_by(|a, b| direction.compare(a, b));
+
+        let start_idx = match &opts.after {
+            Some(after) => keys.partition_point(|key| direction.compare(key, after).is_le()),
+            None => 0,
+        };
+        let end_idx = std::cmp::min(start_idx.saturating_add(opts.limit), total);
+        let next_key = if end_idx < total && end_idx > start_idx {
+            Some(keys[end_idx - 1].clone())
+        } else {
+            None
+        };
+        let items = keys[start_idx..end_idx].iter().map(|(_, entity_id)| *entity_id).collect();
+        Ok(PageResult::new(items, total, None).with_next_key(next_key))
+    }
+}
+
+impl FederatedStore<StoreProxy> {
+    /// Register a notification with the backends that can raise it: the one
+    /// holding the entity for `NotifyConfig::EntityId`, every backend for
+    /// `NotifyConfig::EntityType`. Notifications from all of them arrive on
+    /// `sender`.
+    pub fn register_notification(&self, config: NotifyConfig, sender: Sender<Notification>) -> Result<()> {
+        match &config {
+            NotifyConfig::EntityId { entity_id, .. } => self.store_for(*entity_id).register_notification(config.clone(), sender),
+            NotifyConfig::EntityType { .. } => {
+                for (_, proxy) in &self.backends {
+                    proxy.register_notification(config.clone(), sender.clone())?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Undo `register_notification`. Returns true if any backend had it.
+    pub fn unregister_notification(&self, config: &NotifyConfig, sender: &Sender<Notification>) -> bool {
+        match config {
+            NotifyConfig::EntityId { entity_id, .. } => self.store_for(*entity_id).unregister_notification(config, sender),
+            NotifyConfig::EntityType { .. } => self
+                .backends
+                .iter()
+                .fold(false, |removed, (_, proxy)| proxy.unregister_notification(config, sender) || removed),
+        }
+    }
+
+    /// Read pending notifications from every backend
+    pub fn process_notifications(&self) -> Result<()> {
+        for (_, proxy) in &self.backends {
+            proxy.process_notifications()?;
+        }
+        Ok(())
+    }
+}
+
+impl<S: StoreTrait> StoreTrait for FederatedStore<S> {
+    fn get_entity_type(&self, name: &str) -> Result<EntityType> {
+        self.default_store().get_entity_type(name)
+    }
+
+    fn resolve_entity_type(&self, entity_type: EntityType) -> Result<String> {
+        self.default_store().resolve_entity_type(entity_type)
+    }
+
+    fn get_field_type(&self, name: &str) -> Result<FieldType> {
+        self.default_store().get_field_type(name)
+    }
+
+    fn resolve_field_type(&self, field_type: FieldType) -> Result<String> {
+        self.default_store().resolve_field_type(field_type)
+    }
+
+    fn get_types_bulk(&self, entity_types: &[&str], field_types: &[&str]) -> Result<crate::TypesBulk> {
+        self.default_store().get_types_bulk(entity_types, field_types)
+    }
+
+    fn get_entity_schema(&self, entity_type: EntityType) -> Result<EntitySchema<Single>> {
+        self.default_store().get_entity_schema(entity_type)
+    }
+
+    fn get_complete_entity_schema(&self, entity_type: EntityType) -> Result<&EntitySchema<Complete>> {
+        self.default_store().get_complete_entity_schema(entity_type)
+    }
+
+    fn get_complete_entity_schema_owned(&self, entity_type: EntityType) -> Result<EntitySchema<Complete>> {
+        self.default_store().get_complete_entity_schema_owned(entity_type)
+    }
+
+    fn get_field_schema(&self, entity_type: EntityType, field_type: FieldType) -> Result<FieldSchema> {
+        self.default_store().get_field_schema(entity_type, field_type)
+    }
+
+    fn set_field_schema(&mut self, entity_type: EntityType, field_type: FieldType, schema: FieldSchema) -> Result<()> {
+        for (_, store) in self.backends.iter_mut() {
+            store.set_field_schema(entity_type, field_type, schema.clone())?;
+        }
+        Ok(())
+    }
+
+    fn entity_exists(&self, entity_id: EntityId) -> bool {
+        self.store_for(entity_id).entity_exists(entity_id)
+    }
+
+    fn field_exists(&self, entity_type: EntityType, field_type: FieldType) -> bool {
+        self.default_store().field_exists(entity_type, field_type)
+    }
+
+    fn resolve_indirection(&self, entity_id: EntityId, fields: &[FieldType]) -> Result<(EntityId, FieldType)> {
+        let Some((last, path)) = fields.split_last() else {
+            return Err(Error::BadIndirection(
+                entity_id,
+                fields.to_vec(),
+                BadIndirectionReason::UnexpectedValueType(FieldType(0), "Empty field path".to_string()),
+            ));
+        };
+
+        let mut current_entity_id = entity_id;
+        for field_type in path {
+            let (value, _, _) = self.store_for(current_entity_id).read(current_entity_id, &[*field_type]).map_err(|e| {
+                Error::BadIndirection(
+                    current_entity_id,
+                    fields.to_vec(),
+                    BadIndirectionReason::FailedToResolveField(*field_type, e.to_string()),
+                )
+            })?;
+            current_entity_id = match value {
+                Value::EntityReference(Some(reference)) if self.entity_exists(reference) => reference,
+                Value::EntityReference(Some(reference)) => {
+                    return Err(Error::BadIndirection(current_entity_id, fields.to_vec(), BadIndirectionReason::InvalidEntityId(reference)));
+                }
+                Value::EntityReference(None) => {
+                    return Err(Error::BadIndirection(current_entity_id, fields.to_vec(), BadIndirectionReason::EmptyEntityReference));
+                }
+                other => {
+                    return Err(Error::BadIndirection(
+                        current_entity_id,
+                        fields.to_vec(),
+                        BadIndirectionReason::UnexpectedValueType(*field_type, format!("{:?}", other)),
+                    ));
+                }
+            };
+        }
+        Ok((current_entity_id, *last))
+    }
+
+    fn read(&self, entity_id: EntityId, field_path: &[FieldType]) -> Result<(Value, Timestamp, Option<EntityId>)> {
+        let (entity_id, field_type) = self.resolve_indirection(entity_id, field_path)?;
+        self.store_for(entity_id).read(entity_id, &[field_type])
+    }
+
+    fn write(&mut self, entity_id: EntityId, field_path: &[FieldType], value: Value, writer_id: Option<EntityId>, write_time: Option<Timestamp>, push_condition: Option<PushCondition>, adjust_behavior: Option<AdjustBehavior>) -> Result<()> {
+        let (entity_id, field_type) = self.resolve_indirection(entity_id, field_path)?;
+        self.store_for_mut(entity_id).write(entity_id, &[field_type], value, writer_id, write_time, push_condition, adjust_behavior)
+    }
+
+    fn create_entity(&mut self, entity_type: EntityType, parent_id: Option<EntityId>, name: &str) -> Result<EntityId> {
+        let index = match (self.type_routes.get(&entity_type), parent_id) {
+            (Some(index), _) => *index,
+            (None, Some(parent_id)) => self.index_for(parent_id),
+            (None, None) => 0,
+        };
+        let entity_id = self.backends[index].1.create_entity(entity_type, parent_id, name)?;
+
+        // An id outside the backend's ranges would be looked up elsewhere
+        if self.index_for(entity_id) != index {
+            self.backends[index].1.delete_entity(entity_id)?;
+            return Err(Error::InvalidRequest(format!(
+                "Backend '{}' created {:?}, which is routed to '{}'",
+                self.backends[index].0,
+                entity_id,
+                self.backend_of(entity_id)
+            )));
+        }
+        Ok(entity_id)
+    }
+
+    fn delete_entity(&mut self, entity_id: EntityId) -> Result<()> {
+        self.store_for_mut(entity_id).delete_entity(entity_id)
+    }
+
+    fn update_schema(&mut self, schema: EntitySchema<Single, String, String>) -> Result<()> {
+        for (_, store) in self.backends.iter_mut() {
+            store.update_schema(schema.clone())?;
+        }
+        Ok(())
+    }
+
+    /// The first backend's snapshot with every other backend's entities and
+    /// fields added. It has no checksum, since no single store holds it.
+    fn take_snapshot(&self) -> crate::data::Snapshot {
+        let mut snapshot = self.default_store().take_snapshot();
+        for (_, store) in &self.backends[1..] {
+            let other = store.take_snapshot();
+            for (entity_type, entity_ids) in other.entities {
+                let entities = snapshot.entities.entry(entity_type).or_default();
+                for entity_id in entity_ids.iter() {
+                    entities.find_or_push(*entity_id);
+                }
+            }
+            snapshot.fields.extend(other.fields);
+            snapshot.series.extend(other.series);
+        }
+        snapshot.checksum = None;
+        snapshot
+    }
+
+    /// Counts and sizes added up across the backends. Type names are
+    /// shared, so those are the largest of any backend.
+    fn stats(&self) -> Result<StoreStats> {
+        let mut total = StoreStats::default();
+        for (_, store) in &self.backends {
+            let stats = store.stats()?;
+            for (name, count) in stats.entity_counts {
+                *total.entity_counts.entry(name).or_default() += count;
+            }
+            total.field_count += stats.field_count;
+            total.memory_estimate += stats.memory_estimate;
+            total.entity_notifications += stats.entity_notifications;
+            total.type_notifications += stats.type_notifications;
+            total.entity_type_names = total.entity_type_names.max(stats.entity_type_names);
+            total.field_type_names = total.field_type_names.max(stats.field_type_names);
+        }
+        Ok(total)
+    }
+
+    fn find_entities_paginated(&self, entity_type: EntityType, page_opts: Option<&PageOpts>, filter: Option<&str>) -> Result<PageResult<EntityId>> {
+        self.paginate(self.find_everywhere(entity_type, filter)?, page_opts)
+    }
+
+    fn find_entities_exact(&self, entity_type: EntityType, page_opts: Option<&PageOpts>, filter: Option<&str>) -> Result<PageResult<EntityId>> {
+        let mut found = self.find_everywhere(entity_type, filter)?;
+        found.retain(|entity_id| entity_id.extract_type() == entity_type);
+        self.paginate(found, page_opts)
+    }
+
+    fn find_entities(&self, entity_type: EntityType, filter: Option<&str>) -> Result<Vec<EntityId>> {
+        self.find_everywhere(entity_type, filter)
+    }
+
+    fn get_entity_types(&self) -> Result<Vec<EntityType>> {
+        self.default_store().get_entity_types()
+    }
+
+    fn get_entity_types_paginated(&self, page_opts: Option<&PageOpts>) -> Result<PageResult<EntityType>> {
+        self.default_store().get_entity_types_paginated(page_opts)
+    }
+}
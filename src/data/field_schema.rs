@@ -1,5 +1,8 @@
-use crate::{data::{FieldType, Timestamp}, EntityId, StoreTrait, Value};
+use std::collections::BTreeMap;
+
+use crate::{data::{Decimal, Duration, FieldType, Timestamp}, EntityId, StoreTrait, Value};
 use serde::{Deserialize, Serialize};
+use qlib_rs_derive::{RespDecode, RespEncode};
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum StorageScope {
@@ -7,6 +10,201 @@ pub enum StorageScope {
     Configuration
 }
 
+/// How diverged values of a field are reconciled when two stores sync with
+/// each other after a partition
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MergePolicy {
+    /// The write with the most recent write time wins
+    #[default]
+    LastWriterWins,
+    /// Add/Subtract adjustments are replayed as deltas so concurrent increments add up
+    Counter,
+    /// Entity lists are merged as the union of both sides
+    SetUnion,
+}
+
+impl MergePolicy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MergePolicy::LastWriterWins => "LastWriterWins",
+            MergePolicy::Counter => "Counter",
+            MergePolicy::SetUnion => "SetUnion",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "LastWriterWins" => Some(MergePolicy::LastWriterWins),
+            "Counter" => Some(MergePolicy::Counter),
+            "SetUnion" => Some(MergePolicy::SetUnion),
+            _ => None,
+        }
+    }
+}
+
+/// Who may write a field, checked against the request's `ClientContext`.
+/// Admins pass every scope. Writes made without an authenticated subject
+/// (local code, replication, triggers) are not restricted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WriteScope {
+    /// Only the entity itself, e.g. a user changing their own password
+    OwnerOnly,
+    /// Only subjects that are (or inherit from) `Service`
+    ServiceOnly,
+    /// Only admins
+    AdminOnly,
+}
+
+impl WriteScope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WriteScope::OwnerOnly => "OwnerOnly",
+            WriteScope::ServiceOnly => "ServiceOnly",
+            WriteScope::AdminOnly => "AdminOnly",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "OwnerOnly" => Some(WriteScope::OwnerOnly),
+            "ServiceOnly" => Some(WriteScope::ServiceOnly),
+            "AdminOnly" => Some(WriteScope::AdminOnly),
+            _ => None,
+        }
+    }
+}
+
+/// What happens to an `EntityReference`/`EntityList` field when an entity it
+/// points at is deleted with `delete_entity`. Without one the reference is
+/// left dangling.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OnDelete {
+    /// Clear the reference, or remove the entity from the list
+    Nullify,
+    /// Delete the referencing entity too
+    Cascade,
+    /// Refuse to delete an entity that is still referenced
+    Restrict,
+}
+
+impl OnDelete {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OnDelete::Nullify => "Nullify",
+            OnDelete::Cascade => "Cascade",
+            OnDelete::Restrict => "Restrict",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "Nullify" => Some(OnDelete::Nullify),
+            "Cascade" => Some(OnDelete::Cascade),
+            "Restrict" => Some(OnDelete::Restrict),
+            _ => None,
+        }
+    }
+}
+
+/// Type of the samples a `Series` field keeps
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SampleType {
+    Int,
+    Float,
+}
+
+impl SampleType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SampleType::Int => "Int",
+            SampleType::Float => "Float",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "Int" => Some(SampleType::Int),
+            "Float" => Some(SampleType::Float),
+            _ => None,
+        }
+    }
+
+    /// The sample type of a value, if it is one
+    pub fn of(value: &Value) -> Option<Self> {
+        match value {
+            Value::Int(_) => Some(SampleType::Int),
+            Value::Float(_) => Some(SampleType::Float),
+            _ => None,
+        }
+    }
+
+    /// Value of a series that was never written
+    pub fn zero(&self) -> Value {
+        match self {
+            SampleType::Int => Value::Int(0),
+            SampleType::Float => Value::Float(0.0),
+        }
+    }
+}
+
+/// Engineering metadata describing a field to clients.
+///
+/// `min` and `max` are also enforced by the store on writes to numeric
+/// fields (Int, Float, Decimal, and Duration in seconds).
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, RespEncode, RespDecode)]
+pub struct FieldMetadata {
+    /// Unit of measure, e.g. `rpm` or `°C`
+    #[serde(default)]
+    pub unit: Option<String>,
+    #[serde(default)]
+    pub min: Option<f64>,
+    #[serde(default)]
+    pub max: Option<f64>,
+    /// Number of decimal places clients should display
+    #[serde(default)]
+    pub precision: Option<u8>,
+    /// Human-readable name to show instead of the field name
+    #[serde(default)]
+    pub label: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+    /// Restricts who may write the field; anyone can when unset
+    #[serde(default)]
+    pub write_scope: Option<WriteScope>,
+    /// For reference fields, what deleting a referenced entity does
+    #[serde(default)]
+    #[resp(default)]
+    pub on_delete: Option<OnDelete>,
+    /// For String fields, whether `Store::search` indexes the text (with
+    /// the `search` feature)
+    #[serde(default)]
+    #[resp(default)]
+    pub searchable: bool,
+    /// For the Name field, whether an entity's name must differ from the
+    /// names of the other children of its parent
+    #[serde(default)]
+    #[resp(default)]
+    pub unique_among_siblings: bool,
+}
+
+impl FieldMetadata {
+    pub fn is_empty(&self) -> bool {
+        *self == FieldMetadata::default()
+    }
+
+    /// Check a value against `min`/`max`; non-numeric values always pass
+    pub fn in_range(&self, value: &Value) -> bool {
+        let number = match value {
+            Value::Int(i) => *i as f64,
+            Value::Float(f) => *f,
+            Value::Decimal(d) => d.to_f64(),
+            Value::Duration(d) => d.as_seconds_f64(),
+            _ => return true,
+        };
+        self.min.is_none_or(|min| number >= min) && self.max.is_none_or(|max| number <= max)
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum FieldSchema<T=FieldType> {
     Blob {
@@ -14,56 +212,180 @@ pub enum FieldSchema<T=FieldType> {
         default_value: Vec<u8>,
         rank: i64,
         storage_scope: StorageScope,
+        #[serde(default)]
+        validator: Option<String>,
+        #[serde(default)]
+        metadata: FieldMetadata,
     },
     Bool {
         field_type: T,
         default_value: bool,
         rank: i64,
         storage_scope: StorageScope,
+        #[serde(default)]
+        validator: Option<String>,
+        #[serde(default)]
+        metadata: FieldMetadata,
     },
     Choice {
         field_type: T,
         default_value: i64,
         rank: i64,
         choices: Vec<String>,
+        /// Field path (e.g. `Root/Config/Modes/Children`) of an EntityList
+        /// whose entity names are used as the choices instead of `choices`
+        #[serde(default)]
+        choices_source: Option<String>,
         storage_scope: StorageScope,
+        #[serde(default)]
+        validator: Option<String>,
+        #[serde(default)]
+        metadata: FieldMetadata,
     },
     EntityList {
         field_type: T,
         default_value: Vec<EntityId>,
         rank: i64,
         storage_scope: StorageScope,
+        #[serde(default)]
+        merge_policy: MergePolicy,
+        #[serde(default)]
+        validator: Option<String>,
+        #[serde(default)]
+        metadata: FieldMetadata,
     },
     EntityReference {
         field_type: T,
         default_value: Option<EntityId>,
         rank: i64,
         storage_scope: StorageScope,
+        #[serde(default)]
+        validator: Option<String>,
+        #[serde(default)]
+        metadata: FieldMetadata,
     },
     Float {
         field_type: T,
         default_value: f64,
         rank: i64,
         storage_scope: StorageScope,
+        #[serde(default)]
+        merge_policy: MergePolicy,
+        #[serde(default)]
+        validator: Option<String>,
+        #[serde(default)]
+        metadata: FieldMetadata,
     },
     Int {
         field_type: T,
         default_value: i64,
         rank: i64,
         storage_scope: StorageScope,
+        #[serde(default)]
+        merge_policy: MergePolicy,
+        #[serde(default)]
+        validator: Option<String>,
+        #[serde(default)]
+        metadata: FieldMetadata,
     },
     String {
         field_type: T,
         default_value: String,
         rank: i64,
         storage_scope: StorageScope,
+        #[serde(default)]
+        validator: Option<String>,
+        #[serde(default)]
+        metadata: FieldMetadata,
     },
     Timestamp {
         field_type: T,
         default_value: Timestamp,
         rank: i64,
         storage_scope: StorageScope,
-    }
+        #[serde(default)]
+        validator: Option<String>,
+        #[serde(default)]
+        metadata: FieldMetadata,
+    },
+    /// Read-only field whose value is produced by evaluating a CEL expression
+    /// against the owning entity when it is read (e.g. `Speed * Ratio` or
+    /// `Parent->Speed`). A path through an `EntityList` aggregates over the
+    /// listed entities with `Sum`, `Count`, `Min` or `Max`, e.g.
+    /// `Sum(Children->Power)`. The result is cached until one of the fields
+    /// the expression references is written.
+    Computed {
+        field_type: T,
+        expression: String,
+        rank: i64,
+        storage_scope: StorageScope,
+        #[serde(default)]
+        metadata: FieldMetadata,
+    },
+    Decimal {
+        field_type: T,
+        default_value: Decimal,
+        rank: i64,
+        storage_scope: StorageScope,
+        #[serde(default)]
+        merge_policy: MergePolicy,
+        #[serde(default)]
+        validator: Option<String>,
+        #[serde(default)]
+        metadata: FieldMetadata,
+    },
+    Duration {
+        field_type: T,
+        default_value: Duration,
+        rank: i64,
+        storage_scope: StorageScope,
+        #[serde(default)]
+        merge_policy: MergePolicy,
+        #[serde(default)]
+        validator: Option<String>,
+        #[serde(default)]
+        metadata: FieldMetadata,
+    },
+    StringList {
+        field_type: T,
+        default_value: Vec<String>,
+        rank: i64,
+        storage_scope: StorageScope,
+        #[serde(default)]
+        merge_policy: MergePolicy,
+        #[serde(default)]
+        validator: Option<String>,
+        #[serde(default)]
+        metadata: FieldMetadata,
+    },
+    /// String-keyed map of values, for structured per-field data
+    Map {
+        field_type: T,
+        default_value: BTreeMap<String, Value>,
+        rank: i64,
+        storage_scope: StorageScope,
+        #[serde(default)]
+        validator: Option<String>,
+        #[serde(default)]
+        metadata: FieldMetadata,
+    },
+    /// Int or Float field that also keeps the history of the values written
+    /// to it, e.g. for measurement trends. Reads give the latest sample and
+    /// `Store::read_series` the history: at most `capacity` samples, and with
+    /// a `retention` none older than that before the latest.
+    Series {
+        field_type: T,
+        sample_type: SampleType,
+        capacity: usize,
+        #[serde(default)]
+        retention: Option<Duration>,
+        rank: i64,
+        storage_scope: StorageScope,
+        #[serde(default)]
+        validator: Option<String>,
+        #[serde(default)]
+        metadata: FieldMetadata,
+    },
 }
 
 impl<T: Clone> FieldSchema<T> {
@@ -78,6 +400,12 @@ impl<T: Clone> FieldSchema<T> {
             FieldSchema::Int { field_type, .. } => field_type.clone(),
             FieldSchema::String { field_type, .. } => field_type.clone(),
             FieldSchema::Timestamp { field_type, .. } => field_type.clone(),
+            FieldSchema::Computed { field_type, .. } => field_type.clone(),
+            FieldSchema::Decimal { field_type, .. } => field_type.clone(),
+            FieldSchema::Duration { field_type, .. } => field_type.clone(),
+            FieldSchema::StringList { field_type, .. } => field_type.clone(),
+            FieldSchema::Map { field_type, .. } => field_type.clone(),
+            FieldSchema::Series { field_type, .. } => field_type.clone(),
         }
     }
 
@@ -92,6 +420,13 @@ impl<T: Clone> FieldSchema<T> {
             FieldSchema::Int { default_value, .. } => Value::Int(*default_value),
             FieldSchema::String { default_value, .. } => Value::String(default_value.clone()),
             FieldSchema::Timestamp { default_value, .. } => Value::Timestamp(*default_value),
+            // Computed fields are never stored, so there is no meaningful default
+            FieldSchema::Computed { .. } => Value::String(String::new()),
+            FieldSchema::Decimal { default_value, .. } => Value::Decimal(*default_value),
+            FieldSchema::Duration { default_value, .. } => Value::Duration(*default_value),
+            FieldSchema::StringList { default_value, .. } => Value::StringList(default_value.clone()),
+            FieldSchema::Map { default_value, .. } => Value::Map(default_value.clone()),
+            FieldSchema::Series { sample_type, .. } => sample_type.zero(),
         }
     }
 
@@ -106,6 +441,12 @@ impl<T: Clone> FieldSchema<T> {
             FieldSchema::Int { rank, .. } => *rank,
             FieldSchema::String { rank, .. } => *rank,
             FieldSchema::Timestamp { rank, .. } => *rank,
+            FieldSchema::Computed { rank, .. } => *rank,
+            FieldSchema::Decimal { rank, .. } => *rank,
+            FieldSchema::Duration { rank, .. } => *rank,
+            FieldSchema::StringList { rank, .. } => *rank,
+            FieldSchema::Map { rank, .. } => *rank,
+            FieldSchema::Series { rank, .. } => *rank,
         }
     }
 
@@ -120,6 +461,204 @@ impl<T: Clone> FieldSchema<T> {
             FieldSchema::Int { storage_scope, .. } => storage_scope,
             FieldSchema::String { storage_scope, .. } => storage_scope,
             FieldSchema::Timestamp { storage_scope, .. } => storage_scope,
+            FieldSchema::Computed { storage_scope, .. } => storage_scope,
+            FieldSchema::Decimal { storage_scope, .. } => storage_scope,
+            FieldSchema::Duration { storage_scope, .. } => storage_scope,
+            FieldSchema::StringList { storage_scope, .. } => storage_scope,
+            FieldSchema::Map { storage_scope, .. } => storage_scope,
+            FieldSchema::Series { storage_scope, .. } => storage_scope,
+        }
+    }
+
+    pub fn merge_policy(&self) -> MergePolicy {
+        match self {
+            FieldSchema::EntityList { merge_policy, .. } => merge_policy.clone(),
+            FieldSchema::Float { merge_policy, .. } => merge_policy.clone(),
+            FieldSchema::Int { merge_policy, .. } => merge_policy.clone(),
+            FieldSchema::Decimal { merge_policy, .. } => merge_policy.clone(),
+            FieldSchema::Duration { merge_policy, .. } => merge_policy.clone(),
+            FieldSchema::StringList { merge_policy, .. } => merge_policy.clone(),
+            _ => MergePolicy::LastWriterWins,
+        }
+    }
+
+    pub fn with_storage_scope(mut self, scope: StorageScope) -> Self {
+        match &mut self {
+            FieldSchema::Blob { storage_scope, .. } => *storage_scope = scope,
+            FieldSchema::Bool { storage_scope, .. } => *storage_scope = scope,
+            FieldSchema::Choice { storage_scope, .. } => *storage_scope = scope,
+            FieldSchema::EntityList { storage_scope, .. } => *storage_scope = scope,
+            FieldSchema::EntityReference { storage_scope, .. } => *storage_scope = scope,
+            FieldSchema::Float { storage_scope, .. } => *storage_scope = scope,
+            FieldSchema::Int { storage_scope, .. } => *storage_scope = scope,
+            FieldSchema::String { storage_scope, .. } => *storage_scope = scope,
+            FieldSchema::Timestamp { storage_scope, .. } => *storage_scope = scope,
+            FieldSchema::Computed { storage_scope, .. } => *storage_scope = scope,
+            FieldSchema::Decimal { storage_scope, .. } => *storage_scope = scope,
+            FieldSchema::Duration { storage_scope, .. } => *storage_scope = scope,
+            FieldSchema::StringList { storage_scope, .. } => *storage_scope = scope,
+            FieldSchema::Map { storage_scope, .. } => *storage_scope = scope,
+            FieldSchema::Series { storage_scope, .. } => *storage_scope = scope,
+        }
+        self
+    }
+
+    /// Same schema merged with `policy`. Fields that can only be merged by
+    /// last writer wins are returned unchanged.
+    pub fn with_merge_policy(mut self, policy: MergePolicy) -> Self {
+        match &mut self {
+            FieldSchema::EntityList { merge_policy, .. }
+            | FieldSchema::Float { merge_policy, .. }
+            | FieldSchema::Int { merge_policy, .. }
+            | FieldSchema::Decimal { merge_policy, .. }
+            | FieldSchema::Duration { merge_policy, .. }
+            | FieldSchema::StringList { merge_policy, .. } => *merge_policy = policy,
+            _ => {}
+        }
+        self
+    }
+
+    /// CEL expression a write must satisfy, with `old` and `new` bound to the
+    /// current and proposed values
+    pub fn validator(&self) -> Option<&str> {
+        match self {
+            FieldSchema::Blob { validator, .. } => validator.as_deref(),
+            FieldSchema::Bool { validator, .. } => validator.as_deref(),
+            FieldSchema::Choice { validator, .. } => validator.as_deref(),
+            FieldSchema::EntityList { validator, .. } => validator.as_deref(),
+            FieldSchema::EntityReference { validator, .. } => validator.as_deref(),
+            FieldSchema::Float { validator, .. } => validator.as_deref(),
+            FieldSchema::Int { validator, .. } => validator.as_deref(),
+            FieldSchema::String { validator, .. } => validator.as_deref(),
+            FieldSchema::Timestamp { validator, .. } => validator.as_deref(),
+            FieldSchema::Computed { .. } => None,
+            FieldSchema::Decimal { validator, .. } => validator.as_deref(),
+            FieldSchema::Duration { validator, .. } => validator.as_deref(),
+            FieldSchema::StringList { validator, .. } => validator.as_deref(),
+            FieldSchema::Map { validator, .. } => validator.as_deref(),
+            FieldSchema::Series { validator, .. } => validator.as_deref(),
+        }
+    }
+
+    pub fn metadata(&self) -> &FieldMetadata {
+        match self {
+            FieldSchema::Blob { metadata, .. } => metadata,
+            FieldSchema::Bool { metadata, .. } => metadata,
+            FieldSchema::Choice { metadata, .. } => metadata,
+            FieldSchema::EntityList { metadata, .. } => metadata,
+            FieldSchema::EntityReference { metadata, .. } => metadata,
+            FieldSchema::Float { metadata, .. } => metadata,
+            FieldSchema::Int { metadata, .. } => metadata,
+            FieldSchema::String { metadata, .. } => metadata,
+            FieldSchema::Timestamp { metadata, .. } => metadata,
+            FieldSchema::Computed { metadata, .. } => metadata,
+            FieldSchema::Decimal { metadata, .. } => metadata,
+            FieldSchema::Duration { metadata, .. } => metadata,
+            FieldSchema::StringList { metadata, .. } => metadata,
+            FieldSchema::Map { metadata, .. } => metadata,
+            FieldSchema::Series { metadata, .. } => metadata,
+        }
+    }
+
+    pub fn metadata_mut(&mut self) -> &mut FieldMetadata {
+        match self {
+            FieldSchema::Blob { metadata, .. } => metadata,
+            FieldSchema::Bool { metadata, .. } => metadata,
+            FieldSchema::Choice { metadata, .. } => metadata,
+            FieldSchema::EntityList { metadata, .. } => metadata,
+            FieldSchema::EntityReference { metadata, .. } => metadata,
+            FieldSchema::Float { metadata, .. } => metadata,
+            FieldSchema::Int { metadata, .. } => metadata,
+            FieldSchema::String { metadata, .. } => metadata,
+            FieldSchema::Timestamp { metadata, .. } => metadata,
+            FieldSchema::Computed { metadata, .. } => metadata,
+            FieldSchema::Decimal { metadata, .. } => metadata,
+            FieldSchema::Duration { metadata, .. } => metadata,
+            FieldSchema::StringList { metadata, .. } => metadata,
+            FieldSchema::Map { metadata, .. } => metadata,
+            FieldSchema::Series { metadata, .. } => metadata,
+        }
+    }
+
+    /// Same schema with the given metadata
+    pub fn with_metadata(mut self, metadata: FieldMetadata) -> Self {
+        *self.metadata_mut() = metadata;
+        self
+    }
+
+    /// Who may write this field, see `WriteScope`
+    pub fn write_scope(&self) -> Option<WriteScope> {
+        self.metadata().write_scope
+    }
+
+    pub fn with_write_scope(mut self, write_scope: WriteScope) -> Self {
+        self.metadata_mut().write_scope = Some(write_scope);
+        self
+    }
+
+    /// What deleting a referenced entity does to this field, see `OnDelete`.
+    /// Only reference fields have one.
+    pub fn on_delete(&self) -> Option<OnDelete> {
+        match self {
+            FieldSchema::EntityReference { metadata, .. } | FieldSchema::EntityList { metadata, .. } => metadata.on_delete,
+            _ => None,
+        }
+    }
+
+    pub fn with_on_delete(mut self, on_delete: OnDelete) -> Self {
+        self.metadata_mut().on_delete = Some(on_delete);
+        self
+    }
+
+    /// Whether the text of this field is indexed for `Store::search`.
+    /// Only String fields can be searchable.
+    pub fn is_searchable(&self) -> bool {
+        match self {
+            FieldSchema::String { metadata, .. } => metadata.searchable,
+            _ => false,
+        }
+    }
+
+    pub fn with_searchable(mut self, searchable: bool) -> Self {
+        self.metadata_mut().searchable = searchable;
+        self
+    }
+
+    /// Whether names must be unique among siblings, for a Name field
+    pub fn is_unique_among_siblings(&self) -> bool {
+        self.metadata().unique_among_siblings
+    }
+
+    pub fn with_unique_among_siblings(mut self, unique: bool) -> Self {
+        self.metadata_mut().unique_among_siblings = unique;
+        self
+    }
+
+    /// How many samples a series keeps, and for how long
+    pub fn series_limits(&self) -> Option<(usize, Option<Duration>)> {
+        match self {
+            FieldSchema::Series { capacity, retention, .. } => Some((*capacity, *retention)),
+            _ => None,
+        }
+    }
+
+    /// The CEL expression of a computed field
+    pub fn expression(&self) -> Option<&str> {
+        match self {
+            FieldSchema::Computed { expression, .. } => Some(expression.as_str()),
+            _ => None,
+        }
+    }
+
+    pub fn is_computed(&self) -> bool {
+        matches!(self, FieldSchema::Computed { .. })
+    }
+
+    /// Field path of the EntityList providing the choices, if they are data-driven
+    pub fn choices_source(&self) -> Option<&str> {
+        match self {
+            FieldSchema::Choice { choices_source, .. } => choices_source.as_deref(),
+            _ => None,
         }
     }
 
@@ -134,120 +673,268 @@ impl<T: Clone> FieldSchema<T> {
 impl FieldSchema {
     pub fn from_string_schema(schema: FieldSchema<String>, store: &impl StoreTrait) -> Self {
         match schema {
-            FieldSchema::Blob { field_type, default_value, rank, storage_scope } => FieldSchema::Blob {
+            FieldSchema::Blob { field_type, default_value, rank, storage_scope, validator, metadata } => FieldSchema::Blob {
                 field_type: store.get_field_type(field_type.as_str()).expect("Field type not found"),
                 default_value,
                 rank,
                 storage_scope,
+                validator,
+                metadata,
             },
-            FieldSchema::Bool { field_type, default_value, rank, storage_scope } => FieldSchema::Bool {
+            FieldSchema::Bool { field_type, default_value, rank, storage_scope, validator, metadata } => FieldSchema::Bool {
                 field_type: store.get_field_type(field_type.as_str()).expect("Field type not found"),
                 default_value,
                 rank,
                 storage_scope,
+                validator,
+                metadata,
             },
-            FieldSchema::Choice { field_type, default_value, rank, choices, storage_scope } => FieldSchema::Choice {
+            FieldSchema::Choice { field_type, default_value, rank, choices, choices_source, storage_scope, validator, metadata } => FieldSchema::Choice {
                 field_type: store.get_field_type(field_type.as_str()).expect("Field type not found"),
                 default_value,
                 rank,
                 choices,
+                choices_source,
                 storage_scope,
+                validator,
+                metadata,
             },
-            FieldSchema::EntityList { field_type, default_value, rank, storage_scope } => FieldSchema::EntityList {
+            FieldSchema::EntityList { field_type, default_value, rank, storage_scope, merge_policy, validator, metadata } => FieldSchema::EntityList {
                 field_type: store.get_field_type(field_type.as_str()).expect("Field type not found"),
                 default_value,
                 rank,
                 storage_scope,
+                merge_policy,
+                validator,
+                metadata,
             },
-            FieldSchema::EntityReference { field_type, default_value, rank, storage_scope } => FieldSchema::EntityReference {
+            FieldSchema::EntityReference { field_type, default_value, rank, storage_scope, validator, metadata } => FieldSchema::EntityReference {
                 field_type: store.get_field_type(field_type.as_str()).expect("Field type not found"),
                 default_value,
                 rank,
                 storage_scope,
+                validator,
+                metadata,
             },
-            FieldSchema::Float { field_type, default_value, rank, storage_scope } => FieldSchema::Float {
+            FieldSchema::Float { field_type, default_value, rank, storage_scope, merge_policy, validator, metadata } => FieldSchema::Float {
                 field_type: store.get_field_type(field_type.as_str()).expect("Field type not found"),
                 default_value,
                 rank,
                 storage_scope,
+                merge_policy,
+                validator,
+                metadata,
             },
-            FieldSchema::Int { field_type, default_value, rank, storage_scope } => FieldSchema::Int {
+            FieldSchema::Int { field_type, default_value, rank, storage_scope, merge_policy, validator, metadata } => FieldSchema::Int {
                 field_type: store.get_field_type(field_type.as_str()).expect("Field type not found"),
                 default_value,
                 rank,
                 storage_scope,
+                merge_policy,
+                validator,
+                metadata,
             },
-            FieldSchema::String { field_type, default_value, rank, storage_scope } => FieldSchema::String {
+            FieldSchema::String { field_type, default_value, rank, storage_scope, validator, metadata } => FieldSchema::String {
                 field_type: store.get_field_type(field_type.as_str()).expect("Field type not found"),
                 default_value,
                 rank,
                 storage_scope,
+                validator,
+                metadata,
             },
-            FieldSchema::Timestamp { field_type, default_value, rank, storage_scope } => FieldSchema::Timestamp {
+            FieldSchema::Timestamp { field_type, default_value, rank, storage_scope, validator, metadata } => FieldSchema::Timestamp {
                 field_type: store.get_field_type(field_type.as_str()).expect("Field type not found"),
                 default_value,
                 rank,
                 storage_scope,
+                validator,
+                metadata,
+            },
+            FieldSchema::Computed { field_type, expression, rank, storage_scope, metadata } => FieldSchema::Computed {
+                field_type: store.get_field_type(field_type.as_str()).expect("Field type not found"),
+                expression,
+                rank,
+                storage_scope,
+                metadata,
+            },
+            FieldSchema::Decimal { field_type, default_value, rank, storage_scope, merge_policy, validator, metadata } => FieldSchema::Decimal {
+                field_type: store.get_field_type(field_type.as_str()).expect("Field type not found"),
+                default_value,
+                rank,
+                storage_scope,
+                merge_policy,
+                validator,
+                metadata,
+            },
+            FieldSchema::Duration { field_type, default_value, rank, storage_scope, merge_policy, validator, metadata } => FieldSchema::Duration {
+                field_type: store.get_field_type(field_type.as_str()).expect("Field type not found"),
+                default_value,
+                rank,
+                storage_scope,
+                merge_policy,
+                validator,
+                metadata,
+            },
+            FieldSchema::StringList { field_type, default_value, rank, storage_scope, merge_policy, validator, metadata } => FieldSchema::StringList {
+                field_type: store.get_field_type(field_type.as_str()).expect("Field type not found"),
+                default_value,
+                rank,
+                storage_scope,
+                merge_policy,
+                validator,
+                metadata,
+            },
+            FieldSchema::Map { field_type, default_value, rank, storage_scope, validator, metadata } => FieldSchema::Map {
+                field_type: store.get_field_type(field_type.as_str()).expect("Field type not found"),
+                default_value,
+                rank,
+                storage_scope,
+                validator,
+                metadata,
+            },
+            FieldSchema::Series { field_type, sample_type, capacity, retention, rank, storage_scope, validator, metadata } => FieldSchema::Series {
+                field_type: store.get_field_type(field_type.as_str()).expect("Field type not found"),
+                sample_type,
+                capacity,
+                retention,
+                rank,
+                storage_scope,
+                validator,
+                metadata,
             },
         }
     }
 
     pub fn to_string_schema(&self, store: &impl StoreTrait) -> FieldSchema<String> {
         match self {
-            FieldSchema::Blob { field_type, default_value, rank, storage_scope } => FieldSchema::Blob {
+            FieldSchema::Blob { field_type, default_value, rank, storage_scope, validator, metadata } => FieldSchema::Blob {
                 field_type: store.resolve_field_type(*field_type).expect("Field type not found"),
                 default_value: default_value.clone(),
                 rank: *rank,
                 storage_scope: storage_scope.clone(),
+                validator: validator.clone(),
+                metadata: metadata.clone(),
             },
-            FieldSchema::Bool { field_type, default_value, rank, storage_scope } => FieldSchema::Bool {
+            FieldSchema::Bool { field_type, default_value, rank, storage_scope, validator, metadata } => FieldSchema::Bool {
                 field_type: store.resolve_field_type(*field_type).expect("Field type not found"),
                 default_value: *default_value,
                 rank: *rank,
                 storage_scope: storage_scope.clone(),
+                validator: validator.clone(),
+                metadata: metadata.clone(),
             },
-            FieldSchema::Choice { field_type, default_value, rank, choices, storage_scope } => FieldSchema::Choice {
+            FieldSchema::Choice { field_type, default_value, rank, choices, choices_source, storage_scope, validator, metadata } => FieldSchema::Choice {
                 field_type: store.resolve_field_type(*field_type).expect("Field type not found"),
                 default_value: *default_value,
                 rank: *rank,
                 choices: choices.clone(),
+                choices_source: choices_source.clone(),
                 storage_scope: storage_scope.clone(),
+                validator: validator.clone(),
+                metadata: metadata.clone(),
             },
-            FieldSchema::EntityList { field_type, default_value, rank, storage_scope } => FieldSchema::EntityList {
+            FieldSchema::EntityList { field_type, default_value, rank, storage_scope, merge_policy, validator, metadata } => FieldSchema::EntityList {
                 field_type: store.resolve_field_type(*field_type).expect("Field type not found"),
                 default_value: default_value.clone(),
                 rank: *rank,
                 storage_scope: storage_scope.clone(),
+                merge_policy: merge_policy.clone(),
+                validator: validator.clone(),
+                metadata: metadata.clone(),
             },
-            FieldSchema::EntityReference { field_type, default_value, rank, storage_scope } => FieldSchema::EntityReference {
+            FieldSchema::EntityReference { field_type, default_value, rank, storage_scope, validator, metadata } => FieldSchema::EntityReference {
                 field_type: store.resolve_field_type(*field_type).expect("Field type not found"),
                 default_value: default_value.clone(),
                 rank: *rank,
                 storage_scope: storage_scope.clone(),
+                validator: validator.clone(),
+                metadata: metadata.clone(),
             },
-            FieldSchema::Float { field_type, default_value, rank, storage_scope } => FieldSchema::Float {
+            FieldSchema::Float { field_type, default_value, rank, storage_scope, merge_policy, validator, metadata } => FieldSchema::Float {
                 field_type: store.resolve_field_type(*field_type).expect("Field type not found"),
                 default_value: *default_value,
                 rank: *rank,
                 storage_scope: storage_scope.clone(),
+                merge_policy: merge_policy.clone(),
+                validator: validator.clone(),
+                metadata: metadata.clone(),
             },
-            FieldSchema::Int { field_type, default_value, rank, storage_scope } => FieldSchema::Int {
+            FieldSchema::Int { field_type, default_value, rank, storage_scope, merge_policy, validator, metadata } => FieldSchema::Int {
                 field_type: store.resolve_field_type(*field_type).expect("Field type not found"),
                 default_value: *default_value,
                 rank: *rank,
                 storage_scope: storage_scope.clone(),
+                merge_policy: merge_policy.clone(),
+                validator: validator.clone(),
+                metadata: metadata.clone(),
             },
-            FieldSchema::String { field_type, default_value, rank, storage_scope } => FieldSchema::String {
+            FieldSchema::String { field_type, default_value, rank, storage_scope, validator, metadata } => FieldSchema::String {
                 field_type: store.resolve_field_type(*field_type).expect("Field type not found"),
                 default_value: default_value.clone(),
                 rank: *rank,
                 storage_scope: storage_scope.clone(),
+                validator: validator.clone(),
+                metadata: metadata.clone(),
+            },
+            FieldSchema::Timestamp { field_type, default_value, rank, storage_scope, validator, metadata } => FieldSchema::Timestamp {
+                field_type: store.resolve_field_type(*field_type).expect("Field type not found"),
+                default_value: *default_value,
+                rank: *rank,
+                storage_scope: storage_scope.clone(),
+                validator: validator.clone(),
+                metadata: metadata.clone(),
+            },
+            FieldSchema::Computed { field_type, expression, rank, storage_scope, metadata } => FieldSchema::Computed {
+                field_type: store.resolve_field_type(*field_type).expect("Field type not found"),
+                expression: expression.clone(),
+                rank: *rank,
+                storage_scope: storage_scope.clone(),
+                metadata: metadata.clone(),
+            },
+            FieldSchema::Decimal { field_type, default_value, rank, storage_scope, merge_policy, validator, metadata } => FieldSchema::Decimal {
+                field_type: store.resolve_field_type(*field_type).expect("Field type not found"),
+                default_value: *default_value,
+                rank: *rank,
+                storage_scope: storage_scope.clone(),
+                merge_policy: merge_policy.clone(),
+                validator: validator.clone(),
+                metadata: metadata.clone(),
             },
-            FieldSchema::Timestamp { field_type, default_value, rank, storage_scope } => FieldSchema::Timestamp {
+            FieldSchema::Duration { field_type, default_value, rank, storage_scope, merge_policy, validator, metadata } => FieldSchema::Duration {
                 field_type: store.resolve_field_type(*field_type).expect("Field type not found"),
                 default_value: *default_value,
                 rank: *rank,
                 storage_scope: storage_scope.clone(),
+                merge_policy: merge_policy.clone(),
+                validator: validator.clone(),
+                metadata: metadata.clone(),
+            },
+            FieldSchema::StringList { field_type, default_value, rank, storage_scope, merge_policy, validator, metadata } => FieldSchema::StringList {
+                field_type: store.resolve_field_type(*field_type).expect("Field type not found"),
+                default_value: default_value.clone(),
+                rank: *rank,
+                storage_scope: storage_scope.clone(),
+                merge_policy: merge_policy.clone(),
+                validator: validator.clone(),
+                metadata: metadata.clone(),
+            },
+            FieldSchema::Map { field_type, default_value, rank, storage_scope, validator, metadata } => FieldSchema::Map {
+                field_type: store.resolve_field_type(*field_type).expect("Field type not found"),
+                default_value: default_value.clone(),
+                rank: *rank,
+                storage_scope: storage_scope.clone(),
+                validator: validator.clone(),
+                metadata: metadata.clone(),
+            },
+            FieldSchema::Series { field_type, sample_type, capacity, retention, rank, storage_scope, validator, metadata } => FieldSchema::Series {
+                field_type: store.resolve_field_type(*field_type).expect("Field type not found"),
+                sample_type: *sample_type,
+                capacity: *capacity,
+                retention: *retention,
+                rank: *rank,
+                storage_scope: storage_scope.clone(),
+                validator: validator.clone(),
+                metadata: metadata.clone(),
             },
         }
     }
@@ -1,91 +1,205 @@
 use crate::{FieldType, StoreTrait};
 
+pub const ACTION: &str = "Action";
 pub const ACTIVE: &str = "Active";
 pub const AUTH_METHOD: &str = "AuthMethod";
 pub const AVAILABLE_LIST: &str = "AvailableList";
+pub const BUILD_INFO: &str = "BuildInfo";
 pub const CANDIDATE_LIST: &str = "CandidateList";
 pub const CHILDREN: &str = "Children";
+pub const COMPRESSION: &str = "Compression";
 pub const CONDITION: &str = "Condition";
 pub const CURRENT_LEADER: &str = "CurrentLeader";
 pub const DEATH_DETECTION_TIMEOUT: &str = "DeathDetectionTimeout";
 pub const DESCRIPTION: &str = "Description";
+pub const DIRECTORY: &str = "Directory";
 pub const FAIL_OVER: &str = "FailOver";
 pub const FAIL_OVER_GRACE_PERIOD: &str = "FailOverGracePeriod";
 pub const FAILED_ATTEMPTS: &str = "FailedAttempts";
+pub const FORMAT: &str = "Format";
+pub const HEALTH: &str = "Health";
+pub const HEALTH_MESSAGE: &str = "HealthMessage";
 pub const HEARTBEAT: &str = "Heartbeat";
+pub const HOSTNAME: &str = "Hostname";
+pub const IP_ADDRESSES: &str = "IpAddresses";
+pub const IS_TEMPLATE: &str = "IsTemplate";
+pub const LAST_RUN: &str = "LastRun";
+pub const LEADER_TOKEN: &str = "LeaderToken";
 pub const LAST_LOGIN: &str = "LastLogin";
 pub const LOCKED_UNTIL: &str = "LockedUntil";
 pub const MAKE_ME: &str = "MakeMe";
+pub const MISSED_RUN_POLICY: &str = "MissedRunPolicy";
 pub const NAME: &str = "Name";
+pub const OPERATING_SYSTEM: &str = "OperatingSystem";
 pub const PARENT: &str = "Parent";
 pub const PASSWORD: &str = "Password";
+pub const QLIB_VERSION: &str = "QlibVersion";
 pub const RESOURCE_FIELD: &str = "ResourceField";
 pub const RESOURCE_TYPE: &str = "ResourceType";
+pub const RETENTION: &str = "Retention";
+pub const SCHEDULE: &str = "Schedule";
 pub const SCOPE: &str = "Scope";
 pub const SECRET: &str = "Secret";
 pub const START_TIME: &str = "StartTime";
 pub const STATUS: &str = "Status";
 pub const SYNC_STATUS: &str = "SyncStatus";
+pub const TARGET: &str = "Target";
+pub const TARGET_FIELD: &str = "TargetField";
+pub const VERSION: &str = "Version";
 
 #[derive(Clone)]
 pub struct FT {
+    pub action: Option<FieldType>,
     pub active: Option<FieldType>,
     pub auth_method: Option<FieldType>,
     pub available_list: Option<FieldType>,
+    pub build_info: Option<FieldType>,
     pub candidate_list: Option<FieldType>,
     pub children: Option<FieldType>,
+    pub compression: Option<FieldType>,
     pub condition: Option<FieldType>,
     pub current_leader: Option<FieldType>,
     pub death_detection_timeout: Option<FieldType>,
     pub description: Option<FieldType>,
+    pub directory: Option<FieldType>,
     pub fail_over: Option<FieldType>,
     pub fail_over_grace_period: Option<FieldType>,
     pub failed_attempts: Option<FieldType>,
+    pub format: Option<FieldType>,
+    pub health: Option<FieldType>,
+    pub health_message: Option<FieldType>,
     pub heartbeat: Option<FieldType>,
+    pub hostname: Option<FieldType>,
+    pub ip_addresses: Option<FieldType>,
+    pub is_template: Option<FieldType>,
+    pub last_run: Option<FieldType>,
+    pub leader_token: Option<FieldType>,
     pub last_login: Option<FieldType>,
     pub locked_until: Option<FieldType>,
     pub make_me: Option<FieldType>,
+    pub missed_run_policy: Option<FieldType>,
     pub name: Option<FieldType>,
+    pub operating_system: Option<FieldType>,
     pub parent: Option<FieldType>,
     pub password: Option<FieldType>,
+    pub qlib_version: Option<FieldType>,
     pub resource_field: Option<FieldType>,
     pub resource_type: Option<FieldType>,
+    pub retention: Option<FieldType>,
+    pub schedule: Option<FieldType>,
     pub scope: Option<FieldType>,
     pub secret: Option<FieldType>,
     pub start_time: Option<FieldType>,
     pub status: Option<FieldType>,
     pub sync_status: Option<FieldType>,
+    pub target: Option<FieldType>,
+    pub target_field: Option<FieldType>,
+    pub version: Option<FieldType>,
 }
 
 impl FT {
     pub fn new(store: &impl StoreTrait) -> Self {
+        const NAMES: [&str; 46] = [
+            ACTION,
+            ACTIVE,
+            AUTH_METHOD,
+            AVAILABLE_LIST,
+            BUILD_INFO,
+            CANDIDATE_LIST,
+            CHILDREN,
+            COMPRESSION,
+            CONDITION,
+            CURRENT_LEADER,
+            DEATH_DETECTION_TIMEOUT,
+            DESCRIPTION,
+            DIRECTORY,
+            FAIL_OVER,
+            FAIL_OVER_GRACE_PERIOD,
+            FAILED_ATTEMPTS,
+            FORMAT,
+            HEALTH,
+            HEALTH_MESSAGE,
+            HEARTBEAT,
+            HOSTNAME,
+            IP_ADDRESSES,
+            IS_TEMPLATE,
+            LAST_RUN,
+            LEADER_TOKEN,
+            LAST_LOGIN,
+            LOCKED_UNTIL,
+            MAKE_ME,
+            MISSED_RUN_POLICY,
+            NAME,
+            OPERATING_SYSTEM,
+            PARENT,
+            PASSWORD,
+            QLIB_VERSION,
+            RESOURCE_FIELD,
+            RESOURCE_TYPE,
+            RETENTION,
+            SCHEDULE,
+            SCOPE,
+            SECRET,
+            START_TIME,
+            STATUS,
+            SYNC_STATUS,
+            TARGET,
+            TARGET_FIELD,
+            VERSION,
+        ];
+
+        // One round trip; older servers without GET_TYPES_BULK get one lookup per name
+        let ids = store.get_field_types(&NAMES)
+            .unwrap_or_else(|_| NAMES.iter().map(|name| store.get_field_type(name).ok()).collect());
+        let mut ids = ids.into_iter();
+
         FT {
-            active: store.get_field_type(ACTIVE).ok(),
-            auth_method: store.get_field_type(AUTH_METHOD).ok(),
-            available_list: store.get_field_type(AVAILABLE_LIST).ok(),
-            candidate_list: store.get_field_type(CANDIDATE_LIST).ok(),
-            children: store.get_field_type(CHILDREN).ok(),
-            condition: store.get_field_type(CONDITION).ok(),
-            current_leader: store.get_field_type(CURRENT_LEADER).ok(),
-            death_detection_timeout: store.get_field_type(DEATH_DETECTION_TIMEOUT).ok(),
-            description: store.get_field_type(DESCRIPTION).ok(),
-            fail_over: store.get_field_type(FAIL_OVER).ok(),
-            fail_over_grace_period: store.get_field_type(FAIL_OVER_GRACE_PERIOD).ok(),
-            failed_attempts: store.get_field_type(FAILED_ATTEMPTS).ok(),
-            heartbeat: store.get_field_type(HEARTBEAT).ok(),
-            last_login: store.get_field_type(LAST_LOGIN).ok(),
-            locked_until: store.get_field_type(LOCKED_UNTIL).ok(),
-            make_me: store.get_field_type(MAKE_ME).ok(),
-            name: store.get_field_type(NAME).ok(),
-            parent: store.get_field_type(PARENT).ok(),
-            password: store.get_field_type(PASSWORD).ok(),
-            resource_field: store.get_field_type(RESOURCE_FIELD).ok(),
-            resource_type: store.get_field_type(RESOURCE_TYPE).ok(),
-            scope: store.get_field_type(SCOPE).ok(),
-            secret: store.get_field_type(SECRET).ok(),
-            start_time: store.get_field_type(START_TIME).ok(),
-            status: store.get_field_type(STATUS).ok(),
-            sync_status: store.get_field_type(SYNC_STATUS).ok(),
+            action: ids.next().flatten(),
+            active: ids.next().flatten(),
+            auth_method: ids.next().flatten(),
+            available_list: ids.next().flatten(),
+            build_info: ids.next().flatten(),
+            candidate_list: ids.next().flatten(),
+            children: ids.next().flatten(),
+            compression: ids.next().flatten(),
+            condition: ids.next().flatten(),
+            current_leader: ids.next().flatten(),
+            death_detection_timeout: ids.next().flatten(),
+            description: ids.next().flatten(),
+            directory: ids.next().flatten(),
+            fail_over: ids.next().flatten(),
+            fail_over_grace_period: ids.next().flatten(),
+            failed_attempts: ids.next().flatten(),
+            format: ids.next().flatten(),
+            health: ids.next().flatten(),
+            health_message: ids.next().flatten(),
+            heartbeat: ids.next().flatten(),
+            hostname: ids.next().flatten(),
+            ip_addresses: ids.next().flatten(),
+            is_template: ids.next().flatten(),
+            last_run: ids.next().flatten(),
+            leader_token: ids.next().flatten(),
+            last_login: ids.next().flatten(),
+            locked_until: ids.next().flatten(),
+            make_me: ids.next().flatten(),
+            missed_run_policy: ids.next().flatten(),
+            name: ids.next().flatten(),
+            operating_system: ids.next().flatten(),
+            parent: ids.next().flatten(),
+            password: ids.next().flatten(),
+            qlib_version: ids.next().flatten(),
+            resource_field: ids.next().flatten(),
+            resource_type: ids.next().flatten(),
+            retention: ids.next().flatten(),
+            schedule: ids.next().flatten(),
+            scope: ids.next().flatten(),
+            secret: ids.next().flatten(),
+            start_time: ids.next().flatten(),
+            status: ids.next().flatten(),
+            sync_status: ids.next().flatten(),
+            target: ids.next().flatten(),
+            target_field: ids.next().flatten(),
+            version: ids.next().flatten(),
         }
     }
 }
\ No newline at end of file
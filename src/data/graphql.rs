@@ -0,0 +1,744 @@
+//! A read-only GraphQL API over the entity tree.
+//!
+//! Every entity type is an object type implementing the `Entity` interface,
+//! with its fields (inherited ones included). EntityReference fields are
+//! relations to `Entity`, EntityList fields to `[Entity!]!`; select the
+//! fields of a particular type with an inline fragment (`... on Pump`).
+//! The `Query` type has a list field per entity type, whose arguments are
+//! turned into a CEL filter, and `entity(id:)`.
+//!
+//! Only queries are executed. Named fragments, directives, subscriptions,
+//! mutations and introspection are not supported; `GraphQlExecutor::schema`
+//! returns the schema in SDL instead.
+
+use std::collections::HashMap;
+
+use serde_json::{Map as JsonMap, Value as JsonValue};
+
+use crate::{value_to_json_value, CelExecutor, EntityId, EntityType, Error, FieldSchema, Result, StoreTrait};
+
+/// A literal or variable in a query
+#[derive(Debug, Clone, PartialEq)]
+enum InputValue {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+    Enum(String),
+    List(Vec<InputValue>),
+    Variable(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Field {
+    alias: Option<String>,
+    name: String,
+    arguments: Vec<(String, InputValue)>,
+    selections: Vec<Selection>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Selection {
+    Field(Field),
+    InlineFragment {
+        type_condition: Option<String>,
+        selections: Vec<Selection>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Operation {
+    /// Declared variables and their defaults
+    variables: Vec<(String, Option<InputValue>)>,
+    selections: Vec<Selection>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Punct(char),
+    Spread,
+    Name(String),
+    Int(i64),
+    Float(f64),
+    String(String),
+}
+
+fn syntax_error(message: impl std::fmt::Display) -> Error {
+    Error::InvalidRequest(format!("GraphQL syntax error: {}", message))
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            _ if c.is_whitespace() || c == ',' || c == '\u{feff}' => i += 1,
+            '#' => {
+                while i < chars.len() && chars[i] != '\n' {
+                    i += 1;
+                }
+            }
+            '{' | '}' | '(' | ')' | '[' | ']' | ':' | '!' | '$' | '=' | '@' => {
+                tokens.push(Token::Punct(c));
+                i += 1;
+            }
+            '.' if chars.get(i..i + 3) == Some(&['.', '.', '.']) => {
+                tokens.push(Token::Spread);
+                i += 3;
+            }
+            '"' => {
+                let mut text = String::new();
+                i += 1;
+                loop {
+                    match chars.get(i) {
+                        None | Some('\n') => return Err(syntax_error("unterminated string")),
+                        Some('"') => break,
+                        Some('\\') => {
+                            let escaped = match chars.get(i + 1) {
+                                Some('n') => '\n',
+                                Some('t') => '\t',
+                                Some('r') => '\r',
+                                Some('b') => '\u{8}',
+                                Some('f') => '\u{c}',
+                                Some('u') => {
+                                    let code: String = chars.get(i + 2..i + 6).ok_or_else(|| syntax_error("bad unicode escape"))?.iter().collect();
+                                    i += 4;
+                                    u32::from_str_radix(&code, 16)
+                                        .ok()
+                                        .and_then(char::from_u32)
+                                        .ok_or_else(|| syntax_error("bad unicode escape"))?
+                                }
+                                Some(other) => *other,
+                                None => return Err(syntax_error("unterminated string")),
+                            };
+                            text.push(escaped);
+                            i += 2;
+                        }
+                        Some(other) => {
+                            text.push(*other);
+                            i += 1;
+                        }
+                    }
+                }
+                tokens.push(Token::String(text));
+                i += 1;
+            }
+            _ if c == '-' || c.is_ascii_digit() => {
+                let start = i;
+                i += 1;
+                while i < chars.len() && (chars[i].is_ascii_alphanumeric() || matches!(chars[i], '.' | '+' | '-')) {
+                    i += 1;
+                }
+                let text: String = chars[start..i].iter().collect();
+                let token = match text.contains(['.', 'e', 'E']) {
+                    true => text.parse().ok().map(Token::Float),
+                    false => text.parse().ok().map(Token::Int),
+                };
+                tokens.push(token.ok_or_else(|| syntax_error(format!("bad number '{}'", text)))?);
+            }
+            _ if c == '_' || c.is_ascii_alphabetic() => {
+                let start = i;
+                while i < chars.len() && (chars[i] == '_' || chars[i].is_ascii_alphanumeric()) {
+                    i += 1;
+                }
+                tokens.push(Token::Name(chars[start..i].iter().collect()));
+            }
+            _ => return Err(syntax_error(format!("unexpected character '{}'", c))),
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Result<Token> {
+        let token = self.tokens.get(self.pos).cloned().ok_or_else(|| syntax_error("unexpected end of query"))?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(&Token::Punct(c)) {
+            self.pos += 1;
+            return true;
+        }
+        false
+    }
+
+    fn expect(&mut self, c: char) -> Result<()> {
+        match self.next()? {
+            Token::Punct(p) if p == c => Ok(()),
+            other => Err(syntax_error(format!("expected '{}', found {:?}", c, other))),
+        }
+    }
+
+    fn name(&mut self) -> Result<String> {
+        match self.next()? {
+            Token::Name(name) => Ok(name),
+            other => Err(syntax_error(format!("expected a name, found {:?}", other))),
+        }
+    }
+
+    fn document(&mut self) -> Result<Operation> {
+        let operation = match self.peek() {
+            Some(Token::Punct('{')) => Operation {
+                variables: Vec::new(),
+                selections: self.selection_set()?,
+            },
+            Some(Token::Name(keyword)) if keyword == "query" => {
+                self.pos += 1;
+                if matches!(self.peek(), Some(Token::Name(_))) {
+                    self.pos += 1;
+                }
+                let variables = self.variable_definitions()?;
+                Operation {
+                    variables,
+                    selections: self.selection_set()?,
+                }
+            }
+            Some(Token::Name(keyword)) => return Err(Error::InvalidRequest(format!("GraphQL '{}' operations are not supported", keyword))),
+            other => return Err(syntax_error(format!("expected an operation, found {:?}", other))),
+        };
+        if self.peek().is_some() {
+            return Err(Error::InvalidRequest("Only one GraphQL operation per request is supported".to_string()));
+        }
+        Ok(operation)
+    }
+
+    fn variable_definitions(&mut self) -> Result<Vec<(String, Option<InputValue>)>> {
+        let mut variables = Vec::new();
+        if !self.eat('(') {
+            return Ok(variables);
+        }
+        while !self.eat(')') {
+            self.expect('$')?;
+            let name = self.name()?;
+            self.expect(':')?;
+            self.type_reference()?;
+            let default = match self.eat('=') {
+                true => Some(self.value()?),
+                false => None,
+            };
+            variables.push((name, default));
+        }
+        Ok(variables)
+    }
+
+    /// Skip a type such as `[String!]!`; variables are checked where used
+    fn type_reference(&mut self) -> Result<()> {
+        if self.eat('[') {
+            self.type_reference()?;
+            self.expect(']')?;
+        } else {
+            self.name()?;
+        }
+        self.eat('!');
+        Ok(())
+    }
+
+    fn selection_set(&mut self) -> Result<Vec<Selection>> {
+        self.expect('{')?;
+        let mut selections = Vec::new();
+        while !self.eat('}') {
+            if self.peek() == Some(&Token::Spread) {
+                self.pos += 1;
+                let type_condition = match self.peek() {
+                    Some(Token::Name(on)) if on == "on" => {
+                        self.pos += 1;
+                        Some(self.name()?)
+                    }
+                    Some(Token::Name(_)) => return Err(Error::InvalidRequest("GraphQL named fragments are not supported".to_string())),
+                    _ => None,
+                };
+                selections.push(Selection::InlineFragment {
+                    type_condition,
+                    selections: self.selection_set()?,
+                });
+                continue;
+            }
+
+            let mut name = self.name()?;
+            let mut alias = None;
+            if self.eat(':') {
+                alias = Some(name);
+                name = self.name()?;
+            }
+            let mut arguments = Vec::new();
+            if self.eat('(') {
+                while !self.eat(')') {
+                    let argument = self.name()?;
+                    self.expect(':')?;
+                    arguments.push((argument, self.value()?));
+                }
+            }
+            if self.peek() == Some(&Token::Punct('@')) {
+                return Err(Error::InvalidRequest("GraphQL directives are not supported".to_string()));
+            }
+            let selections_of_field = match self.peek() == Some(&Token::Punct('{')) {
+                true => self.selection_set()?,
+                false => Vec::new(),
+            };
+            selections.push(Selection::Field(Field {
+                alias,
+                name,
+                arguments,
+                selections: selections_of_field,
+            }));
+        }
+        Ok(selections)
+    }
+
+    fn value(&mut self) -> Result<InputValue> {
+        Ok(match self.next()? {
+            Token::Punct('$') => InputValue::Variable(self.name()?),
+            Token::Punct('[') => {
+                let mut items = Vec::new();
+                while !self.eat(']') {
+                    items.push(self.value()?);
+                }
+                InputValue::List(items)
+            }
+            Token::Int(value) => InputValue::Int(value),
+            Token::Float(value) => InputValue::Float(value),
+            Token::String(value) => InputValue::String(value),
+            Token::Name(name) => match name.as_str() {
+                "true" => InputValue::Bool(true),
+                "false" => InputValue::Bool(false),
+                "null" => InputValue::Null,
+                _ => InputValue::Enum(name),
+            },
+            other => return Err(syntax_error(format!("expected a value, found {:?}", other))),
+        })
+    }
+}
+
+fn parse(query: &str) -> Result<Operation> {
+    Parser {
+        tokens: tokenize(query)?,
+        pos: 0,
+    }
+    .document()
+}
+
+fn json_to_input(value: &JsonValue) -> InputValue {
+    match value {
+        JsonValue::Null => InputValue::Null,
+        JsonValue::Bool(value) => InputValue::Bool(*value),
+        JsonValue::Number(number) => number.as_i64().map(InputValue::Int).unwrap_or_else(|| InputValue::Float(number.as_f64().unwrap_or_default())),
+        JsonValue::String(value) => InputValue::String(value.clone()),
+        JsonValue::Array(items) => InputValue::List(items.iter().map(json_to_input).collect()),
+        // Only ever compared against scalar fields
+        JsonValue::Object(_) => InputValue::String(value.to_string()),
+    }
+}
+
+/// True for names GraphQL accepts as type and field names
+fn is_graphql_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c == '_' || c.is_ascii_alphabetic())
+        && chars.all(|c| c == '_' || c.is_ascii_alphanumeric())
+        && !name.starts_with("__")
+}
+
+/// The GraphQL type of a field's value; None for relations
+fn scalar_type(field_schema: &FieldSchema) -> Option<&'static str> {
+    Some(match field_schema {
+        FieldSchema::Bool { .. } => "Boolean",
+        FieldSchema::Int { .. } => "Int",
+        FieldSchema::Float { .. } => "Float",
+        FieldSchema::String { .. } | FieldSchema::Choice { .. } => "String",
+        // Unix seconds, as in JSON snapshots
+        FieldSchema::Timestamp { .. } => "Int",
+        FieldSchema::StringList { .. } => "[String!]",
+        FieldSchema::EntityReference { .. } | FieldSchema::EntityList { .. } => return None,
+        _ => "JSON",
+    })
+}
+
+/// Whether a field can be filtered on with an equality argument
+fn is_filterable(field_schema: &FieldSchema) -> bool {
+    matches!(
+        field_schema,
+        FieldSchema::Bool { .. } | FieldSchema::Int { .. } | FieldSchema::Float { .. } | FieldSchema::String { .. } | FieldSchema::Choice { .. }
+    )
+}
+
+/// Fields of an entity type (inherited ones included) with GraphQL names, by rank
+fn graphql_fields(store: &impl StoreTrait, entity_type: EntityType) -> Result<Vec<(String, FieldSchema)>> {
+    let mut fields: Vec<(String, FieldSchema)> = store
+        .get_complete_entity_schema_owned(entity_type)?
+        .fields
+        .values()
+        .filter_map(|field_schema| {
+            let name = store.resolve_field_type(field_schema.field_type()).ok()?;
+            is_graphql_name(&name).then(|| (name, field_schema.clone()))
+        })
+        .collect();
+    fields.sort_by_key(|(name, field_schema)| (field_schema.rank(), name.clone()));
+    Ok(fields)
+}
+
+/// Entity types with GraphQL names, by name
+fn graphql_types(store: &impl StoreTrait) -> Result<Vec<(String, EntityType)>> {
+    let mut types: Vec<(String, EntityType)> = store
+        .get_entity_types()?
+        .into_iter()
+        .filter_map(|entity_type| store.resolve_entity_type(entity_type).ok().map(|name| (name, entity_type)))
+        .filter(|(name, _)| is_graphql_name(name) && name != "Query" && name != "Entity")
+        .collect();
+    types.sort_by(|(a, _), (b, _)| a.cmp(b));
+    Ok(types)
+}
+
+/// Whether `entity_type` is `name` or inherits from it
+fn is_a(store: &impl StoreTrait, entity_type: EntityType, name: &str) -> bool {
+    let mut pending = vec![entity_type];
+    while let Some(entity_type) = pending.pop() {
+        if store.resolve_entity_type(entity_type).is_ok_and(|type_name| type_name == name) {
+            return true;
+        }
+        if let Ok(schema) = store.get_entity_schema(entity_type) {
+            pending.extend(schema.inherit);
+        }
+    }
+    false
+}
+
+/// A CEL literal for comparing with a field
+fn cel_literal(store: &impl StoreTrait, entity_type: EntityType, field_schema: &FieldSchema, value: &InputValue) -> Result<String> {
+    let mismatch = || Error::InvalidRequest(format!("Can't compare {:?} with {:?}", field_schema.field_type(), value));
+    Ok(match (field_schema, value) {
+        (FieldSchema::Bool { .. }, InputValue::Bool(value)) => value.to_string(),
+        (FieldSchema::Int { .. } | FieldSchema::Choice { .. }, InputValue::Int(value)) => value.to_string(),
+        (FieldSchema::Float { .. }, InputValue::Int(value)) => format!("{:?}", *value as f64),
+        (FieldSchema::Float { .. }, InputValue::Float(value)) => format!("{:?}", value),
+        (FieldSchema::String { .. }, InputValue::String(value) | InputValue::Enum(value)) => {
+            format!("'{}'", value.replace('\\', "\\\\").replace('\'', "\\'"))
+        }
+        (FieldSchema::Choice { .. }, InputValue::String(name) | InputValue::Enum(name)) => store
+            .get_choices(entity_type, field_schema.field_type())?
+            .iter()
+            .position(|choice| choice == name)
+            .ok_or_else(|| Error::InvalidRequest(format!("'{}' is not a choice of {:?}", name, field_schema.field_type())))?
+            .to_string(),
+        _ => return Err(mismatch()),
+    })
+}
+
+/// Runs GraphQL queries against a store.
+///
+/// ```rust,ignore
+/// let mut graphql = GraphQlExecutor::new();
+/// let response = graphql.execute(&store, r#"{
+///     Site(Name: "Plant") {
+///         Name
+///         Children(filter: "Power > 20", first: 10) { id ... on Pump { Power } }
+///     }
+/// }"#, &Default::default());
+/// ```
+///
+/// The response is a GraphQL response object: `data`, and `errors` if any.
+/// A field that fails is null in `data` and listed in `errors` with its path,
+/// so one bad field doesn't fail the whole query.
+pub struct GraphQlExecutor {
+    executor: CelExecutor,
+}
+
+impl Default for GraphQlExecutor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// State of one execution
+struct Execution<'a> {
+    variables: HashMap<String, InputValue>,
+    path: Vec<JsonValue>,
+    errors: Vec<JsonValue>,
+    executor: &'a mut CelExecutor,
+}
+
+impl GraphQlExecutor {
+    pub fn new() -> Self {
+        Self {
+            executor: CelExecutor::new(),
+        }
+    }
+
+    /// The schema of `store` in GraphQL SDL
+    pub fn schema(&self, store: &impl StoreTrait) -> Result<String> {
+        let mut sdl = String::from("scalar JSON\n\ninterface Entity {\n  id: ID!\n}\n");
+        let mut query = String::from("\ntype Query {\n  entity(id: ID!): Entity\n");
+
+        for (type_name, entity_type) in graphql_types(store)? {
+            let fields = graphql_fields(store, entity_type)?;
+            sdl.push_str(&format!("\ntype {} implements Entity {{\n  id: ID!\n", type_name));
+            for (name, field_schema) in &fields {
+                let field_type = match field_schema {
+                    FieldSchema::EntityReference { .. } => "Entity".to_string(),
+                    FieldSchema::EntityList { .. } => {
+                        sdl.push_str(&format!("  {}(filter: String, first: Int): [Entity!]!\n", name));
+                        continue;
+                    }
+                    _ => scalar_type(field_schema).unwrap_or("JSON").to_string(),
+                };
+                sdl.push_str(&format!("  {}: {}\n", name, field_type));
+            }
+            sdl.push_str("}\n");
+
+            let arguments: String = fields
+                .iter()
+                .filter(|(_, field_schema)| is_filterable(field_schema))
+                .map(|(name, field_schema)| format!(", {}: {}", name, scalar_type(field_schema).unwrap_or("JSON")))
+                .collect();
+            query.push_str(&format!("  {}(filter: String, first: Int{}): [{}!]!\n", type_name, arguments, type_name));
+        }
+
+        sdl.push_str(&query);
+        sdl.push_str("}\n");
+        Ok(sdl)
+    }
+
+    /// Run `query` with `variables`, returning the GraphQL response
+    pub fn execute(&mut self, store: &impl StoreTrait, query: &str, variables: &JsonMap<String, JsonValue>) -> JsonValue {
+        let operation = match parse(query) {
+            Ok(operation) => operation,
+            Err(e) => return serde_json::json!({ "errors": [{ "message": e.to_string() }] }),
+        };
+
+        let mut declared = HashMap::new();
+        for (name, default) in operation.variables {
+            let value = variables.get(&name).map(json_to_input).or(default).unwrap_or(InputValue::Null);
+            declared.insert(name, value);
+        }
+
+        let mut execution = Execution {
+            variables: declared,
+            path: Vec::new(),
+            errors: Vec::new(),
+            executor: &mut self.executor,
+        };
+        let mut data = JsonMap::new();
+        execution.select_root(store, &operation.selections, &mut data);
+
+        let mut response = JsonMap::new();
+        response.insert("data".to_string(), JsonValue::Object(data));
+        if !execution.errors.is_empty() {
+            response.insert("errors".to_string(), JsonValue::Array(execution.errors));
+        }
+        JsonValue::Object(response)
+    }
+}
+
+impl Execution<'_> {
+    fn resolve(&self, value: &InputValue) -> Result<InputValue> {
+        match value {
+            InputValue::Variable(name) => self
+                .variables
+                .get(name)
+                .cloned()
+                .ok_or_else(|| Error::InvalidRequest(format!("Variable '${}' is not defined", name))),
+            InputValue::List(items) => items.iter().map(|item| self.resolve(item)).collect::<Result<_>>().map(InputValue::List),
+            other => Ok(other.clone()),
+        }
+    }
+
+    fn argument(&self, field: &Field, name: &str) -> Result<Option<InputValue>> {
+        field
+            .arguments
+            .iter()
+            .find(|(argument, _)| argument == name)
+            .map(|(_, value)| self.resolve(value))
+            .transpose()
+            .map(|value| value.filter(|value| *value != InputValue::Null))
+    }
+
+    fn first(&self, field: &Field) -> Result<Option<usize>> {
+        match self.argument(field, "first")? {
+            None => Ok(None),
+            Some(InputValue::Int(first)) if first >= 0 => Ok(Some(first as usize)),
+            Some(other) => Err(Error::InvalidRequest(format!("'first' must be a non-negative Int, not {:?}", other))),
+        }
+    }
+
+    fn filter(&self, field: &Field) -> Result<Option<String>> {
+        match self.argument(field, "filter")? {
+            None => Ok(None),
+            Some(InputValue::String(filter)) => Ok(Some(filter)),
+            Some(other) => Err(Error::InvalidRequest(format!("'filter' must be a String, not {:?}", other))),
+        }
+    }
+
+    /// Run `resolve` for the field, recording an error and yielding null if
+    /// it fails
+    fn field<F>(&mut self, field: &Field, output: &mut JsonMap<String, JsonValue>, resolve: F)
+    where
+        F: FnOnce(&mut Self) -> Result<JsonValue>,
+    {
+        let key = field.alias.clone().unwrap_or_else(|| field.name.clone());
+        self.path.push(JsonValue::String(key.clone()));
+        let value = resolve(self).unwrap_or_else(|e| {
+            self.errors.push(serde_json::json!({ "message": e.to_string(), "path": self.path.clone() }));
+            JsonValue::Null
+        });
+        self.path.pop();
+        output.insert(key, value);
+    }
+
+    fn select_root(&mut self, store: &impl StoreTrait, selections: &[Selection], output: &mut JsonMap<String, JsonValue>) {
+        for selection in selections {
+            match selection {
+                Selection::Field(field) => self.field(field, output, |execution| execution.resolve_root(store, field)),
+                Selection::InlineFragment { type_condition, selections } => {
+                    if type_condition.as_deref().is_none_or(|type_name| type_name == "Query") {
+                        self.select_root(store, selections, output);
+                    }
+                }
+            }
+        }
+    }
+
+    fn resolve_root(&mut self, store: &impl StoreTrait, field: &Field) -> Result<JsonValue> {
+        match field.name.as_str() {
+            "__typename" => Ok(JsonValue::String("Query".to_string())),
+            "entity" => {
+                let entity_id = match self.argument(field, "id")? {
+                    Some(InputValue::String(id)) => id.parse().ok().map(EntityId),
+                    Some(InputValue::Int(id)) => u64::try_from(id).ok().map(EntityId),
+                    _ => return Err(Error::InvalidRequest("'entity' needs an 'id'".to_string())),
+                };
+                match entity_id.filter(|entity_id| store.entity_exists(*entity_id)) {
+                    Some(entity_id) => self.resolve_entity(store, entity_id, &field.selections),
+                    None => Ok(JsonValue::Null),
+                }
+            }
+            type_name => {
+                let entity_type = store
+                    .get_entity_type(type_name)
+                    .ok()
+                    .filter(|_| is_graphql_name(type_name))
+                    .ok_or_else(|| Error::InvalidRequest(format!("Cannot query field '{}' on type 'Query'", type_name)))?;
+
+                let mut conditions: Vec<String> = self.filter(field)?.into_iter().map(|filter| format!("({})", filter)).collect();
+                let fields = graphql_fields(store, entity_type)?;
+                for (argument, value) in &field.arguments {
+                    if argument == "filter" || argument == "first" {
+                        continue;
+                    }
+                    let field_schema = fields
+                        .iter()
+                        .find(|(name, field_schema)| name == argument && is_filterable(field_schema))
+                        .map(|(_, field_schema)| field_schema)
+                        .ok_or_else(|| Error::InvalidRequest(format!("Unknown argument '{}' on field 'Query.{}'", argument, type_name)))?;
+                    let literal = cel_literal(store, entity_type, field_schema, &self.resolve(value)?)?;
+                    conditions.push(format!("{} == {}", argument, literal));
+                }
+                let filter = (!conditions.is_empty()).then(|| conditions.join(" && "));
+
+                let mut entities = store.find_entities(entity_type, filter.as_deref())?;
+                if let Some(first) = self.first(field)? {
+                    entities.truncate(first);
+                }
+                self.resolve_list(store, &entities, &field.selections)
+            }
+        }
+    }
+
+    fn resolve_list(&mut self, store: &impl StoreTrait, entities: &[EntityId], selections: &[Selection]) -> Result<JsonValue> {
+        let mut items = Vec::with_capacity(entities.len());
+        for (index, entity_id) in entities.iter().enumerate() {
+            self.path.push(JsonValue::from(index));
+            let item = self.resolve_entity(store, *entity_id, selections);
+            self.path.pop();
+            items.push(item?);
+        }
+        Ok(JsonValue::Array(items))
+    }
+
+    fn resolve_entity(&mut self, store: &impl StoreTrait, entity_id: EntityId, selections: &[Selection]) -> Result<JsonValue> {
+        if selections.is_empty() {
+            return Err(Error::InvalidRequest("Entity fields need a selection of subfields".to_string()));
+        }
+        let mut output = JsonMap::new();
+        self.select_entity(store, entity_id, selections, &mut output)?;
+        Ok(JsonValue::Object(output))
+    }
+
+    fn select_entity(&mut self, store: &impl StoreTrait, entity_id: EntityId, selections: &[Selection], output: &mut JsonMap<String, JsonValue>) -> Result<()> {
+        let entity_type = entity_id.extract_type();
+        for selection in selections {
+            match selection {
+                Selection::Field(field) => self.field(field, output, |execution| execution.resolve_field(store, entity_id, field)),
+                Selection::InlineFragment { type_condition, selections } => {
+                    let applies = type_condition
+                        .as_deref()
+                        .is_none_or(|type_name| type_name == "Entity" || is_a(store, entity_type, type_name));
+                    if applies {
+                        self.select_entity(store, entity_id, selections, output)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn resolve_field(&mut self, store: &impl StoreTrait, entity_id: EntityId, field: &Field) -> Result<JsonValue> {
+        let entity_type = entity_id.extract_type();
+        match field.name.as_str() {
+            "id" => return Ok(JsonValue::String(String::from(entity_id))),
+            "__typename" => return Ok(JsonValue::String(store.resolve_entity_type(entity_type)?)),
+            _ => {}
+        }
+
+        let type_name = store.resolve_entity_type(entity_type)?;
+        let unknown = || Error::InvalidRequest(format!("Cannot query field '{}' on type '{}'", field.name, type_name));
+        let field_type = store.get_field_type(&field.name).map_err(|_| unknown())?;
+        let field_schema = store
+            .get_complete_entity_schema_owned(entity_type)?
+            .fields
+            .get(&field_type)
+            .cloned()
+            .ok_or_else(unknown)?;
+        let (value, _, _) = store.read(entity_id, &[field_type])?;
+
+        match field_schema {
+            FieldSchema::EntityReference { .. } => match value.as_entity_reference().copied().flatten() {
+                Some(target) if store.entity_exists(target) => self.resolve_entity(store, target, &field.selections),
+                _ => Ok(JsonValue::Null),
+            },
+            FieldSchema::EntityList { .. } => {
+                let mut entities = value.as_entity_list().cloned().unwrap_or_default();
+                entities.retain(|target| store.entity_exists(*target));
+                if let Some(filter) = self.filter(field)? {
+                    // As in find_entities, an entity the filter fails on doesn't match
+                    entities.retain(|target| matches!(self.executor.execute(&filter, *target, store), Ok(cel::Value::Bool(true))));
+                }
+                if let Some(first) = self.first(field)? {
+                    entities.truncate(first);
+                }
+                self.resolve_list(store, &entities, &field.selections)
+            }
+            _ => {
+                if !field.selections.is_empty() {
+                    return Err(Error::InvalidRequest(format!("Field '{}' of type '{}' has no subfields", field.name, type_name)));
+                }
+                let choices = match field_schema {
+                    FieldSchema::Choice { .. } => Some(store.get_choices(entity_type, field_type)?),
+                    _ => None,
+                };
+                Ok(value_to_json_value(&value, choices.as_ref()))
+            }
+        }
+    }
+}
@@ -0,0 +1,92 @@
+use std::time::{Duration, Instant};
+
+use crate::{Result, Store, WriteInfo};
+
+/// Name a `GroupCommit` reads the store's write queue under
+pub const GROUP_COMMIT_WRITE_READER: &str = "group-commit";
+
+/// Group commit for a command dispatcher: the writes of the commands that
+/// arrive within `window` of the first uncommitted one are persisted with
+/// one WAL append, and their notifications delivered together afterwards.
+/// This trades up to `window` of latency for far fewer appends and wakeups
+/// under a steady stream of small writes.
+///
+/// ```rust,ignore
+/// let mut group = GroupCommit::new(Duration::from_micros(500));
+/// group.attach(&mut store);
+/// loop {
+///     let timeout = group.time_left(Instant::now());
+///     if let Some(command) = next_command(timeout) { /* run it */ }
+///     if group.is_due(&store, Instant::now()) {
+///         group.commit(&mut store, |writes| wal.append_all(writes))?;
+///     }
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct GroupCommit {
+    window: Duration,
+    max_writes: usize,
+    opened_at: Option<Instant>,
+}
+
+impl GroupCommit {
+    /// Commit once the oldest uncommitted write is `window` old. A zero
+    /// window commits after every command.
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            max_writes: usize::MAX,
+            opened_at: None,
+        }
+    }
+
+    /// Also commit as soon as `max_writes` writes are waiting
+    pub fn with_max_writes(mut self, max_writes: usize) -> Self {
+        self.max_writes = max_writes.max(1);
+        self
+    }
+
+    pub fn window(&self) -> Duration {
+        self.window
+    }
+
+    /// Hold the store's notifications until each commit, and start
+    /// reading its write queue
+    pub fn attach(&self, store: &mut Store) {
+        store.hold_notifications();
+        store.add_write_reader(GROUP_COMMIT_WRITE_READER);
+    }
+
+    /// Check if the open batch should be committed, opening a batch when
+    /// the first write of one is seen. Call after every command.
+    pub fn is_due(&mut self, store: &Store, now: Instant) -> bool {
+        if store.unread_writes(GROUP_COMMIT_WRITE_READER).next().is_none() && store.held_notifications() == 0 {
+            self.opened_at = None;
+            return false;
+        }
+        let opened_at = *self.opened_at.get_or_insert(now);
+        store.unread_writes(GROUP_COMMIT_WRITE_READER).take(self.max_writes).count() >= self.max_writes || now.saturating_duration_since(opened_at) >= self.window
+    }
+
+    /// How long the dispatcher may wait for the next command before the
+    /// open batch is due, or None without one
+    pub fn time_left(&self, now: Instant) -> Option<Duration> {
+        self.opened_at.map(|opened_at| self.window.saturating_sub(now.saturating_duration_since(opened_at)))
+    }
+
+    /// Hand the batch's writes to `persist` (e.g. one WAL append), then
+    /// deliver the notifications they caused. The writes stay queued for the
+    /// store's other readers (such as replication). If `persist` fails they
+    /// stay unread and the notifications stay held, so a later commit
+    /// retries both. Returns the number of writes committed.
+    pub fn commit(&mut self, store: &mut Store, persist: impl FnOnce(&[WriteInfo]) -> Result<()>) -> Result<usize> {
+        let seq = store.write_seq();
+        let writes: Vec<WriteInfo> = store.unread_writes(GROUP_COMMIT_WRITE_READER).cloned().collect();
+        persist(&writes)?;
+        store.mark_writes_read(GROUP_COMMIT_WRITE_READER, seq);
+
+        store.flush_notifications();
+        self.opened_at = None;
+        Ok(writes.len())
+    }
+}
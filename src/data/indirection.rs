@@ -1,10 +1,12 @@
+use serde::{Deserialize, Serialize};
+
 use crate::{
     data::StoreTrait, et, ft, EntityId, FieldType, Result
 };
 
 pub const INDIRECTION_DELIMITER: &str = "->";
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum BadIndirectionReason {
     NegativeIndex(i64),
     ArrayIndexOutOfBounds(usize, usize),
@@ -92,7 +94,7 @@ pub fn path<T: StoreTrait>(store: &T, entity_id: EntityId) -> Result<String> {
 
 /// Resolve a path to an entity ID by traversing down from the root
 /// This works with both Store and StoreProxy since they have the same method signatures
-pub fn path_to_entity_id<T: StoreTrait>(store: &T, path: &str) -> Result<EntityId> {
+pub fn path_to_entity_id<T: StoreTrait + ?Sized>(store: &T, path: &str) -> Result<EntityId> {
     if path.is_empty() {
         return Err(crate::Error::InvalidFieldValue("Empty path".to_string()));
     }
@@ -151,3 +153,27 @@ pub fn path_to_entity_id<T: StoreTrait>(store: &T, path: &str) -> Result<EntityI
     
     Ok(current_id)
 }
+
+/// Resolve a field path such as `Root/Machines/M1/Pump3/Speed` to the entity
+/// it starts from and the field types to follow.
+///
+/// Everything before the last `/` is an entity path (see `path_to_entity_id`)
+/// and the last segment is a field name, which may itself use indirection
+/// (e.g. `Root/Machines/M1/Pump3/Parent->Name`).
+pub fn path_to_field_path<T: StoreTrait + ?Sized>(store: &T, path: &str) -> Result<(EntityId, Vec<FieldType>)> {
+    let (entity_path, field_path) = path
+        .rsplit_once('/')
+        .ok_or_else(|| crate::Error::InvalidFieldValue(format!("Path '{}' has no field segment", path)))?;
+
+    let entity_id = path_to_entity_id(store, entity_path)?;
+    Ok((entity_id, parse_field_path(store, field_path)?))
+}
+
+/// Resolve a field path with indirection such as `Parent->Name` to the
+/// field types to follow
+pub fn parse_field_path<T: StoreTrait + ?Sized>(store: &T, field_path: &str) -> Result<Vec<FieldType>> {
+    field_path
+        .split(INDIRECTION_DELIMITER)
+        .map(|field_name| store.get_field_type(field_name))
+        .collect()
+}
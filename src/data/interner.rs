@@ -1,7 +1,16 @@
-use ahash::AHashMap;
+use ahash::{AHashMap, AHashSet};
 use std::option::Option;
 use serde::{Deserialize, Serialize};
 
+use crate::{Error, Result};
+
+/// Maps type names to dense numeric ids.
+///
+/// Ids are handed out in first-intern order and are append-only: once a name
+/// has an id, that id is never reassigned, reused or renumbered. Clients cache
+/// these ids (see `ET`/`FT`), so the mapping has to survive restarts intact;
+/// it is part of every binary snapshot, and `export()`/`import()` carry it
+/// anywhere else it needs to go.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Interner {
     map: AHashMap<String, u64>,
@@ -38,4 +47,67 @@ impl Interner {
     pub fn resolve(&self, id: u64) -> Option<&String> {
         self.vec.get(id as usize)
     }
-}
\ No newline at end of file
+
+    /// Number of interned names
+    pub fn len(&self) -> usize {
+        self.vec.len()
+    }
+
+    /// Check if nothing has been interned yet
+    pub fn is_empty(&self) -> bool {
+        self.vec.is_empty()
+    }
+
+    /// The full name to id mapping, ordered by id
+    pub fn export(&self) -> Vec<(String, u64)> {
+        self.vec
+            .iter()
+            .enumerate()
+            .map(|(id, name)| (name.clone(), id as u64))
+            .collect()
+    }
+
+    /// Pin names to the ids of a previously exported mapping.
+    ///
+    /// Names that are already interned must keep the id they have, and new
+    /// names must continue the id sequence without gaps, so importing only
+    /// ever extends an interner. The mapping is checked before anything is
+    /// added; on error the interner is unchanged. Returns how many names were
+    /// added.
+    pub fn import(&mut self, mapping: &[(String, u64)]) -> Result<usize> {
+        let mut sorted: Vec<&(String, u64)> = mapping.iter().collect();
+        sorted.sort_by_key(|(_, id)| *id);
+
+        let mut next_id = self.vec.len() as u64;
+        let mut added = Vec::new();
+        let mut seen = AHashSet::new();
+        for (name, id) in sorted {
+            match self.map.get(name.as_str()) {
+                Some(existing) if existing == id => continue,
+                Some(_) => return Err(Error::TypeIdConflict(name.clone(), *id)),
+                None => {}
+            }
+
+            // A duplicate name within the mapping itself also lands here
+            if *id != next_id || !seen.insert(name.as_str()) {
+                return Err(Error::TypeIdConflict(name.clone(), *id));
+            }
+            added.push(name);
+            next_id += 1;
+        }
+
+        let count = added.len();
+        for name in added {
+            self.intern(name);
+        }
+        Ok(count)
+    }
+}
+
+/// Exported entity and field type ids of a store, e.g. to carry them across
+/// a rebuild that doesn't go through a binary snapshot
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TypeIdMapping {
+    pub entity_types: Vec<(String, u64)>,
+    pub field_types: Vec<(String, u64)>,
+}
@@ -1,13 +1,30 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
 use std::io::Write;
 use serde::{Deserialize, Serialize};
 use serde_json::Value as JsonValue;
 
 use crate::{
-    now, EntityId, EntitySchema, EntityType, Error, FieldSchema, FieldType, Result, Single, Store, Value
+    now, Decimal, Duration, EntityId, EntitySchema, EntityType, Error, FieldSchema, FieldType, Result, Single, Store, Value
 };
-use crate::data::{StoreTrait, StorageScope};
+use crate::data::{store_trait::collect_field_schemas, FieldMetadata, StoreTrait, StorageScope, MergePolicy, OnDelete, SampleType, WriteScope};
+
+/// Parse the `mergePolicy` attribute of a JSON field schema
+fn parse_merge_policy(merge_policy: Option<&str>) -> MergePolicy {
+    match merge_policy {
+        Some("Counter") => MergePolicy::Counter,
+        Some("SetUnion") => MergePolicy::SetUnion,
+        _ => MergePolicy::LastWriterWins,
+    }
+}
+
+/// Parse the `storageScope` attribute of a JSON field schema
+fn parse_storage_scope(storage_scope: Option<&str>) -> StorageScope {
+    match storage_scope {
+        Some("Runtime") => StorageScope::Runtime,
+        _ => StorageScope::Configuration,
+    }
+}
 
 /// JSON-friendly representation of a field schema
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -15,13 +32,51 @@ pub struct JsonFieldSchema {
     pub name: String,
     #[serde(rename = "dataType")]
     pub data_type: String,
+    /// Optional when reading, since TOML has no null
+    #[serde(default)]
     pub default: JsonValue,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub choices: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none", rename = "choicesSource")]
+    pub choices_source: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub rank: Option<i64>,
     #[serde(skip_serializing_if = "Option::is_none", rename = "storageScope")]
     pub storage_scope: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none", rename = "mergePolicy")]
+    pub merge_policy: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub validator: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expression: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unit: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub precision: Option<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none", rename = "writeScope")]
+    pub write_scope: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none", rename = "onDelete")]
+    pub on_delete: Option<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub searchable: bool,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not", rename = "uniqueAmongSiblings")]
+    pub unique_among_siblings: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none", rename = "sampleType")]
+    pub sample_type: Option<String>,
+    /// Number of samples a Series field keeps
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub capacity: Option<usize>,
+    /// How long a Series field keeps samples, in seconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retention: Option<JsonValue>,
 }
 
 /// JSON-friendly representation of an entity schema
@@ -29,7 +84,7 @@ pub struct JsonFieldSchema {
 pub struct JsonEntitySchema {
     #[serde(rename = "entityType")]
     pub entity_type: String,
-    #[serde(skip_serializing_if = "Vec::is_empty", rename = "inheritsFrom")]
+    #[serde(default, skip_serializing_if = "Vec::is_empty", rename = "inheritsFrom")]
     pub inherits_from: Vec<String>,
     pub fields: Vec<JsonFieldSchema>,
 }
@@ -48,6 +103,9 @@ pub struct JsonEntity {
 pub struct JsonSnapshot {
     pub schemas: Vec<JsonEntitySchema>,
     pub tree: JsonEntity,
+    /// Content hashes taken along with the snapshot, see `verify_json_snapshot`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checksum: Option<crate::SnapshotChecksum>,
 }
 
 impl JsonFieldSchema {
@@ -88,8 +146,27 @@ impl JsonFieldSchema {
             FieldSchema::Timestamp { default_value, .. } => {
                 ("Timestamp".to_string(), serde_json::to_value(default_value.unix_timestamp()).unwrap_or(JsonValue::Null), None)
             },
+            FieldSchema::Computed { .. } => {
+                ("Computed".to_string(), JsonValue::Null, None)
+            },
+            FieldSchema::Decimal { default_value, .. } => {
+                ("Decimal".to_string(), decimal_to_json(default_value), None)
+            },
+            FieldSchema::Duration { default_value, .. } => {
+                ("Duration".to_string(), duration_to_json(default_value), None)
+            },
+            FieldSchema::StringList { default_value, .. } => {
+                ("StringList".to_string(), string_list_to_json(default_value), None)
+            },
+            FieldSchema::Map { default_value, .. } => {
+                ("Map".to_string(), map_to_json(default_value), None)
+            },
+            FieldSchema::Series { .. } => {
+                ("Series".to_string(), JsonValue::Null, None)
+            },
         };
 
+        let metadata = field_schema.metadata();
         Self {
             name: store.resolve_field_type(field_schema.field_type()).unwrap_or_else(|_| format!("{:?}", field_schema.field_type())),
             data_type,
@@ -100,6 +177,59 @@ impl JsonFieldSchema {
                 StorageScope::Runtime => "Runtime".to_string(),
                 StorageScope::Configuration => "Configuration".to_string(),
             }),
+            merge_policy: match field_schema.merge_policy() {
+                MergePolicy::LastWriterWins => None,
+                MergePolicy::Counter => Some("Counter".to_string()),
+                MergePolicy::SetUnion => Some("SetUnion".to_string()),
+            },
+            validator: field_schema.validator().map(|v| v.to_string()),
+            expression: field_schema.expression().map(|e| e.to_string()),
+            choices_source: field_schema.choices_source().map(|s| s.to_string()),
+            unit: metadata.unit.clone(),
+            min: metadata.min,
+            max: metadata.max,
+            precision: metadata.precision,
+            label: metadata.label.clone(),
+            description: metadata.description.clone(),
+            write_scope: metadata.write_scope.map(|scope| scope.as_str().to_string()),
+            on_delete: metadata.on_delete.map(|on_delete| on_delete.as_str().to_string()),
+            searchable: metadata.searchable,
+            unique_among_siblings: metadata.unique_among_siblings,
+            sample_type: match field_schema {
+                FieldSchema::Series { sample_type, .. } => Some(sample_type.as_str().to_string()),
+                _ => None,
+            },
+            capacity: field_schema.series_limits().map(|(capacity, _)| capacity),
+            retention: field_schema.series_limits().and_then(|(_, retention)| retention).map(|retention| duration_to_json(&retention)),
+        }
+    }
+
+    /// Sample type, capacity and retention of a Series field
+    fn series(&self) -> Result<(SampleType, usize, Option<Duration>)> {
+        let sample_type = match self.sample_type.as_deref() {
+            Some(name) => SampleType::from_name(name)
+                .ok_or_else(|| Error::InvalidFieldType(format!("Unknown sample type '{}' for series field '{}'", name, self.name)))?,
+            None => SampleType::Float,
+        };
+        let capacity = self.capacity
+            .ok_or_else(|| Error::InvalidFieldType(format!("Series field '{}' requires a capacity", self.name)))?;
+        let retention = self.retention.as_ref().map(json_to_duration).transpose()?;
+        Ok((sample_type, capacity, retention))
+    }
+
+    /// The engineering metadata attributes of this field
+    pub fn metadata(&self) -> FieldMetadata {
+        FieldMetadata {
+            unit: self.unit.clone(),
+            min: self.min,
+            max: self.max,
+            precision: self.precision,
+            label: self.label.clone(),
+            description: self.description.clone(),
+            write_scope: self.write_scope.as_deref().and_then(WriteScope::from_name),
+            on_delete: self.on_delete.as_deref().and_then(OnDelete::from_name),
+            searchable: self.searchable,
+            unique_among_siblings: self.unique_among_siblings,
         }
     }
 
@@ -111,16 +241,19 @@ impl JsonFieldSchema {
             Some("Configuration") => StorageScope::Configuration,
             _ => StorageScope::Runtime, // Default to Runtime if not specified or invalid
         };
+        let merge_policy = parse_merge_policy(self.merge_policy.as_deref());
+        let validator = self.validator.clone();
+        let metadata = self.metadata();
 
         match self.data_type.as_str() {
             "Blob" => {
                 let default_value: Vec<u8> = serde_json::from_value(self.default.clone())
                     .unwrap_or_default();
-                Ok(FieldSchema::Blob { field_type, default_value, rank, storage_scope })
+                Ok(FieldSchema::Blob { field_type, default_value, rank, storage_scope, validator, metadata })
             },
             "Bool" => {
                 let default_value = self.default.as_bool().unwrap_or(false);
-                Ok(FieldSchema::Bool { field_type, default_value, rank, storage_scope })
+                Ok(FieldSchema::Bool { field_type, default_value, rank, storage_scope, validator, metadata })
             },
             "Choice" => {
                 let choices = self.choices.clone().unwrap_or_default();
@@ -129,7 +262,8 @@ impl JsonFieldSchema {
                 } else {
                     0
                 };
-                Ok(FieldSchema::Choice { field_type, default_value, rank, choices, storage_scope })
+                let choices_source = self.choices_source.clone();
+                Ok(FieldSchema::Choice { field_type, default_value, rank, choices, choices_source, storage_scope, validator, metadata })
             },
             "EntityList" => {
                 let default_value = if let Some(array) = self.default.as_array() {
@@ -140,31 +274,56 @@ impl JsonFieldSchema {
                 } else {
                     Vec::new()
                 };
-                Ok(FieldSchema::EntityList { field_type, default_value, rank, storage_scope })
+                Ok(FieldSchema::EntityList { field_type, default_value, rank, storage_scope, merge_policy, validator, metadata })
             },
             "EntityReference" => {
                 let default_value = self.default.as_str()
                     .and_then(|s| s.parse::<u64>().ok().map(EntityId));
-                Ok(FieldSchema::EntityReference { field_type, default_value, rank, storage_scope })
+                Ok(FieldSchema::EntityReference { field_type, default_value, rank, storage_scope, validator, metadata })
             },
             "Float" => {
                 let default_value = self.default.as_f64().unwrap_or(0.0);
-                Ok(FieldSchema::Float { field_type, default_value, rank, storage_scope })
+                Ok(FieldSchema::Float { field_type, default_value, rank, storage_scope, merge_policy, validator, metadata })
             },
             "Int" => {
                 let default_value = self.default.as_i64().unwrap_or(0);
-                Ok(FieldSchema::Int { field_type, default_value, rank, storage_scope })
+                Ok(FieldSchema::Int { field_type, default_value, rank, storage_scope, merge_policy, validator, metadata })
             },
             "String" => {
                 let default_value = self.default.as_str().unwrap_or("").to_string();
-                Ok(FieldSchema::String { field_type, default_value, rank, storage_scope })
+                Ok(FieldSchema::String { field_type, default_value, rank, storage_scope, validator, metadata })
             },
             "Timestamp" => {
                 let unix_timestamp: i64 = serde_json::from_value(self.default.clone())
                     .unwrap_or(0);
                 let default_value = time::OffsetDateTime::from_unix_timestamp(unix_timestamp)
                     .unwrap_or_else(|_| super::epoch());
-                Ok(FieldSchema::Timestamp { field_type, default_value, rank, storage_scope })
+                Ok(FieldSchema::Timestamp { field_type, default_value, rank, storage_scope, validator, metadata })
+            },
+            "Computed" => {
+                let expression = self.expression.clone()
+                    .ok_or_else(|| Error::InvalidFieldType(format!("Computed field '{}' requires an expression", self.name)))?;
+                Ok(FieldSchema::Computed { field_type, expression, rank, storage_scope, metadata })
+            },
+            "Decimal" => {
+                let default_value = json_to_decimal(&self.default).unwrap_or(Decimal::ZERO);
+                Ok(FieldSchema::Decimal { field_type, default_value, rank, storage_scope, merge_policy, validator, metadata })
+            },
+            "Duration" => {
+                let default_value = json_to_duration(&self.default).unwrap_or(Duration::ZERO);
+                Ok(FieldSchema::Duration { field_type, default_value, rank, storage_scope, merge_policy, validator, metadata })
+            },
+            "StringList" => {
+                let default_value = json_to_string_list(&self.default).unwrap_or_default();
+                Ok(FieldSchema::StringList { field_type, default_value, rank, storage_scope, merge_policy, validator, metadata })
+            },
+            "Map" => {
+                let default_value = json_to_map(&self.default).unwrap_or_default();
+                Ok(FieldSchema::Map { field_type, default_value, rank, storage_scope, validator, metadata })
+            },
+            "Series" => {
+                let (sample_type, capacity, retention) = self.series()?;
+                Ok(FieldSchema::Series { field_type, sample_type, capacity, retention, rank, storage_scope, validator, metadata })
             },
             _ => Err(Error::InvalidFieldType(format!("Unknown data type: {}", self.data_type))),
         }
@@ -218,32 +377,50 @@ impl JsonEntitySchema {
             
             // Override the rank to maintain file order
             field_schema = match field_schema {
-                FieldSchema::Blob { field_type, default_value, storage_scope, .. } => {
-                    FieldSchema::Blob { field_type, default_value, rank, storage_scope }
+                FieldSchema::Blob { field_type, default_value, storage_scope, validator, metadata, .. } => {
+                    FieldSchema::Blob { field_type, default_value, rank, storage_scope, validator, metadata }
+                },
+                FieldSchema::Bool { field_type, default_value, storage_scope, validator, metadata, .. } => {
+                    FieldSchema::Bool { field_type, default_value, rank, storage_scope, validator, metadata }
+                },
+                FieldSchema::Choice { field_type, default_value, choices, choices_source, storage_scope, validator, metadata, .. } => {
+                    FieldSchema::Choice { field_type, default_value, rank, choices, choices_source, storage_scope, validator, metadata }
                 },
-                FieldSchema::Bool { field_type, default_value, storage_scope, .. } => {
-                    FieldSchema::Bool { field_type, default_value, rank, storage_scope }
+                FieldSchema::EntityList { field_type, default_value, storage_scope, merge_policy, validator, metadata, .. } => {
+                    FieldSchema::EntityList { field_type, default_value, rank, storage_scope, merge_policy, validator, metadata }
                 },
-                FieldSchema::Choice { field_type, default_value, choices, storage_scope, .. } => {
-                    FieldSchema::Choice { field_type, default_value, rank, choices, storage_scope }
+                FieldSchema::EntityReference { field_type, default_value, storage_scope, validator, metadata, .. } => {
+                    FieldSchema::EntityReference { field_type, default_value, rank, storage_scope, validator, metadata }
                 },
-                FieldSchema::EntityList { field_type, default_value, storage_scope, .. } => {
-                    FieldSchema::EntityList { field_type, default_value, rank, storage_scope }
+                FieldSchema::Float { field_type, default_value, storage_scope, merge_policy, validator, metadata, .. } => {
+                    FieldSchema::Float { field_type, default_value, rank, storage_scope, merge_policy, validator, metadata }
                 },
-                FieldSchema::EntityReference { field_type, default_value, storage_scope, .. } => {
-                    FieldSchema::EntityReference { field_type, default_value, rank, storage_scope }
+                FieldSchema::Int { field_type, default_value, storage_scope, merge_policy, validator, metadata, .. } => {
+                    FieldSchema::Int { field_type, default_value, rank, storage_scope, merge_policy, validator, metadata }
                 },
-                FieldSchema::Float { field_type, default_value, storage_scope, .. } => {
-                    FieldSchema::Float { field_type, default_value, rank, storage_scope }
+                FieldSchema::String { field_type, default_value, storage_scope, validator, metadata, .. } => {
+                    FieldSchema::String { field_type, default_value, rank, storage_scope, validator, metadata }
                 },
-                FieldSchema::Int { field_type, default_value, storage_scope, .. } => {
-                    FieldSchema::Int { field_type, default_value, rank, storage_scope }
+                FieldSchema::Timestamp { field_type, default_value, storage_scope, validator, metadata, .. } => {
+                    FieldSchema::Timestamp { field_type, default_value, rank, storage_scope, validator, metadata }
                 },
-                FieldSchema::String { field_type, default_value, storage_scope, .. } => {
-                    FieldSchema::String { field_type, default_value, rank, storage_scope }
+                FieldSchema::Computed { field_type, expression, storage_scope, metadata, .. } => {
+                    FieldSchema::Computed { field_type, expression, rank, storage_scope, metadata }
                 },
-                FieldSchema::Timestamp { field_type, default_value, storage_scope, .. } => {
-                    FieldSchema::Timestamp { field_type, default_value, rank, storage_scope }
+                FieldSchema::Decimal { field_type, default_value, storage_scope, merge_policy, validator, metadata, .. } => {
+                    FieldSchema::Decimal { field_type, default_value, rank, storage_scope, merge_policy, validator, metadata }
+                },
+                FieldSchema::Duration { field_type, default_value, storage_scope, merge_policy, validator, metadata, .. } => {
+                    FieldSchema::Duration { field_type, default_value, rank, storage_scope, merge_policy, validator, metadata }
+                },
+                FieldSchema::StringList { field_type, default_value, storage_scope, merge_policy, validator, metadata, .. } => {
+                    FieldSchema::StringList { field_type, default_value, rank, storage_scope, merge_policy, validator, metadata }
+                },
+                FieldSchema::Map { field_type, default_value, storage_scope, validator, metadata, .. } => {
+                    FieldSchema::Map { field_type, default_value, rank, storage_scope, validator, metadata }
+                },
+                FieldSchema::Series { field_type, sample_type, capacity, retention, storage_scope, validator, metadata, .. } => {
+                    FieldSchema::Series { field_type, sample_type, capacity, retention, rank, storage_scope, validator, metadata }
                 },
             };
             schema.fields.insert(field_schema.field_type().clone(), field_schema);
@@ -253,6 +430,124 @@ impl JsonEntitySchema {
     }
 }
 
+/// Decimals are written as strings so they never round-trip through f64
+fn decimal_to_json(value: &Decimal) -> JsonValue {
+    JsonValue::String(value.to_string())
+}
+
+/// Accepts either a decimal string or a plain JSON number
+fn json_to_decimal(json_value: &JsonValue) -> Result<Decimal> {
+    match json_value {
+        JsonValue::String(s) => s.parse(),
+        JsonValue::Number(n) => n.to_string().parse(),
+        _ => Err(Error::InvalidFieldValue("Expected decimal string or number".to_string())),
+    }
+}
+
+/// Durations are written as seconds, fractional only when needed
+fn duration_to_json(value: &Duration) -> JsonValue {
+    if value.subsec_nanoseconds() == 0 {
+        JsonValue::Number(serde_json::Number::from(value.whole_seconds()))
+    } else {
+        serde_json::Number::from_f64(value.as_seconds_f64())
+            .map(JsonValue::Number)
+            .unwrap_or(JsonValue::Null)
+    }
+}
+
+fn json_to_duration(json_value: &JsonValue) -> Result<Duration> {
+    if let Some(secs) = json_value.as_i64() {
+        Ok(Duration::seconds(secs))
+    } else if let Some(secs) = json_value.as_f64() {
+        Duration::checked_seconds_f64(secs)
+            .ok_or_else(|| Error::InvalidFieldValue("Duration out of range".to_string()))
+    } else {
+        Err(Error::InvalidFieldValue("Expected duration in seconds".to_string()))
+    }
+}
+
+fn string_list_to_json(value: &[String]) -> JsonValue {
+    JsonValue::Array(value.iter().cloned().map(JsonValue::String).collect())
+}
+
+fn json_to_string_list(json_value: &JsonValue) -> Result<Vec<String>> {
+    json_value
+        .as_array()
+        .ok_or_else(|| Error::InvalidFieldValue("Expected array of strings".to_string()))?
+        .iter()
+        .map(|item| {
+            item.as_str()
+                .map(str::to_string)
+                .ok_or_else(|| Error::InvalidFieldValue("Expected array of strings".to_string()))
+        })
+        .collect()
+}
+
+/// Variant names of `Value`, used to tell tagged map entries from nested maps
+const VALUE_VARIANTS: &[&str] = &[
+    "Blob", "Bool", "Choice", "EntityList", "EntityReference", "Float", "Int", "String",
+    "Timestamp", "Decimal", "Duration", "StringList", "Map",
+];
+
+fn is_tagged(map: &serde_json::Map<String, JsonValue>) -> bool {
+    map.len() == 1 && map.keys().all(|key| VALUE_VARIANTS.contains(&key.as_str()))
+}
+
+/// Maps are written as JSON objects. Map entries carry no schema, so bools,
+/// ints, floats, strings, string lists and nested maps use plain JSON and
+/// everything else keeps its type tag (e.g. `{"Decimal": "1.50"}`).
+fn map_to_json(value: &BTreeMap<String, Value>) -> JsonValue {
+    JsonValue::Object(value.iter().map(|(key, value)| (key.clone(), map_entry_to_json(value))).collect())
+}
+
+fn map_entry_to_json(value: &Value) -> JsonValue {
+    match value {
+        Value::Bool(v) => JsonValue::Bool(*v),
+        Value::Int(v) => JsonValue::Number(serde_json::Number::from(*v)),
+        Value::Float(v) if v.is_finite() => value_to_json_value(value, None),
+        Value::String(v) => JsonValue::String(v.clone()),
+        Value::StringList(v) => string_list_to_json(v),
+        Value::Map(v) => match map_to_json(v) {
+            // A nested map that looks like a tagged value has to be tagged itself
+            JsonValue::Object(object) if is_tagged(&object) => {
+                let mut tagged = serde_json::Map::new();
+                tagged.insert("Map".to_string(), JsonValue::Object(object));
+                JsonValue::Object(tagged)
+            }
+            plain => plain,
+        },
+        _ => serde_json::to_value(value).unwrap_or(JsonValue::Null),
+    }
+}
+
+fn json_to_map(json_value: &JsonValue) -> Result<BTreeMap<String, Value>> {
+    json_value
+        .as_object()
+        .ok_or_else(|| Error::InvalidFieldValue("Expected object for map".to_string()))?
+        .iter()
+        .map(|(key, value)| Ok((key.clone(), json_to_map_entry(value)?)))
+        .collect()
+}
+
+fn json_to_map_entry(json_value: &JsonValue) -> Result<Value> {
+    match json_value {
+        JsonValue::Bool(v) => Ok(Value::Bool(*v)),
+        JsonValue::Number(n) => match n.as_i64() {
+            Some(v) => Ok(Value::Int(v)),
+            None => Ok(Value::Float(n.as_f64().unwrap_or_default())),
+        },
+        JsonValue::String(v) => Ok(Value::String(v.clone())),
+        JsonValue::Array(_) => Ok(Value::StringList(json_to_string_list(json_value)?)),
+        JsonValue::Object(object) if is_tagged(object) => match object.get("Map") {
+            Some(nested) => Ok(Value::Map(json_to_map(nested)?)),
+            None => serde_json::from_value(json_value.clone())
+                .map_err(|e| Error::InvalidFieldValue(format!("Invalid tagged map entry: {}", e))),
+        },
+        JsonValue::Object(_) => Ok(Value::Map(json_to_map(json_value)?)),
+        JsonValue::Null => Err(Error::InvalidFieldValue("Map entries cannot be null".to_string())),
+    }
+}
+
 /// Helper function to convert Value to JsonValue for entity data
 pub fn value_to_json_value(value: &Value, choices: Option<&Vec<String>>) -> JsonValue {
     match value {
@@ -281,6 +576,10 @@ pub fn value_to_json_value(value: &Value, choices: Option<&Vec<String>>) -> Json
         Value::Int(v) => JsonValue::Number(serde_json::Number::from(*v)),
         Value::String(v) => JsonValue::String(v.to_string()),
         Value::Timestamp(v) => serde_json::to_value(v.unix_timestamp()).unwrap_or(JsonValue::Null),
+        Value::Decimal(v) => decimal_to_json(v),
+        Value::Duration(v) => duration_to_json(v),
+        Value::StringList(v) => string_list_to_json(v),
+        Value::Map(v) => map_to_json(v),
     }
 }
 
@@ -331,6 +630,10 @@ pub fn value_to_json_value_with_paths<T: StoreTrait>(
         Value::Int(v) => JsonValue::Number(serde_json::Number::from(*v)),
         Value::String(v) => JsonValue::String(v.to_string()),
         Value::Timestamp(v) => serde_json::to_value(v.unix_timestamp()).unwrap_or(JsonValue::Null),
+        Value::Decimal(v) => decimal_to_json(v),
+        Value::Duration(v) => duration_to_json(v),
+        Value::StringList(v) => string_list_to_json(v),
+        Value::Map(v) => map_to_json(v),
     }
 }
 
@@ -415,6 +718,24 @@ pub fn json_value_to_value(json_value: &JsonValue, field_schema: &FieldSchema) -
                 .map_err(|_| Error::InvalidFieldValue("Invalid unix timestamp".to_string()))?;
             Ok(Value::Timestamp(timestamp))
         },
+        FieldSchema::Computed { .. } => {
+            Err(Error::InvalidFieldValue("Computed fields cannot be assigned a value".to_string()))
+        },
+        FieldSchema::Decimal { .. } => Ok(Value::Decimal(json_to_decimal(json_value)?)),
+        FieldSchema::Duration { .. } => Ok(Value::Duration(json_to_duration(json_value)?)),
+        FieldSchema::StringList { .. } => Ok(Value::StringList(json_to_string_list(json_value)?)),
+        FieldSchema::Map { .. } => Ok(Value::Map(json_to_map(json_value)?)),
+        // Only the latest sample of a series is kept in a JSON snapshot
+        FieldSchema::Series { sample_type: SampleType::Int, .. } => {
+            let int_val = json_value.as_i64()
+                .ok_or_else(|| Error::InvalidFieldValue("Expected integer value".to_string()))?;
+            Ok(Value::Int(int_val))
+        },
+        FieldSchema::Series { sample_type: SampleType::Float, .. } => {
+            let float_val = json_value.as_f64()
+                .ok_or_else(|| Error::InvalidFieldValue("Expected float value".to_string()))?;
+            Ok(Value::Float(float_val))
+        },
     }
 }
 
@@ -556,14 +877,10 @@ fn apply_pending_field_updates<T: StoreTrait>(
     )))
 }
 
-/// Take a JSON snapshot of the current store state
-/// This finds the Root entity automatically and creates a hierarchical representation
-/// Works with any type implementing StoreTrait
-pub fn take_json_snapshot<T: StoreTrait>(store: &mut T) -> Result<JsonSnapshot> {
-    // Collect all schemas by getting all entity types first
+/// The schemas of every entity type in JSON format, sorted by type name
+pub fn take_json_schemas<T: StoreTrait>(store: &T) -> Result<Vec<JsonEntitySchema>> {
     let mut json_schemas = Vec::new();
-    let entity_types = store.get_entity_types()?;
-    for entity_type in entity_types {
+    for entity_type in store.get_entity_types()? {
         if let Ok(schema) = store.get_entity_schema(entity_type) {
             json_schemas.push(JsonEntitySchema::from_entity_schema(&schema, store));
         }
@@ -571,6 +888,14 @@ pub fn take_json_snapshot<T: StoreTrait>(store: &mut T) -> Result<JsonSnapshot>
 
     // Sort schemas for consistent output
     json_schemas.sort_by(|a, b| a.entity_type.cmp(&b.entity_type));
+    Ok(json_schemas)
+}
+
+/// Take a JSON snapshot of the current store state
+/// This finds the Root entity automatically and creates a hierarchical representation
+/// Works with any type implementing StoreTrait
+pub fn take_json_snapshot<T: StoreTrait>(store: &mut T) -> Result<JsonSnapshot> {
+    let json_schemas = take_json_schemas(store)?;
 
     // Find the Root entity
     let root_entities = store.find_entities(store.get_entity_type("Root")?, None)?;
@@ -580,10 +905,13 @@ pub fn take_json_snapshot<T: StoreTrait>(store: &mut T) -> Result<JsonSnapshot>
     // Build the entity tree starting from root using the helper function
     let root_entity = build_json_entity_tree(store, *root_entity_id)?;
 
-    Ok(JsonSnapshot {
+    let mut snapshot = JsonSnapshot {
         schemas: json_schemas,
         tree: root_entity,
-    })
+        checksum: None,
+    };
+    snapshot.checksum = Some(crate::data::checksum::json_snapshot_checksum(&snapshot));
+    Ok(snapshot)
 }
 
 /// Helper function to build a JSON entity tree with special handling for Children fields
@@ -597,17 +925,16 @@ pub fn build_json_entity_tree<T: StoreTrait>(
     }
 
     let entity_type = entity_id.extract_type();
-    let complete_schema = store.get_complete_entity_schema(entity_type)?;
-    
+
     // First, collect and sort all fields by rank to ensure correct processing order
-    // Clone the data we need to avoid borrowing conflicts
-    let schema_fields: Vec<(crate::FieldType, crate::FieldSchema)> = complete_schema.fields
-        .iter()
+    let schema_fields: Vec<(crate::FieldType, crate::FieldSchema)> = collect_field_schemas(store, entity_type)?
+        .into_iter()
         .filter(|(_, field_schema)| {
-            // Only include configuration fields in snapshots, excluding runtime fields
+            // Only include configuration fields in snapshots, excluding runtime fields.
+            // Computed fields are derived on read and never stored.
             !matches!(field_schema.storage_scope(), crate::data::StorageScope::Runtime)
+                && !field_schema.is_computed()
         })
-        .map(|(ft, fs)| (*ft, fs.clone()))
         .collect();
     
     // Sort by rank to ensure consistent field ordering
@@ -638,7 +965,8 @@ pub fn build_json_entity_tree<T: StoreTrait>(
                 }
             } else {
                 // For other fields, use path-aware value conversion for entity references
-                let choices_ref = if let crate::FieldSchema::Choice { choices, .. } = field_schema {
+                // Data-driven choices are kept as numbers since their names live in the store
+                let choices_ref = if let crate::FieldSchema::Choice { choices, choices_source: None, .. } = field_schema {
                     Some(choices)
                 } else {
                     None
@@ -675,8 +1003,29 @@ pub fn build_json_entity_tree<T: StoreTrait>(
 /// This recreates the entity hierarchy from the JSON snapshot
 /// Works with any type implementing StoreTrait
 pub fn restore_json_snapshot<T: StoreTrait>(store: &mut T, json_snapshot: &JsonSnapshot) -> Result<()> {
+    // Perform schema updates first
+    for string_schema in json_schemas_to_string_schemas(&json_snapshot.schemas, HashMap::new())? {
+        store.update_schema(string_schema)?;
+    }
+
+    // Restore the entity tree starting from the root
+    let mut pending_updates = Vec::new();
+    restore_entity_recursive_internal(store, &json_snapshot.tree, None, "", &mut pending_updates)?;
+    apply_pending_field_updates(store, pending_updates)?;
+
+    Ok(())
+}
+
+/// Convert JSON schemas to the string schemas `update_schema` takes, base
+/// types first. Each type's ranks are offset past those of the types it
+/// inherits from, so inherited fields keep their order. `max_ranks` holds
+/// the highest rank of base types that aren't in `schemas`.
+pub(crate) fn json_schemas_to_string_schemas(
+    schemas: &[JsonEntitySchema],
+    mut max_ranks: HashMap<String, i64>,
+) -> Result<Vec<EntitySchema<Single, String, String>>> {
     // Sort schemas by dependency order (base classes first)
-    let mut sorted_schemas = json_snapshot.schemas.clone();
+    let mut sorted_schemas = schemas.to_vec();
     sorted_schemas.sort_by(|a, b| {
         // If a inherits from b, b should come first
         if a.inherits_from.contains(&b.entity_type) {
@@ -690,10 +1039,7 @@ pub fn restore_json_snapshot<T: StoreTrait>(store: &mut T, json_snapshot: &JsonS
         a.entity_type.cmp(&b.entity_type)
     });
 
-    // First, restore schemas in dependency order with proper rank adjustments
-    let mut max_ranks: std::collections::HashMap<String, i64> = std::collections::HashMap::new();
-
-    // Perform schema updates first
+    let mut string_schemas = Vec::new();
     for json_schema in &sorted_schemas {
         // Calculate rank offset based on ALL inherited schemas
         // For multiple inheritance, we need to accumulate offsets properly
@@ -735,19 +1081,17 @@ pub fn restore_json_snapshot<T: StoreTrait>(store: &mut T, json_snapshot: &JsonS
                     field_type: field.name.clone(),
                     default_value: field.default.as_array().map(|arr| arr.iter().filter_map(|v| v.as_u64().map(|n| n as u8)).collect()).unwrap_or_default(),
                     rank: field.rank.unwrap_or(0),
-                    storage_scope: match field.storage_scope.as_ref().map(|s| s.as_str()).unwrap_or("Configuration") {
-                        "Runtime" => crate::data::StorageScope::Runtime,
-                        _ => crate::data::StorageScope::Configuration,
-                    },
+                    storage_scope: parse_storage_scope(field.storage_scope.as_deref()),
+                    validator: field.validator.clone(),
+                    metadata: field.metadata(),
                 },
                 "Bool" => FieldSchema::Bool {
                     field_type: field.name.clone(),
                     default_value: field.default.as_bool().unwrap_or(false),
                     rank: field.rank.unwrap_or(0),
-                    storage_scope: match field.storage_scope.as_ref().map(|s| s.as_str()).unwrap_or("Configuration") {
-                        "Runtime" => crate::data::StorageScope::Runtime,
-                        _ => crate::data::StorageScope::Configuration,
-                    },
+                    storage_scope: parse_storage_scope(field.storage_scope.as_deref()),
+                    validator: field.validator.clone(),
+                    metadata: field.metadata(),
                 },
                 "Choice" => FieldSchema::Choice {
                     field_type: field.name.clone(),
@@ -756,84 +1100,133 @@ pub fn restore_json_snapshot<T: StoreTrait>(store: &mut T, json_snapshot: &JsonS
                     }).unwrap_or(0),
                     rank: field.rank.unwrap_or(0),
                     choices: field.choices.clone().unwrap_or_default(),
-                    storage_scope: match field.storage_scope.as_ref().map(|s| s.as_str()).unwrap_or("Configuration") {
-                        "Runtime" => crate::data::StorageScope::Runtime,
-                        _ => crate::data::StorageScope::Configuration,
-                    },
+                    choices_source: field.choices_source.clone(),
+                    storage_scope: parse_storage_scope(field.storage_scope.as_deref()),
+                    validator: field.validator.clone(),
+                    metadata: field.metadata(),
                 },
                 "EntityList" => FieldSchema::EntityList {
                     field_type: field.name.clone(),
                     default_value: Vec::new(), // Will be populated during entity creation
                     rank: field.rank.unwrap_or(0),
-                    storage_scope: match field.storage_scope.as_ref().map(|s| s.as_str()).unwrap_or("Configuration") {
-                        "Runtime" => crate::data::StorageScope::Runtime,
-                        _ => crate::data::StorageScope::Configuration,
-                    },
+                    storage_scope: parse_storage_scope(field.storage_scope.as_deref()),
+                    merge_policy: parse_merge_policy(field.merge_policy.as_deref()),
+                    validator: field.validator.clone(),
+                    metadata: field.metadata(),
                 },
                 "EntityReference" => FieldSchema::EntityReference {
                     field_type: field.name.clone(),
                     default_value: None, // Will be populated during entity creation
                     rank: field.rank.unwrap_or(0),
-                    storage_scope: match field.storage_scope.as_ref().map(|s| s.as_str()).unwrap_or("Configuration") {
-                        "Runtime" => crate::data::StorageScope::Runtime,
-                        _ => crate::data::StorageScope::Configuration,
-                    },
+                    storage_scope: parse_storage_scope(field.storage_scope.as_deref()),
+                    validator: field.validator.clone(),
+                    metadata: field.metadata(),
                 },
                 "Float" => FieldSchema::Float {
                     field_type: field.name.clone(),
                     default_value: field.default.as_f64().unwrap_or(0.0),
                     rank: field.rank.unwrap_or(0),
-                    storage_scope: match field.storage_scope.as_ref().map(|s| s.as_str()).unwrap_or("Configuration") {
-                        "Runtime" => crate::data::StorageScope::Runtime,
-                        _ => crate::data::StorageScope::Configuration,
-                    },
+                    storage_scope: parse_storage_scope(field.storage_scope.as_deref()),
+                    merge_policy: parse_merge_policy(field.merge_policy.as_deref()),
+                    validator: field.validator.clone(),
+                    metadata: field.metadata(),
                 },
                 "Int" => FieldSchema::Int {
                     field_type: field.name.clone(),
                     default_value: field.default.as_i64().unwrap_or(0),
                     rank: field.rank.unwrap_or(0),
-                    storage_scope: match field.storage_scope.as_ref().map(|s| s.as_str()).unwrap_or("Configuration") {
-                        "Runtime" => crate::data::StorageScope::Runtime,
-                        _ => crate::data::StorageScope::Configuration,
-                    },
+                    storage_scope: parse_storage_scope(field.storage_scope.as_deref()),
+                    merge_policy: parse_merge_policy(field.merge_policy.as_deref()),
+                    validator: field.validator.clone(),
+                    metadata: field.metadata(),
                 },
                 "String" => FieldSchema::String {
                     field_type: field.name.clone(),
                     default_value: field.default.as_str().unwrap_or("").to_string(),
                     rank: field.rank.unwrap_or(0),
-                    storage_scope: match field.storage_scope.as_ref().map(|s| s.as_str()).unwrap_or("Configuration") {
-                        "Runtime" => crate::data::StorageScope::Runtime,
-                        _ => crate::data::StorageScope::Configuration,
-                    },
+                    storage_scope: parse_storage_scope(field.storage_scope.as_deref()),
+                    validator: field.validator.clone(),
+                    metadata: field.metadata(),
                 },
                 "Timestamp" => FieldSchema::Timestamp {
                     field_type: field.name.clone(),
                     default_value: crate::data::epoch(),
                     rank: field.rank.unwrap_or(0),
-                    storage_scope: match field.storage_scope.as_ref().map(|s| s.as_str()).unwrap_or("Configuration") {
-                        "Runtime" => crate::data::StorageScope::Runtime,
-                        _ => crate::data::StorageScope::Configuration,
-                    },
+                    storage_scope: parse_storage_scope(field.storage_scope.as_deref()),
+                    validator: field.validator.clone(),
+                    metadata: field.metadata(),
+                },
+                "Computed" => FieldSchema::Computed {
+                    field_type: field.name.clone(),
+                    expression: field.expression.clone().unwrap_or_default(),
+                    rank: field.rank.unwrap_or(0),
+                    storage_scope: parse_storage_scope(field.storage_scope.as_deref()),
+                    metadata: field.metadata(),
+                },
+                "Decimal" => FieldSchema::Decimal {
+                    field_type: field.name.clone(),
+                    default_value: json_to_decimal(&field.default).unwrap_or(Decimal::ZERO),
+                    rank: field.rank.unwrap_or(0),
+                    storage_scope: parse_storage_scope(field.storage_scope.as_deref()),
+                    merge_policy: parse_merge_policy(field.merge_policy.as_deref()),
+                    validator: field.validator.clone(),
+                    metadata: field.metadata(),
+                },
+                "Duration" => FieldSchema::Duration {
+                    field_type: field.name.clone(),
+                    default_value: json_to_duration(&field.default).unwrap_or(Duration::ZERO),
+                    rank: field.rank.unwrap_or(0),
+                    storage_scope: parse_storage_scope(field.storage_scope.as_deref()),
+                    merge_policy: parse_merge_policy(field.merge_policy.as_deref()),
+                    validator: field.validator.clone(),
+                    metadata: field.metadata(),
+                },
+                "StringList" => FieldSchema::StringList {
+                    field_type: field.name.clone(),
+                    default_value: json_to_string_list(&field.default).unwrap_or_default(),
+                    rank: field.rank.unwrap_or(0),
+                    storage_scope: parse_storage_scope(field.storage_scope.as_deref()),
+                    merge_policy: parse_merge_policy(field.merge_policy.as_deref()),
+                    validator: field.validator.clone(),
+                    metadata: field.metadata(),
+                },
+                "Map" => FieldSchema::Map {
+                    field_type: field.name.clone(),
+                    default_value: json_to_map(&field.default).unwrap_or_default(),
+                    rank: field.rank.unwrap_or(0),
+                    storage_scope: parse_storage_scope(field.storage_scope.as_deref()),
+                    validator: field.validator.clone(),
+                    metadata: field.metadata(),
+                },
+                "Series" => {
+                    let (sample_type, capacity, retention) = field.series()?;
+                    FieldSchema::Series {
+                        field_type: field.name.clone(),
+                        sample_type,
+                        capacity,
+                        retention,
+                        rank: field.rank.unwrap_or(0),
+                        storage_scope: parse_storage_scope(field.storage_scope.as_deref()),
+                        validator: field.validator.clone(),
+                        metadata: field.metadata(),
+                    }
                 },
                 _ => FieldSchema::String {
                     field_type: field.name.clone(),
                     default_value: "".to_string(),
                     rank: field.rank.unwrap_or(0),
                     storage_scope: crate::data::StorageScope::Configuration,
+                    validator: field.validator.clone(),
+                    metadata: field.metadata(),
                 },
             };
             string_schema.fields.insert(field.name.clone(), field_schema);
         }
 
-        store.update_schema(string_schema)?;
+        string_schemas.push(string_schema);
     }
 
-    // Restore the entity tree starting from the root
-    let mut pending_updates = Vec::new();
-    restore_entity_recursive_internal(store, &json_snapshot.tree, None, "", &mut pending_updates)?;
-    apply_pending_field_updates(store, pending_updates)?;
-
-    Ok(())
+    Ok(string_schemas)
 }
 
 /// Helper function to recursively restore entities from JSON
@@ -863,17 +1256,19 @@ fn restore_entity_recursive_internal<T: StoreTrait>(
         &name
     )?;
 
-    // Get the entity schema to understand field types
-    let complete_schema = store.get_complete_entity_schema(store.get_entity_type(&json_entity.entity_type)?)?;
-    
-    // Clone the fields map to avoid borrowing conflicts
-    let schema_fields = complete_schema.fields.clone();
+    // Get the entity schema (including inherited fields) to understand field types
+    let schema_fields: HashMap<FieldType, FieldSchema> = collect_field_schemas(store, store.get_entity_type(&json_entity.entity_type)?)?
+        .into_iter()
+        .collect();
 
     // Set field values (except Children - we'll handle that last)
     for (field_name, json_value) in &json_entity.fields {
         if field_name == "Children" {
             continue; // Handle children separately
         }
+        if field_name == "Parent" {
+            continue; // Already set by create_entity
+        }
 
         let field_type = store.get_field_type(field_name)?;
         if let Some(field_schema) = schema_fields.get(&field_type) {
@@ -949,6 +1344,207 @@ pub fn restore_entity_recursive<T: StoreTrait>(
     Ok(entity_id)
 }
 
+/// A problem found while validating a JSON subtree for import
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportError {
+    /// Path of the offending entity within the store, e.g. `Root/Machines/M1`
+    pub path: String,
+    /// Field the error refers to, if any
+    pub field: Option<String>,
+    pub message: String,
+}
+
+impl std::fmt::Display for ImportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.field {
+            Some(field) => write!(f, "{}::{}: {}", self.path, field, self.message),
+            None => write!(f, "{}: {}", self.path, self.message),
+        }
+    }
+}
+
+/// Outcome of `import_subtree`
+#[derive(Debug, Clone, Default)]
+pub struct ImportReport {
+    /// The created root entity, None on a dry run or when validation failed
+    pub root_id: Option<EntityId>,
+    /// Number of entities in the imported tree
+    pub entity_count: usize,
+    pub errors: Vec<ImportError>,
+}
+
+impl ImportReport {
+    pub fn is_valid(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
+
+/// Export an entity and its descendants as a JSON tree
+///
+/// Same format as the `tree` of a `JsonSnapshot`, so the result can be kept
+/// in a file and brought back with `import_subtree`.
+pub fn export_subtree<T: StoreTrait>(store: &mut T, entity_id: EntityId) -> Result<JsonEntity> {
+    build_json_entity_tree(store, entity_id)
+}
+
+/// Import a JSON tree (as produced by `export_subtree`) under `parent_id`
+///
+/// The whole tree is validated against the current schemas first: entity
+/// types, field names, field values and entity references (which may point
+/// to existing entities or to entities within the imported tree). Nothing
+/// is written if any entity fails validation; the problems are listed in the
+/// returned report instead. With `dry_run` the tree is only validated.
+pub fn import_subtree<T: StoreTrait>(
+    store: &mut T,
+    json_entity: &JsonEntity,
+    parent_id: Option<EntityId>,
+    dry_run: bool,
+) -> Result<ImportReport> {
+    let parent_path = match parent_id {
+        Some(parent_id) => crate::path(store, parent_id)?,
+        None => String::new(),
+    };
+
+    let mut imported_paths = std::collections::HashSet::new();
+    collect_import_paths(json_entity, &parent_path, &mut imported_paths);
+
+    let mut report = ImportReport::default();
+    validate_import_entity(store, json_entity, &parent_path, &imported_paths, &mut report);
+
+    if dry_run || !report.is_valid() {
+        return Ok(report);
+    }
+
+    report.root_id = Some(restore_entity_recursive(store, json_entity, parent_id)?);
+    Ok(report)
+}
+
+fn import_entity_path(json_entity: &JsonEntity, parent_path: &str) -> String {
+    let name = json_entity.fields.get("Name").and_then(|v| v.as_str()).unwrap_or_default();
+    if parent_path.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}/{}", parent_path, name)
+    }
+}
+
+fn import_children(json_entity: &JsonEntity) -> impl Iterator<Item = std::result::Result<JsonEntity, serde_json::Error>> + '_ {
+    json_entity
+        .fields
+        .get("Children")
+        .and_then(|children| children.as_array())
+        .into_iter()
+        .flatten()
+        .map(|child| serde_json::from_value::<JsonEntity>(child.clone()))
+}
+
+fn collect_import_paths(json_entity: &JsonEntity, parent_path: &str, paths: &mut std::collections::HashSet<String>) {
+    let entity_path = import_entity_path(json_entity, parent_path);
+    for child in import_children(json_entity).flatten() {
+        collect_import_paths(&child, &entity_path, paths);
+    }
+    paths.insert(entity_path);
+}
+
+fn validate_import_entity<T: StoreTrait>(
+    store: &T,
+    json_entity: &JsonEntity,
+    parent_path: &str,
+    imported_paths: &std::collections::HashSet<String>,
+    report: &mut ImportReport,
+) {
+    let entity_path = import_entity_path(json_entity, parent_path);
+    report.entity_count += 1;
+
+    let mut error = |field: Option<&str>, message: String| {
+        report.errors.push(ImportError {
+            path: entity_path.clone(),
+            field: field.map(str::to_string),
+            message,
+        });
+    };
+
+    if !json_entity.fields.get("Name").is_some_and(|name| name.is_string()) {
+        error(Some("Name"), "Missing or non-string Name".to_string());
+    }
+
+    let schema_fields: HashMap<FieldType, FieldSchema> = match store
+        .get_entity_type(&json_entity.entity_type)
+        .and_then(|entity_type| collect_field_schemas(store, entity_type))
+    {
+        Ok(fields) => fields.into_iter().collect(),
+        Err(e) => {
+            error(None, format!("Unknown entity type '{}': {}", json_entity.entity_type, e));
+            return;
+        }
+    };
+
+    for (field_name, json_value) in &json_entity.fields {
+        if field_name == "Children" || field_name == "Parent" {
+            continue;
+        }
+
+        let Some(field_schema) = store
+            .get_field_type(field_name)
+            .ok()
+            .and_then(|field_type| schema_fields.get(&field_type))
+        else {
+            error(Some(field_name.as_str()), format!("Not a field of {}", json_entity.entity_type));
+            continue;
+        };
+
+        match field_schema {
+            FieldSchema::Computed { .. } => error(Some(field_name.as_str()), "Computed fields cannot be imported".to_string()),
+            FieldSchema::EntityReference { .. } | FieldSchema::EntityList { .. } => {
+                let references: Vec<&JsonValue> = match json_value {
+                    JsonValue::Null => Vec::new(),
+                    JsonValue::Array(items) if matches!(field_schema, FieldSchema::EntityList { .. }) => items.iter().collect(),
+                    JsonValue::String(_) if matches!(field_schema, FieldSchema::EntityReference { .. }) => vec![json_value],
+                    _ => {
+                        error(Some(field_name.as_str()), format!("Unexpected value {}", json_value));
+                        continue;
+                    }
+                };
+
+                for reference in references {
+                    let resolvable = match reference.as_str() {
+                        Some(reference) => match reference.parse::<u64>() {
+                            Ok(id) => store.entity_exists(EntityId(id)),
+                            Err(_) => imported_paths.contains(reference) || crate::path_to_entity_id(store, reference).is_ok(),
+                        },
+                        None => reference.as_u64().is_some_and(|id| store.entity_exists(EntityId(id))),
+                    };
+                    if !resolvable {
+                        error(Some(field_name.as_str()), format!("Unresolved entity reference {}", reference));
+                    }
+                }
+            }
+            _ => {
+                if let Err(e) = json_value_to_value(json_value, field_schema) {
+                    error(Some(field_name.as_str()), e.to_string());
+                }
+            }
+        }
+    }
+
+    if let Some(children) = json_entity.fields.get("Children") {
+        if !children.is_array() {
+            error(Some("Children"), "Expected an array of entities".to_string());
+        }
+    }
+
+    for (index, child) in import_children(json_entity).enumerate() {
+        match child {
+            Ok(child) => validate_import_entity(store, &child, &entity_path, imported_paths, report),
+            Err(e) => report.errors.push(ImportError {
+                path: entity_path.clone(),
+                field: Some("Children".to_string()),
+                message: format!("Child {} is not a valid entity: {}", index, e),
+            }),
+        }
+    }
+}
+
 /// Helper function to clear all contents from a directory
 fn clear_directory_contents(dir_path: &std::path::Path) -> std::io::Result<()> {
     let entries = fs::read_dir(dir_path)?;
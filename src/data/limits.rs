@@ -0,0 +1,183 @@
+use std::time::Instant;
+
+use rustc_hash::FxHashMap;
+
+use crate::{ClientContext, EntityId};
+
+/// Field read from a subject entity to override `Limits::commands_per_second`
+pub const RATE_LIMIT: &str = "RateLimit";
+/// Field read from a subject entity to override `Limits::burst`
+pub const RATE_BURST: &str = "RateBurst";
+/// Field read from a subject entity to override `Limits::max_entities`
+pub const MAX_ENTITIES: &str = "MaxEntities";
+/// Field read from a subject entity to override `Limits::max_notifications`
+pub const MAX_NOTIFICATIONS: &str = "MaxNotifications";
+
+/// Limits applied to one client. `None` means unlimited.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Limits {
+    /// Sustained command rate
+    pub commands_per_second: Option<f64>,
+    /// Commands that may be sent at once after a quiet period. Defaults to
+    /// one second's worth of commands.
+    pub burst: Option<u32>,
+    /// Entities the subject may create
+    pub max_entities: Option<u64>,
+    /// Notifications the subject may have registered at the same time
+    pub max_notifications: Option<u64>,
+}
+
+/// Token bucket: holds up to `capacity` tokens and refills at `rate` per second
+#[derive(Debug, Clone)]
+pub struct TokenBucket {
+    capacity: f64,
+    rate: f64,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    /// A full bucket
+    pub fn new(rate: f64, capacity: f64, now: Instant) -> Self {
+        Self {
+            capacity,
+            rate,
+            tokens: capacity,
+            last_refill: now,
+        }
+    }
+
+    /// Change the rate and capacity, keeping the tokens already earned
+    pub fn configure(&mut self, rate: f64, capacity: f64) {
+        self.rate = rate;
+        self.capacity = capacity;
+        self.tokens = self.tokens.min(capacity);
+    }
+
+    /// Take one token, if there is one
+    pub fn try_take(&mut self, now: Instant) -> bool {
+        let elapsed = now.saturating_duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.capacity);
+        self.last_refill = now;
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Who usage is counted against: the authenticated subject, or the
+/// connection for clients that haven't authenticated
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum LimitKey {
+    Subject(EntityId),
+    Connection(String),
+}
+
+impl std::fmt::Display for LimitKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LimitKey::Subject(subject) => write!(f, "{:?}", subject),
+            LimitKey::Connection(connection) => write!(f, "{}", connection),
+        }
+    }
+}
+
+impl LimitKey {
+    pub fn from_context(context: &ClientContext) -> Option<Self> {
+        context
+            .subject
+            .map(LimitKey::Subject)
+            .or_else(|| context.connection.clone().map(LimitKey::Connection))
+    }
+}
+
+/// What one client has used so far
+#[derive(Debug, Clone, Default)]
+pub struct Usage {
+    bucket: Option<TokenBucket>,
+    pub entities_created: u64,
+    pub notifications: u64,
+}
+
+/// Tracks command rates and quotas per client. Enabled with
+/// `Store::enable_limits`, which also decides the limits for each client.
+#[derive(Debug, Clone, Default)]
+pub struct LimitEnforcer {
+    defaults: Limits,
+    usage: FxHashMap<LimitKey, Usage>,
+}
+
+impl LimitEnforcer {
+    /// Apply `defaults` to every client without limits of its own
+    pub fn new(defaults: Limits) -> Self {
+        Self {
+            defaults,
+            usage: FxHashMap::default(),
+        }
+    }
+
+    pub fn defaults(&self) -> &Limits {
+        &self.defaults
+    }
+
+    pub fn set_defaults(&mut self, defaults: Limits) {
+        self.defaults = defaults;
+    }
+
+    /// Usage counted against `key` so far
+    pub fn usage(&self, key: &LimitKey) -> Option<&Usage> {
+        self.usage.get(key)
+    }
+
+    /// Forget a client's usage, e.g. when its connection closes. Quotas of
+    /// a subject are kept for the lifetime of the store unless reset here.
+    pub fn reset(&mut self, key: &LimitKey) {
+        self.usage.remove(key);
+    }
+
+    /// Take a token for one command. Returns false if the client is over its rate.
+    pub fn try_command(&mut self, key: LimitKey, limits: &Limits, now: Instant) -> bool {
+        let Some(rate) = limits.commands_per_second else {
+            return true;
+        };
+        let capacity = limits.burst.map(f64::from).unwrap_or(rate).max(1.0);
+
+        let usage = self.usage.entry(key).or_default();
+        let bucket = usage.bucket.get_or_insert_with(|| TokenBucket::new(rate, capacity, now));
+        bucket.configure(rate, capacity);
+        bucket.try_take(now)
+    }
+
+    /// Count a created entity. Returns false, without counting it, if the
+    /// client has reached its quota.
+    pub fn try_create_entity(&mut self, key: LimitKey, limits: &Limits) -> bool {
+        let usage = self.usage.entry(key).or_default();
+        if limits.max_entities.is_some_and(|max| usage.entities_created >= max) {
+            return false;
+        }
+        usage.entities_created += 1;
+        true
+    }
+
+    /// Count a registered notification. Returns false, without counting it,
+    /// if the client has reached its quota.
+    pub fn try_register_notification(&mut self, key: LimitKey, limits: &Limits) -> bool {
+        let usage = self.usage.entry(key).or_default();
+        if limits.max_notifications.is_some_and(|max| usage.notifications >= max) {
+            return false;
+        }
+        usage.notifications += 1;
+        true
+    }
+
+    /// Give back a notification registration
+    pub fn release_notification(&mut self, key: &LimitKey) {
+        if let Some(usage) = self.usage.get_mut(key) {
+            usage.notifications = usage.notifications.saturating_sub(1);
+        }
+    }
+}
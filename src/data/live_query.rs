@@ -0,0 +1,166 @@
+use std::collections::BTreeSet;
+
+use crate::{hash_notify_config, CelExecutor, EntityId, EntityType, FieldType, Notification, NotifyConfig, Result, StoreTrait, Value};
+
+/// A change to the result set of a `LiveQuery`
+#[derive(Debug, Clone, PartialEq)]
+pub enum LiveQueryEvent {
+    /// The entity started matching
+    Added(EntityId),
+    /// The entity stopped matching or was deleted
+    Removed(EntityId),
+    /// A watched field of a matching entity changed
+    Updated {
+        entity_id: EntityId,
+        field_type: FieldType,
+        value: Option<Value>,
+    },
+}
+
+/// The result set of a find (entity type plus filter) kept up to date from
+/// notifications, e.g. to back a list view.
+///
+/// `notify_configs` lists the notifications to register: one per field the
+/// filter reads, plus the extra `fields` the view shows. Each notification
+/// passed to `apply` re-checks that one entity against the filter and
+/// reports whether it joined, left or changed within the result set.
+///
+/// Creating or deleting an entity doesn't send notifications, so entities
+/// that match from the moment they are created (or disappear without a
+/// write) are only picked up by `refresh`. The same goes for changes behind
+/// an indirection in the filter (e.g. `Parent->Name`); only the first field
+/// of such a path is watched.
+///
+/// ```rust,ignore
+/// let mut query = LiveQuery::new(&store, et_pump, Some("Running == true"), &[ft_power])?;
+/// let queue = NotificationQueue::new();
+/// for config in query.notify_configs() {
+///     store.register_notification(config, queue.clone())?;
+/// }
+/// while let Some(notification) = queue.pop() {
+///     if let Some(event) = query.apply(&store, &notification) { /* update the view */ }
+/// }
+/// ```
+#[derive(Debug)]
+pub struct LiveQuery {
+    entity_type: EntityType,
+    filter: Option<String>,
+    watched_fields: Vec<FieldType>,
+    entities: BTreeSet<EntityId>,
+    executor: CelExecutor,
+}
+
+impl LiveQuery {
+    /// Run the query for its initial result set
+    pub fn new(store: &impl StoreTrait, entity_type: EntityType, filter: Option<&str>, fields: &[FieldType]) -> Result<Self> {
+        let mut executor = CelExecutor::new();
+        let mut watched_fields = Vec::new();
+        if let Some(filter) = filter {
+            for path in executor.referenced_fields(filter, store)? {
+                if let Some(field_type) = path.first() {
+                    if !watched_fields.contains(field_type) {
+                        watched_fields.push(*field_type);
+                    }
+                }
+            }
+        }
+        for field_type in fields {
+            if !watched_fields.contains(field_type) {
+                watched_fields.push(*field_type);
+            }
+        }
+
+        let entities = store.find_entities(entity_type, filter)?.into_iter().collect();
+        Ok(Self {
+            entity_type,
+            filter: filter.map(|filter| filter.to_string()),
+            watched_fields,
+            entities,
+            executor,
+        })
+    }
+
+    /// Notifications to register for this query, all delivered to the same
+    /// queue or channel whose notifications are passed to `apply`
+    pub fn notify_configs(&self) -> Vec<NotifyConfig> {
+        self.watched_fields
+            .iter()
+            .map(|field_type| NotifyConfig::EntityType {
+                entity_type: self.entity_type,
+                field_type: *field_type,
+                trigger_on_change: true,
+                context: vec![],
+                filter: None,
+            })
+            .collect()
+    }
+
+    /// The matching entities, in id order
+    pub fn entities(&self) -> impl Iterator<Item = EntityId> + '_ {
+        self.entities.iter().copied()
+    }
+
+    pub fn contains(&self, entity_id: EntityId) -> bool {
+        self.entities.contains(&entity_id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+
+    /// Update the result set from one notification. Notifications from
+    /// other registrations sharing the queue are ignored.
+    pub fn apply(&mut self, store: &impl StoreTrait, notification: &Notification) -> Option<LiveQueryEvent> {
+        let is_ours = self.notify_configs().iter().any(|config| hash_notify_config(config) == notification.config_hash);
+        let field_type = notification.current.field_path.first().copied().filter(|_| is_ours)?;
+        let entity_id = notification.current.entity_id;
+
+        match (self.entities.contains(&entity_id), self.matches(store, entity_id)) {
+            (false, true) => {
+                self.entities.insert(entity_id);
+                Some(LiveQueryEvent::Added(entity_id))
+            }
+            (true, false) => {
+                self.entities.remove(&entity_id);
+                Some(LiveQueryEvent::Removed(entity_id))
+            }
+            (true, true) => Some(LiveQueryEvent::Updated {
+                entity_id,
+                field_type,
+                value: notification.current.value.clone(),
+            }),
+            (false, false) => None,
+        }
+    }
+
+    /// Run the query again and report the entities that joined or left the
+    /// result set since, e.g. because they were created or deleted
+    pub fn refresh(&mut self, store: &impl StoreTrait) -> Result<Vec<LiveQueryEvent>> {
+        let entities: BTreeSet<EntityId> = store.find_entities(self.entity_type, self.filter.as_deref())?.into_iter().collect();
+
+        let mut events: Vec<LiveQueryEvent> = self
+            .entities
+            .difference(&entities)
+            .map(|entity_id| LiveQueryEvent::Removed(*entity_id))
+            .collect();
+        events.extend(entities.difference(&self.entities).map(|entity_id| LiveQueryEvent::Added(*entity_id)));
+
+        self.entities = entities;
+        Ok(events)
+    }
+
+    fn matches(&mut self, store: &impl StoreTrait, entity_id: EntityId) -> bool {
+        if !store.entity_exists(entity_id) {
+            return false;
+        }
+        let Some(filter) = &self.filter else {
+            return true;
+        };
+        // A filter that fails on this entity doesn't match it, as in `find_entities`
+        matches!(self.executor.execute(filter, entity_id, store), Ok(cel::Value::Bool(true)))
+    }
+}
@@ -1,45 +1,108 @@
+pub mod aggregate;
+pub mod audit;
+#[cfg(feature = "bootstrap")]
+pub mod bootstrap;
+pub mod buffer_pool;
+mod checksum;
+pub mod codec;
+pub mod deadline;
+pub mod drain;
+pub mod durable;
 pub mod et;
+mod decimal;
 mod entity_id;
+mod entity_view;
+mod federated;
+#[cfg(feature = "graphql")]
+pub mod graphql;
 pub mod entity_schema;
 mod field_schema;
 mod field;
 pub mod ft;
+mod group_commit;
 pub mod interner;
+pub mod limits;
 mod indirection;
 mod json_snapshot;
+mod live_query;
 mod notifications;
 mod pagination;
 pub mod resp;
+pub mod retry;
+#[cfg(feature = "s3")]
+pub mod s3;
+#[cfg(feature = "search")]
+pub mod search;
+pub mod slowlog;
+#[cfg(feature = "sim")]
+pub mod sim;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+pub mod time;
+mod series;
 mod snapshots;
+pub mod snapshot_sink;
+mod stats;
 mod store_proxy;
+mod cached_store_proxy;
 mod async_store_proxy;
+mod async_store_trait;
 mod store;
+mod shared_store;
 mod store_trait;
 mod value;
 mod cache;
 mod utils;
 pub mod pipeline;
+pub mod replication;
+pub mod triggers;
+pub mod type_registry;
 
-pub use entity_id::EntityId;
+pub use decimal::{Decimal, MAX_DECIMAL_SCALE};
+pub use entity_id::{EntityId, IdAllocator, MAX_NODE_ID, NODE_BITS};
+pub use entity_view::{EntityView, entity_to_value, entity_from_value};
 pub use entity_schema::{EntitySchema, Single, Complete};
 pub use field::Field;
-pub use field_schema::{FieldSchema, StorageScope};
+pub use field_schema::{FieldSchema, FieldMetadata, StorageScope, MergePolicy, OnDelete, SampleType, WriteScope};
 use serde::{Deserialize, Serialize};
 use smallvec::SmallVec;
-pub use store::{Store};
-pub use store_trait::{StoreTrait};
-pub use indirection::{BadIndirectionReason, INDIRECTION_DELIMITER, path, path_to_entity_id};
-pub use pagination::{PageOpts, PageResult};
+pub use store::{ReadSnapshot, Store};
+pub use shared_store::SharedStore;
+pub use store_trait::{DanglingReference, StoreTrait, TypesBulk};
+pub use async_store_trait::{AsyncStoreTrait, AsyncStoreAdapter};
+pub use indirection::{BadIndirectionReason, INDIRECTION_DELIMITER, path, path_to_entity_id, path_to_field_path, parse_field_path};
+pub use pagination::{PageOpts, PageResult, SortDirection};
 pub use snapshots::Snapshot;
-pub use json_snapshot::{JsonSnapshot, JsonEntitySchema, JsonEntity, value_to_json_value, json_value_to_value, value_to_json_value_with_paths, build_json_entity_tree, take_json_snapshot, restore_json_snapshot, restore_entity_recursive, factory_restore_json_snapshot, restore_json_snapshot_via_proxy};
+pub use snapshot_sink::{download_snapshot, list_snapshots, restore_from_sink, upload_snapshot, DirectorySink, SnapshotSink, StoredSnapshot};
+pub use stats::StoreStats;
+pub use checksum::{SnapshotChecksum, verify_snapshot, verify_json_snapshot};
+pub use json_snapshot::{JsonSnapshot, JsonEntitySchema, JsonEntity, value_to_json_value, json_value_to_value, value_to_json_value_with_paths, build_json_entity_tree, export_subtree, import_subtree, ImportError, ImportReport, take_json_snapshot, take_json_schemas, restore_json_snapshot, restore_entity_recursive, factory_restore_json_snapshot, restore_json_snapshot_via_proxy};
 pub use cache::Cache;
+pub use live_query::{LiveQuery, LiveQueryEvent};
 
 pub use store_proxy::StoreProxy;
-pub use async_store_proxy::AsyncStoreProxy;
+pub use cached_store_proxy::{CachedStoreProxy, CacheStats};
+pub use federated::FederatedStore;
+pub use async_store_proxy::{AsyncStoreProxy, NotificationStream, DEFAULT_BLOB_CHUNK_SIZE, DEFAULT_NOTIFICATION_STREAM_CAPACITY, SHUTDOWN_TIMEOUT, TIMEOUT_GRACE};
+pub use resp::{ProtocolLimits, MAX_MESSAGE_SIZE, MAX_NESTING_DEPTH};
+pub use codec::Codec;
 pub use value::Value;
-pub use notifications::{NotifyConfig, Notification, NotificationQueue, NotifyInfo, hash_notify_config};
-pub use interner::Interner;
+pub use notifications::{NotifyConfig, Notification, NotificationQueue, OverflowPolicy, NotifyInfo, hash_notify_config};
+pub use interner::{Interner, TypeIdMapping};
 pub use pipeline::{Pipeline, AsyncPipeline, PipelineResults, FromDecodedResponse};
+pub use replication::{PeerReplicator, PeerInfo, REPLICATION_WRITE_READER};
+pub use durable::{DurableSubscriptions, DEFAULT_DURABLE_CAPACITY};
+pub use time::{HlcTimestamp, HybridClock, DEFAULT_MAX_CLOCK_SKEW};
+pub use triggers::{Trigger, TriggerAction, TriggerId};
+pub use type_registry::{TypeRegistry, FieldTypes};
+pub use aggregate::{AggregateOp, Aggregator};
+pub use audit::{AuditLog, AuditQuery, AuditRecord, AuditSink, DEFAULT_AUDIT_CAPACITY};
+pub use deadline::{CancelRegistry, CancelToken, Deadline};
+pub use retry::RetryPolicy;
+pub use drain::{Drain, DrainGuard};
+pub use group_commit::{GroupCommit, GROUP_COMMIT_WRITE_READER};
+pub use slowlog::{SlowLog, SlowLogAction, SlowLogEntry, DEFAULT_SLOWLOG_CAPACITY, DEFAULT_SLOWLOG_THRESHOLD_MICROS};
+pub use limits::{LimitEnforcer, LimitKey, Limits, TokenBucket, Usage};
 
 pub use utils::{from_base64, to_base64};
 
@@ -51,30 +114,32 @@ pub struct FieldType(pub u64);
 
 pub type IndirectFieldType = SmallVec<[FieldType; 4]>;
 
-pub type Timestamp = time::OffsetDateTime;
+pub type Timestamp = ::time::OffsetDateTime;
+
+pub type Duration = ::time::Duration;
 
 pub fn now() -> Timestamp {
-    time::OffsetDateTime::now_utc()
+    ::time::OffsetDateTime::now_utc()
 }
 
 pub fn epoch() -> Timestamp {
-    time::OffsetDateTime::UNIX_EPOCH
+    ::time::OffsetDateTime::UNIX_EPOCH
 }
 
 pub fn nanos_to_timestamp(nanos: u64) -> Timestamp {
-    epoch() + time::Duration::nanoseconds(nanos as i64)
+    epoch() + ::time::Duration::nanoseconds(nanos as i64)
 }
 
 pub fn secs_to_timestamp(secs: u64) -> Timestamp {
-    epoch() + time::Duration::seconds(secs as i64)
+    epoch() + ::time::Duration::seconds(secs as i64)
 }
 
 pub fn millis_to_timestamp(millis: u64) -> Timestamp {
-    epoch() + time::Duration::milliseconds(millis as i64)
+    epoch() + ::time::Duration::milliseconds(millis as i64)
 }
 
 pub fn micros_to_timestamp(micros: u64) -> Timestamp {
-    epoch() + time::Duration::microseconds(micros as i64)
+    epoch() + ::time::Duration::microseconds(micros as i64)
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -99,6 +164,22 @@ impl std::fmt::Display for AdjustBehavior {
     }
 }
 
+/// Who is making the requests the store is about to handle.
+///
+/// The store has no notion of connections, so whoever dispatches commands
+/// sets this before each one with `Store::set_client_context`. It is used to
+/// audit writes and to enforce field `WriteScope`s.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ClientContext {
+    /// Authenticated subject, if the connection has logged in
+    pub subject: Option<EntityId>,
+    /// Free-form connection description, e.g. the peer address
+    pub connection: Option<String>,
+    /// Whether the subject has administrative rights
+    #[serde(default)]
+    pub admin: bool,
+}
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum WriteInfo {
     FieldUpdate {
@@ -109,6 +190,17 @@ pub enum WriteInfo {
         adjust_behavior: AdjustBehavior,
         write_time: Option<Timestamp>,
         writer_id: Option<EntityId>,
+        /// Operand of an Add/Subtract write, so peers can replay the
+        /// adjustment instead of overwriting with the resulting value
+        #[serde(default)]
+        delta: Option<Value>,
+        /// Trigger whose action performed this write, if any
+        #[serde(default)]
+        triggered_by: Option<TriggerId>,
+        /// Hybrid clock reading the write was made at, if the writing store
+        /// has one (`Store::enable_hlc`). `write_time` is its physical part.
+        #[serde(default)]
+        hlc: Option<HlcTimestamp>,
     },
     CreateEntity {
         entity_type: EntityType,
@@ -129,4 +221,13 @@ pub enum WriteInfo {
         snapshot_counter: u64,
         timestamp: Timestamp,
     },
+    /// An entity (and its subtree) was soft deleted, see `Store::soft_delete_entity`
+    SoftDeleteEntity {
+        entity_id: EntityId,
+        timestamp: Timestamp,
+    },
+    RestoreEntity {
+        entity_id: EntityId,
+        timestamp: Timestamp,
+    },
 }
\ No newline at end of file
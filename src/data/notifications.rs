@@ -1,12 +1,12 @@
-use std::cell::RefCell;
 use std::collections::VecDeque;
 use std::collections::{BTreeMap, hash_map::DefaultHasher};
 use std::hash::{Hash, Hasher};
-use std::rc::Rc;
+use std::sync::{Arc, Mutex, MutexGuard};
 use serde::{Deserialize, Serialize};
 use qlib_rs_derive::{RespDecode, RespEncode};
 
-use crate::{EntityId, EntityType, FieldType, IndirectFieldType, Value, Timestamp};
+use crate::{parse_field_path, EntityId, EntityType, FieldType, IndirectFieldType, StoreTrait, Value, Timestamp};
+use crate::data::resp::{OwnedRespValue, RespValue};
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Hash, RespEncode, RespDecode)]
 pub enum NotifyConfig {
@@ -15,16 +15,39 @@ pub enum NotifyConfig {
         field_type: FieldType,
         trigger_on_change: bool, // Notification will always trigger on write, but can be configured to trigger on change instead
         context: Vec<Vec<FieldType>>, // Context fields to include in the notification (these fields are relative to the entity with indirection support)
+        #[serde(default)]
+        #[resp(default)]
+        filter: Option<String>, // CEL expression the write must pass to be notified, see `NotifyConfig::filter`
     },
     EntityType {
         entity_type: EntityType,
         field_type: FieldType,
         trigger_on_change: bool, // Notification will always trigger on write, but can be configured to trigger on change instead
         context: Vec<Vec<FieldType>>, // Context fields to include in the notification (these fields are relative to the entity with indirection support)
+        #[serde(default)]
+        #[resp(default)]
+        filter: Option<String>, // CEL expression the write must pass to be notified, see `NotifyConfig::filter`
     },
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl NotifyConfig {
+    /// CEL expression a write must pass before it is notified.
+    ///
+    /// The store evaluates it after the write, relative to the written
+    /// entity, so field names (and indirection such as `Parent->Name`) read
+    /// the state the write produced. `new` and `old` are bound to the
+    /// field's new and previous value, e.g. `new > 80.0 && old <= 80.0` only
+    /// notifies when a temperature crosses 80. It is checked after
+    /// `trigger_on_change`. Nothing is delivered if the expression fails or
+    /// isn't true.
+    pub fn filter(&self) -> Option<&str> {
+        match self {
+            NotifyConfig::EntityId { filter, .. } | NotifyConfig::EntityType { filter, .. } => filter.as_deref(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, RespEncode, RespDecode)]
 pub struct NotifyInfo {
     pub entity_id: EntityId,
     pub field_path: IndirectFieldType,
@@ -33,6 +56,16 @@ pub struct NotifyInfo {
     pub writer_id: Option<EntityId>,
 }
 
+/// A change to a field that a `NotifyConfig` was registered for.
+///
+/// `context` has an entry for every path in the config's `context`, read
+/// from the notified entity right after the write was applied, so handlers
+/// see the same state the write produced without reading it again. Each
+/// entry carries the value, write time and writer the path resolved to.
+/// Paths that couldn't be read (e.g. an indirection through an empty
+/// reference) are still present, with `value`, `timestamp` and `writer_id`
+/// set to None. Entries are ordered by path, not by their order in the
+/// config; look them up with `context_value` or `context_info`.
 #[derive(Debug, Clone)]
 pub struct Notification {
     pub current: NotifyInfo,   // Current field value and metadata
@@ -41,21 +74,142 @@ pub struct Notification {
     pub config_hash: u64,  // Hash of the NotifyConfig that triggered this notification
 }
 
+impl Notification {
+    /// The context entry for a path from the config's `context`
+    pub fn context_info(&self, field_path: &[FieldType]) -> Option<&NotifyInfo> {
+        self.context.get(field_path)
+    }
+
+    /// The value a context path resolved to. None if the path wasn't in the
+    /// config or couldn't be read.
+    pub fn context_value(&self, field_path: &[FieldType]) -> Option<&Value> {
+        self.context_info(field_path).and_then(|info| info.value.as_ref())
+    }
+
+    /// Like `context_value`, with the path given by name, e.g. `Parent->Name`
+    pub fn context_value_by_name<T: StoreTrait + ?Sized>(&self, store: &T, field_path: &str) -> crate::Result<Option<&Value>> {
+        Ok(self.context_value(&parse_field_path(store, field_path)?))
+    }
+}
+
+/// What a bounded `NotificationQueue` does with a notification that arrives
+/// while it is full
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OverflowPolicy {
+    /// Make room by dropping the oldest pending notification
+    #[default]
+    DropOldest,
+    /// Drop the notification that just arrived
+    DropNewest,
+    /// Drop everything pending and stop delivering. The store removes the
+    /// registration, and `is_disconnected()` tells the consumer it fell behind.
+    Disconnect,
+}
+
+#[derive(Debug, Default)]
+struct QueueState {
+    pending: VecDeque<Notification>,
+    capacity: Option<usize>,
+    policy: OverflowPolicy,
+    dropped: u64,
+    disconnected: bool,
+}
+
 /// Notification sender type for sending notifications to a specific channel
+///
+/// Queues made with `new()` grow without bound. A consumer that may stall
+/// should use `bounded()`, so a slow reader loses notifications (and can see
+/// how many with `dropped()`) instead of growing the store's memory.
 #[derive(Clone, Debug)]
-pub struct NotificationQueue(Rc<RefCell<VecDeque<Notification>>>);
+pub struct NotificationQueue(Arc<Mutex<QueueState>>);
 
 impl NotificationQueue {
     pub fn new() -> Self {
-        NotificationQueue(Rc::new(RefCell::new(VecDeque::new())))
+        NotificationQueue(Arc::new(Mutex::new(QueueState::default())))
+    }
+
+    /// A queue holding at most `capacity` pending notifications
+    pub fn bounded(capacity: usize, policy: OverflowPolicy) -> Self {
+        NotificationQueue(Arc::new(Mutex::new(QueueState {
+            capacity: Some(capacity),
+            policy,
+            ..Default::default()
+        })))
     }
 
-    pub fn push(&self, notification: Notification) {
-        self.0.borrow_mut().push_back(notification);
+    /// Queue a notification, applying the overflow policy if the queue is
+    /// full. Returns false if the notification was not queued.
+    pub fn push(&self, notification: Notification) -> bool {
+        let mut state = self.state();
+        if state.disconnected {
+            state.dropped += 1;
+            return false;
+        }
+
+        if state.capacity.is_some_and(|capacity| state.pending.len() >= capacity) {
+            match state.policy {
+                OverflowPolicy::DropOldest => {
+                    if state.pending.pop_front().is_none() {
+                        // Zero capacity: nothing can ever be queued
+                        state.dropped += 1;
+                        return false;
+                    }
+                    state.dropped += 1;
+                }
+                OverflowPolicy::DropNewest => {
+                    state.dropped += 1;
+                    return false;
+                }
+                OverflowPolicy::Disconnect => {
+                    state.dropped += state.pending.len() as u64 + 1;
+                    state.pending.clear();
+                    state.disconnected = true;
+                    return false;
+                }
+            }
+        }
+
+        state.pending.push_back(notification);
+        true
     }
 
     pub fn pop(&self) -> Option<Notification> {
-        self.0.borrow_mut().pop_front()
+        self.state().pending.pop_front()
+    }
+
+    /// Number of notifications waiting to be read. A lag that keeps growing
+    /// means the consumer can't keep up.
+    pub fn lag(&self) -> usize {
+        self.state().pending.len()
+    }
+
+    /// Notifications lost to the overflow policy so far
+    pub fn dropped(&self) -> u64 {
+        self.state().dropped
+    }
+
+    /// Check if the queue overflowed under `OverflowPolicy::Disconnect`
+    pub fn is_disconnected(&self) -> bool {
+        self.state().disconnected
+    }
+
+    /// The most notifications the queue holds, if it is bounded
+    pub fn capacity(&self) -> Option<usize> {
+        self.state().capacity
+    }
+
+    pub fn policy(&self) -> OverflowPolicy {
+        self.state().policy
+    }
+
+    /// Check if both handles refer to the same queue
+    pub fn same_queue(&self, other: &NotificationQueue) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+
+    fn state(&self) -> MutexGuard<'_, QueueState> {
+        // A panic mid-push leaves the queue consistent, so a poisoned lock is fine to reuse
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
     }
 }
 
@@ -134,4 +288,64 @@ impl<'de> Deserialize<'de> for Notification {
             config_hash: helper.config_hash,
         })
     }
+}
+
+// Notifications go over RESP as `[current, previous, [[path, info], ...], config_hash]`
+impl crate::data::resp::RespEncode for Notification {
+    fn encode(&self) -> OwnedRespValue {
+        let context = self
+            .context
+            .iter()
+            .map(|(path, info)| OwnedRespValue::Array(vec![path.encode(), info.encode()]))
+            .collect();
+        OwnedRespValue::Array(vec![
+            self.current.encode(),
+            self.previous.encode(),
+            OwnedRespValue::Array(context),
+            // The hash uses all 64 bits, so it is carried as a signed integer
+            OwnedRespValue::Integer(self.config_hash as i64),
+        ])
+    }
+}
+
+impl<'a> crate::data::resp::RespDecode<'a> for Notification {
+    fn decode(input: RespValue<'a>) -> crate::Result<Self> {
+        let elements = match input {
+            RespValue::Array(elements) => elements,
+            // Older servers send the notification as JSON
+            RespValue::BulkString(data) => {
+                return serde_json::from_slice(data)
+                    .map_err(|e| crate::Error::InvalidRequest(format!("Invalid notification JSON: {}", e)));
+            }
+            _ => return Err(crate::Error::InvalidRequest("Expected array for Notification".to_string())),
+        };
+        let [current, previous, context, config_hash]: [RespValue<'a>; 4] = elements
+            .try_into()
+            .map_err(|_| crate::Error::InvalidRequest("Expected 4 elements for Notification".to_string()))?;
+
+        let RespValue::Array(pairs) = context else {
+            return Err(crate::Error::InvalidRequest("Expected array for notification context".to_string()));
+        };
+        let mut context_map = BTreeMap::new();
+        for pair in pairs {
+            let RespValue::Array(pair) = pair else {
+                return Err(crate::Error::InvalidRequest("Expected [path, info] pair in notification context".to_string()));
+            };
+            let [path, info]: [RespValue<'a>; 2] = pair
+                .try_into()
+                .map_err(|_| crate::Error::InvalidRequest("Expected [path, info] pair in notification context".to_string()))?;
+            context_map.insert(Vec::<FieldType>::decode(path)?, NotifyInfo::decode(info)?);
+        }
+
+        let RespValue::Integer(config_hash) = config_hash else {
+            return Err(crate::Error::InvalidRequest("Expected integer for notification config hash".to_string()));
+        };
+
+        Ok(Notification {
+            current: NotifyInfo::decode(current)?,
+            previous: NotifyInfo::decode(previous)?,
+            context: context_map,
+            config_hash: config_hash as u64,
+        })
+    }
 }
\ No newline at end of file
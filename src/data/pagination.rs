@@ -1,6 +1,49 @@
+use std::cmp::Ordering;
+
 use serde::{Deserialize, Serialize};
 use qlib_rs_derive::{RespDecode, RespEncode};
 
+use crate::{EntityId, FieldType, Value};
+
+/// Direction of a sorted page, see `PageOpts::order_by`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SortDirection {
+    Ascending,
+    Descending,
+}
+
+impl SortDirection {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SortDirection::Ascending => "Ascending",
+            SortDirection::Descending => "Descending",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "Ascending" => Some(SortDirection::Ascending),
+            "Descending" => Some(SortDirection::Descending),
+            _ => None,
+        }
+    }
+
+    /// Order two (value, entity id) sort keys. Equal values are ordered by
+    /// entity id so every entity has a distinct position.
+    pub fn compare(&self, a: &(Value, EntityId), b: &(Value, EntityId)) -> Ordering {
+        let ordering = match (&a.0, &b.0) {
+            (Value::Float(x), Value::Float(y)) => x.total_cmp(y),
+            (x, y) => x.partial_cmp(y).unwrap_or(Ordering::Equal),
+        }
+        .then(a.1.cmp(&b.1));
+
+        match self {
+            SortDirection::Ascending => ordering,
+            SortDirection::Descending => ordering.reverse(),
+        }
+    }
+}
+
 /// Pagination options for retrieving lists of items
 #[derive(Debug, Clone, Serialize, Deserialize, RespEncode, RespDecode)]
 pub struct PageOpts {
@@ -8,6 +51,17 @@ pub struct PageOpts {
     pub limit: usize,
     /// The starting point for pagination
     pub cursor: Option<usize>,
+    /// Sort entities by a field's value. Sorted pages continue from `after`
+    /// instead of `cursor`.
+    #[serde(default)]
+    #[resp(default)]
+    pub order_by: Option<(FieldType, SortDirection)>,
+    /// Sort key of the last entity already seen, from `PageResult::next_key`.
+    /// Unlike an offset it doesn't shift when entities are created or
+    /// deleted between pages.
+    #[serde(default)]
+    #[resp(default)]
+    pub after: Option<(Value, EntityId)>,
 }
 
 impl Default for PageOpts {
@@ -15,13 +69,20 @@ impl Default for PageOpts {
         PageOpts {
             limit: 100,
             cursor: None,
+            order_by: None,
+            after: None,
         }
     }
 }
 
 impl PageOpts {
     pub fn new(limit: usize, cursor: Option<usize>) -> Self {
-        PageOpts { limit, cursor }
+        PageOpts { limit, cursor, ..Default::default() }
+    }
+
+    pub fn with_order_by(mut self, field_type: FieldType, direction: SortDirection) -> Self {
+        self.order_by = Some((field_type, direction));
+        self
     }
 }
 
@@ -34,6 +95,9 @@ pub struct PageResult<T> {
     pub total: usize,
     /// Cursor for retrieving the next page, if available
     pub next_cursor: Option<usize>,
+    /// Sort key to continue a sorted query from, if there are more items
+    #[serde(default)]
+    pub next_key: Option<(Value, EntityId)>,
 }
 
 impl<T> PageResult<T> {
@@ -42,6 +106,24 @@ impl<T> PageResult<T> {
             items,
             total,
             next_cursor,
+            next_key: None,
+        }
+    }
+
+    pub fn with_next_key(mut self, next_key: Option<(Value, EntityId)>) -> Self {
+        self.next_key = next_key;
+        self
+    }
+
+    /// Options for the page after this one, or None if this was the last.
+    /// `opts` are the options this page was fetched with.
+    pub fn next_page(&self, opts: &PageOpts) -> Option<PageOpts> {
+        let mut next = opts.clone();
+        if opts.order_by.is_some() {
+            next.after = Some(self.next_key.clone()?);
+        } else {
+            next.cursor = Some(self.next_cursor?);
         }
+        Some(next)
     }
 }
@@ -50,18 +50,45 @@
 //! let write_ok: () = results.get(1)?;
 //! let entity_id: EntityId = results.get(2)?;
 //! ```
+//! 
+//! ## Atomic Execution
+//! 
+//! `execute_atomic()` sends the batch wrapped in `MULTI`/`EXEC`, so the server
+//! applies every queued command or none of them:
+//! 
+//! ```rust,ignore
+//! let mut pipeline = proxy.pipeline();
+//! pipeline.write(from_id, &[balance], Value::Int(90), None, None, None, None)?;
+//! pipeline.write(to_id, &[balance], Value::Int(110), None, None, None, None)?;
+//! let results = pipeline.execute_atomic()?;
+//! ```
 
 use crate::{
-    EntityId, EntitySchema, EntityType, Error, FieldSchema, FieldType, PageResult, Result, Single, Value, Timestamp, PushCondition, AdjustBehavior
+    EntityId, EntitySchema, EntityType, Error, FieldSchema, FieldType, PageOpts, PageResult, Result, Single, Complete, Value, Timestamp, PushCondition, AdjustBehavior,
+    NotifyConfig, Notification, NotificationQueue, TypesBulk, AuditQuery, AuditRecord,
 };
+use crossbeam::channel::Sender;
 use std::time::Duration;
+use crate::data::codec::Codec;
+use crate::data::buffer_pool::{take_buffer, PooledBuffer};
+use crate::data::entity_schema::{EntitySchemaResp, FieldSchemaResp};
 use crate::data::resp::{
-    RespCommand, RespDecode, RespValue, RespToBytes, RespFromBytes,
-    ReadCommand, WriteCommand, CreateEntityCommand, DeleteEntityCommand,
+    error_from_frame, with_binary_ids, ProtocolLimits,
+    RespCommand, RespEncode, RespDecode, RespValue, RespToBytes, RespFromBytes,
+    ReadCommand, WriteCommand, FencedWriteCommand, CreateEntityCommand, CreateEntityFullCommand, InstantiateTemplateCommand, DeleteEntityCommand,
     GetEntityTypeCommand, ResolveEntityTypeCommand, GetFieldTypeCommand, ResolveFieldTypeCommand,
-    EntityExistsCommand, FieldExistsCommand,
-    FindEntitiesCommand, GetEntityTypesCommand,
-    NotificationCommand,
+    GetTypesBulkCommand,
+    GetEntitySchemaCommand, GetCompleteEntitySchemaCommand, UpdateSchemaCommand,
+    GetFieldSchemaCommand, SetFieldSchemaCommand,
+    EntityExistsCommand, FieldExistsCommand, ResolveIndirectionCommand,
+    ResolvePathCommand, ReadPathCommand, WritePathCommand,
+    ReadBlobRangeCommand, WriteBlobAppendCommand,
+    FindEntitiesPaginatedCommand, FindEntitiesExactCommand, FindEntitiesCommand,
+    GetEntityTypesCommand, GetEntityTypesPaginatedCommand,
+    TakeSnapshotCommand, MachineInfoCommand, AuditQueryCommand,
+    RegisterNotificationCommand, UnregisterNotificationCommand,
+    MultiCommand, ExecCommand,
+    PushCommand,
 };
 
 /// A queued command in the pipeline
@@ -73,29 +100,40 @@ struct QueuedCommand {
 
 /// Type information for decoding responses
 #[derive(Debug, Clone)]
-#[allow(dead_code)]
 enum ResponseType {
     Read,
     Write,
+    FencedWrite,
     CreateEntity,
     DeleteEntity,
     GetEntityType,
     ResolveEntityType,
     GetFieldType,
     ResolveFieldType,
+    GetTypesBulk,
     GetEntitySchema,
+    GetCompleteEntitySchema,
     UpdateSchema,
     GetFieldSchema,
     SetFieldSchema,
     EntityExists,
     FieldExists,
     ResolveIndirection,
+    ResolvePath,
+    ReadPath,
+    WritePath,
+    ReadBlobRange,
+    WriteBlobAppend,
     FindEntitiesPaginated,
     FindEntitiesExact,
     FindEntities,
     GetEntityTypes,
     GetEntityTypesPaginated,
     TakeSnapshot,
+    MachineInfo,
+    AuditQuery,
+    RegisterNotification,
+    UnregisterNotification,
 }
 
 /// Results from pipeline execution
@@ -109,25 +147,37 @@ pub struct PipelineResults {
 pub enum DecodedResponse {
     Read((Value, Timestamp, Option<EntityId>)),
     Write(()),
+    FencedWrite(()),
     CreateEntity(EntityId),
     DeleteEntity(()),
     GetEntityType(EntityType),
     ResolveEntityType(String),
     GetFieldType(FieldType),
     ResolveFieldType(String),
+    GetTypesBulk(TypesBulk),
     GetEntitySchema(EntitySchema<Single>),
+    GetCompleteEntitySchema(EntitySchema<Complete>),
     UpdateSchema(()),
     GetFieldSchema(FieldSchema<String>),  // Keep as string for sync version
     SetFieldSchema(()),
     EntityExists(bool),
     FieldExists(bool),
     ResolveIndirection((EntityId, FieldType)),
+    ResolvePath(EntityId),
+    ReadPath((Value, Timestamp, Option<EntityId>)),
+    WritePath(()),
+    ReadBlobRange((Vec<u8>, usize)),
+    WriteBlobAppend(usize),
     FindEntitiesPaginated(PageResult<EntityId>),
     FindEntitiesExact(PageResult<EntityId>),
     FindEntities(Vec<EntityId>),
     GetEntityTypes(Vec<EntityType>),
     GetEntityTypesPaginated(PageResult<EntityType>),
     TakeSnapshot(String),  // JSON string
+    MachineInfo(String),
+    AuditQuery(Vec<AuditRecord>),
+    RegisterNotification(()),
+    UnregisterNotification(bool),
 }
 
 impl PipelineResults {
@@ -163,7 +213,7 @@ pub trait FromDecodedResponse: Sized {
 impl FromDecodedResponse for (Value, Timestamp, Option<EntityId>) {
     fn from_decoded(response: &DecodedResponse) -> Result<Self> {
         match response {
-            DecodedResponse::Read(val) => Ok(val.clone()),
+            DecodedResponse::Read(val) | DecodedResponse::ReadPath(val) => Ok(val.clone()),
             _ => Err(Error::StoreProxyError("Type mismatch: expected Read response".to_string())),
         }
     }
@@ -172,8 +222,9 @@ impl FromDecodedResponse for (Value, Timestamp, Option<EntityId>) {
 impl FromDecodedResponse for () {
     fn from_decoded(response: &DecodedResponse) -> Result<Self> {
         match response {
-            DecodedResponse::Write(()) | DecodedResponse::DeleteEntity(()) | 
-            DecodedResponse::UpdateSchema(()) | DecodedResponse::SetFieldSchema(()) => Ok(()),
+            DecodedResponse::Write(()) | DecodedResponse::FencedWrite(()) | DecodedResponse::WritePath(()) |
+            DecodedResponse::DeleteEntity(()) | DecodedResponse::UpdateSchema(()) | DecodedResponse::SetFieldSchema(()) |
+            DecodedResponse::RegisterNotification(()) => Ok(()),
             _ => Err(Error::StoreProxyError("Type mismatch: expected OK response".to_string())),
         }
     }
@@ -182,8 +233,8 @@ impl FromDecodedResponse for () {
 impl FromDecodedResponse for EntityId {
     fn from_decoded(response: &DecodedResponse) -> Result<Self> {
         match response {
-            DecodedResponse::CreateEntity(id) => Ok(*id),
-            _ => Err(Error::StoreProxyError("Type mismatch: expected CreateEntity response".to_string())),
+            DecodedResponse::CreateEntity(id) | DecodedResponse::ResolvePath(id) => Ok(*id),
+            _ => Err(Error::StoreProxyError("Type mismatch: expected CreateEntity or ResolvePath response".to_string())),
         }
     }
 }
@@ -197,8 +248,6 @@ impl FromDecodedResponse for EntityType {
     }
 }
 
-// Removed - conflicting with new implementation below
-
 impl FromDecodedResponse for FieldType {
     fn from_decoded(response: &DecodedResponse) -> Result<Self> {
         match response {
@@ -208,6 +257,24 @@ impl FromDecodedResponse for FieldType {
     }
 }
 
+impl FromDecodedResponse for TypesBulk {
+    fn from_decoded(response: &DecodedResponse) -> Result<Self> {
+        match response {
+            DecodedResponse::GetTypesBulk(types) => Ok(types.clone()),
+            _ => Err(Error::StoreProxyError("Type mismatch: expected GetTypesBulk response".to_string())),
+        }
+    }
+}
+
+impl FromDecodedResponse for Vec<AuditRecord> {
+    fn from_decoded(response: &DecodedResponse) -> Result<Self> {
+        match response {
+            DecodedResponse::AuditQuery(records) => Ok(records.clone()),
+            _ => Err(Error::StoreProxyError("Type mismatch: expected AuditQuery response".to_string())),
+        }
+    }
+}
+
 impl FromDecodedResponse for EntitySchema<Single> {
     fn from_decoded(response: &DecodedResponse) -> Result<Self> {
         match response {
@@ -217,6 +284,15 @@ impl FromDecodedResponse for EntitySchema<Single> {
     }
 }
 
+impl FromDecodedResponse for EntitySchema<Complete> {
+    fn from_decoded(response: &DecodedResponse) -> Result<Self> {
+        match response {
+            DecodedResponse::GetCompleteEntitySchema(schema) => Ok(schema.clone()),
+            _ => Err(Error::StoreProxyError("Type mismatch: expected GetCompleteEntitySchema response".to_string())),
+        }
+    }
+}
+
 // For sync pipeline, FieldSchema is returned as string-based
 impl FromDecodedResponse for FieldSchema<String> {
     fn from_decoded(response: &DecodedResponse) -> Result<Self> {
@@ -242,7 +318,8 @@ impl FromDecodedResponse for FieldSchema {
 impl FromDecodedResponse for bool {
     fn from_decoded(response: &DecodedResponse) -> Result<Self> {
         match response {
-            DecodedResponse::EntityExists(b) | DecodedResponse::FieldExists(b) => Ok(*b),
+            DecodedResponse::EntityExists(b) | DecodedResponse::FieldExists(b) |
+            DecodedResponse::UnregisterNotification(b) => Ok(*b),
             _ => Err(Error::StoreProxyError("Type mismatch: expected bool response".to_string())),
         }
     }
@@ -257,6 +334,24 @@ impl FromDecodedResponse for (EntityId, FieldType) {
     }
 }
 
+impl FromDecodedResponse for (Vec<u8>, usize) {
+    fn from_decoded(response: &DecodedResponse) -> Result<Self> {
+        match response {
+            DecodedResponse::ReadBlobRange(val) => Ok(val.clone()),
+            _ => Err(Error::StoreProxyError("Type mismatch: expected ReadBlobRange response".to_string())),
+        }
+    }
+}
+
+impl FromDecodedResponse for usize {
+    fn from_decoded(response: &DecodedResponse) -> Result<Self> {
+        match response {
+            DecodedResponse::WriteBlobAppend(len) => Ok(*len),
+            _ => Err(Error::StoreProxyError("Type mismatch: expected WriteBlobAppend response".to_string())),
+        }
+    }
+}
+
 impl FromDecodedResponse for Vec<EntityId> {
     fn from_decoded(response: &DecodedResponse) -> Result<Self> {
         match response {
@@ -297,7 +392,8 @@ impl FromDecodedResponse for PageResult<EntityType> {
 impl FromDecodedResponse for String {
     fn from_decoded(response: &DecodedResponse) -> Result<Self> {
         match response {
-            DecodedResponse::ResolveEntityType(s) | DecodedResponse::ResolveFieldType(s) | DecodedResponse::TakeSnapshot(s) => Ok(s.clone()),
+            DecodedResponse::ResolveEntityType(s) | DecodedResponse::ResolveFieldType(s) |
+            DecodedResponse::TakeSnapshot(s) | DecodedResponse::MachineInfo(s) => Ok(s.clone()),
             _ => Err(Error::StoreProxyError("Type mismatch: expected String response".to_string())),
         }
     }
@@ -315,10 +411,304 @@ impl FromDecodedResponse for crate::data::Snapshot {
     }
 }
 
+/// A reply decoded while the connection is held. Entity schemas need type
+/// lookups over that same connection, so they are converted once it is released
+enum Received {
+    Ready(DecodedResponse),
+    Schema { schema: EntitySchemaResp, complete: bool },
+}
+
+/// A frame the receive loop waits for, in the order the server sends them
+enum ExpectedFrame<'r> {
+    /// The reply to a queued command
+    Reply(&'r ResponseType),
+    /// A bare status such as `+OK` or `+QUEUED` that carries no result
+    Status(&'static str),
+    /// The `EXEC` reply, holding one element per queued command
+    Exec(Vec<&'r ResponseType>),
+}
+
+/// Encode the commands to send along with the frames expected back.
+/// Atomic batches are wrapped in `MULTI`/`EXEC`.
+fn prepare_batch<'r>(commands: &[&'r QueuedCommand], atomic: bool, limits: &ProtocolLimits) -> Result<(PooledBuffer, Vec<ExpectedFrame<'r>>)> {
+    let mut all_bytes = take_buffer();
+    let mut expected = Vec::new();
+
+    if atomic {
+        let multi = MultiCommand { _marker: std::marker::PhantomData };
+        multi.encode_into(&mut all_bytes);
+        expected.push(ExpectedFrame::Status("OK"));
+    }
+
+    for cmd in commands {
+        limits.check_frame(cmd.encoded_bytes.len())?;
+        all_bytes.extend_from_slice(&cmd.encoded_bytes);
+        expected.push(if atomic {
+            ExpectedFrame::Status("QUEUED")
+        } else {
+            ExpectedFrame::Reply(&cmd.response_type)
+        });
+    }
+
+    if atomic {
+        let exec = ExecCommand { _marker: std::marker::PhantomData };
+        exec.encode_into(&mut all_bytes);
+        expected.push(ExpectedFrame::Exec(commands.iter().map(|cmd| &cmd.response_type).collect()));
+    }
+
+    Ok((all_bytes, expected))
+}
+
+/// Matches incoming frames against the expected ones. After a failure the
+/// remaining replies are still read off the connection so that it stays in
+/// step with the server; the first error is reported once all have arrived.
+struct ReplyCollector<'r> {
+    expected: Vec<ExpectedFrame<'r>>,
+    next: usize,
+    received: Vec<Received>,
+    error: Option<Error>,
+}
+
+impl<'r> ReplyCollector<'r> {
+    fn new(expected: Vec<ExpectedFrame<'r>>) -> Self {
+        Self {
+            expected,
+            next: 0,
+            received: Vec::new(),
+            error: None,
+        }
+    }
+
+    fn is_done(&self) -> bool {
+        self.next >= self.expected.len()
+    }
+
+    /// Take one complete frame. A frame that isn't the expected reply but
+    /// decodes as a notification is handed back to the caller.
+    fn accept<'v>(&mut self, resp_value: RespValue<'v>) -> Option<PushCommand<'v>> {
+        let result = match &resp_value {
+            RespValue::Error(error_msg) => Err(error_from_frame(error_msg)),
+            _ => match decode_frame(resp_value.clone(), &self.expected[self.next]) {
+                Ok(received) => Ok(received),
+                Err(e) => {
+                    if let Ok(push) = PushCommand::decode(resp_value) {
+                        return Some(push);
+                    }
+                    Err(e)
+                }
+            },
+        };
+
+        self.next += 1;
+        match result {
+            Ok(received) => self.received.extend(received),
+            Err(e) => {
+                self.error.get_or_insert(e);
+            }
+        }
+        None
+    }
+
+    fn finish(self) -> Result<Vec<Received>> {
+        match self.error {
+            Some(e) => Err(e),
+            None => Ok(self.received),
+        }
+    }
+}
+
+/// Decode one expected frame into the replies it carries
+fn decode_frame(resp_value: RespValue, expected: &ExpectedFrame) -> Result<Vec<Received>> {
+    match expected {
+        ExpectedFrame::Reply(response_type) => Ok(vec![decode_response(resp_value, response_type)?]),
+        ExpectedFrame::Status(status) => match resp_value {
+            RespValue::SimpleString(s) if s == *status => Ok(Vec::new()),
+            _ => Err(Error::StoreProxyError(format!("Expected {} response", status))),
+        },
+        ExpectedFrame::Exec(response_types) => match resp_value {
+            RespValue::Array(items) if items.len() == response_types.len() => items
+                .into_iter()
+                .zip(response_types)
+                .map(|(item, response_type)| decode_response(item, response_type))
+                .collect(),
+            RespValue::Null => Err(Error::StoreProxyError("Transaction was aborted by the server".to_string())),
+            _ => Err(Error::StoreProxyError("Expected one EXEC reply per queued command".to_string())),
+        },
+    }
+}
+
+/// Decode a response based on its type
+fn decode_response(resp_value: RespValue, response_type: &ResponseType) -> Result<Received> {
+    // Check if this is an error response from the server
+    if let RespValue::Error(error_msg) = &resp_value {
+        return Err(error_from_frame(error_msg));
+    }
+
+    let decoded = match response_type {
+        ResponseType::Read | ResponseType::ReadPath => {
+            let response = crate::data::resp::ReadResponse::decode(resp_value)
+                .map_err(|e| Error::StoreProxyError(format!("Failed to decode Read response: {}", e)))?;
+            let value = (response.value, response.timestamp, response.writer_id);
+            match response_type {
+                ResponseType::Read => DecodedResponse::Read(value),
+                _ => DecodedResponse::ReadPath(value),
+            }
+        }
+        ResponseType::Write | ResponseType::FencedWrite | ResponseType::WritePath | ResponseType::DeleteEntity |
+        ResponseType::UpdateSchema | ResponseType::SetFieldSchema |
+        ResponseType::RegisterNotification | ResponseType::UnregisterNotification => {
+            match resp_value {
+                RespValue::SimpleString(s) if s == "OK" => {
+                    match response_type {
+                        ResponseType::Write => DecodedResponse::Write(()),
+                        ResponseType::FencedWrite => DecodedResponse::FencedWrite(()),
+                        ResponseType::WritePath => DecodedResponse::WritePath(()),
+                        ResponseType::DeleteEntity => DecodedResponse::DeleteEntity(()),
+                        ResponseType::UpdateSchema => DecodedResponse::UpdateSchema(()),
+                        ResponseType::SetFieldSchema => DecodedResponse::SetFieldSchema(()),
+                        ResponseType::RegisterNotification => DecodedResponse::RegisterNotification(()),
+                        ResponseType::UnregisterNotification => DecodedResponse::UnregisterNotification(true),
+                        _ => unreachable!(),
+                    }
+                }
+                _ => return Err(Error::StoreProxyError("Expected OK response".to_string())),
+            }
+        }
+        ResponseType::CreateEntity => {
+            let response = crate::data::resp::CreateEntityResponse::decode(resp_value)
+                .map_err(|e| Error::StoreProxyError(format!("Failed to decode CreateEntity response: {}", e)))?;
+            DecodedResponse::CreateEntity(response.entity_id)
+        }
+        ResponseType::GetEntityType => {
+            let response = crate::data::resp::IntegerResponse::decode(resp_value)
+                .map_err(|e| Error::StoreProxyError(format!("Failed to decode GetEntityType response: {}", e)))?;
+            DecodedResponse::GetEntityType(EntityType(response.value as u32))
+        }
+        ResponseType::ResolveEntityType => {
+            let response = crate::data::resp::StringResponse::decode(resp_value)
+                .map_err(|e| Error::StoreProxyError(format!("Failed to decode ResolveEntityType response: {}", e)))?;
+            DecodedResponse::ResolveEntityType(response.value)
+        }
+        ResponseType::GetFieldType => {
+            let response = crate::data::resp::IntegerResponse::decode(resp_value)
+                .map_err(|e| Error::StoreProxyError(format!("Failed to decode GetFieldType response: {}", e)))?;
+            DecodedResponse::GetFieldType(FieldType(response.value as u64))
+        }
+        ResponseType::ResolveFieldType => {
+            let response = crate::data::resp::StringResponse::decode(resp_value)
+                .map_err(|e| Error::StoreProxyError(format!("Failed to decode ResolveFieldType response: {}", e)))?;
+            DecodedResponse::ResolveFieldType(response.value)
+        }
+        ResponseType::GetTypesBulk => {
+            let response = crate::data::resp::TypesBulkResponse::decode(resp_value)
+                .map_err(|e| Error::StoreProxyError(format!("Failed to decode GetTypesBulk response: {}", e)))?;
+            DecodedResponse::GetTypesBulk((response.entity_types, response.field_types))
+        }
+        ResponseType::GetEntitySchema | ResponseType::GetCompleteEntitySchema => {
+            let schema = EntitySchemaResp::decode(resp_value)
+                .map_err(|e| Error::StoreProxyError(format!("Failed to decode GetEntitySchema response: {}", e)))?;
+            return Ok(Received::Schema {
+                schema,
+                complete: matches!(response_type, ResponseType::GetCompleteEntitySchema),
+            });
+        }
+        ResponseType::GetFieldSchema => {
+            let response = crate::data::resp::FieldSchemaResponse::decode(resp_value)
+                .map_err(|e| Error::StoreProxyError(format!("Failed to decode GetFieldSchema response: {}", e)))?;
+            // Kept string-based; typed conversion would need lookups on the busy connection
+            DecodedResponse::GetFieldSchema(response.schema.to_field_schema())
+        }
+        ResponseType::EntityExists => {
+            let response = crate::data::resp::BooleanResponse::decode(resp_value)
+                .map_err(|e| Error::StoreProxyError(format!("Failed to decode EntityExists response: {}", e)))?;
+            DecodedResponse::EntityExists(response.result)
+        }
+        ResponseType::FieldExists => {
+            let response = crate::data::resp::BooleanResponse::decode(resp_value)
+                .map_err(|e| Error::StoreProxyError(format!("Failed to decode FieldExists response: {}", e)))?;
+            DecodedResponse::FieldExists(response.result)
+        }
+        ResponseType::ResolveIndirection => {
+            let response = crate::data::resp::ResolveIndirectionResponse::decode(resp_value)
+                .map_err(|e| Error::StoreProxyError(format!("Failed to decode ResolveIndirection response: {}", e)))?;
+            DecodedResponse::ResolveIndirection((response.entity_id, response.field_type))
+        }
+        ResponseType::ResolvePath => {
+            let response = crate::data::resp::ResolvePathResponse::decode(resp_value)
+                .map_err(|e| Error::StoreProxyError(format!("Failed to decode ResolvePath response: {}", e)))?;
+            DecodedResponse::ResolvePath(response.entity_id)
+        }
+        ResponseType::ReadBlobRange => {
+            let response = crate::data::resp::BlobRangeResponse::decode(resp_value)
+                .map_err(|e| Error::StoreProxyError(format!("Failed to decode ReadBlobRange response: {}", e)))?;
+            DecodedResponse::ReadBlobRange((response.data, response.total_len))
+        }
+        ResponseType::WriteBlobAppend => {
+            let response = crate::data::resp::IntegerResponse::decode(resp_value)
+                .map_err(|e| Error::StoreProxyError(format!("Failed to decode WriteBlobAppend response: {}", e)))?;
+            DecodedResponse::WriteBlobAppend(response.value as usize)
+        }
+        ResponseType::FindEntitiesPaginated | ResponseType::FindEntitiesExact => {
+            let response = crate::data::resp::PaginatedEntityResponse::decode(resp_value)
+                .map_err(|e| Error::StoreProxyError(format!("Failed to decode paginated entity response: {}", e)))?;
+            let page = PageResult {
+                items: response.items,
+                total: response.total,
+                next_cursor: response.next_cursor,
+                next_key: response.next_key,
+            };
+            match response_type {
+                ResponseType::FindEntitiesPaginated => DecodedResponse::FindEntitiesPaginated(page),
+                _ => DecodedResponse::FindEntitiesExact(page),
+            }
+        }
+        ResponseType::FindEntities => {
+            let response = crate::data::resp::EntityListResponse::decode(resp_value)
+                .map_err(|e| Error::StoreProxyError(format!("Failed to decode FindEntities response: {}", e)))?;
+            DecodedResponse::FindEntities(response.entities)
+        }
+        ResponseType::GetEntityTypes => {
+            let response = crate::data::resp::EntityTypeListResponse::decode(resp_value)
+                .map_err(|e| Error::StoreProxyError(format!("Failed to decode GetEntityTypes response: {}", e)))?;
+            DecodedResponse::GetEntityTypes(response.entity_types)
+        }
+        ResponseType::GetEntityTypesPaginated => {
+            let response = crate::data::resp::PaginatedEntityTypeResponse::decode(resp_value)
+                .map_err(|e| Error::StoreProxyError(format!("Failed to decode GetEntityTypesPaginated response: {}", e)))?;
+            DecodedResponse::GetEntityTypesPaginated(PageResult::new(response.items, response.total, response.next_cursor))
+        }
+        ResponseType::TakeSnapshot => {
+            let response = crate::data::resp::SnapshotResponse::decode(resp_value)
+                .map_err(|e| Error::StoreProxyError(format!("Failed to decode TakeSnapshot response: {}", e)))?;
+            DecodedResponse::TakeSnapshot(response.data)
+        }
+        ResponseType::MachineInfo => {
+            let response = crate::data::resp::StringResponse::decode(resp_value)
+                .map_err(|e| Error::StoreProxyError(format!("Failed to decode MachineInfo response: {}", e)))?;
+            DecodedResponse::MachineInfo(response.value)
+        }
+        ResponseType::AuditQuery => {
+            let response = crate::data::resp::AuditQueryResponse::decode(resp_value)
+                .map_err(|e| Error::StoreProxyError(format!("Failed to decode AuditQuery response: {}", e)))?;
+            DecodedResponse::AuditQuery(response.into_records()?)
+        }
+    };
+
+    Ok(Received::Ready(decoded))
+}
+
+/// Local sender bookkeeping for a queued LISTEN/UNLISTEN, applied on execute
+#[derive(Debug)]
+enum NotificationChange {
+    Register(NotifyConfig, Sender<Notification>),
+    Unregister(NotifyConfig, Sender<Notification>),
+}
+
 /// Synchronous pipeline for batching commands
 pub struct Pipeline<'a> {
     proxy: &'a crate::data::StoreProxy,
     commands: Vec<QueuedCommand>,
+    notification_changes: Vec<(usize, NotificationChange)>,
 }
 
 impl<'a> Pipeline<'a> {
@@ -326,6 +716,7 @@ impl<'a> Pipeline<'a> {
         Self {
             proxy,
             commands: Vec::new(),
+            notification_changes: Vec::new(),
         }
     }
 
@@ -382,6 +773,44 @@ impl<'a> Pipeline<'a> {
         Ok(self)
     }
 
+    /// Queue a create entity command carrying the initial field values
+    pub fn create_entity_with_fields(
+        &mut self,
+        entity_type: EntityType,
+        parent_id: Option<EntityId>,
+        name: &str,
+        fields: Vec<(FieldType, Value)>,
+    ) -> Result<&mut Self> {
+        let command = CreateEntityFullCommand {
+            entity_type,
+            parent_id,
+            name: name.to_string(),
+            fields,
+            _marker: std::marker::PhantomData,
+        };
+        self.queue_command(command, ResponseType::CreateEntity)?;
+        Ok(self)
+    }
+
+    /// Queue an instantiate template command
+    pub fn instantiate_template(
+        &mut self,
+        template_id: EntityId,
+        parent_id: Option<EntityId>,
+        name: &str,
+        overrides: Vec<(FieldType, Value)>,
+    ) -> Result<&mut Self> {
+        let command = InstantiateTemplateCommand {
+            template_id,
+            parent_id,
+            name: name.to_string(),
+            overrides,
+            _marker: std::marker::PhantomData,
+        };
+        self.queue_command(command, ResponseType::CreateEntity)?;
+        Ok(self)
+    }
+
     /// Queue a delete entity command
     pub fn delete_entity(&mut self, entity_id: EntityId) -> Result<&mut Self> {
         let command = DeleteEntityCommand {
@@ -432,6 +861,17 @@ impl<'a> Pipeline<'a> {
         Ok(self)
     }
 
+    /// Queue a bulk type lookup command
+    pub fn get_types_bulk(&mut self, entity_types: &[&str], field_types: &[&str]) -> Result<&mut Self> {
+        let command = GetTypesBulkCommand {
+            entity_types: entity_types.iter().map(|name| name.to_string()).collect(),
+            field_types: field_types.iter().map(|name| name.to_string()).collect(),
+            _marker: std::marker::PhantomData,
+        };
+        self.queue_command(command, ResponseType::GetTypesBulk)?;
+        Ok(self)
+    }
+
     /// Queue an entity exists command
     pub fn entity_exists(&mut self, entity_id: EntityId) -> Result<&mut Self> {
         let command = EntityExistsCommand {
@@ -458,6 +898,8 @@ impl<'a> Pipeline<'a> {
         let command = FindEntitiesCommand {
             entity_type,
             filter: filter.map(|s| s.to_string()),
+            timeout_ms: None,
+            request_id: None,
             _marker: std::marker::PhantomData,
         };
         self.queue_command(command, ResponseType::FindEntities)?;
@@ -473,274 +915,12 @@ impl<'a> Pipeline<'a> {
         Ok(self)
     }
 
-    /// Helper to queue a command
-    fn queue_command<C: RespCommand<'static>>(&mut self, command: C, response_type: ResponseType) -> Result<()> {
-        let encoded = command.encode();
-        let encoded_bytes = encoded.to_bytes();
-        self.commands.push(QueuedCommand {
-            encoded_bytes,
-            response_type,
-        });
-        Ok(())
-    }
-
-    /// Execute all queued commands and return results
-    pub fn execute(self) -> Result<PipelineResults> {
-        if self.commands.is_empty() {
-            return Ok(PipelineResults {
-                responses: Vec::new(),
-            });
-        }
-
-        // Send all commands at once
-        let mut all_bytes = Vec::new();
-        for cmd in &self.commands {
-            all_bytes.extend_from_slice(&cmd.encoded_bytes);
-        }
-
-        let mut conn = self.proxy.tcp_connection.borrow_mut();
-        conn.send_bytes(&all_bytes)
-            .map_err(|e| Error::StoreProxyError(format!("Failed to send pipeline commands: {}", e)))?;
-
-        // Receive all responses, handling notifications
-        let mut responses = Vec::new();
-        let mut command_index = 0;
-        loop {
-            // Try to parse response
-            let consumed_and_result = {
-                let conn_ref = &conn;
-                match RespValue::from_bytes(&conn_ref.read_buffer) {
-                    Ok((resp_value, remaining)) => {
-                        let consumed = conn_ref.read_buffer.len() - remaining.len();
-                        if command_index < self.commands.len() {
-                            let cmd = &self.commands[command_index];
-                            match self.decode_response(resp_value.clone(), &cmd.response_type) {
-                                Ok(decoded) => {
-                                    responses.push(decoded);
-                                    command_index += 1;
-                                    Some((consumed, Ok(true)))
-                                }
-                                Err(e) => {
-                                    // Try as notification
-                                    if let Ok(notification) = NotificationCommand::decode(resp_value.clone()) {
-                                        self.proxy.handle_notification(notification);
-                                        Some((consumed, Ok(false)))
-                                    } else {
-                                        // Consume bytes before returning error
-                                        Some((consumed, Err(Error::StoreProxyError(format!("Failed to decode response or notification: {}", e)))))
-                                    }
-                                }
-                            }
-                        } else {
-                            // Extra response, try as notification
-                            if let Ok(notification) = NotificationCommand::decode(resp_value.clone()) {
-                                self.proxy.handle_notification(notification);
-                                Some((consumed, Ok(false)))
-                            } else {
-                                // Consume bytes before returning error
-                                Some((consumed, Err(Error::StoreProxyError("Unexpected extra response".to_string()))))
-                            }
-                        }
-                    }
-                    Err(_) => None
-                }
-            };
-
-            if let Some((consumed, result)) = consumed_and_result {
-                conn.read_buffer.drain(..consumed);
-                match result {
-                    Ok(_is_response) => {
-                        if command_index >= self.commands.len() {
-                            break;
-                        }
-                    }
-                    Err(e) => return Err(e),
-                }
-            } else {
-                // Need more data
-                let readable = conn.wait_for_readable(Some(Duration::from_millis(10)))
-                    .map_err(|e| Error::StoreProxyError(format!("Poll error: {}", e)))?;
-                if readable {
-                    conn.read_bytes()
-                        .map_err(|e| Error::StoreProxyError(format!("Failed to read bytes: {}", e)))?;
-                }
-            }
-        }
-
-        Ok(PipelineResults { responses })
-    }
-
-    /// Decode a response based on its type
-    fn decode_response(&self, resp_value: RespValue, response_type: &ResponseType) -> Result<DecodedResponse> {
-        // Check if this is an error response from the server
-        if let RespValue::Error(error_msg) = &resp_value {
-            return Err(Error::StoreProxyError(error_msg.to_string()));
-        }
-        
-        match response_type {
-            ResponseType::Read => {
-                let response = crate::data::resp::ReadResponse::decode(resp_value)
-                    .map_err(|e| Error::StoreProxyError(format!("Failed to decode Read response: {}", e)))?;
-                Ok(DecodedResponse::Read((response.value, response.timestamp, response.writer_id)))
-            }
-            ResponseType::Write | ResponseType::DeleteEntity | ResponseType::UpdateSchema | ResponseType::SetFieldSchema => {
-                match resp_value {
-                    RespValue::SimpleString(s) if s == "OK" => {
-                        match response_type {
-                            ResponseType::Write => Ok(DecodedResponse::Write(())),
-                            ResponseType::DeleteEntity => Ok(DecodedResponse::DeleteEntity(())),
-                            ResponseType::UpdateSchema => Ok(DecodedResponse::UpdateSchema(())),
-                            ResponseType::SetFieldSchema => Ok(DecodedResponse::SetFieldSchema(())),
-                            _ => unreachable!(),
-                        }
-                    }
-                    RespValue::Error(msg) => Err(Error::StoreProxyError(format!("Server error: {}", msg))),
-                    _ => Err(Error::StoreProxyError("Expected OK response".to_string())),
-                }
-            }
-            ResponseType::CreateEntity => {
-                let response = crate::data::resp::CreateEntityResponse::decode(resp_value)
-                    .map_err(|e| Error::StoreProxyError(format!("Failed to decode CreateEntity response: {}", e)))?;
-                Ok(DecodedResponse::CreateEntity(response.entity_id))
-            }
-            ResponseType::GetEntityType => {
-                let response = crate::data::resp::IntegerResponse::decode(resp_value)
-                    .map_err(|e| Error::StoreProxyError(format!("Failed to decode GetEntityType response: {}", e)))?;
-                Ok(DecodedResponse::GetEntityType(EntityType(response.value as u32)))
-            }
-            ResponseType::ResolveEntityType => {
-                let response = crate::data::resp::StringResponse::decode(resp_value)
-                    .map_err(|e| Error::StoreProxyError(format!("Failed to decode ResolveEntityType response: {}", e)))?;
-                Ok(DecodedResponse::ResolveEntityType(response.value))
-            }
-            ResponseType::GetFieldType => {
-                let response = crate::data::resp::IntegerResponse::decode(resp_value)
-                    .map_err(|e| Error::StoreProxyError(format!("Failed to decode GetFieldType response: {}", e)))?;
-                Ok(DecodedResponse::GetFieldType(FieldType(response.value as u64)))
-            }
-            ResponseType::ResolveFieldType => {
-                let response = crate::data::resp::StringResponse::decode(resp_value)
-                    .map_err(|e| Error::StoreProxyError(format!("Failed to decode ResolveFieldType response: {}", e)))?;
-                Ok(DecodedResponse::ResolveFieldType(response.value))
-            }
-            ResponseType::GetEntitySchema => {
-                let schema_resp = crate::data::entity_schema::EntitySchemaResp::decode(resp_value)
-                    .map_err(|e| Error::StoreProxyError(format!("Failed to decode GetEntitySchema response: {}", e)))?;
-                let schema_string = schema_resp.to_entity_schema(self.proxy)?;
-                let typed_schema = EntitySchema::from_string_schema(schema_string, self.proxy);
-                Ok(DecodedResponse::GetEntitySchema(typed_schema))
-            }
-            ResponseType::GetFieldSchema => {
-                let response = crate::data::resp::FieldSchemaResponse::decode(resp_value)
-                    .map_err(|e| Error::StoreProxyError(format!("Failed to decode GetFieldSchema response: {}", e)))?;
-                let field_schema_string = response.schema.to_field_schema();
-                // Note: For now we return the string-based schema. Full typed conversion would require async context.
-                Ok(DecodedResponse::GetFieldSchema(field_schema_string))
-            }
-            ResponseType::EntityExists => {
-                let response = crate::data::resp::BooleanResponse::decode(resp_value)
-                    .map_err(|e| Error::StoreProxyError(format!("Failed to decode EntityExists response: {}", e)))?;
-                Ok(DecodedResponse::EntityExists(response.result))
-            }
-            ResponseType::FieldExists => {
-                let response = crate::data::resp::BooleanResponse::decode(resp_value)
-                    .map_err(|e| Error::StoreProxyError(format!("Failed to decode FieldExists response: {}", e)))?;
-                Ok(DecodedResponse::FieldExists(response.result))
-            }
-            ResponseType::ResolveIndirection => {
-                let response = crate::data::resp::ResolveIndirectionResponse::decode(resp_value)
-                    .map_err(|e| Error::StoreProxyError(format!("Failed to decode ResolveIndirection response: {}", e)))?;
-                Ok(DecodedResponse::ResolveIndirection((response.entity_id, response.field_type)))
-            }
-            ResponseType::FindEntitiesPaginated => {
-                let response = crate::data::resp::PaginatedEntityResponse::decode(resp_value)
-                    .map_err(|e| Error::StoreProxyError(format!("Failed to decode FindEntitiesPaginated response: {}", e)))?;
-                Ok(DecodedResponse::FindEntitiesPaginated(PageResult {
-                    items: response.items,
-                    total: response.total,
-                    next_cursor: response.next_cursor,
-                }))
-            }
-            ResponseType::FindEntitiesExact => {
-                let response = crate::data::resp::PaginatedEntityResponse::decode(resp_value)
-                    .map_err(|e| Error::StoreProxyError(format!("Failed to decode FindEntitiesExact response: {}", e)))?;
-                Ok(DecodedResponse::FindEntitiesExact(PageResult {
-                    items: response.items,
-                    total: response.total,
-                    next_cursor: response.next_cursor,
-                }))
-            }
-            ResponseType::FindEntities => {
-                let response = crate::data::resp::EntityListResponse::decode(resp_value)
-                    .map_err(|e| Error::StoreProxyError(format!("Failed to decode FindEntities response: {}", e)))?;
-                Ok(DecodedResponse::FindEntities(response.entities))
-            }
-            ResponseType::GetEntityTypes => {
-                let response = crate::data::resp::EntityTypeListResponse::decode(resp_value)
-                    .map_err(|e| Error::StoreProxyError(format!("Failed to decode GetEntityTypes response: {}", e)))?;
-                Ok(DecodedResponse::GetEntityTypes(response.entity_types))
-            }
-            ResponseType::GetEntityTypesPaginated => {
-                let response = crate::data::resp::PaginatedEntityTypeResponse::decode(resp_value)
-                    .map_err(|e| Error::StoreProxyError(format!("Failed to decode GetEntityTypesPaginated response: {}", e)))?;
-                Ok(DecodedResponse::GetEntityTypesPaginated(PageResult {
-                    items: response.items,
-                    total: response.total,
-                    next_cursor: response.next_cursor,
-                }))
-            }
-            ResponseType::TakeSnapshot => {
-                let response = crate::data::resp::SnapshotResponse::decode(resp_value)
-                    .map_err(|e| Error::StoreProxyError(format!("Failed to decode TakeSnapshot response: {}", e)))?;
-                Ok(DecodedResponse::TakeSnapshot(response.data))
-            }
-        }
-    }
-
-    /// Get the number of queued commands
-    pub fn len(&self) -> usize {
-        self.commands.len()
-    }
-
-    /// Check if pipeline is empty
-    pub fn is_empty(&self) -> bool {
-        self.commands.is_empty()
-    }
-
-    /// Clear all queued commands
-    pub fn clear(&mut self) {
-        self.commands.clear();
-    }
-}
-
-/// Asynchronous pipeline for batching commands
-pub struct AsyncPipeline<'a> {
-    proxy: &'a crate::data::AsyncStoreProxy,
-    commands: Vec<QueuedCommand>,
-}
-
-impl<'a> AsyncPipeline<'a> {
-    pub(crate) fn new(proxy: &'a crate::data::AsyncStoreProxy) -> Self {
-        Self {
-            proxy,
-            commands: Vec::new(),
-        }
-    }
-
-    /// Queue a read command
-    pub fn read(&mut self, entity_id: EntityId, field_path: &[FieldType]) -> Result<&mut Self> {
-        let command = ReadCommand {
-            entity_id,
-            field_path: field_path.to_vec(),
-            _marker: std::marker::PhantomData,
-        };
-        self.queue_command(command, ResponseType::Read)?;
-        Ok(self)
-    }
-
-    /// Queue a write command
-    pub fn write(
+    /// Queue a fenced write command
+    #[allow(clippy::too_many_arguments)]
+    pub fn fenced_write(
         &mut self,
+        fault_tolerance_id: EntityId,
+        fencing_token: i64,
         entity_id: EntityId,
         field_path: &[FieldType],
         value: Value,
@@ -749,7 +929,9 @@ impl<'a> AsyncPipeline<'a> {
         push_condition: Option<PushCondition>,
         adjust_behavior: Option<AdjustBehavior>,
     ) -> Result<&mut Self> {
-        let command = WriteCommand {
+        let command = FencedWriteCommand {
+            fault_tolerance_id,
+            fencing_token,
             entity_id,
             field_path: field_path.to_vec(),
             value,
@@ -759,13 +941,419 @@ impl<'a> AsyncPipeline<'a> {
             adjust_behavior,
             _marker: std::marker::PhantomData,
         };
-        self.queue_command(command, ResponseType::Write)?;
+        self.queue_command(command, ResponseType::FencedWrite)?;
         Ok(self)
     }
 
-    /// Queue a create entity command
-    pub fn create_entity(
-        &mut self,
+    /// Queue a resolve path command
+    pub fn resolve_path(&mut self, path: &str) -> Result<&mut Self> {
+        let command = ResolvePathCommand {
+            path: path.to_string(),
+            _marker: std::marker::PhantomData,
+        };
+        self.queue_command(command, ResponseType::ResolvePath)?;
+        Ok(self)
+    }
+
+    /// Queue a read path command
+    pub fn read_path(&mut self, path: &str) -> Result<&mut Self> {
+        let command = ReadPathCommand {
+            path: path.to_string(),
+            _marker: std::marker::PhantomData,
+        };
+        self.queue_command(command, ResponseType::ReadPath)?;
+        Ok(self)
+    }
+
+    /// Queue a write path command
+    pub fn write_path(
+        &mut self,
+        path: &str,
+        value: Value,
+        writer_id: Option<EntityId>,
+        write_time: Option<Timestamp>,
+        push_condition: Option<PushCondition>,
+        adjust_behavior: Option<AdjustBehavior>,
+    ) -> Result<&mut Self> {
+        let command = WritePathCommand {
+            path: path.to_string(),
+            value,
+            writer_id,
+            write_time,
+            push_condition,
+            adjust_behavior,
+            _marker: std::marker::PhantomData,
+        };
+        self.queue_command(command, ResponseType::WritePath)?;
+        Ok(self)
+    }
+
+    /// Queue a blob range read command
+    pub fn read_blob_range(&mut self, entity_id: EntityId, field_path: &[FieldType], offset: usize, len: usize) -> Result<&mut Self> {
+        let command = ReadBlobRangeCommand {
+            entity_id,
+            field_path: field_path.to_vec(),
+            offset,
+            len,
+            _marker: std::marker::PhantomData,
+        };
+        self.queue_command(command, ResponseType::ReadBlobRange)?;
+        Ok(self)
+    }
+
+    /// Queue a blob append command
+    pub fn write_blob_append(&mut self, entity_id: EntityId, field_path: &[FieldType], data: Vec<u8>, writer_id: Option<EntityId>) -> Result<&mut Self> {
+        let command = WriteBlobAppendCommand {
+            entity_id,
+            field_path: field_path.to_vec(),
+            data,
+            writer_id,
+            _marker: std::marker::PhantomData,
+        };
+        self.queue_command(command, ResponseType::WriteBlobAppend)?;
+        Ok(self)
+    }
+
+    /// Queue a get entity schema command
+    pub fn get_entity_schema(&mut self, entity_type: EntityType) -> Result<&mut Self> {
+        let command = GetEntitySchemaCommand {
+            entity_type,
+            _marker: std::marker::PhantomData,
+        };
+        self.queue_command(command, ResponseType::GetEntitySchema)?;
+        Ok(self)
+    }
+
+    /// Queue a get complete entity schema command
+    pub fn get_complete_entity_schema(&mut self, entity_type: EntityType) -> Result<&mut Self> {
+        let command = GetCompleteEntitySchemaCommand {
+            entity_type,
+            _marker: std::marker::PhantomData,
+        };
+        self.queue_command(command, ResponseType::GetCompleteEntitySchema)?;
+        Ok(self)
+    }
+
+    /// Queue an update schema command
+    pub fn update_schema(&mut self, schema: EntitySchema<Single, String, String>) -> Result<&mut Self> {
+        let command = UpdateSchemaCommand {
+            schema: EntitySchemaResp::from_string_schema(schema),
+            _marker: std::marker::PhantomData,
+        };
+        self.queue_command(command, ResponseType::UpdateSchema)?;
+        Ok(self)
+    }
+
+    /// Queue a get field schema command
+    pub fn get_field_schema(&mut self, entity_type: EntityType, field_type: FieldType) -> Result<&mut Self> {
+        let command = GetFieldSchemaCommand {
+            entity_type,
+            field_type,
+            _marker: std::marker::PhantomData,
+        };
+        self.queue_command(command, ResponseType::GetFieldSchema)?;
+        Ok(self)
+    }
+
+    /// Queue a set field schema command. The schema is string-based, as its
+    /// field name can't be looked up while commands are being queued.
+    pub fn set_field_schema(&mut self, entity_type: EntityType, field_type: FieldType, schema: FieldSchema<String>) -> Result<&mut Self> {
+        let command = SetFieldSchemaCommand {
+            entity_type,
+            field_type,
+            schema: FieldSchemaResp::from_string_schema(&schema),
+            _marker: std::marker::PhantomData,
+        };
+        self.queue_command(command, ResponseType::SetFieldSchema)?;
+        Ok(self)
+    }
+
+    /// Queue a resolve indirection command
+    pub fn resolve_indirection(&mut self, entity_id: EntityId, fields: &[FieldType]) -> Result<&mut Self> {
+        let command = ResolveIndirectionCommand {
+            entity_id,
+            fields: fields.to_vec(),
+            _marker: std::marker::PhantomData,
+        };
+        self.queue_command(command, ResponseType::ResolveIndirection)?;
+        Ok(self)
+    }
+
+    /// Queue a paginated find entities command (includes inherited types)
+    pub fn find_entities_paginated(&mut self, entity_type: EntityType, page_opts: Option<&PageOpts>, filter: Option<&str>) -> Result<&mut Self> {
+        let command = FindEntitiesPaginatedCommand {
+            entity_type,
+            page_opts: page_opts.cloned(),
+            filter: filter.map(|s| s.to_string()),
+            timeout_ms: None,
+            request_id: None,
+            _marker: std::marker::PhantomData,
+        };
+        self.queue_command(command, ResponseType::FindEntitiesPaginated)?;
+        Ok(self)
+    }
+
+    /// Queue a paginated find entities command (exact type only)
+    pub fn find_entities_exact(&mut self, entity_type: EntityType, page_opts: Option<&PageOpts>, filter: Option<&str>) -> Result<&mut Self> {
+        let command = FindEntitiesExactCommand {
+            entity_type,
+            page_opts: page_opts.cloned(),
+            filter: filter.map(|s| s.to_string()),
+            timeout_ms: None,
+            request_id: None,
+            _marker: std::marker::PhantomData,
+        };
+        self.queue_command(command, ResponseType::FindEntitiesExact)?;
+        Ok(self)
+    }
+
+    /// Queue a paginated get entity types command
+    pub fn get_entity_types_paginated(&mut self, page_opts: Option<&PageOpts>) -> Result<&mut Self> {
+        let command = GetEntityTypesPaginatedCommand {
+            page_opts: page_opts.cloned(),
+            _marker: std::marker::PhantomData,
+        };
+        self.queue_command(command, ResponseType::GetEntityTypesPaginated)?;
+        Ok(self)
+    }
+
+    /// Queue a take snapshot command
+    pub fn take_snapshot(&mut self) -> Result<&mut Self> {
+        let command = TakeSnapshotCommand {
+            _marker: std::marker::PhantomData,
+        };
+        self.queue_command(command, ResponseType::TakeSnapshot)?;
+        Ok(self)
+    }
+
+    /// Queue a machine info command
+    pub fn machine_info(&mut self) -> Result<&mut Self> {
+        let command = MachineInfoCommand {
+            _marker: std::marker::PhantomData,
+        };
+        self.queue_command(command, ResponseType::MachineInfo)?;
+        Ok(self)
+    }
+
+    /// Queue an audit query command
+    pub fn audit_query(&mut self, query: &AuditQuery) -> Result<&mut Self> {
+        let command = AuditQueryCommand::from_query(query);
+        self.queue_command(command, ResponseType::AuditQuery)?;
+        Ok(self)
+    }
+
+    /// Queue a register notification command. `sender` starts receiving
+    /// notifications once the pipeline is executed.
+    pub fn register_notification(&mut self, config: NotifyConfig, sender: Sender<Notification>) -> Result<&mut Self> {
+        let command = RegisterNotificationCommand {
+            config: config.clone(),
+            _marker: std::marker::PhantomData,
+        };
+        self.notification_changes.push((self.commands.len(), NotificationChange::Register(config, sender)));
+        self.queue_command(command, ResponseType::RegisterNotification)?;
+        Ok(self)
+    }
+
+    /// Queue an unregister notification command. As with
+    /// `StoreProxy::unregister_notification`, the server is only told once the
+    /// last sender for `config` is removed.
+    pub fn unregister_notification(&mut self, config: &NotifyConfig, sender: &Sender<Notification>) -> Result<&mut Self> {
+        let command = UnregisterNotificationCommand {
+            config: config.clone(),
+            _marker: std::marker::PhantomData,
+        };
+        self.notification_changes.push((self.commands.len(), NotificationChange::Unregister(config.clone(), sender.clone())));
+        self.queue_command(command, ResponseType::UnregisterNotification)?;
+        Ok(self)
+    }
+
+    /// Helper to queue a command
+    fn queue_command<C: RespCommand<'static>>(&mut self, command: C, response_type: ResponseType) -> Result<()> {
+        let binary_ids = self.proxy.codec() == Codec::RespBinaryIds;
+        let encoded_bytes = with_binary_ids(binary_ids, || command.encode().to_bytes());
+        self.commands.push(QueuedCommand {
+            encoded_bytes,
+            response_type,
+        });
+        Ok(())
+    }
+
+    /// Execute all queued commands and return results
+    pub fn execute(self) -> Result<PipelineResults> {
+        self.run(false)
+    }
+
+    /// Execute all queued commands as a single server-side transaction
+    /// (`MULTI`/`EXEC`). The server applies every command or none of them, and
+    /// any failure fails the whole batch.
+    pub fn execute_atomic(self) -> Result<PipelineResults> {
+        self.run(true)
+    }
+
+    fn run(self, atomic: bool) -> Result<PipelineResults> {
+        // Removing one of several senders for a config is purely local, so
+        // that UNLISTEN is answered here instead of being sent
+        let mut answered_locally = vec![false; self.commands.len()];
+        for (index, change) in &self.notification_changes {
+            match change {
+                NotificationChange::Register(config, sender) => {
+                    self.proxy.add_notification_sender(config.clone(), sender.clone());
+                }
+                NotificationChange::Unregister(config, sender) => {
+                    answered_locally[*index] = !self.proxy.remove_notification_sender(config, sender);
+                }
+            }
+        }
+
+        let to_send: Vec<&QueuedCommand> = self.commands
+            .iter()
+            .zip(&answered_locally)
+            .filter(|(_, local)| !**local)
+            .map(|(cmd, _)| cmd)
+            .collect();
+
+        let received = if to_send.is_empty() {
+            Vec::new()
+        } else {
+            let mut conn = self.proxy.tcp_connection.borrow_mut();
+
+            // Send all commands at once
+            let (all_bytes, expected) = prepare_batch(&to_send, atomic, &conn.limits)?;
+            conn.send_bytes(&all_bytes)?;
+
+            // Receive all responses, handling notifications
+            let mut collector = ReplyCollector::new(expected);
+            while !collector.is_done() {
+                let consumed = match RespValue::from_bytes(&conn.read_buffer) {
+                    Ok((resp_value, remaining)) => {
+                        let consumed = conn.read_buffer.len() - remaining.len();
+                        if let Some(push) = collector.accept(resp_value) {
+                            self.proxy.handle_push(push);
+                        }
+                        Some(consumed)
+                    }
+                    // Malformed data will never parse, so don't wait for more of it
+                    Err(e @ Error::ProtocolError(_)) => return Err(e),
+                    Err(_) => None
+                };
+
+                if let Some(consumed) = consumed {
+                    conn.read_buffer.drain(..consumed);
+                } else {
+                    // Need more data
+                    let readable = conn.wait_for_readable(Some(Duration::from_millis(10)))
+                        .map_err(|e| Error::StoreProxyError(format!("Poll error: {}", e)))?;
+                    if readable {
+                        conn.read_bytes()?;
+                    }
+                }
+            }
+            collector.finish()?
+        };
+
+        // The connection is free again, so schemas can be converted now
+        let mut received = received.into_iter();
+        let mut responses = Vec::with_capacity(self.commands.len());
+        for local in answered_locally {
+            let response = if local {
+                DecodedResponse::UnregisterNotification(true)
+            } else {
+                let next = received.next()
+                    .ok_or_else(|| Error::StoreProxyError("Missing pipeline response".to_string()))?;
+                self.resolve(next)?
+            };
+            responses.push(response);
+        }
+
+        Ok(PipelineResults { responses })
+    }
+
+    /// Finish decoding a reply that needed the connection to be released
+    fn resolve(&self, received: Received) -> Result<DecodedResponse> {
+        match received {
+            Received::Ready(response) => Ok(response),
+            Received::Schema { schema, complete } => {
+                let schema_string = schema.to_entity_schema(self.proxy)?;
+                let typed_schema = EntitySchema::from_string_schema(schema_string, self.proxy);
+                if complete {
+                    Ok(DecodedResponse::GetCompleteEntitySchema(EntitySchema::<Complete>::from(typed_schema)))
+                } else {
+                    Ok(DecodedResponse::GetEntitySchema(typed_schema))
+                }
+            }
+        }
+    }
+
+    /// Get the number of queued commands
+    pub fn len(&self) -> usize {
+        self.commands.len()
+    }
+
+    /// Check if pipeline is empty
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+
+    /// Clear all queued commands
+    pub fn clear(&mut self) {
+        self.commands.clear();
+        self.notification_changes.clear();
+    }
+}
+
+/// Asynchronous pipeline for batching commands
+pub struct AsyncPipeline<'a> {
+    proxy: &'a crate::data::AsyncStoreProxy,
+    commands: Vec<QueuedCommand>,
+}
+
+impl<'a> AsyncPipeline<'a> {
+    pub(crate) fn new(proxy: &'a crate::data::AsyncStoreProxy) -> Self {
+        Self {
+            proxy,
+            commands: Vec::new(),
+        }
+    }
+
+    /// Queue a read command
+    pub fn read(&mut self, entity_id: EntityId, field_path: &[FieldType]) -> Result<&mut Self> {
+        let command = ReadCommand {
+            entity_id,
+            field_path: field_path.to_vec(),
+            _marker: std::marker::PhantomData,
+        };
+        self.queue_command(command, ResponseType::Read)?;
+        Ok(self)
+    }
+
+    /// Queue a write command
+    pub fn write(
+        &mut self,
+        entity_id: EntityId,
+        field_path: &[FieldType],
+        value: Value,
+        writer_id: Option<EntityId>,
+        write_time: Option<Timestamp>,
+        push_condition: Option<PushCondition>,
+        adjust_behavior: Option<AdjustBehavior>,
+    ) -> Result<&mut Self> {
+        let command = WriteCommand {
+            entity_id,
+            field_path: field_path.to_vec(),
+            value,
+            writer_id,
+            write_time,
+            push_condition,
+            adjust_behavior,
+            _marker: std::marker::PhantomData,
+        };
+        self.queue_command(command, ResponseType::Write)?;
+        Ok(self)
+    }
+
+    /// Queue a create entity command
+    pub fn create_entity(
+        &mut self,
         entity_type: EntityType,
         parent_id: Option<EntityId>,
         name: &str,
@@ -780,6 +1368,44 @@ impl<'a> AsyncPipeline<'a> {
         Ok(self)
     }
 
+    /// Queue a create entity command carrying the initial field values
+    pub fn create_entity_with_fields(
+        &mut self,
+        entity_type: EntityType,
+        parent_id: Option<EntityId>,
+        name: &str,
+        fields: Vec<(FieldType, Value)>,
+    ) -> Result<&mut Self> {
+        let command = CreateEntityFullCommand {
+            entity_type,
+            parent_id,
+            name: name.to_string(),
+            fields,
+            _marker: std::marker::PhantomData,
+        };
+        self.queue_command(command, ResponseType::CreateEntity)?;
+        Ok(self)
+    }
+
+    /// Queue an instantiate template command
+    pub fn instantiate_template(
+        &mut self,
+        template_id: EntityId,
+        parent_id: Option<EntityId>,
+        name: &str,
+        overrides: Vec<(FieldType, Value)>,
+    ) -> Result<&mut Self> {
+        let command = InstantiateTemplateCommand {
+            template_id,
+            parent_id,
+            name: name.to_string(),
+            overrides,
+            _marker: std::marker::PhantomData,
+        };
+        self.queue_command(command, ResponseType::CreateEntity)?;
+        Ok(self)
+    }
+
     /// Queue a delete entity command
     pub fn delete_entity(&mut self, entity_id: EntityId) -> Result<&mut Self> {
         let command = DeleteEntityCommand {
@@ -830,6 +1456,17 @@ impl<'a> AsyncPipeline<'a> {
         Ok(self)
     }
 
+    /// Queue a bulk type lookup command
+    pub fn get_types_bulk(&mut self, entity_types: &[&str], field_types: &[&str]) -> Result<&mut Self> {
+        let command = GetTypesBulkCommand {
+            entity_types: entity_types.iter().map(|name| name.to_string()).collect(),
+            field_types: field_types.iter().map(|name| name.to_string()).collect(),
+            _marker: std::marker::PhantomData,
+        };
+        self.queue_command(command, ResponseType::GetTypesBulk)?;
+        Ok(self)
+    }
+
     /// Queue an entity exists command
     pub fn entity_exists(&mut self, entity_id: EntityId) -> Result<&mut Self> {
         let command = EntityExistsCommand {
@@ -856,6 +1493,8 @@ impl<'a> AsyncPipeline<'a> {
         let command = FindEntitiesCommand {
             entity_type,
             filter: filter.map(|s| s.to_string()),
+            timeout_ms: None,
+            request_id: None,
             _marker: std::marker::PhantomData,
         };
         self.queue_command(command, ResponseType::FindEntities)?;
@@ -871,10 +1510,260 @@ impl<'a> AsyncPipeline<'a> {
         Ok(self)
     }
 
+    /// Queue a fenced write command
+    #[allow(clippy::too_many_arguments)]
+    pub fn fenced_write(
+        &mut self,
+        fault_tolerance_id: EntityId,
+        fencing_token: i64,
+        entity_id: EntityId,
+        field_path: &[FieldType],
+        value: Value,
+        writer_id: Option<EntityId>,
+        write_time: Option<Timestamp>,
+        push_condition: Option<PushCondition>,
+        adjust_behavior: Option<AdjustBehavior>,
+    ) -> Result<&mut Self> {
+        let command = FencedWriteCommand {
+            fault_tolerance_id,
+            fencing_token,
+            entity_id,
+            field_path: field_path.to_vec(),
+            value,
+            writer_id,
+            write_time,
+            push_condition,
+            adjust_behavior,
+            _marker: std::marker::PhantomData,
+        };
+        self.queue_command(command, ResponseType::FencedWrite)?;
+        Ok(self)
+    }
+
+    /// Queue a resolve path command
+    pub fn resolve_path(&mut self, path: &str) -> Result<&mut Self> {
+        let command = ResolvePathCommand {
+            path: path.to_string(),
+            _marker: std::marker::PhantomData,
+        };
+        self.queue_command(command, ResponseType::ResolvePath)?;
+        Ok(self)
+    }
+
+    /// Queue a read path command
+    pub fn read_path(&mut self, path: &str) -> Result<&mut Self> {
+        let command = ReadPathCommand {
+            path: path.to_string(),
+            _marker: std::marker::PhantomData,
+        };
+        self.queue_command(command, ResponseType::ReadPath)?;
+        Ok(self)
+    }
+
+    /// Queue a write path command
+    pub fn write_path(
+        &mut self,
+        path: &str,
+        value: Value,
+        writer_id: Option<EntityId>,
+        write_time: Option<Timestamp>,
+        push_condition: Option<PushCondition>,
+        adjust_behavior: Option<AdjustBehavior>,
+    ) -> Result<&mut Self> {
+        let command = WritePathCommand {
+            path: path.to_string(),
+            value,
+            writer_id,
+            write_time,
+            push_condition,
+            adjust_behavior,
+            _marker: std::marker::PhantomData,
+        };
+        self.queue_command(command, ResponseType::WritePath)?;
+        Ok(self)
+    }
+
+    /// Queue a blob range read command
+    pub fn read_blob_range(&mut self, entity_id: EntityId, field_path: &[FieldType], offset: usize, len: usize) -> Result<&mut Self> {
+        let command = ReadBlobRangeCommand {
+            entity_id,
+            field_path: field_path.to_vec(),
+            offset,
+            len,
+            _marker: std::marker::PhantomData,
+        };
+        self.queue_command(command, ResponseType::ReadBlobRange)?;
+        Ok(self)
+    }
+
+    /// Queue a blob append command
+    pub fn write_blob_append(&mut self, entity_id: EntityId, field_path: &[FieldType], data: Vec<u8>, writer_id: Option<EntityId>) -> Result<&mut Self> {
+        let command = WriteBlobAppendCommand {
+            entity_id,
+            field_path: field_path.to_vec(),
+            data,
+            writer_id,
+            _marker: std::marker::PhantomData,
+        };
+        self.queue_command(command, ResponseType::WriteBlobAppend)?;
+        Ok(self)
+    }
+
+    /// Queue a get entity schema command
+    pub fn get_entity_schema(&mut self, entity_type: EntityType) -> Result<&mut Self> {
+        let command = GetEntitySchemaCommand {
+            entity_type,
+            _marker: std::marker::PhantomData,
+        };
+        self.queue_command(command, ResponseType::GetEntitySchema)?;
+        Ok(self)
+    }
+
+    /// Queue a get complete entity schema command
+    pub fn get_complete_entity_schema(&mut self, entity_type: EntityType) -> Result<&mut Self> {
+        let command = GetCompleteEntitySchemaCommand {
+            entity_type,
+            _marker: std::marker::PhantomData,
+        };
+        self.queue_command(command, ResponseType::GetCompleteEntitySchema)?;
+        Ok(self)
+    }
+
+    /// Queue an update schema command
+    pub fn update_schema(&mut self, schema: EntitySchema<Single, String, String>) -> Result<&mut Self> {
+        let command = UpdateSchemaCommand {
+            schema: EntitySchemaResp::from_string_schema(schema),
+            _marker: std::marker::PhantomData,
+        };
+        self.queue_command(command, ResponseType::UpdateSchema)?;
+        Ok(self)
+    }
+
+    /// Queue a get field schema command
+    pub fn get_field_schema(&mut self, entity_type: EntityType, field_type: FieldType) -> Result<&mut Self> {
+        let command = GetFieldSchemaCommand {
+            entity_type,
+            field_type,
+            _marker: std::marker::PhantomData,
+        };
+        self.queue_command(command, ResponseType::GetFieldSchema)?;
+        Ok(self)
+    }
+
+    /// Queue a set field schema command. The schema is string-based, as its
+    /// field name can't be looked up while commands are being queued.
+    pub fn set_field_schema(&mut self, entity_type: EntityType, field_type: FieldType, schema: FieldSchema<String>) -> Result<&mut Self> {
+        let command = SetFieldSchemaCommand {
+            entity_type,
+            field_type,
+            schema: FieldSchemaResp::from_string_schema(&schema),
+            _marker: std::marker::PhantomData,
+        };
+        self.queue_command(command, ResponseType::SetFieldSchema)?;
+        Ok(self)
+    }
+
+    /// Queue a resolve indirection command
+    pub fn resolve_indirection(&mut self, entity_id: EntityId, fields: &[FieldType]) -> Result<&mut Self> {
+        let command = ResolveIndirectionCommand {
+            entity_id,
+            fields: fields.to_vec(),
+            _marker: std::marker::PhantomData,
+        };
+        self.queue_command(command, ResponseType::ResolveIndirection)?;
+        Ok(self)
+    }
+
+    /// Queue a paginated find entities command (includes inherited types)
+    pub fn find_entities_paginated(&mut self, entity_type: EntityType, page_opts: Option<&PageOpts>, filter: Option<&str>) -> Result<&mut Self> {
+        let command = FindEntitiesPaginatedCommand {
+            entity_type,
+            page_opts: page_opts.cloned(),
+            filter: filter.map(|s| s.to_string()),
+            timeout_ms: None,
+            request_id: None,
+            _marker: std::marker::PhantomData,
+        };
+        self.queue_command(command, ResponseType::FindEntitiesPaginated)?;
+        Ok(self)
+    }
+
+    /// Queue a paginated find entities command (exact type only)
+    pub fn find_entities_exact(&mut self, entity_type: EntityType, page_opts: Option<&PageOpts>, filter: Option<&str>) -> Result<&mut Self> {
+        let command = FindEntitiesExactCommand {
+            entity_type,
+            page_opts: page_opts.cloned(),
+            filter: filter.map(|s| s.to_string()),
+            timeout_ms: None,
+            request_id: None,
+            _marker: std::marker::PhantomData,
+        };
+        self.queue_command(command, ResponseType::FindEntitiesExact)?;
+        Ok(self)
+    }
+
+    /// Queue a paginated get entity types command
+    pub fn get_entity_types_paginated(&mut self, page_opts: Option<&PageOpts>) -> Result<&mut Self> {
+        let command = GetEntityTypesPaginatedCommand {
+            page_opts: page_opts.cloned(),
+            _marker: std::marker::PhantomData,
+        };
+        self.queue_command(command, ResponseType::GetEntityTypesPaginated)?;
+        Ok(self)
+    }
+
+    /// Queue a take snapshot command
+    pub fn take_snapshot(&mut self) -> Result<&mut Self> {
+        let command = TakeSnapshotCommand {
+            _marker: std::marker::PhantomData,
+        };
+        self.queue_command(command, ResponseType::TakeSnapshot)?;
+        Ok(self)
+    }
+
+    /// Queue a machine info command
+    pub fn machine_info(&mut self) -> Result<&mut Self> {
+        let command = MachineInfoCommand {
+            _marker: std::marker::PhantomData,
+        };
+        self.queue_command(command, ResponseType::MachineInfo)?;
+        Ok(self)
+    }
+
+    /// Queue an audit query command
+    pub fn audit_query(&mut self, query: &AuditQuery) -> Result<&mut Self> {
+        let command = AuditQueryCommand::from_query(query);
+        self.queue_command(command, ResponseType::AuditQuery)?;
+        Ok(self)
+    }
+
+    /// Queue a register notification command
+    /// Note: like `AsyncStoreProxy::register_notification`, this only registers
+    /// on the server and the queue is not fed
+    pub fn register_notification(&mut self, config: NotifyConfig, _sender: NotificationQueue) -> Result<&mut Self> {
+        let command = RegisterNotificationCommand {
+            config,
+            _marker: std::marker::PhantomData,
+        };
+        self.queue_command(command, ResponseType::RegisterNotification)?;
+        Ok(self)
+    }
+
+    /// Queue an unregister notification command
+    /// Note: This will remove ALL notifications matching the config for proxy
+    pub fn unregister_notification(&mut self, config: &NotifyConfig, _sender: &NotificationQueue) -> Result<&mut Self> {
+        let command = UnregisterNotificationCommand {
+            config: config.clone(),
+            _marker: std::marker::PhantomData,
+        };
+        self.queue_command(command, ResponseType::UnregisterNotification)?;
+        Ok(self)
+    }
+
     /// Helper to queue a command
     fn queue_command<C: RespCommand<'static>>(&mut self, command: C, response_type: ResponseType) -> Result<()> {
-        let encoded = command.encode();
-        let encoded_bytes = encoded.to_bytes();
+        let binary_ids = self.proxy.codec() == Codec::RespBinaryIds;
+        let encoded_bytes = with_binary_ids(binary_ids, || command.encode().to_bytes());
         self.commands.push(QueuedCommand {
             encoded_bytes,
             response_type,
@@ -884,239 +1773,80 @@ impl<'a> AsyncPipeline<'a> {
 
     /// Execute all queued commands and return results
     pub async fn execute(self) -> Result<PipelineResults> {
+        self.run(false).await
+    }
+
+    /// Execute all queued commands as a single server-side transaction
+    /// (`MULTI`/`EXEC`). The server applies every command or none of them, and
+    /// any failure fails the whole batch.
+    pub async fn execute_atomic(self) -> Result<PipelineResults> {
+        self.run(true).await
+    }
+
+    async fn run(self, atomic: bool) -> Result<PipelineResults> {
         if self.commands.is_empty() {
             return Ok(PipelineResults {
                 responses: Vec::new(),
             });
         }
 
-        // Send all commands at once
-        let mut all_bytes = Vec::new();
-        for cmd in &self.commands {
-            all_bytes.extend_from_slice(&cmd.encoded_bytes);
-        }
+        let received = {
+            let mut conn = self.proxy.connection().await?;
 
-        let mut conn = self.proxy.tcp_connection.lock().await;
-        conn.send_bytes(&all_bytes)
-            .await
-            .map_err(|e| Error::StoreProxyError(format!("Failed to send pipeline commands: {}", e)))?;
-
-        // Receive all responses, handling notifications
-        let mut responses = Vec::new();
-        let mut command_index = 0;
-        loop {
-            // Try to parse response
-            let consumed_and_result = match RespValue::from_bytes(&conn.read_buffer) {
-                Ok((resp_value, remaining)) => {
-                    let consumed = conn.read_buffer.len() - remaining.len();
-                    if command_index < self.commands.len() {
-                        let cmd = &self.commands[command_index];
-                        match self.decode_response(resp_value.clone(), &cmd.response_type).await {
-                            Ok(decoded) => {
-                                responses.push(decoded);
-                                command_index += 1;
-                                Some((consumed, Ok(true)))
-                            }
-                            Err(e) => {
-                                // Try as notification
-                                if let Ok(notification) = NotificationCommand::decode(resp_value.clone()) {
-                                    self.proxy.handle_notification(notification);
-                                    Some((consumed, Ok(false)))
-                                } else {
-                                    // Consume bytes before returning error
-                                    Some((consumed, Err(Error::StoreProxyError(format!("Failed to decode response or notification: {}", e)))))
-                                }
+            // Send all commands at once
+            let to_send: Vec<&QueuedCommand> = self.commands.iter().collect();
+            let (all_bytes, expected) = prepare_batch(&to_send, atomic, &conn.limits)?;
+            conn.send_bytes(&all_bytes).await?;
+            conn.unanswered += expected.len();
+
+            // Receive all responses, handling notifications
+            let mut collector = ReplyCollector::new(expected);
+            while !collector.is_done() {
+                let consumed = match RespValue::from_bytes(&conn.read_buffer) {
+                    Ok((resp_value, remaining)) => {
+                        let consumed = conn.read_buffer.len() - remaining.len();
+                        // Replies to tagged requests may be interleaved on a multiplexed proxy
+                        if !self.proxy.route_tagged_reply(&resp_value) {
+                            match collector.accept(resp_value) {
+                                Some(push) => self.proxy.handle_push(push),
+                                None => conn.unanswered -= 1,
                             }
                         }
-                    } else {
-                        // Extra response, try as notification
-                        if let Ok(notification) = NotificationCommand::decode(resp_value.clone()) {
-                            self.proxy.handle_notification(notification);
-                            Some((consumed, Ok(false)))
-                        } else {
-                            // Consume bytes before returning error
-                            Some((consumed, Err(Error::StoreProxyError("Unexpected extra response".to_string()))))
-                        }
+                        Some(consumed)
                     }
-                }
-                Err(_) => None
-            };
+                    // Malformed data will never parse, so don't wait for more of it
+                    Err(e @ Error::ProtocolError(_)) => return Err(e),
+                    Err(_) => None
+                };
 
-            if let Some((consumed, result)) = consumed_and_result {
-                conn.read_buffer.drain(..consumed);
-                match result {
-                    Ok(_is_response) => {
-                        if command_index >= self.commands.len() {
-                            break;
-                        }
-                    }
-                    Err(e) => return Err(e),
+                if let Some(consumed) = consumed {
+                    conn.read_buffer.drain(..consumed);
+                } else {
+                    // Need more data
+                    conn.read_bytes().await?;
                 }
-            } else {
-                // Need more data
-                conn.read_bytes()
-                    .await
-                    .map_err(|e| Error::StoreProxyError(format!("Failed to read pipeline response: {}", e)))?;
             }
-        }
-
-        Ok(PipelineResults { responses })
-    }
+            collector.finish()?
+        };
 
-    /// Decode a response based on its type
-    async fn decode_response(&self, resp_value: RespValue<'_>, response_type: &ResponseType) -> Result<DecodedResponse> {
-        // Check if this is an error response from the server
-        if let RespValue::Error(error_msg) = &resp_value {
-            return Err(Error::StoreProxyError(error_msg.to_string()));
-        }
-        
-        match response_type {
-            ResponseType::Read => {
-                let response = crate::data::resp::ReadResponse::decode(resp_value)
-                    .map_err(|e| Error::StoreProxyError(format!("Failed to decode Read response: {}", e)))?;
-                Ok(DecodedResponse::Read((response.value, response.timestamp, response.writer_id)))
-            }
-            ResponseType::Write | ResponseType::DeleteEntity | ResponseType::UpdateSchema | ResponseType::SetFieldSchema => {
-                match resp_value {
-                    RespValue::SimpleString(s) if s == "OK" => {
-                        match response_type {
-                            ResponseType::Write => Ok(DecodedResponse::Write(())),
-                            ResponseType::DeleteEntity => Ok(DecodedResponse::DeleteEntity(())),
-                            ResponseType::UpdateSchema => Ok(DecodedResponse::UpdateSchema(())),
-                            ResponseType::SetFieldSchema => Ok(DecodedResponse::SetFieldSchema(())),
-                            _ => unreachable!(),
-                        }
+        // The connection lock is released, so schemas can be converted now
+        let mut responses = Vec::with_capacity(received.len());
+        for next in received {
+            let response = match next {
+                Received::Ready(response) => response,
+                Received::Schema { schema, complete } => {
+                    let typed_schema = self.proxy.entity_schema_from_resp(schema).await?;
+                    if complete {
+                        DecodedResponse::GetCompleteEntitySchema(EntitySchema::<Complete>::from(typed_schema))
+                    } else {
+                        DecodedResponse::GetEntitySchema(typed_schema)
                     }
-                    RespValue::Error(msg) => Err(Error::StoreProxyError(format!("Server error: {}", msg))),
-                    _ => Err(Error::StoreProxyError("Expected OK response".to_string())),
-                }
-            }
-            ResponseType::CreateEntity => {
-                let response = crate::data::resp::CreateEntityResponse::decode(resp_value)
-                    .map_err(|e| Error::StoreProxyError(format!("Failed to decode CreateEntity response: {}", e)))?;
-                Ok(DecodedResponse::CreateEntity(response.entity_id))
-            }
-            ResponseType::GetEntityType => {
-                let response = crate::data::resp::IntegerResponse::decode(resp_value)
-                    .map_err(|e| Error::StoreProxyError(format!("Failed to decode GetEntityType response: {}", e)))?;
-                Ok(DecodedResponse::GetEntityType(EntityType(response.value as u32)))
-            }
-            ResponseType::ResolveEntityType => {
-                let response = crate::data::resp::StringResponse::decode(resp_value)
-                    .map_err(|e| Error::StoreProxyError(format!("Failed to decode ResolveEntityType response: {}", e)))?;
-                Ok(DecodedResponse::ResolveEntityType(response.value))
-            }
-            ResponseType::GetFieldType => {
-                let response = crate::data::resp::IntegerResponse::decode(resp_value)
-                    .map_err(|e| Error::StoreProxyError(format!("Failed to decode GetFieldType response: {}", e)))?;
-                Ok(DecodedResponse::GetFieldType(FieldType(response.value as u64)))
-            }
-            ResponseType::ResolveFieldType => {
-                let response = crate::data::resp::StringResponse::decode(resp_value)
-                    .map_err(|e| Error::StoreProxyError(format!("Failed to decode ResolveFieldType response: {}", e)))?;
-                Ok(DecodedResponse::ResolveFieldType(response.value))
-            }
-            ResponseType::GetEntitySchema => {
-                let schema_resp = crate::data::entity_schema::EntitySchemaResp::decode(resp_value)
-                    .map_err(|e| Error::StoreProxyError(format!("Failed to decode GetEntitySchema response: {}", e)))?;
-                
-                // Convert to typed schema
-                let mut fields = rustc_hash::FxHashMap::default();
-                for field_resp in schema_resp.fields {
-                    fields.insert(field_resp.field_type.clone(), field_resp.to_field_schema());
-                }
-
-                let mut schema_string = EntitySchema::<Single, String, String>::new(
-                    schema_resp.entity_type.clone(),
-                    schema_resp.inherit.clone(),
-                );
-                schema_string.fields = fields;
-                
-                let typed_entity_type = self.proxy.get_entity_type(&schema_string.entity_type).await?;
-                let mut typed_inherit = Vec::new();
-                for inherit_str in &schema_string.inherit {
-                    typed_inherit.push(self.proxy.get_entity_type(inherit_str).await?);
-                }
-                
-                let mut typed_fields = rustc_hash::FxHashMap::default();
-                for (field_type_str, field_schema) in schema_string.fields {
-                    let field_type = self.proxy.get_field_type(&field_type_str).await?;
-                    let typed_field_schema = self.proxy.convert_field_schema_from_string(field_schema).await?;
-                    typed_fields.insert(field_type, typed_field_schema);
                 }
-                
-                let mut typed_schema = EntitySchema::<Single, EntityType, FieldType>::new(
-                    typed_entity_type,
-                    typed_inherit,
-                );
-                typed_schema.fields = typed_fields;
-                Ok(DecodedResponse::GetEntitySchema(typed_schema))
-            }
-            ResponseType::GetFieldSchema => {
-                let response = crate::data::resp::FieldSchemaResponse::decode(resp_value)
-                    .map_err(|e| Error::StoreProxyError(format!("Failed to decode GetFieldSchema response: {}", e)))?;
-                let field_schema_string = response.schema.to_field_schema();
-                // For async pipeline, we also keep it as string to avoid complex type conversion
-                Ok(DecodedResponse::GetFieldSchema(field_schema_string))
-            }
-            ResponseType::EntityExists => {
-                let response = crate::data::resp::BooleanResponse::decode(resp_value)
-                    .map_err(|e| Error::StoreProxyError(format!("Failed to decode EntityExists response: {}", e)))?;
-                Ok(DecodedResponse::EntityExists(response.result))
-            }
-            ResponseType::FieldExists => {
-                let response = crate::data::resp::BooleanResponse::decode(resp_value)
-                    .map_err(|e| Error::StoreProxyError(format!("Failed to decode FieldExists response: {}", e)))?;
-                Ok(DecodedResponse::FieldExists(response.result))
-            }
-            ResponseType::ResolveIndirection => {
-                let response = crate::data::resp::ResolveIndirectionResponse::decode(resp_value)
-                    .map_err(|e| Error::StoreProxyError(format!("Failed to decode ResolveIndirection response: {}", e)))?;
-                Ok(DecodedResponse::ResolveIndirection((response.entity_id, response.field_type)))
-            }
-            ResponseType::FindEntitiesPaginated => {
-                let response = crate::data::resp::PaginatedEntityResponse::decode(resp_value)
-                    .map_err(|e| Error::StoreProxyError(format!("Failed to decode FindEntitiesPaginated response: {}", e)))?;
-                Ok(DecodedResponse::FindEntitiesPaginated(PageResult {
-                    items: response.items,
-                    total: response.total,
-                    next_cursor: response.next_cursor,
-                }))
-            }
-            ResponseType::FindEntitiesExact => {
-                let response = crate::data::resp::PaginatedEntityResponse::decode(resp_value)
-                    .map_err(|e| Error::StoreProxyError(format!("Failed to decode FindEntitiesExact response: {}", e)))?;
-                Ok(DecodedResponse::FindEntitiesExact(PageResult {
-                    items: response.items,
-                    total: response.total,
-                    next_cursor: response.next_cursor,
-                }))
-            }
-            ResponseType::FindEntities => {
-                let response = crate::data::resp::EntityListResponse::decode(resp_value)
-                    .map_err(|e| Error::StoreProxyError(format!("Failed to decode FindEntities response: {}", e)))?;
-                Ok(DecodedResponse::FindEntities(response.entities))
-            }
-            ResponseType::GetEntityTypes => {
-                let response = crate::data::resp::EntityTypeListResponse::decode(resp_value)
-                    .map_err(|e| Error::StoreProxyError(format!("Failed to decode GetEntityTypes response: {}", e)))?;
-                Ok(DecodedResponse::GetEntityTypes(response.entity_types))
-            }
-            ResponseType::GetEntityTypesPaginated => {
-                let response = crate::data::resp::PaginatedEntityTypeResponse::decode(resp_value)
-                    .map_err(|e| Error::StoreProxyError(format!("Failed to decode GetEntityTypesPaginated response: {}", e)))?;
-                Ok(DecodedResponse::GetEntityTypesPaginated(PageResult {
-                    items: response.items,
-                    total: response.total,
-                    next_cursor: response.next_cursor,
-                }))
-            }
-            ResponseType::TakeSnapshot => {
-                let response = crate::data::resp::SnapshotResponse::decode(resp_value)
-                    .map_err(|e| Error::StoreProxyError(format!("Failed to decode TakeSnapshot response: {}", e)))?;
-                Ok(DecodedResponse::TakeSnapshot(response.data))
-            }
+            };
+            responses.push(response);
         }
+
+        Ok(PipelineResults { responses })
     }
 
     /// Get the number of queued commands
@@ -0,0 +1,375 @@
+use rustc_hash::FxHashMap;
+
+use crate::data::resp::{FullSyncRequestCommand, FullSyncResponseCommand, PeerHandshakeCommand, SyncWriteCommand};
+use crate::{
+    now, AdjustBehavior, Duration, EntityId, Error, FieldType, HlcTimestamp, MergePolicy, Result, Snapshot, Store, StoreTrait, WriteInfo,
+    DEFAULT_MAX_CLOCK_SKEW,
+};
+
+/// Handshake state of a single peer as seen by the local replicator
+#[derive(Debug, Clone, PartialEq)]
+pub struct PeerInfo {
+    pub machine_id: String,
+    pub start_time: u64,
+    /// True once the peer has answered our handshake (or we answered theirs)
+    pub handshake_complete: bool,
+    /// True once a full sync has been exchanged with this peer
+    pub synced: bool,
+    /// How far the peer's clock was ahead of ours (negative if behind) at
+    /// its last handshake, less the time the handshake took to arrive. None
+    /// if the peer didn't send its clock.
+    pub clock_skew: Option<Duration>,
+}
+
+/// Drives peer-to-peer replication of a local `Store` using the peer RESP
+/// commands (HANDSHAKE, FSYNCREQ, FSYNCRESP, SYNCSET).
+///
+/// The replicator does not own any sockets; the caller is responsible for
+/// delivering the commands it produces and feeding back the ones it receives.
+/// This keeps it usable from both mio and tokio based servers.
+///
+/// Startup protocol:
+/// - Both sides send a handshake carrying their start time.
+/// - The side that started later requests a full sync from the older one,
+///   which is considered the source of truth.
+/// - Afterwards, each side periodically streams its write queue to its peers
+///   as SYNCSET batches. Remote field updates are reconciled according to the
+///   field's `MergePolicy` (last-writer-wins by write timestamp by default).
+///
+/// Handshakes carry the sender's clock. A peer whose clock is off ours by
+/// more than `max_clock_skew` is logged as a warning and listed by
+/// `skewed_peers`, since last-writer-wins goes by write times; stores with
+/// `Store::enable_hlc` keep causal order anyway.
+#[derive(Debug)]
+pub struct PeerReplicator {
+    pub machine_id: String,
+    pub start_time: u64,
+    /// Clock skew above which a peer is reported
+    pub max_clock_skew: Duration,
+    peers: FxHashMap<String, PeerInfo>,
+    /// Clock of the last counter delta applied from each peer, per field,
+    /// so a batch that is delivered again isn't counted twice
+    counted: FxHashMap<CounterKey, HlcTimestamp>,
+}
+
+/// Peer a counter delta came from, and the field it adjusts
+type CounterKey = (String, EntityId, FieldType);
+
+/// Name the replicator reads the store's write queue under
+pub const REPLICATION_WRITE_READER: &str = "replication";
+
+impl PeerReplicator {
+    pub fn new(machine_id: impl Into<String>, start_time: u64) -> Self {
+        Self {
+            machine_id: machine_id.into(),
+            start_time,
+            max_clock_skew: DEFAULT_MAX_CLOCK_SKEW,
+            peers: FxHashMap::default(),
+            counted: FxHashMap::default(),
+        }
+    }
+
+    /// Start reading `store`'s write queue, so writes queued from now on
+    /// are kept until they are sent to the peers
+    pub fn attach(&self, store: &mut Store) {
+        store.add_write_reader(REPLICATION_WRITE_READER);
+    }
+
+    /// Whether `store` has queued writes the peers haven't been sent
+    pub fn has_unsent_writes(&self, store: &Store) -> bool {
+        store.unread_writes(REPLICATION_WRITE_READER).next().is_some()
+    }
+
+    /// Build the handshake to send to a newly connected peer
+    pub fn handshake(&self) -> PeerHandshakeCommand<'static> {
+        PeerHandshakeCommand {
+            start_time: self.start_time,
+            is_response: false,
+            machine_id: self.machine_id.clone(),
+            clock: HlcTimestamp::from_timestamp(now()),
+            _marker: std::marker::PhantomData,
+        }
+    }
+
+    /// Process a handshake received from a peer.
+    ///
+    /// Returns the handshake response to send back (if the peer initiated the
+    /// exchange) and a full sync request if the peer is older than us and we
+    /// should adopt its state.
+    pub fn handle_handshake(
+        &mut self,
+        handshake: &PeerHandshakeCommand,
+    ) -> (Option<PeerHandshakeCommand<'static>>, Option<FullSyncRequestCommand<'static>>) {
+        let peer = self.peers
+            .entry(handshake.machine_id.clone())
+            .or_insert_with(|| PeerInfo {
+                machine_id: handshake.machine_id.clone(),
+                start_time: handshake.start_time,
+                handshake_complete: false,
+                synced: false,
+                clock_skew: None,
+            });
+        peer.start_time = handshake.start_time;
+        peer.handshake_complete = true;
+
+        let local_time = now();
+        if handshake.clock.wall != 0 {
+            let skew = handshake.clock.to_timestamp() - local_time;
+            if skew.abs() > self.max_clock_skew {
+                log::warn!(
+                    "Clock of peer '{}' is {} off ours, more than the {} allowed",
+                    handshake.machine_id, skew, self.max_clock_skew
+                );
+            }
+            peer.clock_skew = Some(skew);
+        }
+
+        let response = if handshake.is_response {
+            None
+        } else {
+            Some(PeerHandshakeCommand {
+                start_time: self.start_time,
+                is_response: true,
+                machine_id: self.machine_id.clone(),
+                clock: HlcTimestamp::from_timestamp(local_time),
+                _marker: std::marker::PhantomData,
+            })
+        };
+
+        // Ties are broken by machine id so exactly one side requests the sync
+        let peer_is_older = (handshake.start_time, handshake.machine_id.as_str())
+            < (self.start_time, self.machine_id.as_str());
+        let sync_request = if peer_is_older && !peer.synced {
+            Some(FullSyncRequestCommand {
+                _marker: std::marker::PhantomData,
+            })
+        } else {
+            None
+        };
+
+        (response, sync_request)
+    }
+
+    /// Forget a peer after its connection was closed
+    pub fn remove_peer(&mut self, machine_id: &str) -> Option<PeerInfo> {
+        self.counted.retain(|(peer, _, _), _| peer != machine_id);
+        self.peers.remove(machine_id)
+    }
+
+    /// Get the state of a known peer
+    pub fn peer(&self, machine_id: &str) -> Option<&PeerInfo> {
+        self.peers.get(machine_id)
+    }
+
+    /// Iterate over all known peers
+    pub fn peers(&self) -> impl Iterator<Item = &PeerInfo> {
+        self.peers.values()
+    }
+
+    /// Peers whose clock was off ours by more than `max_clock_skew` at their
+    /// last handshake
+    pub fn skewed_peers(&self) -> impl Iterator<Item = &PeerInfo> {
+        self.peers
+            .values()
+            .filter(|peer| peer.clock_skew.is_some_and(|skew| skew.abs() > self.max_clock_skew))
+    }
+
+    /// Build the response to a full sync request from the local store state
+    pub fn full_sync_response(&self, store: &Store) -> Result<FullSyncResponseCommand<'static>> {
+        let snapshot_data = serde_json::to_string(&store.take_snapshot())
+            .map_err(|e| Error::InvalidRequest(format!("Failed to serialize snapshot: {}", e)))?;
+
+        Ok(FullSyncResponseCommand {
+            snapshot_data,
+            _marker: std::marker::PhantomData,
+        })
+    }
+
+    /// Replace the local store state with the snapshot received from a peer
+    pub fn apply_full_sync(&mut self, machine_id: &str, store: &mut Store, response: &FullSyncResponseCommand) -> Result<()> {
+        let snapshot: Snapshot = serde_json::from_str(&response.snapshot_data)
+            .map_err(|e| Error::InvalidRequest(format!("Failed to deserialize snapshot: {}", e)))?;
+
+        store.restore_snapshot(snapshot);
+        // Anything queued before the restore describes state that no longer
+        // exists, so it isn't sent to the peers
+        let seq = store.write_seq();
+        store.mark_writes_read(REPLICATION_WRITE_READER, seq);
+
+        if let Some(peer) = self.peers.get_mut(machine_id) {
+            peer.synced = true;
+        }
+
+        Ok(())
+    }
+
+    /// Collect the writes queued since the last batch into a SYNCSET batch
+    /// for the peers. The writes stay queued for the store's other readers
+    /// (such as persistence) until they have read them too.
+    ///
+    /// Returns None if there is nothing to replicate.
+    pub fn drain_sync_writes(&self, store: &mut Store) -> Result<Option<SyncWriteCommand<'static>>> {
+        let seq = store.write_seq();
+        let writes: Vec<&WriteInfo> = store.unread_writes(REPLICATION_WRITE_READER).collect();
+        if writes.is_empty() {
+            store.mark_writes_read(REPLICATION_WRITE_READER, seq);
+            return Ok(None);
+        }
+
+        let requests_data = serde_json::to_string(&writes)
+            .map_err(|e| Error::InvalidRequest(format!("Failed to serialize writes: {}", e)))?;
+        store.mark_writes_read(REPLICATION_WRITE_READER, seq);
+
+        Ok(Some(SyncWriteCommand {
+            requests_data,
+            _marker: std::marker::PhantomData,
+        }))
+    }
+
+    /// Apply a SYNCSET batch received from the peer `machine_id` to the
+    /// local store.
+    ///
+    /// Field updates are reconciled using the field's `MergePolicy`. Under
+    /// last-writer-wins a remote write that is older than the local value is
+    /// discarded by the store. Writes applied here are queued for the
+    /// store's other readers (such as persistence) but not sent back out by
+    /// the replicator, which prevents echo loops.
+    ///
+    /// The batch is applied as a whole: if any write in it fails, none of
+    /// them are, so the sender can resend the batch without it being applied
+    /// twice. Counter deltas that are no newer than the last one applied from
+    /// the same peer are skipped, so a batch delivered twice is only counted
+    /// once.
+    pub fn apply_sync_writes(&mut self, machine_id: &str, store: &mut Store, command: &SyncWriteCommand) -> Result<()> {
+        let writes: Vec<WriteInfo> = serde_json::from_str(&command.requests_data)
+            .map_err(|e| Error::InvalidRequest(format!("Failed to deserialize writes: {}", e)))?;
+
+        // Writes made by the peer's triggers are part of the batch already
+        let triggers_disabled = store.triggers_disabled();
+        store.disable_triggers();
+
+        let seq = store.write_seq();
+        let mut counted = FxHashMap::default();
+        let result = store.atomically(|store| {
+            writes
+                .into_iter()
+                .try_for_each(|write| self.apply_write(machine_id, store, write, &mut counted))
+        });
+        store.skip_writes(REPLICATION_WRITE_READER, seq);
+
+        if !triggers_disabled {
+            store.enable_triggers();
+        }
+
+        if result.is_ok() {
+            self.counted.extend(counted);
+        }
+        result
+    }
+
+    /// Whether a counter delta stamped `stamp` from `key` was applied
+    /// already, by an earlier batch or earlier in this one
+    fn is_counted(&self, key: &CounterKey, stamp: HlcTimestamp, counted: &FxHashMap<CounterKey, HlcTimestamp>) -> bool {
+        counted
+            .get(key)
+            .or_else(|| self.counted.get(key))
+            .is_some_and(|last| stamp <= *last)
+    }
+
+    fn apply_write(&self, machine_id: &str, store: &mut Store, write: WriteInfo, counted: &mut FxHashMap<CounterKey, HlcTimestamp>) -> Result<()> {
+        match write {
+            WriteInfo::FieldUpdate { entity_id, field_type, value, push_condition, adjust_behavior, write_time, writer_id, delta, hlc, .. } => {
+                // Later local writes must read past the peer's clock
+                if let Some(hlc) = hlc {
+                    store.observe_hlc(hlc);
+                }
+                if !store.entity_exists(entity_id) {
+                    return Ok(());
+                }
+
+                let merge_policy = store
+                    .get_complete_entity_schema(entity_id.extract_type())?
+                    .fields
+                    .get(&field_type)
+                    .map(|field_schema| field_schema.merge_policy())
+                    .unwrap_or_default();
+
+                match (merge_policy, delta, value) {
+                    // Replay the adjustment itself so concurrent increments add up.
+                    // The write time only tells deltas apart, since every delta must be
+                    // applied once.
+                    (MergePolicy::Counter, Some(delta), _) if adjust_behavior != AdjustBehavior::Set => {
+                        let stamp = hlc.or_else(|| write_time.map(HlcTimestamp::from_timestamp));
+                        if let Some(stamp) = stamp {
+                            let key = (machine_id.to_string(), entity_id, field_type);
+                            if self.is_counted(&key, stamp, counted) {
+                                return Ok(());
+                            }
+                            counted.insert(key, stamp);
+                        }
+                        store.write(
+                            entity_id,
+                            &[field_type],
+                            delta,
+                            writer_id,
+                            None,
+                            Some(push_condition),
+                            Some(adjust_behavior),
+                        )?;
+                    }
+                    // Removals are replayed as such, everything else is merged into the local list
+                    (MergePolicy::SetUnion, Some(delta), _) if adjust_behavior == AdjustBehavior::Subtract => {
+                        store.write(entity_id, &[field_type], delta, writer_id, None, Some(push_condition), Some(AdjustBehavior::Subtract))?;
+                    }
+                    (MergePolicy::SetUnion, _, Some(value)) => {
+                        store.write(entity_id, &[field_type], value, writer_id, None, Some(push_condition), Some(AdjustBehavior::Add))?;
+                    }
+                    // Last writer wins: the replicated value is already the result of any
+                    // adjustment, and the store discards it if the local value is newer
+                    (_, _, Some(value)) => {
+                        store.write(
+                            entity_id,
+                            &[field_type],
+                            value,
+                            writer_id,
+                            write_time,
+                            Some(push_condition),
+                            None,
+                        )?;
+                    }
+                    (_, _, None) => {}
+                }
+            }
+            WriteInfo::CreateEntity { entity_type, parent_id, name, created_entity_id, .. } => {
+                if !store.entity_exists(created_entity_id) {
+                    let mut created_entity_id = Some(created_entity_id);
+                    store.create_entity_with_id(entity_type, parent_id, &mut created_entity_id, &name)?;
+                }
+            }
+            WriteInfo::DeleteEntity { entity_id, .. } => {
+                if store.entity_exists(entity_id) || store.is_soft_deleted(entity_id) {
+                    store.delete_entity(entity_id)?;
+                }
+            }
+            WriteInfo::SoftDeleteEntity { entity_id, .. } => {
+                if store.entity_exists(entity_id) {
+                    store.soft_delete_entity(entity_id)?;
+                }
+            }
+            WriteInfo::RestoreEntity { entity_id, .. } => {
+                if store.is_soft_deleted(entity_id) {
+                    store.restore_entity(entity_id)?;
+                }
+            }
+            WriteInfo::SchemaUpdate { schema, .. } => {
+                let schema = schema.to_string_schema(store);
+                store.update_schema(schema)?;
+            }
+            WriteInfo::Snapshot { .. } => {
+                // Snapshots are local bookkeeping and are not replicated
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -97,6 +97,45 @@ use crate::{
 #[cfg(feature = "derive")]
 pub use qlib_rs_derive::{RespEncode, RespDecode, respc};
 
+/// Default limit on the size of a single RESP frame
+pub const MAX_MESSAGE_SIZE: usize = 16 * 1024 * 1024;
+
+/// Deepest nesting of arrays the parser accepts, so a hostile frame can't
+/// exhaust the stack
+pub const MAX_NESTING_DEPTH: usize = 128;
+
+/// Per-connection limits on RESP frames
+///
+/// Clients check outgoing commands against the limit before sending them and
+/// stop buffering a response once it grows past it; servers can call
+/// [`ProtocolLimits::check_frame`] on incoming data the same way.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProtocolLimits {
+    pub max_message_size: usize,
+}
+
+impl Default for ProtocolLimits {
+    fn default() -> Self {
+        ProtocolLimits {
+            max_message_size: MAX_MESSAGE_SIZE,
+        }
+    }
+}
+
+impl ProtocolLimits {
+    pub fn new(max_message_size: usize) -> Self {
+        ProtocolLimits { max_message_size }
+    }
+
+    /// Fail with `Error::FrameTooLarge` if a frame of `size` bytes is over the limit
+    pub fn check_frame(&self, size: usize) -> crate::Result<()> {
+        if size > self.max_message_size {
+            return Err(crate::Error::FrameTooLarge(size, self.max_message_size));
+        }
+        Ok(())
+    }
+}
+
 /// Redis RESP data types with zero-copy deserialization support
 ///
 /// # Examples
@@ -162,121 +201,73 @@ pub enum OwnedRespValue {
 }
 
 pub trait RespToBytes {
-    fn to_bytes(&self) -> Vec<u8>;
+    /// Append the encoding to `out`, e.g. a buffer from `buffer_pool::take_buffer`
+    fn write_to(&self, out: &mut Vec<u8>);
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut result = Vec::new();
+        self.write_to(&mut result);
+        result
+    }
 }
 
 pub trait RespFromBytes<'a>: Sized {
     fn from_bytes(input: &'a [u8]) -> Result<(Self, &'a [u8])>;
 }
 
+fn write_line(out: &mut Vec<u8>, prefix: u8, line: &[u8]) {
+    out.reserve(line.len() + 3);
+    out.push(prefix);
+    out.extend_from_slice(line);
+    out.extend_from_slice(b"\r\n");
+}
+
+fn write_number(out: &mut Vec<u8>, prefix: u8, number: i64) {
+    let mut buf = itoa::Buffer::new();
+    write_line(out, prefix, buf.format(number).as_bytes());
+}
+
+fn write_bulk_string(out: &mut Vec<u8>, data: &[u8]) {
+    write_number(out, b'$', data.len() as i64);
+    out.reserve(data.len() + 2);
+    out.extend_from_slice(data);
+    out.extend_from_slice(b"\r\n");
+}
+
+const NULL_BYTES: &[u8] = b"$-1\r\n";
+
 impl<'a> RespToBytes for RespValue<'a> {
-    fn to_bytes(&self) -> Vec<u8> {
+    fn write_to(&self, out: &mut Vec<u8>) {
         match self {
-            RespValue::SimpleString(s) => {
-                let mut result = Vec::with_capacity(s.len() + 3);
-                result.push(b'+');
-                result.extend_from_slice(s.as_bytes());
-                result.extend_from_slice(b"\r\n");
-                result
-            },
-            RespValue::Error(e) => {
-                let mut result = Vec::with_capacity(e.len() + 3);
-                result.push(b'-');
-                result.extend_from_slice(e.as_bytes());
-                result.extend_from_slice(b"\r\n");
-                result
-            },
-            RespValue::Integer(i) => {
-                let mut result = Vec::with_capacity(24); // Max i64 is 20 digits + ':' + '\r\n'
-                result.push(b':');
-                let mut buf = itoa::Buffer::new();
-                result.extend_from_slice(buf.format(*i).as_bytes());
-                result.extend_from_slice(b"\r\n");
-                result
-            },
-            RespValue::BulkString(data) => {
-                let mut result = Vec::with_capacity(data.len() + 25); // data + max length digits + markers
-                result.push(b'$');
-                let mut buf = itoa::Buffer::new();
-                result.extend_from_slice(buf.format(data.len()).as_bytes());
-                result.extend_from_slice(b"\r\n");
-                result.extend_from_slice(data);
-                result.extend_from_slice(b"\r\n");
-                result
-            },
+            RespValue::SimpleString(s) => write_line(out, b'+', s.as_bytes()),
+            RespValue::Error(e) => write_line(out, b'-', e.as_bytes()),
+            RespValue::Integer(i) => write_number(out, b':', *i),
+            RespValue::BulkString(data) => write_bulk_string(out, data),
             RespValue::Array(elements) => {
-                // Pre-allocate: rough estimate of 50 bytes per element
-                let mut result = Vec::with_capacity(elements.len() * 50 + 25);
-                result.push(b'*');
-                let mut buf = itoa::Buffer::new();
-                result.extend_from_slice(buf.format(elements.len()).as_bytes());
-                result.extend_from_slice(b"\r\n");
-                
+                write_number(out, b'*', elements.len() as i64);
                 for element in elements {
-                    result.extend_from_slice(&element.to_bytes());
+                    element.write_to(out);
                 }
-                result
-            },
-            RespValue::Null => {
-                const NULL_BYTES: &[u8] = b"$-1\r\n";
-                NULL_BYTES.to_vec()
             },
+            RespValue::Null => out.extend_from_slice(NULL_BYTES),
         }
     }
 }
 
 impl RespToBytes for OwnedRespValue {
-    fn to_bytes(&self) -> Vec<u8> {
+    fn write_to(&self, out: &mut Vec<u8>) {
         match self {
-            OwnedRespValue::SimpleString(s) => {
-                let mut result = Vec::with_capacity(s.len() + 3);
-                result.push(b'+');
-                result.extend_from_slice(s.as_bytes());
-                result.extend_from_slice(b"\r\n");
-                result
-            },
-            OwnedRespValue::Error(s) => {
-                let mut result = Vec::with_capacity(s.len() + 3);
-                result.push(b'-');
-                result.extend_from_slice(s.as_bytes());
-                result.extend_from_slice(b"\r\n");
-                result
-            },
-            OwnedRespValue::Integer(i) => {
-                let mut result = Vec::with_capacity(24); // Max i64 is 20 digits + ':' + '\r\n'
-                result.push(b':');
-                let mut buf = itoa::Buffer::new();
-                result.extend_from_slice(buf.format(*i).as_bytes());
-                result.extend_from_slice(b"\r\n");
-                result
-            },
-            OwnedRespValue::BulkString(data) => {
-                let mut result = Vec::with_capacity(data.len() + 25); // data + max length digits + markers
-                result.push(b'$');
-                let mut buf = itoa::Buffer::new();
-                result.extend_from_slice(buf.format(data.len()).as_bytes());
-                result.extend_from_slice(b"\r\n");
-                result.extend_from_slice(data);
-                result.extend_from_slice(b"\r\n");
-                result
-            },
+            OwnedRespValue::SimpleString(s) => write_line(out, b'+', s.as_bytes()),
+            OwnedRespValue::Error(e) => write_line(out, b'-', e.as_bytes()),
+            OwnedRespValue::Integer(i) => write_number(out, b':', *i),
+            OwnedRespValue::BulkString(data) => write_bulk_string(out, data),
             OwnedRespValue::Array(elements) => {
-                // Pre-allocate: rough estimate of 50 bytes per element
-                let mut result = Vec::with_capacity(elements.len() * 50 + 25);
-                result.push(b'*');
-                let mut buf = itoa::Buffer::new();
-                result.extend_from_slice(buf.format(elements.len()).as_bytes());
-                result.extend_from_slice(b"\r\n");
-                
+                write_number(out, b'*', elements.len() as i64);
                 for element in elements {
-                    result.extend_from_slice(&element.to_bytes());
+                    element.write_to(out);
                 }
-                result
-            },
-            OwnedRespValue::Null => {
-                const NULL_BYTES: &[u8] = b"$-1\r\n";
-                NULL_BYTES.to_vec()
             },
+            OwnedRespValue::Null => out.extend_from_slice(NULL_BYTES),
         }
     }
 }
@@ -291,6 +282,11 @@ impl <'a> RespFromBytes<'a> for RespValue<'a> {
 pub trait RespEncode {
     /// Serialize to RESP format
     fn encode(&self) -> OwnedRespValue;
+
+    /// Serialize straight to bytes, appended to `out`
+    fn encode_into(&self, out: &mut Vec<u8>) {
+        self.encode().write_to(out);
+    }
 }
 
 /// Trait for zero-copy RESP deserialization
@@ -341,19 +337,378 @@ impl std::fmt::Display for RespError {
 
 impl std::error::Error for RespError {}
 
+/// Protocol-level failures reported to clients as RESP error frames.
+///
+/// Each variant has a stable code that starts the frame, e.g.
+/// `-ERR_ARITY GET expected 3 got 2`, so clients can recover the typed error
+/// with [`ProtocolError::from_frame`] instead of matching on message text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProtocolError {
+    /// No command with this name is registered
+    UnknownCommand(String),
+    /// (command, expected element count, actual element count)
+    ArityMismatch(String, usize, usize),
+    /// The bytes received are not valid RESP
+    MalformedFrame(String),
+    /// (frame size, limit)
+    FrameTooLarge(usize, usize),
+    /// The command is known but not supported by this server
+    Unsupported(String),
+    /// The command ran past the timeout it was sent with (milliseconds)
+    Timeout(u64),
+    /// The command was cancelled (request id)
+    Cancelled(u64),
+    /// The server is draining before shutdown and takes no new commands
+    ShuttingDown,
+}
+
+impl ProtocolError {
+    pub const UNKNOWN_COMMAND: &'static str = "ERR_UNKNOWN_COMMAND";
+    pub const ARITY: &'static str = "ERR_ARITY";
+    pub const MALFORMED: &'static str = "ERR_MALFORMED";
+    pub const FRAME_TOO_LARGE: &'static str = "ERR_FRAME_TOO_LARGE";
+    pub const UNSUPPORTED: &'static str = "ERR_UNSUPPORTED";
+    pub const TIMEOUT: &'static str = "ERR_TIMEOUT";
+    pub const CANCELLED: &'static str = "ERR_CANCELLED";
+    pub const SHUTTING_DOWN: &'static str = "ERR_SHUTTING_DOWN";
+
+    /// The stable code this error is sent with
+    pub fn code(&self) -> &'static str {
+        match self {
+            ProtocolError::UnknownCommand(_) => Self::UNKNOWN_COMMAND,
+            ProtocolError::ArityMismatch(..) => Self::ARITY,
+            ProtocolError::MalformedFrame(_) => Self::MALFORMED,
+            ProtocolError::FrameTooLarge(..) => Self::FRAME_TOO_LARGE,
+            ProtocolError::Unsupported(_) => Self::UNSUPPORTED,
+            ProtocolError::Timeout(_) => Self::TIMEOUT,
+            ProtocolError::Cancelled(_) => Self::CANCELLED,
+            ProtocolError::ShuttingDown => Self::SHUTTING_DOWN,
+        }
+    }
+
+    /// Encode as a RESP error frame
+    pub fn to_resp(&self) -> OwnedRespValue {
+        OwnedRespValue::Error(self.to_string())
+    }
+
+    /// Parse the text of an error frame written by [`ProtocolError::to_resp`].
+    /// Returns None for frames without a known code.
+    pub fn from_frame(message: &str) -> Option<ProtocolError> {
+        let (code, detail) = message.split_once(' ').unwrap_or((message, ""));
+        match code {
+            Self::UNKNOWN_COMMAND => Some(ProtocolError::UnknownCommand(detail.to_string())),
+            Self::ARITY => match detail.split(' ').collect::<Vec<_>>()[..] {
+                [command, "expected", expected, "got", got] => Some(ProtocolError::ArityMismatch(
+                    command.to_string(),
+                    expected.parse().ok()?,
+                    got.parse().ok()?,
+                )),
+                _ => None,
+            },
+            Self::MALFORMED => Some(ProtocolError::MalformedFrame(detail.to_string())),
+            Self::FRAME_TOO_LARGE => match detail.split(' ').collect::<Vec<_>>()[..] {
+                ["size", size, "limit", limit] => Some(ProtocolError::FrameTooLarge(size.parse().ok()?, limit.parse().ok()?)),
+                _ => None,
+            },
+            Self::UNSUPPORTED => Some(ProtocolError::Unsupported(detail.to_string())),
+            Self::TIMEOUT => match detail.split(' ').collect::<Vec<_>>()[..] {
+                ["after", millis, "ms"] => Some(ProtocolError::Timeout(millis.parse().ok()?)),
+                _ => None,
+            },
+            Self::CANCELLED => Some(ProtocolError::Cancelled(detail.parse().ok()?)),
+            Self::SHUTTING_DOWN => Some(ProtocolError::ShuttingDown),
+            _ => None,
+        }
+    }
+
+    /// The protocol error to send back for a failed request, if the failure
+    /// was at the protocol level rather than in the store
+    pub fn from_error(error: &crate::Error) -> Option<ProtocolError> {
+        match error {
+            crate::Error::ProtocolError(e) => Some(e.clone()),
+            crate::Error::FrameTooLarge(size, limit) => Some(ProtocolError::FrameTooLarge(*size, *limit)),
+            crate::Error::Timeout(timeout) => Some(ProtocolError::Timeout(timeout.as_millis() as u64)),
+            crate::Error::Cancelled(request_id) => Some(ProtocolError::Cancelled(*request_id)),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for ProtocolError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProtocolError::UnknownCommand(name) => write!(f, "{} {}", self.code(), name),
+            ProtocolError::ArityMismatch(command, expected, got) => write!(f, "{} {} expected {} got {}", self.code(), command, expected, got),
+            ProtocolError::MalformedFrame(msg) => write!(f, "{} {}", self.code(), msg),
+            ProtocolError::FrameTooLarge(size, limit) => write!(f, "{} size {} limit {}", self.code(), size, limit),
+            ProtocolError::Unsupported(msg) => write!(f, "{} {}", self.code(), msg),
+            ProtocolError::Timeout(millis) => write!(f, "{} after {} ms", self.code(), millis),
+            ProtocolError::Cancelled(request_id) => write!(f, "{} {}", self.code(), request_id),
+            ProtocolError::ShuttingDown => write!(f, "{}", self.code()),
+        }
+    }
+}
+
+impl std::error::Error for ProtocolError {}
+
+impl From<ProtocolError> for crate::Error {
+    fn from(error: ProtocolError) -> Self {
+        match error {
+            ProtocolError::FrameTooLarge(size, limit) => crate::Error::FrameTooLarge(size, limit),
+            ProtocolError::Timeout(millis) => crate::Error::Timeout(std::time::Duration::from_millis(millis)),
+            ProtocolError::Cancelled(request_id) => crate::Error::Cancelled(request_id),
+            other => crate::Error::ProtocolError(other),
+        }
+    }
+}
+
+/// Encode an error as a RESP error frame that [`error_from_frame`] turns
+/// back into the same variant.
+///
+/// Protocol failures are sent as [`ProtocolError`] frames. Any other error
+/// is sent as its [`code`](crate::Error::code), its parameters as a JSON
+/// array and then its message for whoever reads the frame, e.g.
+/// `-ERR_ENTITY_NOT_FOUND [4294967303] Entity not found: EntityId(4294967303)`.
+pub fn error_to_frame(error: &crate::Error) -> OwnedRespValue {
+    if let Some(protocol_error) = ProtocolError::from_error(error) {
+        return protocol_error.to_resp();
+    }
+    // Error frames are a single line
+    let message = error.to_string().replace(['\r', '\n'], " ");
+    OwnedRespValue::Error(format!("{} {} {}", error.code(), error_params(error), message))
+}
+
+fn error_params(error: &crate::Error) -> serde_json::Value {
+    use crate::Error as E;
+    use serde_json::json;
+    match error {
+        E::BadIndirection(entity_id, fields, reason) => json!([entity_id, fields, reason]),
+        E::EntityAlreadyExists(entity_id) | E::EntityNotFound(entity_id) | E::NotLeader(entity_id) => json!([entity_id]),
+        E::EntityTypeNotFound(entity_type) => json!([entity_type]),
+        E::CacheFieldNotFound(field_type) => json!([field_type]),
+        E::FieldTypeNotFound(entity_id, field_type) => json!([entity_id, field_type]),
+        E::EntityNameNotFound(text)
+        | E::EntityTypeStrNotFound(text)
+        | E::FieldTypeStrNotFound(text)
+        | E::InvalidFieldType(text)
+        | E::InvalidFieldValue(text)
+        | E::InvalidNotifyConfig(text)
+        | E::InvalidRequest(text)
+        | E::RateLimited(text)
+        | E::PasswordHashError(text)
+        | E::InvalidPassword(text)
+        | E::AuthenticationMethodNotImplemented(text)
+        | E::StoreProxyError(text)
+        | E::ExecutionError(text) => json!([text]),
+        E::UnsupportedAdjustBehavior(entity_id, field_type, behavior) => json!([entity_id, field_type, behavior]),
+        E::ValueTypeMismatch(entity_id, field_type, got, expected) => json!([entity_id, field_type, got, expected]),
+        E::BadValueCast(got, expected) => json!([got, expected]),
+        E::StaleFencingToken(entity_id, provided, current) => json!([entity_id, provided, current]),
+        E::EntityReferenced(entity_id, by, field_type) | E::PermissionDenied(entity_id, by, field_type) => json!([entity_id, by, field_type]),
+        E::TypeIdConflict(name, id) => json!([name, id]),
+        E::QuotaExceeded(client, quota, limit) => json!([client, quota, limit]),
+        E::ChecksumMismatch(entity_types) => json!([entity_types]),
+        E::NotificationsDiscarded(subscription, oldest) => json!([subscription, oldest]),
+        // Unit variants, and those sent as protocol errors
+        _ => json!([]),
+    }
+}
+
+/// Rebuild an error written by [`error_to_frame`]. Returns None for frames
+/// without a known code or whose parameters don't fit it.
+fn error_from_code(message: &str) -> Option<crate::Error> {
+    use crate::Error as E;
+
+    fn params<T: serde::de::DeserializeOwned>(params: serde_json::Value) -> Option<T> {
+        serde_json::from_value(params).ok()
+    }
+
+    let (code, rest) = message.split_once(' ')?;
+    let p: serde_json::Value = serde_json::Deserializer::from_str(rest).into_iter().next()?.ok()?;
+    let error = match code {
+        "ERR_BAD_INDIRECTION" => params(p).map(|(entity_id, fields, reason)| E::BadIndirection(entity_id, fields, reason))?,
+        "ERR_ENTITY_ALREADY_EXISTS" => params(p).map(|(entity_id,)| E::EntityAlreadyExists(entity_id))?,
+        "ERR_ENTITY_NOT_FOUND" => params(p).map(|(entity_id,)| E::EntityNotFound(entity_id))?,
+        "ERR_ENTITY_NAME_NOT_FOUND" => params(p).map(|(name,)| E::EntityNameNotFound(name))?,
+        "ERR_ENTITY_TYPE_NOT_FOUND" => params(p).map(|(entity_type,)| E::EntityTypeNotFound(entity_type))?,
+        "ERR_ENTITY_TYPE_NAME_NOT_FOUND" => params(p).map(|(name,)| E::EntityTypeStrNotFound(name))?,
+        "ERR_CACHE_FIELD_NOT_FOUND" => params(p).map(|(field_type,)| E::CacheFieldNotFound(field_type))?,
+        "ERR_FIELD_NOT_FOUND" => params(p).map(|(entity_id, field_type)| E::FieldTypeNotFound(entity_id, field_type))?,
+        "ERR_FIELD_NAME_NOT_FOUND" => params(p).map(|(name,)| E::FieldTypeStrNotFound(name))?,
+        "ERR_INVALID_FIELD_TYPE" => params(p).map(|(msg,)| E::InvalidFieldType(msg))?,
+        "ERR_INVALID_FIELD_VALUE" => params(p).map(|(msg,)| E::InvalidFieldValue(msg))?,
+        "ERR_INVALID_NOTIFY_CONFIG" => params(p).map(|(msg,)| E::InvalidNotifyConfig(msg))?,
+        "ERR_UNSUPPORTED_ADJUST_BEHAVIOR" => {
+            params(p).map(|(entity_id, field_type, behavior)| E::UnsupportedAdjustBehavior(entity_id, field_type, behavior))?
+        }
+        "ERR_VALUE_TYPE_MISMATCH" => {
+            params(p).map(|(entity_id, field_type, got, expected)| E::ValueTypeMismatch(entity_id, field_type, got, expected))?
+        }
+        "ERR_BAD_VALUE_CAST" => params(p).map(|(got, expected)| E::BadValueCast(got, expected))?,
+        "ERR_INVALID_REQUEST" => params(p).map(|(msg,)| E::InvalidRequest(msg))?,
+        "ERR_STALE_FENCING_TOKEN" => params(p).map(|(entity_id, provided, current)| E::StaleFencingToken(entity_id, provided, current))?,
+        "ERR_ENTITY_REFERENCED" => params(p).map(|(entity_id, by, field_type)| E::EntityReferenced(entity_id, by, field_type))?,
+        "ERR_PERMISSION_DENIED" => params(p).map(|(subject, entity_id, field_type)| E::PermissionDenied(subject, entity_id, field_type))?,
+        "ERR_TYPE_ID_CONFLICT" => params(p).map(|(name, id)| E::TypeIdConflict(name, id))?,
+        "ERR_RATE_LIMITED" => params(p).map(|(client,)| E::RateLimited(client))?,
+        "ERR_QUOTA_EXCEEDED" => params(p).map(|(client, quota, limit)| E::QuotaExceeded(client, quota, limit))?,
+        "ERR_CHECKSUM_MISMATCH" => params(p).map(|(entity_types,)| E::ChecksumMismatch(entity_types))?,
+        "ERR_NOT_LEADER" => params(p).map(|(candidate,)| E::NotLeader(candidate))?,
+        "ERR_NOTIFICATIONS_DISCARDED" => params(p).map(|(subscription, oldest)| E::NotificationsDiscarded(subscription, oldest))?,
+        "ERR_INVALID_CREDENTIALS" => E::InvalidCredentials,
+        "ERR_ACCOUNT_DISABLED" => E::AccountDisabled,
+        "ERR_ACCOUNT_LOCKED" => E::AccountLocked,
+        "ERR_SUBJECT_NOT_FOUND" => E::SubjectNotFound,
+        "ERR_PASSWORD_HASH" => params(p).map(|(msg,)| E::PasswordHashError(msg))?,
+        "ERR_INVALID_NAME" => E::InvalidName,
+        "ERR_INVALID_PASSWORD" => params(p).map(|(msg,)| E::InvalidPassword(msg))?,
+        "ERR_SUBJECT_ALREADY_EXISTS" => E::SubjectAlreadyExists,
+        "ERR_INVALID_AUTHENTICATION_METHOD" => E::InvalidAuthenticationMethod,
+        "ERR_AUTHENTICATION_METHOD_NOT_IMPLEMENTED" => params(p).map(|(method,)| E::AuthenticationMethodNotImplemented(method))?,
+        "ERR_STORE_PROXY" => params(p).map(|(msg,)| E::StoreProxyError(msg))?,
+        "ERR_CONNECTION_LOST" => E::ConnectionLost,
+        "ERR_EXECUTION" => params(p).map(|(msg,)| E::ExecutionError(msg))?,
+        _ => return None,
+    };
+    Some(error)
+}
+
+/// Convert an error frame received from the server into an Error, keeping
+/// protocol errors and errors written by [`error_to_frame`] typed
+pub fn error_from_frame(message: &str) -> crate::Error {
+    if let Some(error) = ProtocolError::from_frame(message) {
+        return error.into();
+    }
+    match error_from_code(message) {
+        Some(error) => error,
+        None => crate::Error::StoreProxyError(format!("Server error: {}", message)),
+    }
+}
+
+/// Name of the frame that carries a correlation id, see [`encode_tagged`]
+pub const TAGGED: &str = "TAGGED";
+
+/// Wrap an encoded command or reply in a frame carrying `correlation_id`:
+/// `["TAGGED", correlation_id, frame]`.
+///
+/// Tagging is optional and chosen per request. A server that receives a
+/// tagged command runs the command it carries and tags the reply with the
+/// same id, so a client can have many requests in flight on one connection
+/// and match replies that arrive out of order. Untagged commands keep the
+/// usual in-order replies, and notifications are never tagged.
+pub fn encode_tagged(correlation_id: u64, frame: &[u8]) -> Vec<u8> {
+    let mut result = Vec::with_capacity(frame.len() + 40);
+    result.extend_from_slice(b"*3\r\n$6\r\nTAGGED\r\n:");
+    let mut buf = itoa::Buffer::new();
+    result.extend_from_slice(buf.format(correlation_id).as_bytes());
+    result.extend_from_slice(b"\r\n");
+    result.extend_from_slice(frame);
+    result
+}
+
+/// Split a tagged frame into its correlation id and the frame it carries.
+/// Returns `None` if `value` isn't tagged.
+pub fn decode_tagged<'v, 'a>(value: &'v RespValue<'a>) -> Option<(u64, &'v RespValue<'a>)> {
+    let RespValue::Array(elements) = value else {
+        return None;
+    };
+    let [name, RespValue::Integer(correlation_id), frame] = elements.as_slice() else {
+        return None;
+    };
+    let is_tagged = match name {
+        RespValue::BulkString(name) => *name == TAGGED.as_bytes(),
+        RespValue::SimpleString(name) => *name == TAGGED,
+        _ => false,
+    };
+    if !is_tagged || *correlation_id < 0 {
+        return None;
+    }
+    Some((*correlation_id as u64, frame))
+}
+
+fn malformed(msg: &str) -> crate::Error {
+    ProtocolError::MalformedFrame(msg.to_string()).into()
+}
+
+/// Value of eight ASCII digits, most significant first, or `None` if any
+/// byte isn't a digit. Works on all eight at once within a `u64`.
+#[inline]
+fn parse_eight_digits(chunk: &[u8]) -> Option<u64> {
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(chunk);
+    let v = u64::from_le_bytes(bytes);
+
+    // Every byte must be 0x30..=0x39
+    const HIGH_NIBBLES: u64 = 0xF0F0_F0F0_F0F0_F0F0;
+    const ZEROS: u64 = 0x3030_3030_3030_3030;
+    if v & HIGH_NIBBLES != ZEROS || v.wrapping_add(0x0606_0606_0606_0606) & HIGH_NIBBLES != ZEROS {
+        return None;
+    }
+
+    // Combine neighbouring digits, then pairs, then quads
+    let v = (v & 0x0F0F_0F0F_0F0F_0F0F).wrapping_mul(2561) >> 8;
+    let v = (v & 0x00FF_00FF_00FF_00FF).wrapping_mul(6_553_601) >> 16;
+    Some((v & 0x0000_FFFF_0000_FFFF).wrapping_mul(42_949_672_960_001) >> 32)
+}
+
+/// Parse an optionally signed decimal integer straight from bytes, without
+/// going through `str`. Returns `None` for anything `i64::from_str` would
+/// reject, apart from a leading `+`, which RESP never sends.
+pub fn parse_i64_bytes(input: &[u8]) -> Option<i64> {
+    let (negative, digits) = match input.split_first() {
+        Some((b'-', rest)) => (true, rest),
+        _ => (false, input),
+    };
+    if digits.is_empty() {
+        return None;
+    }
+
+    let mut magnitude: u64 = 0;
+    let mut chunks = digits.chunks_exact(8);
+    for chunk in &mut chunks {
+        magnitude = magnitude.checked_mul(100_000_000)?.checked_add(parse_eight_digits(chunk)?)?;
+    }
+    for &byte in chunks.remainder() {
+        let digit = byte.wrapping_sub(b'0');
+        if digit > 9 {
+            return None;
+        }
+        magnitude = magnitude.checked_mul(10)?.checked_add(digit as u64)?;
+    }
+
+    if negative {
+        if magnitude == 1 << 63 {
+            Some(i64::MIN)
+        } else {
+            i64::try_from(magnitude).ok().map(|m| -m)
+        }
+    } else {
+        i64::try_from(magnitude).ok()
+    }
+}
+
 /// Zero-copy RESP parser
 pub struct RespParser;
 
 impl RespParser {
     /// Find the end of a RESP line (\r\n)
     fn find_line_end(input: &[u8]) -> Option<usize> {
-        for i in 0..input.len().saturating_sub(1) {
-            if input[i] == b'\r' && input[i + 1] == b'\n' {
-                return Some(i);
+        let mut start = 0;
+        while let Some(offset) = memchr::memchr(b'\r', &input[start..]) {
+            let i = start + offset;
+            match input.get(i + 1) {
+                Some(b'\n') => return Some(i),
+                Some(_) => start = i + 1,
+                None => return None,
             }
         }
         None
     }
+
+    /// Parse the number on a header line, e.g. a bulk string length
+    fn parse_header_number(bytes: &[u8], what: &str) -> Result<i64> {
+        parse_i64_bytes(bytes).ok_or_else(|| malformed(what))
+    }
     
     /// Parse a simple string (+<string>\r\n)
     pub fn parse_simple_string(input: &[u8]) -> Result<(&str, &[u8])> {
@@ -366,7 +721,7 @@ impl RespParser {
             
         let str_bytes = &input[1..line_end + 1];
         let string = std::str::from_utf8(str_bytes)
-            .map_err(|_| crate::Error::InvalidRequest("Invalid UTF-8 in simple string".to_string()))?;
+            .map_err(|_| malformed("Invalid UTF-8 in simple string"))?;
             
         let remaining = &input[line_end + 3..]; // Skip \r\n
         Ok((string, remaining))
@@ -383,7 +738,7 @@ impl RespParser {
             
         let str_bytes = &input[1..line_end + 1];
         let string = std::str::from_utf8(str_bytes)
-            .map_err(|_| crate::Error::InvalidRequest("Invalid UTF-8 in error".to_string()))?;
+            .map_err(|_| malformed("Invalid UTF-8 in error"))?;
             
         let remaining = &input[line_end + 3..]; // Skip \r\n
         Ok((string, remaining))
@@ -398,12 +753,7 @@ impl RespParser {
         let line_end = Self::find_line_end(&input[1..])
             .ok_or_else(|| crate::Error::InvalidRequest("Incomplete integer".to_string()))?;
             
-        let str_bytes = &input[1..line_end + 1];
-        let num_str = std::str::from_utf8(str_bytes)
-            .map_err(|_| crate::Error::InvalidRequest("Invalid UTF-8 in integer".to_string()))?;
-            
-        let number = num_str.parse::<i64>()
-            .map_err(|_| crate::Error::InvalidRequest("Invalid integer format".to_string()))?;
+        let number = Self::parse_header_number(&input[1..line_end + 1], "Invalid integer format")?;
             
         let remaining = &input[line_end + 3..]; // Skip \r\n
         Ok((number, remaining))
@@ -418,11 +768,9 @@ impl RespParser {
         let line_end = Self::find_line_end(&input[1..])
             .ok_or_else(|| crate::Error::InvalidRequest("Incomplete bulk string length".to_string()))?;
             
-        let length_str = std::str::from_utf8(&input[1..line_end + 1])
-            .map_err(|_| crate::Error::InvalidRequest("Invalid UTF-8 in bulk string length".to_string()))?;
-            
-        let length = length_str.parse::<i32>()
-            .map_err(|_| crate::Error::InvalidRequest("Invalid bulk string length".to_string()))?;
+        let length = Self::parse_header_number(&input[1..line_end + 1], "Invalid bulk string length")?;
+        let length = i32::try_from(length)
+            .map_err(|_| malformed("Invalid bulk string length"))?;
             
         if length == -1 {
             // Null bulk string
@@ -431,7 +779,7 @@ impl RespParser {
         }
         
         if length < 0 {
-            return Err(crate::Error::InvalidRequest("Invalid bulk string length".to_string()));
+            return Err(malformed("Invalid bulk string length"));
         }
         
         let length = length as usize;
@@ -449,6 +797,10 @@ impl RespParser {
     
     /// Parse an array (*<count>\r\n<element1><element2>...)
     pub fn parse_array(input: &[u8]) -> Result<(Vec<RespValue>, &[u8])> {
+        Self::parse_array_at(input, 0)
+    }
+
+    fn parse_array_at(input: &[u8], depth: usize) -> Result<(Vec<RespValue<'_>>, &[u8])> {
         if input.is_empty() || input[0] != b'*' {
             return Err(crate::Error::InvalidRequest("Not an array".to_string()));
         }
@@ -456,21 +808,25 @@ impl RespParser {
         let line_end = Self::find_line_end(&input[1..])
             .ok_or_else(|| crate::Error::InvalidRequest("Incomplete array count".to_string()))?;
             
-        let count_str = std::str::from_utf8(&input[1..line_end + 1])
-            .map_err(|_| crate::Error::InvalidRequest("Invalid UTF-8 in array count".to_string()))?;
-            
-        let count = count_str.parse::<i32>()
-            .map_err(|_| crate::Error::InvalidRequest("Invalid array count".to_string()))?;
+        let count = Self::parse_header_number(&input[1..line_end + 1], "Invalid array count")?;
+        let count = i32::try_from(count)
+            .map_err(|_| malformed("Invalid array count"))?;
             
         if count < 0 {
-            return Err(crate::Error::InvalidRequest("Invalid array count".to_string()));
+            return Err(malformed("Invalid array count"));
+        }
+        
+        if depth >= MAX_NESTING_DEPTH {
+            return Err(malformed("Arrays nested too deeply"));
         }
         
-        let mut elements = Vec::with_capacity(count as usize);
         let mut remaining = &input[line_end + 3..]; // Skip count and \r\n
+        // Every element takes at least 3 bytes, so a count past that can't be
+        // honest and mustn't decide the allocation
+        let mut elements = Vec::with_capacity((count as usize).min(remaining.len() / 3));
         
         for _ in 0..count {
-            let (element, new_remaining) = Self::parse_value(remaining)?;
+            let (element, new_remaining) = Self::parse_value_at(remaining, depth + 1)?;
             elements.push(element);
             remaining = new_remaining;
         }
@@ -480,6 +836,10 @@ impl RespParser {
     
     /// Parse any RESP value
     pub fn parse_value(input: &[u8]) -> Result<(RespValue, &[u8])> {
+        Self::parse_value_at(input, 0)
+    }
+
+    fn parse_value_at(input: &[u8], depth: usize) -> Result<(RespValue<'_>, &[u8])> {
         if input.is_empty() {
             return Err(crate::Error::InvalidRequest("Empty input".to_string()));
         }
@@ -505,10 +865,10 @@ impl RespParser {
                 }
             },
             b'*' => {
-                let (array, remaining) = Self::parse_array(input)?;
+                let (array, remaining) = Self::parse_array_at(input, depth)?;
                 Ok((RespValue::Array(array), remaining))
             },
-            _ => Err(crate::Error::InvalidRequest("Invalid RESP type marker".to_string())),
+            _ => Err(malformed("Invalid RESP type marker")),
         }
     }
 }
@@ -517,8 +877,83 @@ impl RespParser {
 // Auto-derived RESP implementations for core types
 // ============================================================================
 
+/// First byte of a bulk string that carries ids as fixed width little endian
+/// binary instead of ASCII decimal. Entity ids and field types take 8 bytes
+/// each and entity types 4, and a list of them is packed into one bulk
+/// string. Decimal text never starts with it, so decoders accept both.
+///
+/// Ids are only encoded this way inside `with_binary_ids`, which proxies use
+/// once the server agreed to `Codec::RespBinaryIds`; otherwise they go out
+/// as RESP integers.
+pub const BINARY_ID_MARKER: u8 = b'#';
+
+thread_local! {
+    static BINARY_IDS: std::cell::Cell<bool> = const { std::cell::Cell::new(false) };
+}
+
+/// Run `encode` with ids encoded as `BINARY_ID_MARKER` binary if `enabled`,
+/// or as RESP integers if not. Only for connections that negotiated
+/// `Codec::RespBinaryIds`, since older peers can't read binary ids.
+pub fn with_binary_ids<T>(enabled: bool, encode: impl FnOnce() -> T) -> T {
+    struct Restore(bool);
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            BINARY_IDS.with(|flag| flag.set(self.0));
+        }
+    }
+
+    let _restore = Restore(BINARY_IDS.with(|flag| flag.replace(enabled)));
+    encode()
+}
+
+fn binary_ids() -> bool {
+    BINARY_IDS.with(|flag| flag.get())
+}
+
+/// An id as decimal: a RESP integer, or a bulk string if it's too large for one
+fn encode_decimal_id(id: u64) -> OwnedRespValue {
+    match i64::try_from(id) {
+        Ok(id) => OwnedRespValue::Integer(id),
+        Err(_) => OwnedRespValue::BulkString(id.to_string().into_bytes()),
+    }
+}
+
+/// Pack fixed width ids into one marked bulk string
+fn encode_binary_ids<const N: usize>(count: usize, ids: impl Iterator<Item = [u8; N]>) -> OwnedRespValue {
+    let mut data = Vec::with_capacity(1 + count * N);
+    data.push(BINARY_ID_MARKER);
+    for id in ids {
+        data.extend_from_slice(&id);
+    }
+    OwnedRespValue::BulkString(data)
+}
+
+/// The ids packed in a marked bulk string, or `None` if `data` isn't marked
+fn decode_binary_ids<const N: usize>(data: &[u8]) -> Option<Result<impl Iterator<Item = [u8; N]> + '_>> {
+    let packed = data.strip_prefix(&[BINARY_ID_MARKER])?;
+    if packed.len() % N != 0 {
+        return Some(Err(malformed("Binary ids aren't a whole number of ids")));
+    }
+    Some(Ok(packed.chunks_exact(N).map(|chunk| {
+        let mut id = [0u8; N];
+        id.copy_from_slice(chunk);
+        id
+    })))
+}
+
+/// The single id in a marked bulk string, or `None` if `data` isn't marked
+fn decode_binary_id<const N: usize>(data: &[u8]) -> Option<Result<[u8; N]>> {
+    let packed = data.strip_prefix(&[BINARY_ID_MARKER])?;
+    Some(packed.try_into().map_err(|_| malformed("Binary id has the wrong width")))
+}
+
 impl<'a> RespDecode<'a> for EntityId {
     fn decode(input: RespValue<'a>) -> Result<Self> {
+        if let RespValue::BulkString(data) = &input {
+            if let Some(id) = decode_binary_id::<8>(data) {
+                return Ok(EntityId(u64::from_le_bytes(id?)));
+            }
+        }
         match input {
             RespValue::Integer(i) if i >= 0 => Ok(EntityId(i as u64)),
             RespValue::BulkString(data) => {
@@ -540,12 +975,21 @@ impl<'a> RespDecode<'a> for EntityId {
 
 impl RespEncode for EntityId {
     fn encode(&self) -> OwnedRespValue {
-        OwnedRespValue::Integer(self.0 as i64)
+        if binary_ids() {
+            encode_binary_ids(1, std::iter::once(self.0.to_le_bytes()))
+        } else {
+            encode_decimal_id(self.0)
+        }
     }
 }
 
 impl<'a> RespDecode<'a> for EntityType {
     fn decode(input: RespValue<'a>) -> Result<Self> {
+        if let RespValue::BulkString(data) = &input {
+            if let Some(id) = decode_binary_id::<4>(data) {
+                return Ok(EntityType(u32::from_le_bytes(id?)));
+            }
+        }
         match input {
             RespValue::Integer(i) if i >= 0 => Ok(EntityType(i as u32)),
             RespValue::BulkString(data) => {
@@ -567,12 +1011,21 @@ impl<'a> RespDecode<'a> for EntityType {
 
 impl RespEncode for EntityType {
     fn encode(&self) -> OwnedRespValue {
-        OwnedRespValue::Integer(self.0 as i64)
+        if binary_ids() {
+            encode_binary_ids(1, std::iter::once(self.0.to_le_bytes()))
+        } else {
+            encode_decimal_id(u64::from(self.0))
+        }
     }
 }
 
 impl<'a> RespDecode<'a> for FieldType {
     fn decode(input: RespValue<'a>) -> Result<Self> {
+        if let RespValue::BulkString(data) = &input {
+            if let Some(id) = decode_binary_id::<8>(data) {
+                return Ok(FieldType(u64::from_le_bytes(id?)));
+            }
+        }
         match input {
             RespValue::Integer(i) if i >= 0 => Ok(FieldType(i as u64)),
             RespValue::BulkString(data) => {
@@ -594,7 +1047,11 @@ impl<'a> RespDecode<'a> for FieldType {
 
 impl RespEncode for FieldType {
     fn encode(&self) -> OwnedRespValue {
-        OwnedRespValue::Integer(self.0 as i64)
+        if binary_ids() {
+            encode_binary_ids(1, std::iter::once(self.0.to_le_bytes()))
+        } else {
+            encode_decimal_id(self.0)
+        }
     }
 }
 
@@ -625,6 +1082,10 @@ impl RespDecode<'_> for Vec<String> {
 impl RespDecode<'_> for Vec<EntityId> {
     fn decode(input: RespValue<'_>) -> Result<Self> {
         match input {
+            RespValue::BulkString(data) => match decode_binary_ids::<8>(data) {
+                Some(ids) => Ok(ids?.map(|id| EntityId(u64::from_le_bytes(id))).collect()),
+                None => Err(crate::Error::InvalidRequest("Expected array for Vec<EntityId>".to_string())),
+            },
             RespValue::Array(elements) => {
                 let mut result = Vec::with_capacity(elements.len());
                 for element in elements {
@@ -641,6 +1102,10 @@ impl RespDecode<'_> for Vec<EntityId> {
 impl RespDecode<'_> for Vec<EntityType> {
     fn decode(input: RespValue<'_>) -> Result<Self> {
         match input {
+            RespValue::BulkString(data) => match decode_binary_ids::<4>(data) {
+                Some(ids) => Ok(ids?.map(|id| EntityType(u32::from_le_bytes(id))).collect()),
+                None => Err(crate::Error::InvalidRequest("Expected array for Vec<EntityType>".to_string())),
+            },
             RespValue::Array(elements) => {
                 let mut result = Vec::with_capacity(elements.len());
                 for element in elements {
@@ -657,6 +1122,10 @@ impl RespDecode<'_> for Vec<EntityType> {
 impl RespDecode<'_> for Vec<FieldType> {
     fn decode(input: RespValue<'_>) -> Result<Self> {
         match input {
+            RespValue::BulkString(data) => match decode_binary_ids::<8>(data) {
+                Some(ids) => Ok(ids?.map(|id| FieldType(u64::from_le_bytes(id))).collect()),
+                None => Err(crate::Error::InvalidRequest("Expected array for Vec<FieldType>".to_string())),
+            },
             RespValue::Array(elements) => {
                 let mut result = Vec::with_capacity(elements.len());
                 for element in elements {
@@ -670,6 +1139,38 @@ impl RespDecode<'_> for Vec<FieldType> {
     }
 }
 
+impl RespDecode<'_> for Vec<Option<EntityType>> {
+    fn decode(input: RespValue<'_>) -> Result<Self> {
+        match input {
+            RespValue::Array(elements) => {
+                let mut result = Vec::with_capacity(elements.len());
+                for element in elements {
+                    let decoded = Option::<EntityType>::decode(element)?;
+                    result.push(decoded);
+                }
+                Ok(result)
+            },
+            _ => Err(crate::Error::InvalidRequest("Expected array for Vec<Option<EntityType>>".to_string())),
+        }
+    }
+}
+
+impl RespDecode<'_> for Vec<Option<FieldType>> {
+    fn decode(input: RespValue<'_>) -> Result<Self> {
+        match input {
+            RespValue::Array(elements) => {
+                let mut result = Vec::with_capacity(elements.len());
+                for element in elements {
+                    let decoded = Option::<FieldType>::decode(element)?;
+                    result.push(decoded);
+                }
+                Ok(result)
+            },
+            _ => Err(crate::Error::InvalidRequest("Expected array for Vec<Option<FieldType>>".to_string())),
+        }
+    }
+}
+
 impl RespDecode<'_> for Vec<Vec<FieldType>> {
     fn decode(input: RespValue<'_>) -> Result<Self> {
         match input {
@@ -799,6 +1300,120 @@ impl<'a> RespDecode<'a> for crate::PushCondition {
     }
 }
 
+impl RespEncode for crate::WriteScope {
+    fn encode(&self) -> OwnedRespValue {
+        OwnedRespValue::BulkString(self.as_str().as_bytes().to_vec())
+    }
+}
+
+impl<'a> RespDecode<'a> for crate::WriteScope {
+    fn decode(input: RespValue<'a>) -> Result<Self> {
+        let s = match input {
+            RespValue::BulkString(data) => std::str::from_utf8(data)
+                .map_err(|_| crate::Error::InvalidRequest("Invalid UTF-8 in WriteScope".to_string()))?,
+            RespValue::SimpleString(s) => s,
+            _ => return Err(crate::Error::InvalidRequest("Invalid WriteScope type".to_string())),
+        };
+        crate::WriteScope::from_name(s)
+            .ok_or_else(|| crate::Error::InvalidRequest("Invalid WriteScope value".to_string()))
+    }
+}
+
+impl RespEncode for crate::MergePolicy {
+    fn encode(&self) -> OwnedRespValue {
+        OwnedRespValue::BulkString(self.as_str().as_bytes().to_vec())
+    }
+}
+
+impl<'a> RespDecode<'a> for crate::MergePolicy {
+    fn decode(input: RespValue<'a>) -> Result<Self> {
+        let s = match input {
+            RespValue::BulkString(data) => std::str::from_utf8(data)
+                .map_err(|_| crate::Error::InvalidRequest("Invalid UTF-8 in MergePolicy".to_string()))?,
+            RespValue::SimpleString(s) => s,
+            _ => return Err(crate::Error::InvalidRequest("Invalid MergePolicy type".to_string())),
+        };
+        crate::MergePolicy::from_name(s)
+            .ok_or_else(|| crate::Error::InvalidRequest("Invalid MergePolicy value".to_string()))
+    }
+}
+
+impl RespEncode for crate::OnDelete {
+    fn encode(&self) -> OwnedRespValue {
+        OwnedRespValue::BulkString(self.as_str().as_bytes().to_vec())
+    }
+}
+
+impl<'a> RespDecode<'a> for crate::OnDelete {
+    fn decode(input: RespValue<'a>) -> Result<Self> {
+        let s = match input {
+            RespValue::BulkString(data) => std::str::from_utf8(data)
+                .map_err(|_| crate::Error::InvalidRequest("Invalid UTF-8 in OnDelete".to_string()))?,
+            RespValue::SimpleString(s) => s,
+            _ => return Err(crate::Error::InvalidRequest("Invalid OnDelete type".to_string())),
+        };
+        crate::OnDelete::from_name(s)
+            .ok_or_else(|| crate::Error::InvalidRequest("Invalid OnDelete value".to_string()))
+    }
+}
+
+impl RespEncode for crate::SortDirection {
+    fn encode(&self) -> OwnedRespValue {
+        OwnedRespValue::BulkString(self.as_str().as_bytes().to_vec())
+    }
+}
+
+impl<'a> RespDecode<'a> for crate::SortDirection {
+    fn decode(input: RespValue<'a>) -> Result<Self> {
+        let s = match input {
+            RespValue::BulkString(data) => std::str::from_utf8(data)
+                .map_err(|_| crate::Error::InvalidRequest("Invalid UTF-8 in SortDirection".to_string()))?,
+            RespValue::SimpleString(s) => s,
+            _ => return Err(crate::Error::InvalidRequest("Invalid SortDirection type".to_string())),
+        };
+        crate::SortDirection::from_name(s)
+            .ok_or_else(|| crate::Error::InvalidRequest("Invalid SortDirection value".to_string()))
+    }
+}
+
+impl RespEncode for crate::AggregateOp {
+    fn encode(&self) -> OwnedRespValue {
+        OwnedRespValue::BulkString(self.as_str().as_bytes().to_vec())
+    }
+}
+
+impl<'a> RespDecode<'a> for crate::AggregateOp {
+    fn decode(input: RespValue<'a>) -> Result<Self> {
+        let s = match input {
+            RespValue::BulkString(data) => std::str::from_utf8(data)
+                .map_err(|_| crate::Error::InvalidRequest("Invalid UTF-8 in AggregateOp".to_string()))?,
+            RespValue::SimpleString(s) => s,
+            _ => return Err(crate::Error::InvalidRequest("Invalid AggregateOp type".to_string())),
+        };
+        crate::AggregateOp::from_name(s)
+            .ok_or_else(|| crate::Error::InvalidRequest("Invalid AggregateOp value".to_string()))
+    }
+}
+
+impl RespEncode for crate::SlowLogAction {
+    fn encode(&self) -> OwnedRespValue {
+        OwnedRespValue::BulkString(self.as_str().as_bytes().to_vec())
+    }
+}
+
+impl<'a> RespDecode<'a> for crate::SlowLogAction {
+    fn decode(input: RespValue<'a>) -> Result<Self> {
+        let s = match input {
+            RespValue::BulkString(data) => std::str::from_utf8(data)
+                .map_err(|_| crate::Error::InvalidRequest("Invalid UTF-8 in SlowLogAction".to_string()))?,
+            RespValue::SimpleString(s) => s,
+            _ => return Err(crate::Error::InvalidRequest("Invalid SlowLogAction type".to_string())),
+        };
+        crate::SlowLogAction::from_name(s)
+            .ok_or_else(|| crate::Error::InvalidRequest(format!("Unknown SLOWLOG subcommand '{}'", s)))
+    }
+}
+
 impl RespEncode for crate::AdjustBehavior {
     fn encode(&self) -> OwnedRespValue {
         let value = match self {
@@ -886,6 +1501,69 @@ impl RespEncode for Timestamp {
     }
 }
 
+impl<'a> RespDecode<'a> for crate::Decimal {
+    fn decode(input: RespValue<'a>) -> Result<Self> {
+        match input {
+            RespValue::Integer(i) => Ok(crate::Decimal::from_int(i)),
+            RespValue::BulkString(data) => std::str::from_utf8(data)
+                .map_err(|_| crate::Error::InvalidRequest("Invalid UTF-8 in Decimal".to_string()))?
+                .parse(),
+            RespValue::SimpleString(s) => s.parse(),
+            _ => Err(crate::Error::InvalidRequest("Invalid Decimal type".to_string())),
+        }
+    }
+}
+
+impl RespEncode for crate::Decimal {
+    fn encode(&self) -> OwnedRespValue {
+        // Encode as the decimal string so no precision is lost on the wire
+        OwnedRespValue::BulkString(self.to_string().into_bytes())
+    }
+}
+
+impl<'a> RespDecode<'a> for crate::Duration {
+    fn decode(input: RespValue<'a>) -> Result<Self> {
+        let nanos = i64::decode(input)
+            .map_err(|_| crate::Error::InvalidRequest("Invalid Duration format".to_string()))?;
+        Ok(crate::Duration::nanoseconds(nanos))
+    }
+}
+
+impl RespEncode for crate::Duration {
+    fn encode(&self) -> OwnedRespValue {
+        // Encode as whole nanoseconds, same as Timestamp
+        OwnedRespValue::Integer(self.whole_nanoseconds() as i64)
+    }
+}
+
+// Maps are encoded as a flat [key, value, key, value, ...] array
+impl RespEncode for std::collections::BTreeMap<String, crate::Value> {
+    fn encode(&self) -> OwnedRespValue {
+        let mut elements = Vec::with_capacity(self.len() * 2);
+        for (key, value) in self {
+            elements.push(key.encode());
+            elements.push(value.encode());
+        }
+        OwnedRespValue::Array(elements)
+    }
+}
+
+impl<'a> RespDecode<'a> for std::collections::BTreeMap<String, crate::Value> {
+    fn decode(input: RespValue<'a>) -> Result<Self> {
+        match input {
+            RespValue::Array(elements) if elements.len() % 2 == 0 => {
+                let mut result = std::collections::BTreeMap::new();
+                let mut elements = elements.into_iter();
+                while let (Some(key), Some(value)) = (elements.next(), elements.next()) {
+                    result.insert(String::decode(key)?, crate::Value::decode(value)?);
+                }
+                Ok(result)
+            },
+            _ => Err(crate::Error::InvalidRequest("Expected key/value array for map".to_string())),
+        }
+    }
+}
+
 // Implementation for PhantomData
 impl<T> RespEncode for std::marker::PhantomData<T> {
     fn encode(&self) -> OwnedRespValue {
@@ -924,6 +1602,27 @@ impl<'a, T: RespDecode<'a>> RespDecode<'a> for Option<T> {
     }
 }
 
+// Pairs are sent as a two element array
+impl<A: RespEncode, B: RespEncode> RespEncode for (A, B) {
+    fn encode(&self) -> OwnedRespValue {
+        OwnedRespValue::Array(vec![self.0.encode(), self.1.encode()])
+    }
+}
+
+impl<'a, A: RespDecode<'a>, B: RespDecode<'a>> RespDecode<'a> for (A, B) {
+    fn decode(input: RespValue<'a>) -> Result<Self> {
+        match input {
+            RespValue::Array(elements) if elements.len() == 2 => {
+                let mut elements = elements.into_iter();
+                let first = A::decode(elements.next().unwrap())?;
+                let second = B::decode(elements.next().unwrap())?;
+                Ok((first, second))
+            }
+            _ => Err(crate::Error::InvalidRequest("Expected a two element array for a pair".to_string())),
+        }
+    }
+}
+
 impl RespEncode for u64 {
     fn encode(&self) -> OwnedRespValue {
         OwnedRespValue::Integer(*self as i64)
@@ -1090,28 +1789,68 @@ impl RespDecode<'_> for Vec<u8> {
 // Vec<EntityId> implementation
 impl RespEncode for Vec<EntityId> {
     fn encode(&self) -> OwnedRespValue {
-        let elements: Vec<OwnedRespValue> = self.iter()
-            .map(|item| item.encode())
-            .collect();
-        OwnedRespValue::Array(elements)
+        if binary_ids() {
+            encode_binary_ids(self.len(), self.iter().map(|item| item.0.to_le_bytes()))
+        } else {
+            OwnedRespValue::Array(self.iter().map(|item| item.encode()).collect())
+        }
     }
 }
 
 // Vec<EntityType> implementation
 impl RespEncode for Vec<EntityType> {
     fn encode(&self) -> OwnedRespValue {
-        let elements: Vec<OwnedRespValue> = self.iter()
-            .map(|item| item.encode())
-            .collect();
-        OwnedRespValue::Array(elements)
+        if binary_ids() {
+            encode_binary_ids(self.len(), self.iter().map(|item| item.0.to_le_bytes()))
+        } else {
+            OwnedRespValue::Array(self.iter().map(|item| item.encode()).collect())
+        }
     }
 }
 
 // Vec<FieldType> implementation
 impl RespEncode for Vec<FieldType> {
     fn encode(&self) -> OwnedRespValue {
-        let elements: Vec<OwnedRespValue> = self.iter()
-            .map(|item| item.encode())
+        if binary_ids() {
+            encode_binary_ids(self.len(), self.iter().map(|item| item.0.to_le_bytes()))
+        } else {
+            OwnedRespValue::Array(self.iter().map(|item| item.encode()).collect())
+        }
+    }
+}
+
+// IndirectFieldType implementation, encoded like Vec<FieldType>
+impl RespEncode for crate::IndirectFieldType {
+    fn encode(&self) -> OwnedRespValue {
+        if binary_ids() {
+            encode_binary_ids(self.len(), self.iter().map(|item| item.0.to_le_bytes()))
+        } else {
+            OwnedRespValue::Array(self.iter().map(|item| item.encode()).collect())
+        }
+    }
+}
+
+impl RespDecode<'_> for crate::IndirectFieldType {
+    fn decode(input: RespValue<'_>) -> Result<Self> {
+        Vec::<FieldType>::decode(input).map(crate::IndirectFieldType::from_vec)
+    }
+}
+
+// Vec<Option<EntityType>> implementation, unknown names encode as null
+impl RespEncode for Vec<Option<EntityType>> {
+    fn encode(&self) -> OwnedRespValue {
+        let elements: Vec<OwnedRespValue> = self.iter()
+            .map(|item| item.encode())
+            .collect();
+        OwnedRespValue::Array(elements)
+    }
+}
+
+// Vec<Option<FieldType>> implementation, unknown names encode as null
+impl RespEncode for Vec<Option<FieldType>> {
+    fn encode(&self) -> OwnedRespValue {
+        let elements: Vec<OwnedRespValue> = self.iter()
+            .map(|item| item.encode())
             .collect();
         OwnedRespValue::Array(elements)
     }
@@ -1137,6 +1876,36 @@ impl RespEncode for Vec<Vec<FieldType>> {
     }
 }
 
+impl RespEncode for Vec<(Timestamp, Value)> {
+    fn encode(&self) -> OwnedRespValue {
+        OwnedRespValue::Array(self.iter().map(|sample| sample.encode()).collect())
+    }
+}
+
+impl RespDecode<'_> for Vec<(Timestamp, Value)> {
+    fn decode(input: RespValue<'_>) -> Result<Self> {
+        match input {
+            RespValue::Array(elements) => elements.into_iter().map(<(Timestamp, Value)>::decode).collect(),
+            _ => Err(crate::Error::InvalidRequest("Expected array of samples".to_string())),
+        }
+    }
+}
+
+impl RespEncode for Vec<(FieldType, Value)> {
+    fn encode(&self) -> OwnedRespValue {
+        OwnedRespValue::Array(self.iter().map(|field| field.encode()).collect())
+    }
+}
+
+impl RespDecode<'_> for Vec<(FieldType, Value)> {
+    fn decode(input: RespValue<'_>) -> Result<Self> {
+        match input {
+            RespValue::Array(elements) => elements.into_iter().map(<(FieldType, Value)>::decode).collect(),
+            _ => Err(crate::Error::InvalidRequest("Expected array of field values".to_string())),
+        }
+    }
+}
+
 // Vec<FieldSchemaResp> implementation
 impl RespEncode for Vec<FieldSchemaResp> {
     fn encode(&self) -> OwnedRespValue {
@@ -1195,6 +1964,23 @@ pub struct WriteCommand<'a> {
     pub _marker: std::marker::PhantomData<&'a ()>,
 }
 
+/// Write command that is only applied while `fencing_token` matches the
+/// LeaderToken of the given fault tolerance entity
+#[respc(name = "FSET")]
+#[derive(Debug, Clone)]
+pub struct FencedWriteCommand<'a> {
+    pub fault_tolerance_id: EntityId,
+    pub fencing_token: i64,
+    pub entity_id: EntityId,
+    pub field_path: Vec<FieldType>,
+    pub value: Value,
+    pub writer_id: Option<EntityId>,
+    pub write_time: Option<Timestamp>,
+    pub push_condition: Option<crate::PushCondition>,
+    pub adjust_behavior: Option<crate::AdjustBehavior>,
+    pub _marker: std::marker::PhantomData<&'a ()>,
+}
+
 /// Create entity command
 #[respc(name = "CREATE")]
 #[derive(Debug, Clone)]
@@ -1205,6 +1991,58 @@ pub struct CreateEntityCommand<'a> {
     pub _marker: std::marker::PhantomData<&'a ()>,
 }
 
+/// Create entity command carrying the initial field values, which are
+/// applied together with the creation
+#[respc(name = "CREATE_ENTITY_FULL")]
+#[derive(Debug, Clone)]
+pub struct CreateEntityFullCommand<'a> {
+    pub entity_type: EntityType,
+    pub parent_id: Option<EntityId>,
+    pub name: String,
+    pub fields: Vec<(FieldType, Value)>,
+    pub _marker: std::marker::PhantomData<&'a ()>,
+}
+
+/// Create a copy of a template entity and its descendants
+#[respc(name = "INSTANTIATE")]
+#[derive(Debug, Clone)]
+pub struct InstantiateTemplateCommand<'a> {
+    pub template_id: EntityId,
+    pub parent_id: Option<EntityId>,
+    pub name: String,
+    pub overrides: Vec<(FieldType, Value)>,
+    pub _marker: std::marker::PhantomData<&'a ()>,
+}
+
+/// Find or create an entity by parent and name, see `StoreTrait::get_or_create`
+#[respc(name = "GET_OR_CREATE")]
+#[derive(Debug, Clone)]
+pub struct GetOrCreateCommand<'a> {
+    pub entity_type: EntityType,
+    pub parent_id: Option<EntityId>,
+    pub name: String,
+    pub _marker: std::marker::PhantomData<&'a ()>,
+}
+
+/// Write a field unless it already holds the value, see `StoreTrait::upsert_field`
+#[respc(name = "UPSERT")]
+#[derive(Debug, Clone)]
+pub struct UpsertFieldCommand<'a> {
+    pub entity_id: EntityId,
+    pub field_type: FieldType,
+    pub value: Value,
+    pub _marker: std::marker::PhantomData<&'a ()>,
+}
+
+/// Rename an entity, see `StoreTrait::rename_entity`
+#[respc(name = "RENAME")]
+#[derive(Debug, Clone)]
+pub struct RenameEntityCommand<'a> {
+    pub entity_id: EntityId,
+    pub new_name: String,
+    pub _marker: std::marker::PhantomData<&'a ()>,
+}
+
 /// Delete entity command
 #[respc(name = "DEL")]
 #[derive(Debug, Clone)]
@@ -1245,6 +2083,15 @@ pub struct ResolveFieldTypeCommand<'a> {
     pub _marker: std::marker::PhantomData<&'a ()>,
 }
 
+/// Resolve many entity and field type names in one round trip
+#[respc(name = "GET_TYPES_BULK")]
+#[derive(Debug, Clone)]
+pub struct GetTypesBulkCommand<'a> {
+    pub entity_types: Vec<String>,
+    pub field_types: Vec<String>,
+    pub _marker: std::marker::PhantomData<&'a ()>,
+}
+
 /// Get entity schema command
 #[respc(name = "GETSCH")]
 #[derive(Debug, Clone)]
@@ -1313,6 +2160,57 @@ pub struct ResolveIndirectionCommand<'a> {
     pub _marker: std::marker::PhantomData<&'a ()>,
 }
 
+/// Resolve an entity path (e.g. `Root/Machines/M1`) to its entity id
+#[respc(name = "RESOLVE_PATH")]
+#[derive(Debug, Clone)]
+pub struct ResolvePathCommand<'a> {
+    pub path: String,
+    pub _marker: std::marker::PhantomData<&'a ()>,
+}
+
+/// Read a field addressed by path (e.g. `Root/Machines/M1/Pump3/Speed`)
+#[respc(name = "READ_PATH")]
+#[derive(Debug, Clone)]
+pub struct ReadPathCommand<'a> {
+    pub path: String,
+    pub _marker: std::marker::PhantomData<&'a ()>,
+}
+
+/// Write a field addressed by path (e.g. `Root/Machines/M1/Pump3/Speed`)
+#[respc(name = "WRITE_PATH")]
+#[derive(Debug, Clone)]
+pub struct WritePathCommand<'a> {
+    pub path: String,
+    pub value: Value,
+    pub writer_id: Option<EntityId>,
+    pub write_time: Option<Timestamp>,
+    pub push_condition: Option<crate::PushCondition>,
+    pub adjust_behavior: Option<crate::AdjustBehavior>,
+    pub _marker: std::marker::PhantomData<&'a ()>,
+}
+
+/// Read a byte range of a blob field, so large blobs can be fetched in chunks
+#[respc(name = "READ_BLOB_RANGE")]
+#[derive(Debug, Clone)]
+pub struct ReadBlobRangeCommand<'a> {
+    pub entity_id: EntityId,
+    pub field_path: Vec<FieldType>,
+    pub offset: usize,
+    pub len: usize,
+    pub _marker: std::marker::PhantomData<&'a ()>,
+}
+
+/// Append bytes to the end of a blob field
+#[respc(name = "APPEND_BLOB")]
+#[derive(Debug, Clone)]
+pub struct WriteBlobAppendCommand<'a> {
+    pub entity_id: EntityId,
+    pub field_path: Vec<FieldType>,
+    pub data: Vec<u8>,
+    pub writer_id: Option<EntityId>,
+    pub _marker: std::marker::PhantomData<&'a ()>,
+}
+
 /// Find entities with pagination command
 #[respc(name = "FINDPAG")]
 #[derive(Debug, Clone)]
@@ -1320,6 +2218,10 @@ pub struct FindEntitiesPaginatedCommand<'a> {
     pub entity_type: EntityType,
     pub page_opts: Option<crate::data::PageOpts>,
     pub filter: Option<String>,
+    /// Give up after this many milliseconds
+    pub timeout_ms: Option<u64>,
+    /// Id a `CANCEL` can refer to this request by
+    pub request_id: Option<u64>,
     pub _marker: std::marker::PhantomData<&'a ()>,
 }
 
@@ -1330,6 +2232,10 @@ pub struct FindEntitiesExactCommand<'a> {
     pub entity_type: EntityType,
     pub page_opts: Option<crate::data::PageOpts>,
     pub filter: Option<String>,
+    /// Give up after this many milliseconds
+    pub timeout_ms: Option<u64>,
+    /// Id a `CANCEL` can refer to this request by
+    pub request_id: Option<u64>,
     pub _marker: std::marker::PhantomData<&'a ()>,
 }
 
@@ -1339,6 +2245,84 @@ pub struct FindEntitiesExactCommand<'a> {
 pub struct FindEntitiesCommand<'a> {
     pub entity_type: EntityType,
     pub filter: Option<String>,
+    /// Give up after this many milliseconds
+    pub timeout_ms: Option<u64>,
+    /// Id a `CANCEL` can refer to this request by
+    pub request_id: Option<u64>,
+    pub _marker: std::marker::PhantomData<&'a ()>,
+}
+
+/// Entities and fields referring to an entity, see `StoreTrait::find_referencing`
+#[respc(name = "FIND_REFERENCING")]
+#[derive(Debug, Clone)]
+pub struct FindReferencingCommand<'a> {
+    pub entity_id: EntityId,
+    pub _marker: std::marker::PhantomData<&'a ()>,
+}
+
+/// Full-text search of the searchable String fields, see `Store::search`.
+/// Answered with the matching entities.
+#[respc(name = "SEARCH")]
+#[derive(Debug, Clone)]
+pub struct SearchCommand<'a> {
+    pub entity_type: EntityType,
+    pub query: String,
+    pub _marker: std::marker::PhantomData<&'a ()>,
+}
+
+/// Sample history of a Series field, see `Store::read_series`
+#[respc(name = "READ_SERIES")]
+#[derive(Debug, Clone)]
+pub struct ReadSeriesCommand<'a> {
+    pub entity_id: EntityId,
+    pub field_type: FieldType,
+    pub from: Option<Timestamp>,
+    pub to: Option<Timestamp>,
+    pub downsample: Option<crate::Duration>,
+    pub _marker: std::marker::PhantomData<&'a ()>,
+}
+
+/// Content hashes of the store's state, see `Store::checksum`
+#[respc(name = "VERIFY")]
+#[derive(Debug, Clone)]
+pub struct VerifyCommand<'a> {
+    pub _marker: std::marker::PhantomData<&'a ()>,
+}
+
+/// Size and usage figures of the store, see `StoreTrait::stats`
+#[respc(name = "STATS")]
+#[derive(Debug, Clone)]
+pub struct StatsCommand<'a> {
+    pub _marker: std::marker::PhantomData<&'a ()>,
+}
+
+/// Count, sum or take the min/max of a field, see `StoreTrait::aggregate`
+#[respc(name = "AGGREGATE")]
+#[derive(Debug, Clone)]
+pub struct AggregateCommand<'a> {
+    pub entity_type: EntityType,
+    pub field_type: FieldType,
+    pub op: crate::AggregateOp,
+    pub filter: Option<String>,
+    pub _marker: std::marker::PhantomData<&'a ()>,
+}
+
+/// Cancel a running request by the id it was sent with. Answered with OK
+/// whether or not the request was still running.
+#[respc(name = "CANCEL")]
+#[derive(Debug, Clone)]
+pub struct CancelCommand<'a> {
+    pub request_id: u64,
+    pub _marker: std::marker::PhantomData<&'a ()>,
+}
+
+/// Switch the connection to another codec, named as in
+/// `codec::Codec::name`. Answered with OK in RESP, after which both sides
+/// use the new codec; a server that can't speak it answers `ERR_UNSUPPORTED`.
+#[respc(name = "CODEC")]
+#[derive(Debug, Clone)]
+pub struct CodecCommand<'a> {
+    pub codec: String,
     pub _marker: std::marker::PhantomData<&'a ()>,
 }
 
@@ -1371,6 +2355,68 @@ pub struct MachineInfoCommand<'a> {
     pub _marker: std::marker::PhantomData<&'a ()>,
 }
 
+/// Query the audit log command
+#[respc(name = "AUDIT_QUERY")]
+#[derive(Debug, Clone)]
+pub struct AuditQueryCommand<'a> {
+    pub since: Option<Timestamp>,
+    pub until: Option<Timestamp>,
+    pub entity_id: Option<EntityId>,
+    pub subject: Option<EntityId>,
+    pub limit: Option<usize>,
+    pub _marker: std::marker::PhantomData<&'a ()>,
+}
+
+impl AuditQueryCommand<'_> {
+    pub fn from_query(query: &crate::AuditQuery) -> Self {
+        Self {
+            since: query.since,
+            until: query.until,
+            entity_id: query.entity_id,
+            subject: query.subject,
+            limit: query.limit,
+            _marker: std::marker::PhantomData,
+        }
+    }
+
+    pub fn to_query(&self) -> crate::AuditQuery {
+        crate::AuditQuery {
+            since: self.since,
+            until: self.until,
+            entity_id: self.entity_id,
+            subject: self.subject,
+            limit: self.limit,
+        }
+    }
+}
+
+/// Get the writes made to one entity since a time, from the audit log
+#[respc(name = "GET_CHANGES")]
+#[derive(Debug, Clone)]
+pub struct GetChangesCommand<'a> {
+    pub entity_id: EntityId,
+    pub since: Timestamp,
+    pub _marker: std::marker::PhantomData<&'a ()>,
+}
+
+/// Read or clear the slow log: `SLOWLOG GET [count]` answered with the
+/// entries, or `SLOWLOG RESET` answered with OK
+#[respc(name = "SLOWLOG")]
+#[derive(Debug, Clone)]
+pub struct SlowLogCommand<'a> {
+    pub action: crate::SlowLogAction,
+    pub count: Option<usize>,
+    pub _marker: std::marker::PhantomData<&'a ()>,
+}
+
+/// End the connection. The server replies OK once the commands sent before
+/// it are answered, then closes the connection.
+#[respc(name = "QUIT")]
+#[derive(Debug, Clone)]
+pub struct QuitCommand<'a> {
+    pub _marker: std::marker::PhantomData<&'a ()>,
+}
+
 /// Register notification command
 #[respc(name = "LISTEN")]
 #[derive(Debug, Clone)]
@@ -1387,6 +2433,114 @@ pub struct UnregisterNotificationCommand<'a> {
     pub _marker: std::marker::PhantomData<&'a ()>,
 }
 
+/// Create a durable notification subscription, or attach to it if it
+/// exists with the same config. Answered with the sequence number of the
+/// last notification the subscription received.
+#[respc(name = "DLISTEN")]
+#[derive(Debug, Clone)]
+pub struct SubscribeDurableCommand<'a> {
+    pub subscription_id: String,
+    pub config: crate::NotifyConfig,
+    pub _marker: std::marker::PhantomData<&'a ()>,
+}
+
+/// Attach to a durable subscription after reconnecting. The server pushes
+/// every notification after `last_seq` as `DNOTIFY` before answering with
+/// the sequence number of the last one.
+#[respc(name = "RESUME")]
+#[derive(Debug, Clone)]
+pub struct ResumeCommand<'a> {
+    pub subscription_id: String,
+    pub last_seq: u64,
+    pub _marker: std::marker::PhantomData<&'a ()>,
+}
+
+/// Remove a durable subscription and the notifications kept for it
+#[respc(name = "DUNLISTEN")]
+#[derive(Debug, Clone)]
+pub struct UnsubscribeDurableCommand<'a> {
+    pub subscription_id: String,
+    pub _marker: std::marker::PhantomData<&'a ()>,
+}
+
+/// Start a transaction; the server answers each following command with
+/// `+QUEUED` until `EXEC`
+#[respc(name = "MULTI")]
+#[derive(Debug, Clone)]
+pub struct MultiCommand<'a> {
+    pub _marker: std::marker::PhantomData<&'a ()>,
+}
+
+/// Apply every command queued since `MULTI` as a single transaction. The
+/// reply is an array holding each command's reply in order
+#[respc(name = "EXEC")]
+#[derive(Debug, Clone)]
+pub struct ExecCommand<'a> {
+    pub _marker: std::marker::PhantomData<&'a ()>,
+}
+
+/// Any command a client sends to a store. Servers decode incoming frames with
+/// this and match on the variant; each variant is sent exactly as the command
+/// it wraps.
+#[respc]
+#[derive(Debug, Clone)]
+pub enum StoreCommand<'a> {
+    Read(ReadCommand<'a>),
+    Write(WriteCommand<'a>),
+    FencedWrite(FencedWriteCommand<'a>),
+    CreateEntity(CreateEntityCommand<'a>),
+    CreateEntityFull(CreateEntityFullCommand<'a>),
+    InstantiateTemplate(InstantiateTemplateCommand<'a>),
+    GetOrCreate(GetOrCreateCommand<'a>),
+    UpsertField(UpsertFieldCommand<'a>),
+    RenameEntity(RenameEntityCommand<'a>),
+    DeleteEntity(DeleteEntityCommand<'a>),
+    GetEntityType(GetEntityTypeCommand<'a>),
+    ResolveEntityType(ResolveEntityTypeCommand<'a>),
+    GetFieldType(GetFieldTypeCommand<'a>),
+    ResolveFieldType(ResolveFieldTypeCommand<'a>),
+    GetTypesBulk(GetTypesBulkCommand<'a>),
+    GetEntitySchema(GetEntitySchemaCommand<'a>),
+    GetCompleteEntitySchema(GetCompleteEntitySchemaCommand<'a>),
+    UpdateSchema(UpdateSchemaCommand<'a>),
+    GetFieldSchema(GetFieldSchemaCommand<'a>),
+    SetFieldSchema(SetFieldSchemaCommand<'a>),
+    EntityExists(EntityExistsCommand<'a>),
+    FieldExists(FieldExistsCommand<'a>),
+    ResolveIndirection(ResolveIndirectionCommand<'a>),
+    ResolvePath(ResolvePathCommand<'a>),
+    ReadPath(ReadPathCommand<'a>),
+    WritePath(WritePathCommand<'a>),
+    ReadBlobRange(ReadBlobRangeCommand<'a>),
+    WriteBlobAppend(WriteBlobAppendCommand<'a>),
+    FindEntitiesPaginated(FindEntitiesPaginatedCommand<'a>),
+    FindEntitiesExact(FindEntitiesExactCommand<'a>),
+    FindEntities(FindEntitiesCommand<'a>),
+    FindReferencing(FindReferencingCommand<'a>),
+    Aggregate(AggregateCommand<'a>),
+    Search(SearchCommand<'a>),
+    ReadSeries(ReadSeriesCommand<'a>),
+    Verify(VerifyCommand<'a>),
+    Stats(StatsCommand<'a>),
+    Cancel(CancelCommand<'a>),
+    Codec(CodecCommand<'a>),
+    GetEntityTypes(GetEntityTypesCommand<'a>),
+    GetEntityTypesPaginated(GetEntityTypesPaginatedCommand<'a>),
+    TakeSnapshot(TakeSnapshotCommand<'a>),
+    MachineInfo(MachineInfoCommand<'a>),
+    AuditQuery(AuditQueryCommand<'a>),
+    GetChanges(GetChangesCommand<'a>),
+    SlowLog(SlowLogCommand<'a>),
+    Quit(QuitCommand<'a>),
+    RegisterNotification(RegisterNotificationCommand<'a>),
+    UnregisterNotification(UnregisterNotificationCommand<'a>),
+    SubscribeDurable(SubscribeDurableCommand<'a>),
+    Resume(ResumeCommand<'a>),
+    UnsubscribeDurable(UnsubscribeDurableCommand<'a>),
+    Multi(MultiCommand<'a>),
+    Exec(ExecCommand<'a>),
+}
+
 // ============================================================================
 // RESP Response Structs for complex return types
 // ============================================================================
@@ -1399,6 +2553,13 @@ pub struct ReadResponse {
     pub writer_id: Option<EntityId>,
 }
 
+/// Response for blob range reads
+#[derive(Debug, Clone, RespEncode, RespDecode)]
+pub struct BlobRangeResponse {
+    pub data: Vec<u8>,
+    pub total_len: usize,
+}
+
 /// Response for resolve indirection operations
 #[derive(Debug, Clone, RespEncode, RespDecode)]
 pub struct ResolveIndirectionResponse {
@@ -1412,6 +2573,19 @@ pub struct CreateEntityResponse {
     pub entity_id: EntityId,
 }
 
+/// Response for `GET_OR_CREATE`
+#[derive(Debug, Clone, RespEncode, RespDecode)]
+pub struct GetOrCreateResponse {
+    pub entity_id: EntityId,
+    pub created: bool,
+}
+
+/// Response for resolve path operations
+#[derive(Debug, Clone, RespEncode, RespDecode)]
+pub struct ResolvePathResponse {
+    pub entity_id: EntityId,
+}
+
 /// Response for simple boolean operations (exists checks)
 #[derive(Debug, Clone)]
 pub struct BooleanResponse {
@@ -1473,12 +2647,39 @@ impl<'a> RespDecode<'a> for IntegerResponse {
     }
 }
 
+/// Response for bulk type lookups, in request order. Unknown names are null.
+#[derive(Debug, Clone, RespEncode, RespDecode)]
+pub struct TypesBulkResponse {
+    pub entity_types: Vec<Option<EntityType>>,
+    pub field_types: Vec<Option<FieldType>>,
+}
+
 /// Response for entity list operations
 #[derive(Debug, Clone, RespEncode, RespDecode)]
 pub struct EntityListResponse {
     pub entities: Vec<EntityId>,
 }
 
+/// Response for `FIND_REFERENCING`: the referring entities and, at the same
+/// index, the field holding the reference
+#[derive(Debug, Clone, RespEncode, RespDecode)]
+pub struct ReferencingResponse {
+    pub entity_ids: Vec<EntityId>,
+    pub field_types: Vec<FieldType>,
+}
+
+/// Response for `READ_SERIES`: (timestamp, value) samples, oldest first
+#[derive(Debug, Clone, RespEncode, RespDecode)]
+pub struct SeriesResponse {
+    pub samples: Vec<(Timestamp, Value)>,
+}
+
+/// Response for `AGGREGATE`, null for a Min/Max over no entities
+#[derive(Debug, Clone, RespEncode, RespDecode)]
+pub struct AggregateResponse {
+    pub value: Option<Value>,
+}
+
 /// Response for entity type list operations
 #[derive(Debug, Clone, RespEncode, RespDecode)]
 pub struct EntityTypeListResponse {
@@ -1497,12 +2698,96 @@ pub struct SnapshotResponse {
     pub data: String, // JSON-serialized snapshot data
 }
 
+/// Response for audit queries
+#[derive(Debug, Clone, RespEncode, RespDecode)]
+pub struct AuditQueryResponse {
+    pub data: String, // JSON-serialized audit records
+}
+
+impl AuditQueryResponse {
+    pub fn from_records(records: &[crate::AuditRecord]) -> Result<Self> {
+        let data = serde_json::to_string(records)
+            .map_err(|e| crate::Error::InvalidRequest(format!("Failed to serialize audit records: {}", e)))?;
+        Ok(Self { data })
+    }
+
+    pub fn into_records(self) -> Result<Vec<crate::AuditRecord>> {
+        serde_json::from_str(&self.data)
+            .map_err(|e| crate::Error::StoreProxyError(format!("Failed to deserialize audit records: {}", e)))
+    }
+}
+
+/// Response for `GET_CHANGES`
+#[derive(Debug, Clone, RespEncode, RespDecode)]
+pub struct ChangesResponse {
+    pub data: String, // JSON-serialized writes
+}
+
+impl ChangesResponse {
+    pub fn from_writes(writes: &[crate::WriteInfo]) -> Result<Self> {
+        let data = serde_json::to_string(writes)
+            .map_err(|e| crate::Error::InvalidRequest(format!("Failed to serialize changes: {}", e)))?;
+        Ok(Self { data })
+    }
+
+    pub fn into_writes(self) -> Result<Vec<crate::WriteInfo>> {
+        serde_json::from_str(&self.data)
+            .map_err(|e| crate::Error::StoreProxyError(format!("Failed to deserialize changes: {}", e)))
+    }
+}
+
+/// Response for `SLOWLOG GET`
+#[derive(Debug, Clone, RespEncode, RespDecode)]
+pub struct SlowLogResponse {
+    pub data: String, // JSON-serialized slow log entries
+}
+
+impl SlowLogResponse {
+    pub fn from_entries(entries: &[crate::SlowLogEntry]) -> Result<Self> {
+        let data = serde_json::to_string(entries)
+            .map_err(|e| crate::Error::InvalidRequest(format!("Failed to serialize slow log entries: {}", e)))?;
+        Ok(Self { data })
+    }
+
+    pub fn into_entries(self) -> Result<Vec<crate::SlowLogEntry>> {
+        serde_json::from_str(&self.data)
+            .map_err(|e| crate::Error::StoreProxyError(format!("Failed to deserialize slow log entries: {}", e)))
+    }
+}
+
+/// Response for `VERIFY`
+#[derive(Debug, Clone, RespEncode, RespDecode)]
+pub struct ChecksumResponse {
+    pub data: String, // JSON-serialized SnapshotChecksum
+}
+
+impl ChecksumResponse {
+    pub fn from_checksum(checksum: &crate::SnapshotChecksum) -> Result<Self> {
+        let data = serde_json::to_string(checksum)
+            .map_err(|e| crate::Error::InvalidRequest(format!("Failed to serialize checksum: {}", e)))?;
+        Ok(Self { data })
+    }
+
+    pub fn into_checksum(self) -> Result<crate::SnapshotChecksum> {
+        serde_json::from_str(&self.data)
+            .map_err(|e| crate::Error::StoreProxyError(format!("Failed to deserialize checksum: {}", e)))
+    }
+}
+
+/// Response for `STATS`: the map from `StoreStats::to_map`
+#[derive(Debug, Clone, RespEncode, RespDecode)]
+pub struct StatsResponse {
+    pub stats: std::collections::BTreeMap<String, Value>,
+}
+
 /// Response for paginated entity results
 #[derive(Debug, Clone, RespEncode, RespDecode)]
 pub struct PaginatedEntityResponse {
     pub items: Vec<EntityId>,
     pub total: usize,
     pub next_cursor: Option<usize>,
+    #[resp(default)]
+    pub next_key: Option<(Value, EntityId)>,
 }
 
 /// Response for paginated entity type results
@@ -1533,6 +2818,10 @@ pub struct PeerHandshakeCommand<'a> {
     pub start_time: u64,
     pub is_response: bool,
     pub machine_id: String,
+    /// The sender's wall clock when it sent the handshake, to measure skew.
+    /// Peers that don't send it leave it at zero.
+    #[resp(default)]
+    pub clock: crate::HlcTimestamp,
     pub _marker: std::marker::PhantomData<&'a ()>,
 }
 
@@ -1559,10 +2848,43 @@ pub struct SyncWriteCommand<'a> {
     pub _marker: std::marker::PhantomData<&'a ()>,
 }
 
-/// Notification message command
+/// Notification message command. The notification is a nested array; JSON
+/// notifications from older servers are still accepted.
 #[respc(name = "NOTIFY")]
 #[derive(Debug, Clone)]
 pub struct NotificationCommand<'a> {
-    pub notification_data: String, // JSON-serialized notification
+    pub notification: crate::Notification,
     pub _marker: std::marker::PhantomData<&'a ()>,
 }
+
+impl NotificationCommand<'_> {
+    /// Encode with the notification as a JSON string, for clients that
+    /// predate the nested array encoding
+    pub fn encode_legacy(&self) -> Result<OwnedRespValue> {
+        let json = serde_json::to_vec(&self.notification)
+            .map_err(|e| crate::Error::InvalidRequest(format!("Failed to serialize notification: {}", e)))?;
+        Ok(OwnedRespValue::Array(vec![
+            OwnedRespValue::BulkString(Self::COMMAND_NAME.as_bytes().to_vec()),
+            OwnedRespValue::BulkString(json),
+        ]))
+    }
+}
+
+/// A notification for a durable subscription, numbered so the client can
+/// resume after the last one it handled
+#[respc(name = "DNOTIFY")]
+#[derive(Debug, Clone)]
+pub struct DurableNotificationCommand<'a> {
+    pub subscription_id: String,
+    pub seq: u64,
+    pub notification: crate::Notification,
+    pub _marker: std::marker::PhantomData<&'a ()>,
+}
+
+/// Frames the server pushes to a client between replies
+#[respc]
+#[derive(Debug, Clone)]
+pub enum PushCommand<'a> {
+    Notification(NotificationCommand<'a>),
+    DurableNotification(DurableNotificationCommand<'a>),
+}
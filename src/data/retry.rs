@@ -0,0 +1,137 @@
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+use rand::Rng;
+
+use crate::{Error, Result};
+
+/// How to repeat a request that failed with a transient error, so callers
+/// don't each write their own retry loop around a proxy.
+///
+/// The delay before each retry doubles from `initial_backoff` up to
+/// `max_backoff`, and up to `jitter` of it is taken off at random so
+/// clients that failed together don't retry together. Which errors are
+/// worth retrying is decided by the filter, `Error::is_retryable` unless
+/// replaced with `retry_if`.
+///
+/// ```rust,ignore
+/// let policy = RetryPolicy::new().with_max_attempts(8).with_backoff(Duration::from_millis(20), Duration::from_secs(2));
+/// let value = policy.run(|| proxy.read(pump, &[speed_ft]))?;
+/// let value = policy.run_async(|| proxy.read(pump, &[speed_ft])).await?;
+/// ```
+#[derive(Clone)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    jitter: f64,
+    filter: Arc<dyn Fn(&Error) -> bool + Send + Sync>,
+}
+
+impl std::fmt::Debug for RetryPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RetryPolicy")
+            .field("max_attempts", &self.max_attempts)
+            .field("initial_backoff", &self.initial_backoff)
+            .field("max_backoff", &self.max_backoff)
+            .field("jitter", &self.jitter)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RetryPolicy {
+    /// 5 attempts, backing off from 50ms to 5s with half of each delay
+    /// jittered, retrying errors that are `is_retryable`
+    pub fn new() -> Self {
+        Self {
+            max_attempts: 5,
+            initial_backoff: Duration::from_millis(50),
+            max_backoff: Duration::from_secs(5),
+            jitter: 0.5,
+            filter: Arc::new(Error::is_retryable),
+        }
+    }
+
+    /// Give up after `max_attempts` tries in total, the first included
+    pub fn with_max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+
+    /// Wait `initial_backoff` after the first failure, doubling up to
+    /// `max_backoff`
+    pub fn with_backoff(mut self, initial_backoff: Duration, max_backoff: Duration) -> Self {
+        self.initial_backoff = initial_backoff;
+        self.max_backoff = max_backoff.max(initial_backoff);
+        self
+    }
+
+    /// Take up to this fraction (0 to 1) off each delay at random
+    pub fn with_jitter(mut self, jitter: f64) -> Self {
+        self.jitter = jitter.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Retry only the errors `filter` accepts
+    pub fn retry_if(mut self, filter: impl Fn(&Error) -> bool + Send + Sync + 'static) -> Self {
+        self.filter = Arc::new(filter);
+        self
+    }
+
+    pub fn max_attempts(&self) -> u32 {
+        self.max_attempts
+    }
+
+    /// Whether to try again after `attempt` (counting from 1) failed with
+    /// `error`
+    pub fn should_retry(&self, error: &Error, attempt: u32) -> bool {
+        attempt < self.max_attempts && (self.filter)(error)
+    }
+
+    /// How long to wait after `attempt` (counting from 1) failed
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let doublings = attempt.saturating_sub(1).min(31);
+        let delay = self.initial_backoff.saturating_mul(1 << doublings).min(self.max_backoff);
+        if self.jitter == 0.0 {
+            return delay;
+        }
+        delay.mul_f64(1.0 - self.jitter * rand::thread_rng().gen::<f64>())
+    }
+
+    /// Run `operation` until it succeeds, fails with an error the filter
+    /// rejects or runs out of attempts, sleeping the thread in between.
+    /// Returns the last error when it gives up.
+    pub fn run<T>(&self, mut operation: impl FnMut() -> Result<T>) -> Result<T> {
+        let mut attempt = 1;
+        loop {
+            match operation() {
+                Err(error) if self.should_retry(&error, attempt) => std::thread::sleep(self.backoff(attempt)),
+                result => return result,
+            }
+            attempt += 1;
+        }
+    }
+
+    /// Like `run`, for async operations such as `AsyncStoreProxy` calls
+    pub async fn run_async<T, F, Fut>(&self, mut operation: F) -> Result<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let mut attempt = 1;
+        loop {
+            match operation().await {
+                Err(error) if self.should_retry(&error, attempt) => tokio::time::sleep(self.backoff(attempt)).await,
+                result => return result,
+            }
+            attempt += 1;
+        }
+    }
+}
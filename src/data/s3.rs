@@ -0,0 +1,490 @@
+//! A `SnapshotSink` for S3 and S3-compatible object stores (MinIO, Ceph,
+//! Garage, ...), speaking the REST API directly with Signature Version 4.
+//!
+//! The sink uses path-style requests over plain HTTP. To reach an HTTPS-only
+//! endpoint, put a TLS-terminating proxy in front of it on the local network.
+
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration as StdDuration;
+
+use time::UtcOffset;
+
+use crate::data::snapshot_sink::SnapshotSink;
+use crate::{Error, Result, Timestamp};
+
+/// S3 refuses parts smaller than this, except for the last one
+pub const MIN_PART_SIZE: usize = 5 * 1024 * 1024;
+/// Objects larger than this are uploaded in parts by default
+pub const DEFAULT_PART_SIZE: usize = 8 * 1024 * 1024;
+
+/// Where and how to reach the bucket
+#[derive(Debug, Clone)]
+pub struct S3Config {
+    /// e.g. `http://minio.local:9000`
+    pub endpoint: String,
+    pub region: String,
+    pub bucket: String,
+    /// Put in front of every key, e.g. `site-7/`, so several stores can
+    /// share a bucket
+    pub prefix: String,
+    pub access_key: String,
+    pub secret_key: String,
+    /// Objects larger than this are uploaded in parts of this size. Should
+    /// be at least `MIN_PART_SIZE` for real S3.
+    pub part_size: usize,
+    /// Read and write timeout of each request
+    pub timeout: StdDuration,
+}
+
+impl S3Config {
+    pub fn new(endpoint: &str, bucket: &str, access_key: &str, secret_key: &str) -> Self {
+        Self {
+            endpoint: endpoint.to_string(),
+            region: "us-east-1".to_string(),
+            bucket: bucket.to_string(),
+            prefix: String::new(),
+            access_key: access_key.to_string(),
+            secret_key: secret_key.to_string(),
+            part_size: DEFAULT_PART_SIZE,
+            timeout: StdDuration::from_secs(30),
+        }
+    }
+
+    pub fn with_region(mut self, region: &str) -> Self {
+        self.region = region.to_string();
+        self
+    }
+
+    pub fn with_prefix(mut self, prefix: &str) -> Self {
+        self.prefix = prefix.to_string();
+        self
+    }
+
+    pub fn with_part_size(mut self, part_size: usize) -> Self {
+        self.part_size = part_size;
+        self
+    }
+}
+
+/// Keeps snapshots as objects in an S3 bucket, see `S3Config`.
+///
+/// ```rust,ignore
+/// let mut sink = S3Sink::new(S3Config::new("http://minio.local:9000", "backups", key, secret).with_prefix("site-7/"))?;
+/// upload_snapshot(&mut sink, &store.take_snapshot())?;
+/// restore_from_sink(&mut store, &mut sink, Some(before_the_incident))?;
+/// ```
+#[derive(Debug, Clone)]
+pub struct S3Sink {
+    config: S3Config,
+    /// Value of the Host header
+    host: String,
+    /// host:port to connect to
+    address: String,
+}
+
+/// Status, headers (names lowercased) and body of a response
+#[derive(Debug)]
+struct Response {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl Response {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find(|(n, _)| n == name).map(|(_, value)| value.as_str())
+    }
+}
+
+/// The parts of a request that are signed
+pub(crate) struct CanonicalRequest<'a> {
+    pub method: &'a str,
+    /// URI-encoded path
+    pub path: &'a str,
+    pub query: &'a [(String, String)],
+    /// Lowercase names, sorted
+    pub headers: &'a [(String, String)],
+    /// Hex SHA-256 of the body
+    pub payload_hash: &'a str,
+}
+
+impl S3Sink {
+    pub fn new(config: S3Config) -> Result<Self> {
+        let url = url::Url::parse(&config.endpoint)
+            .map_err(|e| Error::InvalidRequest(format!("Invalid S3 endpoint '{}': {}", config.endpoint, e)))?;
+        if url.scheme() != "http" {
+            return Err(Error::InvalidRequest(format!(
+                "S3 endpoint '{}' is not plain HTTP; reach HTTPS endpoints through a TLS-terminating proxy",
+                config.endpoint
+            )));
+        }
+        let hostname = url
+            .host_str()
+            .ok_or_else(|| Error::InvalidRequest(format!("S3 endpoint '{}' has no host", config.endpoint)))?;
+        let port = url.port_or_known_default().unwrap_or(80);
+        let host = match url.port() {
+            Some(port) => format!("{}:{}", hostname, port),
+            None => hostname.to_string(),
+        };
+
+        Ok(Self {
+            address: format!("{}:{}", hostname, port),
+            host,
+            config,
+        })
+    }
+
+    pub fn config(&self) -> &S3Config {
+        &self.config
+    }
+
+    fn object_path(&self, key: &str) -> String {
+        format!("/{}/{}", uri_encode(&self.config.bucket, false), uri_encode(&format!("{}{}", self.config.prefix, key), true))
+    }
+
+    fn send(&self, method: &str, path: &str, query: &[(String, String)], body: &[u8]) -> Result<Response> {
+        let amz_date = amz_date(crate::now());
+        let payload_hash = hex(&sha256(body));
+        let headers = vec![
+            ("host".to_string(), self.host.clone()),
+            ("x-amz-content-sha256".to_string(), payload_hash.clone()),
+            ("x-amz-date".to_string(), amz_date.clone()),
+        ];
+        let authorization = authorization(
+            &self.config,
+            &CanonicalRequest { method, path, query, headers: &headers, payload_hash: &payload_hash },
+            &amz_date,
+        );
+
+        let target = match query.is_empty() {
+            true => path.to_string(),
+            false => format!("{}?{}", path, canonical_query(query)),
+        };
+        let mut request = format!("{} {} HTTP/1.1\r\n", method, target);
+        for (name, value) in &headers {
+            request.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        request.push_str(&format!("authorization: {}\r\ncontent-length: {}\r\nconnection: close\r\n\r\n", authorization, body.len()));
+
+        let what = format!("S3 {} {}", method, target);
+        let io_error = |e: std::io::Error| Error::InvalidRequest(format!("{} failed: {}", what, e));
+        let address = self
+            .address
+            .to_socket_addrs()
+            .map_err(io_error)?
+            .next()
+            .ok_or_else(|| Error::InvalidRequest(format!("{} failed: {} doesn't resolve", what, self.address)))?;
+        let mut stream = TcpStream::connect_timeout(&address, self.config.timeout).map_err(io_error)?;
+        stream.set_read_timeout(Some(self.config.timeout)).map_err(io_error)?;
+        stream.set_write_timeout(Some(self.config.timeout)).map_err(io_error)?;
+        stream.write_all(request.as_bytes()).map_err(io_error)?;
+        stream.write_all(body).map_err(io_error)?;
+        let mut raw = Vec::new();
+        stream.read_to_end(&mut raw).map_err(io_error)?;
+
+        let response = parse_response(&raw).ok_or_else(|| Error::InvalidRequest(format!("{} failed: malformed response", what)))?;
+        if !(200..300).contains(&response.status) {
+            let body = String::from_utf8_lossy(&response.body);
+            let message = xml_elements(&body, "Message").first().map(|message| xml_unescape(message)).unwrap_or_default();
+            return Err(Error::InvalidRequest(format!("{} failed with {}: {}", what, response.status, message)));
+        }
+        Ok(response)
+    }
+
+    fn put_multipart(&self, key: &str, data: &[u8]) -> Result<()> {
+        let path = self.object_path(key);
+        let created = self.send("POST", &path, &[("uploads".to_string(), String::new())], &[])?;
+        let body = String::from_utf8_lossy(&created.body).to_string();
+        let upload_id = xml_elements(&body, "UploadId")
+            .first()
+            .map(|upload_id| xml_unescape(upload_id))
+            .ok_or_else(|| Error::InvalidRequest(format!("S3 didn't start a multipart upload of {}", key)))?;
+
+        let uploaded = data
+            .chunks(self.config.part_size.max(1))
+            .enumerate()
+            .map(|(index, part)| {
+                let query = [("partNumber".to_string(), (index + 1).to_string()), ("uploadId".to_string(), upload_id.clone())];
+                let response = self.send("PUT", &path, &query, part)?;
+                let etag = response
+                    .header("etag")
+                    .ok_or_else(|| Error::InvalidRequest(format!("S3 returned no ETag for part {} of {}", index + 1, key)))?;
+                Ok(format!("<Part><PartNumber>{}</PartNumber><ETag>{}</ETag></Part>", index + 1, xml_escape(etag)))
+            })
+            .collect::<Result<Vec<String>>>()
+            .and_then(|parts| {
+                let complete = format!("<CompleteMultipartUpload>{}</CompleteMultipartUpload>", parts.concat());
+                self.send("POST", &path, &[("uploadId".to_string(), upload_id.clone())], complete.as_bytes())
+            });
+
+        if let Err(e) = uploaded {
+            // Don't leave the parts behind to be billed for
+            let _ = self.send("DELETE", &path, &[("uploadId".to_string(), upload_id)], &[]);
+            return Err(e);
+        }
+        Ok(())
+    }
+}
+
+impl SnapshotSink for S3Sink {
+    fn put(&mut self, key: &str, data: &[u8]) -> Result<()> {
+        if data.len() > self.config.part_size {
+            return self.put_multipart(key, data);
+        }
+        self.send("PUT", &self.object_path(key), &[], data).map(|_| ())
+    }
+
+    fn get(&mut self, key: &str) -> Result<Vec<u8>> {
+        self.send("GET", &self.object_path(key), &[], &[]).map(|response| response.body)
+    }
+
+    fn list(&mut self) -> Result<Vec<(String, u64)>> {
+        let path = format!("/{}", uri_encode(&self.config.bucket, false));
+        let mut keys = Vec::new();
+        let mut continuation = None;
+        loop {
+            let mut query = vec![("list-type".to_string(), "2".to_string()), ("prefix".to_string(), self.config.prefix.clone())];
+            if let Some(token) = continuation.take() {
+                query.push(("continuation-token".to_string(), token));
+            }
+            let response = self.send("GET", &path, &query, &[])?;
+            let body = String::from_utf8_lossy(&response.body).to_string();
+
+            for contents in xml_elements(&body, "Contents") {
+                let key = xml_elements(contents, "Key").first().map(|key| xml_unescape(key)).unwrap_or_default();
+                let size = xml_elements(contents, "Size").first().and_then(|size| size.parse().ok()).unwrap_or(0);
+                if let Some(key) = key.strip_prefix(&self.config.prefix) {
+                    keys.push((key.to_string(), size));
+                }
+            }
+
+            let truncated = xml_elements(&body, "IsTruncated").first() == Some(&"true");
+            continuation = xml_elements(&body, "NextContinuationToken").first().map(|token| xml_unescape(token));
+            if !truncated || continuation.is_none() {
+                return Ok(keys);
+            }
+        }
+    }
+
+    fn delete(&mut self, key: &str) -> Result<()> {
+        self.send("DELETE", &self.object_path(key), &[], &[]).map(|_| ())
+    }
+}
+
+fn parse_response(raw: &[u8]) -> Option<Response> {
+    let header_end = raw.windows(4).position(|window| window == b"\r\n\r\n")?;
+    let head = std::str::from_utf8(&raw[..header_end]).ok()?;
+    let mut lines = head.split("\r\n");
+    let status = lines.next()?.split(' ').nth(1)?.parse().ok()?;
+    let headers: Vec<(String, String)> = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_ascii_lowercase(), value.trim().to_string()))
+        .collect();
+
+    let mut response = Response { status, headers, body: raw[header_end + 4..].to_vec() };
+    if response.header("transfer-encoding").is_some_and(|encoding| encoding.eq_ignore_ascii_case("chunked")) {
+        response.body = dechunk(&response.body)?;
+    } else if let Some(length) = response.header("content-length").and_then(|length| length.parse().ok()) {
+        response.body.truncate(length);
+    }
+    Some(response)
+}
+
+fn dechunk(mut chunked: &[u8]) -> Option<Vec<u8>> {
+    let mut body = Vec::new();
+    loop {
+        let line_end = chunked.windows(2).position(|window| window == b"\r\n")?;
+        let size = std::str::from_utf8(&chunked[..line_end]).ok()?.split(';').next()?.trim();
+        let size = usize::from_str_radix(size, 16).ok()?;
+        if size == 0 {
+            return Some(body);
+        }
+        let start = line_end + 2;
+        body.extend_from_slice(chunked.get(start..start + size)?);
+        chunked = chunked.get(start + size + 2..)?;
+    }
+}
+
+/// The inner text of every `<tag>` element, outermost matches only
+fn xml_elements<'a>(xml: &'a str, tag: &str) -> Vec<&'a str> {
+    let (open, close) = (format!("<{}>", tag), format!("</{}>", tag));
+    let mut elements = Vec::new();
+    let mut rest = xml;
+    while let Some(start) = rest.find(&open) {
+        let inner = &rest[start + open.len()..];
+        let Some(end) = inner.find(&close) else {
+            break;
+        };
+        elements.push(&inner[..end]);
+        rest = &inner[end + close.len()..];
+    }
+    elements
+}
+
+fn xml_unescape(text: &str) -> String {
+    text.replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// `YYYYMMDDTHHMMSSZ`, the time format of `x-amz-date`
+pub(crate) fn amz_date(time: Timestamp) -> String {
+    let t = time.to_offset(UtcOffset::UTC);
+    format!(
+        "{:04}{:02}{:02}T{:02}{:02}{:02}Z",
+        t.year(),
+        u8::from(t.month()),
+        t.day(),
+        t.hour(),
+        t.minute(),
+        t.second()
+    )
+}
+
+/// Percent-encode everything but unreserved characters (and `/` if
+/// `keep_slash`), as Signature Version 4 expects
+fn uri_encode(text: &str, keep_slash: bool) -> String {
+    let mut encoded = String::with_capacity(text.len());
+    for byte in text.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => encoded.push(byte as char),
+            b'/' if keep_slash => encoded.push('/'),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+fn canonical_query(query: &[(String, String)]) -> String {
+    let mut pairs: Vec<(String, String)> = query.iter().map(|(name, value)| (uri_encode(name, false), uri_encode(value, false))).collect();
+    pairs.sort();
+    pairs.iter().map(|(name, value)| format!("{}={}", name, value)).collect::<Vec<_>>().join("&")
+}
+
+/// The `Authorization` header of a request signed at `amz_date`
+pub(crate) fn authorization(config: &S3Config, request: &CanonicalRequest, amz_date: &str) -> String {
+    let signed_headers = request.headers.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>().join(";");
+    let canonical_headers: String = request.headers.iter().map(|(name, value)| format!("{}:{}\n", name, value.trim())).collect();
+    let canonical_request = format!(
+        "{}\n{}\n{}\n{}\n{}\n{}",
+        request.method,
+        request.path,
+        canonical_query(request.query),
+        canonical_headers,
+        signed_headers,
+        request.payload_hash
+    );
+
+    let date = &amz_date[..8];
+    let scope = format!("{}/{}/s3/aws4_request", date, config.region);
+    let string_to_sign = format!("AWS4-HMAC-SHA256\n{}\n{}\n{}", amz_date, scope, hex(&sha256(canonical_request.as_bytes())));
+
+    let key = [date, config.region.as_str(), "s3", "aws4_request"]
+        .iter()
+        .fold(format!("AWS4{}", config.secret_key).into_bytes(), |key, part| hmac_sha256(&key, part.as_bytes()).to_vec());
+    let signature = hex(&hmac_sha256(&key, string_to_sign.as_bytes()));
+
+    format!(
+        "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+        config.access_key, scope, signed_headers, signature
+    )
+}
+
+pub(crate) fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+const SHA256_K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+fn sha256_block(state: &mut [u32; 8], block: &[u8]) {
+    let mut w = [0u32; 64];
+    for (word, bytes) in w.iter_mut().zip(block.chunks_exact(4)) {
+        *word = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+    }
+    for i in 16..64 {
+        let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+        let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+        w[i] = w[i - 16].wrapping_add(s0).wrapping_add(w[i - 7]).wrapping_add(s1);
+    }
+
+    let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = *state;
+    for (k, w) in SHA256_K.iter().zip(w.iter()) {
+        let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+        let ch = (e & f) ^ (!e & g);
+        let t1 = h.wrapping_add(s1).wrapping_add(ch).wrapping_add(*k).wrapping_add(*w);
+        let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+        let maj = (a & b) ^ (a & c) ^ (b & c);
+        let t2 = s0.wrapping_add(maj);
+        h = g;
+        g = f;
+        f = e;
+        e = d.wrapping_add(t1);
+        d = c;
+        c = b;
+        b = a;
+        a = t1.wrapping_add(t2);
+    }
+
+    for (state, value) in state.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+        *state = state.wrapping_add(value);
+    }
+}
+
+pub(crate) fn sha256(data: &[u8]) -> [u8; 32] {
+    let mut state: [u32; 8] = [0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19];
+
+    let blocks = data.chunks_exact(64);
+    let mut tail = blocks.remainder().to_vec();
+    for block in blocks {
+        sha256_block(&mut state, block);
+    }
+
+    tail.push(0x80);
+    while tail.len() % 64 != 56 {
+        tail.push(0);
+    }
+    tail.extend_from_slice(&((data.len() as u64).wrapping_mul(8)).to_be_bytes());
+    for block in tail.chunks_exact(64) {
+        sha256_block(&mut state, block);
+    }
+
+    let mut digest = [0u8; 32];
+    for (bytes, word) in digest.chunks_exact_mut(4).zip(state) {
+        bytes.copy_from_slice(&word.to_be_bytes());
+    }
+    digest
+}
+
+pub(crate) fn hmac_sha256(key: &[u8], message: &[u8]) -> [u8; 32] {
+    let mut block = [0u8; 64];
+    if key.len() > 64 {
+        block[..32].copy_from_slice(&sha256(key));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+
+    let mut inner: Vec<u8> = block.iter().map(|byte| byte ^ 0x36).collect();
+    inner.extend_from_slice(message);
+    let mut outer: Vec<u8> = block.iter().map(|byte| byte ^ 0x5c).collect();
+    outer.extend_from_slice(&sha256(&inner));
+    sha256(&outer)
+}
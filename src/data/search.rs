@@ -0,0 +1,205 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use rustc_hash::{FxHashMap, FxHashSet};
+
+use crate::{EntityId, EntityType, Error, FieldType, Result, Value};
+
+/// Where a token occurs: the field and its word positions in it
+type Postings = FxHashMap<(EntityId, FieldType), Vec<u32>>;
+
+/// Inverted index over the String fields marked `searchable` in their
+/// metadata. Tokens are kept sorted so prefix queries are a range scan.
+#[derive(Debug, Clone, Default)]
+pub struct SearchIndex {
+    /// Searchable fields of each entity type, including inherited ones
+    searchable: FxHashSet<(EntityType, FieldType)>,
+    postings: BTreeMap<String, Postings>,
+    /// Tokens indexed for each field, so a value can be unindexed without it
+    indexed: FxHashMap<EntityId, FxHashMap<FieldType, Vec<String>>>,
+}
+
+/// One term of a query
+#[derive(Debug, PartialEq)]
+enum Term {
+    Word(String),
+    Prefix(String),
+    Phrase(Vec<String>),
+}
+
+/// Lowercase words of a text, split on anything that isn't a letter or digit
+fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| word.to_lowercase())
+        .collect()
+}
+
+/// Parse a query: words, `prefix*` and `"quoted phrases"`. Every term must
+/// match for an entity to be found.
+fn parse_query(query: &str) -> Result<Vec<Term>> {
+    let mut terms = Vec::new();
+    for (i, part) in query.split('"').enumerate() {
+        if i % 2 == 1 {
+            let words = tokenize(part);
+            if !words.is_empty() {
+                terms.push(Term::Phrase(words));
+            }
+            continue;
+        }
+        for word in part.split_whitespace() {
+            let (word, prefix) = match word.strip_suffix('*') {
+                Some(word) => (word, true),
+                None => (word, false),
+            };
+            // A word with punctuation inside is searched for as a phrase
+            let mut tokens = tokenize(word);
+            match (tokens.len(), prefix) {
+                (0, _) => {}
+                (1, true) => terms.push(Term::Prefix(tokens.remove(0))),
+                (1, false) => terms.push(Term::Word(tokens.remove(0))),
+                _ => terms.push(Term::Phrase(tokens)),
+            }
+        }
+    }
+
+    if query.matches('"').count() % 2 == 1 {
+        return Err(Error::InvalidRequest(format!("Unterminated phrase in search '{}'", query)));
+    }
+    if terms.is_empty() {
+        return Err(Error::InvalidRequest("Empty search".to_string()));
+    }
+    Ok(terms)
+}
+
+impl SearchIndex {
+    pub fn is_searchable(&self, entity_id: EntityId, field_type: FieldType) -> bool {
+        self.searchable.contains(&(entity_id.extract_type(), field_type))
+    }
+
+    pub fn searchable_fields(&self) -> &FxHashSet<(EntityType, FieldType)> {
+        &self.searchable
+    }
+
+    /// Start over with a new set of searchable fields and their current values
+    pub fn rebuild<'a>(&mut self, searchable: FxHashSet<(EntityType, FieldType)>, fields: impl Iterator<Item = (EntityId, FieldType, &'a Value)>) {
+        *self = SearchIndex { searchable, ..Default::default() };
+        for (entity_id, field_type, value) in fields {
+            self.update(entity_id, field_type, Some(value));
+        }
+    }
+
+    /// Index a field's new value, or unindex it for None
+    pub fn update(&mut self, entity_id: EntityId, field_type: FieldType, value: Option<&Value>) {
+        if !self.is_searchable(entity_id, field_type) {
+            return;
+        }
+
+        if let Some(tokens) = self.indexed.get_mut(&entity_id).and_then(|fields| fields.remove(&field_type)) {
+            for token in tokens {
+                if let Some(postings) = self.postings.get_mut(&token) {
+                    postings.remove(&(entity_id, field_type));
+                    if postings.is_empty() {
+                        self.postings.remove(&token);
+                    }
+                }
+            }
+        }
+
+        let Some(Value::String(text)) = value else {
+            return;
+        };
+        let tokens = tokenize(text);
+        for (position, token) in tokens.iter().enumerate() {
+            self.postings
+                .entry(token.clone())
+                .or_default()
+                .entry((entity_id, field_type))
+                .or_default()
+                .push(position as u32);
+        }
+        self.indexed.entry(entity_id).or_default().insert(field_type, tokens);
+    }
+
+    /// Unindex every field of an entity
+    pub fn remove_entity(&mut self, entity_id: EntityId) {
+        let field_types: Vec<FieldType> = self
+            .indexed
+            .get(&entity_id)
+            .map(|fields| fields.keys().copied().collect())
+            .unwrap_or_default();
+        for field_type in field_types {
+            self.update(entity_id, field_type, None);
+        }
+        self.indexed.remove(&entity_id);
+    }
+
+    /// Entities matching every term of `query`, in any of their searchable
+    /// fields (each phrase within a single field)
+    pub fn search(&self, query: &str) -> Result<FxHashSet<EntityId>> {
+        let mut found: Option<FxHashSet<EntityId>> = None;
+        for term in parse_query(query)? {
+            let matches = self.matches(&term);
+            found = Some(match found {
+                Some(found) => found.intersection(&matches).copied().collect(),
+                None => matches,
+            });
+        }
+        Ok(found.unwrap_or_default())
+    }
+
+    fn matches(&self, term: &Term) -> FxHashSet<EntityId> {
+        match term {
+            Term::Word(word) => self
+                .postings
+                .get(word)
+                .map(|postings| postings.keys().map(|(entity_id, _)| *entity_id).collect())
+                .unwrap_or_default(),
+            Term::Prefix(prefix) => self
+                .postings
+                .range::<str, _>((std::ops::Bound::Included(prefix.as_str()), std::ops::Bound::Unbounded))
+                .take_while(|(token, _)| token.starts_with(prefix.as_str()))
+                .flat_map(|(_, postings)| postings.keys().map(|(entity_id, _)| *entity_id))
+                .collect(),
+            Term::Phrase(words) => self.phrase_matches(words),
+        }
+    }
+
+    /// Fields where the words follow each other, starting from the fields
+    /// holding the first word
+    fn phrase_matches(&self, words: &[String]) -> FxHashSet<EntityId> {
+        let Some(first) = self.postings.get(&words[0]) else {
+            return FxHashSet::default();
+        };
+
+        first
+            .iter()
+            .filter(|(key, positions)| {
+                positions.iter().any(|start| {
+                    words.iter().enumerate().skip(1).all(|(offset, word)| {
+                        self.postings
+                            .get(word)
+                            .and_then(|postings| postings.get(*key))
+                            .is_some_and(|positions| positions.contains(&(start + offset as u32)))
+                    })
+                })
+            })
+            .map(|((entity_id, _), _)| *entity_id)
+            .collect()
+    }
+}
+
+/// Index a field's new value (None once it is gone), copying the index
+/// first if a read snapshot shares it
+pub(crate) fn reindex_search(index: &mut Arc<SearchIndex>, entity_id: EntityId, field_type: FieldType, value: Option<&Value>) {
+    if index.is_searchable(entity_id, field_type) {
+        Arc::make_mut(index).update(entity_id, field_type, value);
+    }
+}
+
+/// Unindex every field of a deleted entity
+pub(crate) fn unindex_entity(index: &mut Arc<SearchIndex>, entity_id: EntityId) {
+    if index.indexed.contains_key(&entity_id) {
+        Arc::make_mut(index).remove_entity(entity_id);
+    }
+}
@@ -0,0 +1,64 @@
+use std::collections::VecDeque;
+
+use crate::{Duration, Error, Result, Timestamp, Value};
+
+/// History of a `Series` field, oldest sample first
+pub type Samples = VecDeque<(Timestamp, Value)>;
+
+/// Add a sample, then drop the oldest ones beyond `capacity` or more than
+/// `retention` older than the latest
+pub(crate) fn append(samples: &mut Samples, time: Timestamp, value: Value, capacity: usize, retention: Option<Duration>) {
+    // Writes arrive in time order, except for replicated ones racing local ones
+    let index = samples.partition_point(|(sample_time, _)| *sample_time <= time);
+    samples.insert(index, (time, value));
+
+    while samples.len() > capacity {
+        samples.pop_front();
+    }
+    if let (Some(retention), Some((latest, _))) = (retention, samples.back()) {
+        let oldest = *latest - retention;
+        while samples.front().is_some_and(|(sample_time, _)| *sample_time < oldest) {
+            samples.pop_front();
+        }
+    }
+}
+
+/// The samples taken between `from` and `to` (both inclusive). With a
+/// `downsample` interval the samples are averaged over consecutive buckets
+/// of that length, starting at `from` (or the first sample), and each
+/// bucket is reported at its start.
+pub fn select(samples: &Samples, from: Option<Timestamp>, to: Option<Timestamp>, downsample: Option<Duration>) -> Result<Vec<(Timestamp, Value)>> {
+    let in_range = samples
+        .iter()
+        .filter(|(time, _)| from.is_none_or(|from| *time >= from) && to.is_none_or(|to| *time <= to));
+
+    let Some(interval) = downsample else {
+        return Ok(in_range.cloned().collect());
+    };
+    if interval <= Duration::ZERO {
+        return Err(Error::InvalidRequest(format!("Downsample interval must be positive, got {}", interval)));
+    }
+
+    let mut buckets: Vec<(Timestamp, Vec<&Value>)> = Vec::new();
+    for (time, value) in in_range {
+        let start = from.or(buckets.first().map(|(start, _)| *start)).unwrap_or(*time);
+        let bucket_start = start + interval * ((*time - start) / interval).floor();
+        match buckets.last_mut() {
+            Some((last_start, values)) if *last_start == bucket_start => values.push(value),
+            _ => buckets.push((bucket_start, vec![value])),
+        }
+    }
+    Ok(buckets.into_iter().map(|(start, values)| (start, mean(&values))).collect())
+}
+
+/// Average of same-typed samples, rounded for Int samples
+fn mean(values: &[&Value]) -> Value {
+    let count = values.len() as f64;
+    match values.first() {
+        Some(Value::Int(_)) => {
+            let total: i128 = values.iter().filter_map(|value| value.as_int()).map(i128::from).sum();
+            Value::Int((total as f64 / count).round() as i64)
+        }
+        _ => Value::Float(values.iter().filter_map(|value| value.as_float()).sum::<f64>() / count),
+    }
+}
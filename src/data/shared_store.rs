@@ -0,0 +1,203 @@
+use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use crate::{
+    AdjustBehavior, AggregateOp, Complete, EntityId, EntitySchema, EntityType, Error, FieldSchema, FieldType, PageOpts, PageResult, PushCondition, ReadSnapshot, Result, Single, Store, StoreTrait, Timestamp, TypesBulk, Value
+};
+
+/// A `Store` shared between threads behind one `RwLock`.
+///
+/// Reads (lookups, `read`, finds, snapshots) hold the lock shared and run in
+/// parallel. The store's caches of computed values and value indexes take
+/// their own locks only briefly, and each reader evaluates filters and
+/// computed fields with its own CEL executor, so readers don't wait for each
+/// other. Anything that changes the store holds the lock exclusively.
+///
+/// The lock isn't sharded by entity type, and reads don't bypass it: the
+/// store keeps all types in the same maps, and one write can change several
+/// types through `Parent` and `Children`, references, triggers and computed
+/// fields. A write therefore holds up every reader while it runs. Readers
+/// that can't wait should take a `begin_read_snapshot`, which holds the lock
+/// only while the view is made.
+///
+/// `StoreTrait` is implemented for `&SharedStore`, so any number of threads
+/// can use the same store through a reference (or an `Arc`):
+///
+/// ```rust,ignore
+/// let shared = Arc::new(SharedStore::new(store));
+/// let reader = Arc::clone(&shared);
+/// std::thread::spawn(move || (&*reader).read(entity_id, &[ft_name]));
+/// (&*shared).write(entity_id, &[ft_name], Value::from("pump"), None, None, None, None)?;
+/// ```
+#[derive(Debug)]
+pub struct SharedStore {
+    store: RwLock<Store>,
+}
+
+impl SharedStore {
+    pub fn new(store: Store) -> Self {
+        Self { store: RwLock::new(store) }
+    }
+
+    /// Hold the store for reading, e.g. to make several reads see the same state
+    pub fn read_guard(&self) -> RwLockReadGuard<'_, Store> {
+        self.store.read().unwrap()
+    }
+
+    /// Hold the store for writing, e.g. to register notifications or triggers
+    pub fn write_guard(&self) -> RwLockWriteGuard<'_, Store> {
+        self.store.write().unwrap()
+    }
+
+    /// Read-only view of the store as it is now, see `Store::begin_read_snapshot`.
+    /// The lock is only held while the view is made.
+    pub fn begin_read_snapshot(&self) -> ReadSnapshot {
+        self.read_guard().begin_read_snapshot()
+    }
+
+    pub fn into_inner(self) -> Store {
+        self.store.into_inner().unwrap()
+    }
+
+    /// Copy of the complete schema for an entity type (including inherited
+    /// fields), as the lock can't be held past the call
+    pub fn get_complete_entity_schema(&self, entity_type: EntityType) -> Result<EntitySchema<Complete>> {
+        self.read_guard().get_complete_entity_schema(entity_type).cloned()
+    }
+}
+
+impl StoreTrait for &SharedStore {
+    fn get_entity_type(&self, name: &str) -> Result<EntityType> {
+        self.read_guard().get_entity_type(name)
+    }
+
+    fn resolve_entity_type(&self, entity_type: EntityType) -> Result<String> {
+        self.read_guard().resolve_entity_type(entity_type)
+    }
+
+    fn get_field_type(&self, name: &str) -> Result<FieldType> {
+        self.read_guard().get_field_type(name)
+    }
+
+    fn resolve_field_type(&self, field_type: FieldType) -> Result<String> {
+        self.read_guard().resolve_field_type(field_type)
+    }
+
+    fn get_types_bulk(&self, entity_types: &[&str], field_types: &[&str]) -> Result<TypesBulk> {
+        StoreTrait::get_types_bulk(&*self.read_guard(), entity_types, field_types)
+    }
+
+    fn get_entity_schema(&self, entity_type: EntityType) -> Result<EntitySchema<Single>> {
+        self.read_guard().get_entity_schema(entity_type)
+    }
+
+    fn get_complete_entity_schema(&self, _entity_type: EntityType) -> Result<&EntitySchema<Complete>> {
+        Err(Error::InvalidRequest("SharedStore can't lend a schema past its lock, use get_complete_entity_schema_owned".to_string()))
+    }
+
+    fn get_complete_entity_schema_owned(&self, entity_type: EntityType) -> Result<EntitySchema<Complete>> {
+        SharedStore::get_complete_entity_schema(self, entity_type)
+    }
+
+    fn get_field_schema(&self, entity_type: EntityType, field_type: FieldType) -> Result<FieldSchema> {
+        self.read_guard().get_field_schema(entity_type, field_type)
+    }
+
+    fn set_field_schema(&mut self, entity_type: EntityType, field_type: FieldType, schema: FieldSchema) -> Result<()> {
+        self.write_guard().set_field_schema(entity_type, field_type, schema)
+    }
+
+    fn entity_exists(&self, entity_id: EntityId) -> bool {
+        self.read_guard().entity_exists(entity_id)
+    }
+
+    fn field_exists(&self, entity_type: EntityType, field_type: FieldType) -> bool {
+        self.read_guard().field_exists(entity_type, field_type)
+    }
+
+    fn resolve_indirection(&self, entity_id: EntityId, fields: &[FieldType]) -> Result<(EntityId, FieldType)> {
+        self.read_guard().resolve_indirection(entity_id, fields)
+    }
+
+    fn read(&self, entity_id: EntityId, field_path: &[FieldType]) -> Result<(Value, Timestamp, Option<EntityId>)> {
+        self.read_guard().read(entity_id, field_path)
+    }
+
+    fn write(&mut self, entity_id: EntityId, field_path: &[FieldType], value: Value, writer_id: Option<EntityId>, write_time: Option<Timestamp>, push_condition: Option<PushCondition>, adjust_behavior: Option<AdjustBehavior>) -> Result<()> {
+        self.write_guard().write(entity_id, field_path, value, writer_id, write_time, push_condition, adjust_behavior)
+    }
+
+    fn create_entity(&mut self, entity_type: EntityType, parent_id: Option<EntityId>, name: &str) -> Result<EntityId> {
+        self.write_guard().create_entity(entity_type, parent_id, name)
+    }
+
+    fn delete_entity(&mut self, entity_id: EntityId) -> Result<()> {
+        self.write_guard().delete_entity(entity_id)
+    }
+
+    fn get_or_create(&mut self, entity_type: EntityType, parent_id: Option<EntityId>, name: &str) -> Result<(EntityId, bool)> {
+        self.write_guard().get_or_create(entity_type, parent_id, name)
+    }
+
+    fn upsert_field(&mut self, entity_id: EntityId, field_type: FieldType, value: Value) -> Result<bool> {
+        self.write_guard().upsert_field(entity_id, field_type, value)
+    }
+
+    fn rename_entity(&mut self, entity_id: EntityId, new_name: &str) -> Result<()> {
+        self.write_guard().rename_entity(entity_id, new_name)
+    }
+
+    fn update_schema(&mut self, schema: EntitySchema<Single, String, String>) -> Result<()> {
+        self.write_guard().update_schema(schema)
+    }
+
+    fn take_snapshot(&self) -> crate::data::Snapshot {
+        self.read_guard().take_snapshot()
+    }
+
+    fn stats(&self) -> Result<crate::StoreStats> {
+        Ok(self.read_guard().stats())
+    }
+
+    fn find_entities_paginated(&self, entity_type: EntityType, page_opts: Option<&PageOpts>, filter: Option<&str>) -> Result<PageResult<EntityId>> {
+        self.read_guard().find_entities_paginated(entity_type, page_opts, filter)
+    }
+
+    fn find_entities_exact(&self, entity_type: EntityType, page_opts: Option<&PageOpts>, filter: Option<&str>) -> Result<PageResult<EntityId>> {
+        self.read_guard().find_entities_exact(entity_type, page_opts, filter)
+    }
+
+    fn find_entities(&self, entity_type: EntityType, filter: Option<&str>) -> Result<Vec<EntityId>> {
+        self.read_guard().find_entities(entity_type, filter)
+    }
+
+    fn find_referencing(&self, entity_id: EntityId) -> Result<Vec<(EntityId, FieldType)>> {
+        Ok(self.read_guard().find_referencing(entity_id))
+    }
+
+    fn aggregate(&self, entity_type: EntityType, field_type: FieldType, op: AggregateOp, filter: Option<&str>) -> Result<Option<Value>> {
+        StoreTrait::aggregate(&*self.read_guard(), entity_type, field_type, op, filter)
+    }
+
+    fn get_entity_types(&self) -> Result<Vec<EntityType>> {
+        self.read_guard().get_entity_types()
+    }
+
+    fn get_entity_types_paginated(&self, page_opts: Option<&PageOpts>) -> Result<PageResult<EntityType>> {
+        self.read_guard().get_entity_types_paginated(page_opts)
+    }
+
+    // Tree operations make several changes; each runs under one write lock
+    // so other threads never see it half done
+
+    fn move_entity(&mut self, entity_id: EntityId, new_parent: Option<EntityId>) -> Result<()> {
+        self.write_guard().move_entity(entity_id, new_parent)
+    }
+
+    fn copy_entity_tree(&mut self, entity_id: EntityId, new_parent: Option<EntityId>) -> Result<EntityId> {
+        self.write_guard().copy_entity_tree(entity_id, new_parent)
+    }
+
+    fn delete_entity_tree(&mut self, entity_id: EntityId) -> Result<()> {
+        self.write_guard().delete_entity_tree(entity_id)
+    }
+}
@@ -0,0 +1,503 @@
+//! Deterministic simulation of replicated stores. A `Simulation` runs
+//! several in-memory stores with a virtual `SimClock` and a `SimNetwork`
+//! that can delay, drop, duplicate and reorder the peer commands exchanged
+//! by their `PeerReplicator`s. Every random choice comes from one seed, so a
+//! failing run is reproduced by running the same seed again.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::data::resp::{FullSyncRequestCommand, FullSyncResponseCommand, PeerHandshakeCommand, SyncWriteCommand};
+use crate::{
+    epoch, Duration, EntityId, EntitySchema, EntityType, Error, FieldType, PeerReplicator, Result, Single, Store, StoreTrait, Timestamp, Value,
+};
+
+/// Virtual time shared by every node of a simulation. It only moves when
+/// the simulation advances it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SimClock {
+    start: Timestamp,
+    now: Timestamp,
+}
+
+impl SimClock {
+    pub fn new(start: Timestamp) -> Self {
+        Self { start, now: start }
+    }
+
+    pub fn now(&self) -> Timestamp {
+        self.now
+    }
+
+    pub fn advance(&mut self, by: Duration) {
+        self.now += by;
+    }
+
+    pub fn start(&self) -> Timestamp {
+        self.start
+    }
+
+    /// Virtual time passed since the clock started
+    pub fn elapsed(&self) -> Duration {
+        self.now - self.start
+    }
+}
+
+impl Default for SimClock {
+    fn default() -> Self {
+        Self::new(epoch())
+    }
+}
+
+/// How the simulated network treats each message
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimConfig {
+    /// Smallest delay between sending and delivering a message
+    pub min_latency: Duration,
+    /// Largest delay between sending and delivering a message
+    pub max_latency: Duration,
+    /// Chance, from 0 to 1, that a message is lost
+    pub drop_rate: f64,
+    /// Chance, from 0 to 1, that a message is delivered twice
+    pub duplicate_rate: f64,
+    /// Keep messages between two nodes in the order they were sent, as a
+    /// TCP connection would. When false, latency alone decides the order.
+    pub in_order: bool,
+    /// How far the clock moves on each `step`
+    pub tick: Duration,
+}
+
+impl SimConfig {
+    pub fn with_latency(mut self, min: Duration, max: Duration) -> Self {
+        self.min_latency = min;
+        self.max_latency = max.max(min);
+        self
+    }
+
+    pub fn with_drop_rate(mut self, drop_rate: f64) -> Self {
+        self.drop_rate = drop_rate.clamp(0.0, 1.0);
+        self
+    }
+
+    pub fn with_duplicate_rate(mut self, duplicate_rate: f64) -> Self {
+        self.duplicate_rate = duplicate_rate.clamp(0.0, 1.0);
+        self
+    }
+
+    pub fn with_in_order(mut self, in_order: bool) -> Self {
+        self.in_order = in_order;
+        self
+    }
+
+    pub fn with_tick(mut self, tick: Duration) -> Self {
+        self.tick = tick;
+        self
+    }
+}
+
+impl Default for SimConfig {
+    /// A lossless, in-order network with 1 to 10ms of latency
+    fn default() -> Self {
+        Self {
+            min_latency: Duration::milliseconds(1),
+            max_latency: Duration::milliseconds(10),
+            drop_rate: 0.0,
+            duplicate_rate: 0.0,
+            in_order: true,
+            tick: Duration::milliseconds(1),
+        }
+    }
+}
+
+/// A peer command in flight between two nodes
+#[derive(Debug, Clone)]
+pub enum SimMessage {
+    Handshake(PeerHandshakeCommand<'static>),
+    FullSyncRequest(FullSyncRequestCommand<'static>),
+    FullSyncResponse(FullSyncResponseCommand<'static>),
+    SyncWrite(SyncWriteCommand<'static>),
+}
+
+impl SimMessage {
+    /// The RESP command name, as it would appear on the wire
+    pub fn name(&self) -> &'static str {
+        match self {
+            SimMessage::Handshake(_) => "HANDSHAKE",
+            SimMessage::FullSyncRequest(_) => "FSYNCREQ",
+            SimMessage::FullSyncResponse(_) => "FSYNCRESP",
+            SimMessage::SyncWrite(_) => "SYNCSET",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Envelope {
+    from: usize,
+    to: usize,
+    message: SimMessage,
+}
+
+/// A network between simulated nodes. Messages wait in a queue until the
+/// clock reaches their delivery time; links can be cut in both directions.
+#[derive(Debug)]
+pub struct SimNetwork {
+    config: SimConfig,
+    rng: StdRng,
+    /// Keyed by delivery time, then by send order to break ties
+    queue: BTreeMap<(Timestamp, u64), Envelope>,
+    /// Latest delivery time per link, used to keep links in order
+    last_delivery: BTreeMap<(usize, usize), Timestamp>,
+    /// Cut links, stored with the lower node index first
+    blocked: BTreeSet<(usize, usize)>,
+    sent: u64,
+}
+
+impl SimNetwork {
+    pub fn new(seed: u64, config: SimConfig) -> Self {
+        Self {
+            config,
+            rng: StdRng::seed_from_u64(seed),
+            queue: BTreeMap::new(),
+            last_delivery: BTreeMap::new(),
+            blocked: BTreeSet::new(),
+            sent: 0,
+        }
+    }
+
+    pub fn config(&self) -> &SimConfig {
+        &self.config
+    }
+
+    /// Number of messages waiting for delivery
+    pub fn in_flight(&self) -> usize {
+        self.queue.len()
+    }
+
+    pub fn is_blocked(&self, a: usize, b: usize) -> bool {
+        self.blocked.contains(&(a.min(b), a.max(b)))
+    }
+
+    /// Cut the link between `a` and `b`. Messages already in flight on it are lost.
+    pub fn block(&mut self, a: usize, b: usize) {
+        self.blocked.insert((a.min(b), a.max(b)));
+        self.queue.retain(|_, envelope| !((envelope.from == a && envelope.to == b) || (envelope.from == b && envelope.to == a)));
+    }
+
+    pub fn unblock(&mut self, a: usize, b: usize) {
+        self.blocked.remove(&(a.min(b), a.max(b)));
+    }
+
+    /// Queue `message` for delivery. Returns what happened to it, for the trace.
+    fn send(&mut self, now: Timestamp, from: usize, to: usize, message: SimMessage) -> &'static str {
+        if self.is_blocked(from, to) {
+            return "blocked";
+        }
+        if self.rng.gen_bool(self.config.drop_rate) {
+            return "dropped";
+        }
+
+        let copies = if self.rng.gen_bool(self.config.duplicate_rate) { 2 } else { 1 };
+        for _ in 0..copies {
+            let delivery = self.delivery_time(now, from, to);
+            self.sent += 1;
+            self.queue.insert((delivery, self.sent), Envelope { from, to, message: message.clone() });
+        }
+        if copies == 2 { "duplicated" } else { "sent" }
+    }
+
+    fn delivery_time(&mut self, now: Timestamp, from: usize, to: usize) -> Timestamp {
+        let min = self.config.min_latency.whole_microseconds() as i64;
+        let max = self.config.max_latency.whole_microseconds() as i64;
+        let mut delivery = now + Duration::microseconds(self.rng.gen_range(min..=max.max(min)));
+        if self.config.in_order {
+            let last = self.last_delivery.entry((from, to)).or_insert(delivery);
+            delivery = delivery.max(*last);
+            *last = delivery;
+        }
+        delivery
+    }
+
+    /// Take the next message due at or before `now`
+    fn next_due(&mut self, now: Timestamp) -> Option<Envelope> {
+        let (&key, _) = self.queue.iter().next().filter(|((delivery, _), _)| *delivery <= now)?;
+        self.queue.remove(&key)
+    }
+}
+
+#[derive(Debug)]
+struct SimNode {
+    store: Store,
+    replicator: PeerReplicator,
+    skew: Duration,
+}
+
+/// Replicated stores run against a virtual clock and a simulated network,
+/// for Jepsen-style tests of partitions, clock skew and message loss that
+/// run in-process and reproduce from a seed.
+///
+/// Node `i` is named `node-i` and started before node `i + 1`. Nodes shake
+/// hands when the simulation is created, but nothing is delivered until the
+/// simulation steps. Each step ships the nodes' pending writes to their
+/// peers, delivers the messages that are due, then advances the clock.
+///
+/// ```rust,ignore
+/// let mut sim = Simulation::new(seed, 3, SimConfig::default().with_drop_rate(0.1))?;
+/// let et_pump = sim.update_schema(SchemaBuilder::object("Pump").int("Speed", 0).build())?;
+/// sim.run_for(Duration::milliseconds(50))?;
+/// sim.set_skew(1, Duration::seconds(-5));
+/// sim.partition(0, 1);
+/// ...
+/// println!("{}", sim.trace().join("\n"));
+/// ```
+#[derive(Debug)]
+pub struct Simulation {
+    seed: u64,
+    clock: SimClock,
+    network: SimNetwork,
+    nodes: Vec<SimNode>,
+    trace: Vec<String>,
+}
+
+impl Simulation {
+    /// `size` empty nodes that start connecting to each other
+    pub fn new(seed: u64, size: usize, config: SimConfig) -> Result<Self> {
+        let mut sim = Self {
+            seed,
+            clock: SimClock::default(),
+            network: SimNetwork::new(seed, config),
+            nodes: (0..size)
+                .map(|index| SimNode {
+                    store: Store::new(),
+                    replicator: PeerReplicator::new(Self::machine_id(index), index as u64 + 1),
+                    skew: Duration::ZERO,
+                })
+                .collect(),
+            trace: Vec::new(),
+        };
+        sim.set_store_times();
+        for first in 0..size {
+            for second in first + 1..size {
+                sim.connect(first, second);
+            }
+        }
+        Ok(sim)
+    }
+
+    pub fn machine_id(index: usize) -> String {
+        format!("node-{}", index)
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn clock(&self) -> &SimClock {
+        &self.clock
+    }
+
+    pub fn network(&self) -> &SimNetwork {
+        &self.network
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// The store of node `index`; panics if there is no such node
+    pub fn store(&self, index: usize) -> &Store {
+        &self.nodes[index].store
+    }
+
+    pub fn store_mut(&mut self, index: usize) -> &mut Store {
+        &mut self.nodes[index].store
+    }
+
+    pub fn replicator(&self, index: usize) -> &PeerReplicator {
+        &self.nodes[index].replicator
+    }
+
+    /// Everything the network did so far, one line per event. Two runs with
+    /// the same seed and the same calls produce the same trace.
+    pub fn trace(&self) -> &[String] {
+        &self.trace
+    }
+
+    /// The time on node `index`'s clock: the virtual time plus its skew
+    pub fn node_time(&self, index: usize) -> Timestamp {
+        self.clock.now() + self.nodes[index].skew
+    }
+
+    /// Make node `index`'s clock run ahead (or behind, if negative) of the others
+    pub fn set_skew(&mut self, index: usize, skew: Duration) {
+        self.nodes[index].skew = skew;
+        self.set_store_times();
+        self.record(format!("skew {} {}", Self::machine_id(index), skew));
+    }
+
+    /// Add or update a schema on every node, as `TestCluster::update_schema` does
+    pub fn update_schema(&mut self, schema: EntitySchema<Single, String, String>) -> Result<EntityType> {
+        for node in &mut self.nodes {
+            node.store.update_schema(schema.clone())?;
+        }
+        match self.nodes.first() {
+            Some(node) => node.store.get_entity_type(&schema.entity_type),
+            None => Err(Error::EntityTypeStrNotFound(schema.entity_type)),
+        }
+    }
+
+    /// Write a field on node `index`, stamped with that node's clock
+    pub fn write(&mut self, index: usize, entity_id: EntityId, field_path: &[FieldType], value: Value) -> Result<()> {
+        let write_time = self.node_time(index);
+        self.nodes[index].store.write(entity_id, field_path, value, None, Some(write_time), None, None)
+    }
+
+    /// Cut the link between nodes `a` and `b`. Both sides see the
+    /// connection drop and forget each other.
+    pub fn partition(&mut self, a: usize, b: usize) {
+        self.network.block(a, b);
+        self.nodes[a].replicator.remove_peer(&Self::machine_id(b));
+        self.nodes[b].replicator.remove_peer(&Self::machine_id(a));
+        self.record(format!("partition {} {}", Self::machine_id(a), Self::machine_id(b)));
+    }
+
+    /// Cut node `index` off from every other node
+    pub fn isolate(&mut self, index: usize) {
+        for other in 0..self.nodes.len() {
+            if other != index && !self.network.is_blocked(index, other) {
+                self.partition(index, other);
+            }
+        }
+    }
+
+    /// Restore every cut link. The nodes on each side shake hands again,
+    /// and the younger one takes a full sync from the older one.
+    pub fn heal(&mut self) {
+        for first in 0..self.nodes.len() {
+            for second in first + 1..self.nodes.len() {
+                if self.network.is_blocked(first, second) {
+                    self.network.unblock(first, second);
+                    self.record(format!("heal {} {}", Self::machine_id(first), Self::machine_id(second)));
+                    self.connect(first, second);
+                }
+            }
+        }
+    }
+
+    /// Ship pending writes, deliver the messages that are due, then move the
+    /// clock forward by one tick
+    pub fn step(&mut self) -> Result<()> {
+        self.ship_writes()?;
+        while let Some(envelope) = self.network.next_due(self.clock.now()) {
+            self.deliver(envelope)?;
+        }
+        let tick = self.network.config().tick;
+        self.clock.advance(tick);
+        self.set_store_times();
+        Ok(())
+    }
+
+    /// Step until `duration` of virtual time has passed
+    pub fn run_for(&mut self, duration: Duration) -> Result<()> {
+        let until = self.clock.now() + duration;
+        while self.clock.now() < until {
+            self.step()?;
+        }
+        Ok(())
+    }
+
+    /// Step until no writes are pending and nothing is in flight, for at
+    /// most `limit` of virtual time. Returns whether the network went quiet.
+    pub fn run_until_quiet(&mut self, limit: Duration) -> Result<bool> {
+        let until = self.clock.now() + limit;
+        while self.clock.now() < until {
+            self.step()?;
+            if self.network.in_flight() == 0 && self.nodes.iter().all(|node| !node.replicator.has_unsent_writes(&node.store)) {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Read a field on every node, for convergence checks
+    pub fn read_all(&self, entity_id: EntityId, field_path: &[FieldType]) -> Result<Vec<Value>> {
+        self.nodes
+            .iter()
+            .map(|node| node.store.read(entity_id, field_path).map(|(value, _, _)| value))
+            .collect()
+    }
+
+    /// Stamp each node's changes with its own clock rather than the wall clock
+    fn set_store_times(&mut self) {
+        for index in 0..self.nodes.len() {
+            let time = self.node_time(index);
+            self.nodes[index].store.set_time(Some(time));
+        }
+    }
+
+    fn connect(&mut self, first: usize, second: usize) {
+        let handshake = self.nodes[first].replicator.handshake();
+        self.send(first, second, SimMessage::Handshake(handshake));
+    }
+
+    fn ship_writes(&mut self) -> Result<()> {
+        for source in 0..self.nodes.len() {
+            let node = &mut self.nodes[source];
+            let Some(batch) = node.replicator.drain_sync_writes(&mut node.store)? else {
+                continue;
+            };
+            for target in 0..self.nodes.len() {
+                if target != source {
+                    self.send(source, target, SimMessage::SyncWrite(batch.clone()));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn send(&mut self, from: usize, to: usize, message: SimMessage) {
+        let name = message.name();
+        let outcome = self.network.send(self.clock.now(), from, to, message);
+        self.record(format!("{} {} -> {} {}", outcome, Self::machine_id(from), Self::machine_id(to), name));
+    }
+
+    fn deliver(&mut self, envelope: Envelope) -> Result<()> {
+        let Envelope { from, to, message } = envelope;
+        self.record(format!("deliver {} -> {} {}", Self::machine_id(from), Self::machine_id(to), message.name()));
+
+        match message {
+            SimMessage::Handshake(handshake) => {
+                let (response, sync_request) = self.nodes[to].replicator.handle_handshake(&handshake);
+                if let Some(response) = response {
+                    self.send(to, from, SimMessage::Handshake(response));
+                }
+                if let Some(sync_request) = sync_request {
+                    self.send(to, from, SimMessage::FullSyncRequest(sync_request));
+                }
+            }
+            SimMessage::FullSyncRequest(_) => {
+                let response = self.nodes[to].replicator.full_sync_response(&self.nodes[to].store)?;
+                self.send(to, from, SimMessage::FullSyncResponse(response));
+            }
+            SimMessage::FullSyncResponse(response) => {
+                let node = &mut self.nodes[to];
+                node.replicator.apply_full_sync(&Self::machine_id(from), &mut node.store, &response)?;
+            }
+            SimMessage::SyncWrite(batch) => {
+                let node = &mut self.nodes[to];
+                node.replicator.apply_sync_writes(&Self::machine_id(from), &mut node.store, &batch)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn record(&mut self, event: String) {
+        let elapsed = self.clock.elapsed();
+        self.trace.push(format!("{}us {}", elapsed.whole_microseconds(), event));
+    }
+}
@@ -0,0 +1,196 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::data::resp::RespValue;
+use crate::{now, Timestamp};
+
+/// Commands taking at least this long are logged by default (10ms)
+pub const DEFAULT_SLOWLOG_THRESHOLD_MICROS: u64 = 10_000;
+
+/// Number of slow commands kept by default
+pub const DEFAULT_SLOWLOG_CAPACITY: usize = 128;
+
+/// Arguments logged per command; the rest are summarized
+pub const SLOWLOG_MAX_ARGS: usize = 32;
+
+/// Characters logged per argument; the rest are summarized
+pub const SLOWLOG_MAX_ARG_LEN: usize = 128;
+
+/// What a `SLOWLOG` command does
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SlowLogAction {
+    /// The most recent entries, newest first
+    Get,
+    /// Drop every entry
+    Reset,
+}
+
+impl SlowLogAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SlowLogAction::Get => "GET",
+            SlowLogAction::Reset => "RESET",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_uppercase().as_str() {
+            "GET" => Some(SlowLogAction::Get),
+            "RESET" => Some(SlowLogAction::Reset),
+            _ => None,
+        }
+    }
+}
+
+/// One command that ran past the threshold
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SlowLogEntry {
+    /// Increasing by one per entry, and not reused after a reset
+    pub id: u64,
+    /// When the command finished
+    pub timestamp: Timestamp,
+    pub duration_micros: u64,
+    /// The command name followed by its arguments, shortened as needed
+    pub args: Vec<String>,
+    /// The connection that sent the command
+    pub client: Option<String>,
+}
+
+/// The most recent commands that took longer than a threshold, as
+/// retrieved with `SLOWLOG GET` and cleared with `SLOWLOG RESET`. The
+/// dispatcher times each command and passes it to `record`.
+///
+/// ```rust,ignore
+/// let started = Instant::now();
+/// let response = dispatch(&mut store, &request);
+/// store.slowlog_mut().record(&request, started.elapsed(), Some(&peer));
+/// ```
+#[derive(Debug, Clone)]
+pub struct SlowLog {
+    entries: VecDeque<SlowLogEntry>,
+    capacity: usize,
+    threshold_micros: Option<u64>,
+    next_id: u64,
+}
+
+impl Default for SlowLog {
+    fn default() -> Self {
+        Self::new(Some(DEFAULT_SLOWLOG_THRESHOLD_MICROS), DEFAULT_SLOWLOG_CAPACITY)
+    }
+}
+
+impl SlowLog {
+    /// Log commands taking at least `threshold_micros` (every command for
+    /// 0, none for None), keeping the last `capacity` of them
+    pub fn new(threshold_micros: Option<u64>, capacity: usize) -> Self {
+        Self {
+            entries: VecDeque::new(),
+            capacity,
+            threshold_micros,
+            next_id: 0,
+        }
+    }
+
+    pub fn threshold_micros(&self) -> Option<u64> {
+        self.threshold_micros
+    }
+
+    pub fn set_threshold_micros(&mut self, threshold_micros: Option<u64>) {
+        self.threshold_micros = threshold_micros;
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Change how many entries are kept, dropping the oldest ones if needed
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        while self.entries.len() > capacity {
+            self.entries.pop_front();
+        }
+    }
+
+    /// Log the command if it took at least the threshold. Returns whether
+    /// it was logged.
+    pub fn record(&mut self, request: &RespValue, duration: Duration, client: Option<&str>) -> bool {
+        let duration_micros = duration.as_micros().min(u64::MAX as u128) as u64;
+        if self.threshold_micros.is_none_or(|threshold| duration_micros < threshold) {
+            return false;
+        }
+
+        let entry = SlowLogEntry {
+            id: self.next_id,
+            timestamp: now(),
+            duration_micros,
+            args: command_args(request),
+            client: client.map(|client| client.to_string()),
+        };
+        self.next_id += 1;
+
+        if self.capacity == 0 {
+            return true;
+        }
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(entry);
+        true
+    }
+
+    /// The `count` most recent entries (all of them for None), newest first
+    pub fn get(&self, count: Option<usize>) -> Vec<SlowLogEntry> {
+        self.entries.iter().rev().take(count.unwrap_or(usize::MAX)).cloned().collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Drop every entry; ids keep counting up
+    pub fn reset(&mut self) {
+        self.entries.clear();
+    }
+}
+
+/// A command frame as readable arguments, with long arguments and long
+/// argument lists cut short
+pub fn command_args(request: &RespValue) -> Vec<String> {
+    let elements = match request {
+        RespValue::Array(elements) => elements.as_slice(),
+        other => std::slice::from_ref(other),
+    };
+
+    let mut args: Vec<String> = elements.iter().take(SLOWLOG_MAX_ARGS).map(|element| shorten(describe(element))).collect();
+    if elements.len() > SLOWLOG_MAX_ARGS {
+        // The last slot says how many were left out, as in Redis
+        args.truncate(SLOWLOG_MAX_ARGS - 1);
+        args.push(format!("... ({} more arguments)", elements.len() - SLOWLOG_MAX_ARGS + 1));
+    }
+    args
+}
+
+fn describe(value: &RespValue) -> String {
+    match value {
+        RespValue::SimpleString(s) | RespValue::Error(s) => s.to_string(),
+        RespValue::Integer(i) => i.to_string(),
+        RespValue::BulkString(data) => String::from_utf8_lossy(data).into_owned(),
+        RespValue::Array(elements) => format!("[{}]", elements.iter().map(describe).collect::<Vec<_>>().join(" ")),
+        RespValue::Null => "(nil)".to_string(),
+    }
+}
+
+fn shorten(arg: String) -> String {
+    let chars = arg.chars().count();
+    if chars <= SLOWLOG_MAX_ARG_LEN {
+        return arg;
+    }
+    let kept: String = arg.chars().take(SLOWLOG_MAX_ARG_LEN).collect();
+    format!("{}... ({} more chars)", kept, chars - SLOWLOG_MAX_ARG_LEN)
+}
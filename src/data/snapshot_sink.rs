@@ -0,0 +1,195 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use time::format_description::{self, FormatItem};
+use time::{PrimitiveDateTime, UtcOffset};
+
+use crate::{verify_snapshot, Error, Result, Snapshot, Store, Timestamp};
+
+/// Name prefix of the snapshots written by `upload_snapshot`
+pub const SNAPSHOT_KEY_PREFIX: &str = "snapshot-";
+const SNAPSHOT_KEY_SUFFIX: &str = ".bin.z";
+
+const COMPACT_TIME_FORMAT: &str = "[year][month][day]T[hour][minute][second].[subsecond digits:9]Z";
+
+fn compact_time_format() -> Vec<FormatItem<'static>> {
+    format_description::parse(COMPACT_TIME_FORMAT).expect("valid format description")
+}
+
+/// `time` in UTC as e.g. `20240310T142501.123456789Z`, for names that sort
+/// in time order
+pub(crate) fn format_compact_time(time: Timestamp) -> String {
+    time.to_offset(UtcOffset::UTC)
+        .format(&compact_time_format())
+        .expect("UTC timestamps format")
+}
+
+/// The reverse of `format_compact_time`
+pub(crate) fn parse_compact_time(text: &str) -> Option<Timestamp> {
+    PrimitiveDateTime::parse(text, &compact_time_format())
+        .ok()
+        .map(PrimitiveDateTime::assume_utc)
+}
+
+/// The key `upload_snapshot` stores a snapshot taken at `created` under
+pub fn snapshot_key(created: Timestamp) -> String {
+    format!("{}{}{}", SNAPSHOT_KEY_PREFIX, format_compact_time(created), SNAPSHOT_KEY_SUFFIX)
+}
+
+/// When the snapshot stored under `key` was taken, if `key` is a snapshot key
+pub fn snapshot_key_time(key: &str) -> Option<Timestamp> {
+    key.strip_prefix(SNAPSHOT_KEY_PREFIX)?
+        .strip_suffix(SNAPSHOT_KEY_SUFFIX)
+        .and_then(parse_compact_time)
+}
+
+/// A snapshot kept in a `SnapshotSink`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StoredSnapshot {
+    pub key: String,
+    pub created: Timestamp,
+    /// Size in bytes, compressed
+    pub size: u64,
+}
+
+/// Somewhere to keep snapshots off the machine the store runs on: a
+/// directory on a mounted volume (`DirectorySink`) or an object store
+/// (`s3::S3Sink`, with the `s3` feature).
+///
+/// Sinks only store bytes under flat keys. `upload_snapshot`,
+/// `list_snapshots` and `restore_from_sink` name, encode and verify the
+/// snapshots, so they work the same with every sink.
+pub trait SnapshotSink {
+    /// Store `data` under `key`, replacing what was there
+    fn put(&mut self, key: &str, data: &[u8]) -> Result<()>;
+
+    fn get(&mut self, key: &str) -> Result<Vec<u8>>;
+
+    /// Every key stored, with the size of its data, in no particular order
+    fn list(&mut self) -> Result<Vec<(String, u64)>>;
+
+    fn delete(&mut self, key: &str) -> Result<()>;
+}
+
+/// Encode `snapshot` and store it in `sink` under a key naming the current
+/// time
+pub fn upload_snapshot(sink: &mut impl SnapshotSink, snapshot: &Snapshot) -> Result<StoredSnapshot> {
+    let created = crate::now();
+    let bytes = bincode::serialize(snapshot).map_err(|e| Error::InvalidRequest(format!("Failed to serialize snapshot: {}", e)))?;
+    let bytes = miniz_oxide::deflate::compress_to_vec_zlib(&bytes, 6);
+
+    let key = snapshot_key(created);
+    sink.put(&key, &bytes)?;
+    Ok(StoredSnapshot {
+        key,
+        created,
+        size: bytes.len() as u64,
+    })
+}
+
+/// The snapshots in `sink`, oldest first. Other keys are left out.
+pub fn list_snapshots(sink: &mut impl SnapshotSink) -> Result<Vec<StoredSnapshot>> {
+    let mut snapshots: Vec<StoredSnapshot> = sink
+        .list()?
+        .into_iter()
+        .filter_map(|(key, size)| snapshot_key_time(&key).map(|created| StoredSnapshot { key, created, size }))
+        .collect();
+    snapshots.sort_by_key(|snapshot| snapshot.created);
+    Ok(snapshots)
+}
+
+/// Read the snapshot stored under `key` and check it against its checksum
+pub fn download_snapshot(sink: &mut impl SnapshotSink, key: &str) -> Result<Snapshot> {
+    let bytes = sink.get(key)?;
+    let bytes = miniz_oxide::inflate::decompress_to_vec_zlib(&bytes)
+        .map_err(|e| Error::InvalidRequest(format!("Failed to decompress snapshot {}: {}", key, e)))?;
+    let snapshot: Snapshot = bincode::deserialize(&bytes).map_err(|e| Error::InvalidRequest(format!("Failed to parse snapshot {}: {}", key, e)))?;
+    verify_snapshot(&snapshot)?;
+    Ok(snapshot)
+}
+
+/// Restore `store` from the newest snapshot in `sink` taken at or before
+/// `at`, or the newest of all if `at` is None. Returns the snapshot used.
+pub fn restore_from_sink(store: &mut Store, sink: &mut impl SnapshotSink, at: Option<Timestamp>) -> Result<StoredSnapshot> {
+    let stored = list_snapshots(sink)?
+        .into_iter()
+        .rev()
+        .find(|snapshot| at.is_none_or(|at| snapshot.created <= at))
+        .ok_or_else(|| match at {
+            Some(at) => Error::InvalidRequest(format!("No snapshot taken at or before {}", at)),
+            None => Error::InvalidRequest("No snapshots".to_string()),
+        })?;
+
+    let snapshot = download_snapshot(sink, &stored.key)?;
+    store.restore_snapshot(snapshot);
+    Ok(stored)
+}
+
+/// A `SnapshotSink` writing one file per key into a directory, e.g. on a
+/// network share or a removable disk
+#[derive(Debug, Clone)]
+pub struct DirectorySink {
+    dir: PathBuf,
+}
+
+impl DirectorySink {
+    /// Keep the snapshots in `dir`, which is created when the first is put
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn path(&self, key: &str) -> Result<PathBuf> {
+        if key.is_empty() || key.starts_with('.') || key.contains(['/', '\\']) {
+            return Err(Error::InvalidRequest(format!("Invalid snapshot key '{}'", key)));
+        }
+        Ok(self.dir.join(key))
+    }
+}
+
+fn io_error(action: &str, path: &Path, e: impl std::fmt::Display) -> Error {
+    Error::InvalidRequest(format!("Failed to {} {}: {}", action, path.display(), e))
+}
+
+impl SnapshotSink for DirectorySink {
+    fn put(&mut self, key: &str, data: &[u8]) -> Result<()> {
+        let path = self.path(key)?;
+        fs::create_dir_all(&self.dir).map_err(|e| io_error("create", &self.dir, e))?;
+        // Written aside and renamed, so `list` never sees part of a file
+        let partial = self.dir.join(format!(".{}.partial", key));
+        fs::write(&partial, data).map_err(|e| io_error("write", &partial, e))?;
+        fs::rename(&partial, &path).map_err(|e| io_error("rename", &partial, e))
+    }
+
+    fn get(&mut self, key: &str) -> Result<Vec<u8>> {
+        let path = self.path(key)?;
+        fs::read(&path).map_err(|e| io_error("read", &path, e))
+    }
+
+    fn list(&mut self) -> Result<Vec<(String, u64)>> {
+        if !self.dir.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut keys = Vec::new();
+        for entry in fs::read_dir(&self.dir).map_err(|e| io_error("read", &self.dir, e))? {
+            let entry = entry.map_err(|e| io_error("read", &self.dir, e))?;
+            let metadata = entry.metadata().map_err(|e| io_error("read", &entry.path(), e))?;
+            let Some(key) = entry.file_name().to_str().map(str::to_string) else {
+                continue;
+            };
+            if metadata.is_file() && !key.starts_with('.') {
+                keys.push((key, metadata.len()));
+            }
+        }
+        Ok(keys)
+    }
+
+    fn delete(&mut self, key: &str) -> Result<()> {
+        let path = self.path(key)?;
+        fs::remove_file(&path).map_err(|e| io_error("remove", &path, e))
+    }
+}
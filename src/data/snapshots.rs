@@ -3,8 +3,9 @@ use sorted_vec::SortedVec;
 
 use serde::{Deserialize, Serialize};
 
-use crate::{EntityId, EntitySchema, EntityType, Field, FieldType, Single};
+use crate::{EntityId, EntitySchema, EntityType, Field, FieldType, Single, SnapshotChecksum};
 use crate::data::interner::Interner;
+use crate::data::series::Samples;
 
 /// Represents a complete snapshot of the store at a point in time
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -14,6 +15,12 @@ pub struct Snapshot {
     pub entity_type_interner: Interner,
     pub field_type_interner: Interner,
     pub fields: FxHashMap<EntityId, FxHashMap<FieldType, Field>>,
+    /// Sample history of the Series fields
+    #[serde(default)]
+    pub series: FxHashMap<EntityId, FxHashMap<FieldType, Samples>>,
+    /// Content hashes taken along with the snapshot, see `verify_snapshot`
+    #[serde(default)]
+    pub checksum: Option<SnapshotChecksum>,
 }
 
 impl Default for Snapshot {
@@ -24,6 +31,8 @@ impl Default for Snapshot {
             entity_type_interner: Interner::new(),
             field_type_interner: Interner::new(),
             fields: FxHashMap::default(),
+            series: FxHashMap::default(),
+            checksum: None,
         }
     }
 }
@@ -43,6 +52,8 @@ impl Snapshot {
             entity_type_interner,
             field_type_interner,
             fields,
+            series: FxHashMap::default(),
+            checksum: None,
         }
     }
 }
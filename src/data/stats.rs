@@ -0,0 +1,100 @@
+use std::collections::BTreeMap;
+
+use crate::{Error, Field, Result, Value};
+
+/// Size and usage figures of a store, see `StoreTrait::stats`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StoreStats {
+    /// Entities of each type, by type name. Derived types count separately.
+    pub entity_counts: BTreeMap<String, usize>,
+    /// Fields stored across all entities
+    pub field_count: usize,
+    /// Rough number of bytes held by fields, series history, entity lists
+    /// and schemas. Allocator overhead and spare capacity aren't counted.
+    pub memory_estimate: usize,
+    /// Notification registrations on a single entity
+    pub entity_notifications: usize,
+    /// Notification registrations on every entity of a type
+    pub type_notifications: usize,
+    /// Names interned as entity types
+    pub entity_type_names: usize,
+    /// Names interned as field types
+    pub field_type_names: usize,
+}
+
+impl StoreStats {
+    pub fn entity_count(&self) -> usize {
+        self.entity_counts.values().sum()
+    }
+
+    /// The stats as a map, as sent in response to `STATS`
+    pub fn to_map(&self) -> BTreeMap<String, Value> {
+        let entity_counts = self
+            .entity_counts
+            .iter()
+            .map(|(name, count)| (name.clone(), Value::Int(*count as i64)))
+            .collect();
+
+        BTreeMap::from([
+            ("entity_counts".to_string(), Value::Map(entity_counts)),
+            ("field_count".to_string(), Value::Int(self.field_count as i64)),
+            ("memory_estimate".to_string(), Value::Int(self.memory_estimate as i64)),
+            ("entity_notifications".to_string(), Value::Int(self.entity_notifications as i64)),
+            ("type_notifications".to_string(), Value::Int(self.type_notifications as i64)),
+            ("entity_type_names".to_string(), Value::Int(self.entity_type_names as i64)),
+            ("field_type_names".to_string(), Value::Int(self.field_type_names as i64)),
+        ])
+    }
+
+    /// Read the stats back from `to_map`. Missing figures are zero, so older
+    /// and newer servers can be queried alike.
+    pub fn from_map(map: &BTreeMap<String, Value>) -> Result<Self> {
+        let count = |key: &str| -> Result<usize> {
+            match map.get(key) {
+                None => Ok(0),
+                Some(Value::Int(count)) => Ok(*count as usize),
+                Some(other) => Err(Error::InvalidRequest(format!("Expected an Int for stat '{}', got {:?}", key, other))),
+            }
+        };
+
+        let mut entity_counts = BTreeMap::new();
+        if let Some(Value::Map(counts)) = map.get("entity_counts") {
+            for (name, count) in counts {
+                let count = count
+                    .as_int()
+                    .ok_or_else(|| Error::InvalidRequest(format!("Expected an Int entity count for '{}'", name)))?;
+                entity_counts.insert(name.clone(), count as usize);
+            }
+        }
+
+        Ok(Self {
+            entity_counts,
+            field_count: count("field_count")?,
+            memory_estimate: count("memory_estimate")?,
+            entity_notifications: count("entity_notifications")?,
+            type_notifications: count("type_notifications")?,
+            entity_type_names: count("entity_type_names")?,
+            field_type_names: count("field_type_names")?,
+        })
+    }
+}
+
+/// Bytes a value holds outside its own slot
+pub(crate) fn heap_size(value: &Value) -> usize {
+    match value {
+        Value::Blob(bytes) => bytes.capacity(),
+        Value::String(text) => text.capacity(),
+        Value::EntityList(ids) => ids.capacity() * std::mem::size_of::<crate::EntityId>(),
+        Value::StringList(items) => items.capacity() * std::mem::size_of::<String>() + items.iter().map(|item| item.capacity()).sum::<usize>(),
+        Value::Map(map) => map
+            .iter()
+            .map(|(key, value)| key.capacity() + std::mem::size_of::<(String, Value)>() + heap_size(value))
+            .sum(),
+        _ => 0,
+    }
+}
+
+/// Bytes a stored field takes, its key included
+pub(crate) fn field_size(field: &Field) -> usize {
+    std::mem::size_of::<(crate::EntityId, crate::FieldType)>() + std::mem::size_of::<Field>() + heap_size(&field.value)
+}
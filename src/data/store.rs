@@ -1,24 +1,53 @@
 use itertools::Itertools;
-use rustc_hash::FxHashMap;
+use rustc_hash::{FxHashMap, FxHashSet};
 use sorted_vec::SortedVec;
 use std::{
-    collections::VecDeque,
+    borrow::Cow,
+    collections::{BTreeMap, HashMap, VecDeque},
     mem::discriminant,
-    sync::{Arc, Mutex},
+    sync::{Arc, RwLock},
+    time::Instant,
 };
 
+#[cfg(feature = "search")]
+use crate::data::search::{reindex_search, unindex_entity, SearchIndex};
+use crate::data::series::{self, Samples};
+use crate::data::slowlog::SlowLog;
+use crate::data::stats::{self, StoreStats};
 use crate::{
     data::{
+        audit::{AuditLog, AuditQuery, AuditRecord}, ClientContext, deadline::Deadline,
+        limits::{LimitEnforcer, LimitKey, Limits, MAX_ENTITIES, MAX_NOTIFICATIONS, RATE_BURST, RATE_LIMIT},
         entity_schema::Complete, hash_notify_config,
-        interner::Interner, now, EntityType, FieldType, Notification,
-        NotificationQueue, NotifyConfig, NotifyInfo, StoreTrait, Timestamp,
-    }, et::ET, expr::CelExecutor, ft::FT, AdjustBehavior, EntityId, EntitySchema, Error, Field, FieldSchema, PageOpts, PageResult, PushCondition, Result, Single, Snapshot, Value, WriteInfo
+        interner::{Interner, TypeIdMapping}, now, EntityType, FieldType, Notification, SnapshotChecksum,
+        NotificationQueue, NotifyConfig, NotifyInfo, path_to_entity_id, path_to_field_path, StoreTrait, Timestamp, Decimal, Duration,
+        triggers::{TriggerAction, MAX_TRIGGER_DEPTH}, Trigger, TriggerId,
+    }, et::ET, expr::{cel_value_to_value, planner::FilterPlan, CelExecutorPool}, ft::FT, AdjustBehavior, EntityId, EntitySchema, Error, HlcTimestamp, HybridClock, IdAllocator, MAX_NODE_ID, Field, FieldMetadata, FieldSchema, OnDelete, PageOpts, PageResult, PushCondition, Result, Single, Snapshot, SortDirection, Value, WriteInfo, WriteScope
 };
 
 pub struct Store {
-    schemas: FxHashMap<EntityType, EntitySchema<Single>>,
-    entities: FxHashMap<EntityType, SortedVec<EntityId>>,
-    fields: FxHashMap<(EntityId, FieldType), Field>,
+    // The bulk of the data is shared with read snapshots and copied on the
+    // first write after a snapshot is taken (see `begin_read_snapshot`)
+    schemas: Arc<FxHashMap<EntityType, EntitySchema<Single>>>,
+    entities: Arc<FxHashMap<EntityType, SortedVec<EntityId>>>,
+    fields: Arc<FxHashMap<(EntityId, FieldType), Field>>,
+
+    /// Who refers to each entity: the entity and field of every
+    /// `EntityReference`/`EntityList` value pointing at it
+    references: Arc<ReferenceIndex>,
+
+    /// Entities removed by `soft_delete_entity`, kept until they are restored or purged
+    tombstones: Arc<FxHashMap<EntityId, Tombstone>>,
+
+    /// How long tombstones are kept, if they expire at all
+    tombstone_retention: Option<Duration>,
+
+    /// Sample history of the Series fields, see `read_series`
+    series: Arc<SeriesHistory>,
+
+    /// Text of the searchable String fields, see `search`
+    #[cfg(feature = "search")]
+    search: Arc<SearchIndex>,
 
     entity_type_interner: Interner,
     field_type_interner: Interner,
@@ -33,9 +62,25 @@ pub struct Store {
     /// This cache is invalidated whenever schemas are updated or inheritance map is rebuilt
     complete_entity_schema_cache: FxHashMap<EntityType, EntitySchema<Complete>>,
 
-    /// Cached CEL executor for filter expressions
-    /// This is wrapped in Arc<Mutex<>> for thread-safe access with interior mutability
-    cel_executor_cache: Arc<Mutex<CelExecutor>>,
+    /// CEL executors with their compiled filters, validators and computed
+    /// field expressions. Readers sharing the store each borrow their own.
+    cel_executors: CelExecutorPool,
+
+    /// Values of computed fields evaluated since their dependencies last changed
+    computed_cache: RwLock<FxHashMap<(EntityId, FieldType), (Value, Timestamp)>>,
+
+    /// Maps a field type to the computed fields whose expression reads it
+    /// This is rebuilt along with the complete entity schema cache
+    computed_dependents: FxHashMap<FieldType, Vec<FieldType>>,
+
+    /// Field types that are computed in any entity type, which have no
+    /// stored values to index. Rebuilt along with the complete entity schema cache
+    computed_fields: FxHashSet<FieldType>,
+
+    /// Entities by the value of a field, built on the first `==` filter on
+    /// the field and kept up to date by writes. None if the field holds
+    /// values that can't be indexed.
+    value_index: RwLock<FxHashMap<FieldType, Option<Arc<ValueIndex>>>>,
 
     /// Notification senders indexed by entity ID and field type
     /// Each config can have multiple senders
@@ -51,11 +96,176 @@ pub struct Store {
 
     pub write_queue: VecDeque<WriteInfo>,
 
+    /// Sequence number of the first write in `write_queue`
+    write_seq: u64,
+
+    /// How far each reader of `write_queue` has read, by reader name. Writes
+    /// are dropped from the queue once every reader is past them.
+    write_readers: FxHashMap<String, WriteReader>,
+
     /// Flag to temporarily disable notifications (e.g., during WAL replay)
     notifications_disabled: bool,
 
+    /// Notifications waiting for `flush_notifications`, while they are held
+    held_notifications: Option<Vec<(NotificationQueue, Notification)>>,
+
     /// Default writer id for operations that don't specify one
     pub default_writer_id: Option<EntityId>,
+
+    /// Triggers defined by `Trigger` entities, indexed by the field type
+    /// they watch. Built on first use and dropped whenever they may have
+    /// changed.
+    triggers: Option<Arc<TriggerIndex>>,
+
+    /// Trigger whose action is currently being written, and how deeply
+    /// trigger actions are nested at this point
+    active_trigger: Option<TriggerId>,
+    trigger_depth: usize,
+
+    /// Flag to temporarily disable triggers (e.g., while applying replicated writes)
+    triggers_disabled: bool,
+
+    /// Audit log of every queued write, if enabled
+    audit: Option<AuditLog>,
+
+    /// Who is making the current request
+    client_context: ClientContext,
+
+    /// Rate limits and quotas per client, if enabled
+    limits: Option<LimitEnforcer>,
+
+    /// When the current request must give up
+    deadline: Deadline,
+
+    /// Commands that ran past the slow log threshold
+    slowlog: SlowLog,
+
+    /// Clock that stamps local writes, if enabled
+    hlc: Option<HybridClock>,
+
+    /// Time the store reads instead of the wall clock, see `set_time`
+    time: Option<Timestamp>,
+
+    /// How ids of new entities are picked
+    id_allocator: IdAllocator,
+}
+
+type ReferenceIndex = FxHashMap<EntityId, FxHashSet<(EntityId, FieldType)>>;
+
+type TriggerIndex = FxHashMap<FieldType, Vec<(TriggerId, Trigger)>>;
+
+type SeriesHistory = FxHashMap<(EntityId, FieldType), Samples>;
+
+type ValueIndex = FxHashMap<IndexKey, FxHashSet<EntityId>>;
+
+/// A field value as a key of the value index. Choices are keyed by their
+/// index, since filters compare them with ints.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum IndexKey {
+    String(String),
+    Int(i64),
+    Bool(bool),
+}
+
+impl IndexKey {
+    fn of(value: &Value) -> Option<Self> {
+        match value {
+            Value::String(s) => Some(IndexKey::String(s.clone())),
+            Value::Int(i) | Value::Choice(i) => Some(IndexKey::Int(*i)),
+            Value::Bool(b) => Some(IndexKey::Bool(*b)),
+            _ => None,
+        }
+    }
+}
+
+/// Forget the sample history of an entity's Series fields
+fn drop_series(history: &mut Arc<SeriesHistory>, entity_id: EntityId) {
+    if history.keys().any(|(eid, _)| *eid == entity_id) {
+        Arc::make_mut(history).retain(|(eid, _), _| *eid != entity_id);
+    }
+}
+
+/// The entities a value points at, if it is a reference
+fn referenced_ids(value: &Value) -> &[EntityId] {
+    match value {
+        Value::EntityReference(Some(id)) => std::slice::from_ref(id),
+        Value::EntityList(ids) => ids,
+        _ => &[],
+    }
+}
+
+/// The entities of the sorted `entities` that are also among the sorted
+/// `candidates` a filter plan looked up, or all of them without candidates
+fn narrow_to<'a>(entities: &'a [EntityId], candidates: Option<&[EntityId]>) -> Cow<'a, [EntityId]> {
+    match candidates {
+        Some(candidates) => Cow::Owned(candidates.iter().filter(|id| entities.binary_search(id).is_ok()).copied().collect()),
+        None => Cow::Borrowed(entities),
+    }
+}
+
+/// A copy of `value` with references to the keys of `copies` pointed at
+/// their copies instead
+fn remap_references(value: &Value, copies: &FxHashMap<EntityId, EntityId>) -> Value {
+    let remap = |id: &EntityId| copies.get(id).copied().unwrap_or(*id);
+    match value {
+        Value::EntityReference(Some(id)) => Value::EntityReference(Some(remap(id))),
+        Value::EntityList(ids) => Value::EntityList(ids.iter().map(remap).collect()),
+        _ => value.clone(),
+    }
+}
+
+/// Update the reverse reference index for a field that used to point at
+/// `old` and now points at `new`
+fn reindex_references(index: &mut Arc<ReferenceIndex>, entity_id: EntityId, field_type: FieldType, old: &[EntityId], new: &[EntityId]) {
+    if old == new {
+        return;
+    }
+    let old: FxHashSet<EntityId> = old.iter().copied().collect();
+    let new: FxHashSet<EntityId> = new.iter().copied().collect();
+    let index = Arc::make_mut(index);
+
+    for target in old.difference(&new) {
+        if let Some(referrers) = index.get_mut(target) {
+            referrers.remove(&(entity_id, field_type));
+            if referrers.is_empty() {
+                index.remove(target);
+            }
+        }
+    }
+    for target in new.difference(&old) {
+        index.entry(*target).or_default().insert((entity_id, field_type));
+    }
+}
+
+/// A soft deleted entity: its fields, and when it was deleted
+#[derive(Debug, Clone)]
+struct Tombstone {
+    deleted_at: Timestamp,
+    fields: FxHashMap<FieldType, Field>,
+}
+
+/// Where a reader of the write queue is, see `Store::unread_writes`
+#[derive(Debug, Clone, Default)]
+struct WriteReader {
+    /// Sequence number of the next write it hasn't read
+    next: u64,
+    /// Writes after `next` it doesn't want, see `Store::skip_writes`
+    skipped: Vec<std::ops::Range<u64>>,
+}
+
+/// Read-only view of a `Store`, see `Store::begin_read_snapshot`.
+///
+/// It derefs to `&Store`, so every read (including `take_snapshot` and the
+/// finds) works on it, while nothing can write to it.
+#[derive(Debug)]
+pub struct ReadSnapshot(Store);
+
+impl std::ops::Deref for ReadSnapshot {
+    type Target = Store;
+
+    fn deref(&self) -> &Store {
+        &self.0
+    }
 }
 
 impl std::fmt::Debug for Store {
@@ -84,9 +294,15 @@ impl std::fmt::Debug for Store {
 impl Store {
     pub fn new() -> Self {
         Store {
-            schemas: FxHashMap::default(),
-            entities: FxHashMap::default(),
-            fields: FxHashMap::default(),
+            schemas: Arc::default(),
+            entities: Arc::default(),
+            fields: Arc::default(),
+            references: Arc::default(),
+            tombstones: Arc::default(),
+            tombstone_retention: None,
+            series: Arc::default(),
+            #[cfg(feature = "search")]
+            search: Arc::default(),
             entity_type_interner: Interner::new(),
             field_type_interner: Interner::new(),
             et: None,
@@ -96,9 +312,28 @@ impl Store {
             id_notifications: FxHashMap::default(),
             type_notifications: FxHashMap::default(),
             write_queue: VecDeque::new(),
+            write_seq: 0,
+            write_readers: FxHashMap::default(),
             notifications_disabled: false,
+            held_notifications: None,
             default_writer_id: None,
-            cel_executor_cache: Arc::new(Mutex::new(CelExecutor::new())),
+            cel_executors: CelExecutorPool::default(),
+            computed_cache: RwLock::new(FxHashMap::default()),
+            value_index: RwLock::new(FxHashMap::default()),
+            computed_dependents: FxHashMap::default(),
+            computed_fields: FxHashSet::default(),
+            triggers: None,
+            active_trigger: None,
+            trigger_depth: 0,
+            triggers_disabled: false,
+            audit: None,
+            client_context: ClientContext::default(),
+            limits: None,
+            deadline: Deadline::none(),
+            slowlog: SlowLog::default(),
+            hlc: None,
+            time: None,
+            id_allocator: IdAllocator::default(),
         }
     }
 
@@ -124,24 +359,49 @@ impl Store {
             if let Some(id) = created_entity_id {
                 id.clone()
             } else {
-                let last_id = self
-                    .entities
-                    .get(&entity_type)
-                    .and_then(|v| v.last())
-                    .cloned()
-                    .unwrap_or(EntityId::new(entity_type.clone(), 0));
-                let entity_id = EntityId::new(entity_type.clone(), last_id.extract_id() + 1);
+                // Only ids in our own range count, other nodes' may be higher
+                let ids = self.id_allocator.range();
+                let last_id = self.entities.get(&entity_type).and_then(|entities| {
+                    let end = entities.partition_point(|id| id.extract_id() < ids.end);
+                    entities[..end].last().map(|id| id.extract_id()).filter(|id| ids.contains(id))
+                });
+                // Soft deleted entities keep their ids until they are purged
+                let last_deleted = self
+                    .tombstones
+                    .keys()
+                    .filter(|id| id.extract_type() == entity_type && ids.contains(&id.extract_id()))
+                    .map(|id| id.extract_id())
+                    .max();
+                let next_id = match last_id.max(last_deleted) {
+                    Some(last) => last + 1,
+                    None => ids.start,
+                };
+                if next_id >= ids.end {
+                    return Err(Error::InvalidRequest(format!(
+                        "No ids left for {:?} in {:?}",
+                        entity_type, ids
+                    )));
+                }
+                let entity_id = EntityId::new(entity_type.clone(), next_id);
                 *created_entity_id = Some(entity_id);
                 entity_id
             }
         };
-        if self.fields.keys().any(|(eid, _)| eid == &entity_id) {
+        if self.fields.keys().any(|(eid, _)| eid == &entity_id) || self.tombstones.contains_key(&entity_id) {
             return Err(Error::EntityAlreadyExists(entity_id));
         }
+        self.check_unique_name(entity_id, parent_id, name)?;
+
+        if let Some(key) = self.quota_key() {
+            let limits = self.limits_for(&self.client_context);
+            if !self.limits.as_mut().unwrap().try_create_entity(key.clone(), &limits) {
+                return Err(Error::QuotaExceeded(key.to_string(), MAX_ENTITIES.to_string(), limits.max_entities.unwrap_or_default()));
+            }
+        }
+        let timestamp = self.now();
 
         {
-            let entities = self
-                .entities
+            let entities = Arc::make_mut(&mut self.entities)
                 .entry(entity_type.clone())
                 .or_insert_with(SortedVec::new);
             entities.push(entity_id);
@@ -158,7 +418,8 @@ impl Store {
             .collect();
 
         // Directly set fields in the entity's field map
-        for (field_type, field_schema) in schema_fields {
+        // Computed fields have no stored value and are evaluated on read
+        for (field_type, field_schema) in schema_fields.into_iter().filter(|(_, fs)| !fs.is_computed()) {
             let value = {
                 if field_type == ft.name.unwrap() {
                     Value::String(name.to_string().into())
@@ -172,34 +433,43 @@ impl Store {
                 }
             };
 
+            reindex_references(&mut self.references, entity_id, field_type, &[], referenced_ids(&value));
+            #[cfg(feature = "search")]
+            reindex_search(&mut self.search, entity_id, field_type, Some(&value));
+            self.reindex_value(entity_id, field_type, None, Some(&value));
             let field_key = (entity_id, field_type);
-            self.fields.insert(
+            Arc::make_mut(&mut self.fields).insert(
                 field_key,
                 Field {
-                    field_type: field_type,
+                    field_type,
                     value,
-                    write_time: now(),
+                    write_time: timestamp,
                     writer_id: None,
                 },
             );
         }
 
+        // Computed fields may aggregate over the parent's children
+        self.computed_cache.write().unwrap().clear();
+        self.triggers = None;
+
         // If we have a parent, add it to the parent's children list
         if let Some(parent) = &parent_id {
             let children_field_key = (*parent, ft.children.unwrap());
-            if let Some(children_field) = self.fields.get_mut(&children_field_key) {
+            reindex_references(&mut self.references, *parent, ft.children.unwrap(), &[], &[entity_id]);
+            if let Some(children_field) = Arc::make_mut(&mut self.fields).get_mut(&children_field_key) {
                 if let Value::EntityList(children) = &mut children_field.value {
                     children.push(entity_id);
-                    children_field.write_time = now();
+                    children_field.write_time = timestamp;
                 }
             } else {
                 // Create the Children field if it doesn't exist
-                self.fields.insert(
+                Arc::make_mut(&mut self.fields).insert(
                     children_field_key,
                     Field {
                         field_type: ft.children.unwrap(),
                         value: Value::EntityList(vec![entity_id]),
-                        write_time: now(),
+                        write_time: timestamp,
                         writer_id: None,
                     },
                 );
@@ -207,12 +477,12 @@ impl Store {
         }
 
         if let Some(created_entity_id) = created_entity_id {
-            self.write_queue.push_back(WriteInfo::CreateEntity {
+            self.queue_write(WriteInfo::CreateEntity {
                 entity_type,
                 parent_id,
                 name: name.to_string(),
                 created_entity_id: *created_entity_id,
-                timestamp: now(),
+                timestamp,
             });
         }
 
@@ -350,11 +620,21 @@ impl Store {
 
     /// Internal entity deletion that doesn't use perform to avoid recursion
     fn delete_entity_internal(&mut self, entity_id: EntityId) -> Result<()> {
+        // Deleting a soft deleted entity purges it
+        if self.tombstones.contains_key(&entity_id) {
+            self.purge_tombstone(entity_id);
+            return Ok(());
+        }
+
         // Check if the entity exists
         if !self.fields.keys().any(|(eid, _)| *eid == entity_id) {
             return Err(Error::EntityNotFound(entity_id));
         }
 
+        // Computed fields may aggregate over the entity being removed
+        self.computed_cache.write().unwrap().clear();
+        self.triggers = None;
+
         // Remove all children first (recursively)
         let children_field_key = {
             let ft = self.ft.as_ref().unwrap();
@@ -389,25 +669,477 @@ impl Store {
                 let ft = self.ft.as_ref().unwrap();
                 (parent_id, ft.children.unwrap())
             };
-            if let Some(children_field) = self.fields.get_mut(&parent_children_key) {
+            let timestamp = self.now();
+            if let Some(children_field) = Arc::make_mut(&mut self.fields).get_mut(&parent_children_key) {
                 if let Value::EntityList(children) = &mut children_field.value {
                     children.retain(|id| *id != entity_id);
-                    children_field.write_time = now();
+                    children_field.write_time = timestamp;
                 }
             }
+            reindex_references(&mut self.references, parent_id, parent_children_key.1, &[entity_id], &[]);
         }
 
-        // Remove fields
-        self.fields.retain(|(eid, _), _| *eid != entity_id);
+        // Remove fields, and what they referred to from the reference index
+        // (references to this entity stay until the referrers change)
+        let outgoing: Vec<(FieldType, Vec<EntityId>)> = self
+            .fields
+            .iter()
+            .filter(|((eid, _), field)| *eid == entity_id && !referenced_ids(&field.value).is_empty())
+            .map(|((_, field_type), field)| (*field_type, referenced_ids(&field.value).to_vec()))
+            .collect();
+        for (field_type, targets) in outgoing {
+            reindex_references(&mut self.references, entity_id, field_type, &targets, &[]);
+        }
+        for ((_, field_type), field) in self.fields.iter().filter(|((eid, _), _)| *eid == entity_id) {
+            self.reindex_value(entity_id, *field_type, Some(&field.value), None);
+        }
+        #[cfg(feature = "search")]
+        unindex_entity(&mut self.search, entity_id);
+        drop_series(&mut self.series, entity_id);
+        Arc::make_mut(&mut self.fields).retain(|(eid, _), _| *eid != entity_id);
 
         // Remove from entity type list
-        if let Some(entities) = self.entities.get_mut(&entity_id.extract_type()) {
+        if let Some(entities) = Arc::make_mut(&mut self.entities).get_mut(&entity_id.extract_type()) {
+            entities.retain(|id| *id != entity_id);
+        }
+
+        Ok(())
+    }
+
+    /// Every entity and field whose `EntityReference`/`EntityList` value
+    /// points at `entity_id`, answered from an index instead of a scan.
+    ///
+    /// This includes the tree itself: the parent's `Children` and the
+    /// children's `Parent`. References to a deleted entity are still listed
+    /// until the referring fields change.
+    pub fn find_referencing(&self, entity_id: EntityId) -> Vec<(EntityId, FieldType)> {
+        self.references
+            .get(&entity_id)
+            .map(|referrers| referrers.iter().copied().sorted().collect())
+            .unwrap_or_default()
+    }
+
+    /// Rebuild the reverse reference index from the stored fields
+    fn rebuild_reference_index(&mut self) {
+        let mut index = ReferenceIndex::default();
+        for ((entity_id, field_type), field) in self.fields.iter() {
+            for target in referenced_ids(&field.value) {
+                index.entry(*target).or_default().insert((*entity_id, *field_type));
+            }
+        }
+        self.references = Arc::new(index);
+    }
+
+    /// Apply the `OnDelete` rules of reference fields pointing into the
+    /// subtree of an entity that is about to be deleted.
+    ///
+    /// Cascades are followed until no more entities are pulled in, and
+    /// references into each round's entities are nullified as they are
+    /// found. A `Restrict` reference fails the delete at any point, so this
+    /// runs inside the delete's `atomically`, which undoes the nullified
+    /// references. Returns the entities to delete along with this one.
+    fn apply_on_delete(&mut self, entity_id: EntityId) -> Result<Vec<EntityId>> {
+        let rules = self.on_delete_rules();
+        if rules.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut doomed = FxHashSet::default();
+        self.collect_subtree(entity_id, &mut doomed);
+
+        let mut cascaded = Vec::new();
+        let mut nullify = FxHashSet::default();
+        loop {
+            nullify.clear();
+            let mut pulled_in = Vec::new();
+
+            for target in &doomed {
+                for (referrer, field_type) in self.references.get(target).into_iter().flatten() {
+                    if doomed.contains(referrer) {
+                        continue;
+                    }
+                    let Some(on_delete) = rules.get(&(referrer.extract_type(), *field_type)) else {
+                        continue;
+                    };
+
+                    match on_delete {
+                        OnDelete::Nullify => {
+                            nullify.insert((*referrer, *field_type));
+                        }
+                        OnDelete::Cascade => pulled_in.push(*referrer),
+                        OnDelete::Restrict => {
+                            return Err(Error::EntityReferenced(*target, *referrer, *field_type));
+                        }
+                    }
+                }
+            }
+
+            for (referrer, field_type) in nullify.drain().sorted() {
+                let value = match self.fields.get(&(referrer, field_type)).map(|field| &field.value) {
+                    Some(Value::EntityList(targets)) => Value::EntityList(targets.iter().copied().filter(|target| !doomed.contains(target)).collect()),
+                    _ => Value::EntityReference(None),
+                };
+                self.write(referrer, &[field_type], value, None, None, None, None)?;
+            }
+
+            if pulled_in.is_empty() {
+                break;
+            }
+            for referrer in pulled_in {
+                if !doomed.contains(&referrer) {
+                    self.collect_subtree(referrer, &mut doomed);
+                    cascaded.push(referrer);
+                }
+            }
+        }
+
+        Ok(cascaded)
+    }
+
+    /// The `OnDelete` rule of every reference field, by the type declaring
+    /// it. The tree fields are left out, delete maintains them itself.
+    fn on_delete_rules(&self) -> FxHashMap<(EntityType, FieldType), OnDelete> {
+        let ft = self.ft.as_ref().unwrap();
+        let (ft_parent, ft_children) = (ft.parent.unwrap(), ft.children.unwrap());
+        self.complete_entity_schema_cache
+            .iter()
+            .flat_map(|(entity_type, schema)| {
+                schema.fields.iter().filter_map(move |(field_type, field_schema)| {
+                    field_schema.on_delete().map(|on_delete| ((*entity_type, *field_type), on_delete))
+                })
+            })
+            .filter(|((_, field_type), _)| *field_type != ft_parent && *field_type != ft_children)
+            .collect()
+    }
+
+    /// Fail with `EntityReferenced` if a `Restrict` reference from outside
+    /// the subtree of `entity_id` points into it
+    fn check_restrict(&self, entity_id: EntityId) -> Result<()> {
+        let rules = self.on_delete_rules();
+        if !rules.values().any(|on_delete| *on_delete == OnDelete::Restrict) {
+            return Ok(());
+        }
+
+        let mut subtree = FxHashSet::default();
+        self.collect_subtree(entity_id, &mut subtree);
+        for target in subtree.iter().sorted() {
+            for (referrer, field_type) in self.references.get(target).into_iter().flatten().sorted() {
+                if !subtree.contains(referrer) && rules.get(&(referrer.extract_type(), *field_type)) == Some(&OnDelete::Restrict) {
+                    return Err(Error::EntityReferenced(*target, *referrer, *field_type));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Tell whoever watches `parent_id`'s Children that the list changed
+    /// from `old` to `new`, for changes made without a write
+    fn notify_children_changed(&mut self, parent_id: EntityId, old: Vec<EntityId>, timestamp: Timestamp) {
+        let ft_children = self.ft.as_ref().unwrap().children.unwrap();
+        let Some(field) = self.fields.get(&(parent_id, ft_children)) else {
+            return;
+        };
+        let (new, writer_id) = (field.value.clone(), field.writer_id);
+        let info = |value: Value| NotifyInfo {
+            entity_id: parent_id,
+            field_path: crate::sfield![ft_children],
+            value: Some(value),
+            timestamp: Some(timestamp),
+            writer_id,
+        };
+        self.trigger_notifications(parent_id, ft_children, info(new), info(Value::EntityList(old)));
+    }
+
+    /// Add an entity and all of its descendants to `subtree`
+    fn collect_subtree(&self, entity_id: EntityId, subtree: &mut FxHashSet<EntityId>) {
+        if !subtree.insert(entity_id) {
+            return;
+        }
+        let ft_children = self.ft.as_ref().unwrap().children.unwrap();
+        if let Some(Value::EntityList(children)) = self.fields.get(&(entity_id, ft_children)).map(|field| &field.value) {
+            for child in children {
+                self.collect_subtree(*child, subtree);
+            }
+        }
+    }
+
+    /// Delete an entity (and its subtree) so that it can be brought back
+    /// with `restore_entity`.
+    ///
+    /// The entity is unlinked from its parent and no longer shows up in
+    /// reads or finds, but its fields are kept in a tombstone until it is
+    /// restored, purged by `purge_deleted` or the retention policy, or deleted
+    /// for good with `delete_entity`. Tombstones aren't part of `Snapshot`.
+    ///
+    /// Like `delete_entity`, it fails while a `Restrict` reference points
+    /// into the subtree.
+    pub fn soft_delete_entity(&mut self, entity_id: EntityId) -> Result<()> {
+        if !self.entity_exists(entity_id) {
+            return Err(Error::EntityNotFound(entity_id));
+        }
+        // A Restrict reference blocks a soft delete like it does a delete,
+        // the other rules wait until the entity is deleted for good
+        self.check_restrict(entity_id)?;
+
+        self.purge_expired();
+
+        let ft = self.ft.as_ref().unwrap();
+        let (ft_parent, ft_children) = (ft.parent.unwrap(), ft.children.unwrap());
+        let parent_id = self
+            .fields
+            .get(&(entity_id, ft_parent))
+            .and_then(|field| field.value.as_entity_reference().copied().flatten());
+
+        let timestamp = self.now();
+        self.bury(entity_id, timestamp);
+
+        if let Some(parent_id) = parent_id {
+            let mut old_children = None;
+            if let Some(children_field) = Arc::make_mut(&mut self.fields).get_mut(&(parent_id, ft_children)) {
+                if let Value::EntityList(children) = &mut children_field.value {
+                    old_children = Some(children.clone());
+                    children.retain(|id| *id != entity_id);
+                    children_field.write_time = timestamp;
+                }
+            }
+            reindex_references(&mut self.references, parent_id, ft_children, &[entity_id], &[]);
+            if let Some(old_children) = old_children {
+                let new_children = self.fields[&(parent_id, ft_children)].value.clone();
+                self.reindex_value(parent_id, ft_children, Some(&Value::EntityList(old_children.clone())), Some(&new_children));
+                self.notify_children_changed(parent_id, old_children, timestamp);
+            }
+        }
+        // Computed fields may aggregate over the parent's children
+        self.computed_cache.write().unwrap().clear();
+        self.triggers = None;
+
+        self.queue_write(WriteInfo::SoftDeleteEntity { entity_id, timestamp });
+        Ok(())
+    }
+
+    /// Move an entity and its descendants from the live maps into tombstones
+    fn bury(&mut self, entity_id: EntityId, deleted_at: Timestamp) {
+        let ft_children = self.ft.as_ref().unwrap().children.unwrap();
+        let children = match self.fields.get(&(entity_id, ft_children)).map(|field| &field.value) {
+            Some(Value::EntityList(children)) => children.clone(),
+            _ => Vec::new(),
+        };
+        for child in children {
+            self.bury(child, deleted_at);
+        }
+
+        let field_types: Vec<FieldType> = self
+            .fields
+            .keys()
+            .filter(|(eid, _)| *eid == entity_id)
+            .map(|(_, field_type)| *field_type)
+            .collect();
+        let live_fields = Arc::make_mut(&mut self.fields);
+        let fields: FxHashMap<FieldType, Field> = field_types
+            .into_iter()
+            .filter_map(|field_type| live_fields.remove(&(entity_id, field_type)).map(|field| (field_type, field)))
+            .collect();
+        for (field_type, field) in &fields {
+            reindex_references(&mut self.references, entity_id, *field_type, referenced_ids(&field.value), &[]);
+            self.reindex_value(entity_id, *field_type, Some(&field.value), None);
+        }
+        #[cfg(feature = "search")]
+        unindex_entity(&mut self.search, entity_id);
+        // Only the latest sample of a series survives a soft delete
+        drop_series(&mut self.series, entity_id);
+
+        if let Some(entities) = Arc::make_mut(&mut self.entities).get_mut(&entity_id.extract_type()) {
             entities.retain(|id| *id != entity_id);
         }
+        Arc::make_mut(&mut self.tombstones).insert(entity_id, Tombstone { deleted_at, fields });
+    }
+
+    /// Bring back a soft deleted entity, along with the descendants that
+    /// were deleted with it, under its original parent.
+    ///
+    /// Fails if the parent has been deleted in the meantime; restore the
+    /// parent first. Also fails with `EntityAlreadyExists` if names are
+    /// unique among siblings and a sibling has taken the entity's name.
+    pub fn restore_entity(&mut self, entity_id: EntityId) -> Result<()> {
+        let Some(tombstone) = self.tombstones.get(&entity_id) else {
+            return Err(Error::EntityNotFound(entity_id));
+        };
+
+        let ft = self.ft.as_ref().unwrap();
+        let (ft_parent, ft_children) = (ft.parent.unwrap(), ft.children.unwrap());
+        let parent_id = tombstone
+            .fields
+            .get(&ft_parent)
+            .and_then(|field| field.value.as_entity_reference().copied().flatten());
+
+        if let Some(parent_id) = parent_id {
+            if !self.entity_exists(parent_id) {
+                return Err(Error::InvalidRequest(format!(
+                    "Cannot restore {:?}, its parent {:?} is deleted",
+                    entity_id, parent_id
+                )));
+            }
+        }
+        if !self.schemas.contains_key(&entity_id.extract_type()) {
+            return Err(Error::EntityTypeNotFound(entity_id.extract_type()));
+        }
+        // A sibling may have taken the name in the meantime
+        let name = tombstone
+            .fields
+            .get(&ft.name.unwrap())
+            .and_then(|field| field.value.as_string())
+            .unwrap_or_default()
+            .to_string();
+        self.check_unique_name(entity_id, parent_id, &name)?;
+
+        self.unbury(entity_id)?;
+
+        let timestamp = self.now();
+        if let Some(parent_id) = parent_id {
+            let children_field = Arc::make_mut(&mut self.fields)
+                .entry((parent_id, ft_children))
+                .or_insert_with(|| Field {
+                    field_type: ft_children,
+                    value: Value::EntityList(Vec::new()),
+                    write_time: timestamp,
+                    writer_id: None,
+                });
+            let old_children = children_field.value.clone();
+            if let Value::EntityList(children) = &mut children_field.value {
+                if !children.contains(&entity_id) {
+                    children.push(entity_id);
+                }
+                children_field.write_time = timestamp;
+            }
+            let new_children = children_field.value.clone();
+            reindex_references(&mut self.references, parent_id, ft_children, &[], &[entity_id]);
+            self.reindex_value(parent_id, ft_children, Some(&old_children), Some(&new_children));
+            if let Value::EntityList(old_children) = old_children {
+                self.notify_children_changed(parent_id, old_children, timestamp);
+            }
+        }
+        // Computed fields may aggregate over the parent's children
+        self.computed_cache.write().unwrap().clear();
+        self.triggers = None;
+
+        self.queue_write(WriteInfo::RestoreEntity { entity_id, timestamp });
+        Ok(())
+    }
+
+    /// Move an entity and its buried descendants back into the live maps
+    fn unbury(&mut self, entity_id: EntityId) -> Result<()> {
+        let Some(tombstone) = Arc::make_mut(&mut self.tombstones).remove(&entity_id) else {
+            return Ok(());
+        };
+        let mut fields = tombstone.fields;
+
+        // Fields added to the schema while the entity was deleted start out
+        // with their default value, like they would for a new entity
+        let complete_schema = self.get_complete_entity_schema(entity_id.extract_type())?;
+        for (field_type, field_schema) in complete_schema.fields.iter().filter(|(_, fs)| !fs.is_computed()) {
+            fields.entry(*field_type).or_insert_with(|| Field {
+                field_type: *field_type,
+                value: field_schema.default_value(),
+                write_time: self.now(),
+                writer_id: None,
+            });
+        }
+
+        // Children that were purged since stay gone
+        let ft_children = self.ft.as_ref().unwrap().children.unwrap();
+        let mut children = Vec::new();
+        if let Some(Value::EntityList(list)) = fields.get_mut(&ft_children).map(|field| &mut field.value) {
+            list.retain(|child| self.tombstones.contains_key(child));
+            children = list.clone();
+        }
 
+        for (field_type, field) in &fields {
+            reindex_references(&mut self.references, entity_id, *field_type, &[], referenced_ids(&field.value));
+            self.reindex_value(entity_id, *field_type, None, Some(&field.value));
+            #[cfg(feature = "search")]
+            reindex_search(&mut self.search, entity_id, *field_type, Some(&field.value));
+        }
+        let live_fields = Arc::make_mut(&mut self.fields);
+        for (field_type, field) in fields {
+            live_fields.insert((entity_id, field_type), field);
+        }
+        Arc::make_mut(&mut self.entities)
+            .entry(entity_id.extract_type())
+            .or_default()
+            .push(entity_id);
+
+        for child in children {
+            self.unbury(child)?;
+        }
         Ok(())
     }
 
+    /// Whether an entity is soft deleted and can still be restored
+    pub fn is_soft_deleted(&self, entity_id: EntityId) -> bool {
+        self.tombstones.contains_key(&entity_id)
+    }
+
+    /// Every soft deleted entity and when it was deleted, oldest first
+    pub fn deleted_entities(&self) -> Vec<(EntityId, Timestamp)> {
+        self.tombstones
+            .iter()
+            .map(|(entity_id, tombstone)| (*entity_id, tombstone.deleted_at))
+            .sorted_by_key(|(entity_id, deleted_at)| (*deleted_at, *entity_id))
+            .collect()
+    }
+
+    /// Keep tombstones for `retention` after the deletion and purge them
+    /// after that, or keep them until purged by hand with `None` (the default).
+    /// Expired tombstones are purged on the next soft delete or `purge_deleted`.
+    pub fn set_tombstone_retention(&mut self, retention: Option<Duration>) {
+        self.tombstone_retention = retention;
+    }
+
+    /// Permanently remove the entities soft deleted before `before`.
+    /// Returns the ids of the purged entities.
+    pub fn purge_deleted(&mut self, before: Timestamp) -> Vec<EntityId> {
+        let expired: Vec<EntityId> = self
+            .tombstones
+            .iter()
+            .filter(|(_, tombstone)| tombstone.deleted_at < before)
+            .map(|(entity_id, _)| *entity_id)
+            .sorted()
+            .collect();
+
+        let mut purged = Vec::new();
+        for entity_id in expired {
+            // Descendants may already have gone with their parent
+            if self.tombstones.contains_key(&entity_id) {
+                purged.extend(self.purge_tombstone(entity_id));
+                self.queue_write(WriteInfo::DeleteEntity { entity_id, timestamp: self.now() });
+            }
+        }
+        purged
+    }
+
+    /// Purge the tombstones that have outlived the retention policy
+    fn purge_expired(&mut self) {
+        if let Some(retention) = self.tombstone_retention {
+            self.purge_deleted(self.now() - retention);
+        }
+    }
+
+    /// Drop a tombstone and those of the descendants buried with it
+    fn purge_tombstone(&mut self, entity_id: EntityId) -> Vec<EntityId> {
+        let Some(tombstone) = Arc::make_mut(&mut self.tombstones).remove(&entity_id) else {
+            return Vec::new();
+        };
+
+        let mut purged = vec![entity_id];
+        let ft_children = self.ft.as_ref().unwrap().children.unwrap();
+        if let Some(Value::EntityList(children)) = tombstone.fields.get(&ft_children).map(|field| &field.value) {
+            for child in children {
+                purged.extend(self.purge_tombstone(*child));
+            }
+        }
+        purged
+    }
+
     /// Find entities of a specific type with pagination
     ///
     /// This method supports inheritance - when searching for a parent type,
@@ -456,9 +1188,18 @@ impl Store {
                 items: Vec::new(),
                 total: 0,
                 next_cursor: None,
+                next_key: None,
             });
         }
 
+        if let Some(order_by) = opts.order_by {
+            let candidates = types_to_search
+                .iter()
+                .filter_map(|et| self.entities.get(et))
+                .flat_map(|entities| entities.iter().copied());
+            return self.find_entities_ordered(entity_type, candidates, &opts, order_by, filter);
+        }
+
         // Get cursor value
         let start_idx = opts.cursor.unwrap_or(0);
 
@@ -489,13 +1230,14 @@ impl Store {
                 items: Vec::new(),
                 total,
                 next_cursor: None,
+                next_key: None,
             });
         }
 
         // Collect only the entities we need for this page
-        let mut items = Vec::with_capacity(opts.limit);
+        let mut items = Vec::with_capacity(opts.limit.min(total - start_idx));
         let mut current_idx = 0;
-        let end_idx = std::cmp::min(start_idx + opts.limit, total);
+        let end_idx = std::cmp::min(start_idx.saturating_add(opts.limit), total);
 
         'outer: for et in types_to_search {
             if let Some(entities) = self.entities.get(et) {
@@ -524,6 +1266,7 @@ impl Store {
             items,
             total,
             next_cursor,
+            next_key: None,
         })
     }
 
@@ -535,26 +1278,28 @@ impl Store {
         start_idx: usize,
         filter_expr: &str,
     ) -> Result<PageResult<EntityId>> {
-        let mut page_items = Vec::with_capacity(opts.limit);
+        // The limit comes from the client, so it doesn't size the allocation alone
+        let candidates: usize = types_to_search
+            .iter()
+            .map(|et| self.entities.get(et).map_or(0, |entities| entities.len()))
+            .sum();
+        let mut page_items = Vec::with_capacity(opts.limit.min(candidates));
         let mut current_filtered_idx = 0;
         let mut total_filtered = 0;
-        let end_target = start_idx + opts.limit;
+        let end_target = start_idx.saturating_add(opts.limit);
 
         // Early termination flags
         let mut page_complete = false;
 
+        let plan = self.plan_filter(filter_expr);
+        let candidates = plan.as_ref().and_then(|plan| plan.candidates(self));
+
         // Iterate through all entities with optimized early termination
         for et in types_to_search {
             if let Some(entities) = self.entities.get(et) {
-                for entity_id in entities {
-                    // Apply filter using cached executor
-                    let passes_filter = {
-                        let mut executor = self.cel_executor_cache.lock().unwrap();
-                        match executor.execute(filter_expr, *entity_id, self) {
-                            Ok(cel::Value::Bool(true)) => true,
-                            _ => false, // Skip for false, non-boolean, or error results
-                        }
-                    };
+                for entity_id in narrow_to(entities, candidates.as_deref()).iter() {
+                    self.deadline.check()?;
+                    let passes_filter = self.passes_filter(plan.as_ref(), filter_expr, *entity_id);
 
                     if passes_filter {
                         total_filtered += 1;
@@ -587,28 +1332,122 @@ impl Store {
             items: page_items,
             total: total_filtered,
             next_cursor,
+            next_key: None,
         })
     }
 
-    /// Find entities of exactly the specified type (no inheritance)
-    ///
-    /// This method only returns entities of the exact type, not derived types.
-    /// This is useful when you need to distinguish between parent and child
-    /// types in an inheritance hierarchy.
-    ///
-    /// For example, if you have the hierarchy:
-    /// - Animal (base type)
-    ///   - Dog (inherits from Animal)
-    ///
-    /// Then calling `find_entities_exact` with `EntityType::from("Animal")` will
-    /// only return entities that were created with the "Animal" type, not Dog entities.
-    pub fn find_entities_exact(
+    /// Sorted pagination: every matching entity is keyed by (value, id) and
+    /// the page starts right after `opts.after`, so entities created or
+    /// deleted between pages don't make it skip or repeat any
+    fn find_entities_ordered(
         &self,
         entity_type: EntityType,
-        page_opts: Option<&PageOpts>,
+        candidates: impl Iterator<Item = EntityId>,
+        opts: &PageOpts,
+        (field_type, direction): (FieldType, SortDirection),
         filter: Option<&str>,
     ) -> Result<PageResult<EntityId>> {
-        let opts = page_opts.cloned().unwrap_or_default();
+        let field_schema = self.get_field_schema(entity_type, field_type)?;
+        if matches!(field_schema.default_value(), Value::Blob(_) | Value::EntityList(_) | Value::StringList(_) | Value::Map(_)) {
+            return Err(Error::InvalidRequest(format!("Can't sort by {:?}, it has no order", field_type)));
+        }
+
+        let plan = filter.and_then(|filter_expr| self.plan_filter(filter_expr));
+        let indexed = plan.as_ref().and_then(|plan| plan.candidates(self));
+        let mut keys = Vec::new();
+        for entity_id in candidates {
+            if indexed.as_ref().is_some_and(|indexed| indexed.binary_search(&entity_id).is_err()) {
+                continue;
+            }
+            self.deadline.check()?;
+            if let Some(filter_expr) = filter {
+                if !self.passes_filter(plan.as_ref(), filter_expr, entity_id) {
+                    continue;
+                }
+            }
+            let (value, _, _) = StoreTrait::read(self, entity_id, &[field_type])?;
+            keys.push((value, entity_id));
+        }
+        keys.sort_by(|a, b| direction.compare(a, b));
+
+        let total = keys.len();
+        let start_idx = match &opts.after {
+            Some(after) => keys.partition_point(|key| direction.compare(key, after).is_le()),
+            None => 0,
+        };
+        let end_idx = std::cmp::min(start_idx.saturating_add(opts.limit), total);
+        let next_key = if end_idx < total && end_idx > start_idx {
+            Some(keys[end_idx - 1].clone())
+        } else {
+            None
+        };
+
+        Ok(PageResult {
+            items: keys[start_idx..end_idx].iter().map(|(_, entity_id)| *entity_id).collect(),
+            total,
+            next_cursor: None,
+            next_key,
+        })
+    }
+
+    /// Build a pushdown plan for a filter expression.
+    /// Returns None if the filter can't be compiled, in which case every
+    /// entity is evaluated (and rejected) by the CEL executor as before.
+    fn plan_filter(&self, filter_expr: &str) -> Option<FilterPlan> {
+        self.cel_executors.with(|executor| FilterPlan::new(executor, filter_expr, self).ok())
+    }
+
+    /// Check whether an entity passes a filter, using the pushed down
+    /// predicates first and the cached CEL executor for anything left over
+    fn passes_filter(&self, plan: Option<&FilterPlan>, filter_expr: &str, entity_id: EntityId) -> bool {
+        if let Some(decided) = plan.and_then(|plan| plan.evaluate(entity_id, self)) {
+            return decided;
+        }
+
+        match self.cel_executors.with(|executor| executor.execute(filter_expr, entity_id, self)) {
+            Ok(cel::Value::Bool(true)) => true,
+            _ => false, // Skip for false, non-boolean, or error results
+        }
+    }
+
+    /// Evaluate a field's validator against a proposed write
+    fn validate_write(&self, validator: &str, entity_id: EntityId, field_type: FieldType, old_value: &Value, new_value: &Value) -> Result<()> {
+        let mut params = HashMap::new();
+        params.insert("old".to_string(), old_value.clone());
+        params.insert("new".to_string(), new_value.clone());
+
+        match self.cel_executors.with(|executor| executor.execute_with_params(validator, entity_id, &params, self)) {
+            Ok(cel::Value::Bool(true)) => Ok(()),
+            Ok(_) => Err(Error::InvalidFieldValue(format!(
+                "Value {:?} for {:?}.{:?} rejected by validator '{}'",
+                new_value, entity_id, field_type, validator
+            ))),
+            Err(e) => Err(Error::InvalidFieldValue(format!(
+                "Validator '{}' for {:?}.{:?} failed: {}",
+                validator, entity_id, field_type, e
+            ))),
+        }
+    }
+
+    /// Find entities of exactly the specified type (no inheritance)
+    ///
+    /// This method only returns entities of the exact type, not derived types.
+    /// This is useful when you need to distinguish between parent and child
+    /// types in an inheritance hierarchy.
+    ///
+    /// For example, if you have the hierarchy:
+    /// - Animal (base type)
+    ///   - Dog (inherits from Animal)
+    ///
+    /// Then calling `find_entities_exact` with `EntityType::from("Animal")` will
+    /// only return entities that were created with the "Animal" type, not Dog entities.
+    pub fn find_entities_exact(
+        &self,
+        entity_type: EntityType,
+        page_opts: Option<&PageOpts>,
+        filter: Option<&str>,
+    ) -> Result<PageResult<EntityId>> {
+        let opts = page_opts.cloned().unwrap_or_default();
 
         // Check if entity type exists - early return if not
         let entities = match self.entities.get(&entity_type) {
@@ -618,10 +1457,15 @@ impl Store {
                     items: Vec::new(),
                     total: 0,
                     next_cursor: None,
+                    next_key: None,
                 });
             }
         };
 
+        if let Some(order_by) = opts.order_by {
+            return self.find_entities_ordered(entity_type, entities.iter().copied(), &opts, order_by, filter);
+        }
+
         // Get cursor value
         let start_idx = opts.cursor.unwrap_or(0);
 
@@ -648,10 +1492,11 @@ impl Store {
                 items: Vec::new(),
                 total,
                 next_cursor: None,
+                next_key: None,
             });
         }
 
-        let end_idx = std::cmp::min(start_idx + opts.limit, total);
+        let end_idx = std::cmp::min(start_idx.saturating_add(opts.limit), total);
         let items = entities[start_idx..end_idx].to_vec();
 
         let next_cursor = if end_idx < total {
@@ -664,6 +1509,7 @@ impl Store {
             items,
             total,
             next_cursor,
+            next_key: None,
         })
     }
 
@@ -675,20 +1521,17 @@ impl Store {
         start_idx: usize,
         filter_expr: &str,
     ) -> Result<PageResult<EntityId>> {
-        let mut page_items = Vec::with_capacity(opts.limit);
+        let mut page_items = Vec::with_capacity(opts.limit.min(entities.len()));
         let mut current_filtered_idx = 0;
         let mut total_filtered = 0;
 
+        let plan = self.plan_filter(filter_expr);
+        let candidates = plan.as_ref().and_then(|plan| plan.candidates(self));
+
         // Process entities in order, collecting only what we need
-        for entity_id in entities {
-            // Apply filter using cached executor
-            let passes_filter = {
-                let mut executor = self.cel_executor_cache.lock().unwrap();
-                match executor.execute(filter_expr, *entity_id, self) {
-                    Ok(cel::Value::Bool(true)) => true,
-                    _ => false, // Skip for false, non-boolean, or error results
-                }
-            };
+        for entity_id in narrow_to(entities, candidates.as_deref()).iter() {
+            self.deadline.check()?;
+            let passes_filter = self.passes_filter(plan.as_ref(), filter_expr, *entity_id);
 
             if passes_filter {
                 total_filtered += 1;
@@ -702,8 +1545,8 @@ impl Store {
             }
         }
 
-        let next_cursor = if start_idx + opts.limit < total_filtered {
-            Some(start_idx + opts.limit)
+        let next_cursor = if start_idx.saturating_add(opts.limit) < total_filtered {
+            Some(start_idx.saturating_add(opts.limit))
         } else {
             None
         };
@@ -712,6 +1555,7 @@ impl Store {
             items: page_items,
             total: total_filtered,
             next_cursor,
+            next_key: None,
         })
     }
 
@@ -782,7 +1626,7 @@ impl Store {
         let start_idx = opts.cursor.unwrap_or(0);
 
         // Get the slice of types for this page
-        let end_idx = std::cmp::min(start_idx + opts.limit, total);
+        let end_idx = std::cmp::min(start_idx.saturating_add(opts.limit), total);
         let items: Vec<EntityType> = if start_idx < total {
             all_types[start_idx..end_idx].to_vec()
         } else {
@@ -800,17 +1644,32 @@ impl Store {
             items,
             total,
             next_cursor,
+            next_key: None,
         })
     }
 
     /// Register a notification configuration with a provided sender
     /// The sender will be added to the list of senders for this notification config
     /// Returns an error if the field_type contains indirection (context fields can be indirect)
+    /// or the config's filter doesn't compile
     pub fn register_notification(
         &mut self,
         config: NotifyConfig,
         sender: NotificationQueue,
     ) -> Result<()> {
+        if let Some(filter) = config.filter() {
+            self.cel_executors
+                .with(|executor| executor.get_or_compile(&filter.replace(crate::INDIRECTION_DELIMITER, "_")).map(|_| ()))
+                .map_err(|e| Error::InvalidNotifyConfig(format!("Invalid filter '{}': {}", filter, e)))?;
+        }
+
+        if let Some(key) = self.quota_key() {
+            let limits = self.limits_for(&self.client_context);
+            if !self.limits.as_mut().unwrap().try_register_notification(key.clone(), &limits) {
+                return Err(Error::QuotaExceeded(key.to_string(), MAX_NOTIFICATIONS.to_string(), limits.max_notifications.unwrap_or_default()));
+            }
+        }
+
         // Add sender to the list for this notification config
         match &config {
             NotifyConfig::EntityId {
@@ -868,7 +1727,7 @@ impl Store {
                         if let Some(senders) = sender_map.get_mut(target_config) {
                             // Find and remove the specific sender
                             let original_len = senders.len();
-                            senders.retain(|sender| !std::ptr::eq(sender, target_sender));
+                            senders.retain(|sender| !sender.same_queue(target_sender));
                             removed_any = senders.len() != original_len;
 
                             // Clean up empty entries
@@ -899,7 +1758,7 @@ impl Store {
                         if let Some(senders) = sender_map.get_mut(target_config) {
                             // Find and remove the specific sender
                             let original_len = senders.len();
-                            senders.retain(|sender| !std::ptr::eq(sender, target_sender));
+                            senders.retain(|sender| !sender.same_queue(target_sender));
                             removed_any = senders.len() != original_len;
 
                             // Clean up empty entries
@@ -920,13 +1779,19 @@ impl Store {
             }
         }
 
+        if removed_any {
+            if let Some(key) = self.quota_key() {
+                self.limits.as_mut().unwrap().release_notification(&key);
+            }
+        }
+
         removed_any
     }
 
     /// Get a reference to the fields map (converts to nested structure for compatibility)
     fn get_fields(&self) -> FxHashMap<EntityId, FxHashMap<FieldType, Field>> {
         let mut nested_fields = FxHashMap::default();
-        for ((entity_id, field_type), field) in &self.fields {
+        for ((entity_id, field_type), field) in self.fields.iter() {
             nested_fields
                 .entry(*entity_id)
                 .or_insert_with(FxHashMap::default)
@@ -945,96 +1810,1805 @@ impl Store {
         self.notifications_disabled = false;
     }
 
-    /// Take a snapshot of the current store state
-    pub fn take_snapshot(&self) -> Snapshot {
-        Snapshot::new(
-            self.schemas.clone(),
-            self.entities.clone(),
-            self.entity_type_interner.clone(),
-            self.field_type_interner.clone(),
-            self.get_fields(),
-        )
+    /// Keep notifications from here on until `flush_notifications`, so a
+    /// batch of writes is delivered together once it is persisted (see
+    /// `GroupCommit`)
+    pub fn hold_notifications(&mut self) {
+        self.held_notifications.get_or_insert_with(Vec::new);
     }
 
-    /// Restore the store state from a snapshot
-    pub fn restore_snapshot(&mut self, snapshot: Snapshot) {
-        self.schemas = snapshot.schemas;
-        self.entities = snapshot.entities;
-        self.entity_type_interner = snapshot.entity_type_interner;
-        self.field_type_interner = snapshot.field_type_interner;
+    /// Deliver the held notifications, in the order they were sent, and
+    /// keep holding new ones. Returns how many were delivered.
+    pub fn flush_notifications(&mut self) -> usize {
+        let Some(held) = self.held_notifications.as_mut() else {
+            return 0;
+        };
+        let held = std::mem::take(held);
+        let count = held.len();
+        for (queue, notification) in held {
+            queue.push(notification);
+        }
+        count
+    }
 
-        // Re-initialize ET and FT after restoring snapshot data
-        self.et = Some(ET::new(self));
-        self.ft = Some(FT::new(self));
+    /// Deliver the held notifications and stop holding them
+    pub fn release_notifications(&mut self) -> usize {
+        let count = self.flush_notifications();
+        self.held_notifications = None;
+        count
+    }
 
-        // Convert nested fields structure to flattened structure
-        self.fields.clear();
-        for (entity_id, entity_fields) in snapshot.fields {
-            for (field_type, field) in entity_fields {
-                self.fields.insert((entity_id, field_type), field);
+    /// Number of notifications waiting for `flush_notifications`
+    pub fn held_notifications(&self) -> usize {
+        self.held_notifications.as_ref().map_or(0, Vec::len)
+    }
+
+    /// Start recording every change in an audit log that keeps the last
+    /// `capacity` records in memory. Does nothing if it is already enabled.
+    pub fn enable_audit(&mut self, capacity: usize) -> &mut AuditLog {
+        self.audit.get_or_insert_with(|| AuditLog::new(capacity))
+    }
+
+    /// Stop auditing and hand back the log
+    pub fn disable_audit(&mut self) -> Option<AuditLog> {
+        self.audit.take()
+    }
+
+    /// The audit log, if auditing is enabled
+    pub fn audit_log(&self) -> Option<&AuditLog> {
+        self.audit.as_ref()
+    }
+
+    /// The audit log, if auditing is enabled, e.g. to add a sink
+    pub fn audit_log_mut(&mut self) -> Option<&mut AuditLog> {
+        self.audit.as_mut()
+    }
+
+    /// The log of commands that took longer than its threshold
+    pub fn slowlog(&self) -> &SlowLog {
+        &self.slowlog
+    }
+
+    /// The slow log, e.g. to change its threshold or clear it
+    pub fn slowlog_mut(&mut self) -> &mut SlowLog {
+        &mut self.slowlog
+    }
+
+    /// Log a command the dispatcher ran if it took at least the slow log
+    /// threshold, under the current client context's connection
+    pub fn record_slow_command(&mut self, request: &crate::data::resp::RespValue, duration: std::time::Duration) -> bool {
+        self.slowlog.record(request, duration, self.client_context.connection.as_deref())
+    }
+
+    /// Stamp local writes with a hybrid logical clock instead of the wall
+    /// clock, so their write times stay in causal order with the writes
+    /// replicated from peers even if the machines' clocks disagree. Each
+    /// write's `WriteInfo` carries the full clock reading.
+    pub fn enable_hlc(&mut self) -> &mut HybridClock {
+        self.hlc.get_or_insert_with(HybridClock::new)
+    }
+
+    /// Go back to the wall clock and hand back the hybrid clock
+    pub fn disable_hlc(&mut self) -> Option<HybridClock> {
+        self.hlc.take()
+    }
+
+    /// The hybrid clock, if enabled
+    pub fn hlc(&self) -> Option<&HybridClock> {
+        self.hlc.as_ref()
+    }
+
+    /// Move the hybrid clock past a reading from another machine, e.g. the
+    /// `hlc` of a replicated write. Does nothing if the clock isn't enabled.
+    pub fn observe_hlc(&mut self, remote: HlcTimestamp) {
+        let physical = self.now();
+        if let Some(clock) = self.hlc.as_mut() {
+            clock.update_at(remote, physical);
+        }
+    }
+
+    /// Stop the store's clock at `time`, e.g. to drive it from a simulation's
+    /// virtual clock. Creations, writes and deletes are stamped with it until
+    /// it is set again, and `None` goes back to the wall clock.
+    pub fn set_time(&mut self, time: Option<Timestamp>) {
+        self.time = time;
+    }
+
+    /// The time the store stamps changes with
+    pub fn now(&self) -> Timestamp {
+        self.time.unwrap_or_else(now)
+    }
+
+    /// Pick the ids of entities created from now on with `allocator`, e.g.
+    /// a node id per store so stores that create entities while
+    /// disconnected never mint the same id. Replicated creations keep the
+    /// id they were made with.
+    pub fn set_id_allocator(&mut self, allocator: IdAllocator) -> Result<()> {
+        if let IdAllocator::Node(node) = allocator {
+            if node > MAX_NODE_ID {
+                return Err(Error::InvalidRequest(format!("Node id {} is above {}", node, MAX_NODE_ID)));
             }
         }
+        if allocator.range().is_empty() {
+            return Err(Error::InvalidRequest(format!("{:?} has no ids to hand out", allocator)));
+        }
+        self.id_allocator = allocator;
+        Ok(())
+    }
 
-        // Clear the cache since schema structure may have changed
-        self.complete_entity_schema_cache.clear();
+    pub fn id_allocator(&self) -> &IdAllocator {
+        &self.id_allocator
+    }
 
-        // Rebuild inheritance map after restoring (this will also rebuild the cache)
-        self.rebuild_inheritance_map();
+    /// Set who the following requests are made by, until changed again
+    pub fn set_client_context(&mut self, context: ClientContext) {
+        self.client_context = context;
     }
 
-    /// Rebuild the inheritance map for fast lookup of derived types
-    /// This should be called whenever schemas are added or updated
-    fn rebuild_inheritance_map(&mut self) {
-        self.inheritance_map.clear();
-        // Clear the complete entity schema cache since inheritance relationships may have changed
-        self.complete_entity_schema_cache.clear();
+    /// Who the current request is made by
+    pub fn client_context(&self) -> &ClientContext {
+        &self.client_context
+    }
 
-        // For each entity type, find all types that inherit from it
-        for entity_type in self
-            .entity_type_interner
-            .ids()
-            .map(|id| EntityType(id as u32))
-        {
-            let mut derived_types = Vec::new();
+    /// Start enforcing rate limits and quotas. `defaults` applies to every
+    /// client whose subject entity doesn't set limits of its own.
+    pub fn enable_limits(&mut self, defaults: Limits) -> &mut LimitEnforcer {
+        let limits = self.limits.get_or_insert_with(LimitEnforcer::default);
+        limits.set_defaults(defaults);
+        limits
+    }
 
-            // Check all other types to see if they inherit from this type
-            for other_type in self
-                .entity_type_interner
-                .ids()
-                .map(|id| EntityType(id as u32))
-            {
-                if self.inherits_from(other_type.clone(), entity_type.clone()) {
-                    derived_types.push(other_type.clone());
+    /// Stop enforcing limits and hand back the usage counted so far
+    pub fn disable_limits(&mut self) -> Option<LimitEnforcer> {
+        self.limits.take()
+    }
+
+    /// The limit enforcer, if limits are enabled
+    pub fn limit_enforcer(&self) -> Option<&LimitEnforcer> {
+        self.limits.as_ref()
+    }
+
+    /// The limits that apply to a client: the defaults, overridden by any
+    /// `RateLimit`, `RateBurst`, `MaxEntities` or `MaxNotifications` field
+    /// set on its subject entity. A value below zero means unlimited.
+    pub fn limits_for(&self, context: &ClientContext) -> Limits {
+        let mut limits = self.limits.as_ref().map(|l| *l.defaults()).unwrap_or_default();
+        let Some(subject) = context.subject else {
+            return limits;
+        };
+
+        let read = |name: &str| {
+            let field_type = self.field_type_interner.get(name)?;
+            self.fields.get(&(subject, FieldType(field_type))).map(|field| field.value.clone())
+        };
+        if let Some(value) = read(RATE_LIMIT) {
+            if let Some(rate) = value.as_float().or_else(|| value.as_int().map(|v| v as f64)) {
+                limits.commands_per_second = (rate >= 0.0).then_some(rate);
+            }
+        }
+        if let Some(burst) = read(RATE_BURST).and_then(|v| v.as_int()) {
+            limits.burst = u32::try_from(burst).ok();
+        }
+        if let Some(max) = read(MAX_ENTITIES).and_then(|v| v.as_int()) {
+            limits.max_entities = u64::try_from(max).ok();
+        }
+        if let Some(max) = read(MAX_NOTIFICATIONS).and_then(|v| v.as_int()) {
+            limits.max_notifications = u64::try_from(max).ok();
+        }
+        limits
+    }
+
+    /// Count one command against the current client's rate limit. The
+    /// command dispatcher calls this before running each command.
+    pub fn check_rate_limit(&mut self) -> Result<()> {
+        if self.limits.is_none() {
+            return Ok(());
+        }
+        let Some(key) = LimitKey::from_context(&self.client_context) else {
+            return Ok(());
+        };
+        let limits = self.limits_for(&self.client_context);
+        let enforcer = self.limits.as_mut().unwrap();
+        if enforcer.try_command(key.clone(), &limits, Instant::now()) {
+            Ok(())
+        } else {
+            Err(Error::RateLimited(key.to_string()))
+        }
+    }
+
+    /// The client that quotas should be charged to, if any. Trigger actions
+    /// run on behalf of the store and aren't charged.
+    fn quota_key(&self) -> Option<LimitKey> {
+        if self.limits.is_none() || self.active_trigger.is_some() {
+            return None;
+        }
+        LimitKey::from_context(&self.client_context)
+    }
+
+    /// Set when the following requests must give up, until changed again.
+    /// The command dispatcher sets this from the timeout and request id a
+    /// command was sent with, and resets it to `Deadline::none()` afterwards.
+    pub fn set_deadline(&mut self, deadline: Deadline) {
+        self.deadline = deadline;
+    }
+
+    /// When the current request must give up
+    pub fn deadline(&self) -> &Deadline {
+        &self.deadline
+    }
+
+    /// Answer an `AUDIT_QUERY`
+    pub fn query_audit(&self, query: &AuditQuery) -> Result<Vec<AuditRecord>> {
+        self.audit
+            .as_ref()
+            .map(|audit| audit.query(query))
+            .ok_or_else(|| Error::InvalidRequest("Audit log is not enabled".to_string()))
+    }
+
+    /// Answer a `GET_CHANGES`: every write to `entity_id` the store made at
+    /// or after `since`, oldest first, including its creation and deletion.
+    /// Taken from the audit log, so it fails if auditing isn't enabled or if
+    /// changes that recent have already dropped out of the log.
+    pub fn get_changes(&self, entity_id: EntityId, since: Timestamp) -> Result<Vec<WriteInfo>> {
+        let audit = self
+            .audit
+            .as_ref()
+            .ok_or_else(|| Error::InvalidRequest("Audit log is not enabled".to_string()))?;
+        if !audit.covers(since) {
+            return Err(Error::InvalidRequest(format!(
+                "Changes since {} are no longer in the audit log",
+                since
+            )));
+        }
+
+        let query = AuditQuery {
+            since: Some(since),
+            entity_id: Some(entity_id),
+            ..Default::default()
+        };
+        Ok(audit.query(&query).into_iter().map(|record| record.write).collect())
+    }
+
+    /// Check the current client may write a field with the given scope
+    fn check_write_scope(&self, write_scope: WriteScope, entity_id: EntityId, field_type: FieldType) -> Result<()> {
+        let Some(subject) = self.client_context.subject else {
+            return Ok(());
+        };
+        // Trigger actions run on behalf of the store, not the client
+        if self.client_context.admin || self.active_trigger.is_some() {
+            return Ok(());
+        }
+
+        let allowed = match write_scope {
+            WriteScope::OwnerOnly => subject == entity_id,
+            WriteScope::ServiceOnly => {
+                let subject_type = subject.extract_type();
+                self.et
+                    .as_ref()
+                    .and_then(|et| et.service)
+                    .is_some_and(|service| subject_type == service || self.inherits_from(subject_type, service))
+            }
+            WriteScope::AdminOnly => false,
+        };
+
+        if allowed {
+            Ok(())
+        } else {
+            Err(Error::PermissionDenied(subject, entity_id, field_type))
+        }
+    }
+
+    /// Check no other child of `parent_id` has `name`, if the entity's type
+    /// declares its Name field unique among siblings. Top-level entities
+    /// are not checked.
+    fn check_unique_name(&self, entity_id: EntityId, parent_id: Option<EntityId>, name: &str) -> Result<()> {
+        let Some(parent_id) = parent_id else {
+            return Ok(());
+        };
+        let ft = self.ft.as_ref().unwrap();
+        let (Some(ft_name), Some(ft_children)) = (ft.name, ft.children) else {
+            return Ok(());
+        };
+        let unique = self
+            .get_complete_entity_schema(entity_id.extract_type())?
+            .fields
+            .get(&ft_name)
+            .is_some_and(|field_schema| field_schema.is_unique_among_siblings());
+        if !unique {
+            return Ok(());
+        }
+
+        let siblings = self.fields.get(&(parent_id, ft_children)).and_then(|field| field.value.as_entity_list());
+        for sibling in siblings.into_iter().flatten() {
+            if *sibling == entity_id {
+                continue;
+            }
+            let sibling_name = self.fields.get(&(*sibling, ft_name)).and_then(|field| field.value.as_string());
+            if sibling_name.is_some_and(|sibling_name| sibling_name == name) {
+                return Err(Error::EntityAlreadyExists(*sibling));
+            }
+        }
+        Ok(())
+    }
+
+    /// Queue a write for persistence and replication, auditing it if enabled
+    fn queue_write(&mut self, write: WriteInfo) {
+        if let Some(audit) = self.audit.as_mut() {
+            audit.record(&self.client_context, &write);
+        }
+        self.write_queue.push_back(write);
+    }
+
+    /// Sequence number the next queued write will get. Pass it to
+    /// `mark_writes_read` after handling the `unread_writes` read before it.
+    pub fn write_seq(&self) -> u64 {
+        self.write_seq + self.write_queue.len() as u64
+    }
+
+    /// Start reading the write queue as `reader`, from the oldest write
+    /// still queued. Until a reader is added, the queue is only trimmed
+    /// past the readers that are. Adding one twice does nothing.
+    pub fn add_write_reader(&mut self, reader: &str) {
+        if !self.write_readers.contains_key(reader) {
+            let next = self.write_seq;
+            self.write_readers.insert(reader.to_string(), WriteReader { next, skipped: Vec::new() });
+        }
+    }
+
+    /// Stop reading the write queue as `reader`
+    pub fn remove_write_reader(&mut self, reader: &str) {
+        self.write_readers.remove(reader);
+        self.trim_write_queue();
+    }
+
+    /// The queued writes `reader` hasn't read yet, oldest first. Each
+    /// reader (e.g. replication and persistence) has its own place in the
+    /// queue, so reading doesn't take writes away from the others. An
+    /// unknown reader reads from the oldest write still queued.
+    pub fn unread_writes<'a>(&'a self, reader: &str) -> impl Iterator<Item = &'a WriteInfo> + 'a {
+        let state = self.write_readers.get(reader);
+        let next = state.map_or(self.write_seq, |state| state.next.max(self.write_seq));
+        let skipped = state.map(|state| state.skipped.as_slice()).unwrap_or_default();
+        let start = self.write_seq;
+        self.write_queue
+            .iter()
+            .enumerate()
+            .skip((next - start) as usize)
+            .filter(move |(index, _)| {
+                let seq = start + *index as u64;
+                !skipped.iter().any(|range| range.contains(&seq))
+            })
+            .map(|(_, write)| write)
+    }
+
+    /// Record that `reader` has handled every write before `seq` (from
+    /// `write_seq`), dropping the writes all readers are past
+    pub fn mark_writes_read(&mut self, reader: &str, seq: u64) {
+        self.add_write_reader(reader);
+        if let Some(state) = self.write_readers.get_mut(reader) {
+            state.next = state.next.max(seq.min(self.write_seq + self.write_queue.len() as u64));
+            state.skipped.retain(|range| range.end > state.next);
+        }
+        self.trim_write_queue();
+    }
+
+    /// Hide the writes queued since `seq` (from `write_seq`) from `reader`,
+    /// for writes it made itself, such as replication applying a peer's
+    /// batch. The other readers still see them.
+    pub fn skip_writes(&mut self, reader: &str, seq: u64) {
+        self.add_write_reader(reader);
+        let end = self.write_seq();
+        if let Some(state) = self.write_readers.get_mut(reader) {
+            if state.next >= seq {
+                state.next = state.next.max(end);
+                state.skipped.retain(|range| range.end > state.next);
+            } else if seq < end {
+                state.skipped.push(seq..end);
+            }
+        }
+        self.trim_write_queue();
+    }
+
+    /// Drop the queued writes every reader has read
+    fn trim_write_queue(&mut self) {
+        let Some(read) = self.write_readers.values().map(|state| state.next).min() else {
+            return;
+        };
+        while self.write_seq < read && self.write_queue.pop_front().is_some() {
+            self.write_seq += 1;
+        }
+    }
+
+    /// Register a trigger to run after writes to `trigger.field_type` on
+    /// entities of `trigger.entity_type` (or any derived type). The trigger
+    /// is stored as a new `Trigger` entity under `parent_id`.
+    pub fn register_trigger(&mut self, parent_id: Option<EntityId>, trigger: Trigger) -> Result<TriggerId> {
+        if !self.field_exists(trigger.entity_type, trigger.field_type) {
+            return Err(Error::InvalidFieldType(format!(
+                "Field {:?} does not exist on entity type {:?}",
+                trigger.field_type, trigger.entity_type
+            )));
+        }
+
+        let et_trigger = self
+            .et
+            .as_ref()
+            .and_then(|et| et.trigger)
+            .ok_or_else(|| Error::EntityTypeStrNotFound(crate::et::TRIGGER.to_string()))?;
+        let ft = self.ft.clone().unwrap_or_else(|| FT::new(self));
+        let field = |field_type: Option<FieldType>, name: &str| field_type.ok_or_else(|| Error::FieldTypeStrNotFound(name.to_string()));
+
+        let entity_type_name = self.resolve_entity_type(trigger.entity_type)?;
+        let field_name = self.resolve_field_type(trigger.field_type)?;
+        let TriggerAction::Write { field_path, expression } = trigger.action;
+        let target_field = field_path
+            .iter()
+            .map(|field_type| self.resolve_field_type(*field_type))
+            .collect::<Result<Vec<_>>>()?
+            .join(crate::INDIRECTION_DELIMITER);
+
+        let mut fields = vec![
+            (field(ft.resource_type, crate::ft::RESOURCE_TYPE)?, Value::String(entity_type_name.clone())),
+            (field(ft.resource_field, crate::ft::RESOURCE_FIELD)?, Value::String(field_name.clone())),
+            (field(ft.target_field, crate::ft::TARGET_FIELD)?, Value::String(target_field)),
+            (field(ft.action, crate::ft::ACTION)?, Value::String(expression)),
+        ];
+        if let Some(condition) = trigger.condition {
+            fields.push((field(ft.condition, crate::ft::CONDITION)?, Value::String(condition)));
+        }
+
+        let name = format!("{}.{}", entity_type_name, field_name);
+        self.create_entity_with_fields(et_trigger, parent_id, &name, fields)
+    }
+
+    /// Remove a trigger by deleting its `Trigger` entity, returning what it did
+    pub fn unregister_trigger(&mut self, trigger_id: TriggerId) -> Result<Trigger> {
+        let trigger = self.load_trigger(trigger_id).ok_or(Error::EntityNotFound(trigger_id))?;
+        self.delete_entity(trigger_id)?;
+        Ok(trigger)
+    }
+
+    /// The trigger a `Trigger` entity defines, if it is one and the names
+    /// it holds resolve
+    fn load_trigger(&self, trigger_id: TriggerId) -> Option<Trigger> {
+        if !self.is_trigger_type(trigger_id.extract_type()) {
+            return None;
+        }
+
+        let ft = self.ft.as_ref()?;
+        let text = |field_type: Option<FieldType>| {
+            self.fields
+                .get(&(trigger_id, field_type?))
+                .and_then(|field| field.value.as_string())
+                .map(str::to_string)
+        };
+
+        let entity_type = self.get_entity_type(&text(ft.resource_type)?).ok()?;
+        let field_type = self.get_field_type(&text(ft.resource_field)?).ok()?;
+        let field_path = text(ft.target_field)?
+            .split(crate::INDIRECTION_DELIMITER)
+            .map(|field_name| self.get_field_type(field_name).ok())
+            .collect::<Option<Vec<_>>>()?;
+        let expression = text(ft.action)?;
+        let condition = text(ft.condition).filter(|condition| !condition.is_empty());
+
+        Some(Trigger {
+            entity_type,
+            field_type,
+            condition,
+            action: TriggerAction::Write { field_path, expression },
+        })
+    }
+
+    /// Whether entities of `entity_type` define triggers
+    fn is_trigger_type(&self, entity_type: EntityType) -> bool {
+        match self.et.as_ref().and_then(|et| et.trigger) {
+            Some(et_trigger) => self
+                .inheritance_map
+                .get(&et_trigger)
+                .map(|derived| derived.contains(&entity_type))
+                .unwrap_or(et_trigger == entity_type),
+            None => false,
+        }
+    }
+
+    /// The triggers of every `Trigger` entity, loaded again if they may have
+    /// changed since they were last used
+    fn trigger_index(&mut self) -> Arc<TriggerIndex> {
+        if let Some(index) = &self.triggers {
+            return Arc::clone(index);
+        }
+
+        let mut index = TriggerIndex::default();
+        for (entity_type, entities) in self.entities.iter() {
+            if !self.is_trigger_type(*entity_type) {
+                continue;
+            }
+            for trigger_id in entities.iter() {
+                if let Some(trigger) = self.load_trigger(*trigger_id) {
+                    index.entry(trigger.field_type).or_default().push((*trigger_id, trigger));
+                }
+            }
+        }
+
+        let index = Arc::new(index);
+        self.triggers = Some(Arc::clone(&index));
+        index
+    }
+
+    /// The triggers watching a field of an entity
+    fn matching_triggers(&mut self, entity_id: EntityId, field_type: FieldType) -> Vec<(TriggerId, Trigger)> {
+        if self.triggers_disabled {
+            return Vec::new();
+        }
+
+        let entity_type = entity_id.extract_type();
+        match self.trigger_index().get(&field_type) {
+            Some(triggers) => triggers
+                .iter()
+                .filter(|(_, trigger)| {
+                    self.inheritance_map
+                        .get(&trigger.entity_type)
+                        .map(|derived| derived.contains(&entity_type))
+                        .unwrap_or(trigger.entity_type == entity_type)
+                })
+                .cloned()
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Disable triggers temporarily (e.g., while applying replicated writes)
+    pub fn disable_triggers(&mut self) {
+        self.triggers_disabled = true;
+    }
+
+    /// Re-enable triggers
+    pub fn enable_triggers(&mut self) {
+        self.triggers_disabled = false;
+    }
+
+    /// Whether triggers are currently disabled
+    pub fn triggers_disabled(&self) -> bool {
+        self.triggers_disabled
+    }
+
+    /// Bump the LeaderToken of a fault tolerance entity whenever its
+    /// CurrentLeader changes, so writes fenced with an older token are rejected.
+    /// Only local assignments advance the token; replicated ones carry it along.
+    fn advance_leader_token(&mut self, entity_id: EntityId, field_type: FieldType, old_value: &Value, new_value: &Value) -> Result<()> {
+        let ft = self.ft.as_ref().unwrap();
+        let (Some(ft_current_leader), Some(ft_leader_token)) = (ft.current_leader, ft.leader_token) else {
+            return Ok(());
+        };
+
+        if field_type != ft_current_leader
+            || old_value == new_value
+            || !self.field_exists(entity_id.extract_type(), ft_leader_token)
+        {
+            return Ok(());
+        }
+
+        self.write(entity_id, &[ft_leader_token], Value::Int(1), None, None, None, Some(AdjustBehavior::Add))
+    }
+
+    /// Apply a write only if `fencing_token` is the current LeaderToken of
+    /// the fault tolerance entity, i.e. the writer is still the leader it
+    /// thinks it is.
+    #[allow(clippy::too_many_arguments)]
+    pub fn fenced_write(
+        &mut self,
+        fault_tolerance_id: EntityId,
+        fencing_token: i64,
+        entity_id: EntityId,
+        field_path: &[FieldType],
+        value: Value,
+        writer_id: Option<EntityId>,
+        write_time: Option<Timestamp>,
+        push_condition: Option<PushCondition>,
+        adjust_behavior: Option<AdjustBehavior>,
+    ) -> Result<()> {
+        let ft_leader_token = self
+            .ft
+            .as_ref()
+            .and_then(|ft| ft.leader_token)
+            .ok_or_else(|| Error::FieldTypeStrNotFound(crate::ft::LEADER_TOKEN.to_string()))?;
+
+        let (current_token, _, _) = self.read(fault_tolerance_id, &[ft_leader_token])?;
+        let current_token = current_token.as_int().unwrap_or_default();
+        if current_token != fencing_token {
+            return Err(Error::StaleFencingToken(fault_tolerance_id, fencing_token, current_token));
+        }
+
+        self.write(entity_id, field_path, value, writer_id, write_time, push_condition, adjust_behavior)
+    }
+
+    /// Resolve an entity path such as `Root/Machines/M1` to its entity id
+    pub fn resolve_path(&self, path: &str) -> Result<EntityId> {
+        path_to_entity_id(self, path)
+    }
+
+    /// Read a field addressed by path, e.g. `Root/Machines/M1/Pump3/Speed`
+    pub fn read_path(&self, path: &str) -> Result<(Value, Timestamp, Option<EntityId>)> {
+        let (entity_id, field_path) = path_to_field_path(self, path)?;
+        self.read(entity_id, &field_path)
+    }
+
+    /// Write a field addressed by path, e.g. `Root/Machines/M1/Pump3/Speed`
+    pub fn write_path(
+        &mut self,
+        path: &str,
+        value: Value,
+        writer_id: Option<EntityId>,
+        write_time: Option<Timestamp>,
+        push_condition: Option<PushCondition>,
+        adjust_behavior: Option<AdjustBehavior>,
+    ) -> Result<()> {
+        let (entity_id, field_path) = path_to_field_path(self, path)?;
+        self.write(entity_id, &field_path, value, writer_id, write_time, push_condition, adjust_behavior)
+    }
+
+    /// Create an entity with initial values for some of its fields, so no
+    /// client sees it half-initialized. Every value is checked against its
+    /// schema, write scope and validator before any is written, and the
+    /// notifications for the writes are delivered together once all of them
+    /// succeeded. If a write still fails, e.g. in a trigger, the store is
+    /// left as it was before (see `atomically`) and the error returned.
+    ///
+    /// `Parent` and `Children` follow from `parent_id` and can't be given.
+    pub fn create_entity_with_fields(
+        &mut self,
+        entity_type: EntityType,
+        parent_id: Option<EntityId>,
+        name: &str,
+        fields: Vec<(FieldType, Value)>,
+    ) -> Result<EntityId> {
+        let (ft_parent, ft_children) = {
+            let ft = self.ft.as_ref().unwrap();
+            (ft.parent.unwrap(), ft.children.unwrap())
+        };
+        if let Some((field_type, _)) = fields.iter().find(|(field_type, _)| *field_type == ft_parent || *field_type == ft_children) {
+            return Err(Error::InvalidRequest(format!(
+                "Field {:?} is set from the parent and cannot be given an initial value",
+                field_type
+            )));
+        }
+
+        self.atomically(|store| {
+            let entity_id = store.create_entity(entity_type, parent_id, name)?;
+            fields
+                .iter()
+                .try_for_each(|(field_type, value)| store.check_initial_value(entity_id, *field_type, value))?;
+            fields
+                .into_iter()
+                .try_for_each(|(field_type, value)| store.write(entity_id, &[field_type], value, None, None, None, None))?;
+            Ok(entity_id)
+        })
+    }
+
+    /// Create a copy of the template `template_id` and its descendants
+    /// under `parent_id`, named `name`. Field values are copied, and
+    /// references between entities of the template are pointed at their
+    /// copies. `overrides` are then written to the new root, as with
+    /// `create_entity_with_fields`. The copies are not templates themselves.
+    ///
+    /// Like `create_entity_with_fields`, the copy appears all at once or,
+    /// if a write fails, not at all.
+    pub fn instantiate_template(
+        &mut self,
+        template_id: EntityId,
+        parent_id: Option<EntityId>,
+        name: &str,
+        overrides: Vec<(FieldType, Value)>,
+    ) -> Result<EntityId> {
+        if !self.is_template(template_id)? {
+            return Err(Error::InvalidRequest(format!("{:?} is not a template", template_id)));
+        }
+
+        let (ft_name, ft_parent, ft_children, ft_is_template) = {
+            let ft = self.ft.as_ref().unwrap();
+            (ft.name.unwrap(), ft.parent.unwrap(), ft.children.unwrap(), ft.is_template)
+        };
+        if let Some((field_type, _)) = overrides.iter().find(|(field_type, _)| *field_type == ft_parent || *field_type == ft_children) {
+            return Err(Error::InvalidRequest(format!(
+                "Field {:?} is set from the parent and cannot be given an initial value",
+                field_type
+            )));
+        }
+
+        // Parents come before their children
+        let mut template = vec![template_id];
+        let mut next = 0;
+        while let Some(entity_id) = template.get(next).copied() {
+            if let Some(Value::EntityList(children)) = self.fields.get(&(entity_id, ft_children)).map(|field| &field.value) {
+                template.extend(children.iter().copied());
+            }
+            next += 1;
+        }
+
+        self.atomically(|store| {
+            let mut copies = FxHashMap::default();
+            for entity_id in &template {
+                let (parent, name) = if *entity_id == template_id {
+                    (parent_id, name.to_string())
+                } else {
+                    let parent = match store.fields.get(&(*entity_id, ft_parent)).map(|field| &field.value) {
+                        Some(Value::EntityReference(Some(parent))) => copies.get(parent).copied(),
+                        _ => None,
+                    };
+                    let name = store.fields.get(&(*entity_id, ft_name)).and_then(|field| field.value.as_string()).unwrap_or_default().to_string();
+                    (parent, name)
+                };
+                let copy = store.create_entity(entity_id.extract_type(), parent, &name)?;
+                copies.insert(*entity_id, copy);
+            }
+
+            let copy_root = copies[&template_id];
+            overrides
+                .iter()
+                .try_for_each(|(field_type, value)| store.check_initial_value(copy_root, *field_type, value))?;
+
+            for entity_id in &template {
+                let copy = copies[entity_id];
+                let values: Vec<(FieldType, Value)> = store
+                    .get_complete_entity_schema(entity_id.extract_type())?
+                    .fields
+                    .keys()
+                    .filter(|field_type| ![ft_name, ft_parent, ft_children].contains(field_type) && Some(**field_type) != ft_is_template)
+                    .filter_map(|field_type| {
+                        let value = &store.fields.get(&(*entity_id, *field_type))?.value;
+                        let copied = remap_references(value, &copies);
+                        (store.fields.get(&(copy, *field_type)).map(|field| &field.value) != Some(&copied)).then_some((*field_type, copied))
+                    })
+                    .collect();
+                for (field_type, value) in values {
+                    store.write(copy, &[field_type], value, None, None, None, None)?;
+                }
+            }
+
+            for (field_type, value) in overrides {
+                store.write(copy_root, &[field_type], value, None, None, None, None)?;
+            }
+            Ok(copy_root)
+        })
+    }
+
+    /// Run `change` as one unit: if it fails, the store is put back the way
+    /// it was before it started, its queued writes are dropped and none of
+    /// its notifications are delivered. Its writes are audited only if it
+    /// succeeds.
+    ///
+    /// The store's data is copy-on-write, so keeping the old state around
+    /// only costs a copy of the maps `change` actually writes to.
+    pub fn atomically<T>(&mut self, change: impl FnOnce(&mut Self) -> Result<T>) -> Result<T> {
+        let checkpoint = Store {
+            schemas: Arc::clone(&self.schemas),
+            entities: Arc::clone(&self.entities),
+            fields: Arc::clone(&self.fields),
+            references: Arc::clone(&self.references),
+            tombstones: Arc::clone(&self.tombstones),
+            series: Arc::clone(&self.series),
+            #[cfg(feature = "search")]
+            search: Arc::clone(&self.search),
+            entity_type_interner: self.entity_type_interner.clone(),
+            field_type_interner: self.field_type_interner.clone(),
+            et: self.et.clone(),
+            ft: self.ft.clone(),
+            inheritance_map: self.inheritance_map.clone(),
+            complete_entity_schema_cache: self.complete_entity_schema_cache.clone(),
+            computed_dependents: self.computed_dependents.clone(),
+            computed_fields: self.computed_fields.clone(),
+            value_index: RwLock::new(self.value_index.read().unwrap().clone()),
+            ..Store::new()
+        };
+        let queued = self.write_queue.len();
+        let held = self.held_notifications.as_ref().map(Vec::len);
+        let audit = self.audit.take();
+        self.hold_notifications();
+
+        let result = change(self);
+
+        self.audit = audit;
+        match &result {
+            Ok(_) => {
+                if let Some(audit) = self.audit.as_mut() {
+                    for write in self.write_queue.range(queued..) {
+                        audit.record(&self.client_context, write);
+                    }
+                }
+            }
+            Err(_) => {
+                self.schemas = checkpoint.schemas;
+                self.entities = checkpoint.entities;
+                self.fields = checkpoint.fields;
+                self.references = checkpoint.references;
+                self.tombstones = checkpoint.tombstones;
+                self.series = checkpoint.series;
+                #[cfg(feature = "search")]
+                {
+                    self.search = checkpoint.search;
+                }
+                self.entity_type_interner = checkpoint.entity_type_interner;
+                self.field_type_interner = checkpoint.field_type_interner;
+                self.et = checkpoint.et;
+                self.ft = checkpoint.ft;
+                self.inheritance_map = checkpoint.inheritance_map;
+                self.complete_entity_schema_cache = checkpoint.complete_entity_schema_cache;
+                self.computed_dependents = checkpoint.computed_dependents;
+                self.computed_fields = checkpoint.computed_fields;
+                self.value_index = checkpoint.value_index;
+                self.computed_cache.write().unwrap().clear();
+                self.triggers = None;
+
+                self.write_queue.truncate(queued);
+                if let Some(notifications) = self.held_notifications.as_mut() {
+                    notifications.truncate(held.unwrap_or(0));
+                }
+            }
+        }
+        if held.is_none() {
+            self.release_notifications();
+        }
+        result
+    }
+
+    /// The checks `write` makes before changing a field, for an initial
+    /// value of a new entity
+    fn check_initial_value(&self, entity_id: EntityId, field_type: FieldType, value: &Value) -> Result<()> {
+        let field_schema = self
+            .get_complete_entity_schema(entity_id.extract_type())?
+            .fields
+            .get(&field_type)
+            .ok_or(Error::FieldTypeNotFound(entity_id, field_type))?;
+        if field_schema.is_computed() {
+            return Err(Error::InvalidRequest(format!(
+                "Field {:?} of {:?} is computed and cannot be written",
+                field_type, entity_id
+            )));
+        }
+
+        let old_value = self
+            .fields
+            .get(&(entity_id, field_type))
+            .map_or_else(|| field_schema.default_value(), |field| field.value.clone());
+        if discriminant(value) != discriminant(&old_value) {
+            return Err(Error::ValueTypeMismatch(entity_id, field_type, old_value, value.clone()));
+        }
+
+        let bounds = field_schema.metadata();
+        if !bounds.in_range(value) {
+            return Err(Error::InvalidFieldValue(format!(
+                "Value {:?} for {:?}.{:?} is outside the allowed range [{}, {}]",
+                value,
+                entity_id,
+                field_type,
+                bounds.min.map_or("-inf".to_string(), |min| min.to_string()),
+                bounds.max.map_or("inf".to_string(), |max| max.to_string()),
+            )));
+        }
+
+        if let Some(write_scope) = field_schema.write_scope() {
+            self.check_write_scope(write_scope, entity_id, field_type)?;
+        }
+        if let Some(validator) = field_schema.validator() {
+            self.validate_write(validator, entity_id, field_type, &old_value, value)?;
+        }
+        Ok(())
+    }
+
+    /// Read `len` bytes of a blob field starting at `offset`, along with the
+    /// blob's total length. The slice is cut short at the end of the blob and
+    /// is empty once `offset` reaches it.
+    pub fn read_blob_range(&self, entity_id: EntityId, field_path: &[FieldType], offset: usize, len: usize) -> Result<(Vec<u8>, usize)> {
+        let (resolved_entity_id, resolved_field_type) = self.resolve_indirection(entity_id, field_path)?;
+        let slice_of = |blob: &[u8]| -> Result<(Vec<u8>, usize)> {
+            if offset > blob.len() {
+                return Err(Error::InvalidRequest(format!(
+                    "Offset {} is past the end of the {} byte blob {:?}.{:?}",
+                    offset, blob.len(), resolved_entity_id, resolved_field_type
+                )));
+            }
+            let end = offset.saturating_add(len).min(blob.len());
+            Ok((blob[offset..end].to_vec(), blob.len()))
+        };
+
+        // Slice the stored blob in place so large values aren't cloned per chunk
+        let value = match self.fields.get(&(resolved_entity_id, resolved_field_type)) {
+            Some(field) => match &field.value {
+                Value::Blob(blob) => return slice_of(blob),
+                other => other.clone(),
+            },
+            None => self.read(resolved_entity_id, &[resolved_field_type])?.0,
+        };
+        match value {
+            Value::Blob(blob) => slice_of(&blob),
+            other => Err(Error::ValueTypeMismatch(resolved_entity_id, resolved_field_type, Value::Blob(Vec::new()), other)),
+        }
+    }
+
+    /// Append bytes to a blob field, returning the blob's new length
+    pub fn write_blob_append(&mut self, entity_id: EntityId, field_path: &[FieldType], data: Vec<u8>, writer_id: Option<EntityId>) -> Result<usize> {
+        self.write(entity_id, field_path, Value::Blob(data), writer_id, None, None, Some(AdjustBehavior::Add))?;
+        let (resolved_entity_id, resolved_field_type) = self.resolve_indirection(entity_id, field_path)?;
+        match self.fields.get(&(resolved_entity_id, resolved_field_type)).map(|field| &field.value) {
+            Some(Value::Blob(blob)) => Ok(blob.len()),
+            Some(other) => Err(Error::ValueTypeMismatch(resolved_entity_id, resolved_field_type, Value::Blob(Vec::new()), other.clone())),
+            None => Err(Error::FieldTypeNotFound(resolved_entity_id, resolved_field_type)),
+        }
+    }
+
+    /// Apply a write to a field whose indirection was already resolved
+    #[allow(clippy::too_many_arguments)]
+    fn write_field(&mut self, entity_id: EntityId, field_type: FieldType, value: Value, writer_id: Option<EntityId>, write_time: Option<Timestamp>, push_condition: Option<PushCondition>, adjust_behavior: Option<AdjustBehavior>) -> Result<()> {
+        let push_condition = push_condition.unwrap_or(PushCondition::Always);
+        let adjust_behavior = adjust_behavior.unwrap_or(AdjustBehavior::Set);
+
+        // Get the schema from cache (should be populated by rebuild_complete_entity_schema_cache())
+        let entity_schema = self.get_complete_entity_schema(entity_id.extract_type())?;
+        let (default_value, validator, bounds, write_scope, series_limits) = {
+            let field_schema = entity_schema
+                .fields
+                .get(&field_type)
+                .ok_or_else(|| Error::FieldTypeNotFound(entity_id, field_type))?;
+            if field_schema.is_computed() {
+                return Err(Error::InvalidRequest(format!(
+                    "Field {:?} of {:?} is computed and cannot be written",
+                    field_type, entity_id
+                )));
+            }
+            // Only min/max are needed here, so avoid cloning the descriptive strings
+            let metadata = field_schema.metadata();
+            let bounds = FieldMetadata { min: metadata.min, max: metadata.max, ..Default::default() };
+            (field_schema.default_value(), field_schema.validator().map(|v| v.to_string()), bounds, field_schema.write_scope(), field_schema.series_limits())
+        };
+
+        if let Some(write_scope) = write_scope {
+            self.check_write_scope(write_scope, entity_id, field_type)?;
+        }
+
+        let old_value = self
+            .fields
+            .get(&(entity_id, field_type))
+            .map_or_else(|| default_value.clone(), |field| field.value.clone());
+        // Check that the value being written is the same type as the field schema
+        if discriminant(&value) != discriminant(&default_value) {
+            return Err(Error::ValueTypeMismatch(
+                entity_id,
+                field_type,
+                default_value,
+                value.clone(),
+            ));
+        }
+
+        let mut new_value = value.clone();
+
+        match adjust_behavior {
+            AdjustBehavior::Add => match &old_value {
+                Value::Int(old_int) => {
+                    new_value = Value::Int(old_int + new_value.as_int().unwrap_or(0));
+                }
+                Value::Float(old_float) => {
+                    new_value = Value::Float(old_float + new_value.as_float().unwrap_or(0.0));
+                }
+                Value::Decimal(old_decimal) => {
+                    let delta = new_value.as_decimal().unwrap_or(Decimal::ZERO);
+                    new_value = Value::Decimal(old_decimal.checked_add(&delta).ok_or_else(|| {
+                        Error::InvalidFieldValue(format!("Decimal overflow adding {} to {}", delta, old_decimal))
+                    })?);
+                }
+                Value::Duration(old_duration) => {
+                    new_value = Value::Duration(*old_duration + new_value.as_duration().unwrap_or(Duration::ZERO));
+                }
+                Value::StringList(old_list) => {
+                    new_value = Value::StringList(
+                        old_list
+                            .iter()
+                            .chain(new_value.as_string_list().unwrap_or(&Vec::new()).iter())
+                            .unique()
+                            .cloned()
+                            .collect(),
+                    );
+                }
+                Value::Map(old_map) => {
+                    // Entries from the new map are added, replacing existing keys
+                    let mut merged = old_map.clone();
+                    if let Value::Map(entries) = new_value {
+                        merged.extend(entries);
+                    }
+                    new_value = Value::Map(merged);
+                }
+                Value::EntityReference(old_ref) => {
+                    if old_ref.is_some() {
+                        // prefer the old value if old value exists
+                        new_value = old_value.clone();
+                    }
+                    // otherwise just use the new value (which could be None or Some)
+                }
+                Value::EntityList(old_list) => {
+                    new_value = Value::EntityList(
+                        old_list
+                            .iter()
+                            .chain(new_value.as_entity_list().unwrap_or(&Vec::new()).iter())
+                            .unique()
+                            .cloned()
+                            .collect(),
+                    );
+                }
+                Value::String(old_string) => {
+                    new_value = Value::String(format!(
+                        "{}{}",
+                        old_string,
+                        new_value.as_string().unwrap_or_default()
+                    ).into());
+                }
+                Value::Blob(old_file) => {
+                    let combined_vec: Vec<u8> = old_file
+                        .iter()
+                        .chain(new_value.as_blob().unwrap_or(&[]).iter())
+                        .cloned()
+                        .collect();
+                    new_value = Value::Blob(combined_vec.into());
+                }
+                _ => {
+                    return Err(Error::UnsupportedAdjustBehavior(
+                        entity_id,
+                        field_type,
+                        adjust_behavior.clone(),
+                    ));
+                }
+            },
+            AdjustBehavior::Subtract => match &old_value {
+                Value::Int(old_int) => {
+                    new_value = Value::Int(old_int - new_value.as_int().unwrap_or(0));
+                }
+                Value::Float(old_float) => {
+                    new_value = Value::Float(old_float - new_value.as_float().unwrap_or(0.0));
+                }
+                Value::Decimal(old_decimal) => {
+                    let delta = new_value.as_decimal().unwrap_or(Decimal::ZERO);
+                    new_value = Value::Decimal(old_decimal.checked_sub(&delta).ok_or_else(|| {
+                        Error::InvalidFieldValue(format!("Decimal overflow subtracting {} from {}", delta, old_decimal))
+                    })?);
+                }
+                Value::Duration(old_duration) => {
+                    new_value = Value::Duration(*old_duration - new_value.as_duration().unwrap_or(Duration::ZERO));
+                }
+                Value::StringList(old_list) => {
+                    let removed = new_value.as_string_list().cloned().unwrap_or_default();
+                    new_value = Value::StringList(
+                        old_list
+                            .iter()
+                            .filter(|item| !removed.contains(item))
+                            .cloned()
+                            .collect(),
+                    );
+                }
+                Value::Map(old_map) => {
+                    // Keys present in the new map are removed, whatever their values
+                    let removed = new_value.as_map().cloned().unwrap_or_default();
+                    new_value = Value::Map(
+                        old_map
+                            .iter()
+                            .filter(|(key, _)| !removed.contains_key(*key))
+                            .map(|(key, value)| (key.clone(), value.clone()))
+                            .collect(),
+                    );
+                }
+                Value::EntityReference(old_ref) => {
+                    if let Some(old_id) = old_ref {
+                        if let Some(new_id) = new_value.as_entity_reference().unwrap_or(&None) {
+                            if old_id == new_id {
+                                // If the new value matches the old value, set to None
+                                new_value = Value::EntityReference(None);
+                            } else {
+                                // Otherwise, keep the old value
+                                new_value = old_value.clone();
+                            }
+                        }
+                    }
+                }
+                Value::EntityList(old_list) => {
+                    let new_list = new_value.as_entity_list().cloned().unwrap_or_default();
+                    new_value = Value::EntityList(
+                        old_list
+                            .iter()
+                            .filter(|item| !new_list.contains(item))
+                            .cloned()
+                            .collect(),
+                    );
+                }
+                _ => {
+                    return Err(Error::UnsupportedAdjustBehavior(
+                        entity_id,
+                        field_type,
+                        adjust_behavior.clone(),
+                    ));
+                }
+            },
+            _ => {
+                // No adjustment needed
+            }
+        }
+
+        let delta = match adjust_behavior {
+            AdjustBehavior::Set => None,
+            _ => Some(value),
+        };
+
+        if !bounds.in_range(&new_value) {
+            return Err(Error::InvalidFieldValue(format!(
+                "Value {:?} for {:?}.{:?} is outside the allowed range [{}, {}]",
+                new_value,
+                entity_id,
+                field_type,
+                bounds.min.map_or("-inf".to_string(), |min| min.to_string()),
+                bounds.max.map_or("inf".to_string(), |max| max.to_string()),
+            )));
+        }
+
+        // Replicated writes were checked where they were made
+        if write_time.is_none() {
+            let ft = self.ft.as_ref().unwrap();
+            if Some(field_type) == ft.name {
+                let parent_id = ft.parent.and_then(|ft_parent| self.fields.get(&(entity_id, ft_parent))).and_then(|field| field.value.as_entity_reference().cloned().flatten());
+                self.check_unique_name(entity_id, parent_id, new_value.as_string().unwrap_or_default())?;
+            } else if Some(field_type) == ft.parent {
+                if let Some(name) = ft.name.and_then(|ft_name| self.fields.get(&(entity_id, ft_name))).and_then(|field| field.value.as_string()) {
+                    self.check_unique_name(entity_id, new_value.as_entity_reference().cloned().flatten(), name)?;
+                }
+            }
+        }
+
+        if let Some(validator) = validator {
+            self.validate_write(&validator, entity_id, field_type, &old_value, &new_value)?;
+        }
+
+        // Every check passed, so the field can be added if the entity doesn't have it yet
+        self.invalidate_computed(field_type);
+        if self.is_trigger_type(entity_id.extract_type()) {
+            self.triggers = None;
+        }
+        // Local writes are stamped by the hybrid clock, if there is one
+        let local_time = self.now();
+        let hlc = match write_time {
+            None => self.hlc.as_mut().map(|hlc| hlc.now_at(local_time)),
+            Some(_) => None,
+        };
+        let field = Arc::make_mut(&mut self.fields)
+            .entry((entity_id, field_type))
+            .or_insert_with(|| Field {
+                field_type,
+                value: default_value.clone(),
+                write_time: local_time,
+                writer_id: None,
+            });
+
+        // Only update if the incoming write is newer or if no write_time is
+        // specified (local write), and for Changes only if the value differs
+        let incoming_time = write_time.or(hlc.map(|hlc| hlc.to_timestamp())).unwrap_or(local_time);
+        let newer = write_time.is_none() || incoming_time >= field.write_time;
+        if !newer || (push_condition == PushCondition::Changes && field.value == new_value) {
+            return Ok(());
+        }
+
+        field.value = new_value.clone();
+        field.write_time = incoming_time;
+        field.writer_id = writer_id.or(self.default_writer_id);
+        let (stamped_at, writer_id) = (field.write_time, field.writer_id);
+
+        // Trigger notifications after a write operation
+        let current_info = NotifyInfo {
+            entity_id,
+            field_path: crate::sfield![field_type],
+            value: Some(new_value.clone()),
+            timestamp: Some(stamped_at),
+            writer_id,
+        };
+        let previous_info = NotifyInfo {
+            entity_id,
+            field_path: crate::sfield![field_type],
+            value: Some(old_value.clone()),
+            timestamp: Some(stamped_at), // Use the time before the write
+            writer_id,
+        };
+
+        self.queue_write(WriteInfo::FieldUpdate {
+            entity_id,
+            field_type,
+            value: Some(new_value.clone()),
+            push_condition,
+            adjust_behavior,
+            write_time: Some(stamped_at),
+            writer_id,
+            delta,
+            triggered_by: self.active_trigger,
+            hlc,
+        });
+        reindex_references(&mut self.references, entity_id, field_type, referenced_ids(&old_value), referenced_ids(&new_value));
+        self.reindex_value(entity_id, field_type, Some(&old_value), Some(&new_value));
+        #[cfg(feature = "search")]
+        reindex_search(&mut self.search, entity_id, field_type, Some(&new_value));
+        if let Some((capacity, retention)) = series_limits {
+            let samples = Arc::make_mut(&mut self.series).entry((entity_id, field_type)).or_default();
+            series::append(samples, incoming_time, new_value.clone(), capacity, retention);
+        }
+
+        if write_time.is_none() {
+            self.advance_leader_token(entity_id, field_type, &old_value, &new_value)?;
+        }
+
+        self.trigger_notifications(
+            entity_id,
+            field_type,
+            current_info,
+            previous_info,
+        );
+
+        self.run_triggers(entity_id, field_type, old_value, new_value)
+    }
+
+    /// Run the triggers watching a field after it was written
+    fn run_triggers(&mut self, entity_id: EntityId, field_type: FieldType, old_value: Value, new_value: Value) -> Result<()> {
+        let matching = self.matching_triggers(entity_id, field_type);
+        if matching.is_empty() {
+            return Ok(());
+        }
+
+        if self.trigger_depth >= MAX_TRIGGER_DEPTH {
+            return Err(Error::ExecutionError(format!(
+                "Trigger recursion limit of {} exceeded at {:?}.{:?}",
+                MAX_TRIGGER_DEPTH, entity_id, field_type
+            )));
+        }
+
+        let mut params = HashMap::new();
+        params.insert("old".to_string(), old_value);
+        params.insert("new".to_string(), new_value);
+
+        for (trigger_id, trigger) in matching {
+            if let Some(condition) = &trigger.condition {
+                let result = self
+                    .cel_executors
+                    .with(|executor| executor.execute_with_params(condition, entity_id, &params, self))?;
+                if result != cel::Value::Bool(true) {
+                    continue;
+                }
+            }
+
+            match &trigger.action {
+                TriggerAction::Write { field_path, expression } => {
+                    let result = self
+                        .cel_executors
+                        .with(|executor| executor.execute_with_params(expression, entity_id, &params, self))?;
+                    let value = cel_value_to_value(result)?;
+
+                    let previous_trigger = self.active_trigger.replace(trigger_id);
+                    self.trigger_depth += 1;
+                    let result = self.write(entity_id, field_path, value, None, None, None, None);
+                    self.trigger_depth -= 1;
+                    self.active_trigger = previous_trigger;
+                    result?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Take a snapshot of the current store state
+    pub fn take_snapshot(&self) -> Snapshot {
+        let mut series: FxHashMap<EntityId, FxHashMap<FieldType, Samples>> = FxHashMap::default();
+        for ((entity_id, field_type), samples) in self.series.iter() {
+            series.entry(*entity_id).or_default().insert(*field_type, samples.clone());
+        }
+
+        Snapshot {
+            series,
+            checksum: Some(self.checksum()),
+            ..Snapshot::new(
+                (*self.schemas).clone(),
+                (*self.entities).clone(),
+                self.entity_type_interner.clone(),
+                self.field_type_interner.clone(),
+                self.get_fields(),
+            )
+        }
+    }
+
+    /// Content hashes of the store's state, per entity type and overall.
+    /// Matches the checksum of a snapshot taken now, so comparing it after a
+    /// restore or full sync confirms the store holds the snapshot's state.
+    pub fn checksum(&self) -> SnapshotChecksum {
+        crate::data::checksum::compute(
+            &self.schemas,
+            &self.entities,
+            |entity_type| {
+                self.entity_type_interner
+                    .resolve(entity_type.0 as u64)
+                    .cloned()
+                    .unwrap_or_else(|| entity_type.0.to_string())
+            },
+            self.fields.iter().map(|((entity_id, field_type), field)| (*entity_id, *field_type, field)),
+            self.series.iter().map(|((entity_id, field_type), samples)| (*entity_id, *field_type, samples)),
+        )
+    }
+
+    /// Entity counts, memory estimate and other size figures, see
+    /// `StoreTrait::stats`
+    pub fn stats(&self) -> StoreStats {
+        let type_name = |entity_type: EntityType| {
+            self.entity_type_interner
+                .resolve(entity_type.0 as u64)
+                .cloned()
+                .unwrap_or_else(|| entity_type.0.to_string())
+        };
+        let mut entity_counts: BTreeMap<String, usize> = self.schemas.keys().map(|entity_type| (type_name(*entity_type), 0)).collect();
+        for (entity_type, entities) in self.entities.iter() {
+            *entity_counts.entry(type_name(*entity_type)).or_default() += entities.len();
+        }
+
+        let sample_size = std::mem::size_of::<(Timestamp, Value)>();
+        let memory_estimate = self.fields.values().map(stats::field_size).sum::<usize>()
+            + self
+                .series
+                .values()
+                .flat_map(|samples| samples.iter())
+                .map(|(_, value)| sample_size + stats::heap_size(value))
+                .sum::<usize>()
+            + self.entities.values().map(|entities| entities.len() * std::mem::size_of::<EntityId>()).sum::<usize>()
+            + self.schemas.values().map(|schema| schema.fields.len() * std::mem::size_of::<FieldSchema>()).sum::<usize>();
+
+        let registrations = |fields: &FxHashMap<FieldType, FxHashMap<NotifyConfig, Vec<NotificationQueue>>>| -> usize {
+            fields.values().flat_map(|configs| configs.values()).map(|queues| queues.len()).sum()
+        };
+
+        StoreStats {
+            entity_counts,
+            field_count: self.fields.len(),
+            memory_estimate,
+            entity_notifications: self.id_notifications.values().map(registrations).sum(),
+            type_notifications: self.type_notifications.values().map(registrations).sum(),
+            entity_type_names: self.entity_type_interner.len(),
+            field_type_names: self.field_type_interner.len(),
+        }
+    }
+
+    /// Restore the store state from a snapshot
+    pub fn restore_snapshot(&mut self, snapshot: Snapshot) {
+        self.schemas = Arc::new(snapshot.schemas);
+        self.entities = Arc::new(snapshot.entities);
+        self.entity_type_interner = snapshot.entity_type_interner;
+        self.field_type_interner = snapshot.field_type_interner;
+
+        // Re-initialize ET and FT after restoring snapshot data
+        self.et = Some(ET::new(self));
+        self.ft = Some(FT::new(self));
+
+        // Convert nested fields structure to flattened structure
+        let mut fields = FxHashMap::default();
+        for (entity_id, entity_fields) in snapshot.fields {
+            for (field_type, field) in entity_fields {
+                fields.insert((entity_id, field_type), field);
+            }
+        }
+        self.fields = Arc::new(fields);
+        self.series = Arc::new(
+            snapshot
+                .series
+                .into_iter()
+                .flat_map(|(entity_id, samples)| samples.into_iter().map(move |(field_type, samples)| ((entity_id, field_type), samples)))
+                .collect(),
+        );
+        self.rebuild_reference_index();
+        self.value_index.write().unwrap().clear();
+        // Reindexed once the schemas say which fields are searchable
+        #[cfg(feature = "search")]
+        {
+            self.search = Arc::default();
+        }
+
+        // Tombstones aren't part of a snapshot
+        self.tombstones = Arc::default();
+
+        // Clear the cache since schema structure may have changed
+        self.complete_entity_schema_cache.clear();
+
+        // Rebuild inheritance map after restoring (this will also rebuild the cache)
+        self.rebuild_inheritance_map();
+    }
+
+    /// Start a read-only view of the store as it is now.
+    ///
+    /// This is cheap: the view shares the store's data, and a write only
+    /// copies a table the first time it changes it while a view is alive.
+    /// Long reads (serializing a snapshot, paging through a large find) can
+    /// run against the view, on another thread if need be, without holding
+    /// up writers or seeing their changes half way through.
+    pub fn begin_read_snapshot(&self) -> ReadSnapshot {
+        ReadSnapshot(Store {
+            schemas: Arc::clone(&self.schemas),
+            entities: Arc::clone(&self.entities),
+            fields: Arc::clone(&self.fields),
+            references: Arc::clone(&self.references),
+            tombstones: Arc::clone(&self.tombstones),
+            series: Arc::clone(&self.series),
+            #[cfg(feature = "search")]
+            search: Arc::clone(&self.search),
+            entity_type_interner: self.entity_type_interner.clone(),
+            field_type_interner: self.field_type_interner.clone(),
+            et: self.et.clone(),
+            ft: self.ft.clone(),
+            inheritance_map: self.inheritance_map.clone(),
+            complete_entity_schema_cache: self.complete_entity_schema_cache.clone(),
+            cel_executors: CelExecutorPool::default(),
+            computed_cache: RwLock::new(FxHashMap::default()),
+            computed_dependents: self.computed_dependents.clone(),
+            computed_fields: self.computed_fields.clone(),
+            client_context: self.client_context.clone(),
+            default_writer_id: self.default_writer_id,
+            notifications_disabled: true,
+            triggers_disabled: true,
+            time: self.time,
+            ..Store::new()
+        })
+    }
+
+    /// Export the entity and field type ids assigned so far
+    pub fn export_type_ids(&self) -> TypeIdMapping {
+        TypeIdMapping {
+            entity_types: self.entity_type_interner.export(),
+            field_types: self.field_type_interner.export(),
+        }
+    }
+
+    /// Pin type names to previously exported ids, so a store rebuilt from
+    /// schemas (rather than a binary snapshot) hands out the same ids as
+    /// before. Must be called before the names are interned any other way;
+    /// a mapping that disagrees with the ids already assigned is rejected
+    /// with `Error::TypeIdConflict` and nothing is changed.
+    pub fn import_type_ids(&mut self, mapping: &TypeIdMapping) -> Result<()> {
+        let mut entity_type_interner = self.entity_type_interner.clone();
+        let mut field_type_interner = self.field_type_interner.clone();
+        entity_type_interner.import(&mapping.entity_types)?;
+        field_type_interner.import(&mapping.field_types)?;
+
+        self.entity_type_interner = entity_type_interner;
+        self.field_type_interner = field_type_interner;
+        self.et = Some(ET::new(self));
+        self.ft = Some(FT::new(self));
+        self.rebuild_inheritance_map();
+        Ok(())
+    }
+
+    /// Assign ids to a schema's entity type and fields without applying the
+    /// schema itself. Names that already have an id keep it; new field names
+    /// are interned in sorted order so the result doesn't depend on map
+    /// iteration order. Inherited types must already exist.
+    pub fn ensure_ids(
+        &mut self,
+        schema: &EntitySchema<Single, String, String>,
+    ) -> Result<(EntityType, FxHashMap<String, FieldType>)> {
+        for parent in schema.inherit.iter() {
+            self.entity_type_interner
+                .get(parent.as_str())
+                .ok_or_else(|| Error::EntityTypeStrNotFound(parent.clone()))?;
+        }
+
+        let entity_type = EntityType(
+            self.entity_type_interner
+                .intern(schema.entity_type.as_str()) as u32,
+        );
+
+        let mut field_names: Vec<&String> = schema.fields.keys().collect();
+        field_names.sort();
+        let field_types = field_names
+            .into_iter()
+            .map(|name| (name.clone(), FieldType(self.field_type_interner.intern(name.as_str()))))
+            .collect();
+
+        Ok((entity_type, field_types))
+    }
+
+    /// Rebuild the inheritance map for fast lookup of derived types
+    /// This should be called whenever schemas are added or updated
+    fn rebuild_inheritance_map(&mut self) {
+        self.inheritance_map.clear();
+        // Clear the complete entity schema cache since inheritance relationships may have changed
+        self.complete_entity_schema_cache.clear();
+
+        // For each entity type, find all types that inherit from it
+        for entity_type in self
+            .entity_type_interner
+            .ids()
+            .map(|id| EntityType(id as u32))
+        {
+            let mut derived_types = Vec::new();
+
+            // Check all other types to see if they inherit from this type
+            for other_type in self
+                .entity_type_interner
+                .ids()
+                .map(|id| EntityType(id as u32))
+            {
+                if self.inherits_from(other_type.clone(), entity_type.clone()) {
+                    derived_types.push(other_type.clone());
+                }
+            }
+
+            // Include the type itself in the list
+            derived_types.push(entity_type.clone());
+
+            self.inheritance_map
+                .insert(entity_type.clone(), derived_types);
+        }
+
+        // Rebuild the complete entity schema cache
+        self.rebuild_complete_entity_schema_cache();
+    }
+
+    /// Rebuild the complete entity schema cache for all entity types
+    /// This should be called after inheritance map changes or schema updates
+    fn rebuild_complete_entity_schema_cache(&mut self) {
+        self.complete_entity_schema_cache.clear();
+        self.computed_dependents.clear();
+        self.computed_cache.write().unwrap().clear();
+        self.triggers = None;
+
+        // Build complete schemas for all entity types
+        for entity_type in self
+            .entity_type_interner
+            .ids()
+            .map(|id| EntityType(id as u32))
+        {
+            if let Ok(complete_schema) = self.build_complete_entity_schema(entity_type.clone()) {
+                self.complete_entity_schema_cache
+                    .insert(entity_type.clone(), complete_schema);
+            }
+        }
+
+        self.computed_fields = self
+            .complete_entity_schema_cache
+            .values()
+            .flat_map(|schema| schema.fields.values())
+            .filter(|field_schema| field_schema.is_computed())
+            .map(|field_schema| field_schema.field_type())
+            .collect();
+        let computed_fields = &self.computed_fields;
+        self.value_index.write().unwrap().retain(|field_type, _| !computed_fields.contains(field_type));
+
+        self.rebuild_computed_dependents();
+        #[cfg(feature = "search")]
+        self.rebuild_search_index();
+    }
+
+    /// Reindex every searchable field if the set of searchable fields changed
+    #[cfg(feature = "search")]
+    fn rebuild_search_index(&mut self) {
+        let searchable: FxHashSet<(EntityType, FieldType)> = self
+            .complete_entity_schema_cache
+            .iter()
+            .flat_map(|(entity_type, schema)| {
+                schema
+                    .fields
+                    .iter()
+                    .filter(|(_, field_schema)| field_schema.is_searchable())
+                    .map(|(field_type, _)| (*entity_type, *field_type))
+            })
+            .collect();
+        if &searchable == self.search.searchable_fields() {
+            return;
+        }
+
+        let mut index = SearchIndex::default();
+        index.rebuild(searchable, self.fields.iter().map(|((entity_id, field_type), field)| (*entity_id, *field_type, &field.value)));
+        self.search = Arc::new(index);
+    }
+
+    /// Entities of a type (including derived types) whose searchable String
+    /// fields match `query`, in id order.
+    ///
+    /// A query is a list of terms that must all match: words, `prefix*`
+    /// and `"quoted phrases"`, compared case-insensitively. Only fields
+    /// marked `searchable` in their metadata are indexed.
+    #[cfg(feature = "search")]
+    pub fn search(&self, entity_type: EntityType, query: &str) -> Result<Vec<EntityId>> {
+        let types = self.inheritance_map.get(&entity_type).map(|types| types.as_slice()).unwrap_or_default();
+        Ok(self
+            .search
+            .search(query)?
+            .into_iter()
+            .filter(|entity_id| types.contains(&entity_id.extract_type()))
+            .sorted()
+            .collect())
+    }
+
+    /// The samples of a Series field taken between `from` and `to` (both
+    /// inclusive), oldest first, optionally averaged over `downsample`
+    /// intervals.
+    ///
+    /// Every write appends a sample, stamped with its write time; the
+    /// history starts empty when the entity is created and only keeps the
+    /// latest sample through a soft delete.
+    pub fn read_series(&self, entity_id: EntityId, field_type: FieldType, from: Option<Timestamp>, to: Option<Timestamp>, downsample: Option<Duration>) -> Result<Vec<(Timestamp, Value)>> {
+        if !self.entity_exists(entity_id) {
+            return Err(Error::EntityNotFound(entity_id));
+        }
+        let field_schema = self
+            .get_complete_entity_schema(entity_id.extract_type())?
+            .fields
+            .get(&field_type)
+            .ok_or(Error::FieldTypeNotFound(entity_id, field_type))?;
+        if field_schema.series_limits().is_none() {
+            return Err(Error::InvalidRequest(format!("Field {:?} of {:?} is not a series", field_type, entity_id)));
+        }
+
+        match self.series.get(&(entity_id, field_type)) {
+            Some(samples) => series::select(samples, from, to, downsample),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Record which field types each computed field reads, including every
+    /// field along an indirection path (e.g. `Parent->Power` depends on both
+    /// `Parent` and `Power`)
+    fn rebuild_computed_dependents(&mut self) {
+        let computed: Vec<(FieldType, String)> = self
+            .complete_entity_schema_cache
+            .values()
+            .flat_map(|schema| schema.fields.values())
+            .filter_map(|field_schema| {
+                field_schema
+                    .expression()
+                    .map(|expression| (field_schema.field_type(), expression.to_string()))
+            })
+            .collect();
+
+        let mut dependents: FxHashMap<FieldType, Vec<FieldType>> = FxHashMap::default();
+        self.cel_executors.with(|executor| {
+            for (computed_field, expression) in computed {
+                // Expressions that don't compile simply fail when read
+                let Ok(paths) = executor.referenced_fields(&expression, self) else {
+                    continue;
+                };
+
+                for dependency in paths.into_iter().flatten() {
+                    let entry = dependents.entry(dependency).or_default();
+                    if !entry.contains(&computed_field) {
+                        entry.push(computed_field);
+                    }
+                }
+            }
+        });
+
+        self.computed_dependents = dependents;
+    }
+
+    /// Drop cached values of every computed field that (transitively) reads the given field
+    fn invalidate_computed(&self, field_type: FieldType) {
+        if self.computed_dependents.is_empty() {
+            return;
+        }
+
+        let mut stale = Vec::new();
+        let mut pending = vec![field_type];
+        while let Some(field_type) = pending.pop() {
+            for dependent in self.computed_dependents.get(&field_type).into_iter().flatten() {
+                if !stale.contains(dependent) {
+                    stale.push(*dependent);
+                    pending.push(*dependent);
                 }
             }
+        }
 
-            // Include the type itself in the list
-            derived_types.push(entity_type.clone());
+        if !stale.is_empty() {
+            self.computed_cache
+                .write()
+                .unwrap()
+                .retain(|(_, field_type), _| !stale.contains(field_type));
+        }
+    }
 
-            self.inheritance_map
-                .insert(entity_type.clone(), derived_types);
+    /// Entities whose value of `field_path` could equal `value`, looked up
+    /// in the value index of the last field and followed back through the
+    /// reference index for the fields before it. None if the field can't be
+    /// indexed, in which case every entity has to be checked.
+    ///
+    /// The result may hold entities of any type, and is exact for direct
+    /// fields. Through an entity list it may hold entities that don't match.
+    pub(crate) fn entities_with_value(&self, field_path: &[FieldType], value: &Value) -> Option<Vec<EntityId>> {
+        let (field_type, hops) = field_path.split_last()?;
+        let key = IndexKey::of(value)?;
+        // Computed fields aren't stored, so there is nothing to index
+        if field_path.iter().any(|ft| self.computed_fields.contains(ft)) {
+            return None;
         }
 
-        // Rebuild the complete entity schema cache
-        self.rebuild_complete_entity_schema_cache();
+        let index = self.value_index(*field_type)?;
+        let mut found: Vec<EntityId> = index.get(&key).into_iter().flatten().copied().collect();
+        for hop in hops.iter().rev() {
+            found = found
+                .iter()
+                .flat_map(|target| self.references.get(target).into_iter().flatten())
+                .filter(|(_, referrer_field)| referrer_field == hop)
+                .map(|(referrer, _)| *referrer)
+                .collect::<FxHashSet<_>>()
+                .into_iter()
+                .collect();
+        }
+        found.sort();
+        Some(found)
     }
 
-    /// Rebuild the complete entity schema cache for all entity types
-    /// This should be called after inheritance map changes or schema updates
-    fn rebuild_complete_entity_schema_cache(&mut self) {
-        self.complete_entity_schema_cache.clear();
+    /// The value index of a field, built from the stored fields if it isn't yet
+    fn value_index(&self, field_type: FieldType) -> Option<Arc<ValueIndex>> {
+        if let Some(index) = self.value_index.read().unwrap().get(&field_type) {
+            return index.clone();
+        }
 
-        // Build complete schemas for all entity types
-        for entity_type in self
-            .entity_type_interner
-            .ids()
-            .map(|id| EntityType(id as u32))
-        {
-            if let Ok(complete_schema) = self.build_complete_entity_schema(entity_type.clone()) {
-                self.complete_entity_schema_cache
-                    .insert(entity_type.clone(), complete_schema);
+        self.value_index
+            .write()
+            .unwrap()
+            .entry(field_type)
+            .or_insert_with(|| {
+                let mut index = ValueIndex::default();
+                for ((entity_id, ft), field) in self.fields.iter() {
+                    if *ft == field_type {
+                        index.entry(IndexKey::of(&field.value)?).or_default().insert(*entity_id);
+                    }
+                }
+                Some(Arc::new(index))
+            })
+            .clone()
+    }
+
+    /// Move an entity to the entry of its new value in a field's value
+    /// index, if the index has been built. `None` stands for a field the
+    /// entity doesn't have (any more).
+    fn reindex_value(&self, entity_id: EntityId, field_type: FieldType, old: Option<&Value>, new: Option<&Value>) {
+        let mut value_index = self.value_index.write().unwrap();
+        let Some(Some(index)) = value_index.get_mut(&field_type) else {
+            return;
+        };
+        let (old, new) = (old.map(IndexKey::of), new.map(IndexKey::of));
+        if old == new {
+            return;
+        }
+        if new == Some(None) {
+            // The field now holds a value that can't be indexed
+            value_index.insert(field_type, None);
+            return;
+        }
+
+        let index = Arc::make_mut(index);
+        if let Some(Some(old)) = old {
+            if let Some(entities) = index.get_mut(&old) {
+                entities.remove(&entity_id);
+                if entities.is_empty() {
+                    index.remove(&old);
+                }
             }
         }
+        if let Some(Some(new)) = new {
+            index.entry(new).or_default().insert(entity_id);
+        }
+    }
+
+    /// Evaluate a computed field, returning the cached value if its dependencies haven't changed
+    fn read_computed(&self, entity_id: EntityId, field_type: FieldType, expression: &str) -> Result<(Value, Timestamp, Option<EntityId>)> {
+        if let Some((value, timestamp)) = self.computed_cache.read().unwrap().get(&(entity_id, field_type)) {
+            return Ok((value.clone(), *timestamp, None));
+        }
+
+        let result = self.cel_executors.with(|executor| executor.execute(expression, entity_id, self));
+        let value = cel_value_to_value(result?)?;
+        let timestamp = self.now();
+
+        self.computed_cache
+            .write()
+            .unwrap()
+            .insert((entity_id, field_type), (value.clone(), timestamp));
+
+        Ok((value, timestamp, None))
     }
 
     /// Check if derived_type inherits from base_type (directly or indirectly)
@@ -1216,6 +3790,25 @@ impl Store {
         ))
     }
 
+    /// Check a write against the notification's filter, if it has one.
+    /// `new` and `old` are bound to the written field's values.
+    fn passes_notify_filter(&self, config: &NotifyConfig, entity_id: EntityId, current_info: &NotifyInfo, previous_info: &NotifyInfo) -> bool {
+        let Some(filter) = config.filter() else {
+            return true;
+        };
+
+        let mut params = HashMap::new();
+        if let Some(value) = &current_info.value {
+            params.insert("new".to_string(), value.clone());
+        }
+        if let Some(value) = &previous_info.value {
+            params.insert("old".to_string(), value.clone());
+        }
+
+        let result = self.cel_executors.with(|executor| executor.execute_with_params(filter, entity_id, &params, self));
+        matches!(result, Ok(cel::Value::Bool(true)))
+    }
+
     /// Trigger notifications for a write operation
     fn trigger_notifications(
         &mut self,
@@ -1253,7 +3846,7 @@ impl Store {
                             true // Always trigger on write
                         };
 
-                        if should_notify {
+                        if should_notify && self.passes_notify_filter(config, entity_id, &current_info, &previous_info) {
                             notifications_to_trigger.push((config.clone(), context.clone()));
                         }
                     }
@@ -1288,7 +3881,7 @@ impl Store {
                                 true // Always trigger on write
                             };
 
-                            if should_notify {
+                            if should_notify && self.passes_notify_filter(config, entity_id, &current_info, &previous_info) {
                                 notifications_to_trigger.push((config.clone(), context.clone()));
                             }
                         }
@@ -1319,8 +3912,14 @@ impl Store {
                         if let Some(queue_map) = field_map.get_mut(config_field_type) {
                             if let Some(queues) = queue_map.get_mut(&config) {
                                 for queue in queues.iter() {
-                                    queue.push(notification.clone());
+                                    match self.held_notifications.as_mut() {
+                                        Some(held) => held.push((queue.clone(), notification.clone())),
+                                        None => {
+                                            queue.push(notification.clone());
+                                        }
+                                    }
                                 }
+                                queues.retain(|queue| !queue.is_disconnected());
                             }
                         }
                     }
@@ -1335,8 +3934,14 @@ impl Store {
                             if let Some(queues) = queue_map.get_mut(&config) {
                                 // Send to all senders for this config
                                 for queue in queues.iter() {
-                                    queue.push(notification.clone());
+                                    match self.held_notifications.as_mut() {
+                                        Some(held) => held.push((queue.clone(), notification.clone())),
+                                        None => {
+                                            queue.push(notification.clone());
+                                        }
+                                    }
                                 }
+                                queues.retain(|queue| !queue.is_disconnected());
                             }
                         }
                     }
@@ -1425,6 +4030,13 @@ impl StoreTrait for Store {
         
         if let Some(field) = self.fields.get(&field_key) {
             Ok((field.value.clone(), field.write_time, field.writer_id))
+        } else if let Some(expression) = self
+            .get_complete_entity_schema(resolved_entity_id.extract_type())
+            .ok()
+            .and_then(|schema| schema.fields.get(&resolved_field_type))
+            .and_then(|field_schema| field_schema.expression())
+        {
+            self.read_computed(resolved_entity_id, resolved_field_type, expression)
         } else {
             // Try to provide a more helpful error message
             let field_name = self.resolve_field_type(resolved_field_type)
@@ -1442,243 +4054,16 @@ impl StoreTrait for Store {
 
     fn write(&mut self, entity_id: EntityId, field_path: &[FieldType], value: Value, writer_id: Option<EntityId>, write_time: Option<Timestamp>, push_condition: Option<PushCondition>, adjust_behavior: Option<AdjustBehavior>) -> Result<()> {
         let (entity_id, field_type) = self.resolve_indirection(entity_id, field_path)?;
-        let push_condition = push_condition.unwrap_or(PushCondition::Always);
-        let adjust_behavior = adjust_behavior.unwrap_or(AdjustBehavior::Set);
-
-        // Get the schema from cache (should be populated by rebuild_complete_entity_schema_cache())
-        let entity_schema = self.get_complete_entity_schema(entity_id.extract_type())?;
-        let default_value = {
-            let field_schema = entity_schema
-                .fields
-                .get(&field_type)
-                .ok_or_else(|| Error::FieldTypeNotFound(entity_id, field_type))?;
-            field_schema.default_value()
-        };
-
-        let field = self
-            .fields
-            .entry((entity_id, field_type))
-            .or_insert_with(|| Field {
-                field_type: field_type,
-                value: default_value.clone(),
-                write_time: now(),
-                writer_id: None,
-            });
-
-        let old_value = field.value.clone();
-        // Check that the value being written is the same type as the field schema
-        // If the value is None, use the default value from the schema
-        if discriminant(&value) != discriminant(&default_value) {
-            return Err(Error::ValueTypeMismatch(
-                entity_id,
-                field_type,
-                default_value,
-                value.clone(),
-            ));
-        }
-
-        let mut new_value = value.clone();
-
-        match adjust_behavior {
-            AdjustBehavior::Add => match &old_value {
-                Value::Int(old_int) => {
-                    new_value = Value::Int(old_int + new_value.as_int().unwrap_or(0));
-                }
-                Value::Float(old_float) => {
-                    new_value = Value::Float(old_float + new_value.as_float().unwrap_or(0.0));
-                }
-                Value::EntityReference(old_ref) => {
-                    if old_ref.is_some() {
-                        // prefer the old value if old value exists
-                        new_value = old_value.clone();
-                    }
-                    // otherwise just use the new value (which could be None or Some)
-                }
-                Value::EntityList(old_list) => {
-                    new_value = Value::EntityList(
-                        old_list
-                            .iter()
-                            .chain(new_value.as_entity_list().unwrap_or(&Vec::new()).iter())
-                            .unique()
-                            .cloned()
-                            .collect(),
-                    );
-                }
-                Value::String(old_string) => {
-                    new_value = Value::String(format!(
-                        "{}{}",
-                        old_string,
-                        new_value.as_string().unwrap_or_default()
-                    ).into());
-                }
-                Value::Blob(old_file) => {
-                    let combined_vec: Vec<u8> = old_file
-                        .iter()
-                        .chain(new_value.as_blob().unwrap_or(&[]).iter())
-                        .cloned()
-                        .collect();
-                    new_value = Value::Blob(combined_vec.into());
-                }
-                _ => {
-                    return Err(Error::UnsupportedAdjustBehavior(
-                        entity_id,
-                        field_type,
-                        adjust_behavior.clone(),
-                    ));
-                }
-            },
-            AdjustBehavior::Subtract => match &old_value {
-                Value::Int(old_int) => {
-                    new_value = Value::Int(old_int - new_value.as_int().unwrap_or(0));
-                }
-                Value::Float(old_float) => {
-                    new_value = Value::Float(old_float - new_value.as_float().unwrap_or(0.0));
-                }
-                Value::EntityReference(old_ref) => {
-                    if let Some(old_id) = old_ref {
-                        if let Some(new_id) = new_value.as_entity_reference().unwrap_or(&None) {
-                            if old_id == new_id {
-                                // If the new value matches the old value, set to None
-                                new_value = Value::EntityReference(None);
-                            } else {
-                                // Otherwise, keep the old value
-                                new_value = old_value.clone();
-                            }
-                        }
-                    }
-                }
-                Value::EntityList(old_list) => {
-                    let new_list = new_value.as_entity_list().cloned().unwrap_or_default();
-                    new_value = Value::EntityList(
-                        old_list
-                            .iter()
-                            .filter(|item| !new_list.contains(item))
-                            .cloned()
-                            .collect(),
-                    );
-                }
-                _ => {
-                    return Err(Error::UnsupportedAdjustBehavior(
-                        entity_id,
-                        field_type,
-                        adjust_behavior.clone(),
-                    ));
-                }
-            },
-            _ => {
-                // No adjustment needed
-            }
-        }
-
-        // Store values for notification before updating the field
-        let notification_new_value = new_value.clone();
-        let notification_old_value = old_value.clone();
-
-        match push_condition {
-            PushCondition::Always => {
-                // Only update if the incoming write is newer or if no write_time is specified (local write)
-                let incoming_time = write_time.unwrap_or_else(|| now());
-                if write_time.is_none() || incoming_time >= field.write_time {
-                    field.value = new_value;
-                    field.write_time = incoming_time;
-                    if let Some(writer_id) = writer_id {
-                        field.writer_id = Some(writer_id.clone());
-                    } else {
-                        field.writer_id = self.default_writer_id.clone();
-                    }
-
-                    // Trigger notifications after a write operation
-                    let current_info = NotifyInfo {
-                        entity_id,
-                        field_path: crate::sfield![field_type],
-                        value: Some(notification_new_value.clone()),
-                        timestamp: Some(field.write_time),
-                        writer_id: field.writer_id.clone(),
-                    };
-                    let previous_info = NotifyInfo {
-                        entity_id,
-                        field_path: crate::sfield![field_type],
-                        value: Some(notification_old_value.clone()),
-                        timestamp: Some(field.write_time), // Use the time before the write
-                        writer_id: field.writer_id.clone(),
-                    };
-
-                    self.write_queue.push_back(WriteInfo::FieldUpdate {
-                        entity_id,
-                        field_type,
-                        value: Some(notification_new_value.clone()),
-                        push_condition,
-                        adjust_behavior,
-                        write_time: Some(field.write_time),
-                        writer_id: field.writer_id.clone(),
-                    });
 
-                    self.trigger_notifications(
-                        entity_id,
-                        field_type,
-                        current_info,
-                        previous_info,
-                    );
-
-                } else {
-                    // Incoming write is older, ignore it
-                    return Ok(());
-                }
-            }
-            PushCondition::Changes => {
-                // Changes write, only update if the value is different AND the write is newer
-                let incoming_time = write_time.unwrap_or_else(|| now());
-                if (write_time.is_none() || incoming_time >= field.write_time)
-                    && field.value != new_value
-                {
-                    field.value = new_value;
-                    field.write_time = incoming_time;
-                    if let Some(writer_id) = writer_id {
-                        field.writer_id = Some(writer_id.clone());
-                    } else {
-                        field.writer_id = self.default_writer_id.clone();
-                    }
-
-                    // Trigger notifications after a write operation
-                    let current_info = NotifyInfo {
-                        entity_id: entity_id,
-                        field_path: crate::sfield![field_type],
-                        value: Some(notification_new_value.clone()),
-                        timestamp: Some(field.write_time),
-                        writer_id: field.writer_id.clone(),
-                    };
-                    let previous_info = NotifyInfo {
-                        entity_id: entity_id,
-                        field_path: crate::sfield![field_type],
-                        value: Some(notification_old_value.clone()),
-                        timestamp: Some(field.write_time), // Use the time before the write
-                        writer_id: field.writer_id.clone(),
-                    };
-
-                    self.write_queue.push_back(WriteInfo::FieldUpdate {
-                        entity_id,
-                        field_type,
-                        value: Some(notification_new_value.clone()),
-                        push_condition,
-                        adjust_behavior,
-                        write_time: Some(field.write_time),
-                        writer_id: field.writer_id.clone(),
-                    });
-
-                    self.trigger_notifications(
-                        entity_id,
-                        field_type,
-                        current_info,
-                        previous_info,
-                    );
-                } else if write_time.is_some() && incoming_time < field.write_time {
-                    // Incoming write is older, ignore it
-                    return Ok(());
-                }
-            }
+        // A write that sets off triggers lands together with every write they
+        // cascade into, or not at all. Writing to a `Trigger` entity can change
+        // which triggers match, so those writes are wrapped too.
+        if self.trigger_depth == 0
+            && (self.is_trigger_type(entity_id.extract_type()) || !self.matching_triggers(entity_id, field_type).is_empty())
+        {
+            return self.atomically(|store| store.write_field(entity_id, field_type, value, writer_id, write_time, push_condition, adjust_behavior));
         }
-
-        Ok(())
+        self.write_field(entity_id, field_type, value, writer_id, write_time, push_condition, adjust_behavior)
     }
 
     fn create_entity(&mut self, entity_type: EntityType, parent_id: Option<EntityId>, name: &str) -> Result<EntityId> {
@@ -1689,35 +4074,68 @@ impl StoreTrait for Store {
         Ok(created_entity_id)
     }
 
-    fn delete_entity(&mut self, entity_id: EntityId) -> Result<()> {
-        self.delete_entity_internal(entity_id)?;
+    fn rename_entity(&mut self, entity_id: EntityId, new_name: &str) -> Result<()> {
+        let ft = self.ft.as_ref().unwrap();
+        let (ft_name, ft_parent, ft_children) = match (ft.name, ft.parent, ft.children) {
+            (Some(ft_name), Some(ft_parent), Some(ft_children)) => (ft_name, ft_parent, ft_children),
+            _ => return Err(Error::InvalidRequest("Name, Parent and Children field types are not defined".to_string())),
+        };
 
-        self.write_queue.push_back(WriteInfo::DeleteEntity {
-            entity_id,
-            timestamp: now(),
-        });
+        let (old_name, _, _) = self.read(entity_id, &[ft_name])?;
+        if old_name.as_string() == Some(new_name) {
+            return Ok(());
+        }
+        self.write(entity_id, &[ft_name], Value::String(new_name.to_string()), None, None, None, None)?;
 
+        // Tell whoever watches the parent's children that one of them changed name
+        let (parent, _, _) = self.read(entity_id, &[ft_parent])?;
+        if let Some(parent_id) = parent.as_entity_reference().copied().flatten() {
+            let (_, write_time, writer_id) = self.read(entity_id, &[ft_name])?;
+            let info = |value: Value| NotifyInfo {
+                entity_id,
+                field_path: crate::sfield![ft_name],
+                value: Some(value),
+                timestamp: Some(write_time),
+                writer_id,
+            };
+            self.trigger_notifications(parent_id, ft_children, info(Value::String(new_name.to_string())), info(old_name));
+        }
         Ok(())
     }
 
-    fn update_schema(&mut self, schema: EntitySchema<Single, String, String>) -> Result<()> {
-        // Validate whether inherited entity types exist or not:
-        for parent in schema.inherit.iter() {
-            self.entity_type_interner
-                .get(parent.as_str())
-                .ok_or_else(|| Error::EntityTypeStrNotFound(parent.clone()))?;
-        }
+    fn delete_entity(&mut self, entity_id: EntityId) -> Result<()> {
+        // The OnDelete writes and the deletes succeed or fail together
+        self.atomically(|store| {
+            let cascaded = if store.entity_exists(entity_id) {
+                store.apply_on_delete(entity_id)?
+            } else {
+                Vec::new()
+            };
 
-        // Get or create the entity type if it doesn't exist
-        let entity_type = EntityType(
-            self.entity_type_interner
-                .intern(schema.entity_type.as_str()) as u32,
-        );
+            store.delete_entity_internal(entity_id)?;
+            store.queue_write(WriteInfo::DeleteEntity {
+                entity_id,
+                timestamp: store.now(),
+            });
 
-        // Intern all field types before converting the schema
-        for field_name in schema.fields.keys() {
-            self.field_type_interner.intern(field_name.as_str());
-        }
+            for cascaded_id in cascaded {
+                // May have gone already as part of another deleted subtree
+                if store.entity_exists(cascaded_id) {
+                    store.delete_entity_internal(cascaded_id)?;
+                    store.queue_write(WriteInfo::DeleteEntity {
+                        entity_id: cascaded_id,
+                        timestamp: store.now(),
+                    });
+                }
+            }
+
+            Ok(())
+        })
+    }
+
+    fn update_schema(&mut self, schema: EntitySchema<Single, String, String>) -> Result<()> {
+        // Get or create the entity and field types before converting the schema
+        let (entity_type, _) = self.ensure_ids(&schema)?;
 
         let schema = EntitySchema::<Single>::from_string_schema(schema.clone(), self);
 
@@ -1727,10 +4145,10 @@ impl StoreTrait for Store {
             .map(|schema| schema.clone())
             .unwrap_or_else(|_| EntitySchema::<Complete>::new(entity_type.clone()));
 
-        self.schemas.insert(entity_type.clone(), schema.clone());
+        Arc::make_mut(&mut self.schemas).insert(entity_type.clone(), schema.clone());
 
         if !self.entities.contains_key(&entity_type) {
-            self.entities.insert(entity_type.clone(), SortedVec::new());
+            Arc::make_mut(&mut self.entities).insert(entity_type.clone(), SortedVec::new());
         }
 
         // Clear the complete entity schema cache since a schema was updated
@@ -1748,11 +4166,20 @@ impl StoreTrait for Store {
                 .unwrap_or(&SortedVec::new())
             {
                 let field_key = (*entity_id, removed_field.field_type().clone());
-                self.fields.remove(&field_key);
+                if let Some(field) = Arc::make_mut(&mut self.fields).remove(&field_key) {
+                    self.reindex_value(field_key.0, field_key.1, Some(&field.value), None);
+                    reindex_references(&mut self.references, field_key.0, field_key.1, referenced_ids(&field.value), &[]);
+                    #[cfg(feature = "search")]
+                    reindex_search(&mut self.search, field_key.0, field_key.1, None);
+                }
+                if self.series.contains_key(&field_key) {
+                    Arc::make_mut(&mut self.series).remove(&field_key);
+                }
             }
         }
 
-        for added_field in complete_new_schema.diff(&complete_old_schema) {
+        let timestamp = self.now();
+        for added_field in complete_new_schema.diff(&complete_old_schema).into_iter().filter(|fs| !fs.is_computed()) {
             // If the field was added, we need to add it to all entities
             for entity_id in self
                 .entities
@@ -1760,12 +4187,16 @@ impl StoreTrait for Store {
                 .unwrap_or(&SortedVec::new())
             {
                 let field_key = (*entity_id, added_field.field_type().clone());
-                self.fields.insert(
+                reindex_references(&mut self.references, field_key.0, field_key.1, &[], referenced_ids(&added_field.default_value()));
+                #[cfg(feature = "search")]
+                reindex_search(&mut self.search, field_key.0, field_key.1, Some(&added_field.default_value()));
+                self.reindex_value(field_key.0, field_key.1, None, Some(&added_field.default_value()));
+                Arc::make_mut(&mut self.fields).insert(
                     field_key,
                     Field {
                         field_type: added_field.field_type().clone(),
                         value: added_field.default_value(),
-                        write_time: now(),
+                        write_time: timestamp,
                         writer_id: None,
                     },
                 );
@@ -1778,9 +4209,9 @@ impl StoreTrait for Store {
         // Rebuild inheritance map after schema changes
         self.rebuild_inheritance_map();
 
-        self.write_queue.push_back(WriteInfo::SchemaUpdate {
+        self.queue_write(WriteInfo::SchemaUpdate {
             schema,
-            timestamp: now(),
+            timestamp: self.now(),
         });
 
         Ok(())
@@ -1790,6 +4221,10 @@ impl StoreTrait for Store {
         self.take_snapshot()
     }
 
+    fn stats(&self) -> Result<StoreStats> {
+        Ok(self.stats())
+    }
+
     fn find_entities_paginated(
         &self,
         entity_type: EntityType,
@@ -1816,6 +4251,10 @@ impl StoreTrait for Store {
         self.find_entities(entity_type, filter)
     }
 
+    fn find_referencing(&self, entity_id: EntityId) -> Result<Vec<(EntityId, FieldType)>> {
+        Ok(self.find_referencing(entity_id))
+    }
+
     fn get_entity_types(&self) -> Result<Vec<EntityType>> {
         self.get_entity_types()
     }
@@ -1,4 +1,4 @@
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::io::{Read, Write};
 use std::time::Duration;
 
@@ -6,9 +6,11 @@ use crossbeam::channel::Sender;
 use mio::{Events, Interest, Poll, Token};
 use ahash::AHashMap;
 
-use crate::data::resp::{BooleanResponse, CreateEntityCommand, CreateEntityResponse, DeleteEntityCommand, EntityExistsCommand, EntityListResponse, EntityTypeListResponse, FieldExistsCommand, FieldSchemaResponse, FindEntitiesCommand, FindEntitiesExactCommand, FindEntitiesPaginatedCommand, GetCompleteEntitySchemaCommand, GetEntitySchemaCommand, GetEntityTypeCommand, GetEntityTypesCommand, GetEntityTypesPaginatedCommand, GetFieldSchemaCommand, GetFieldTypeCommand, IntegerResponse, NotificationCommand, PaginatedEntityResponse, PaginatedEntityTypeResponse, ReadCommand, ReadResponse, RegisterNotificationCommand, ResolveEntityTypeCommand, ResolveFieldTypeCommand, ResolveIndirectionCommand, ResolveIndirectionResponse, RespCommand, RespDecode, RespFromBytes, RespToBytes, RespValue, SetFieldSchemaCommand, SnapshotResponse, StringResponse, TakeSnapshotCommand, UnregisterNotificationCommand, UpdateSchemaCommand, WriteCommand};
+use crate::data::buffer_pool::take_buffer;
+use crate::data::codec::Codec;
+use crate::data::resp::{error_from_frame, with_binary_ids, ProtocolLimits, AggregateCommand, CodecCommand, QuitCommand, AggregateResponse, BooleanResponse, CreateEntityCommand, CreateEntityResponse, DeleteEntityCommand, EntityExistsCommand, EntityListResponse, EntityTypeListResponse, FieldExistsCommand, FieldSchemaResponse, FindEntitiesCommand, FindEntitiesExactCommand, FindEntitiesPaginatedCommand, FindReferencingCommand, GetCompleteEntitySchemaCommand, GetEntitySchemaCommand, GetEntityTypeCommand, GetEntityTypesCommand, GetEntityTypesPaginatedCommand, GetFieldSchemaCommand, GetFieldTypeCommand, GetTypesBulkCommand, IntegerResponse, DurableNotificationCommand, NotificationCommand, PushCommand, PaginatedEntityResponse, PaginatedEntityTypeResponse, ReadCommand, ReadResponse, ReferencingResponse, RegisterNotificationCommand, ResumeCommand, SubscribeDurableCommand, UnsubscribeDurableCommand, ResolveEntityTypeCommand, ResolveFieldTypeCommand, ResolveIndirectionCommand, ResolveIndirectionResponse, RespCommand, ReadSeriesCommand, SearchCommand, SeriesResponse, VerifyCommand, ChecksumResponse, StatsCommand, StatsResponse, RespDecode, RespFromBytes, RespValue, SetFieldSchemaCommand, SnapshotResponse, StringResponse, TakeSnapshotCommand, TypesBulkResponse, UnregisterNotificationCommand, UpdateSchemaCommand, WriteCommand};
 use crate::{
-    AdjustBehavior, Complete, EntityId, EntitySchema, EntitySchemaResp, EntityType, Error, FieldSchema, FieldType, Notification, NotifyConfig, PageOpts, PageResult, PushCondition, Result, Single, Timestamp, Value
+    AdjustBehavior, AggregateOp, Complete, EntityId, EntitySchema, EntitySchemaResp, EntityType, Error, FieldSchema, FieldType, Notification, NotifyConfig, PageOpts, PageResult, PushCondition, Result, Single, Timestamp, TypesBulk, Value
 };
 use crate::data::StoreTrait;
 
@@ -18,7 +20,7 @@ const READ_POLL_INTERVAL: Duration = Duration::from_millis(10);
 fn expect_ok(resp_value: RespValue) -> Result<()> {
     match resp_value {
         RespValue::SimpleString(s) if s == "OK" => Ok(()),
-        RespValue::Error(msg) => Err(Error::StoreProxyError(format!("Server error: {}", msg))),
+        RespValue::Error(msg) => Err(error_from_frame(msg)),
         _ => Err(Error::StoreProxyError("Expected OK response".to_string())),
     }
 }
@@ -30,10 +32,11 @@ pub struct TcpConnection {
     poll: Poll,
     token: Token,
     pub(crate) read_buffer: Vec<u8>,
+    pub(crate) limits: ProtocolLimits,
 }
 
 impl TcpConnection {
-    pub fn new(stream: std::net::TcpStream) -> anyhow::Result<Self> {
+    pub fn new(stream: std::net::TcpStream, limits: ProtocolLimits) -> anyhow::Result<Self> {
         // Convert std::net::TcpStream to mio::net::TcpStream
         let mut stream = mio::net::TcpStream::from_std(stream);
         
@@ -49,6 +52,7 @@ impl TcpConnection {
             poll,
             token,
             read_buffer: Vec::new(),
+            limits,
         })
     }
     
@@ -96,7 +100,9 @@ impl TcpConnection {
             Ok(0) => return Err(Error::ConnectionLost),
             Ok(bytes_read) => {
                 self.read_buffer.extend_from_slice(&buffer[..bytes_read]);
-                Ok(())
+                // Only called while the frame at the head of the buffer is incomplete,
+                // so the buffer length is a lower bound on that frame's size
+                self.limits.check_frame(self.read_buffer.len())
             }
             Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
                 Ok(()) // No data available
@@ -104,13 +110,27 @@ impl TcpConnection {
             Err(e) => Err(Error::StoreProxyError(format!("TCP read error: {}", e))),
         }
     }
+
+    /// Close both directions of the socket
+    pub fn shutdown(&mut self) -> Result<()> {
+        match self.stream.shutdown(std::net::Shutdown::Both) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotConnected => Err(Error::StoreProxyError(format!("Failed to close connection: {}", e))),
+            _ => Ok(()),
+        }
+    }
 }
 
+/// Mapping from durable subscription id to (last sequence number delivered, sender)
+type DurableSenders = AHashMap<String, (u64, Sender<(u64, Notification)>)>;
+
 #[derive(Debug)]
 pub struct StoreProxy {
     pub(crate) tcp_connection: RefCell<TcpConnection>,
     /// Mapping from config_hash to (NotifyConfig, list of notification senders)
     notification_senders: RefCell<AHashMap<u64, (NotifyConfig, Vec<Sender<Notification>>)>>,
+    durable_senders: RefCell<DurableSenders>,
+    /// Codec the server agreed to, see `negotiate_codec`
+    codec: Cell<Codec>,
 }
 
 impl StoreProxy {
@@ -121,6 +141,11 @@ impl StoreProxy {
 
     /// Connect to TCP server
     pub fn connect(address: &str) -> Result<Self> {
+        Self::connect_with_limits(address, ProtocolLimits::default())
+    }
+
+    /// Connect to TCP server, rejecting frames larger than `limits` allows
+    pub fn connect_with_limits(address: &str, limits: ProtocolLimits) -> Result<Self> {
         // Connect to TCP server
         let stream = std::net::TcpStream::connect(address)
             .map_err(|e| Error::StoreProxyError(format!("Failed to connect to {}: {}", address, e)))?;
@@ -133,24 +158,50 @@ impl StoreProxy {
         stream.set_nonblocking(true)
             .map_err(|e| Error::StoreProxyError(format!("Failed to set non-blocking: {}", e)))?;
 
-        let tcp_connection = TcpConnection::new(stream)
+        let tcp_connection = TcpConnection::new(stream, limits)
             .map_err(|e| Error::StoreProxyError(format!("Failed to create TCP connection: {}", e)))?;
 
         Ok(StoreProxy {
             tcp_connection: RefCell::new(tcp_connection),
             notification_senders: RefCell::new(AHashMap::new()),
+            durable_senders: RefCell::new(AHashMap::new()),
+            codec: Cell::new(Codec::Resp),
         })
     }
 
+    /// Ask the server to switch the connection to `codec` with the `CODEC`
+    /// command, and use it for the following commands once the server
+    /// agrees. The proxy only speaks codecs that keep RESP framing.
+    pub fn negotiate_codec(&self, codec: Codec) -> Result<()> {
+        if !codec.is_resp() {
+            return Err(Error::StoreProxyError(format!("StoreProxy can't speak the {} codec", codec)));
+        }
+        self.send_command_ok(&CodecCommand {
+            codec: codec.name().to_string(),
+            _marker: std::marker::PhantomData,
+        })?;
+        self.codec.set(codec);
+        Ok(())
+    }
+
+    /// Codec the connection speaks, `Codec::Resp` until `negotiate_codec`
+    pub fn codec(&self) -> Codec {
+        self.codec.get()
+    }
+
     fn send_command_get_response<C, R>(&self, command: &C) -> Result<R>
     where
         C: RespCommand<'static>,
         R: for<'a> RespDecode<'a>,
     {
-        let encoded = command.encode();
-        let encoded_bytes = encoded.to_bytes();
+        let mut encoded_bytes = take_buffer();
+        with_binary_ids(self.codec() == Codec::RespBinaryIds, || command.encode_into(&mut encoded_bytes));
         
-        self.tcp_connection.borrow_mut().send_bytes(&encoded_bytes)?;
+        {
+            let mut conn = self.tcp_connection.borrow_mut();
+            conn.limits.check_frame(encoded_bytes.len())?;
+            conn.send_bytes(&encoded_bytes)?;
+        }
 
         loop {
             // Try to parse and get the number of bytes consumed
@@ -162,15 +213,15 @@ impl StoreProxy {
                         
                         // Check if this is an error response from the server
                         if let RespValue::Error(error_msg) = &resp_value {
-                            Some((consumed, Err(Error::StoreProxyError(error_msg.to_string()))))
+                            Some((consumed, Err(error_from_frame(error_msg))))
                         } else {
                             match R::decode(resp_value.clone()) {
                                 Ok(response_struct) => {
                                     Some((consumed, Ok(Some(response_struct))))
                                 }
                                 Err(e) => {
-                                    if let Ok(notification) = NotificationCommand::decode(resp_value.clone()) {
-                                        self.handle_notification(notification);
+                                    if let Ok(push) = PushCommand::decode(resp_value.clone()) {
+                                        self.handle_push(push);
                                         Some((consumed, Ok(None)))
                                     } else {
                                         // We need to consume the bytes even on error, otherwise subsequent commands will fail
@@ -180,6 +231,8 @@ impl StoreProxy {
                             }
                         }
                     }
+                    // Malformed data will never parse, so don't wait for more of it
+                    Err(e @ Error::ProtocolError(_)) => return Err(e),
                     Err(_) => None
                 }
             };
@@ -213,10 +266,14 @@ impl StoreProxy {
     {
         // We need to get the raw RESP value and check if it's OK
         // Use a custom inline check instead of trying to decode RespValue itself
-        let encoded = command.encode();
-        let encoded_bytes = encoded.to_bytes();
+        let mut encoded_bytes = take_buffer();
+        with_binary_ids(self.codec() == Codec::RespBinaryIds, || command.encode_into(&mut encoded_bytes));
         
-        self.tcp_connection.borrow_mut().send_bytes(&encoded_bytes)?;
+        {
+            let mut conn = self.tcp_connection.borrow_mut();
+            conn.limits.check_frame(encoded_bytes.len())?;
+            conn.send_bytes(&encoded_bytes)?;
+        }
 
         loop {
             // Try to parse and get the number of bytes consumed
@@ -226,8 +283,8 @@ impl StoreProxy {
                     Ok((resp_value, remaining)) => {
                         let consumed = conn.read_buffer.len() - remaining.len();
                         // First, try to decode as notification
-                        if let Ok(notification) = NotificationCommand::decode(resp_value.clone()) {
-                            self.handle_notification(notification);
+                        if let Ok(push) = PushCommand::decode(resp_value.clone()) {
+                            self.handle_push(push);
                             Ok(Some((consumed, None))) // None means notification handled, continue
                         } else {
                             // Not a notification, check if OK
@@ -235,6 +292,7 @@ impl StoreProxy {
                             Ok(Some((consumed, Some(result))))
                         }
                     }
+                    Err(e @ Error::ProtocolError(_)) => Err(e),
                     Err(_) => Ok(None)
                 }
             }?;
@@ -303,6 +361,27 @@ impl StoreProxy {
         Ok(string_response.value)
     }
 
+    /// Resolve many entity and field type names in one round trip.
+    /// Unknown names are `None`.
+    pub fn get_types_bulk(&self, entity_types: &[&str], field_types: &[&str]) -> Result<TypesBulk> {
+        let command = GetTypesBulkCommand {
+            entity_types: entity_types.iter().map(|name| name.to_string()).collect(),
+            field_types: field_types.iter().map(|name| name.to_string()).collect(),
+            _marker: std::marker::PhantomData,
+        };
+
+        let response = self.send_command_get_response::<GetTypesBulkCommand, TypesBulkResponse>(&command)?;
+        if response.entity_types.len() != entity_types.len() || response.field_types.len() != field_types.len() {
+            return Err(Error::StoreProxyError("GET_TYPES_BULK returned the wrong number of types".to_string()));
+        }
+        Ok((response.entity_types, response.field_types))
+    }
+
+    /// Resolve many field type names in one round trip
+    pub fn get_field_types(&self, names: &[&str]) -> Result<Vec<Option<FieldType>>> {
+        Ok(self.get_types_bulk(&[], names)?.1)
+    }
+
     /// Get entity schema
     pub fn get_entity_schema(&self, entity_type: EntityType) -> Result<EntitySchema<Single>> {
         let command = GetEntitySchemaCommand {
@@ -348,6 +427,12 @@ impl StoreProxy {
             rank: schema.rank(),
             default_value: schema.default_value(),
             choices: schema.choices(),
+            choices_source: schema.choices_source().map(|s| s.to_string()),
+            expression: schema.expression().map(|e| e.to_string()),
+            metadata: schema.metadata().clone(),
+            series: schema.series_limits(),
+            validator: schema.validator().map(|v| v.to_string()),
+            merge_policy: schema.merge_policy(),
         };
 
         let command = SetFieldSchemaCommand {
@@ -446,6 +531,90 @@ impl StoreProxy {
         self.send_command_ok(&command)
     }
 
+    /// Write a field value, rejected with `Error::StaleFencingToken` unless
+    /// `fencing_token` is the current LeaderToken of `fault_tolerance_id`
+    #[allow(clippy::too_many_arguments)]
+    pub fn fenced_write(&self, fault_tolerance_id: EntityId, fencing_token: i64, entity_id: EntityId, field_path: &[FieldType], value: Value, writer_id: Option<EntityId>, write_time: Option<Timestamp>, push_condition: Option<PushCondition>, adjust_behavior: Option<AdjustBehavior>) -> Result<()> {
+        let command = crate::data::resp::FencedWriteCommand {
+            fault_tolerance_id,
+            fencing_token,
+            entity_id,
+            field_path: field_path.to_vec(),
+            value,
+            writer_id,
+            write_time,
+            push_condition,
+            adjust_behavior,
+            _marker: std::marker::PhantomData,
+        };
+        self.send_command_ok(&command)
+    }
+
+    /// Resolve an entity path such as `Root/Machines/M1` to its entity id on the server
+    pub fn resolve_path(&self, path: &str) -> Result<EntityId> {
+        let command = crate::data::resp::ResolvePathCommand {
+            path: path.to_string(),
+            _marker: std::marker::PhantomData,
+        };
+
+        let response = self.send_command_get_response::<_, crate::data::resp::ResolvePathResponse>(&command)?;
+        Ok(response.entity_id)
+    }
+
+    /// Read a field addressed by path, e.g. `Root/Machines/M1/Pump3/Speed`
+    pub fn read_path(&self, path: &str) -> Result<(Value, Timestamp, Option<EntityId>)> {
+        let command = crate::data::resp::ReadPathCommand {
+            path: path.to_string(),
+            _marker: std::marker::PhantomData,
+        };
+
+        let read_response = self.send_command_get_response::<_, crate::data::resp::ReadResponse>(&command)?;
+        Ok((read_response.value, read_response.timestamp, read_response.writer_id))
+    }
+
+    /// Write a field addressed by path, e.g. `Root/Machines/M1/Pump3/Speed`
+    pub fn write_path(&self, path: &str, value: Value, writer_id: Option<EntityId>, write_time: Option<Timestamp>, push_condition: Option<PushCondition>, adjust_behavior: Option<AdjustBehavior>) -> Result<()> {
+        let command = crate::data::resp::WritePathCommand {
+            path: path.to_string(),
+            value,
+            writer_id,
+            write_time,
+            push_condition,
+            adjust_behavior,
+            _marker: std::marker::PhantomData,
+        };
+        self.send_command_ok(&command)
+    }
+
+    /// Read `len` bytes of a blob field starting at `offset`, returning the
+    /// bytes and the blob's total length
+    pub fn read_blob_range(&self, entity_id: EntityId, field_path: &[FieldType], offset: usize, len: usize) -> Result<(Vec<u8>, usize)> {
+        let command = crate::data::resp::ReadBlobRangeCommand {
+            entity_id,
+            field_path: field_path.to_vec(),
+            offset,
+            len,
+            _marker: std::marker::PhantomData,
+        };
+
+        let response = self.send_command_get_response::<_, crate::data::resp::BlobRangeResponse>(&command)?;
+        Ok((response.data, response.total_len))
+    }
+
+    /// Append bytes to a blob field, returning the blob's new length
+    pub fn write_blob_append(&self, entity_id: EntityId, field_path: &[FieldType], data: Vec<u8>, writer_id: Option<EntityId>) -> Result<usize> {
+        let command = crate::data::resp::WriteBlobAppendCommand {
+            entity_id,
+            field_path: field_path.to_vec(),
+            data,
+            writer_id,
+            _marker: std::marker::PhantomData,
+        };
+
+        let response = self.send_command_get_response::<_, crate::data::resp::IntegerResponse>(&command)?;
+        Ok(response.value as usize)
+    }
+
     /// Create a new entity
     pub fn create_entity(&self, entity_type: EntityType, parent_id: Option<EntityId>, name: &str) -> Result<EntityId> {
         let command = CreateEntityCommand {
@@ -459,6 +628,74 @@ impl StoreProxy {
         Ok(create_response.entity_id)
     }
 
+    /// Create an entity with initial field values, applied together with
+    /// the creation (`CREATE_ENTITY_FULL`)
+    pub fn create_entity_with_fields(&self, entity_type: EntityType, parent_id: Option<EntityId>, name: &str, fields: Vec<(FieldType, Value)>) -> Result<EntityId> {
+        let command = crate::data::resp::CreateEntityFullCommand {
+            entity_type,
+            parent_id,
+            name: name.to_string(),
+            fields,
+            _marker: std::marker::PhantomData,
+        };
+
+        let create_response = self.send_command_get_response::<_, CreateEntityResponse>(&command)?;
+        Ok(create_response.entity_id)
+    }
+
+    /// Create a copy of a template and its descendants (`INSTANTIATE`),
+    /// see `Store::instantiate_template`
+    pub fn instantiate_template(&self, template_id: EntityId, parent_id: Option<EntityId>, name: &str, overrides: Vec<(FieldType, Value)>) -> Result<EntityId> {
+        let command = crate::data::resp::InstantiateTemplateCommand {
+            template_id,
+            parent_id,
+            name: name.to_string(),
+            overrides,
+            _marker: std::marker::PhantomData,
+        };
+
+        let create_response = self.send_command_get_response::<_, CreateEntityResponse>(&command)?;
+        Ok(create_response.entity_id)
+    }
+
+    /// Find or create an entity by parent and name in one round trip, so
+    /// the server decides atomically (`GET_OR_CREATE`)
+    pub fn get_or_create(&self, entity_type: EntityType, parent_id: Option<EntityId>, name: &str) -> Result<(EntityId, bool)> {
+        let command = crate::data::resp::GetOrCreateCommand {
+            entity_type,
+            parent_id,
+            name: name.to_string(),
+            _marker: std::marker::PhantomData,
+        };
+
+        let response = self.send_command_get_response::<_, crate::data::resp::GetOrCreateResponse>(&command)?;
+        Ok((response.entity_id, response.created))
+    }
+
+    /// Write a field unless it already holds `value` (`UPSERT`), returning
+    /// whether it was written
+    pub fn upsert_field(&self, entity_id: EntityId, field_type: FieldType, value: Value) -> Result<bool> {
+        let command = crate::data::resp::UpsertFieldCommand {
+            entity_id,
+            field_type,
+            value,
+            _marker: std::marker::PhantomData,
+        };
+
+        let response = self.send_command_get_response::<_, crate::data::resp::BooleanResponse>(&command)?;
+        Ok(response.result)
+    }
+
+    /// Rename an entity (`RENAME`), enforcing name uniqueness on the server
+    pub fn rename_entity(&self, entity_id: EntityId, new_name: &str) -> Result<()> {
+        let command = crate::data::resp::RenameEntityCommand {
+            entity_id,
+            new_name: new_name.to_string(),
+            _marker: std::marker::PhantomData,
+        };
+        self.send_command_ok(&command)
+    }
+
     /// Delete an entity
     pub fn delete_entity(&self, entity_id: EntityId) -> Result<()> {
         let command = DeleteEntityCommand {
@@ -470,26 +707,8 @@ impl StoreProxy {
 
     /// Update entity schema
     pub fn update_schema(&self, schema: EntitySchema<Single, String, String>) -> Result<()> {
-        // Convert EntitySchema to EntitySchemaResp
-        let fields_resp: Vec<crate::data::entity_schema::FieldSchemaResp> = schema
-            .fields
-            .into_iter()
-            .map(|(field_type_str, field_schema)| {
-                crate::data::entity_schema::FieldSchemaResp {
-                    field_type: field_type_str,
-                    rank: field_schema.rank(),
-                    default_value: field_schema.default_value(),
-                    choices: field_schema.choices(),
-                }
-            })
-            .collect();
+        let schema_resp = crate::data::entity_schema::EntitySchemaResp::from_string_schema(schema);
 
-        let schema_resp = crate::data::entity_schema::EntitySchemaResp {
-            entity_type: schema.entity_type,
-            inherit: schema.inherit,
-            fields: fields_resp,
-        };
-        
         let command = UpdateSchemaCommand {
             schema: schema_resp,
             _marker: std::marker::PhantomData,
@@ -527,6 +746,8 @@ impl StoreProxy {
             entity_type,
             page_opts: page_opts.cloned(),
             filter: filter.map(|s| s.to_string()),
+            timeout_ms: None,
+            request_id: None,
             _marker: std::marker::PhantomData,
         };
         
@@ -536,7 +757,7 @@ impl StoreProxy {
             paginated_response.items,
             paginated_response.total,
             paginated_response.next_cursor,
-        ))
+        ).with_next_key(paginated_response.next_key))
     }
 
     /// Find entities exactly of the specified type (no inheritance) with pagination
@@ -545,6 +766,8 @@ impl StoreProxy {
             entity_type,
             page_opts: page_opts.cloned(),
             filter: filter.map(|s| s.to_string()),
+            timeout_ms: None,
+            request_id: None,
             _marker: std::marker::PhantomData,
         };
         
@@ -554,7 +777,7 @@ impl StoreProxy {
             paginated_response.items,
             paginated_response.total,
             paginated_response.next_cursor,
-        ))
+        ).with_next_key(paginated_response.next_key))
     }
 
     /// Get machine info (machine ID/name)
@@ -567,6 +790,48 @@ impl StoreProxy {
         Ok(string_response.value)
     }
 
+    /// Query the server's audit log. Fails if auditing isn't enabled there.
+    pub fn audit_query(&self, query: &crate::AuditQuery) -> Result<Vec<crate::AuditRecord>> {
+        let command = crate::data::resp::AuditQueryCommand::from_query(query);
+        let response = self.send_command_get_response::<_, crate::data::resp::AuditQueryResponse>(&command)?;
+        response.into_records()
+    }
+
+    /// The writes the server made to `entity_id` at or after `since`, oldest
+    /// first. Fails if auditing isn't enabled there or the log no longer
+    /// goes back that far.
+    pub fn get_changes(&self, entity_id: EntityId, since: crate::Timestamp) -> Result<Vec<crate::WriteInfo>> {
+        let command = crate::data::resp::GetChangesCommand {
+            entity_id,
+            since,
+            _marker: std::marker::PhantomData,
+        };
+        let response = self.send_command_get_response::<_, crate::data::resp::ChangesResponse>(&command)?;
+        response.into_writes()
+    }
+
+    /// The server's `count` slowest recent commands (all of them for None),
+    /// newest first
+    pub fn slowlog_get(&self, count: Option<usize>) -> Result<Vec<crate::SlowLogEntry>> {
+        let command = crate::data::resp::SlowLogCommand {
+            action: crate::SlowLogAction::Get,
+            count,
+            _marker: std::marker::PhantomData,
+        };
+        let response = self.send_command_get_response::<_, crate::data::resp::SlowLogResponse>(&command)?;
+        response.into_entries()
+    }
+
+    /// Clear the server's slow log
+    pub fn slowlog_reset(&self) -> Result<()> {
+        let command = crate::data::resp::SlowLogCommand {
+            action: crate::SlowLogAction::Reset,
+            count: None,
+            _marker: std::marker::PhantomData,
+        };
+        self.send_command_ok(&command)
+    }
+
     /// Get entity types with pagination
     pub fn get_entity_types_paginated(&self, page_opts: Option<&PageOpts>) -> Result<PageResult<EntityType>> {
         let command = GetEntityTypesPaginatedCommand {
@@ -587,6 +852,8 @@ impl StoreProxy {
         let command = FindEntitiesCommand {
             entity_type,
             filter: filter.map(|s| s.to_string()),
+            timeout_ms: None,
+            request_id: None,
             _marker: std::marker::PhantomData,
         };
         
@@ -594,6 +861,81 @@ impl StoreProxy {
         Ok(entity_list_response.entities)
     }
 
+    /// Entities and fields referring to an entity, answered by the server's index
+    pub fn find_referencing(&self, entity_id: EntityId) -> Result<Vec<(EntityId, FieldType)>> {
+        let command = FindReferencingCommand {
+            entity_id,
+            _marker: std::marker::PhantomData,
+        };
+
+        let response = self.send_command_get_response::<FindReferencingCommand, ReferencingResponse>(&command)?;
+        if response.entity_ids.len() != response.field_types.len() {
+            return Err(Error::StoreProxyError("FIND_REFERENCING returned mismatched lists".to_string()));
+        }
+        Ok(response.entity_ids.into_iter().zip(response.field_types).collect())
+    }
+
+    /// Full-text search on the server, see `Store::search`
+    pub fn search(&self, entity_type: EntityType, query: &str) -> Result<Vec<EntityId>> {
+        let command = SearchCommand {
+            entity_type,
+            query: query.to_string(),
+            _marker: std::marker::PhantomData,
+        };
+
+        let response = self.send_command_get_response::<SearchCommand, EntityListResponse>(&command)?;
+        Ok(response.entities)
+    }
+
+    /// Sample history of a Series field, see `Store::read_series`
+    pub fn read_series(&self, entity_id: EntityId, field_type: FieldType, from: Option<Timestamp>, to: Option<Timestamp>, downsample: Option<crate::Duration>) -> Result<Vec<(Timestamp, Value)>> {
+        let command = ReadSeriesCommand {
+            entity_id,
+            field_type,
+            from,
+            to,
+            downsample,
+            _marker: std::marker::PhantomData,
+        };
+
+        let response = self.send_command_get_response::<ReadSeriesCommand, SeriesResponse>(&command)?;
+        Ok(response.samples)
+    }
+
+    /// Content hashes of the server's state, see `Store::checksum`
+    pub fn verify(&self) -> Result<crate::SnapshotChecksum> {
+        let command = VerifyCommand {
+            _marker: std::marker::PhantomData,
+        };
+
+        let response = self.send_command_get_response::<VerifyCommand, ChecksumResponse>(&command)?;
+        response.into_checksum()
+    }
+
+    /// Size and usage figures of the server's store, see `StoreTrait::stats`
+    pub fn stats(&self) -> Result<crate::StoreStats> {
+        let command = StatsCommand {
+            _marker: std::marker::PhantomData,
+        };
+
+        let response = self.send_command_get_response::<StatsCommand, StatsResponse>(&command)?;
+        crate::StoreStats::from_map(&response.stats)
+    }
+
+    /// Aggregate a field on the server, see `StoreTrait::aggregate`
+    pub fn aggregate(&self, entity_type: EntityType, field_type: FieldType, op: AggregateOp, filter: Option<&str>) -> Result<Option<Value>> {
+        let command = AggregateCommand {
+            entity_type,
+            field_type,
+            op,
+            filter: filter.map(|s| s.to_string()),
+            _marker: std::marker::PhantomData,
+        };
+
+        let response = self.send_command_get_response::<AggregateCommand, AggregateResponse>(&command)?;
+        Ok(response.value)
+    }
+
     pub fn get_entity_types(&self) -> Result<Vec<EntityType>> {
         let command = GetEntityTypesCommand {
             _marker: std::marker::PhantomData,
@@ -607,14 +949,7 @@ impl StoreProxy {
 
     /// Handle a notification command received from the server
     pub(crate) fn handle_notification(&self, notification_cmd: NotificationCommand) {
-        // Deserialize the notification from JSON
-        let notification: Notification = match serde_json::from_str(&notification_cmd.notification_data) {
-            Ok(n) => n,
-            Err(_e) => {
-                // Silently ignore deserialization errors - they shouldn't happen in normal operation
-                return;
-            }
-        };
+        let notification = notification_cmd.notification;
 
         // Get the config_hash from the notification to find matching senders
         let config_hash = notification.config_hash;
@@ -629,6 +964,25 @@ impl StoreProxy {
         }
     }
 
+    /// Handle a notification for a durable subscription, skipping any the
+    /// sender already got (a replay can overlap notifications received live)
+    pub(crate) fn handle_durable_notification(&self, command: DurableNotificationCommand) {
+        let mut senders = self.durable_senders.borrow_mut();
+        if let Some((last_seq, sender)) = senders.get_mut(&command.subscription_id) {
+            if command.seq > *last_seq && sender.try_send((command.seq, command.notification)).is_ok() {
+                *last_seq = command.seq;
+            }
+        }
+    }
+
+    /// Handle a frame the server pushed between replies
+    pub(crate) fn handle_push(&self, push: PushCommand) {
+        match push {
+            PushCommand::Notification(command) => self.handle_notification(command),
+            PushCommand::DurableNotification(command) => self.handle_durable_notification(command),
+        }
+    }
+
     /// Process notifications for up to some time
     /// This checks if any notification commands were received from the server
     pub fn process_notifications(&self) -> Result<()> {
@@ -639,7 +993,7 @@ impl StoreProxy {
                 match RespValue::from_bytes(&conn.read_buffer) {
                     Ok((resp_value, remaining)) => {
                         let consumed = conn.read_buffer.len() - remaining.len();
-                        match NotificationCommand::decode(resp_value) {
+                        match PushCommand::decode(resp_value) {
                             Ok(response_struct) => {
                                 Ok(Some((consumed, Some(response_struct))))
                             }
@@ -651,6 +1005,7 @@ impl StoreProxy {
                             },
                         }
                     }
+                    Err(e @ Error::ProtocolError(_)) => Err(e),
                     Err(_) => Ok(None)
                 }
             }?;
@@ -659,7 +1014,7 @@ impl StoreProxy {
                 // Remove only the consumed bytes, keeping the remaining unparsed data
                 self.tcp_connection.borrow_mut().read_buffer.drain(..consumed);
                 if let Some(response_struct) = response_struct {
-                    self.handle_notification(response_struct);
+                    self.handle_push(response_struct);
                     
                     // Continue loop to see if we can get the more notifications
                     // without having to read more data
@@ -680,6 +1035,62 @@ impl StoreProxy {
         Ok(())
     }
 
+    /// Track `sender` locally so notifications for `config` are forwarded to it
+    pub(crate) fn add_notification_sender(&self, config: NotifyConfig, sender: Sender<Notification>) {
+        let config_hash = crate::data::notifications::hash_notify_config(&config);
+        let mut senders = self.notification_senders.borrow_mut();
+        let entry = senders.entry(config_hash).or_insert_with(|| (config, Vec::new()));
+        entry.1.push(sender);
+    }
+
+    /// Stop forwarding notifications for `config` to `sender`. Returns true if
+    /// that was the last sender, meaning the server should be told to stop too
+    pub(crate) fn remove_notification_sender(&self, config: &NotifyConfig, sender: &Sender<Notification>) -> bool {
+        let config_hash = crate::data::notifications::hash_notify_config(config);
+        let mut senders = self.notification_senders.borrow_mut();
+        if let Some((_config, sender_list)) = senders.get_mut(&config_hash) {
+            // Remove this specific sender by comparing channels
+            sender_list.retain(|s| !s.same_channel(sender));
+
+            if sender_list.is_empty() {
+                senders.remove(&config_hash);
+                return true;
+            }
+        }
+        false
+    }
+
+    /// End the session cleanly instead of just dropping the socket: remove
+    /// this connection's notification registrations from the server, send
+    /// `QUIT` and close the connection. Pipelines borrow the proxy, so none
+    /// can be left unsent by the time it is closed.
+    pub fn close(self) -> Result<()> {
+        let configs: Vec<NotifyConfig> = self
+            .notification_senders
+            .borrow_mut()
+            .drain()
+            .map(|(_, (config, _senders))| config)
+            .collect();
+
+        let mut result = Ok(());
+        for config in configs {
+            let command = UnregisterNotificationCommand {
+                config,
+                _marker: std::marker::PhantomData,
+            };
+            // Keep going, so the connection is still closed
+            if let Err(e) = self.send_command_ok(&command) {
+                result = result.and(Err(e));
+            }
+        }
+
+        let command = QuitCommand {
+            _marker: std::marker::PhantomData,
+        };
+        result = result.and(self.send_command_ok(&command));
+        result.and(self.tcp_connection.borrow_mut().shutdown())
+    }
+
     /// Register notification with provided sender
     /// Note: For proxy, this registers the notification on the remote server
     /// and stores the sender locally to forward notifications
@@ -688,15 +1099,7 @@ impl StoreProxy {
         config: NotifyConfig,
         sender: Sender<Notification>,
     ) -> Result<()> {
-        // Calculate config hash
-        let config_hash = crate::data::notifications::hash_notify_config(&config);
-        
-        // Add sender to our local mapping
-        {
-            let mut senders = self.notification_senders.borrow_mut();
-            let entry = senders.entry(config_hash).or_insert_with(|| (config.clone(), Vec::new()));
-            entry.1.push(sender);
-        } // Drop the borrow before calling send_command_ok
+        self.add_notification_sender(config.clone(), sender);
         
         let command = RegisterNotificationCommand {
             config,
@@ -709,32 +1112,8 @@ impl StoreProxy {
 
     /// Unregister a notification by removing a specific sender
     pub fn unregister_notification(&self, target_config: &NotifyConfig, sender: &Sender<Notification>) -> bool {
-        // Calculate config hash
-        let config_hash = crate::data::notifications::hash_notify_config(target_config);
-        
-        // Remove sender from our local mapping
-        let mut senders = self.notification_senders.borrow_mut();
-        let should_unregister_on_server = if let Some((_config, sender_list)) = senders.get_mut(&config_hash) {
-            // Remove this specific sender by comparing channels
-            sender_list.retain(|s| !s.same_channel(sender));
-            
-            // If no more senders for this config, remove the config entry and unregister on server
-            if sender_list.is_empty() {
-                senders.remove(&config_hash);
-                true
-            } else {
-                false
-            }
-        } else {
-            // Config not found, nothing to do
-            false
-        };
-        
-        // Drop the borrow before calling send_command_ok
-        drop(senders);
-        
         // Only unregister on server if we removed the last sender for this config
-        if should_unregister_on_server {
+        if self.remove_notification_sender(target_config, sender) {
             let command = UnregisterNotificationCommand {
                 config: target_config.clone(),
                 _marker: std::marker::PhantomData,
@@ -749,6 +1128,113 @@ impl StoreProxy {
         }
     }
 
+    /// Subscribe to notifications that the server keeps for this
+    /// subscription while no client is connected, see
+    /// `DurableSubscriptions`. Subscribing to an existing subscription with
+    /// the same config attaches to it.
+    ///
+    /// Notifications arrive on `sender` with their sequence number. Returns
+    /// the sequence number of the last notification the subscription had
+    /// received before; to get the ones after it, use `resume_durable`.
+    pub fn subscribe_durable(&self, subscription_id: &str, config: NotifyConfig, sender: Sender<(u64, Notification)>) -> Result<u64> {
+        // Add the sender first so a notification right after the reply isn't lost
+        self.durable_senders.borrow_mut().insert(subscription_id.to_string(), (0, sender));
+
+        let command = SubscribeDurableCommand {
+            subscription_id: subscription_id.to_string(),
+            config,
+            _marker: std::marker::PhantomData,
+        };
+        match self.send_command_get_response::<_, IntegerResponse>(&command) {
+            Ok(response) => Ok(response.value as u64),
+            Err(e) => {
+                self.durable_senders.borrow_mut().remove(subscription_id);
+                Err(e)
+            }
+        }
+    }
+
+    /// Attach to a durable subscription after connecting again, e.g. after
+    /// the service restarted. Every notification after `last_seq` (the last
+    /// one handled) is delivered to `sender` before this returns, followed by
+    /// new ones as they arrive. Returns the sequence number of the last
+    /// notification replayed.
+    ///
+    /// Fails with the server's `NotificationsDiscarded` error if some of the
+    /// missed notifications are no longer kept, in which case the consumer
+    /// should read the current state and subscribe again.
+    pub fn resume_durable(&self, subscription_id: &str, last_seq: u64, sender: Sender<(u64, Notification)>) -> Result<u64> {
+        // The replay arrives before the reply
+        self.durable_senders.borrow_mut().insert(subscription_id.to_string(), (last_seq, sender));
+
+        let command = ResumeCommand {
+            subscription_id: subscription_id.to_string(),
+            last_seq,
+            _marker: std::marker::PhantomData,
+        };
+        match self.send_command_get_response::<_, IntegerResponse>(&command) {
+            Ok(response) => Ok(response.value as u64),
+            Err(e) => {
+                self.durable_senders.borrow_mut().remove(subscription_id);
+                Err(e)
+            }
+        }
+    }
+
+    /// Remove a durable subscription from the server, along with the
+    /// notifications it kept. Closing the proxy leaves durable subscriptions
+    /// in place.
+    pub fn unsubscribe_durable(&self, subscription_id: &str) -> Result<()> {
+        self.durable_senders.borrow_mut().remove(subscription_id);
+        let command = UnsubscribeDurableCommand {
+            subscription_id: subscription_id.to_string(),
+            _marker: std::marker::PhantomData,
+        };
+        self.send_command_ok(&command)
+    }
+
+    /// Block until a field satisfies `predicate` and return the value that
+    /// did, or fail with `Error::Timeout` once `timeout` has passed.
+    ///
+    /// The notification is registered before the field is read, so a write
+    /// landing between the read and the registration can't be missed.
+    /// Notifications for other registrations that arrive meanwhile are
+    /// forwarded as usual.
+    pub fn wait_for(&self, entity_id: EntityId, field_type: FieldType, predicate: impl Fn(&Value) -> bool, timeout: Duration) -> Result<Value> {
+        let deadline = std::time::Instant::now() + timeout;
+        let config = NotifyConfig::EntityId {
+            entity_id,
+            field_type,
+            trigger_on_change: true,
+            context: vec![],
+            filter: None,
+        };
+        let (sender, receiver) = crossbeam::channel::unbounded();
+        let registered = self.register_notification(config.clone(), sender.clone());
+
+        let result = (|| -> Result<Value> {
+            registered?;
+            let (value, _, _) = self.read(entity_id, &[field_type])?;
+            if predicate(&value) {
+                return Ok(value);
+            }
+            loop {
+                for notification in receiver.try_iter() {
+                    if let Some(value) = notification.current.value.filter(|value| predicate(value)) {
+                        return Ok(value);
+                    }
+                }
+                if std::time::Instant::now() >= deadline {
+                    return Err(Error::Timeout(timeout));
+                }
+                self.process_notifications()?;
+            }
+        })();
+
+        self.unregister_notification(&config, &sender);
+        result
+    }
+
 }
 
 impl StoreTrait for StoreProxy {
@@ -768,6 +1254,14 @@ impl StoreTrait for StoreProxy {
         self.resolve_field_type(field_type)
     }
 
+    fn get_types_bulk(&self, entity_types: &[&str], field_types: &[&str]) -> Result<TypesBulk> {
+        self.get_types_bulk(entity_types, field_types)
+    }
+
+    fn get_field_types(&self, names: &[&str]) -> Result<Vec<Option<FieldType>>> {
+        self.get_field_types(names)
+    }
+
     fn get_entity_schema(&self, entity_type: EntityType) -> Result<EntitySchema<Single>> {
         self.get_entity_schema(entity_type)
     }
@@ -778,6 +1272,10 @@ impl StoreTrait for StoreProxy {
         unimplemented!("StoreProxy cannot return references to remote data")
     }
 
+    fn get_complete_entity_schema_owned(&self, entity_type: EntityType) -> Result<EntitySchema<Complete>> {
+        self.get_complete_entity_schema(entity_type)
+    }
+
     fn get_field_schema(&self, entity_type: EntityType, field_type: FieldType) -> Result<FieldSchema> {
         self.get_field_schema(entity_type, field_type)
     }
@@ -790,6 +1288,12 @@ impl StoreTrait for StoreProxy {
             rank: schema.rank(),
             default_value: schema.default_value(),
             choices: schema.choices(),
+            choices_source: schema.choices_source().map(|s| s.to_string()),
+            expression: schema.expression().map(|e| e.to_string()),
+            metadata: schema.metadata().clone(),
+            series: schema.series_limits(),
+            validator: schema.validator().map(|v| v.to_string()),
+            merge_policy: schema.merge_policy(),
         };
 
         let command = SetFieldSchemaCommand {
@@ -844,6 +1348,18 @@ impl StoreTrait for StoreProxy {
         Ok(create_response.entity_id)
     }
 
+    fn get_or_create(&mut self, entity_type: EntityType, parent_id: Option<EntityId>, name: &str) -> Result<(EntityId, bool)> {
+        StoreProxy::get_or_create(self, entity_type, parent_id, name)
+    }
+
+    fn upsert_field(&mut self, entity_id: EntityId, field_type: FieldType, value: Value) -> Result<bool> {
+        StoreProxy::upsert_field(self, entity_id, field_type, value)
+    }
+
+    fn rename_entity(&mut self, entity_id: EntityId, new_name: &str) -> Result<()> {
+        StoreProxy::rename_entity(self, entity_id, new_name)
+    }
+
     fn delete_entity(&mut self, entity_id: EntityId) -> Result<()> {
         let command = DeleteEntityCommand {
             entity_id,
@@ -853,37 +1369,17 @@ impl StoreTrait for StoreProxy {
     }
 
     fn update_schema(&mut self, schema: EntitySchema<Single, String, String>) -> Result<()> {
-        // Convert EntitySchema to EntitySchemaResp
-        let fields_resp: Vec<crate::data::entity_schema::FieldSchemaResp> = schema
-            .fields
-            .into_iter()
-            .map(|(field_type_str, field_schema)| {
-                crate::data::entity_schema::FieldSchemaResp {
-                    field_type: field_type_str,
-                    rank: field_schema.rank(),
-                    default_value: field_schema.default_value(),
-                    choices: field_schema.choices(),
-                }
-            })
-            .collect();
-
-        let schema_resp = crate::data::entity_schema::EntitySchemaResp {
-            entity_type: schema.entity_type,
-            inherit: schema.inherit,
-            fields: fields_resp,
-        };
-        
-        let command = UpdateSchemaCommand {
-            schema: schema_resp,
-            _marker: std::marker::PhantomData,
-        };
-        self.send_command_ok(&command)
+        StoreProxy::update_schema(self, schema)
     }
 
     fn take_snapshot(&self) -> crate::data::Snapshot {
         self.take_snapshot()
     }
 
+    fn stats(&self) -> Result<crate::StoreStats> {
+        self.stats()
+    }
+
     fn find_entities_paginated(&self, entity_type: EntityType, page_opts: Option<&PageOpts>, filter: Option<&str>) -> Result<PageResult<EntityId>> {
         self.find_entities_paginated(entity_type, page_opts, filter)
     }
@@ -896,6 +1392,14 @@ impl StoreTrait for StoreProxy {
         self.find_entities(entity_type, filter)
     }
 
+    fn find_referencing(&self, entity_id: EntityId) -> Result<Vec<(EntityId, FieldType)>> {
+        self.find_referencing(entity_id)
+    }
+
+    fn aggregate(&self, entity_type: EntityType, field_type: FieldType, op: AggregateOp, filter: Option<&str>) -> Result<Option<Value>> {
+        self.aggregate(entity_type, field_type, op, filter)
+    }
+
     fn get_entity_types(&self) -> Result<Vec<EntityType>> {
         self.get_entity_types()
     }
@@ -1,7 +1,21 @@
 use crate::{
-    AdjustBehavior, Complete, EntityId, EntitySchema, EntityType, FieldSchema, FieldType, PageOpts, PageResult, PushCondition, Result, Single, Timestamp, Value
+    data::indirection::path_to_field_path, data::aggregate::{zero_of, Aggregator}, ft, AdjustBehavior, AggregateOp, Complete, EntityId, EntitySchema, EntityType, Error, FieldSchema, FieldType, PageOpts, PageResult, PushCondition, Result, Single, StoreStats, Timestamp, Value
 };
 
+/// Bulk type lookup results: entity types, then field types, in request
+/// order. Unknown names are `None`.
+pub type TypesBulk = (Vec<Option<EntityType>>, Vec<Option<FieldType>>);
+
+/// A reference field pointing at an entity that doesn't exist,
+/// see `StoreTrait::find_dangling_references`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DanglingReference {
+    pub entity_id: EntityId,
+    pub field_type: FieldType,
+    /// The missing entity
+    pub target: EntityId,
+}
+
 /// Async trait defining the common interface for store implementations
 /// This allows different store implementations to be used interchangeably
 pub trait StoreTrait {
@@ -13,12 +27,33 @@ pub trait StoreTrait {
 
     fn resolve_field_type(&self, field_type: FieldType) -> Result<String>;
 
+    /// Look up many entity and field type names at once, e.g. at startup.
+    /// Results are in request order; unknown names are `None` instead of
+    /// failing the whole lookup. Remote stores answer in one round trip.
+    fn get_types_bulk(&self, entity_types: &[&str], field_types: &[&str]) -> Result<TypesBulk> {
+        Ok((
+            entity_types.iter().map(|name| self.get_entity_type(name).ok()).collect(),
+            field_types.iter().map(|name| self.get_field_type(name).ok()).collect(),
+        ))
+    }
+
+    /// Look up many field type names at once, see `get_types_bulk`
+    fn get_field_types(&self, names: &[&str]) -> Result<Vec<Option<FieldType>>> {
+        Ok(self.get_types_bulk(&[], names)?.1)
+    }
+
     /// Get the schema for a specific entity type
     fn get_entity_schema(&self, entity_type: EntityType) -> Result<EntitySchema<Single>>;
 
     /// Get the complete schema for a specific entity type (including inherited fields)
     fn get_complete_entity_schema(&self, entity_type: EntityType) -> Result<&EntitySchema<Complete>>;
 
+    /// Get a copy of the complete schema for a specific entity type. Stores that
+    /// can't lend out a reference (remote or lock-guarded ones) override this
+    fn get_complete_entity_schema_owned(&self, entity_type: EntityType) -> Result<EntitySchema<Complete>> {
+        self.get_complete_entity_schema(entity_type).cloned()
+    }
+
     /// Get the schema for a specific field
     fn get_field_schema(&self, entity_type: EntityType, field_type: FieldType) -> Result<FieldSchema>;
 
@@ -52,6 +87,11 @@ pub trait StoreTrait {
     /// Take a snapshot
     fn take_snapshot(&self) -> crate::data::Snapshot;
 
+    /// Entity counts per type, field count, memory estimate, notification
+    /// registrations and interner sizes, for capacity planning. Remote
+    /// stores ask the server with `STATS`.
+    fn stats(&self) -> Result<StoreStats>;
+
     /// Find entities of a specific type with pagination (includes inherited types)
     fn find_entities_paginated(&self, entity_type: EntityType, page_opts: Option<&PageOpts>, filter: Option<&str>) -> Result<PageResult<EntityId>>;
 
@@ -66,4 +106,374 @@ pub trait StoreTrait {
 
     /// Get all entity types with pagination
     fn get_entity_types_paginated(&self, page_opts: Option<&PageOpts>) -> Result<PageResult<EntityType>>;
-}
\ No newline at end of file
+
+    /// The choice names of a Choice field.
+    ///
+    /// If the schema has a `choices_source`, the choices are the names of
+    /// the entities in that EntityList (in list order), so the options can
+    /// be maintained as data. Otherwise the schema's `choices` are used.
+    fn get_choices(&self, entity_type: EntityType, field_type: FieldType) -> Result<Vec<String>> {
+        let field_schema = collect_field_schemas(self, entity_type)?
+            .into_iter()
+            .find(|(ft, _)| *ft == field_type)
+            .map(|(_, field_schema)| field_schema)
+            .ok_or_else(|| Error::FieldTypeNotFound(EntityId::new(entity_type, 0), field_type))?;
+
+        if !matches!(field_schema, FieldSchema::Choice { .. }) {
+            return Err(Error::InvalidFieldType(format!("{:?} is not a Choice field", field_type)));
+        }
+
+        let Some(choices_source) = field_schema.choices_source() else {
+            return Ok(field_schema.choices());
+        };
+
+        let ft_name = self.get_field_type(ft::NAME)?;
+        let (source_id, source_path) = path_to_field_path(self, choices_source)?;
+        let (entities, _, _) = self.read(source_id, &source_path)?;
+        let entities = entities
+            .as_entity_list()
+            .ok_or_else(|| Error::InvalidFieldType(format!("Choices source '{}' is not an EntityList", choices_source)))?;
+
+        entities
+            .iter()
+            .map(|entity_id| {
+                let (name, _, _) = self.read(*entity_id, &[ft_name])?;
+                Ok(name.as_string().unwrap_or_default().to_string())
+            })
+            .collect()
+    }
+
+    /// The name of a choice value, e.g. `Value::Choice(1)` -> "LDAP"
+    fn resolve_choice(&self, entity_type: EntityType, field_type: FieldType, value: i64) -> Result<String> {
+        let choices = self.get_choices(entity_type, field_type)?;
+        usize::try_from(value)
+            .ok()
+            .and_then(|index| choices.into_iter().nth(index))
+            .ok_or_else(|| Error::InvalidFieldValue(format!("Choice {} is out of range for {:?}", value, field_type)))
+    }
+
+    /// The value of a choice given its name, e.g. "LDAP" -> 1
+    fn choice_value(&self, entity_type: EntityType, field_type: FieldType, name: &str) -> Result<i64> {
+        self.get_choices(entity_type, field_type)?
+            .iter()
+            .position(|choice| choice == name)
+            .map(|index| index as i64)
+            .ok_or_else(|| Error::InvalidFieldValue(format!("'{}' is not a choice of {:?}", name, field_type)))
+    }
+
+    /// The entity of exactly `entity_type` named `name` under `parent_id`
+    /// (or at the top level for None), created if there is none. Returns
+    /// the entity and whether it was created. If a sibling of another type
+    /// already has the name, fails with `EntityAlreadyExists` instead of
+    /// creating a second entity at the same path.
+    ///
+    /// `Store` and `SharedStore` look up and create under one lock, and
+    /// remote stores send `GET_OR_CREATE` so the server does the same, so
+    /// two services starting together end up with the same entity. This
+    /// default is only as atomic as the store it runs on.
+    fn get_or_create(&mut self, entity_type: EntityType, parent_id: Option<EntityId>, name: &str) -> Result<(EntityId, bool)> {
+        let ft_name = self.get_field_type(ft::NAME)?;
+        let siblings = match parent_id {
+            Some(parent_id) => {
+                let ft_children = self.get_field_type(ft::CHILDREN)?;
+                self.read(parent_id, &[ft_children])?.0.as_entity_list().cloned().unwrap_or_default()
+            }
+            None => {
+                let ft_parent = self.get_field_type(ft::PARENT)?;
+                let mut top_level = Vec::new();
+                for entity_id in self.find_entities(entity_type, None)? {
+                    if self.read(entity_id, &[ft_parent])?.0 == Value::EntityReference(None) {
+                        top_level.push(entity_id);
+                    }
+                }
+                top_level
+            }
+        };
+
+        for sibling in siblings {
+            if self.read(sibling, &[ft_name])?.0.as_string().map(|sibling_name| sibling_name == name) != Some(true) {
+                continue;
+            }
+            if sibling.extract_type() != entity_type {
+                return Err(Error::EntityAlreadyExists(sibling));
+            }
+            return Ok((sibling, false));
+        }
+
+        Ok((self.create_entity(entity_type, parent_id, name)?, true))
+    }
+
+    /// Write `value` to a field unless it already holds it, returning
+    /// whether it was written. Unchanged values cause no write, so no
+    /// notification and nothing to replicate, which makes startup code that
+    /// sets its configuration idempotent. Atomic like `get_or_create`, and
+    /// sent to remote stores as `UPSERT`.
+    fn upsert_field(&mut self, entity_id: EntityId, field_type: FieldType, value: Value) -> Result<bool> {
+        if self.read(entity_id, &[field_type])?.0 == value {
+            return Ok(false);
+        }
+        self.write(entity_id, &[field_type], value, None, None, None, None)?;
+        Ok(true)
+    }
+
+    /// Mark an entity as a template for `instantiate_template`, or unmark
+    /// it. Its type needs a Bool `IsTemplate` field.
+    fn mark_template(&mut self, entity_id: EntityId, is_template: bool) -> Result<()> {
+        let ft_is_template = self.get_field_type(ft::IS_TEMPLATE)?;
+        self.write(entity_id, &[ft_is_template], Value::Bool(is_template), None, None, None, None)
+    }
+
+    /// Whether an entity is marked as a template. Entities of types without
+    /// an `IsTemplate` field never are.
+    fn is_template(&self, entity_id: EntityId) -> Result<bool> {
+        let Ok(ft_is_template) = self.get_field_type(ft::IS_TEMPLATE) else {
+            return Ok(false);
+        };
+        if !self.field_exists(entity_id.extract_type(), ft_is_template) {
+            return Ok(false);
+        }
+        let (value, _, _) = self.read(entity_id, &[ft_is_template])?;
+        Ok(value.as_bool().unwrap_or(false))
+    }
+
+    /// Move an entity (and its subtree) under a new parent.
+    ///
+    /// The entity is removed from the old parent's `Children`, added to the
+    /// new parent's `Children` and its `Parent` is updated. All changes go
+    /// through `write`, so subscribers of those fields are notified.
+    /// Moving an entity under itself or one of its descendants is rejected.
+    fn move_entity(&mut self, entity_id: EntityId, new_parent: Option<EntityId>) -> Result<()> {
+        let ft_parent = self.get_field_type(ft::PARENT)?;
+        let ft_children = self.get_field_type(ft::CHILDREN)?;
+
+        let (old_parent, _, _) = self.read(entity_id, &[ft_parent])?;
+        let old_parent = old_parent.as_entity_reference().copied().flatten();
+        if old_parent == new_parent {
+            return Ok(());
+        }
+
+        if let Some(new_parent) = new_parent {
+            if is_in_subtree(self, ft_parent, new_parent, entity_id)? {
+                return Err(Error::InvalidRequest(format!(
+                    "Cannot move {:?} under its own subtree ({:?})",
+                    entity_id, new_parent
+                )));
+            }
+        }
+
+        // Parent first, so a name clash under the new parent changes nothing
+        self.write(entity_id, &[ft_parent], Value::EntityReference(new_parent), None, None, None, None)?;
+        if let Some(old_parent) = old_parent {
+            self.write(old_parent, &[ft_children], Value::EntityList(vec![entity_id]), None, None, None, Some(AdjustBehavior::Subtract))?;
+        }
+        if let Some(new_parent) = new_parent {
+            self.write(new_parent, &[ft_children], Value::EntityList(vec![entity_id]), None, None, None, Some(AdjustBehavior::Add))?;
+        }
+        Ok(())
+    }
+
+    /// Rename an entity. If its type declares `Name` unique among siblings
+    /// (`FieldSchema::with_unique_among_siblings`), a name already used by
+    /// another child of the same parent is rejected with
+    /// `EntityAlreadyExists`, as are creating or moving an entity into such
+    /// a clash. `Store` also notifies subscribers of the parent's
+    /// `Children` with the renamed entity's `Name`, so anything that looked
+    /// a child up by name can look it up again. Sent to remote stores as
+    /// `RENAME`.
+    fn rename_entity(&mut self, entity_id: EntityId, new_name: &str) -> Result<()> {
+        let ft_name = self.get_field_type(ft::NAME)?;
+        self.write(entity_id, &[ft_name], Value::String(new_name.to_string()), None, None, None, None)
+    }
+
+    /// Copy an entity and all of its descendants under a new parent.
+    ///
+    /// Field values (including inherited fields) are copied as-is, except
+    /// `Parent`/`Children` which describe the new tree and computed fields
+    /// which are derived. Entity references are not remapped, so a reference
+    /// into the original subtree still points at the original entity.
+    /// Returns the id of the new root entity.
+    fn copy_entity_tree(&mut self, entity_id: EntityId, new_parent: Option<EntityId>) -> Result<EntityId> {
+        let ft_name = self.get_field_type(ft::NAME)?;
+        let ft_parent = self.get_field_type(ft::PARENT)?;
+        let ft_children = self.get_field_type(ft::CHILDREN)?;
+
+        if let Some(new_parent) = new_parent {
+            if is_in_subtree(self, ft_parent, new_parent, entity_id)? {
+                return Err(Error::InvalidRequest(format!(
+                    "Cannot copy {:?} into its own subtree ({:?})",
+                    entity_id, new_parent
+                )));
+            }
+        }
+
+        let entity_type = entity_id.extract_type();
+        let (name, _, _) = self.read(entity_id, &[ft_name])?;
+        let copy_id = self.create_entity(entity_type, new_parent, name.as_string().unwrap_or_default())?;
+
+        for (field_type, field_schema) in collect_field_schemas(self, entity_type)? {
+            if field_type == ft_name || field_type == ft_parent || field_type == ft_children {
+                continue;
+            }
+            if matches!(field_schema, FieldSchema::Computed { .. }) {
+                continue;
+            }
+
+            let (value, _, _) = self.read(entity_id, &[field_type])?;
+            self.write(copy_id, &[field_type], value, None, None, None, None)?;
+        }
+
+        let (children, _, _) = self.read(entity_id, &[ft_children])?;
+        for child_id in children.as_entity_list().cloned().unwrap_or_default() {
+            self.copy_entity_tree(child_id, Some(copy_id))?;
+        }
+
+        Ok(copy_id)
+    }
+
+    /// Delete an entity and all of its descendants, deepest entities first.
+    ///
+    /// Each entity is deleted through `delete_entity`, so every deletion is
+    /// observed individually and the parent's `Children` stays consistent
+    /// at every step.
+    fn delete_entity_tree(&mut self, entity_id: EntityId) -> Result<()> {
+        let ft_children = self.get_field_type(ft::CHILDREN)?;
+
+        let (children, _, _) = self.read(entity_id, &[ft_children])?;
+        for child_id in children.as_entity_list().cloned().unwrap_or_default() {
+            self.delete_entity_tree(child_id)?;
+        }
+
+        self.delete_entity(entity_id)
+    }
+
+    /// Check every `EntityReference` and `EntityList` field of every entity
+    /// and report the ones pointing at entities that don't exist, e.g. left
+    /// behind by deletes before the field had an `OnDelete` rule.
+    fn find_dangling_references(&self) -> Result<Vec<DanglingReference>> {
+        let mut dangling = Vec::new();
+        for_each_reference(self, |entity_id, field_type, target| {
+            if !self.entity_exists(target) {
+                dangling.push(DanglingReference { entity_id, field_type, target });
+            }
+        })?;
+        Ok(dangling)
+    }
+
+    /// Every entity and field whose `EntityReference`/`EntityList` value
+    /// points at `entity_id`, e.g. "what points at this pump?".
+    ///
+    /// `Store` answers from a reverse index and remote stores ask the server
+    /// (`FIND_REFERENCING`); this default reads every reference field.
+    fn find_referencing(&self, entity_id: EntityId) -> Result<Vec<(EntityId, FieldType)>> {
+        let mut referencing = Vec::new();
+        for_each_reference(self, |referrer, field_type, target| {
+            if target == entity_id && !referencing.contains(&(referrer, field_type)) {
+                referencing.push((referrer, field_type));
+            }
+        })?;
+        referencing.sort();
+        Ok(referencing)
+    }
+
+    /// Count, sum or take the min/max of a field across the entities of a
+    /// type (including derived types) matching `filter`, e.g. the total
+    /// `Power` of every running pump.
+    ///
+    /// Count gives an Int and a Sum over no entities gives the field's zero;
+    /// a Min/Max over no entities gives None. Remote stores answer with one
+    /// `AGGREGATE` request instead of reading every entity.
+    fn aggregate(&self, entity_type: EntityType, field_type: FieldType, op: AggregateOp, filter: Option<&str>) -> Result<Option<Value>> {
+        let field_schema = collect_field_schemas(self, entity_type)?
+            .into_iter()
+            .find(|(ft, _)| *ft == field_type)
+            .map(|(_, field_schema)| field_schema)
+            .ok_or_else(|| Error::FieldTypeNotFound(EntityId::new(entity_type, 0), field_type))?;
+
+        let mut aggregator = Aggregator::new(op, zero_of(&field_schema.default_value()));
+        for entity_id in self.find_entities(entity_type, filter)? {
+            let value = match op {
+                // The value isn't needed to count
+                AggregateOp::Count => Value::Int(0),
+                _ => self.read(entity_id, &[field_type])?.0,
+            };
+            aggregator.add(value)?;
+        }
+        Ok(aggregator.finish())
+    }
+}
+
+/// Call `f(entity, field, target)` for every entity id held by a reference
+/// field of any entity
+fn for_each_reference<S: StoreTrait + ?Sized>(store: &S, mut f: impl FnMut(EntityId, FieldType, EntityId)) -> Result<()> {
+    for entity_type in store.get_entity_types()? {
+        let reference_fields: Vec<FieldType> = collect_field_schemas(store, entity_type)?
+            .into_iter()
+            .filter(|(_, schema)| matches!(schema, FieldSchema::EntityReference { .. } | FieldSchema::EntityList { .. }))
+            .map(|(field_type, _)| field_type)
+            .collect();
+        if reference_fields.is_empty() {
+            continue;
+        }
+
+        // Derived types are checked on their own turn
+        for entity_id in store.find_entities(entity_type, None)? {
+            if entity_id.extract_type() != entity_type {
+                continue;
+            }
+            for field_type in &reference_fields {
+                let (value, _, _) = store.read(entity_id, &[*field_type])?;
+                let targets = match value {
+                    Value::EntityReference(target) => target.into_iter().collect(),
+                    Value::EntityList(targets) => targets,
+                    _ => Vec::new(),
+                };
+                for target in targets {
+                    f(entity_id, *field_type, target);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Whether `entity_id` is `root_id` or one of its descendants
+fn is_in_subtree<S: StoreTrait + ?Sized>(store: &S, ft_parent: FieldType, entity_id: EntityId, root_id: EntityId) -> Result<bool> {
+    let mut current = Some(entity_id);
+    while let Some(id) = current {
+        if id == root_id {
+            return Ok(true);
+        }
+        let (parent, _, _) = store.read(id, &[ft_parent])?;
+        current = parent.as_entity_reference().copied().flatten();
+    }
+    Ok(false)
+}
+
+/// Field schemas of an entity type including the ones it inherits.
+///
+/// Built from `get_entity_schema` rather than `get_complete_entity_schema`
+/// so it works for stores that can't hand out a reference to a cached
+/// complete schema (e.g. `StoreProxy`).
+pub(crate) fn collect_field_schemas<S: StoreTrait + ?Sized>(store: &S, entity_type: EntityType) -> Result<Vec<(FieldType, FieldSchema)>> {
+    let mut fields: Vec<(FieldType, FieldSchema)> = Vec::new();
+    let mut pending = vec![entity_type];
+    let mut visited = Vec::new();
+
+    while let Some(entity_type) = pending.pop() {
+        if visited.contains(&entity_type) {
+            continue;
+        }
+        visited.push(entity_type);
+
+        let schema = store.get_entity_schema(entity_type)?;
+        for (field_type, field_schema) in schema.fields {
+            // Fields of the derived type override inherited ones
+            if !fields.iter().any(|(existing, _)| *existing == field_type) {
+                fields.push((field_type, field_schema));
+            }
+        }
+        pending.extend(schema.inherit.into_iter().rev());
+    }
+
+    Ok(fields)
+}
@@ -0,0 +1,673 @@
+//! Helpers for testing code that talks to a store, without a running
+//! server: a scriptable `MockStore`, a `SchemaBuilder` for schema fixtures
+//! and a `TestCluster` of replicated in-memory stores.
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::Duration as StdDuration;
+
+use rustc_hash::FxHashMap;
+
+use crate::data::StorageScope;
+use crate::{
+    epoch, AdjustBehavior, Complete, EntityId, EntitySchema, EntityType, Error, FieldSchema, FieldType, MergePolicy, PageOpts, PageResult,
+    PeerReplicator, PushCondition, Result, Single, Snapshot, Store, StoreStats, StoreTrait, Timestamp, Value,
+};
+
+/// A `StoreTrait` method, as scripted and recorded by `MockStore`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MockMethod {
+    GetEntityType,
+    ResolveEntityType,
+    GetFieldType,
+    ResolveFieldType,
+    GetEntitySchema,
+    GetCompleteEntitySchema,
+    GetFieldSchema,
+    SetFieldSchema,
+    EntityExists,
+    FieldExists,
+    ResolveIndirection,
+    Read,
+    Write,
+    CreateEntity,
+    DeleteEntity,
+    UpdateSchema,
+    TakeSnapshot,
+    Stats,
+    FindEntitiesPaginated,
+    FindEntitiesExact,
+    FindEntities,
+    GetEntityTypes,
+    GetEntityTypesPaginated,
+}
+
+/// What `read` returns: the value, when it was written and by whom
+type ReadAnswer = (Value, Timestamp, Option<EntityId>);
+
+#[derive(Debug, Default)]
+struct Script {
+    calls: Vec<MockMethod>,
+    failures: FxHashMap<MockMethod, Error>,
+    next_failures: FxHashMap<MockMethod, VecDeque<Error>>,
+    latencies: FxHashMap<MockMethod, StdDuration>,
+    reads: FxHashMap<(EntityId, Vec<FieldType>), ReadAnswer>,
+    finds: FxHashMap<EntityType, Vec<EntityId>>,
+}
+
+/// A `StoreTrait` implementation whose answers can be scripted, for unit
+/// tests of code written against the trait.
+///
+/// Calls go to an in-memory `Store` unless scripted otherwise: a method can
+/// be made to fail (always or for the next few calls), to take a while, and
+/// reads and finds can be given canned answers. Every call is recorded.
+/// Failures only apply to methods returning a `Result`.
+///
+/// ```rust,ignore
+/// let mut mock = MockStore::new();
+/// mock.store_mut().update_schema(SchemaBuilder::object("Pump").int("Speed", 0).build())?;
+/// mock.respond_read(pump, &[ft_speed], Value::Int(1500));
+/// mock.fail_next(MockMethod::Write, Error::Timeout(Duration::from_secs(1)));
+///
+/// assert!(controller.apply(&mut mock).is_err());
+/// assert_eq!(mock.call_count(MockMethod::Write), 1);
+/// ```
+#[derive(Debug)]
+pub struct MockStore {
+    store: Store,
+    script: Mutex<Script>,
+}
+
+impl Default for MockStore {
+    fn default() -> Self {
+        Self::with_store(Store::new())
+    }
+}
+
+impl MockStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Answer unscripted calls from `store`, e.g. one restored from a
+    /// snapshot fixture
+    pub fn with_store(store: Store) -> Self {
+        Self {
+            store,
+            script: Mutex::default(),
+        }
+    }
+
+    /// The store answering unscripted calls; seed it with schemas and data
+    pub fn store(&self) -> &Store {
+        &self.store
+    }
+
+    pub fn store_mut(&mut self) -> &mut Store {
+        &mut self.store
+    }
+
+    fn script(&self) -> std::sync::MutexGuard<'_, Script> {
+        self.script.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Make every call of `method` fail with `error`
+    pub fn fail(&self, method: MockMethod, error: Error) {
+        self.script().failures.insert(method, error);
+    }
+
+    /// Make the next call of `method` fail with `error`. Queued failures
+    /// are used one per call, before any set with `fail`.
+    pub fn fail_next(&self, method: MockMethod, error: Error) {
+        self.script().next_failures.entry(method).or_default().push_back(error);
+    }
+
+    /// Make every call of `method` take at least `latency`
+    pub fn set_latency(&self, method: MockMethod, latency: StdDuration) {
+        self.script().latencies.insert(method, latency);
+    }
+
+    /// Answer reads of `field_path` on `entity_id` with `value`, written
+    /// at the epoch by no one
+    pub fn respond_read(&self, entity_id: EntityId, field_path: &[FieldType], value: Value) {
+        self.respond_read_with(entity_id, field_path, (value, epoch(), None));
+    }
+
+    /// Answer reads of `field_path` on `entity_id` with `response`
+    pub fn respond_read_with(&self, entity_id: EntityId, field_path: &[FieldType], response: ReadAnswer) {
+        self.script().reads.insert((entity_id, field_path.to_vec()), response);
+    }
+
+    /// Answer `find_entities` for `entity_type` with `entities`, whatever
+    /// the filter
+    pub fn respond_find(&self, entity_type: EntityType, entities: Vec<EntityId>) {
+        self.script().finds.insert(entity_type, entities);
+    }
+
+    /// Drop every scripted failure, latency and answer. Recorded calls are
+    /// kept.
+    pub fn clear_script(&self) {
+        let mut script = self.script();
+        let calls = std::mem::take(&mut script.calls);
+        *script = Script { calls, ..Script::default() };
+    }
+
+    /// Every call made so far, in order
+    pub fn calls(&self) -> Vec<MockMethod> {
+        self.script().calls.clone()
+    }
+
+    pub fn call_count(&self, method: MockMethod) -> usize {
+        self.script().calls.iter().filter(|call| **call == method).count()
+    }
+
+    pub fn clear_calls(&self) {
+        self.script().calls.clear();
+    }
+
+    /// Record a call, wait out its latency and return its scripted failure
+    fn enter(&self, method: MockMethod) -> Result<()> {
+        let (latency, failure) = {
+            let mut script = self.script();
+            script.calls.push(method);
+            let failure = match script.next_failures.get_mut(&method).and_then(|queue| queue.pop_front()) {
+                Some(error) => Some(error),
+                None => script.failures.get(&method).cloned(),
+            };
+            (script.latencies.get(&method).copied(), failure)
+        };
+
+        if let Some(latency) = latency {
+            std::thread::sleep(latency);
+        }
+        failure.map_or(Ok(()), Err)
+    }
+
+    /// `enter` for methods that can't fail
+    fn enter_infallible(&self, method: MockMethod) {
+        let _ = self.enter(method);
+    }
+}
+
+impl StoreTrait for MockStore {
+    fn get_entity_type(&self, name: &str) -> Result<EntityType> {
+        self.enter(MockMethod::GetEntityType)?;
+        self.store.get_entity_type(name)
+    }
+
+    fn resolve_entity_type(&self, entity_type: EntityType) -> Result<String> {
+        self.enter(MockMethod::ResolveEntityType)?;
+        self.store.resolve_entity_type(entity_type)
+    }
+
+    fn get_field_type(&self, name: &str) -> Result<FieldType> {
+        self.enter(MockMethod::GetFieldType)?;
+        self.store.get_field_type(name)
+    }
+
+    fn resolve_field_type(&self, field_type: FieldType) -> Result<String> {
+        self.enter(MockMethod::ResolveFieldType)?;
+        self.store.resolve_field_type(field_type)
+    }
+
+    fn get_entity_schema(&self, entity_type: EntityType) -> Result<EntitySchema<Single>> {
+        self.enter(MockMethod::GetEntitySchema)?;
+        self.store.get_entity_schema(entity_type)
+    }
+
+    fn get_complete_entity_schema(&self, entity_type: EntityType) -> Result<&EntitySchema<Complete>> {
+        self.enter(MockMethod::GetCompleteEntitySchema)?;
+        self.store.get_complete_entity_schema(entity_type)
+    }
+
+    fn get_field_schema(&self, entity_type: EntityType, field_type: FieldType) -> Result<FieldSchema> {
+        self.enter(MockMethod::GetFieldSchema)?;
+        self.store.get_field_schema(entity_type, field_type)
+    }
+
+    fn set_field_schema(&mut self, entity_type: EntityType, field_type: FieldType, schema: FieldSchema) -> Result<()> {
+        self.enter(MockMethod::SetFieldSchema)?;
+        self.store.set_field_schema(entity_type, field_type, schema)
+    }
+
+    fn entity_exists(&self, entity_id: EntityId) -> bool {
+        self.enter_infallible(MockMethod::EntityExists);
+        self.store.entity_exists(entity_id)
+    }
+
+    fn field_exists(&self, entity_type: EntityType, field_type: FieldType) -> bool {
+        self.enter_infallible(MockMethod::FieldExists);
+        self.store.field_exists(entity_type, field_type)
+    }
+
+    fn resolve_indirection(&self, entity_id: EntityId, fields: &[FieldType]) -> Result<(EntityId, FieldType)> {
+        self.enter(MockMethod::ResolveIndirection)?;
+        self.store.resolve_indirection(entity_id, fields)
+    }
+
+    fn read(&self, entity_id: EntityId, field_path: &[FieldType]) -> Result<(Value, Timestamp, Option<EntityId>)> {
+        self.enter(MockMethod::Read)?;
+        if let Some(response) = self.script().reads.get(&(entity_id, field_path.to_vec())) {
+            return Ok(response.clone());
+        }
+        self.store.read(entity_id, field_path)
+    }
+
+    fn write(&mut self, entity_id: EntityId, field_path: &[FieldType], value: Value, writer_id: Option<EntityId>, write_time: Option<Timestamp>, push_condition: Option<PushCondition>, adjust_behavior: Option<AdjustBehavior>) -> Result<()> {
+        self.enter(MockMethod::Write)?;
+        self.store.write(entity_id, field_path, value, writer_id, write_time, push_condition, adjust_behavior)
+    }
+
+    fn create_entity(&mut self, entity_type: EntityType, parent_id: Option<EntityId>, name: &str) -> Result<EntityId> {
+        self.enter(MockMethod::CreateEntity)?;
+        self.store.create_entity(entity_type, parent_id, name)
+    }
+
+    fn delete_entity(&mut self, entity_id: EntityId) -> Result<()> {
+        self.enter(MockMethod::DeleteEntity)?;
+        self.store.delete_entity(entity_id)
+    }
+
+    fn update_schema(&mut self, schema: EntitySchema<Single, String, String>) -> Result<()> {
+        self.enter(MockMethod::UpdateSchema)?;
+        self.store.update_schema(schema)
+    }
+
+    fn take_snapshot(&self) -> Snapshot {
+        self.enter_infallible(MockMethod::TakeSnapshot);
+        self.store.take_snapshot()
+    }
+
+    fn stats(&self) -> Result<StoreStats> {
+        self.enter(MockMethod::Stats)?;
+        Ok(self.store.stats())
+    }
+
+    fn find_entities_paginated(&self, entity_type: EntityType, page_opts: Option<&PageOpts>, filter: Option<&str>) -> Result<PageResult<EntityId>> {
+        self.enter(MockMethod::FindEntitiesPaginated)?;
+        self.store.find_entities_paginated(entity_type, page_opts, filter)
+    }
+
+    fn find_entities_exact(&self, entity_type: EntityType, page_opts: Option<&PageOpts>, filter: Option<&str>) -> Result<PageResult<EntityId>> {
+        self.enter(MockMethod::FindEntitiesExact)?;
+        self.store.find_entities_exact(entity_type, page_opts, filter)
+    }
+
+    fn find_entities(&self, entity_type: EntityType, filter: Option<&str>) -> Result<Vec<EntityId>> {
+        self.enter(MockMethod::FindEntities)?;
+        if let Some(entities) = self.script().finds.get(&entity_type) {
+            return Ok(entities.clone());
+        }
+        self.store.find_entities(entity_type, filter)
+    }
+
+    fn get_entity_types(&self) -> Result<Vec<EntityType>> {
+        self.enter(MockMethod::GetEntityTypes)?;
+        self.store.get_entity_types()
+    }
+
+    fn get_entity_types_paginated(&self, page_opts: Option<&PageOpts>) -> Result<PageResult<EntityType>> {
+        self.enter(MockMethod::GetEntityTypesPaginated)?;
+        self.store.get_entity_types_paginated(page_opts)
+    }
+}
+
+/// Builds entity schemas for test fixtures. Fields are ranked in the order
+/// they are added, stored as configuration and merged with the default
+/// policy; use `field` for anything else.
+///
+/// ```rust,ignore
+/// store.update_schema(SchemaBuilder::object("Pump").int("Speed", 0).bool("Running", false).build())?;
+/// SchemaBuilder::new("Vfd").inherits("Pump").float("Frequency", 50.0).apply(&mut store)?;
+/// ```
+#[derive(Debug, Clone)]
+pub struct SchemaBuilder {
+    schema: EntitySchema<Single, String, String>,
+    next_rank: i64,
+}
+
+impl SchemaBuilder {
+    /// A schema without any fields
+    pub fn new(entity_type: &str) -> Self {
+        Self {
+            schema: EntitySchema::<Single, String, String>::new(entity_type.to_string(), vec![]),
+            next_rank: 0,
+        }
+    }
+
+    /// A schema with the `Name`, `Parent` and `Children` fields entities
+    /// need to be part of the tree
+    pub fn object(entity_type: &str) -> Self {
+        Self::new(entity_type)
+            .string(crate::ft::NAME, "")
+            .entity_reference(crate::ft::PARENT)
+            .entity_list(crate::ft::CHILDREN)
+    }
+
+    pub fn inherits(mut self, entity_type: &str) -> Self {
+        self.schema.inherit.push(entity_type.to_string());
+        self
+    }
+
+    /// Add a field schema as is
+    pub fn field(mut self, field_schema: FieldSchema<String>) -> Self {
+        self.next_rank = self.next_rank.max(field_schema.rank() + 1);
+        self.schema.fields.insert(field_schema.field_type(), field_schema);
+        self
+    }
+
+    /// Store the field added as `name` at runtime instead
+    pub fn runtime(self, name: &str) -> Self {
+        self.modify(name, |field_schema| field_schema.with_storage_scope(StorageScope::Runtime))
+    }
+
+    /// Merge the field added as `name` with `merge_policy`
+    pub fn merge_policy(self, name: &str, merge_policy: MergePolicy) -> Self {
+        self.modify(name, |field_schema| field_schema.with_merge_policy(merge_policy))
+    }
+
+    fn modify(mut self, name: &str, change: impl FnOnce(FieldSchema<String>) -> FieldSchema<String>) -> Self {
+        if let Some(field_schema) = self.schema.fields.remove(name) {
+            self.schema.fields.insert(name.to_string(), change(field_schema));
+        }
+        self
+    }
+
+    fn rank(&mut self) -> i64 {
+        self.next_rank += 1;
+        self.next_rank - 1
+    }
+
+    pub fn string(mut self, name: &str, default_value: &str) -> Self {
+        let rank = self.rank();
+        self.field(FieldSchema::String {
+            field_type: name.to_string(),
+            default_value: default_value.to_string(),
+            rank,
+            storage_scope: StorageScope::Configuration,
+            validator: None,
+            metadata: Default::default(),
+        })
+    }
+
+    pub fn int(mut self, name: &str, default_value: i64) -> Self {
+        let rank = self.rank();
+        self.field(FieldSchema::Int {
+            field_type: name.to_string(),
+            default_value,
+            rank,
+            storage_scope: StorageScope::Configuration,
+            merge_policy: MergePolicy::default(),
+            validator: None,
+            metadata: Default::default(),
+        })
+    }
+
+    pub fn float(mut self, name: &str, default_value: f64) -> Self {
+        let rank = self.rank();
+        self.field(FieldSchema::Float {
+            field_type: name.to_string(),
+            default_value,
+            rank,
+            storage_scope: StorageScope::Configuration,
+            merge_policy: MergePolicy::default(),
+            validator: None,
+            metadata: Default::default(),
+        })
+    }
+
+    pub fn bool(mut self, name: &str, default_value: bool) -> Self {
+        let rank = self.rank();
+        self.field(FieldSchema::Bool {
+            field_type: name.to_string(),
+            default_value,
+            rank,
+            storage_scope: StorageScope::Configuration,
+            validator: None,
+            metadata: Default::default(),
+        })
+    }
+
+    pub fn timestamp(mut self, name: &str) -> Self {
+        let rank = self.rank();
+        self.field(FieldSchema::Timestamp {
+            field_type: name.to_string(),
+            default_value: epoch(),
+            rank,
+            storage_scope: StorageScope::Configuration,
+            validator: None,
+            metadata: Default::default(),
+        })
+    }
+
+    pub fn blob(mut self, name: &str) -> Self {
+        let rank = self.rank();
+        self.field(FieldSchema::Blob {
+            field_type: name.to_string(),
+            default_value: vec![],
+            rank,
+            storage_scope: StorageScope::Configuration,
+            validator: None,
+            metadata: Default::default(),
+        })
+    }
+
+    /// A Choice field defaulting to the first choice
+    pub fn choice(mut self, name: &str, choices: &[&str]) -> Self {
+        let rank = self.rank();
+        self.field(FieldSchema::Choice {
+            field_type: name.to_string(),
+            default_value: 0,
+            rank,
+            choices: choices.iter().map(|choice| choice.to_string()).collect(),
+            choices_source: None,
+            storage_scope: StorageScope::Configuration,
+            validator: None,
+            metadata: Default::default(),
+        })
+    }
+
+    pub fn entity_reference(mut self, name: &str) -> Self {
+        let rank = self.rank();
+        self.field(FieldSchema::EntityReference {
+            field_type: name.to_string(),
+            default_value: None,
+            rank,
+            storage_scope: StorageScope::Configuration,
+            validator: None,
+            metadata: Default::default(),
+        })
+    }
+
+    pub fn entity_list(mut self, name: &str) -> Self {
+        let rank = self.rank();
+        self.field(FieldSchema::EntityList {
+            field_type: name.to_string(),
+            default_value: vec![],
+            rank,
+            storage_scope: StorageScope::Configuration,
+            merge_policy: MergePolicy::default(),
+            validator: None,
+            metadata: Default::default(),
+        })
+    }
+
+    pub fn build(self) -> EntitySchema<Single, String, String> {
+        self.schema
+    }
+
+    /// Add the schema to `store` and return its entity type
+    pub fn apply(self, store: &mut impl StoreTrait) -> Result<EntityType> {
+        let entity_type = self.schema.entity_type.clone();
+        store.update_schema(self.build())?;
+        store.get_entity_type(&entity_type)
+    }
+}
+
+#[derive(Debug)]
+struct TestNode {
+    store: Store,
+    replicator: PeerReplicator,
+    connected: bool,
+}
+
+/// A cluster of in-memory stores replicating with `PeerReplicator`, for
+/// deterministic tests of multi-node behaviour. It doesn't run raft or any
+/// other consensus: there is no leader election or log, and every node
+/// accepts writes, which peers merge as `PeerReplicator` does. Nothing moves
+/// between nodes until `sync` is called, and nodes can be cut off with
+/// `partition` and brought back with `heal`.
+///
+/// Node `i` is named `node-i` and started before node `i + 1`, so node 0 is
+/// the source of truth whenever the replicator calls for a full sync.
+///
+/// ```rust,ignore
+/// let mut cluster = TestCluster::new(3)?;
+/// let et_pump = cluster.update_schema(SchemaBuilder::object("Pump").int("Speed", 0).build())?;
+/// let pump = cluster.store_mut(0).create_entity(et_pump, None, "P1")?;
+/// cluster.sync()?;
+/// assert!(cluster.store(2).entity_exists(pump));
+/// ```
+#[derive(Debug)]
+pub struct TestCluster {
+    nodes: Vec<TestNode>,
+}
+
+impl TestCluster {
+    /// `size` empty nodes, connected to each other
+    pub fn new(size: usize) -> Result<Self> {
+        let mut cluster = Self {
+            nodes: (0..size)
+                .map(|index| TestNode {
+                    store: Store::new(),
+                    replicator: PeerReplicator::new(Self::machine_id(index), index as u64 + 1),
+                    connected: true,
+                })
+                .collect(),
+        };
+        for first in 0..size {
+            for second in first + 1..size {
+                cluster.connect(first, second)?;
+            }
+        }
+        Ok(cluster)
+    }
+
+    pub fn machine_id(index: usize) -> String {
+        format!("node-{}", index)
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// The store of node `index`; panics if there is no such node
+    pub fn store(&self, index: usize) -> &Store {
+        &self.nodes[index].store
+    }
+
+    pub fn store_mut(&mut self, index: usize) -> &mut Store {
+        &mut self.nodes[index].store
+    }
+
+    pub fn replicator(&self, index: usize) -> &PeerReplicator {
+        &self.nodes[index].replicator
+    }
+
+    pub fn is_connected(&self, index: usize) -> bool {
+        self.nodes[index].connected
+    }
+
+    /// Add or update a schema on every node, as if each loaded the same
+    /// bootstrap schema. Replicated schema updates carry type ids only, so
+    /// a node can't apply one for a type it has never seen.
+    pub fn update_schema(&mut self, schema: EntitySchema<Single, String, String>) -> Result<EntityType> {
+        for node in &mut self.nodes {
+            node.store.update_schema(schema.clone())?;
+        }
+        match self.nodes.first() {
+            Some(node) => node.store.get_entity_type(&schema.entity_type),
+            None => Err(Error::EntityTypeStrNotFound(schema.entity_type)),
+        }
+    }
+
+    /// Send every connected node's pending writes to the other connected
+    /// nodes. A partitioned node keeps its writes until it is healed.
+    /// Returns the number of batches sent.
+    pub fn sync(&mut self) -> Result<usize> {
+        let mut batches = 0;
+        for source in 0..self.nodes.len() {
+            if !self.nodes[source].connected {
+                continue;
+            }
+            let node = &mut self.nodes[source];
+            let Some(batch) = node.replicator.drain_sync_writes(&mut node.store)? else {
+                continue;
+            };
+            batches += 1;
+
+            let machine_id = Self::machine_id(source);
+            for target in self.nodes.iter_mut().enumerate().filter(|(index, node)| *index != source && node.connected).map(|(_, node)| node) {
+                target.replicator.apply_sync_writes(&machine_id, &mut target.store, &batch)?;
+            }
+        }
+        Ok(batches)
+    }
+
+    /// Cut node `index` off from the others, as if its connections dropped
+    pub fn partition(&mut self, index: usize) {
+        let machine_id = Self::machine_id(index);
+        self.nodes[index].connected = false;
+        for other in 0..self.nodes.len() {
+            if other != index {
+                let other_id = Self::machine_id(other);
+                self.nodes[other].replicator.remove_peer(&machine_id);
+                self.nodes[index].replicator.remove_peer(&other_id);
+            }
+        }
+    }
+
+    /// Reconnect node `index`. It shakes hands with every connected node
+    /// again and, as with real peers, the younger side of each pair takes a
+    /// full sync from the older one, dropping its unsent writes.
+    pub fn heal(&mut self, index: usize) -> Result<()> {
+        if self.nodes[index].connected {
+            return Ok(());
+        }
+        self.nodes[index].connected = true;
+        for other in 0..self.nodes.len() {
+            if other != index && self.nodes[other].connected {
+                self.connect(index, other)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Run the handshake between two nodes, then the full sync it calls for
+    fn connect(&mut self, first: usize, second: usize) -> Result<()> {
+        let handshake = self.nodes[first].replicator.handshake();
+        let (response, second_syncs) = self.nodes[second].replicator.handle_handshake(&handshake);
+        let first_syncs = match response {
+            Some(response) => self.nodes[first].replicator.handle_handshake(&response).1,
+            None => None,
+        };
+
+        if first_syncs.is_some() {
+            self.full_sync(second, first)?;
+        }
+        if second_syncs.is_some() {
+            self.full_sync(first, second)?;
+        }
+        Ok(())
+    }
+
+    fn full_sync(&mut self, source: usize, target: usize) -> Result<()> {
+        let response = self.nodes[source].replicator.full_sync_response(&self.nodes[source].store)?;
+        let node = &mut self.nodes[target];
+        node.replicator.apply_full_sync(&Self::machine_id(source), &mut node.store, &response)
+    }
+}
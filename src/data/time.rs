@@ -0,0 +1,98 @@
+use serde::{Deserialize, Serialize};
+use qlib_rs_derive::{RespDecode, RespEncode};
+
+use crate::{epoch, nanos_to_timestamp, now, Duration, Timestamp};
+
+/// Skew between two peers' clocks above which `PeerReplicator` warns
+pub const DEFAULT_MAX_CLOCK_SKEW: Duration = Duration::milliseconds(500);
+
+/// A reading of a `HybridClock`: the latest physical time the clock has
+/// seen, in nanoseconds since the epoch, and a counter ordering the events
+/// that share it. Readings order causally, so a write made after another
+/// was received reads later even if the local wall clock is behind.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, RespEncode, RespDecode)]
+pub struct HlcTimestamp {
+    pub wall: u64,
+    pub logical: u64,
+}
+
+impl HlcTimestamp {
+    /// A reading at `timestamp` with no logical part
+    pub fn from_timestamp(timestamp: Timestamp) -> Self {
+        Self {
+            wall: timestamp_to_nanos(timestamp),
+            logical: 0,
+        }
+    }
+
+    /// The physical part, e.g. to use as a field's write time
+    pub fn to_timestamp(&self) -> Timestamp {
+        nanos_to_timestamp(self.wall)
+    }
+}
+
+fn timestamp_to_nanos(timestamp: Timestamp) -> u64 {
+    (timestamp - epoch()).whole_nanoseconds().clamp(0, u64::MAX as i128) as u64
+}
+
+/// A hybrid logical clock. It follows the wall clock but never goes back,
+/// and it moves past every reading it receives from other machines, so
+/// writes stamped with it keep their causal order across machines whose
+/// clocks disagree.
+///
+/// Stamp local events with `now` and pass the readings that come with
+/// remote events to `update`. `Store::enable_hlc` does both for writes.
+#[derive(Debug, Clone, Default)]
+pub struct HybridClock {
+    last: HlcTimestamp,
+}
+
+impl HybridClock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read the clock for a local event
+    pub fn now(&mut self) -> HlcTimestamp {
+        self.now_at(now())
+    }
+
+    /// `now` with the wall clock reading `physical`
+    pub fn now_at(&mut self, physical: Timestamp) -> HlcTimestamp {
+        let physical = timestamp_to_nanos(physical);
+        if physical > self.last.wall {
+            self.last = HlcTimestamp { wall: physical, logical: 0 };
+        } else {
+            self.last.logical += 1;
+        }
+        self.last
+    }
+
+    /// Move the clock past `remote`, read from another machine's clock, and
+    /// read it for receiving the event
+    pub fn update(&mut self, remote: HlcTimestamp) -> HlcTimestamp {
+        self.update_at(remote, now())
+    }
+
+    /// `update` with the wall clock reading `physical`
+    pub fn update_at(&mut self, remote: HlcTimestamp, physical: Timestamp) -> HlcTimestamp {
+        let physical = timestamp_to_nanos(physical);
+        let wall = physical.max(self.last.wall).max(remote.wall);
+        let logical = if wall == self.last.wall && wall == remote.wall {
+            self.last.logical.max(remote.logical) + 1
+        } else if wall == self.last.wall {
+            self.last.logical + 1
+        } else if wall == remote.wall {
+            remote.logical + 1
+        } else {
+            0
+        };
+        self.last = HlcTimestamp { wall, logical };
+        self.last
+    }
+
+    /// The last reading handed out
+    pub fn last(&self) -> HlcTimestamp {
+        self.last
+    }
+}
@@ -0,0 +1,46 @@
+use crate::{EntityId, EntityType, FieldType};
+
+/// The `Trigger` entity that defines a trigger, as returned by
+/// `Store::register_trigger`
+pub type TriggerId = EntityId;
+
+/// Maximum number of nested trigger actions a single write can cause.
+/// Exceeding it fails the write that started the cascade.
+pub const MAX_TRIGGER_DEPTH: usize = 8;
+
+/// What a trigger does once its condition holds
+#[derive(Debug, Clone, PartialEq)]
+pub enum TriggerAction {
+    /// Evaluate `expression` against the written entity and write the
+    /// result to `field_path` (relative to the same entity)
+    Write {
+        field_path: Vec<FieldType>,
+        expression: String,
+    },
+}
+
+/// A reaction to writes on a field, evaluated by the `Store` after the
+/// write has been applied. If a trigger fails, the write and every write
+/// its triggers made are undone and the error is returned.
+///
+/// Triggers are stored as entities of type `Trigger`, so they are part of
+/// snapshots, replicate like other entities and can be managed remotely by
+/// creating, writing or deleting them. Their String fields hold:
+/// `ResourceType` and `ResourceField`, the names of the watched entity type
+/// and field; `Condition`, empty to always run; `TargetField`, the field
+/// path written, with `->` for indirection; and `Action`, the expression
+/// whose result is written. Entities whose names don't resolve are ignored.
+///
+/// Both the condition and the action expression can refer to the fields of
+/// the written entity, as well as `old` and `new` for the field's previous
+/// and current value. Triggers registered on a base type also fire for
+/// entities of derived types.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trigger {
+    pub entity_type: EntityType,
+    pub field_type: FieldType,
+    /// CEL expression that must evaluate to true for the action to run.
+    /// The action always runs if no condition is given.
+    pub condition: Option<String>,
+    pub action: TriggerAction,
+}
@@ -0,0 +1,142 @@
+use std::sync::RwLock;
+
+use rustc_hash::FxHashMap;
+
+use crate::data::StoreTrait;
+use crate::{EntityType, Error, FieldType, Result};
+
+/// A typed set of field types, resolved together in one lookup.
+///
+/// Usually derived rather than implemented by hand. Each struct field is a
+/// `FieldType` (required) or an `Option<FieldType>` (left `None` when the
+/// store doesn't know the name). Names default to the field name in
+/// PascalCase, the same convention as the constants in `ft`:
+///
+/// ```rust,ignore
+/// #[derive(FieldTypes)]
+/// pub struct PumpFields {
+///     pub speed: FieldType,                 // "Speed"
+///     #[field_type(name = "RPMSetpoint")]
+///     pub setpoint: Option<FieldType>,
+/// }
+///
+/// let fields = PumpFields::resolve(&store)?;
+/// ```
+pub trait FieldTypes: Sized {
+    /// The field names, in struct order
+    const NAMES: &'static [&'static str];
+
+    /// Build the set from the ids of `NAMES`, in the same order
+    fn from_field_types(ids: &[Option<FieldType>]) -> Result<Self>;
+
+    /// Resolve every field of the set in one round trip
+    fn resolve(store: &impl StoreTrait) -> Result<Self> {
+        Self::from_field_types(&store.get_field_types(Self::NAMES)?)
+    }
+}
+
+/// Resolves entity and field type names on first use and remembers them.
+///
+/// The fixed `et::ET` and `ft::FT` structs only know the types qlib itself
+/// uses; a registry works for any name. Ids never change once assigned (see
+/// `Interner`), so a resolved name is cached for the registry's lifetime.
+/// Names the store doesn't know aren't cached and are looked up again next
+/// time, since the schema may have been added in the meantime.
+///
+/// A registry is `Sync`, so one instance can be shared between tasks.
+#[derive(Debug, Default)]
+pub struct TypeRegistry {
+    entity_types: RwLock<FxHashMap<String, EntityType>>,
+    field_types: RwLock<FxHashMap<String, FieldType>>,
+}
+
+impl TypeRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get a field type by name, asking the store only the first time
+    pub fn get(&self, store: &impl StoreTrait, name: &str) -> Result<FieldType> {
+        if let Some(field_type) = self.field_types.read().unwrap().get(name) {
+            return Ok(*field_type);
+        }
+
+        let field_type = store.get_field_type(name)?;
+        self.field_types.write().unwrap().insert(name.to_string(), field_type);
+        Ok(field_type)
+    }
+
+    /// Get an entity type by name, asking the store only the first time
+    pub fn entity_type(&self, store: &impl StoreTrait, name: &str) -> Result<EntityType> {
+        if let Some(entity_type) = self.entity_types.read().unwrap().get(name) {
+            return Ok(*entity_type);
+        }
+
+        let entity_type = store.get_entity_type(name)?;
+        self.entity_types.write().unwrap().insert(name.to_string(), entity_type);
+        Ok(entity_type)
+    }
+
+    /// Resolve a typed field set. Names that are already cached are reused
+    /// and the rest are fetched in a single bulk lookup.
+    pub fn field_set<T: FieldTypes>(&self, store: &impl StoreTrait) -> Result<T> {
+        let missing: Vec<&str> = {
+            let cached = self.field_types.read().unwrap();
+            T::NAMES.iter().copied().filter(|name| !cached.contains_key(*name)).collect()
+        };
+        if !missing.is_empty() {
+            self.preload(store, &[], &missing)?;
+        }
+
+        let cached = self.field_types.read().unwrap();
+        let ids: Vec<Option<FieldType>> = T::NAMES.iter().map(|name| cached.get(*name).copied()).collect();
+        T::from_field_types(&ids)
+    }
+
+    /// Resolve and cache many names in one round trip, e.g. at startup.
+    /// Unknown names are skipped.
+    pub fn preload(&self, store: &impl StoreTrait, entity_types: &[&str], field_types: &[&str]) -> Result<()> {
+        let (entity_ids, field_ids) = store.get_types_bulk(entity_types, field_types)?;
+
+        let mut cached = self.entity_types.write().unwrap();
+        for (name, id) in entity_types.iter().zip(entity_ids) {
+            if let Some(id) = id {
+                cached.insert(name.to_string(), id);
+            }
+        }
+        drop(cached);
+
+        let mut cached = self.field_types.write().unwrap();
+        for (name, id) in field_types.iter().zip(field_ids) {
+            if let Some(id) = id {
+                cached.insert(name.to_string(), id);
+            }
+        }
+        Ok(())
+    }
+
+    /// Number of cached names (entity and field types)
+    pub fn len(&self) -> usize {
+        self.entity_types.read().unwrap().len() + self.field_types.read().unwrap().len()
+    }
+
+    /// Check if nothing has been resolved yet
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Forget every cached id, e.g. after connecting to a different store
+    pub fn clear(&self) {
+        self.entity_types.write().unwrap().clear();
+        self.field_types.write().unwrap().clear();
+    }
+}
+
+/// Used by `#[derive(FieldTypes)]` for required fields
+#[doc(hidden)]
+pub fn required_field_type(ids: &[Option<FieldType>], index: usize, name: &str) -> Result<FieldType> {
+    ids.get(index)
+        .copied()
+        .flatten()
+        .ok_or_else(|| Error::FieldTypeStrNotFound(name.to_string()))
+}
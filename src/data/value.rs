@@ -1,6 +1,7 @@
+use std::collections::BTreeMap;
 use std::hash::{Hash, Hasher};
 
-use crate::{data::Timestamp, epoch, EntityId, Result};
+use crate::{data::{Decimal, Duration, Timestamp}, epoch, EntityId, Result};
 use serde::{Deserialize, Serialize};
 
 #[cfg(feature = "derive")]
@@ -18,6 +19,11 @@ pub enum Value {
     Int(i64),
     String(String),
     Timestamp(Timestamp),
+    // Appended rather than sorted so existing wire discriminants stay stable
+    Decimal(Decimal),
+    Duration(Duration),
+    StringList(Vec<String>),
+    Map(BTreeMap<String, Value>),
 }
 
 impl Hash for Value {
@@ -51,6 +57,37 @@ impl Hash for Value {
             Value::Timestamp(t) => {
                 t.hash(state);
             }
+            Value::Decimal(d) => {
+                d.hash(state);
+            }
+            Value::Duration(d) => {
+                d.hash(state);
+            }
+            Value::StringList(l) => {
+                l.hash(state);
+            }
+            Value::Map(m) => {
+                m.hash(state);
+            }
+        }
+    }
+}
+
+/// Values of the same scalar type compare naturally; anything else
+/// (different types, lists, maps, blobs, NaN) has no order
+impl PartialOrd for Value {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        match (self, other) {
+            (Value::Bool(a), Value::Bool(b)) => a.partial_cmp(b),
+            (Value::Choice(a), Value::Choice(b)) => a.partial_cmp(b),
+            (Value::EntityReference(a), Value::EntityReference(b)) => a.partial_cmp(b),
+            (Value::Float(a), Value::Float(b)) => a.partial_cmp(b),
+            (Value::Int(a), Value::Int(b)) => a.partial_cmp(b),
+            (Value::String(a), Value::String(b)) => a.partial_cmp(b),
+            (Value::Timestamp(a), Value::Timestamp(b)) => a.partial_cmp(b),
+            (Value::Decimal(a), Value::Decimal(b)) => a.partial_cmp(b),
+            (Value::Duration(a), Value::Duration(b)) => a.partial_cmp(b),
+            _ => None,
         }
     }
 }
@@ -88,6 +125,22 @@ impl Value {
         matches!(self, Value::Choice(_))
     }
 
+    pub fn is_decimal(&self) -> bool {
+        matches!(self, Value::Decimal(_))
+    }
+
+    pub fn is_duration(&self) -> bool {
+        matches!(self, Value::Duration(_))
+    }
+
+    pub fn is_string_list(&self) -> bool {
+        matches!(self, Value::StringList(_))
+    }
+
+    pub fn is_map(&self) -> bool {
+        matches!(self, Value::Map(_))
+    }
+
     pub fn as_bool(&self) -> Option<bool> {
         if let Value::Bool(b) = self {
             Some(*b)
@@ -120,6 +173,11 @@ impl Value {
         }
     }
 
+    /// Same as `as_string`
+    pub fn as_str(&self) -> Option<&str> {
+        self.as_string()
+    }
+
     pub fn as_blob(&self) -> Option<&[u8]> {
         if let Value::Blob(b) = self {
             Some(b.as_slice())
@@ -136,6 +194,11 @@ impl Value {
         }
     }
 
+    /// The referenced entity, None if this isn't a set EntityReference
+    pub fn as_entity_ref(&self) -> Option<EntityId> {
+        self.as_entity_reference().copied().flatten()
+    }
+
     pub fn as_entity_list(&self) -> Option<&Vec<EntityId>> {
         if let Value::EntityList(e) = self {
             Some(e)
@@ -160,6 +223,38 @@ impl Value {
         }
     }
 
+    pub fn as_decimal(&self) -> Option<Decimal> {
+        if let Value::Decimal(d) = self {
+            Some(*d)
+        } else {
+            None
+        }
+    }
+
+    pub fn as_duration(&self) -> Option<Duration> {
+        if let Value::Duration(d) = self {
+            Some(*d)
+        } else {
+            None
+        }
+    }
+
+    pub fn as_string_list(&self) -> Option<&Vec<String>> {
+        if let Value::StringList(l) = self {
+            Some(l)
+        } else {
+            None
+        }
+    }
+
+    pub fn as_map(&self) -> Option<&BTreeMap<String, Value>> {
+        if let Value::Map(m) = self {
+            Some(m)
+        } else {
+            None
+        }
+    }
+
     pub fn from_bool(b: bool) -> Self {
         Value::Bool(b)
     }
@@ -196,6 +291,22 @@ impl Value {
         Value::Timestamp(t)
     }
 
+    pub fn from_decimal(d: Decimal) -> Self {
+        Value::Decimal(d)
+    }
+
+    pub fn from_duration(d: Duration) -> Self {
+        Value::Duration(d)
+    }
+
+    pub fn from_string_list(l: Vec<String>) -> Self {
+        Value::StringList(l)
+    }
+
+    pub fn from_map(m: BTreeMap<String, Value>) -> Self {
+        Value::Map(m)
+    }
+
     pub fn expect_bool(&self) -> Result<bool> {
         if let Value::Bool(b) = self {
             Ok(*b)
@@ -273,6 +384,261 @@ impl Value {
             ))
         }
     }
+
+    pub fn expect_decimal(&self) -> Result<Decimal> {
+        if let Value::Decimal(d) = self {
+            Ok(*d)
+        } else {
+            Err(crate::Error::BadValueCast(self.clone(), Value::Decimal(Decimal::ZERO)))
+        }
+    }
+
+    pub fn expect_duration(&self) -> Result<Duration> {
+        if let Value::Duration(d) = self {
+            Ok(*d)
+        } else {
+            Err(crate::Error::BadValueCast(self.clone(), Value::Duration(Duration::ZERO)))
+        }
+    }
+
+    pub fn expect_string_list(&self) -> Result<&Vec<String>> {
+        if let Value::StringList(l) = self {
+            Ok(l)
+        } else {
+            Err(crate::Error::BadValueCast(self.clone(), Value::StringList(vec![])))
+        }
+    }
+
+    pub fn expect_map(&self) -> Result<&BTreeMap<String, Value>> {
+        if let Value::Map(m) = self {
+            Ok(m)
+        } else {
+            Err(crate::Error::BadValueCast(self.clone(), Value::Map(BTreeMap::new())))
+        }
+    }
+
+    /// Convert to a Rust type, failing with `Error::BadValueCast` if the
+    /// value holds a different type.
+    ///
+    /// ```ignore
+    /// let speed: f64 = value.try_as()?;
+    /// let parent = value.try_as::<EntityId>()?;
+    /// ```
+    pub fn try_as<T>(&self) -> Result<T>
+    where
+        T: for<'a> TryFrom<&'a Value, Error = crate::Error>,
+    {
+        T::try_from(self)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(b: bool) -> Self {
+        Value::Bool(b)
+    }
+}
+
+impl From<i64> for Value {
+    fn from(i: i64) -> Self {
+        Value::Int(i)
+    }
+}
+
+impl From<i32> for Value {
+    fn from(i: i32) -> Self {
+        Value::Int(i as i64)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(f: f64) -> Self {
+        Value::Float(f)
+    }
+}
+
+impl From<f32> for Value {
+    fn from(f: f32) -> Self {
+        Value::Float(f as f64)
+    }
+}
+
+impl From<String> for Value {
+    fn from(s: String) -> Self {
+        Value::String(s)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(s: &str) -> Self {
+        Value::String(s.to_string())
+    }
+}
+
+impl From<Vec<u8>> for Value {
+    fn from(b: Vec<u8>) -> Self {
+        Value::Blob(b)
+    }
+}
+
+impl From<&[u8]> for Value {
+    fn from(b: &[u8]) -> Self {
+        Value::Blob(b.to_vec())
+    }
+}
+
+impl From<EntityId> for Value {
+    fn from(e: EntityId) -> Self {
+        Value::EntityReference(Some(e))
+    }
+}
+
+impl From<Option<EntityId>> for Value {
+    fn from(e: Option<EntityId>) -> Self {
+        Value::EntityReference(e)
+    }
+}
+
+impl From<Vec<EntityId>> for Value {
+    fn from(e: Vec<EntityId>) -> Self {
+        Value::EntityList(e)
+    }
+}
+
+impl From<Timestamp> for Value {
+    fn from(t: Timestamp) -> Self {
+        Value::Timestamp(t)
+    }
+}
+
+impl From<Decimal> for Value {
+    fn from(d: Decimal) -> Self {
+        Value::Decimal(d)
+    }
+}
+
+impl From<Duration> for Value {
+    fn from(d: Duration) -> Self {
+        Value::Duration(d)
+    }
+}
+
+impl From<Vec<String>> for Value {
+    fn from(l: Vec<String>) -> Self {
+        Value::StringList(l)
+    }
+}
+
+impl From<BTreeMap<String, Value>> for Value {
+    fn from(m: BTreeMap<String, Value>) -> Self {
+        Value::Map(m)
+    }
+}
+
+impl TryFrom<&Value> for bool {
+    type Error = crate::Error;
+
+    fn try_from(value: &Value) -> Result<Self> {
+        value.expect_bool()
+    }
+}
+
+impl TryFrom<&Value> for i64 {
+    type Error = crate::Error;
+
+    fn try_from(value: &Value) -> Result<Self> {
+        value.expect_int()
+    }
+}
+
+impl TryFrom<&Value> for f64 {
+    type Error = crate::Error;
+
+    fn try_from(value: &Value) -> Result<Self> {
+        value.expect_float()
+    }
+}
+
+impl TryFrom<&Value> for String {
+    type Error = crate::Error;
+
+    fn try_from(value: &Value) -> Result<Self> {
+        value.expect_string().map(str::to_string)
+    }
+}
+
+impl TryFrom<&Value> for Vec<u8> {
+    type Error = crate::Error;
+
+    fn try_from(value: &Value) -> Result<Self> {
+        value.expect_blob().map(<[u8]>::to_vec)
+    }
+}
+
+impl TryFrom<&Value> for Option<EntityId> {
+    type Error = crate::Error;
+
+    fn try_from(value: &Value) -> Result<Self> {
+        value.expect_entity_reference().copied()
+    }
+}
+
+/// Fails for an unset reference as well as for other value types
+impl TryFrom<&Value> for EntityId {
+    type Error = crate::Error;
+
+    fn try_from(value: &Value) -> Result<Self> {
+        value
+            .expect_entity_reference()?
+            .ok_or_else(|| crate::Error::BadValueCast(value.clone(), Value::EntityReference(Some(EntityId(0)))))
+    }
+}
+
+impl TryFrom<&Value> for Vec<EntityId> {
+    type Error = crate::Error;
+
+    fn try_from(value: &Value) -> Result<Self> {
+        value.expect_entity_list().cloned()
+    }
+}
+
+impl TryFrom<&Value> for Timestamp {
+    type Error = crate::Error;
+
+    fn try_from(value: &Value) -> Result<Self> {
+        value.expect_timestamp()
+    }
+}
+
+impl TryFrom<&Value> for Decimal {
+    type Error = crate::Error;
+
+    fn try_from(value: &Value) -> Result<Self> {
+        value.expect_decimal()
+    }
+}
+
+impl TryFrom<&Value> for Duration {
+    type Error = crate::Error;
+
+    fn try_from(value: &Value) -> Result<Self> {
+        value.expect_duration()
+    }
+}
+
+impl TryFrom<&Value> for Vec<String> {
+    type Error = crate::Error;
+
+    fn try_from(value: &Value) -> Result<Self> {
+        value.expect_string_list().cloned()
+    }
+}
+
+impl TryFrom<&Value> for BTreeMap<String, Value> {
+    type Error = crate::Error;
+
+    fn try_from(value: &Value) -> Result<Self> {
+        value.expect_map().cloned()
+    }
 }
 
 impl Into<String> for Value {
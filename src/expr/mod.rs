@@ -1,8 +1,31 @@
+pub mod planner;
+
+use cel::extractors::Arguments;
 use cel::{Context, Program};
 use lru::LruCache;
+use std::collections::HashMap;
 use std::num::NonZeroUsize;
+use std::sync::Mutex;
+
+use crate::{to_base64, AsyncStoreTrait, EntityId, FieldType, IndirectFieldType, Result, StoreTrait, Value, INDIRECTION_DELIMITER};
+
+/// CEL executors lent out for one evaluation at a time, so expressions
+/// evaluated by several threads at once don't wait for each other. Each
+/// executor keeps its own compiled programs.
+#[derive(Debug, Default)]
+pub(crate) struct CelExecutorPool {
+    idle: Mutex<Vec<CelExecutor>>,
+}
 
-use crate::{to_base64, EntityId, IndirectFieldType, Result, StoreTrait, Value, INDIRECTION_DELIMITER};
+impl CelExecutorPool {
+    /// Run `f` with an idle executor, or a new one if all are in use
+    pub(crate) fn with<T>(&self, f: impl FnOnce(&mut CelExecutor) -> T) -> T {
+        let mut executor = self.idle.lock().unwrap().pop().unwrap_or_else(CelExecutor::new);
+        let result = f(&mut executor);
+        self.idle.lock().unwrap().push(executor);
+        result
+    }
+}
 
 /// CelExecutor with LRU cache for compiled CEL programs
 #[derive(Debug)]
@@ -64,81 +87,362 @@ impl CelExecutor {
         Ok(self.cache.get(source).unwrap())
     }
 
-    pub fn execute(&mut self, source: &str, relative_id: EntityId, store: &impl StoreTrait) -> Result<cel::Value> {
+    /// The field paths an expression reads from the store
+    pub fn referenced_fields(&mut self, source: &str, store: &impl StoreTrait) -> Result<Vec<IndirectFieldType>> {
         let program = self.get_or_compile(source.replace(INDIRECTION_DELIMITER, "_").as_str())?;
+
+        program
+            .references()
+            .variables()
+            .into_iter()
+            .map(|field| field_path(field, store))
+            .collect()
+    }
+
+    pub fn execute(&mut self, source: &str, relative_id: EntityId, store: &impl StoreTrait) -> Result<cel::Value> {
+        self.execute_with_params(source, relative_id, &HashMap::new(), store)
+    }
+
+    /// Execute an expression with request-scoped parameters.
+    ///
+    /// Variables found in `params` are bound directly instead of being read
+    /// from the store, so callers can evaluate against values that haven't
+    /// been written yet (e.g. the proposed value of a write).
+    pub fn execute_with_params(&mut self, source: &str, relative_id: EntityId, params: &HashMap<String, Value>, store: &impl StoreTrait) -> Result<cel::Value> {
+        let source = source.replace(INDIRECTION_DELIMITER, "_");
+        let mut values = Vec::new();
+        for field in self.variables(&source)? {
+            // Variables found in params aren't read from the store
+            let value = match params.get(&field) {
+                Some(value) => value_to_cel_value(value.clone())?,
+                None => read_variable(store, relative_id, &field_path(&field, store)?)?,
+            };
+            values.push((field, value));
+        }
+
+        let entity_type = store.resolve_entity_type(relative_id.extract_type()).unwrap_or_else(|_| format!("{:?}", relative_id.extract_type()));
+        self.evaluate(&source, relative_id, entity_type, values)
+    }
+
+    /// `referenced_fields` for an async store
+    pub async fn referenced_fields_async(&mut self, source: &str, store: &impl AsyncStoreTrait) -> Result<Vec<IndirectFieldType>> {
+        let mut paths = Vec::new();
+        for field in self.variables(&source.replace(INDIRECTION_DELIMITER, "_"))? {
+            paths.push(field_path_async(&field, store).await?);
+        }
+        Ok(paths)
+    }
+
+    /// `execute` for an async store
+    pub async fn execute_async(&mut self, source: &str, relative_id: EntityId, store: &impl AsyncStoreTrait) -> Result<cel::Value> {
+        self.execute_with_params_async(source, relative_id, &HashMap::new(), store).await
+    }
+
+    /// `execute_with_params` for an async store. Every read finishes before
+    /// the expression runs, so no CEL state is held across an await.
+    pub async fn execute_with_params_async(&mut self, source: &str, relative_id: EntityId, params: &HashMap<String, Value>, store: &impl AsyncStoreTrait) -> Result<cel::Value> {
+        let source = source.replace(INDIRECTION_DELIMITER, "_");
+        let mut values = Vec::new();
+        for field in self.variables(&source)? {
+            let value = match params.get(&field) {
+                Some(value) => value_to_cel_value(value.clone())?,
+                None => read_variable_async(store, relative_id, &field_path_async(&field, store).await?).await?,
+            };
+            values.push((field, value));
+        }
+
+        let entity_type = match store.resolve_entity_type(relative_id.extract_type()).await {
+            Ok(name) => name,
+            Err(_) => format!("{:?}", relative_id.extract_type()),
+        };
+        self.evaluate(&source, relative_id, entity_type, values)
+    }
+
+    /// Variables an expression (with `_` in place of the indirection
+    /// delimiter) refers to
+    fn variables(&mut self, source: &str) -> Result<Vec<String>> {
+        let program = self.get_or_compile(source)?;
+        Ok(program.references().variables().into_iter().map(|field| field.to_string()).collect())
+    }
+
+    /// Run an expression with every variable it refers to already read
+    fn evaluate(&mut self, source: &str, relative_id: EntityId, entity_type: String, values: Vec<(String, cel::Value)>) -> Result<cel::Value> {
+        let program = self.get_or_compile(source)?;
         let mut context = Context::default();
-        let references = program.references();
-        let fields = references.variables();
-
-        for field in fields {
-            // Convert underscore to indirection delimiter for store reading
-            let store_field = field.to_string().replace("_", INDIRECTION_DELIMITER);
-            
-            // Parse indirection: split by delimiter and convert each part to FieldType
-            let field_types: Result<IndirectFieldType> = store_field
-                .split(INDIRECTION_DELIMITER)
-                .map(|field_name| store.get_field_type(field_name))
-                .collect();
-            let field_types = field_types?;
-            
-            let (value, _, _) = store.read(relative_id, &field_types)?;
-            // Use the original field name for CEL context (keep underscores)
-            let cel_field = field.to_string();
-
-            match value {
-                Value::Blob(v) => {
-                    context.add_variable_from_value(cel_field, to_base64(v.to_vec()));
-                },
-                Value::Bool(v) => {
-                    context.add_variable_from_value(cel_field, v);
-                },
-                Value::Choice(v) => {
-                    context.add_variable_from_value(cel_field, v);
-                },
-                Value::EntityReference(v) => {
-                    match v {
-                        Some(e) => {
-                            context.add_variable_from_value(cel_field, e.0);
-                        },
-                        None => {
-                            let _ = context.add_variable(cel_field, 0);
-                        }
-                    }
-                },
-                Value::EntityList(v) => {
-                    let list: Vec<u64> = v.iter().map(|e| e.0).collect();
-                    context.add_variable_from_value(cel_field, list);
-                },
-                Value::Float(v) => {
-                    context.add_variable_from_value(cel_field, v);
-                },
-                Value::String(v) => {
-                    context.add_variable_from_value(cel_field, v.as_str());
-                },
-                Value::Timestamp(v) => {
-                    // Convert time::OffsetDateTime to chrono::DateTime<chrono::FixedOffset>
-                    let unix_timestamp = v.unix_timestamp();
-                    let nanoseconds = v.nanosecond();
-                    let datetime = chrono::DateTime::from_timestamp(
-                        unix_timestamp,
-                        nanoseconds
-                    ).ok_or_else(|| crate::Error::ExecutionError("Failed to convert timestamp".to_string()))?
-                        .with_timezone(&chrono::FixedOffset::east_opt(0).unwrap());
-                    context.add_variable_from_value(cel_field, datetime);
-                },
-                Value::Int(v) => {
-                    context.add_variable_from_value(cel_field, v);
-                },
-            }
+        for (field, value) in values {
+            context.add_variable_from_value(field, value);
         }
+        context.add_function("Sum", sum);
+        context.add_function("Count", count);
+        context.add_function("Min", cel::functions::min);
+        context.add_function("Max", cel::functions::max);
 
         context.add_variable_from_value("EntityId", relative_id.0);
-
-        context.add_variable_from_value("EntityType", store.resolve_entity_type(relative_id.extract_type()).unwrap_or_else(|_| format!("{:?}", relative_id.extract_type())));
+        context.add_variable_from_value("EntityType", entity_type);
 
         match program.execute(&context) {
             Ok(v) => Ok(v),
             Err(e) => Err(crate::Error::ExecutionError(e.to_string()))
         }
     }
-}
\ No newline at end of file
+}
+
+/// The field path a variable names. Underscores in a variable separate the
+/// fields of an indirection (`Parent->Name` is parsed as `Parent_Name`), but
+/// field names may contain them too, so the split is the one whose parts
+/// are all field types, preferring longer field names.
+pub(crate) fn field_path(variable: &str, store: &impl StoreTrait) -> Result<IndirectFieldType> {
+    match split_variable(variable, &mut |name| store.get_field_type(name).ok()) {
+        Some(field_path) => Ok(field_path),
+        None => variable.split('_').map(|name| store.get_field_type(name)).collect(),
+    }
+}
+
+/// `field_path` for an async store
+async fn field_path_async(variable: &str, store: &impl AsyncStoreTrait) -> Result<IndirectFieldType> {
+    // Look up every run of parts first, since the split can't await
+    let bounds: Vec<usize> = std::iter::once(0)
+        .chain(variable.match_indices('_').map(|(at, _)| at))
+        .chain(std::iter::once(variable.len()))
+        .collect();
+    let mut field_types = HashMap::new();
+    for (i, start) in bounds.iter().enumerate() {
+        let start = if i == 0 { *start } else { start + 1 };
+        for end in &bounds[i + 1..] {
+            let name = &variable[start..*end];
+            if let Ok(field_type) = store.get_field_type(name).await {
+                field_types.insert(name, field_type);
+            }
+        }
+    }
+
+    match split_variable(variable, &mut |name| field_types.get(name).copied()) {
+        Some(field_path) => Ok(field_path),
+        None => {
+            let mut field_path = IndirectFieldType::new();
+            for name in variable.split('_') {
+                field_path.push(store.get_field_type(name).await?);
+            }
+            Ok(field_path)
+        }
+    }
+}
+
+fn split_variable(variable: &str, field_type: &mut impl FnMut(&str) -> Option<FieldType>) -> Option<IndirectFieldType> {
+    if let Some(field_type) = field_type(variable) {
+        return Some(IndirectFieldType::from_elem(field_type, 1));
+    }
+    for (at, _) in variable.rmatch_indices('_') {
+        let Some(first) = field_type(&variable[..at]) else {
+            continue;
+        };
+        if let Some(mut rest) = split_variable(&variable[at + 1..], field_type) {
+            rest.insert(0, first);
+            return Some(rest);
+        }
+    }
+    None
+}
+
+/// Read the value a variable names. A path through an `EntityList` fans
+/// out to every entity in the list and reads as the list of their values,
+/// so expressions can aggregate over it, e.g. `Sum(Children->Power)`.
+/// Entities of the list that don't lead on to the last field are left out.
+fn read_variable(store: &impl StoreTrait, relative_id: EntityId, field_path: &[FieldType]) -> Result<cel::Value> {
+    let Some((last, hops)) = field_path.split_last() else {
+        return value_to_cel_value(store.read(relative_id, field_path)?.0);
+    };
+
+    let mut entities = vec![relative_id];
+    let mut fanned_out = false;
+    for hop in hops {
+        let mut next = Vec::with_capacity(entities.len());
+        for entity_id in entities {
+            match store.read(entity_id, &[*hop])?.0 {
+                Value::EntityList(ids) => {
+                    fanned_out = true;
+                    next.extend(ids);
+                }
+                Value::EntityReference(Some(id)) => next.push(id),
+                _ if fanned_out => {}
+                // Let the store report the broken indirection
+                _ => return value_to_cel_value(store.read(relative_id, field_path)?.0),
+            }
+        }
+        entities = next;
+    }
+
+    if !fanned_out {
+        return value_to_cel_value(store.read(entities[0], &[*last])?.0);
+    }
+    let values = entities
+        .into_iter()
+        .map(|entity_id| value_to_cel_value(store.read(entity_id, &[*last])?.0))
+        .collect::<Result<Vec<_>>>()?;
+    Ok(values.into())
+}
+
+/// `read_variable` for an async store
+async fn read_variable_async(store: &impl AsyncStoreTrait, relative_id: EntityId, field_path: &[FieldType]) -> Result<cel::Value> {
+    let Some((last, hops)) = field_path.split_last() else {
+        return value_to_cel_value(store.read(relative_id, field_path).await?.0);
+    };
+
+    let mut entities = vec![relative_id];
+    let mut fanned_out = false;
+    for hop in hops {
+        let mut next = Vec::with_capacity(entities.len());
+        for entity_id in entities {
+            match store.read(entity_id, &[*hop]).await?.0 {
+                Value::EntityList(ids) => {
+                    fanned_out = true;
+                    next.extend(ids);
+                }
+                Value::EntityReference(Some(id)) => next.push(id),
+                _ if fanned_out => {}
+                _ => return value_to_cel_value(store.read(relative_id, field_path).await?.0),
+            }
+        }
+        entities = next;
+    }
+
+    if !fanned_out {
+        return value_to_cel_value(store.read(entities[0], &[*last]).await?.0);
+    }
+    let mut values = Vec::with_capacity(entities.len());
+    for entity_id in entities {
+        values.push(value_to_cel_value(store.read(entity_id, &[*last]).await?.0)?);
+    }
+    Ok(values.into())
+}
+
+/// The values an aggregation runs over: the list it was given, or its
+/// arguments
+fn aggregated(args: &[cel::Value]) -> &[cel::Value] {
+    match args {
+        [cel::Value::List(items)] => items,
+        _ => args,
+    }
+}
+
+/// `Sum(Children->Power)`: the total of a list of numbers, an int unless
+/// one of them is a double
+fn sum(Arguments(args): Arguments) -> std::result::Result<cel::Value, cel::ExecutionError> {
+    let mut int_total: i64 = 0;
+    let mut float_total = 0.0;
+    let mut is_float = false;
+    for item in aggregated(&args) {
+        match item {
+            cel::Value::Int(i) => {
+                int_total = int_total.checked_add(*i).ok_or_else(|| cel::ExecutionError::function_error("Sum", "overflow"))?;
+                float_total += *i as f64;
+            }
+            cel::Value::UInt(u) => {
+                int_total = int_total.checked_add_unsigned(*u).ok_or_else(|| cel::ExecutionError::function_error("Sum", "overflow"))?;
+                float_total += *u as f64;
+            }
+            cel::Value::Float(f) => {
+                is_float = true;
+                float_total += f;
+            }
+            other => return Err(cel::ExecutionError::function_error("Sum", format!("can't add {:?}", other))),
+        }
+    }
+    Ok(if is_float { float_total.into() } else { int_total.into() })
+}
+
+/// `Count(Children->Name)`: how many values a list holds
+fn count(Arguments(args): Arguments) -> std::result::Result<cel::Value, cel::ExecutionError> {
+    Ok((aggregated(&args).len() as i64).into())
+}
+
+/// Convert a store value into the CEL value expressions see
+pub fn value_to_cel_value(value: Value) -> Result<cel::Value> {
+    Ok(match value {
+        Value::Blob(v) => to_base64(v).into(),
+        Value::Bool(v) => v.into(),
+        Value::Choice(v) => v.into(),
+        Value::EntityReference(v) => match v {
+            Some(e) => e.0.into(),
+            None => 0i64.into(),
+        },
+        Value::EntityList(v) => {
+            let list: Vec<u64> = v.iter().map(|e| e.0).collect();
+            list.into()
+        },
+        Value::Float(v) => v.into(),
+        Value::String(v) => v.into(),
+        Value::Timestamp(v) => {
+            // Convert time::OffsetDateTime to chrono::DateTime<chrono::FixedOffset>
+            let unix_timestamp = v.unix_timestamp();
+            let nanoseconds = v.nanosecond();
+            let datetime = chrono::DateTime::from_timestamp(
+                unix_timestamp,
+                nanoseconds
+            ).ok_or_else(|| crate::Error::ExecutionError("Failed to convert timestamp".to_string()))?
+                .with_timezone(&chrono::FixedOffset::east_opt(0).unwrap());
+            datetime.into()
+        },
+        Value::Int(v) => v.into(),
+        // CEL has no decimal type; expressions see the nearest double
+        Value::Decimal(v) => v.to_f64().into(),
+        Value::Duration(v) => cel::Value::Duration(chrono::Duration::nanoseconds(v.whole_nanoseconds() as i64)),
+        Value::StringList(v) => v.into(),
+        Value::Map(v) => {
+            let map = v
+                .into_iter()
+                .map(|(key, value)| Ok((key, value_to_cel_value(value)?)))
+                .collect::<Result<HashMap<String, cel::Value>>>()?;
+            map.into()
+        },
+    })
+}
+
+/// Convert the result of a CEL expression back into a store value
+pub fn cel_value_to_value(value: cel::Value) -> Result<Value> {
+    match value {
+        cel::Value::Int(v) => Ok(Value::Int(v)),
+        cel::Value::UInt(v) => Ok(Value::Int(v as i64)),
+        cel::Value::Float(v) => Ok(Value::Float(v)),
+        cel::Value::String(v) => Ok(Value::String(v.to_string())),
+        cel::Value::Bool(v) => Ok(Value::Bool(v)),
+        cel::Value::Bytes(v) => Ok(Value::Blob(v.to_vec())),
+        cel::Value::Timestamp(v) => {
+            let nanos = v.timestamp_nanos_opt()
+                .ok_or_else(|| crate::Error::ExecutionError("Timestamp out of range".to_string()))?;
+            let timestamp = time::OffsetDateTime::from_unix_timestamp_nanos(nanos as i128)
+                .map_err(|e| crate::Error::ExecutionError(e.to_string()))?;
+            Ok(Value::Timestamp(timestamp))
+        },
+        cel::Value::Duration(v) => {
+            let nanos = v.num_nanoseconds()
+                .ok_or_else(|| crate::Error::ExecutionError("Duration out of range".to_string()))?;
+            Ok(Value::Duration(crate::Duration::nanoseconds(nanos)))
+        },
+        // Lists of strings and string-keyed maps come back as StringList/Map
+        cel::Value::List(v) => v
+            .iter()
+            .map(|item| match item {
+                cel::Value::String(s) => Ok(s.to_string()),
+                other => Err(crate::Error::ExecutionError(format!(
+                    "Unsupported list element type: {}",
+                    other.type_of()
+                ))),
+            })
+            .collect::<Result<Vec<String>>>()
+            .map(Value::StringList),
+        cel::Value::Map(v) => v
+            .map
+            .iter()
+            .map(|(key, value)| match key {
+                cel::objects::Key::String(key) => Ok((key.to_string(), cel_value_to_value(value.clone())?)),
+                other => Err(crate::Error::ExecutionError(format!("Unsupported map key: {:?}", other))),
+            })
+            .collect::<Result<std::collections::BTreeMap<String, Value>>>()
+            .map(Value::Map),
+        other => Err(crate::Error::ExecutionError(format!(
+            "Unsupported expression result type: {}",
+            other.type_of()
+        ))),
+    }
+}
@@ -0,0 +1,151 @@
+use cel::common::ast::{operators, Expr};
+use cel::common::value::CelVal;
+use cel::IdedExpr;
+
+use crate::{EntityId, IndirectFieldType, Result, Store, StoreTrait, Value, INDIRECTION_DELIMITER};
+
+use super::CelExecutor;
+
+/// A `field == literal` condition extracted from a filter expression
+#[derive(Debug, Clone, PartialEq)]
+pub struct EqualityPredicate {
+    pub field_path: IndirectFieldType,
+    pub value: Value,
+}
+
+impl EqualityPredicate {
+    /// Check the predicate directly against the store.
+    ///
+    /// Returns None when the outcome can't be decided without CEL (e.g. the
+    /// field holds a different value type than the literal).
+    fn evaluate(&self, entity_id: EntityId, store: &impl StoreTrait) -> Option<bool> {
+        let (value, _, _) = store.read(entity_id, &self.field_path).ok()?;
+
+        match (&value, &self.value) {
+            (Value::Choice(a), Value::Int(b)) => Some(a == b),
+            (a, b) if std::mem::discriminant(a) == std::mem::discriminant(b) => Some(a == b),
+            _ => None,
+        }
+    }
+}
+
+/// Execution plan for a `find_entities` filter.
+///
+/// Top-level equality conjuncts (`Name == 'x' && Parent->Name == 'y'`) are
+/// looked up in the store's value and reference indexes, so only the entities
+/// they find are checked at all. Those are checked with direct field reads,
+/// and the full CEL expression only needs to run for entities that pass them,
+/// and only if the filter contains other conditions that couldn't be pushed
+/// down.
+#[derive(Debug, Clone, Default)]
+pub struct FilterPlan {
+    predicates: Vec<EqualityPredicate>,
+    residual: bool,
+}
+
+impl FilterPlan {
+    /// Analyze a filter expression, compiling it through the executor's cache
+    pub fn new(executor: &mut CelExecutor, source: &str, store: &impl StoreTrait) -> Result<Self> {
+        let program = executor.get_or_compile(source.replace(INDIRECTION_DELIMITER, "_").as_str())?;
+
+        let mut conjuncts = Vec::new();
+        collect_conjuncts(program.expression(), &mut conjuncts);
+
+        let mut plan = FilterPlan::default();
+        for conjunct in conjuncts {
+            match equality_predicate(conjunct, store) {
+                Some(predicate) => plan.predicates.push(predicate),
+                None => plan.residual = true,
+            }
+        }
+
+        Ok(plan)
+    }
+
+    /// The predicates that are evaluated without CEL
+    pub fn predicates(&self) -> &[EqualityPredicate] {
+        &self.predicates
+    }
+
+    /// True if some part of the filter still requires CEL evaluation
+    pub fn has_residual(&self) -> bool {
+        self.residual
+    }
+
+    /// The only entities that can pass the filter, sorted, as found by
+    /// looking up the predicates in the store's indexes. None if no predicate
+    /// could be looked up, in which case every entity has to be checked.
+    pub fn candidates(&self, store: &Store) -> Option<Vec<EntityId>> {
+        let mut candidates: Option<Vec<EntityId>> = None;
+        for predicate in &self.predicates {
+            let Some(found) = store.entities_with_value(&predicate.field_path, &predicate.value) else {
+                continue;
+            };
+            candidates = Some(match candidates {
+                Some(candidates) => candidates.into_iter().filter(|id| found.binary_search(id).is_ok()).collect(),
+                None => found,
+            });
+        }
+        candidates
+    }
+
+    /// Evaluate the pushed down predicates for an entity.
+    ///
+    /// Returns Some(false) if any predicate rules the entity out, Some(true)
+    /// if the predicates fully decide the filter, and None if the full
+    /// expression must be evaluated.
+    pub fn evaluate(&self, entity_id: EntityId, store: &impl StoreTrait) -> Option<bool> {
+        let mut decided = true;
+
+        for predicate in &self.predicates {
+            match predicate.evaluate(entity_id, store) {
+                Some(false) => return Some(false),
+                Some(true) => {}
+                None => decided = false,
+            }
+        }
+
+        if decided && !self.residual {
+            Some(true)
+        } else {
+            None
+        }
+    }
+}
+
+fn collect_conjuncts<'a>(expression: &'a IdedExpr, conjuncts: &mut Vec<&'a IdedExpr>) {
+    match &expression.expr {
+        Expr::Call(call) if call.func_name == operators::LOGICAL_AND && call.target.is_none() => {
+            for arg in &call.args {
+                collect_conjuncts(arg, conjuncts);
+            }
+        }
+        _ => conjuncts.push(expression),
+    }
+}
+
+fn equality_predicate(expression: &IdedExpr, store: &impl StoreTrait) -> Option<EqualityPredicate> {
+    let call = match &expression.expr {
+        Expr::Call(call) if call.func_name == operators::EQUALS && call.target.is_none() && call.args.len() == 2 => call,
+        _ => return None,
+    };
+
+    let (field, literal) = match (&call.args[0].expr, &call.args[1].expr) {
+        (Expr::Ident(field), Expr::Literal(literal)) => (field, literal),
+        (Expr::Literal(literal), Expr::Ident(field)) => (field, literal),
+        _ => return None,
+    };
+
+    // Identifiers reach the parser with the indirection delimiter replaced by '_'
+    let field_path = super::field_path(field, store).ok()?;
+
+    let value = match literal {
+        CelVal::String(s) => Value::String(s.clone()),
+        CelVal::Int(i) => Value::Int(*i),
+        CelVal::Boolean(b) => Value::Bool(*b),
+        CelVal::Double(f) => Value::Float(*f),
+        _ => return None,
+    };
+
+    Some(EqualityPredicate { field_path, value })
+}
@@ -1,26 +1,53 @@
+// Lets macros that emit `::qlib_rs` paths for downstream crates work here too
+extern crate self as qlib_rs;
+
 pub mod data;
 pub mod auth;
+#[cfg(test)]
 mod test;
 pub mod expr;
 pub mod app;
+pub mod bridge;
+#[cfg(feature = "capi")]
+pub mod capi;
 
 // Re-export derive macros when derive feature is enabled
 #[cfg(feature = "derive")]
-pub use qlib_rs_derive::{RespEncode, RespDecode, respc};
+pub use qlib_rs_derive::{RespEncode, RespDecode, respc, FieldTypes};
 
 pub use data::{
-    BadIndirectionReason, Store, PageOpts,
-    PageResult, NotificationQueue, hash_notify_config, Snapshot, EntityId, EntitySchema, Single, Complete, 
-    Field, FieldSchema, AdjustBehavior, PushCondition, StorageScope,
-    StoreProxy, Value, INDIRECTION_DELIMITER, NotifyConfig, Notification, NotifyInfo,
-    JsonSnapshot, JsonEntitySchema, JsonEntity, value_to_json_value, json_value_to_value, value_to_json_value_with_paths, build_json_entity_tree, take_json_snapshot, restore_json_snapshot,
+    BadIndirectionReason, Store, SharedStore, ReadSnapshot, PageOpts,
+    PageResult, SortDirection, NotificationQueue, OverflowPolicy, hash_notify_config, Snapshot, SnapshotChecksum, SnapshotSink, DirectorySink, StoredSnapshot, upload_snapshot, list_snapshots, download_snapshot, restore_from_sink, StoreStats, verify_snapshot, verify_json_snapshot, EntityId, IdAllocator, MAX_NODE_ID, NODE_BITS, EntitySchema, Single, Complete, 
+    Field, FieldSchema, FieldMetadata, AdjustBehavior, PushCondition, StorageScope, MergePolicy, OnDelete, SampleType, WriteScope,
+    StoreProxy, CachedStoreProxy, CacheStats, FederatedStore, ProtocolLimits, TypeIdMapping, MAX_MESSAGE_SIZE, MAX_NESTING_DEPTH, Value, INDIRECTION_DELIMITER, NotifyConfig, Notification, NotifyInfo,
+    EntityView, entity_to_value, entity_from_value,
+    JsonSnapshot, JsonEntitySchema, JsonEntity, value_to_json_value, json_value_to_value, value_to_json_value_with_paths, build_json_entity_tree, export_subtree, import_subtree, ImportError, ImportReport, take_json_snapshot, take_json_schemas, restore_json_snapshot,
     restore_entity_recursive, factory_restore_json_snapshot, restore_json_snapshot_via_proxy,
-    EntityType, FieldType, Timestamp, now, epoch, nanos_to_timestamp, secs_to_timestamp, 
-    millis_to_timestamp, micros_to_timestamp, ft, et, Cache, path, path_to_entity_id,
-    StoreTrait, from_base64, to_base64, IndirectFieldType,
-    entity_schema::{EntitySchemaResp, FieldSchemaResp}, WriteInfo
+    EntityType, FieldType, Timestamp, Duration, Decimal, MAX_DECIMAL_SCALE, now, epoch, nanos_to_timestamp, secs_to_timestamp, 
+    millis_to_timestamp, micros_to_timestamp, ft, et, Cache, LiveQuery, LiveQueryEvent, path, path_to_entity_id, path_to_field_path, parse_field_path,
+    StoreTrait, TypesBulk, DanglingReference, AggregateOp, AsyncStoreTrait, AsyncStoreAdapter, TypeRegistry, FieldTypes, from_base64, to_base64, IndirectFieldType,
+    entity_schema::{EntitySchemaResp, FieldSchemaResp}, WriteInfo, PeerReplicator, PeerInfo, REPLICATION_WRITE_READER, DurableSubscriptions, DEFAULT_DURABLE_CAPACITY, HlcTimestamp, HybridClock, DEFAULT_MAX_CLOCK_SKEW,
+    Trigger, TriggerAction, TriggerId,
+    ClientContext, AuditLog, AuditQuery, AuditRecord, AuditSink, DEFAULT_AUDIT_CAPACITY,
+    LimitEnforcer, LimitKey, Limits, CancelRegistry, CancelToken, Deadline, RetryPolicy, Drain, DrainGuard, GroupCommit, GROUP_COMMIT_WRITE_READER,
+    SlowLog, SlowLogAction, SlowLogEntry, DEFAULT_SLOWLOG_CAPACITY, DEFAULT_SLOWLOG_THRESHOLD_MICROS
 };
 
+#[cfg(feature = "sim")]
+pub use data::sim;
+
+#[cfg(any(test, feature = "testing"))]
+pub use data::testing;
+
+#[cfg(feature = "bootstrap")]
+pub use data::bootstrap;
+
+#[cfg(feature = "s3")]
+pub use data::s3;
+
+#[cfg(feature = "graphql")]
+pub use data::graphql;
+
 pub use auth::{
     AuthConfig, AuthMethod,
     authenticate_user, find_user_by_name, create_user, set_user_password,
@@ -50,6 +77,35 @@ pub enum Error {
     ValueTypeMismatch(EntityId, FieldType, Value, Value),
     BadValueCast(Value, Value),
     InvalidRequest(String),
+    /// A fenced write carried a token that doesn't match the current leader's
+    /// (fault tolerance entity, provided token, current token)
+    StaleFencingToken(EntityId, i64, i64),
+    /// The entity can't be deleted while a `Restrict` field refers to it
+    /// (entity, referencing entity, field)
+    EntityReferenced(EntityId, EntityId, FieldType),
+    /// The field's WriteScope doesn't allow the client to write it
+    /// (subject, entity, field)
+    PermissionDenied(EntityId, EntityId, FieldType),
+    /// An imported type id mapping disagrees with the ids already assigned
+    /// (type name, requested id)
+    TypeIdConflict(String, u64),
+    /// The client sent commands faster than its rate limit allows
+    /// (subject or connection)
+    RateLimited(String),
+    /// The client has used up a quota (subject or connection, quota, limit)
+    QuotaExceeded(String, String, u64),
+    /// The request ran past its deadline (the timeout it was given)
+    Timeout(std::time::Duration),
+    /// The request was cancelled with `CANCEL` (request id)
+    Cancelled(u64),
+    /// A snapshot's content doesn't match its checksum (entity types that differ)
+    ChecksumMismatch(Vec<String>),
+    /// A write was refused locally because the candidate making it isn't
+    /// the leader (candidate)
+    NotLeader(EntityId),
+    /// A durable subscription can't resume because notifications it missed
+    /// were no longer kept (subscription, oldest sequence number kept)
+    NotificationsDiscarded(String, u64),
 
     // Auth related errors
     InvalidCredentials,
@@ -66,6 +122,9 @@ pub enum Error {
     // StoreProxy related errors
     StoreProxyError(String),
     ConnectionLost,
+    /// A RESP frame exceeded the connection's ProtocolLimits (frame size, limit)
+    FrameTooLarge(usize, usize),
+    ProtocolError(data::resp::ProtocolError),
 
     // Scripting related errors
     ExecutionError(String),
@@ -88,6 +147,17 @@ impl std::fmt::Display for Error {
             Error::InvalidNotifyConfig(msg) => write!(f, "Invalid notification config: {}", msg),
             Error::UnsupportedAdjustBehavior(id, field, behavior) => write!(f, "Unsupported adjust behavior {:?} for {:?}.{:?}", behavior, id, field),
             Error::InvalidRequest(msg) => write!(f, "Invalid request: {}", msg),
+            Error::StaleFencingToken(id, provided, current) => write!(f, "Stale fencing token {} for {:?}, current token is {}", provided, id, current),
+            Error::EntityReferenced(id, by, field) => write!(f, "Entity {:?} is still referenced by {:?}.{:?}", id, by, field),
+            Error::PermissionDenied(subject, id, field) => write!(f, "Permission denied: {:?} may not write {:?}.{:?}", subject, id, field),
+            Error::TypeIdConflict(name, id) => write!(f, "Type id conflict: '{}' can't be assigned id {}", name, id),
+            Error::RateLimited(client) => write!(f, "Rate limit exceeded for {}", client),
+            Error::QuotaExceeded(client, quota, limit) => write!(f, "Quota exceeded for {}: {} is limited to {}", client, quota, limit),
+            Error::Timeout(timeout) => write!(f, "Request timed out after {:?}", timeout),
+            Error::Cancelled(request_id) => write!(f, "Request {} was cancelled", request_id),
+            Error::NotLeader(candidate) => write!(f, "Candidate {:?} is not the leader", candidate),
+            Error::NotificationsDiscarded(subscription, oldest) => write!(f, "Subscription '{}' can't resume: only notifications from {} on are kept", subscription, oldest),
+            Error::ChecksumMismatch(entity_types) => write!(f, "Snapshot checksum mismatch for: {}", entity_types.join(", ")),
             Error::ValueTypeMismatch(id, field, got, expected) => write!(f, "Value type mismatch for {:?}.{:?}: got value type {:?}, expected value type {:?}", id, field, got, expected),
             Error::BadValueCast(got, expected) => write!(f, "Bad value cast: got value type {:?}, expected value type {:?}", got, expected),
             Error::InvalidCredentials => write!(f, "Invalid credentials"),
@@ -102,11 +172,111 @@ impl std::fmt::Display for Error {
             Error::AuthenticationMethodNotImplemented(method) => write!(f, "Authentication method '{}' is not implemented", method),
             Error::StoreProxyError(msg) => write!(f, "Store proxy error: {}", msg),
             Error::ConnectionLost => write!(f, "Connection to store lost"),
+            Error::FrameTooLarge(size, limit) => write!(f, "Frame of {} bytes exceeds the {} byte message size limit", size, limit),
+            Error::ProtocolError(e) => write!(f, "Protocol error: {}", e),
             Error::ExecutionError(msg) => write!(f, "Execution error: {}", msg),
         }
     }
 }
 
+impl Error {
+    /// The stable code this error is sent over the wire with, see
+    /// `data::resp::error_to_frame`. Errors sent as protocol errors use the
+    /// `ProtocolError` code.
+    pub fn code(&self) -> &'static str {
+        use data::resp::ProtocolError;
+        match self {
+            Error::BadIndirection(..) => "ERR_BAD_INDIRECTION",
+            Error::EntityAlreadyExists(_) => "ERR_ENTITY_ALREADY_EXISTS",
+            Error::EntityNotFound(_) => "ERR_ENTITY_NOT_FOUND",
+            Error::EntityNameNotFound(_) => "ERR_ENTITY_NAME_NOT_FOUND",
+            Error::EntityTypeNotFound(_) => "ERR_ENTITY_TYPE_NOT_FOUND",
+            Error::EntityTypeStrNotFound(_) => "ERR_ENTITY_TYPE_NAME_NOT_FOUND",
+            Error::CacheFieldNotFound(_) => "ERR_CACHE_FIELD_NOT_FOUND",
+            Error::FieldTypeNotFound(..) => "ERR_FIELD_NOT_FOUND",
+            Error::FieldTypeStrNotFound(_) => "ERR_FIELD_NAME_NOT_FOUND",
+            Error::InvalidFieldType(_) => "ERR_INVALID_FIELD_TYPE",
+            Error::InvalidFieldValue(_) => "ERR_INVALID_FIELD_VALUE",
+            Error::InvalidNotifyConfig(_) => "ERR_INVALID_NOTIFY_CONFIG",
+            Error::UnsupportedAdjustBehavior(..) => "ERR_UNSUPPORTED_ADJUST_BEHAVIOR",
+            Error::ValueTypeMismatch(..) => "ERR_VALUE_TYPE_MISMATCH",
+            Error::BadValueCast(..) => "ERR_BAD_VALUE_CAST",
+            Error::InvalidRequest(_) => "ERR_INVALID_REQUEST",
+            Error::StaleFencingToken(..) => "ERR_STALE_FENCING_TOKEN",
+            Error::EntityReferenced(..) => "ERR_ENTITY_REFERENCED",
+            Error::PermissionDenied(..) => "ERR_PERMISSION_DENIED",
+            Error::TypeIdConflict(..) => "ERR_TYPE_ID_CONFLICT",
+            Error::RateLimited(_) => "ERR_RATE_LIMITED",
+            Error::QuotaExceeded(..) => "ERR_QUOTA_EXCEEDED",
+            Error::Timeout(_) => ProtocolError::TIMEOUT,
+            Error::Cancelled(_) => ProtocolError::CANCELLED,
+            Error::ChecksumMismatch(_) => "ERR_CHECKSUM_MISMATCH",
+            Error::NotLeader(_) => "ERR_NOT_LEADER",
+            Error::NotificationsDiscarded(..) => "ERR_NOTIFICATIONS_DISCARDED",
+            Error::InvalidCredentials => "ERR_INVALID_CREDENTIALS",
+            Error::AccountDisabled => "ERR_ACCOUNT_DISABLED",
+            Error::AccountLocked => "ERR_ACCOUNT_LOCKED",
+            Error::SubjectNotFound => "ERR_SUBJECT_NOT_FOUND",
+            Error::PasswordHashError(_) => "ERR_PASSWORD_HASH",
+            Error::InvalidName => "ERR_INVALID_NAME",
+            Error::InvalidPassword(_) => "ERR_INVALID_PASSWORD",
+            Error::SubjectAlreadyExists => "ERR_SUBJECT_ALREADY_EXISTS",
+            Error::InvalidAuthenticationMethod => "ERR_INVALID_AUTHENTICATION_METHOD",
+            Error::AuthenticationMethodNotImplemented(_) => "ERR_AUTHENTICATION_METHOD_NOT_IMPLEMENTED",
+            Error::StoreProxyError(_) => "ERR_STORE_PROXY",
+            Error::ConnectionLost => "ERR_CONNECTION_LOST",
+            Error::FrameTooLarge(..) => ProtocolError::FRAME_TOO_LARGE,
+            Error::ProtocolError(e) => e.code(),
+            Error::ExecutionError(_) => "ERR_EXECUTION",
+        }
+    }
+
+    /// Whether the same request may succeed if sent again later: the
+    /// connection dropped, the server was busy, draining or not the leader,
+    /// or the request ran out of time. A timed out write may still have
+    /// been applied, so only repeat requests that are safe to repeat.
+    /// `StoreProxyError` is too vague to tell and counts as fatal.
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            Error::ConnectionLost
+                | Error::Timeout(_)
+                | Error::RateLimited(_)
+                | Error::NotLeader(_)
+                | Error::ProtocolError(data::resp::ProtocolError::ShuttingDown)
+        )
+    }
+
+    /// Whether the request clashed with the current state of the store,
+    /// e.g. something with the same name or id exists, or another writer
+    /// got there first
+    pub fn is_conflict(&self) -> bool {
+        matches!(
+            self,
+            Error::EntityAlreadyExists(_)
+                | Error::SubjectAlreadyExists
+                | Error::StaleFencingToken(..)
+                | Error::EntityReferenced(..)
+                | Error::TypeIdConflict(..)
+        )
+    }
+
+    /// Whether something the request named doesn't exist
+    pub fn is_not_found(&self) -> bool {
+        matches!(
+            self,
+            Error::EntityNotFound(_)
+                | Error::EntityNameNotFound(_)
+                | Error::EntityTypeNotFound(_)
+                | Error::EntityTypeStrNotFound(_)
+                | Error::CacheFieldNotFound(_)
+                | Error::FieldTypeNotFound(..)
+                | Error::FieldTypeStrNotFound(_)
+                | Error::SubjectNotFound
+        )
+    }
+}
+
 /// Creates a SmallVec of FieldType for use in read/write requests.
 ///
 /// This macro creates a `IndirectFieldType` that can be used with
@@ -21,6 +21,8 @@ fn test_create_and_authenticate_user() -> Result<()> {
             default_value: String::new(),
             rank: 0,
             storage_scope: StorageScope::Configuration,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     object_schema.fields.insert(
@@ -30,6 +32,8 @@ fn test_create_and_authenticate_user() -> Result<()> {
             default_value: None,
             rank: 1,
             storage_scope: StorageScope::Configuration,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     object_schema.fields.insert(
@@ -39,6 +43,9 @@ fn test_create_and_authenticate_user() -> Result<()> {
             default_value: Vec::new(),
             rank: 2,
             storage_scope: StorageScope::Configuration,
+            merge_policy: MergePolicy::LastWriterWins,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     store.update_schema(object_schema)?;
@@ -56,6 +63,8 @@ fn test_create_and_authenticate_user() -> Result<()> {
             default_value: String::new(),
             rank: 0,
             storage_scope: StorageScope::Configuration,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     subject_schema.fields.insert(
@@ -66,6 +75,9 @@ fn test_create_and_authenticate_user() -> Result<()> {
             rank: 1,
             storage_scope: StorageScope::Configuration,
             choices: vec!["Native".to_string(), "LDAP".to_string(), "OpenID Connect".to_string()],
+            choices_source: None,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     subject_schema.fields.insert(
@@ -75,6 +87,8 @@ fn test_create_and_authenticate_user() -> Result<()> {
             default_value: true,
             rank: 2,
             storage_scope: StorageScope::Configuration,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     subject_schema.fields.insert(
@@ -84,6 +98,9 @@ fn test_create_and_authenticate_user() -> Result<()> {
             default_value: 0,
             rank: 3,
             storage_scope: StorageScope::Runtime,
+            merge_policy: MergePolicy::LastWriterWins,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     subject_schema.fields.insert(
@@ -93,6 +110,8 @@ fn test_create_and_authenticate_user() -> Result<()> {
             default_value: crate::Timestamp::from_unix_timestamp(0).unwrap(),
             rank: 4,
             storage_scope: StorageScope::Runtime,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     subject_schema.fields.insert(
@@ -102,6 +121,8 @@ fn test_create_and_authenticate_user() -> Result<()> {
             default_value: crate::Timestamp::from_unix_timestamp(0).unwrap(),
             rank: 5,
             storage_scope: StorageScope::Runtime,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     store.update_schema(subject_schema)?;
@@ -156,6 +177,8 @@ fn test_authentication_with_factory_restore_format() -> Result<()> {
             default_value: String::new(),
             rank: 0,
             storage_scope: StorageScope::Configuration,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     object_schema.fields.insert(
@@ -165,6 +188,8 @@ fn test_authentication_with_factory_restore_format() -> Result<()> {
             default_value: None,
             rank: 1,
             storage_scope: StorageScope::Configuration,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     object_schema.fields.insert(
@@ -174,6 +199,9 @@ fn test_authentication_with_factory_restore_format() -> Result<()> {
             default_value: Vec::new(),
             rank: 2,
             storage_scope: StorageScope::Configuration,
+            merge_policy: MergePolicy::LastWriterWins,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     store.update_schema(object_schema)?;
@@ -190,6 +218,8 @@ fn test_authentication_with_factory_restore_format() -> Result<()> {
             default_value: String::new(),
             rank: 0,
             storage_scope: StorageScope::Configuration,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     subject_schema.fields.insert(
@@ -200,6 +230,9 @@ fn test_authentication_with_factory_restore_format() -> Result<()> {
             rank: 1,
             storage_scope: StorageScope::Configuration,
             choices: vec!["Native".to_string(), "LDAP".to_string(), "OpenID Connect".to_string()],
+            choices_source: None,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     subject_schema.fields.insert(
@@ -209,6 +242,8 @@ fn test_authentication_with_factory_restore_format() -> Result<()> {
             default_value: true,
             rank: 2,
             storage_scope: StorageScope::Configuration,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     subject_schema.fields.insert(
@@ -218,6 +253,9 @@ fn test_authentication_with_factory_restore_format() -> Result<()> {
             default_value: 0,
             rank: 3,
             storage_scope: StorageScope::Runtime,
+            merge_policy: MergePolicy::LastWriterWins,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     subject_schema.fields.insert(
@@ -227,6 +265,8 @@ fn test_authentication_with_factory_restore_format() -> Result<()> {
             default_value: crate::Timestamp::from_unix_timestamp(0).unwrap(),
             rank: 4,
             storage_scope: StorageScope::Runtime,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     subject_schema.fields.insert(
@@ -236,6 +276,8 @@ fn test_authentication_with_factory_restore_format() -> Result<()> {
             default_value: crate::Timestamp::from_unix_timestamp(0).unwrap(),
             rank: 5,
             storage_scope: StorageScope::Runtime,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     store.update_schema(subject_schema)?;
@@ -0,0 +1,125 @@
+#[allow(unused_imports)]
+use crate::*;
+#[allow(unused_imports)]
+use crate::testing::SchemaBuilder;
+#[allow(unused_imports)]
+use crate::app::{backup_to, list_backups, restore_from, rotate, BackupFormat, BackupScheduler, Compression, Retention};
+#[allow(unused_imports)]
+use time::format_description::well_known::Rfc3339;
+
+#[allow(dead_code)]
+fn utc(s: &str) -> Timestamp {
+    Timestamp::parse(s, &Rfc3339).unwrap()
+}
+
+#[allow(dead_code)]
+fn temp_dir() -> std::path::PathBuf {
+    std::env::temp_dir().join(format!("qlib-backup-{}", uuid::Uuid::new_v4()))
+}
+
+#[allow(dead_code)]
+fn plant_store() -> Result<(Store, EntityId)> {
+    let mut store = Store::new();
+    let et_root = SchemaBuilder::object("Root").apply(&mut store)?;
+    let et_pump = SchemaBuilder::object("Pump").int("Speed", 0).apply(&mut store)?;
+    let root = store.create_entity(et_root, None, "Root")?;
+    let pump = store.create_entity(et_pump, Some(root), "P1")?;
+    let ft_speed = store.get_field_type("Speed")?;
+    store.write(pump, &[ft_speed], Value::Int(1450), None, None, None, None)?;
+    Ok((store, pump))
+}
+
+#[allow(dead_code)]
+fn speed_of_p1(store: &Store) -> Result<Value> {
+    let et_pump = store.get_entity_type("Pump")?;
+    let pump = store.find_entities(et_pump, Some("Name == 'P1'"))?[0];
+    Ok(store.read(pump, &[store.get_field_type("Speed")?])?.0)
+}
+
+#[test]
+fn test_backup_restore_and_rotate() -> Result<()> {
+    let (mut store, pump) = plant_store()?;
+    let dir = temp_dir();
+
+    let binary = backup_to(&mut store, &dir, BackupFormat::Binary, Compression::Deflate)?;
+    let json = backup_to(&mut store, &dir, BackupFormat::Json, Compression::None)?;
+    assert!(binary.file_name().unwrap().to_str().unwrap().starts_with(app::backup::BACKUP_PREFIX));
+    assert!(binary.join("snapshot.bin.z").is_file());
+    let schema: Vec<JsonEntitySchema> = serde_json::from_slice(&std::fs::read(binary.join("schema.json")).unwrap()).unwrap();
+    assert_eq!(schema.iter().map(|s| s.entity_type.as_str()).collect::<Vec<_>>(), vec!["Pump", "Root"]);
+
+    // The binary format restores exactly, ids included
+    let mut restored = Store::new();
+    let manifest = restore_from(&mut restored, &binary)?;
+    assert_eq!((manifest.format, manifest.compression), (BackupFormat::Binary, Compression::Deflate));
+    assert_eq!(restored.checksum(), store.checksum());
+    assert!(restored.entity_exists(pump));
+
+    let mut restored = Store::new();
+    restore_from(&mut restored, &json)?;
+    assert_eq!(speed_of_p1(&restored)?, Value::Int(1450));
+
+    // A damaged backup is refused and the store is left alone
+    let snapshot_file = json.join("snapshot.json");
+    let damaged = std::fs::read_to_string(&snapshot_file).unwrap().replace("1450", "1451");
+    std::fs::write(&snapshot_file, damaged).unwrap();
+    assert!(matches!(restore_from(&mut restored, &json), Err(Error::ChecksumMismatch(_))));
+    assert_eq!(speed_of_p1(&restored)?, Value::Int(1450));
+
+    let third = backup_to(&mut store, &dir, BackupFormat::Binary, Compression::None)?;
+    assert_eq!(list_backups(&dir)?.iter().map(|(path, _)| path.clone()).collect::<Vec<_>>(), vec![binary.clone(), json.clone(), third.clone()]);
+    assert_eq!(rotate(&dir, &Retention::keep_last(2), now())?, vec![binary]);
+
+    // Everything is past its age, but the newest backup always stays
+    let retention = Retention { keep_last: 0, max_age: Some(Duration::hours(1)) };
+    assert_eq!(rotate(&dir, &retention, now() + Duration::days(1))?, vec![json]);
+    assert_eq!(list_backups(&dir)?.len(), 1);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+    Ok(())
+}
+
+#[test]
+fn test_backup_schedule_from_config_entities() -> Result<()> {
+    let (mut store, _) = plant_store()?;
+    let et_schedule = SchemaBuilder::object("BackupSchedule")
+        .string("Schedule", "")
+        .string("Directory", "")
+        .choice("Format", &["Binary", "Json"])
+        .choice("Compression", &["None", "Deflate"])
+        .int("Retention", 0)
+        .timestamp("LastRun")
+        .apply(&mut store)?;
+    let dir = temp_dir();
+
+    let nightly = store.create_entity(et_schedule, None, "Nightly")?;
+    let set = |store: &mut Store, field: &str, value: Value| -> Result<()> {
+        let field_type = store.get_field_type(field)?;
+        store.write(nightly, &[field_type], value, None, None, None, None)
+    };
+    set(&mut store, "Schedule", Value::String("0 2 * * *".to_string()))?;
+    set(&mut store, "Directory", Value::String(dir.to_string_lossy().to_string()))?;
+    set(&mut store, "Compression", Value::Choice(1))?;
+    set(&mut store, "Retention", Value::Int(2))?;
+
+    let mut scheduler = BackupScheduler::new();
+    // The first tick only records the starting point
+    assert!(scheduler.tick(&mut store, utc("2024-03-10T01:00:00Z"))?.is_empty());
+    assert!(scheduler.tick(&mut store, utc("2024-03-10T01:59:00Z"))?.is_empty());
+
+    // Three nights pass without a scheduler: one backup makes up for them
+    let written = scheduler.tick(&mut store, utc("2024-03-12T02:00:30Z"))?;
+    assert_eq!(written.len(), 1);
+    assert!(written[0].join("snapshot.bin.z").is_file());
+    assert!(scheduler.tick(&mut store, utc("2024-03-12T03:00:00Z"))?.is_empty());
+
+    // The retention is read from the entity too
+    scheduler.tick(&mut store, utc("2024-03-13T02:00:00Z"))?;
+    let last = scheduler.tick(&mut store, utc("2024-03-14T02:00:00Z"))?;
+    let kept = list_backups(&dir)?;
+    assert_eq!(kept.len(), 2);
+    assert_eq!(kept[1].0, last[0]);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+    Ok(())
+}
@@ -0,0 +1,171 @@
+#[allow(unused_imports)]
+use crate::*;
+#[allow(unused_imports)]
+use crate::bootstrap::{apply, plan, Change, Manifest};
+
+#[allow(dead_code)]
+const PLANT: &str = r#"
+[[schemas]]
+entityType = "Object"
+fields = [
+    { name = "Name", dataType = "String", default = "" },
+    { name = "Parent", dataType = "EntityReference" },
+    { name = "Children", dataType = "EntityList", default = [] },
+]
+
+[[schemas]]
+entityType = "Root"
+inheritsFrom = ["Object"]
+fields = []
+
+[[schemas]]
+entityType = "Tank"
+inheritsFrom = ["Object"]
+fields = [{ name = "Level", dataType = "Float", default = 0.0 }]
+
+[[schemas]]
+entityType = "Pump"
+inheritsFrom = ["Object"]
+fields = [
+    { name = "Speed", dataType = "Float", default = 0.0 },
+    { name = "Mode", dataType = "Choice", choices = ["Off", "Auto"], default = "Off" },
+    { name = "Feeds", dataType = "EntityReference" },
+]
+
+[[entities]]
+entityType = "Root"
+Name = "Root"
+
+[[entities.Children]]
+entityType = "Pump"
+Name = "P1"
+Speed = 1200
+Mode = "Auto"
+Feeds = "Root/T1"
+
+[[entities.Children]]
+entityType = "Tank"
+Name = "T1"
+"#;
+
+#[test]
+fn test_bootstrap_apply_is_idempotent() -> Result<()> {
+    let mut store = Store::new();
+    let manifest = Manifest::from_toml(PLANT)?;
+
+    let dry_run = plan(&mut store, &manifest)?;
+    assert!(dry_run.changes.contains(&Change::CreateType { entity_type: "Pump".into() }));
+    assert!(dry_run.changes.contains(&Change::CreateEntity { path: "Root/P1".into(), entity_type: "Pump".into() }));
+    assert!(store.get_entity_type("Pump").is_err());
+
+    let applied = apply(&mut store, &manifest)?;
+    assert_eq!(applied, dry_run);
+    let pump = path_to_entity_id(&store, "Root/P1")?;
+    let tank = path_to_entity_id(&store, "Root/T1")?;
+    assert_eq!(store.read(pump, &[store.get_field_type("Speed")?])?.0, Value::Float(1200.0));
+    assert_eq!(store.read(pump, &[store.get_field_type("Mode")?])?.0, Value::Choice(1));
+    assert_eq!(store.read(pump, &[store.get_field_type("Feeds")?])?.0, Value::EntityReference(Some(tank)));
+
+    assert!(plan(&mut store, &manifest)?.is_empty());
+    assert!(apply(&mut store, &manifest)?.is_empty());
+    assert_eq!(store.find_entities(store.get_entity_type("Pump")?, None)?, vec![pump]);
+    Ok(())
+}
+
+#[test]
+fn test_bootstrap_yaml_updates_existing_store() -> Result<()> {
+    let mut store = Store::new();
+    apply(&mut store, &Manifest::from_toml(PLANT)?)?;
+    let pump = path_to_entity_id(&store, "Root/P1")?;
+
+    let manifest = Manifest::from_yaml(
+        r#"
+schemas:
+  - entityType: Pump
+    inheritsFrom: [Object]
+    fields:
+      - { name: Speed, dataType: Float, default: 0.0 }
+      - { name: Mode, dataType: Choice, choices: [Off, Auto], default: "Off" }
+      - { name: Feeds, dataType: EntityReference }
+      - { name: Serial, dataType: String, default: "" }
+entities:
+  - entityType: Root
+    Name: Root
+    Children:
+      - { entityType: Pump, Name: P1, Speed: 900.0, Serial: A-17 }
+"#,
+    )?;
+    let changes = apply(&mut store, &manifest)?.changes;
+    assert_eq!(
+        changes,
+        vec![
+            Change::UpdateType { entity_type: "Pump".into(), added: vec!["Serial".into()], changed: vec![], removed: vec![] },
+            Change::SetField { path: "Root/P1".into(), field: "Serial".into(), from: Some(serde_json::json!("")), to: serde_json::json!("A-17") },
+            Change::SetField { path: "Root/P1".into(), field: "Speed".into(), from: Some(serde_json::json!(1200.0)), to: serde_json::json!(900.0) },
+        ]
+    );
+    assert_eq!(changes[2].to_string(), "~ Root/P1.Speed: 1200.0 -> 900.0");
+    assert_eq!(store.read(pump, &[store.get_field_type("Serial")?])?.0, Value::from_string("A-17".to_string()));
+    // Fields the manifest leaves out keep their values
+    assert_eq!(store.read(pump, &[store.get_field_type("Mode")?])?.0, Value::Choice(1));
+    assert!(apply(&mut store, &manifest)?.is_empty());
+
+    let clash = Manifest::from_yaml("entities:\n  - { entityType: Root, Name: Root, Children: [{ entityType: Tank, Name: P1 }] }\n")?;
+    assert!(matches!(apply(&mut store, &clash), Err(Error::InvalidRequest(_))));
+    assert!(Manifest::from_toml("schemas = 3").is_err());
+    Ok(())
+}
+
+
+#[test]
+fn test_bootstrap_overlays_take_precedence() -> Result<()> {
+    let dir = std::env::temp_dir().join(format!("qlib-bootstrap-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("base.toml"), PLANT).unwrap();
+    std::fs::write(
+        dir.join("site.yaml"),
+        r#"
+schemas:
+  - entityType: Pump
+    fields:
+      - { name: Speed, dataType: Float, default: 0.0, unit: rpm, max: 3000.0 }
+      - { name: Serial, dataType: String }
+entities:
+  - entityType: Root
+    Name: Root
+    Children:
+      - { entityType: Pump, Name: P1, Speed: 1500.0 }
+      - { entityType: Pump, Name: P2, Mode: Auto }
+"#,
+    )
+    .unwrap();
+    std::fs::write(dir.join("machine.yml"), "entities:\n  - { entityType: Root, Name: Root, Children: [{ entityType: Pump, Name: P1, Serial: M-9 }] }\n").unwrap();
+
+    let manifest = Manifest::from_files([dir.join("base.toml"), dir.join("site.yaml"), dir.join("machine.yml")])?;
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    let pump = manifest.schemas.iter().find(|schema| schema.entity_type == "Pump").unwrap();
+    assert_eq!(pump.inherits_from, vec!["Object".to_string()]);
+    let names: Vec<&str> = pump.fields.iter().map(|field| field.name.as_str()).collect();
+    assert_eq!(names, vec!["Speed", "Mode", "Feeds", "Serial"]);
+    assert_eq!(pump.fields[0].max, Some(3000.0));
+
+    let mut store = Store::new();
+    let dry_run = plan(&mut store, &manifest)?;
+    assert!(dry_run.to_string().contains("+ Root/P2 (Pump)\n"));
+    assert!(dry_run.to_string().contains("~ Root/P1.Speed = 1500.0\n"));
+    apply(&mut store, &manifest)?;
+
+    let p1 = path_to_entity_id(&store, "Root/P1")?;
+    // The site's speed, the base's mode and reference, the machine's serial
+    assert_eq!(store.read(p1, &[store.get_field_type("Speed")?])?.0, Value::Float(1500.0));
+    assert_eq!(store.read(p1, &[store.get_field_type("Mode")?])?.0, Value::Choice(1));
+    assert_eq!(store.read(p1, &[store.get_field_type("Feeds")?])?.0, Value::EntityReference(Some(path_to_entity_id(&store, "Root/T1")?)));
+    assert_eq!(store.read(p1, &[store.get_field_type("Serial")?])?.0, Value::from_string("M-9".to_string()));
+    let p2 = path_to_entity_id(&store, "Root/P2")?;
+    assert_eq!(store.read(p2, &[store.get_field_type("Mode")?])?.0, Value::Choice(1));
+    assert!(apply(&mut store, &manifest)?.is_empty());
+
+    assert!(Manifest::from_files(["plant.ini"]).is_err());
+    Ok(())
+}
@@ -0,0 +1,108 @@
+#[allow(unused_imports)]
+use crate::*;
+#[allow(unused_imports)]
+use crate::capi::*;
+#[allow(unused_imports)]
+use std::ffi::{CStr, CString};
+#[allow(unused_imports)]
+use std::io::{Read, Write};
+#[allow(unused_imports)]
+use std::net::TcpListener;
+#[allow(unused_imports)]
+use std::ptr;
+
+/// Accept one connection and answer each request with the next reply
+#[allow(dead_code)]
+fn serve_script(replies: Vec<Vec<u8>>) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    std::thread::spawn(move || {
+        if let Ok((mut stream, _)) = listener.accept() {
+            let mut request = [0u8; 4096];
+            for reply in replies {
+                let _ = stream.read(&mut request);
+                let _ = stream.write_all(&reply);
+            }
+            while matches!(stream.read(&mut request), Ok(n) if n > 0) {}
+        }
+    });
+    address
+}
+
+#[allow(dead_code)]
+fn last_error() -> String {
+    unsafe { CStr::from_ptr(qlib_last_error()) }.to_string_lossy().into_owned()
+}
+
+#[test]
+fn test_capi_values() {
+    unsafe {
+        let value = qlib_value_float(12.5);
+        assert_eq!(qlib_value_kind(value), QlibValueKind::Float);
+        let mut float = 0.0;
+        assert_eq!(qlib_value_get_float(value, &mut float), QlibStatus::Ok);
+        assert_eq!(float, 12.5);
+        let mut int = 0;
+        assert_eq!(qlib_value_get_int(value, &mut int), QlibStatus::InvalidArgument);
+        assert_eq!(last_error(), "value isn't an Int or Choice");
+        qlib_value_free(value);
+
+        let name = CString::new("Pump 3").unwrap();
+        let value = qlib_value_string(name.as_ptr());
+        let copy = qlib_value_get_string(value);
+        assert_eq!(CStr::from_ptr(copy), name.as_c_str());
+        qlib_string_free(copy);
+        qlib_value_free(value);
+
+        let value = qlib_value_entity_reference(0);
+        let mut entity_id = 1;
+        assert_eq!(qlib_value_get_entity_reference(value, &mut entity_id), QlibStatus::Ok);
+        assert_eq!(entity_id, 0);
+        qlib_value_free(value);
+
+        let value = qlib_value_timestamp(-1_500);
+        let mut nanos = 0;
+        assert_eq!(qlib_value_get_timestamp(value, &mut nanos), QlibStatus::Ok);
+        assert_eq!(nanos, -1_500);
+        qlib_value_free(value);
+
+        let value = qlib_value_blob(b"\x00\x01".as_ptr(), 2);
+        let mut len = 0;
+        let data = qlib_value_get_blob(value, &mut len);
+        assert_eq!(std::slice::from_raw_parts(data, len), b"\x00\x01");
+        qlib_value_free(value);
+    }
+}
+
+#[test]
+fn test_capi_store_calls() {
+    use crate::data::resp::{OwnedRespValue, ResolvePathResponse, RespEncode, RespToBytes};
+
+    let machine = EntityId::new(EntityType(1), 7);
+    let mut replies = vec![ResolvePathResponse { entity_id: machine }.encode().to_bytes()];
+    replies.push(OwnedRespValue::Error("ERR Entity not found".to_string()).to_bytes());
+    // QUIT, sent by qlib_disconnect
+    replies.push(b"+OK\r\n".to_vec());
+    let address = CString::new(serve_script(replies)).unwrap();
+
+    unsafe {
+        let store = qlib_connect(address.as_ptr());
+        assert!(!store.is_null());
+
+        let path = CString::new("Root/Machines/M1").unwrap();
+        let mut entity_id = 0;
+        assert_eq!(qlib_resolve_path(store, path.as_ptr(), &mut entity_id), QlibStatus::Ok);
+        assert_eq!(entity_id, machine.0);
+        assert_eq!(qlib_resolve_path(store, path.as_ptr(), &mut entity_id), QlibStatus::Error);
+        assert!(last_error().contains("Entity not found"));
+
+        assert_eq!(qlib_resolve_path(store, ptr::null(), &mut entity_id), QlibStatus::InvalidArgument);
+        assert_eq!(last_error(), "path is NULL");
+        assert!(qlib_subscription_next(ptr::null()).is_null());
+        qlib_disconnect(store);
+    }
+
+    let refused = CString::new("127.0.0.1:1").unwrap();
+    assert!(unsafe { qlib_connect(refused.as_ptr()) }.is_null());
+    assert!(last_error().starts_with("Store proxy error"));
+}
@@ -23,6 +23,8 @@ fn setup_test_store_with_entity() -> Result<(Store, EntityId)> {
             default_value: String::new(),
             rank: 0,
             storage_scope: StorageScope::Runtime,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     schema.fields.insert(
@@ -32,6 +34,8 @@ fn setup_test_store_with_entity() -> Result<(Store, EntityId)> {
             default_value: None,
             rank: 1,
             storage_scope: StorageScope::Configuration,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     schema.fields.insert(
@@ -41,6 +45,9 @@ fn setup_test_store_with_entity() -> Result<(Store, EntityId)> {
             default_value: Vec::new(),
             rank: 2,
             storage_scope: StorageScope::Configuration,
+            merge_policy: MergePolicy::LastWriterWins,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     
@@ -51,6 +58,9 @@ fn setup_test_store_with_entity() -> Result<(Store, EntityId)> {
             default_value: 0,
             rank: 3,
             storage_scope: StorageScope::Runtime,
+            merge_policy: MergePolicy::LastWriterWins,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     
@@ -61,6 +71,9 @@ fn setup_test_store_with_entity() -> Result<(Store, EntityId)> {
             default_value: 0.0,
             rank: 4,
             storage_scope: StorageScope::Runtime,
+            merge_policy: MergePolicy::LastWriterWins,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     
@@ -71,6 +84,8 @@ fn setup_test_store_with_entity() -> Result<(Store, EntityId)> {
             default_value: false,
             rank: 5,
             storage_scope: StorageScope::Runtime,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     
@@ -80,8 +95,11 @@ fn setup_test_store_with_entity() -> Result<(Store, EntityId)> {
             field_type: "Status".to_string(),
             default_value: 0,
             choices: vec!["Inactive".to_string(), "Active".to_string(), "Pending".to_string()],
+            choices_source: None,
             rank: 6,
             storage_scope: StorageScope::Runtime,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     
@@ -92,6 +110,8 @@ fn setup_test_store_with_entity() -> Result<(Store, EntityId)> {
             default_value: None,
             rank: 7,
             storage_scope: StorageScope::Runtime,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     
@@ -102,6 +122,9 @@ fn setup_test_store_with_entity() -> Result<(Store, EntityId)> {
             default_value: vec![],
             rank: 8,
             storage_scope: StorageScope::Runtime,
+            merge_policy: MergePolicy::LastWriterWins,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     
@@ -112,6 +135,8 @@ fn setup_test_store_with_entity() -> Result<(Store, EntityId)> {
             default_value: epoch(),
             rank: 9,
             storage_scope: StorageScope::Runtime,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     
@@ -122,6 +147,8 @@ fn setup_test_store_with_entity() -> Result<(Store, EntityId)> {
             default_value: vec![],
             rank: 10,
             storage_scope: StorageScope::Runtime,
+            validator: None,
+            metadata: Default::default(),
         }
     );
 
@@ -479,6 +506,8 @@ fn test_cel_executor_execute_with_indirection() -> Result<()> {
             default_value: String::new(),
             rank: 0,
             storage_scope: StorageScope::Runtime,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     dept_schema.fields.insert(
@@ -488,6 +517,8 @@ fn test_cel_executor_execute_with_indirection() -> Result<()> {
             default_value: None,
             rank: 1,
             storage_scope: StorageScope::Configuration,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     dept_schema.fields.insert(
@@ -497,6 +528,9 @@ fn test_cel_executor_execute_with_indirection() -> Result<()> {
             default_value: Vec::new(),
             rank: 2,
             storage_scope: StorageScope::Configuration,
+            merge_policy: MergePolicy::LastWriterWins,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     dept_schema.fields.insert(
@@ -506,6 +540,9 @@ fn test_cel_executor_execute_with_indirection() -> Result<()> {
             default_value: 0,
             rank: 3,
             storage_scope: StorageScope::Runtime,
+            merge_policy: MergePolicy::LastWriterWins,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     store.update_schema(dept_schema)?;
@@ -519,6 +556,8 @@ fn test_cel_executor_execute_with_indirection() -> Result<()> {
             default_value: String::new(),
             rank: 0,
             storage_scope: StorageScope::Runtime,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     user_schema.fields.insert(
@@ -528,6 +567,8 @@ fn test_cel_executor_execute_with_indirection() -> Result<()> {
             default_value: None,
             rank: 1,
             storage_scope: StorageScope::Configuration,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     user_schema.fields.insert(
@@ -537,6 +578,9 @@ fn test_cel_executor_execute_with_indirection() -> Result<()> {
             default_value: Vec::new(),
             rank: 2,
             storage_scope: StorageScope::Configuration,
+            merge_policy: MergePolicy::LastWriterWins,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     user_schema.fields.insert(
@@ -546,6 +590,8 @@ fn test_cel_executor_execute_with_indirection() -> Result<()> {
             default_value: None,
             rank: 3,
             storage_scope: StorageScope::Runtime,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     store.update_schema(user_schema)?;
@@ -600,6 +646,8 @@ fn test_cel_executor_execute_with_deep_indirection() -> Result<()> {
             default_value: String::new(),
             rank: 0,
             storage_scope: StorageScope::Runtime,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     company_schema.fields.insert(
@@ -609,6 +657,8 @@ fn test_cel_executor_execute_with_deep_indirection() -> Result<()> {
             default_value: None,
             rank: 1,
             storage_scope: StorageScope::Runtime,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     company_schema.fields.insert(
@@ -618,6 +668,9 @@ fn test_cel_executor_execute_with_deep_indirection() -> Result<()> {
             default_value: vec![],
             rank: 2,
             storage_scope: StorageScope::Runtime,
+            merge_policy: MergePolicy::LastWriterWins,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     company_schema.fields.insert(
@@ -627,6 +680,9 @@ fn test_cel_executor_execute_with_deep_indirection() -> Result<()> {
             default_value: 0,
             rank: 3,
             storage_scope: StorageScope::Runtime,
+            merge_policy: MergePolicy::LastWriterWins,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     store.update_schema(company_schema)?;
@@ -640,6 +696,8 @@ fn test_cel_executor_execute_with_deep_indirection() -> Result<()> {
             default_value: String::new(),
             rank: 0,
             storage_scope: StorageScope::Runtime,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     dept_schema.fields.insert(
@@ -649,6 +707,8 @@ fn test_cel_executor_execute_with_deep_indirection() -> Result<()> {
             default_value: None,
             rank: 1,
             storage_scope: StorageScope::Runtime,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     dept_schema.fields.insert(
@@ -658,6 +718,9 @@ fn test_cel_executor_execute_with_deep_indirection() -> Result<()> {
             default_value: vec![],
             rank: 2,
             storage_scope: StorageScope::Runtime,
+            merge_policy: MergePolicy::LastWriterWins,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     dept_schema.fields.insert(
@@ -667,6 +730,8 @@ fn test_cel_executor_execute_with_deep_indirection() -> Result<()> {
             default_value: None,
             rank: 3,
             storage_scope: StorageScope::Runtime,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     store.update_schema(dept_schema)?;
@@ -680,6 +745,8 @@ fn test_cel_executor_execute_with_deep_indirection() -> Result<()> {
             default_value: String::new(),
             rank: 0,
             storage_scope: StorageScope::Runtime,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     employee_schema.fields.insert(
@@ -689,6 +756,8 @@ fn test_cel_executor_execute_with_deep_indirection() -> Result<()> {
             default_value: None,
             rank: 1,
             storage_scope: StorageScope::Runtime,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     employee_schema.fields.insert(
@@ -698,6 +767,9 @@ fn test_cel_executor_execute_with_deep_indirection() -> Result<()> {
             default_value: vec![],
             rank: 2,
             storage_scope: StorageScope::Runtime,
+            merge_policy: MergePolicy::LastWriterWins,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     employee_schema.fields.insert(
@@ -707,6 +779,8 @@ fn test_cel_executor_execute_with_deep_indirection() -> Result<()> {
             default_value: None,
             rank: 3,
             storage_scope: StorageScope::Runtime,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     store.update_schema(employee_schema)?;
@@ -766,6 +840,8 @@ fn test_cel_executor_execute_with_indirection_and_entity_lists() -> Result<()> {
             default_value: String::new(),
             rank: 0,
             storage_scope: StorageScope::Runtime,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     project_schema.fields.insert(
@@ -775,6 +851,8 @@ fn test_cel_executor_execute_with_indirection_and_entity_lists() -> Result<()> {
             default_value: None,
             rank: 1,
             storage_scope: StorageScope::Runtime,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     project_schema.fields.insert(
@@ -784,6 +862,9 @@ fn test_cel_executor_execute_with_indirection_and_entity_lists() -> Result<()> {
             default_value: vec![],
             rank: 2,
             storage_scope: StorageScope::Runtime,
+            merge_policy: MergePolicy::LastWriterWins,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     project_schema.fields.insert(
@@ -793,6 +874,9 @@ fn test_cel_executor_execute_with_indirection_and_entity_lists() -> Result<()> {
             default_value: 0,
             rank: 3,
             storage_scope: StorageScope::Runtime,
+            merge_policy: MergePolicy::LastWriterWins,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     store.update_schema(project_schema)?;
@@ -806,6 +890,8 @@ fn test_cel_executor_execute_with_indirection_and_entity_lists() -> Result<()> {
             default_value: String::new(),
             rank: 0,
             storage_scope: StorageScope::Runtime,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     team_schema.fields.insert(
@@ -815,6 +901,8 @@ fn test_cel_executor_execute_with_indirection_and_entity_lists() -> Result<()> {
             default_value: None,
             rank: 1,
             storage_scope: StorageScope::Runtime,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     team_schema.fields.insert(
@@ -824,6 +912,9 @@ fn test_cel_executor_execute_with_indirection_and_entity_lists() -> Result<()> {
             default_value: vec![],
             rank: 2,
             storage_scope: StorageScope::Runtime,
+            merge_policy: MergePolicy::LastWriterWins,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     team_schema.fields.insert(
@@ -833,6 +924,9 @@ fn test_cel_executor_execute_with_indirection_and_entity_lists() -> Result<()> {
             default_value: vec![],
             rank: 3,
             storage_scope: StorageScope::Runtime,
+            merge_policy: MergePolicy::LastWriterWins,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     store.update_schema(team_schema)?;
@@ -896,6 +990,8 @@ fn test_cel_executor_execute_with_null_entity_reference() -> Result<()> {
             default_value: String::new(),
             rank: 0,
             storage_scope: StorageScope::Runtime,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     user_schema.fields.insert(
@@ -905,6 +1001,8 @@ fn test_cel_executor_execute_with_null_entity_reference() -> Result<()> {
             default_value: None,
             rank: 1,
             storage_scope: StorageScope::Runtime,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     user_schema.fields.insert(
@@ -914,6 +1012,9 @@ fn test_cel_executor_execute_with_null_entity_reference() -> Result<()> {
             default_value: vec![],
             rank: 2,
             storage_scope: StorageScope::Runtime,
+            merge_policy: MergePolicy::LastWriterWins,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     user_schema.fields.insert(
@@ -923,6 +1024,8 @@ fn test_cel_executor_execute_with_null_entity_reference() -> Result<()> {
             default_value: None,
             rank: 3,
             storage_scope: StorageScope::Runtime,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     store.update_schema(user_schema)?;
@@ -1023,6 +1126,8 @@ fn test_cel_executor_execute_with_mixed_field_access() -> Result<()> {
             default_value: String::new(),
             rank: 0,
             storage_scope: StorageScope::Runtime,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     dept_schema.fields.insert(
@@ -1032,6 +1137,8 @@ fn test_cel_executor_execute_with_mixed_field_access() -> Result<()> {
             default_value: None,
             rank: 1,
             storage_scope: StorageScope::Runtime,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     dept_schema.fields.insert(
@@ -1041,6 +1148,9 @@ fn test_cel_executor_execute_with_mixed_field_access() -> Result<()> {
             default_value: vec![],
             rank: 2,
             storage_scope: StorageScope::Runtime,
+            merge_policy: MergePolicy::LastWriterWins,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     store.update_schema(dept_schema)?;
@@ -1054,6 +1164,8 @@ fn test_cel_executor_execute_with_mixed_field_access() -> Result<()> {
             default_value: String::new(),
             rank: 0,
             storage_scope: StorageScope::Runtime,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     user_schema.fields.insert(
@@ -1063,6 +1175,8 @@ fn test_cel_executor_execute_with_mixed_field_access() -> Result<()> {
             default_value: None,
             rank: 1,
             storage_scope: StorageScope::Runtime,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     user_schema.fields.insert(
@@ -1072,6 +1186,9 @@ fn test_cel_executor_execute_with_mixed_field_access() -> Result<()> {
             default_value: vec![],
             rank: 2,
             storage_scope: StorageScope::Runtime,
+            merge_policy: MergePolicy::LastWriterWins,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     user_schema.fields.insert(
@@ -1081,6 +1198,9 @@ fn test_cel_executor_execute_with_mixed_field_access() -> Result<()> {
             default_value: 0,
             rank: 3,
             storage_scope: StorageScope::Runtime,
+            merge_policy: MergePolicy::LastWriterWins,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     user_schema.fields.insert(
@@ -1090,6 +1210,8 @@ fn test_cel_executor_execute_with_mixed_field_access() -> Result<()> {
             default_value: None,
             rank: 4,
             storage_scope: StorageScope::Runtime,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     store.update_schema(user_schema)?;
@@ -1138,4 +1260,190 @@ fn test_cel_executor_execute_with_mixed_field_access() -> Result<()> {
     }
 
     Ok(())
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_filter_plan_pushes_down_equality_predicates() -> Result<()> {
+    use crate::expr::planner::FilterPlan;
+
+    let (store, entity_id) = setup_test_store_with_entity()?;
+    let mut executor = CelExecutor::new();
+
+    let plan = FilterPlan::new(&mut executor, "Name == 'John Doe' && Age == 30 && Status == 1", &store)?;
+    assert_eq!(plan.predicates().len(), 3);
+    assert!(!plan.has_residual());
+    assert_eq!(plan.evaluate(entity_id, &store), Some(true));
+
+    let plan = FilterPlan::new(&mut executor, "Name == 'Jane' && Score > 90.0", &store)?;
+    assert_eq!(plan.predicates().len(), 1);
+    assert!(plan.has_residual());
+    assert_eq!(plan.evaluate(entity_id, &store), Some(false));
+
+    // Residual conditions still need the full expression
+    let plan = FilterPlan::new(&mut executor, "'John Doe' == Name && Score > 90.0", &store)?;
+    assert_eq!(plan.evaluate(entity_id, &store), None);
+
+    Ok(())
+}
+
+#[test]
+fn test_filter_plan_looks_up_predicates_in_indexes() -> Result<()> {
+    use crate::expr::planner::FilterPlan;
+    use crate::testing::SchemaBuilder;
+
+    let mut store = Store::new();
+    let et_pump = SchemaBuilder::object("Pump")
+        .bool("Is_Running", false)
+        .float("Flow", 0.0)
+        .apply(&mut store)?;
+    let ft_running = store.get_field_type("Is_Running")?;
+    let station = store.create_entity(et_pump, None, "Station")?;
+    let pump_a = store.create_entity(et_pump, Some(station), "A")?;
+    let pump_b = store.create_entity(et_pump, Some(station), "B")?;
+    store.write(pump_a, &[ft_running], Value::Bool(true), None, None, None, None)?;
+
+    // Field names with underscores are resolved against the schema
+    let mut executor = CelExecutor::new();
+    let plan = FilterPlan::new(&mut executor, "Is_Running == true", &store)?;
+    assert_eq!(plan.predicates().len(), 1);
+    assert!(!plan.has_residual());
+    assert_eq!(plan.candidates(&store), Some(vec![pump_a]));
+
+    // Indirections are followed back through the reference index
+    let plan = FilterPlan::new(&mut executor, "Parent->Name == 'Station' && Is_Running == false", &store)?;
+    assert_eq!(plan.candidates(&store), Some(vec![pump_b]));
+
+    // Floats aren't indexed, so every entity is checked
+    let plan = FilterPlan::new(&mut executor, "Flow == 0.0", &store)?;
+    assert_eq!(plan.candidates(&store), None);
+
+    // Writes keep the index up to date
+    store.write(pump_b, &[ft_running], Value::Bool(true), None, None, None, None)?;
+    assert_eq!(store.find_entities(et_pump, Some("Is_Running == true"))?, vec![pump_a, pump_b]);
+    store.write(pump_a, &[ft_running], Value::Bool(false), None, None, None, None)?;
+    assert_eq!(store.find_entities(et_pump, Some("Is_Running == true"))?, vec![pump_b]);
+    store.delete_entity(pump_b)?;
+    assert_eq!(store.find_entities(et_pump, Some("Is_Running == true"))?, vec![]);
+
+    Ok(())
+}
+
+#[test]
+fn test_value_index_follows_creates_rollbacks_and_schema_changes() -> Result<()> {
+    use crate::expr::planner::FilterPlan;
+    use crate::testing::SchemaBuilder;
+
+    let mut store = Store::new();
+    let et_pump = SchemaBuilder::object("Pump").bool("Is_Running", false).apply(&mut store)?;
+    let et_valve = SchemaBuilder::object("Valve").int("Mode", 0).apply(&mut store)?;
+    let pump_a = store.create_entity(et_pump, None, "A")?;
+    let valve = store.create_entity(et_valve, None, "V")?;
+
+    let mut executor = CelExecutor::new();
+    let stopped = FilterPlan::new(&mut executor, "Is_Running == false", &store)?;
+    let manual = FilterPlan::new(&mut executor, "Mode == 0", &store)?;
+    assert_eq!(stopped.candidates(&store), Some(vec![pump_a]));
+    assert_eq!(manual.candidates(&store), Some(vec![valve]));
+
+    // Created entities are added to the built index
+    let pump_b = store.create_entity(et_pump, None, "B")?;
+    assert_eq!(stopped.candidates(&store), Some(vec![pump_a, pump_b]));
+
+    // A failed change leaves the index as it was before
+    let ft_running = store.get_field_type("Is_Running")?;
+    let result = store.atomically(|store| {
+        store.write(pump_a, &[ft_running], Value::Bool(true), None, None, None, None)?;
+        store.create_entity(et_pump, None, "C")?;
+        store.delete_entity(pump_b)?;
+        Err::<(), _>(Error::InvalidRequest("rolled back".to_string()))
+    });
+    assert!(result.is_err());
+    assert_eq!(stopped.candidates(&store), Some(vec![pump_a, pump_b]));
+
+    // Fields added to or removed from a schema are added to or removed from the index
+    SchemaBuilder::object("Pump").bool("Is_Running", false).int("Mode", 0).apply(&mut store)?;
+    assert_eq!(manual.candidates(&store), Some(vec![pump_a, pump_b, valve]));
+    assert_eq!(stopped.candidates(&store), Some(vec![pump_a, pump_b]));
+    SchemaBuilder::object("Pump").bool("Is_Running", false).apply(&mut store)?;
+    assert_eq!(manual.candidates(&store), Some(vec![valve]));
+
+    store.delete_entity(pump_a)?;
+    assert_eq!(stopped.candidates(&store), Some(vec![pump_b]));
+
+    Ok(())
+}
+
+#[test]
+fn test_find_entities_with_pushdown_filter() -> Result<()> {
+    let (mut store, entity_id) = setup_test_store_with_entity()?;
+    let et_test = store.get_entity_type("TestEntity")?;
+    let ft_age = store.get_field_type("Age")?;
+
+    let parent_id = store.create_entity(et_test, None, "Parent")?;
+    let child_id = store.create_entity(et_test, Some(parent_id), "Child")?;
+    store.write(child_id, &[ft_age], Value::Int(30), None, None, None, None)?;
+
+    assert_eq!(store.find_entities(et_test, Some("Age == 30"))?, vec![entity_id, child_id]);
+    assert_eq!(store.find_entities(et_test, Some("Age == 30 && Parent->Name == 'Parent'"))?, vec![child_id]);
+    assert_eq!(store.find_entities(et_test, Some("Age == 30 && Name != 'Child'"))?, vec![entity_id]);
+
+    let page = store.find_entities_exact(et_test, None, Some("Name == 'Child' && IsActive == false"))?;
+    assert_eq!(page.items, vec![child_id]);
+
+    Ok(())
+}
+
+#[test]
+fn test_cel_executor_execute_with_params() -> Result<()> {
+    let (store, entity_id) = setup_test_store_with_entity()?;
+    let mut executor = CelExecutor::new();
+
+    let mut params = std::collections::HashMap::new();
+    params.insert("new".to_string(), Value::Int(42));
+    params.insert("Age".to_string(), Value::Int(40));
+
+    // Params are bound without touching the store and shadow stored fields
+    let result = executor.execute_with_params("new > Age && Name == 'John Doe'", entity_id, &params, &store)?;
+    assert_eq!(result, cel::Value::Bool(true));
+
+    let result = executor.execute("Age == 30", entity_id, &store)?;
+    assert_eq!(result, cel::Value::Bool(true));
+
+    Ok(())
+}
+
+#[test]
+fn test_cel_executor_execute_async() -> Result<()> {
+    let (store, entity_id) = setup_test_store_with_entity()?;
+    let mut store = AsyncStoreAdapter::new(store);
+    let mut executor = CelExecutor::new();
+
+    // Written once against the async trait, usable with any store
+    async fn bump_age(store: &mut impl AsyncStoreTrait, entity_id: EntityId) -> Result<()> {
+        let ft_age = store.get_field_type("Age").await?;
+        let (age, _, _) = store.read(entity_id, &[ft_age]).await?;
+        store.write(entity_id, &[ft_age], Value::Int(age.as_int().unwrap_or(0) + 1), None, None, None, None).await
+    }
+
+    let runtime = tokio::runtime::Runtime::new().map_err(|e| Error::ExecutionError(e.to_string()))?;
+    runtime.block_on(async {
+        bump_age(&mut store, entity_id).await?;
+
+        let result = executor.execute_async("Age == 31 && Name == 'John Doe'", entity_id, &store).await?;
+        assert_eq!(result, cel::Value::Bool(true));
+
+        let mut params = std::collections::HashMap::new();
+        params.insert("Age".to_string(), Value::Int(5));
+        let result = executor.execute_with_params_async("Age + 1", entity_id, &params, &store).await?;
+        assert_eq!(result, cel::Value::Int(6));
+
+        let fields = executor.referenced_fields_async("Age > 1 && Parent->Name != ''", &store).await?;
+        assert_eq!(fields.len(), 2);
+        Ok::<_, Error>(())
+    })?;
+
+    // The sync and async paths agree
+    let result = executor.execute("Age == 31", entity_id, store.inner())?;
+    assert_eq!(result, cel::Value::Bool(true));
+    Ok(())
+}
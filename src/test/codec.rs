@@ -0,0 +1,83 @@
+#[allow(unused_imports)]
+use crate::*;
+#[allow(unused_imports)]
+use crate::data::codec::{decode_frame, encode_frame, Codec, FRAME_HEADER_SIZE};
+
+#[test]
+fn test_codec_names_and_frames() -> Result<()> {
+    assert_eq!(Codec::default(), Codec::Resp);
+    assert_eq!(Codec::from_name("RKYV"), Some(Codec::Rkyv));
+    assert_eq!(Codec::from_name(Codec::Resp.name()), Some(Codec::Resp));
+    assert_eq!(Codec::from_name(Codec::RespBinaryIds.name()), Some(Codec::RespBinaryIds));
+    assert!(Codec::RespBinaryIds.is_resp() && !Codec::Rkyv.is_resp());
+    assert_eq!(Codec::from_name("msgpack"), None);
+    assert_eq!(Codec::Rkyv.is_supported(), cfg!(feature = "rkyv"));
+
+    let limits = ProtocolLimits::new(16);
+    let mut input = encode_frame(b"hello");
+    input.extend_from_slice(b"rest");
+    assert_eq!(decode_frame(&input, &limits)?, Some((&b"hello"[..], &b"rest"[..])));
+
+    // Partial frames wait for more data, oversized ones fail on the header
+    assert_eq!(decode_frame(&input[..FRAME_HEADER_SIZE + 2], &limits)?, None);
+    assert_eq!(decode_frame(&input[..2], &limits)?, None);
+    let oversized = encode_frame(&[0u8; 17]);
+    assert!(matches!(decode_frame(&oversized[..FRAME_HEADER_SIZE], &limits), Err(Error::FrameTooLarge(17, 16))));
+    Ok(())
+}
+
+#[cfg(feature = "rkyv")]
+#[test]
+fn test_rkyv_codec_round_trip() -> Result<()> {
+    use crate::data::codec::rkyv::{self as wire, StoreCommand, StoreResponse, WireValue};
+    use crate::data::resp::{ReadResponse, RespFromBytes, RespDecode, RespValue, WriteCommand};
+    use std::collections::BTreeMap;
+
+    let entity_id = EntityId::new(EntityType(3), 77);
+    let value = Value::Map(BTreeMap::from([
+        ("limit".to_string(), Value::Decimal(Decimal::new(12_345, 2)?)),
+        ("since".to_string(), Value::Timestamp(secs_to_timestamp(1_700_000_000))),
+        ("window".to_string(), Value::Duration(Duration::milliseconds(1_500))),
+        ("owners".to_string(), Value::EntityList(vec![entity_id])),
+    ]));
+    assert_eq!(Value::try_from(WireValue::from(&value))?, value);
+
+    let write = WriteCommand {
+        entity_id,
+        field_path: vec![FieldType(5), FieldType(6)],
+        value: value.clone(),
+        writer_id: None,
+        write_time: Some(epoch()),
+        push_condition: Some(PushCondition::Changes),
+        adjust_behavior: Some(AdjustBehavior::Add),
+        _marker: std::marker::PhantomData,
+    };
+    let command = StoreCommand::from(&write);
+    let frame = wire::encode_command(&command)?;
+    let (payload, rest) = decode_frame(&frame, &ProtocolLimits::default())?.expect("whole frame");
+    assert!(rest.is_empty());
+    assert_eq!(wire::decode_command(payload)?, command);
+
+    // Read in place from an aligned buffer
+    let mut aligned = ::rkyv::util::AlignedVec::<{ wire::FRAME_ALIGNMENT }>::new();
+    aligned.extend_from_slice(payload);
+    assert_eq!(wire::access_command(&aligned)?.entity_id(), Some(entity_id));
+
+    // The RESP form of the command is what it was made from
+    let resp = command.into_resp_command()?;
+    let decoded = WriteCommand::decode(RespValue::from_bytes(&resp)?.0)?;
+    assert_eq!(decoded.value, value);
+    assert_eq!(decoded.adjust_behavior, Some(AdjustBehavior::Add));
+
+    let read = ReadResponse { value: Value::Int(9), timestamp: epoch(), writer_id: Some(entity_id) };
+    let frame = wire::encode_response(&StoreResponse::from(&read))?;
+    let (payload, _) = decode_frame(&frame, &ProtocolLimits::default())?.expect("whole frame");
+    let response = wire::decode_response(payload)?.into_read_response()?;
+    assert_eq!((response.value, response.writer_id), (Value::Int(9), Some(entity_id)));
+
+    // Error replies carry the RESP error text
+    let error = StoreResponse::Error("ERR_TIMEOUT after 50 ms".to_string());
+    assert!(matches!(error.into_entity_id(), Err(Error::Timeout(_))));
+    assert!(wire::decode_command(b"not an archive").is_err());
+    Ok(())
+}
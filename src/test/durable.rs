@@ -0,0 +1,107 @@
+#[allow(unused_imports)]
+use crate::*;
+#[allow(unused_imports)]
+use crate::testing::SchemaBuilder;
+
+#[allow(dead_code)]
+fn sensor_store() -> Result<(Store, EntityId, FieldType)> {
+    let mut store = Store::new();
+    let et_sensor = SchemaBuilder::object("Sensor").int("Reading", 0).apply(&mut store)?;
+    let sensor = store.create_entity(et_sensor, None, "boiler")?;
+    let ft_reading = store.get_field_type("Reading")?;
+    Ok((store, sensor, ft_reading))
+}
+
+#[allow(dead_code)]
+fn write_reading(store: &mut Store, sensor: EntityId, ft_reading: FieldType, value: i64) -> Result<()> {
+    store.write(sensor, &[ft_reading], Value::Int(value), None, None, None, None)
+}
+
+#[allow(dead_code)]
+fn readings(commands: &[crate::data::resp::DurableNotificationCommand]) -> Vec<(u64, Option<Value>)> {
+    commands.iter().map(|command| (command.seq, command.notification.current.value.clone())).collect()
+}
+
+#[test]
+fn test_durable_subscription_replays_missed_notifications() -> Result<()> {
+    let (mut store, sensor, ft_reading) = sensor_store()?;
+    let config = NotifyConfig::EntityId {
+        entity_id: sensor,
+        field_type: ft_reading,
+        trigger_on_change: false,
+        context: vec![],
+        filter: None,
+    };
+
+    let mut durable = DurableSubscriptions::default();
+    assert_eq!(durable.subscribe(&mut store, "alarms", config.clone())?, 0);
+
+    write_reading(&mut store, sensor, ft_reading, 1)?;
+    let live = durable.poll();
+    assert_eq!(readings(&live), vec![(1, Some(Value::Int(1)))]);
+    assert_eq!(live[0].subscription_id, "alarms");
+
+    // The consumer goes away after handling 1, and two writes happen meanwhile
+    write_reading(&mut store, sensor, ft_reading, 2)?;
+    assert_eq!(durable.poll().len(), 1);
+    write_reading(&mut store, sensor, ft_reading, 3)?;
+
+    // Attaching again with the same config doesn't start over
+    assert_eq!(durable.subscribe(&mut store, "alarms", config.clone())?, 2);
+    let replayed = durable.resume("alarms", 1)?;
+    assert_eq!(readings(&replayed), vec![(2, Some(Value::Int(2))), (3, Some(Value::Int(3)))]);
+    // Replayed notifications aren't delivered again
+    assert!(durable.poll().is_empty());
+    assert!(durable.resume("alarms", 3)?.is_empty());
+
+    let other_config = NotifyConfig::EntityId {
+        entity_id: sensor,
+        field_type: ft_reading,
+        trigger_on_change: true,
+        context: vec![],
+        filter: None,
+    };
+    assert!(matches!(durable.subscribe(&mut store, "alarms", other_config), Err(Error::InvalidRequest(_))));
+    assert!(matches!(durable.resume("alarms", 4), Err(Error::InvalidRequest(_))));
+    assert!(matches!(durable.resume("unknown", 0), Err(Error::InvalidRequest(_))));
+
+    assert!(durable.unsubscribe(&mut store, "alarms"));
+    write_reading(&mut store, sensor, ft_reading, 4)?;
+    assert!(durable.poll().is_empty());
+    assert_eq!(durable.last_seq("alarms"), None);
+    Ok(())
+}
+
+#[test]
+fn test_durable_subscription_gap_and_restore() -> Result<()> {
+    let (mut store, sensor, ft_reading) = sensor_store()?;
+    let config = NotifyConfig::EntityId {
+        entity_id: sensor,
+        field_type: ft_reading,
+        trigger_on_change: false,
+        context: vec![],
+        filter: None,
+    };
+
+    let mut durable = DurableSubscriptions::new(2);
+    durable.subscribe(&mut store, "history", config.clone())?;
+    for value in 1..=4 {
+        write_reading(&mut store, sensor, ft_reading, value)?;
+    }
+    durable.poll();
+
+    // Only 3 and 4 are kept, so a consumer that stopped at 1 missed 2
+    assert!(matches!(durable.resume("history", 1), Err(Error::NotificationsDiscarded(id, 3)) if id == "history"));
+    assert_eq!(readings(&durable.resume("history", 2)?), vec![(3, Some(Value::Int(3))), (4, Some(Value::Int(4)))]);
+
+    // A server restart: the subscriptions come back from a snapshot
+    let json = serde_json::to_string(&durable).map_err(|e| Error::InvalidRequest(e.to_string()))?;
+    let mut restored: DurableSubscriptions = serde_json::from_str(&json).map_err(|e| Error::InvalidRequest(e.to_string()))?;
+    durable.unsubscribe(&mut store, "history");
+    restored.restore(&mut store)?;
+    assert_eq!(restored.config("history"), Some(&config));
+
+    write_reading(&mut store, sensor, ft_reading, 5)?;
+    assert_eq!(readings(&restored.resume("history", 3)?), vec![(4, Some(Value::Int(4))), (5, Some(Value::Int(5)))]);
+    Ok(())
+}
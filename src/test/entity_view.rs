@@ -0,0 +1,77 @@
+#[allow(unused_imports)]
+use crate::*;
+#[allow(unused_imports)]
+use crate::testing::SchemaBuilder;
+#[allow(unused_imports)]
+use serde::{Deserialize, Serialize};
+
+#[allow(dead_code)]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct PumpConfig {
+    name: String,
+    speed: f64,
+    mode: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    feeds: Option<String>,
+}
+
+#[allow(dead_code)]
+fn pump_store() -> Result<(Store, EntityId, EntityId)> {
+    let mut store = Store::new();
+    let et_pump = SchemaBuilder::object("Pump")
+        .float("Speed", 0.0)
+        .choice("Mode", &["Off", "Auto", "Manual"])
+        .entity_reference("Feeds")
+        .timestamp("Serviced")
+        .apply(&mut store)?;
+    let et_tank = SchemaBuilder::object("Tank").apply(&mut store)?;
+    let tank = store.create_entity(et_tank, None, "T1")?;
+    let pump = store.create_entity(et_pump, Some(tank), "P1")?;
+    Ok((store, pump, tank))
+}
+
+#[test]
+fn test_entity_view_serializes_fields_by_name() -> Result<()> {
+    let (mut store, pump, _) = pump_store()?;
+    store.write(pump, &[store.get_field_type("Speed")?], Value::Float(1200.5), None, None, None, None)?;
+    store.write(pump, &[store.get_field_type("Mode")?], Value::Choice(1), None, None, None, None)?;
+    store.write(pump, &[store.get_field_type("Serviced")?], Value::Timestamp(secs_to_timestamp(1_700_000_000)), None, None, None, None)?;
+
+    let view = EntityView::new(&store, pump)?;
+    assert_eq!(view.entity_id(), pump);
+    assert_eq!(view.get("Mode"), Some(&serde_json::json!("Auto")));
+    // Structure and empty references are left out
+    assert_eq!(view.get(ft::PARENT), None);
+    assert_eq!(view.get("Feeds"), None);
+    assert_eq!(
+        serde_json::to_value(&view).unwrap(),
+        serde_json::json!({"Name": "P1", "Speed": 1200.5, "Mode": "Auto", "Serviced": 1_700_000_000})
+    );
+
+    let config: PumpConfig = entity_to_value(&store, pump)?;
+    assert_eq!(config, PumpConfig { name: "P1".into(), speed: 1200.5, mode: "Auto".into(), feeds: None });
+    assert!(matches!(EntityView::new(&store, EntityId(u64::MAX)), Err(Error::EntityNotFound(_))));
+    Ok(())
+}
+
+#[test]
+fn test_entity_from_value_writes_fields() -> Result<()> {
+    let (mut store, pump, tank) = pump_store()?;
+    let config = PumpConfig { name: "P2".into(), speed: 900.0, mode: "Manual".into(), feeds: Some(tank.0.to_string()) };
+    assert_eq!(entity_from_value(&mut store, pump, &config)?, 4);
+    assert_eq!(store.read(pump, &[store.get_field_type("Mode")?])?.0, Value::Choice(2));
+    assert_eq!(store.read(pump, &[store.get_field_type("Feeds")?])?.0, Value::EntityReference(Some(tank)));
+    let round_trip: PumpConfig = entity_to_value(&store, pump)?;
+    assert_eq!(round_trip, config);
+
+    // One bad value means nothing is written
+    let bad = serde_json::json!({"Speed": 10.0, "Mode": 7.5});
+    assert!(entity_from_value(&mut store, pump, &bad).is_err());
+    assert_eq!(store.read(pump, &[store.get_field_type("Speed")?])?.0, Value::Float(900.0));
+
+    assert!(matches!(entity_from_value(&mut store, pump, &serde_json::json!({"Parent": null})), Err(Error::InvalidRequest(_))));
+    assert!(entity_from_value(&mut store, pump, &serde_json::json!({"NoSuchField": 1})).is_err());
+    assert!(entity_from_value(&mut store, pump, &serde_json::json!([1, 2])).is_err());
+    Ok(())
+}
@@ -0,0 +1,83 @@
+#[allow(unused_imports)]
+use crate::*;
+#[allow(unused_imports)]
+use crate::testing::SchemaBuilder;
+
+#[allow(dead_code)]
+fn federation() -> Result<(FederatedStore<Store>, EntityType, EntityType)> {
+    let mut federated = FederatedStore::new("hq", Store::new());
+    federated.add_backend("north", Store::new())?;
+    // Applied to both backends, so they agree on type ids
+    let et_site = SchemaBuilder::object("Site").apply(&mut federated)?;
+    let et_pump = SchemaBuilder::object("Pump").int("Power", 0).entity_reference("Site").apply(&mut federated)?;
+    Ok((federated, et_site, et_pump))
+}
+
+#[test]
+fn test_federated_store_routes_and_merges() -> Result<()> {
+    let (mut federated, et_site, et_pump) = federation()?;
+    assert!(matches!(federated.add_backend("north", Store::new()), Err(Error::InvalidRequest(_))));
+    assert!(matches!(federated.route_type(et_pump, "south"), Err(Error::InvalidRequest(_))));
+    federated.route_type(et_pump, "north")?;
+
+    let site = federated.create_entity(et_site, None, "Plant")?;
+    let remote_pump = federated.create_entity(et_pump, None, "P1")?;
+    let other_pump = federated.create_entity(et_pump, None, "P2")?;
+    assert_eq!(federated.backend_of(site), "hq");
+    assert_eq!(federated.backend_of(remote_pump), "north");
+    assert!(federated.backend("north").unwrap().entity_exists(remote_pump));
+    assert!(!federated.backend("hq").unwrap().entity_exists(remote_pump));
+    // A pump's parent has to live with it
+    assert!(federated.create_entity(et_pump, Some(site), "P3").is_err());
+
+    let ft_power = federated.get_field_type("Power")?;
+    let ft_site = federated.get_field_type("Site")?;
+    let ft_name = federated.get_field_type("Name")?;
+    federated.write(remote_pump, &[ft_power], Value::Int(30), None, None, None, None)?;
+    federated.write(remote_pump, &[ft_site], Value::EntityReference(Some(site)), None, None, None, None)?;
+    federated.write(other_pump, &[ft_power], Value::Int(10), None, None, None, None)?;
+
+    // Indirection follows the reference from one backend into the other
+    assert_eq!(federated.read(remote_pump, &[ft_site, ft_name])?.0, Value::String("Plant".to_string()));
+
+    assert_eq!(federated.find_entities(et_pump, None)?, vec![remote_pump, other_pump]);
+    assert_eq!(federated.find_entities(et_pump, Some("Power > 20"))?, vec![remote_pump]);
+
+    let opts = PageOpts::new(1, None).with_order_by(ft_power, SortDirection::Descending);
+    let first = federated.find_entities_paginated(et_pump, Some(&opts), None)?;
+    assert_eq!((first.items.clone(), first.total), (vec![remote_pump], 2));
+    let second = federated.find_entities_paginated(et_pump, first.next_page(&opts).as_ref(), None)?;
+    assert_eq!(second.items, vec![other_pump]);
+    assert!(second.next_page(&opts).is_none());
+
+    let stats = federated.stats()?;
+    assert_eq!(stats.entity_counts.get("Pump"), Some(&2));
+    assert_eq!(federated.take_snapshot().entities.get(&et_pump).map(|pumps| pumps.len()), Some(2));
+
+    federated.delete_entity(remote_pump)?;
+    assert!(!federated.entity_exists(remote_pump));
+    assert_eq!(federated.find_entities(et_pump, None)?, vec![other_pump]);
+    Ok(())
+}
+
+#[test]
+fn test_federated_store_id_ranges() -> Result<()> {
+    let (mut federated, _, et_pump) = federation()?;
+    federated.route_ids(1000..2000, "north")?;
+
+    // The north store mints its ids from 1000
+    let mut seed = Some(EntityId::new(et_pump, 1000));
+    federated.backend_mut("north").unwrap().create_entity_with_id(et_pump, None, &mut seed, "Seed")?;
+    let seed = seed.unwrap();
+
+    let local = federated.create_entity(et_pump, None, "P1")?;
+    let remote = federated.create_entity(et_pump, Some(seed), "P2")?;
+    assert_eq!(remote.extract_id(), 1001);
+    assert_eq!(federated.backend_of(local), "hq");
+    assert_eq!(federated.backend_of(remote), "north");
+    assert_eq!(federated.find_entities(et_pump, None)?, vec![local, seed, remote]);
+
+    let page = federated.find_entities_exact(et_pump, Some(&PageOpts::new(2, Some(1))), None)?;
+    assert_eq!((page.items, page.total, page.next_cursor), (vec![seed, remote], 3, None));
+    Ok(())
+}
@@ -0,0 +1,148 @@
+#[allow(unused_imports)]
+use crate::*;
+#[allow(unused_imports)]
+use crate::data::resp::{OwnedRespValue, RespDecode, RespEncode, RespFromBytes, RespParser, RespToBytes, RespValue, StoreCommand};
+#[allow(unused_imports)]
+use proptest::prelude::*;
+#[allow(unused_imports)]
+use std::collections::BTreeMap;
+
+/// Names of the commands `StoreCommand` decodes, so generated frames get
+/// past the name check and into the argument decoders
+#[allow(dead_code)]
+const COMMAND_NAMES: &[&str] = &[
+    "CUSTOM_READ", "GET", "SET", "FSET", "CREATE", "CREATE_ENTITY_FULL", "INSTANTIATE", "GET_OR_CREATE", "UPSERT", "RENAME", "DEL", "GETTYPE", "RESTYPE", "GETFLD", "RESFLD", "GET_TYPES_BULK",
+    "GETSCH", "GETCSCH", "SETSCH", "GETFSCH", "SETFSCH", "EXISTS", "FEXISTS", "RESOLVE", "RESOLVE_PATH", "READ_PATH",
+    "WRITE_PATH", "READ_BLOB_RANGE", "APPEND_BLOB", "FINDPAG", "FINDEX", "FIND", "FIND_REFERENCING", "SEARCH",
+    "READ_SERIES", "VERIFY", "STATS", "AGGREGATE", "CANCEL", "CODEC", "TYPES", "TYPEPAG", "SNAP", "MACHINE",
+    "AUDIT_QUERY", "GET_CHANGES", "SLOWLOG", "QUIT", "LISTEN", "UNLISTEN", "DLISTEN", "RESUME", "DUNLISTEN", "MULTI", "EXEC", "HANDSHAKE", "FSYNCREQ", "FSYNCRESP",
+    "SYNCSET", "NOTIFY", "DNOTIFY",
+];
+
+#[allow(dead_code)]
+fn resp_value() -> impl Strategy<Value = OwnedRespValue> {
+    let leaf = prop_oneof![
+        "[^\r\n]{0,16}".prop_map(OwnedRespValue::SimpleString),
+        "[^\r\n]{0,16}".prop_map(OwnedRespValue::Error),
+        any::<i64>().prop_map(OwnedRespValue::Integer),
+        proptest::collection::vec(any::<u8>(), 0..32).prop_map(OwnedRespValue::BulkString),
+        Just(OwnedRespValue::Null),
+    ];
+    leaf.prop_recursive(4, 64, 8, |inner| proptest::collection::vec(inner, 0..8).prop_map(OwnedRespValue::Array))
+}
+
+/// Frames that look like commands: a known name followed by anything
+#[allow(dead_code)]
+fn command_frame() -> impl Strategy<Value = OwnedRespValue> {
+    (proptest::sample::select(COMMAND_NAMES), proptest::collection::vec(resp_value(), 0..8)).prop_map(|(name, args)| {
+        let mut elements = vec![OwnedRespValue::BulkString(name.as_bytes().to_vec())];
+        elements.extend(args);
+        OwnedRespValue::Array(elements)
+    })
+}
+
+#[allow(dead_code)]
+fn value() -> impl Strategy<Value = Value> {
+    let entity_id = any::<u64>().prop_map(EntityId);
+    let leaf = prop_oneof![
+        proptest::collection::vec(any::<u8>(), 0..32).prop_map(Value::Blob),
+        any::<bool>().prop_map(Value::Bool),
+        any::<i64>().prop_map(Value::Choice),
+        proptest::collection::vec(entity_id.clone(), 0..8).prop_map(Value::EntityList),
+        proptest::option::of(entity_id).prop_map(Value::EntityReference),
+        any::<f64>().prop_filter("NaN never equals itself", |f| !f.is_nan()).prop_map(Value::Float),
+        any::<i64>().prop_map(Value::Int),
+        ".{0,16}".prop_map(Value::String),
+        (0u64..4_102_444_800_000_000).prop_map(|micros| Value::Timestamp(micros_to_timestamp(micros))),
+        (any::<i64>(), 0..=MAX_DECIMAL_SCALE).prop_map(|(mantissa, scale)| Value::Decimal(Decimal::new(mantissa, scale).expect("scale in range"))),
+        any::<i64>().prop_map(|nanos| Value::Duration(Duration::nanoseconds(nanos))),
+        proptest::collection::vec(".{0,8}", 0..8).prop_map(Value::StringList),
+    ];
+    leaf.prop_recursive(3, 32, 4, |inner| proptest::collection::btree_map(".{0,8}", inner, 0..4).prop_map(Value::Map))
+}
+
+proptest! {
+    #[test]
+    fn test_resp_parser_never_panics(bytes in proptest::collection::vec(any::<u8>(), 0..256)) {
+        let _ = RespValue::from_bytes(&bytes);
+    }
+
+    #[test]
+    fn test_resp_parser_never_panics_on_near_valid_frames(frame in resp_value(), cut in any::<prop::sample::Index>(), flip in any::<prop::sample::Index>(), byte in any::<u8>()) {
+        let mut bytes = frame.to_bytes();
+        let flip = flip.index(bytes.len());
+        bytes[flip] = byte;
+        let _ = RespValue::from_bytes(&bytes);
+        let _ = RespValue::from_bytes(&bytes[..cut.index(bytes.len())]);
+    }
+
+    #[test]
+    fn test_resp_round_trip(frame in resp_value()) {
+        let bytes = frame.to_bytes();
+        let (parsed, rest) = RespValue::from_bytes(&bytes)?;
+        prop_assert!(rest.is_empty());
+        prop_assert_eq!(parsed.to_bytes(), bytes);
+    }
+
+    #[test]
+    fn test_command_decoders_never_panic(frame in command_frame()) {
+        let bytes = frame.to_bytes();
+        let (parsed, _) = RespValue::from_bytes(&bytes)?;
+        let _ = StoreCommand::decode(parsed);
+    }
+
+    #[test]
+    fn test_value_round_trip(value in value()) {
+        let bytes = value.encode().to_bytes();
+        let (parsed, _) = RespParser::parse_value(&bytes)?;
+        prop_assert_eq!(Value::decode(parsed)?, value);
+    }
+
+    #[test]
+    fn test_value_decoder_never_panics(frame in resp_value()) {
+        let bytes = frame.to_bytes();
+        let (parsed, _) = RespValue::from_bytes(&bytes)?;
+        let _ = Value::decode(parsed);
+    }
+
+    #[test]
+    fn test_decode_frame_never_panics(bytes in proptest::collection::vec(any::<u8>(), 0..64), limit in 0usize..64) {
+        let limits = ProtocolLimits::new(limit);
+        if let Ok(Some((payload, rest))) = crate::data::codec::decode_frame(&bytes, &limits) {
+            prop_assert!(payload.len() <= limit);
+            prop_assert_eq!(crate::data::codec::FRAME_HEADER_SIZE + payload.len() + rest.len(), bytes.len());
+        }
+    }
+}
+
+#[test]
+fn test_resp_parser_rejects_hostile_headers() {
+    // An array count far past the input must not be allocated up front
+    assert!(RespValue::from_bytes(b"*2147483647\r\n").is_err());
+    assert!(RespValue::from_bytes(b"$2147483647\r\nabc\r\n").is_err());
+
+    // Deep nesting fails instead of overflowing the stack
+    let deep = b"*1\r\n".repeat(1_000_000);
+    assert!(RespValue::from_bytes(&deep).is_err());
+}
+
+#[test]
+fn test_pagination_survives_hostile_limits() -> Result<()> {
+    use crate::testing::SchemaBuilder;
+
+    let mut store = Store::new();
+    let et_pump = SchemaBuilder::object("Pump").int("Speed", 0).apply(&mut store)?;
+    let pump = store.create_entity(et_pump, None, "P1")?;
+
+    // Limits and cursors arrive off the wire, so the extremes must not
+    // overflow or decide allocations
+    for cursor in [None, Some(1), Some(usize::MAX)] {
+        let page_opts = PageOpts::new(usize::MAX, cursor);
+        let expected = if cursor.is_none() { vec![pump] } else { vec![] };
+        assert_eq!(store.find_entities_paginated(et_pump, Some(&page_opts), None)?.items, expected);
+        assert_eq!(store.find_entities_exact(et_pump, Some(&page_opts), None)?.items, expected);
+        assert_eq!(store.find_entities_paginated(et_pump, Some(&page_opts), Some("Speed == 0"))?.items, expected);
+        assert_eq!(store.find_entities_exact(et_pump, Some(&page_opts), Some("Speed == 0"))?.items, expected);
+    }
+    Ok(())
+}
@@ -0,0 +1,118 @@
+#[allow(unused_imports)]
+use crate::*;
+#[allow(unused_imports)]
+use crate::testing::SchemaBuilder;
+#[allow(unused_imports)]
+use crate::graphql::GraphQlExecutor;
+#[allow(unused_imports)]
+use serde_json::json;
+
+/// A site with two pumps and a valve, the first pump feeding the valve
+#[allow(dead_code)]
+fn plant_store() -> Result<(Store, EntityId, EntityId, EntityId)> {
+    let mut store = Store::new();
+    let et_site = SchemaBuilder::object("Site").apply(&mut store)?;
+    SchemaBuilder::object("Equipment").choice("Mode", &["Off", "Auto", "Manual"]).apply(&mut store)?;
+    let et_pump = SchemaBuilder::new("Pump")
+        .inherits("Equipment")
+        .int("Power", 0)
+        .entity_reference("Feeds")
+        .apply(&mut store)?;
+    let et_valve = SchemaBuilder::new("Valve").inherits("Equipment").bool("Open", false).apply(&mut store)?;
+
+    let site = store.create_entity(et_site, None, "Plant")?;
+    let p1 = store.create_entity(et_pump, Some(site), "P1")?;
+    let p2 = store.create_entity(et_pump, Some(site), "P2")?;
+    let valve = store.create_entity(et_valve, Some(site), "V1")?;
+    let (ft_power, ft_feeds, ft_mode) = (store.get_field_type("Power")?, store.get_field_type("Feeds")?, store.get_field_type("Mode")?);
+    store.write(p1, &[ft_power], Value::Int(10), None, None, None, None)?;
+    store.write(p2, &[ft_power], Value::Int(30), None, None, None, None)?;
+    store.write(p2, &[ft_mode], Value::Choice(1), None, None, None, None)?;
+    store.write(p1, &[ft_feeds], Value::EntityReference(Some(valve)), None, None, None, None)?;
+    Ok((store, site, p1, p2))
+}
+
+#[test]
+fn test_graphql_nested_query() -> Result<()> {
+    let (store, site, p1, p2) = plant_store()?;
+    let mut graphql = GraphQlExecutor::new();
+
+    let sdl = graphql.schema(&store)?;
+    assert!(sdl.contains("type Pump implements Entity {\n  id: ID!\n"));
+    assert!(sdl.contains("  Children(filter: String, first: Int): [Entity!]!\n"));
+    assert!(sdl.contains("  Feeds: Entity\n"));
+    assert!(sdl.contains("  Pump(filter: String, first: Int, "));
+    assert!(sdl.contains("  Mode: String\n"));
+
+    let response = graphql.execute(
+        &store,
+        r#"query Plant {
+            Site(Name: "Plant") {
+                id
+                Name
+                pumps: Children(filter: "Power > 5") {
+                    __typename
+                    Name
+                    ... on Pump { Power Feeds { Name ... on Valve { Open } } }
+                }
+                Children(first: 1) { Name }
+            }
+            strong: Pump(filter: "Power > 20") { Name }
+            auto: Pump(Mode: Auto) { Name Mode }
+        }"#,
+        &Default::default(),
+    );
+    assert_eq!(
+        response,
+        json!({ "data": {
+            "Site": [{
+                "id": String::from(site),
+                "Name": "Plant",
+                "pumps": [
+                    { "__typename": "Pump", "Name": "P1", "Power": 10, "Feeds": { "Name": "V1", "Open": false } },
+                    { "__typename": "Pump", "Name": "P2", "Power": 30, "Feeds": null },
+                ],
+                "Children": [{ "Name": "P1" }],
+            }],
+            "strong": [{ "Name": "P2" }],
+            "auto": [{ "Name": "P2", "Mode": "Auto" }],
+        }})
+    );
+
+    // Variables, and looking up a single entity
+    let mut variables = serde_json::Map::new();
+    variables.insert("id".to_string(), json!(String::from(p1)));
+    variables.insert("power".to_string(), json!(30));
+    let response = graphql.execute(
+        &store,
+        "query ($id: ID!, $power: Int) { entity(id: $id) { ... on Equipment { Mode } } Pump(Power: $power) { id } }",
+        &variables,
+    );
+    assert_eq!(response, json!({ "data": { "entity": { "Mode": "Off" }, "Pump": [{ "id": String::from(p2) }] } }));
+    Ok(())
+}
+
+#[test]
+fn test_graphql_errors() -> Result<()> {
+    let (store, _, _, _) = plant_store()?;
+    let mut graphql = GraphQlExecutor::new();
+
+    // A bad field is null and reported with its path; the rest still resolves
+    let response = graphql.execute(&store, "{ Pump(first: 1) { Name Speed } Valve { Name } }", &Default::default());
+    assert_eq!(response["data"], json!({ "Pump": [{ "Name": "P1", "Speed": null }], "Valve": [{ "Name": "V1" }] }));
+    assert_eq!(response["errors"][0]["path"], json!(["Pump", 0, "Speed"]));
+    assert_eq!(response["errors"][0]["message"].as_str().map(|m| m.contains("Cannot query field 'Speed' on type 'Pump'")), Some(true));
+
+    let response = graphql.execute(&store, "{ Pump { Feeds } Tank { id } }", &Default::default());
+    let errors = response["errors"].as_array().unwrap();
+    assert_eq!(errors.len(), 2);
+    assert_eq!(errors[0]["path"], json!(["Pump", 0, "Feeds"]));
+    assert_eq!(errors[1]["path"], json!(["Tank"]));
+
+    for query in ["mutation { Pump { id } }", "{ Pump { ...Details } }", "{ Pump { Name", "{ Pump(Name: \"P1) { id } }"] {
+        let response = graphql.execute(&store, query, &Default::default());
+        assert!(response.get("data").is_none(), "{}", query);
+        assert!(response["errors"][0]["message"].is_string());
+    }
+    Ok(())
+}
@@ -0,0 +1,53 @@
+use crate::*;
+use crate::testing::SchemaBuilder;
+
+#[allow(dead_code)]
+fn create_test_store() -> Result<Store> {
+    let mut store = Store::new();
+    SchemaBuilder::object("Node")
+        .choice("Health", &["Unknown", "Healthy", "Degraded", "Unhealthy"])
+        .runtime("Health")
+        .string("HealthMessage", "")
+        .runtime("HealthMessage")
+        .apply(&mut store)?;
+
+    Ok(store)
+}
+
+#[test]
+fn test_aggregate_health_rolls_up_worst_status() -> Result<()> {
+    let mut store = create_test_store()?;
+    let et_node = store.get_entity_type("Node")?;
+    let ft_health = store.get_field_type("Health")?;
+    let ft_health_message = store.get_field_type("HealthMessage")?;
+
+    let root = store.create_entity(et_node, None, "Root")?;
+    let machine_a = store.create_entity(et_node, Some(root), "MachineA")?;
+    let machine_b = store.create_entity(et_node, Some(root), "MachineB")?;
+    let service_a1 = store.create_entity(et_node, Some(machine_a), "ServiceA1")?;
+    let service_a2 = store.create_entity(et_node, Some(machine_a), "ServiceA2")?;
+    let service_b1 = store.create_entity(et_node, Some(machine_b), "ServiceB1")?;
+    let _unreported = store.create_entity(et_node, Some(machine_b), "ServiceB2")?;
+
+    // Nothing reported yet
+    assert_eq!(app::aggregate_health(&mut store, root)?, app::HealthStatus::Unknown);
+
+    app::health::set_health(&mut store, service_a1, app::HealthStatus::Healthy, "")?;
+    app::health::set_health(&mut store, service_a2, app::HealthStatus::Degraded, "queue backlog")?;
+    app::health::set_health(&mut store, service_b1, app::HealthStatus::Healthy, "")?;
+
+    let (message, _, _) = store.read(service_a2, &[ft_health_message])?;
+    assert_eq!(message, Value::String("queue backlog".to_string()));
+
+    assert_eq!(app::aggregate_health(&mut store, root)?, app::HealthStatus::Degraded);
+
+    let (health, _, _) = store.read(machine_a, &[ft_health])?;
+    assert_eq!(health, Value::Choice(app::HealthStatus::Degraded.as_choice()));
+    let (health, _, _) = store.read(machine_b, &[ft_health])?;
+    assert_eq!(health, Value::Choice(app::HealthStatus::Healthy.as_choice()));
+
+    app::health::set_health(&mut store, service_b1, app::HealthStatus::Unhealthy, "disk full")?;
+    assert_eq!(app::aggregate_health(&mut store, root)?, app::HealthStatus::Unhealthy);
+
+    Ok(())
+}
@@ -31,6 +31,8 @@ fn test_inheritance_in_find_entities() -> Result<()> {
             default_value: String::new(),
             rank: 0,
             storage_scope: StorageScope::Runtime,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     animal_schema.fields.insert(
@@ -40,6 +42,8 @@ fn test_inheritance_in_find_entities() -> Result<()> {
             default_value: None,
             rank: 1,
             storage_scope: StorageScope::Configuration,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     animal_schema.fields.insert(
@@ -49,6 +53,9 @@ fn test_inheritance_in_find_entities() -> Result<()> {
             default_value: Vec::new(),
             rank: 2,
             storage_scope: StorageScope::Configuration,
+            merge_policy: MergePolicy::LastWriterWins,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     store.update_schema(animal_schema)?;
@@ -62,6 +69,8 @@ fn test_inheritance_in_find_entities() -> Result<()> {
             default_value: String::new(),
             rank: 1,
             storage_scope: StorageScope::Runtime,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     store.update_schema(mammal_schema)?;
@@ -75,6 +84,8 @@ fn test_inheritance_in_find_entities() -> Result<()> {
             default_value: String::new(),
             rank: 2,
             storage_scope: StorageScope::Runtime,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     store.update_schema(dog_schema)?;
@@ -128,6 +139,8 @@ fn test_inheritance_with_direct_instances() -> Result<()> {
             default_value: String::new(),
             rank: 0,
             storage_scope: StorageScope::Runtime,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     animal_schema.fields.insert(
@@ -137,6 +150,8 @@ fn test_inheritance_with_direct_instances() -> Result<()> {
             default_value: None,
             rank: 1,
             storage_scope: StorageScope::Configuration,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     animal_schema.fields.insert(
@@ -146,6 +161,9 @@ fn test_inheritance_with_direct_instances() -> Result<()> {
             default_value: Vec::new(),
             rank: 2,
             storage_scope: StorageScope::Configuration,
+            merge_policy: MergePolicy::LastWriterWins,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     store.update_schema(animal_schema)?;
@@ -187,6 +205,8 @@ fn test_circular_inheritance_protection() -> Result<()> {
             default_value: String::new(),
             rank: 0,
             storage_scope: StorageScope::Runtime,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     schema_a.fields.insert(
@@ -196,6 +216,8 @@ fn test_circular_inheritance_protection() -> Result<()> {
             default_value: None,
             rank: 1,
             storage_scope: StorageScope::Configuration,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     schema_a.fields.insert(
@@ -205,6 +227,9 @@ fn test_circular_inheritance_protection() -> Result<()> {
             default_value: Vec::new(),
             rank: 2,
             storage_scope: StorageScope::Configuration,
+            merge_policy: MergePolicy::LastWriterWins,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     store.update_schema(schema_a)?;
@@ -257,6 +282,8 @@ fn test_multi_inheritance() -> Result<()> {
             default_value: String::new(),
             rank: 0,
             storage_scope: StorageScope::Runtime,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     flyable_schema.fields.insert(
@@ -266,6 +293,8 @@ fn test_multi_inheritance() -> Result<()> {
             default_value: None,
             rank: 1,
             storage_scope: StorageScope::Configuration,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     flyable_schema.fields.insert(
@@ -275,6 +304,9 @@ fn test_multi_inheritance() -> Result<()> {
             default_value: Vec::new(),
             rank: 2,
             storage_scope: StorageScope::Configuration,
+            merge_policy: MergePolicy::LastWriterWins,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     flyable_schema.fields.insert(
@@ -284,6 +316,8 @@ fn test_multi_inheritance() -> Result<()> {
             default_value: true,
             rank: 3,
             storage_scope: StorageScope::Runtime,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     flyable_schema.fields.insert(
@@ -293,6 +327,9 @@ fn test_multi_inheritance() -> Result<()> {
             default_value: 0.0,
             rank: 4,
             storage_scope: StorageScope::Runtime,
+            merge_policy: MergePolicy::LastWriterWins,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     store.update_schema(flyable_schema)?;
@@ -306,6 +343,8 @@ fn test_multi_inheritance() -> Result<()> {
             default_value: String::new(),
             rank: 0,
             storage_scope: StorageScope::Runtime,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     mammal_schema.fields.insert(
@@ -315,6 +354,8 @@ fn test_multi_inheritance() -> Result<()> {
             default_value: true,
             rank: 1,
             storage_scope: StorageScope::Runtime,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     store.update_schema(mammal_schema)?;
@@ -328,6 +369,9 @@ fn test_multi_inheritance() -> Result<()> {
             default_value: 100.0,
             rank: 0,
             storage_scope: StorageScope::Runtime,
+            merge_policy: MergePolicy::LastWriterWins,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     store.update_schema(bat_schema)?;    // Now get the interned entity and field types
@@ -1,5 +1,5 @@
 #[allow(unused_imports)]
-use crate::data::StorageScope;
+use crate::data::{StorageScope, MergePolicy};
 
 #[allow(unused_imports)]
 use crate::{restore_json_snapshot, take_json_snapshot, EntitySchema, EntityType, FieldSchema, FieldType, Single, Store, StoreTrait, Value, now};
@@ -19,6 +19,8 @@ fn test_json_snapshot_functionality() -> Result<(), Box<dyn std::error::Error>>
             default_value: "".to_string(),
             rank: 0,
             storage_scope: StorageScope::Configuration,
+            validator: None,
+            metadata: Default::default(),
         },
     );
     object_schema.fields.insert(
@@ -28,6 +30,8 @@ fn test_json_snapshot_functionality() -> Result<(), Box<dyn std::error::Error>>
             default_value: "".to_string(),
             rank: 1,
             storage_scope: StorageScope::Configuration,
+            validator: None,
+            metadata: Default::default(),
         },
     );
     object_schema.fields.insert(
@@ -37,6 +41,8 @@ fn test_json_snapshot_functionality() -> Result<(), Box<dyn std::error::Error>>
             default_value: None,
             rank: 2,
             storage_scope: StorageScope::Configuration,
+            validator: None,
+            metadata: Default::default(),
         },
     );
     object_schema.fields.insert(
@@ -46,6 +52,9 @@ fn test_json_snapshot_functionality() -> Result<(), Box<dyn std::error::Error>>
             default_value: vec![],
             rank: 3,
             storage_scope: StorageScope::Configuration,
+            merge_policy: MergePolicy::LastWriterWins,
+            validator: None,
+            metadata: Default::default(),
         },
     );
     
@@ -65,6 +74,8 @@ fn test_json_snapshot_functionality() -> Result<(), Box<dyn std::error::Error>>
             default_value: None,
             rank: 3,
             storage_scope: StorageScope::Runtime,
+            validator: None,
+            metadata: Default::default(),
         },
     );
     root_schema.fields.insert(
@@ -74,6 +85,8 @@ fn test_json_snapshot_functionality() -> Result<(), Box<dyn std::error::Error>>
             default_value: None,
             rank: 4,
             storage_scope: StorageScope::Runtime,
+            validator: None,
+            metadata: Default::default(),
         },
     );
     root_schema.fields.insert(
@@ -83,6 +96,8 @@ fn test_json_snapshot_functionality() -> Result<(), Box<dyn std::error::Error>>
             default_value: "".to_string(),
             rank: 5,
             storage_scope: StorageScope::Runtime,
+            validator: None,
+            metadata: Default::default(),
         },
     );
     
@@ -102,6 +117,8 @@ fn test_json_snapshot_functionality() -> Result<(), Box<dyn std::error::Error>>
             default_value: "Unknown".to_string(),
             rank: 6,
             storage_scope: StorageScope::Configuration,
+            validator: None,
+            metadata: Default::default(),
         },
     );
     
@@ -119,6 +136,9 @@ fn test_json_snapshot_functionality() -> Result<(), Box<dyn std::error::Error>>
             default_value: 0.0,
             rank: 7,
             storage_scope: StorageScope::Runtime,
+            merge_policy: MergePolicy::LastWriterWins,
+            validator: None,
+            metadata: Default::default(),
         },
     );
     sensor_schema.fields.insert(
@@ -128,6 +148,8 @@ fn test_json_snapshot_functionality() -> Result<(), Box<dyn std::error::Error>>
             default_value: "".to_string(),
             rank: 8,
             storage_scope: StorageScope::Configuration,
+            validator: None,
+            metadata: Default::default(),
         },
     );
     sensor_schema.fields.insert(
@@ -137,6 +159,8 @@ fn test_json_snapshot_functionality() -> Result<(), Box<dyn std::error::Error>>
             default_value: now(),
             rank: 9,
             storage_scope: StorageScope::Runtime,
+            validator: None,
+            metadata: Default::default(),
         },
     );
     
@@ -156,6 +180,9 @@ fn test_json_snapshot_functionality() -> Result<(), Box<dyn std::error::Error>>
             default_value: 0.0,
             rank: 10,
             storage_scope: StorageScope::Configuration,
+            merge_policy: MergePolicy::LastWriterWins,
+            validator: None,
+            metadata: Default::default(),
         },
     );
     
@@ -246,6 +273,8 @@ fn test_json_snapshot_restore() -> Result<(), Box<dyn std::error::Error>> {
             default_value: "".to_string(),
             rank: 0,
             storage_scope: StorageScope::Configuration,
+            validator: None,
+            metadata: Default::default(),
         },
     );
     object_schema.fields.insert(
@@ -255,6 +284,8 @@ fn test_json_snapshot_restore() -> Result<(), Box<dyn std::error::Error>> {
             default_value: "".to_string(),
             rank: 1,
             storage_scope: StorageScope::Configuration,
+            validator: None,
+            metadata: Default::default(),
         },
     );
     object_schema.fields.insert(
@@ -264,6 +295,8 @@ fn test_json_snapshot_restore() -> Result<(), Box<dyn std::error::Error>> {
             default_value: None,
             rank: 2,
             storage_scope: StorageScope::Configuration,
+            validator: None,
+            metadata: Default::default(),
         },
     );
     object_schema.fields.insert(
@@ -273,6 +306,9 @@ fn test_json_snapshot_restore() -> Result<(), Box<dyn std::error::Error>> {
             default_value: vec![],
             rank: 3,
             storage_scope: StorageScope::Configuration,
+            merge_policy: MergePolicy::LastWriterWins,
+            validator: None,
+            metadata: Default::default(),
         },
     );
 
@@ -292,6 +328,8 @@ fn test_json_snapshot_restore() -> Result<(), Box<dyn std::error::Error>> {
             default_value: "Active".to_string(),
             rank: 10,
             storage_scope: StorageScope::Configuration,
+            validator: None,
+            metadata: Default::default(),
         },
     );
 
@@ -303,6 +341,8 @@ fn test_json_snapshot_restore() -> Result<(), Box<dyn std::error::Error>> {
             default_value: "".to_string(),
             rank: 10,
             storage_scope: StorageScope::Configuration,
+            validator: None,
+            metadata: Default::default(),
         },
     );
 
@@ -413,6 +453,13 @@ fn test_json_snapshot_restore() -> Result<(), Box<dyn std::error::Error>> {
         panic!("Failed to read root children");
     }
 
+    // The checksum survives the file round trip and catches edits
+    let checksum = crate::verify_json_snapshot(&snapshot)?;
+    let mut reloaded: crate::JsonSnapshot = serde_json::from_str(&serde_json::to_string(&snapshot)?)?;
+    assert_eq!(crate::verify_json_snapshot(&reloaded)?, checksum);
+    reloaded.tree.fields.insert("Status".to_string(), serde_json::json!("Inactive"));
+    assert!(matches!(crate::verify_json_snapshot(&reloaded), Err(crate::Error::ChecksumMismatch(types)) if types == vec!["Root".to_string()]));
+
     println!("JSON snapshot restore test passed successfully!");
     Ok(())
 }
@@ -432,6 +479,8 @@ fn test_json_snapshot_path_resolution() -> Result<(), Box<dyn std::error::Error>
             default_value: "".to_string(),
             rank: 0,
             storage_scope: StorageScope::Configuration,
+            validator: None,
+            metadata: Default::default(),
         },
     );
     object_schema.fields.insert(
@@ -441,6 +490,8 @@ fn test_json_snapshot_path_resolution() -> Result<(), Box<dyn std::error::Error>
             default_value: None,
             rank: 1,
             storage_scope: StorageScope::Configuration,
+            validator: None,
+            metadata: Default::default(),
         },
     );
     object_schema.fields.insert(
@@ -450,6 +501,9 @@ fn test_json_snapshot_path_resolution() -> Result<(), Box<dyn std::error::Error>
             default_value: vec![],
             rank: 2,
             storage_scope: StorageScope::Configuration,
+            merge_policy: MergePolicy::LastWriterWins,
+            validator: None,
+            metadata: Default::default(),
         },
     );
     store.update_schema(object_schema).unwrap();
@@ -465,6 +519,8 @@ fn test_json_snapshot_path_resolution() -> Result<(), Box<dyn std::error::Error>
             default_value: None,
             rank: 5,
             storage_scope: StorageScope::Configuration,
+            validator: None,
+            metadata: Default::default(),
         },
     );
     store.update_schema(folder_schema).unwrap();
@@ -476,6 +532,8 @@ fn test_json_snapshot_path_resolution() -> Result<(), Box<dyn std::error::Error>
             default_value: None,
             rank: 10,
             storage_scope: StorageScope::Configuration,
+            validator: None,
+            metadata: Default::default(),
         },
     );
     store.update_schema(file_schema).unwrap();
@@ -556,6 +614,8 @@ fn test_json_snapshot_storage_scope() {
             default_value: "".to_string(),
             rank: 0,
             storage_scope: StorageScope::Runtime,
+            validator: None,
+            metadata: Default::default(),
         },
     );
     object_schema.fields.insert(
@@ -565,6 +625,8 @@ fn test_json_snapshot_storage_scope() {
             default_value: None,
             rank: 1,
             storage_scope: StorageScope::Configuration,
+            validator: None,
+            metadata: Default::default(),
         },
     );
     object_schema.fields.insert(
@@ -574,6 +636,9 @@ fn test_json_snapshot_storage_scope() {
             default_value: vec![],
             rank: 2,
             storage_scope: StorageScope::Configuration,
+            merge_policy: MergePolicy::LastWriterWins,
+            validator: None,
+            metadata: Default::default(),
         },
     );
     store.update_schema(object_schema).unwrap();
@@ -586,6 +651,8 @@ fn test_json_snapshot_storage_scope() {
             default_value: "config_default".to_string(),
             rank: 3,
             storage_scope: StorageScope::Configuration,
+            validator: None,
+            metadata: Default::default(),
         },
     );
     root_schema.fields.insert(
@@ -595,6 +662,8 @@ fn test_json_snapshot_storage_scope() {
             default_value: "runtime_default".to_string(),
             rank: 4,
             storage_scope: StorageScope::Runtime,
+            validator: None,
+            metadata: Default::default(),
         },
     );
     store.update_schema(root_schema).unwrap();
@@ -653,6 +722,8 @@ fn test_json_snapshot_entity_list_paths() {
             default_value: "".to_string(),
             rank: 0,
             storage_scope: StorageScope::Configuration,
+            validator: None,
+            metadata: Default::default(),
         },
     );
     object_schema.fields.insert(
@@ -662,6 +733,8 @@ fn test_json_snapshot_entity_list_paths() {
             default_value: None,
             rank: 1,
             storage_scope: StorageScope::Configuration,
+            validator: None,
+            metadata: Default::default(),
         },
     );
     object_schema.fields.insert(
@@ -671,6 +744,9 @@ fn test_json_snapshot_entity_list_paths() {
             default_value: vec![],
             rank: 2,
             storage_scope: StorageScope::Configuration,
+            merge_policy: MergePolicy::LastWriterWins,
+            validator: None,
+            metadata: Default::default(),
         },
     );
     store.update_schema(object_schema).unwrap();
@@ -692,6 +768,9 @@ fn test_json_snapshot_entity_list_paths() {
             default_value: vec![],
             rank: 10,
             storage_scope: StorageScope::Configuration,
+            merge_policy: MergePolicy::LastWriterWins,
+            validator: None,
+            metadata: Default::default(),
         },
     );
     store.update_schema(fault_tolerance_schema).unwrap();
@@ -791,3 +870,129 @@ fn test_json_snapshot_entity_list_paths() {
 
     println!("EntityList path test completed!");
 }
+
+#[test]
+fn test_export_and_import_subtree() -> Result<(), Box<dyn std::error::Error>> {
+    let mut store = Store::new();
+
+    let mut object_schema = EntitySchema::<Single, String, String>::new("Object".to_string(), vec![]);
+    object_schema.fields.insert(
+        "Name".to_string(),
+        FieldSchema::String {
+            field_type: "Name".to_string(),
+            default_value: "".to_string(),
+            rank: 0,
+            storage_scope: StorageScope::Configuration,
+            validator: None,
+            metadata: Default::default(),
+        },
+    );
+    object_schema.fields.insert(
+        "Parent".to_string(),
+        FieldSchema::EntityReference {
+            field_type: "Parent".to_string(),
+            default_value: None,
+            rank: 1,
+            storage_scope: StorageScope::Configuration,
+            validator: None,
+            metadata: Default::default(),
+        },
+    );
+    object_schema.fields.insert(
+        "Children".to_string(),
+        FieldSchema::EntityList {
+            field_type: "Children".to_string(),
+            default_value: vec![],
+            rank: 2,
+            storage_scope: StorageScope::Configuration,
+            merge_policy: MergePolicy::LastWriterWins,
+            validator: None,
+            metadata: Default::default(),
+        },
+    );
+    store.update_schema(object_schema)?;
+    store.update_schema(EntitySchema::<Single, String, String>::new("Root".to_string(), vec!["Object".to_string()]))?;
+    store.update_schema(EntitySchema::<Single, String, String>::new("Folder".to_string(), vec!["Object".to_string()]))?;
+
+    let mut file_schema = EntitySchema::<Single, String, String>::new("File".to_string(), vec!["Object".to_string()]);
+    file_schema.fields.insert(
+        "Size".to_string(),
+        FieldSchema::Int {
+            field_type: "Size".to_string(),
+            default_value: 0,
+            rank: 5,
+            storage_scope: StorageScope::Configuration,
+            merge_policy: MergePolicy::LastWriterWins,
+            validator: None,
+            metadata: Default::default(),
+        },
+    );
+    file_schema.fields.insert(
+        "Folder".to_string(),
+        FieldSchema::EntityReference {
+            field_type: "Folder".to_string(),
+            default_value: None,
+            rank: 6,
+            storage_scope: StorageScope::Configuration,
+            validator: None,
+            metadata: Default::default(),
+        },
+    );
+    store.update_schema(file_schema)?;
+
+    let root_et = store.get_entity_type("Root")?;
+    let folder_et = store.get_entity_type("Folder")?;
+    let file_et = store.get_entity_type("File")?;
+    let size_ft = store.get_field_type("Size")?;
+    let folder_ft = store.get_field_type("Folder")?;
+
+    let root_id = store.create_entity(root_et, None, "Root")?;
+    let documents_id = store.create_entity(folder_et, Some(root_id), "Documents")?;
+    let archive_id = store.create_entity(folder_et, Some(root_id), "Archive")?;
+    let file_id = store.create_entity(file_et, Some(documents_id), "report.txt")?;
+    store.write(file_id, &[size_ft], Value::Int(10), None, None, None, None)?;
+    store.write(file_id, &[folder_ft], Value::EntityReference(Some(documents_id)), None, None, None, None)?;
+
+    let mut exported = crate::export_subtree(&mut store, documents_id)?;
+    assert_eq!(exported.entity_type, "Folder");
+
+    // Point the file at the copy of its folder rather than the original
+    let file_json = exported.fields.get_mut("Children").unwrap().as_array_mut().unwrap()[0].as_object_mut().unwrap();
+    assert_eq!(file_json.get("Folder").unwrap(), "Root/Documents");
+    file_json.insert("Folder".to_string(), serde_json::json!("Root/Archive/Documents"));
+
+    // A dry run validates without creating anything
+    let report = crate::import_subtree(&mut store, &exported, Some(archive_id), true)?;
+    assert!(report.is_valid(), "{:?}", report.errors);
+    assert_eq!(report.entity_count, 2);
+    assert_eq!(report.root_id, None);
+    assert!(crate::path_to_entity_id(&store, "Root/Archive/Documents").is_err());
+
+    let report = crate::import_subtree(&mut store, &exported, Some(archive_id), false)?;
+    let copy_id = report.root_id.unwrap();
+    assert_eq!(crate::path_to_entity_id(&store, "Root/Archive/Documents")?, copy_id);
+    let copy_file_id = crate::path_to_entity_id(&store, "Root/Archive/Documents/report.txt")?;
+    assert_eq!(store.read(copy_file_id, &[size_ft])?.0, Value::Int(10));
+    assert_eq!(store.read(copy_file_id, &[folder_ft])?.0, Value::EntityReference(Some(copy_id)));
+
+    // Invalid trees are rejected as a whole with per-entity errors
+    let mut invalid = exported.clone();
+    invalid.fields.insert("Name".to_string(), serde_json::json!("Broken"));
+    invalid.fields.insert("Colour".to_string(), serde_json::json!("red"));
+    let file_json = invalid.fields.get_mut("Children").unwrap().as_array_mut().unwrap()[0].as_object_mut().unwrap();
+    file_json.insert("Size".to_string(), serde_json::json!("large"));
+    file_json.insert("Folder".to_string(), serde_json::json!("Root/Nowhere"));
+
+    let report = crate::import_subtree(&mut store, &invalid, Some(archive_id), false)?;
+    assert!(!report.is_valid());
+    let mut failed: Vec<(String, Option<String>)> = report.errors.iter().map(|e| (e.path.clone(), e.field.clone())).collect();
+    failed.sort();
+    assert_eq!(failed, vec![
+        ("Root/Archive/Broken".to_string(), Some("Colour".to_string())),
+        ("Root/Archive/Broken/report.txt".to_string(), Some("Folder".to_string())),
+        ("Root/Archive/Broken/report.txt".to_string(), Some("Size".to_string())),
+    ]);
+    assert!(crate::path_to_entity_id(&store, "Root/Archive/Broken").is_err());
+
+    Ok(())
+}
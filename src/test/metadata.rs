@@ -0,0 +1,64 @@
+#[allow(unused_imports)]
+use crate::*;
+#[allow(unused_imports)]
+use crate::app::{register_metadata, HostInfo, ServiceMetadata};
+#[allow(unused_imports)]
+use crate::testing::SchemaBuilder;
+
+#[test]
+fn test_register_metadata_fills_defined_fields() -> Result<()> {
+    let mut store = Store::new();
+    let ip_addresses = FieldSchema::StringList {
+        field_type: ft::IP_ADDRESSES.to_string(),
+        default_value: vec![],
+        rank: 10,
+        storage_scope: StorageScope::Runtime,
+        merge_policy: MergePolicy::LastWriterWins,
+        validator: None,
+        metadata: Default::default(),
+    };
+    let et_machine = SchemaBuilder::object("Machine")
+        .string(ft::HOSTNAME, "")
+        .string(ft::QLIB_VERSION, "")
+        .timestamp(ft::START_TIME)
+        .field(ip_addresses)
+        .apply(&mut store)?;
+    // No BuildInfo, which is skipped
+    let et_service = SchemaBuilder::object("Service")
+        .string(ft::VERSION, "")
+        .timestamp(ft::START_TIME)
+        .apply(&mut store)?;
+    let machine = store.create_entity(et_machine, None, "edge-01")?;
+    let service = store.create_entity(et_service, Some(machine), "poller")?;
+
+    let host = HostInfo {
+        hostname: "edge-01.plant".to_string(),
+        operating_system: "linux (x86_64)".to_string(),
+        ip_addresses: vec!["10.0.0.7".to_string()],
+        boot_time: Some(secs_to_timestamp(1_700_000_000)),
+    };
+    let metadata = ServiceMetadata::new("1.4.2").with_build_info("abc123");
+    register_metadata(&mut store, machine, service, &host, &metadata)?;
+
+    let read = |entity_id: EntityId, name: &str| -> Result<Value> { Ok(store.read(entity_id, &[store.get_field_type(name)?])?.0) };
+    assert_eq!(read(machine, ft::HOSTNAME)?, Value::from("edge-01.plant"));
+    assert_eq!(read(machine, ft::QLIB_VERSION)?, Value::from(env!("CARGO_PKG_VERSION")));
+    assert_eq!(read(machine, ft::IP_ADDRESSES)?, Value::StringList(vec!["10.0.0.7".to_string()]));
+    assert_eq!(read(machine, ft::START_TIME)?, Value::Timestamp(secs_to_timestamp(1_700_000_000)));
+    assert_eq!(read(service, ft::VERSION)?, Value::from("1.4.2"));
+    assert!(read(service, ft::START_TIME)?.as_timestamp().is_some_and(|started| started > epoch()));
+
+    // Registering again only moves the service's start time
+    store.write_queue.clear();
+    register_metadata(&mut store, machine, service, &host, &metadata)?;
+    assert_eq!(store.write_queue.len(), 1);
+    assert!(store.write_queue.iter().all(|write| matches!(write, WriteInfo::FieldUpdate { entity_id, .. } if *entity_id == service)));
+    Ok(())
+}
+
+#[test]
+fn test_detect_host_info() {
+    let host = HostInfo::detect();
+    assert!(host.operating_system.ends_with(&format!("({})", std::env::consts::ARCH)));
+    assert!(host.ip_addresses.iter().all(|ip| ip.parse::<std::net::IpAddr>().is_ok()));
+}
@@ -2,5 +2,39 @@
 mod store;
 mod inheritance;
 mod json_snapshot;
+mod entity_view;
 mod cel_executor;
-mod auth;
\ No newline at end of file
+mod auth;
+mod replication;
+mod durable;
+mod federated;
+mod backup;
+mod snapshot_sink;
+mod scheduler;
+mod health;
+mod service_group;
+mod metadata;
+mod value;
+mod protocol;
+mod retry;
+mod codec;
+mod type_registry;
+mod testing;
+#[cfg(feature = "sim")]
+mod sim;
+#[cfg(feature = "s3")]
+mod s3;
+#[cfg(feature = "graphql")]
+mod graphql;
+#[cfg(feature = "mqtt")]
+mod mqtt;
+#[cfg(feature = "modbus")]
+mod modbus;
+#[cfg(feature = "opcua")]
+mod opcua;
+#[cfg(feature = "capi")]
+mod capi;
+#[cfg(feature = "bootstrap")]
+mod bootstrap;
+#[cfg(test)]
+mod fuzz;
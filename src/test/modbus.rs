@@ -0,0 +1,203 @@
+#[allow(unused_imports)]
+use crate::*;
+#[allow(unused_imports)]
+use crate::testing::SchemaBuilder;
+#[allow(unused_imports)]
+use crate::bridge::modbus::{crc16, read_point, DataType, ModbusPoller, ModbusRtu, ModbusTransport, RegisterType};
+#[allow(unused_imports)]
+use std::collections::HashMap;
+#[allow(unused_imports)]
+use std::io::{Cursor, Read, Write};
+#[allow(unused_imports)]
+use std::net::TcpListener;
+#[allow(unused_imports)]
+use std::sync::{Arc, Mutex};
+
+/// A serial port that records what is written and replies with `reply`
+#[allow(dead_code)]
+struct FakePort {
+    written: Vec<u8>,
+    reply: Cursor<Vec<u8>>,
+}
+
+impl Read for FakePort {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.reply.read(buf)
+    }
+}
+
+impl Write for FakePort {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.written.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// A Modbus TCP device with the given holding registers and coils.
+/// Reading anything else answers exception 2 (illegal data address).
+#[allow(dead_code)]
+fn serve_device(registers: HashMap<u16, u16>, coils: Vec<u16>) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    std::thread::spawn(move || {
+        for mut stream in listener.incoming().flatten() {
+            let mut request = [0u8; 12];
+            while stream.read_exact(&mut request).is_ok() {
+                let (function_code, start, quantity) = (request[7], u16::from_be_bytes([request[8], request[9]]), u16::from_be_bytes([request[10], request[11]]));
+                let pdu = match function_code {
+                    0x01 if coils.contains(&start) => vec![0x01, 1, 1],
+                    0x03 if (start..start + quantity).all(|address| registers.contains_key(&address)) => {
+                        let mut pdu = vec![0x03, (2 * quantity) as u8];
+                        for address in start..start + quantity {
+                            pdu.extend_from_slice(&registers[&address].to_be_bytes());
+                        }
+                        pdu
+                    }
+                    _ => vec![function_code | 0x80, 2],
+                };
+                let mut response = request[..4].to_vec();
+                response.extend_from_slice(&(pdu.len() as u16 + 1).to_be_bytes());
+                response.push(request[6]);
+                response.extend_from_slice(&pdu);
+                stream.write_all(&response).unwrap();
+            }
+        }
+    });
+    address
+}
+
+#[allow(dead_code)]
+fn modbus_schema(store: &mut Store) -> Result<(EntityType, EntityType)> {
+    let et_device = SchemaBuilder::object("ModbusDevice")
+        .string("Address", "")
+        .int("UnitId", 1)
+        .choice(ft::HEALTH, &["Unknown", "Healthy", "Degraded", "Unhealthy"])
+        .string(ft::HEALTH_MESSAGE, "")
+        .int(ft::FAILED_ATTEMPTS, 0)
+        .apply(store)?;
+    let et_point = SchemaBuilder::object("ModbusPoint")
+        .choice("RegisterType", &["Coil", "DiscreteInput", "HoldingRegister", "InputRegister"])
+        .int("RegisterAddress", 0)
+        .choice("DataType", &["Bool", "U16", "I16", "U32", "I32", "F32"])
+        .float("Scale", 1.0)
+        .int("PollInterval", 1000)
+        .entity_reference(ft::TARGET)
+        .string(ft::TARGET_FIELD, "")
+        .apply(store)?;
+    Ok((et_device, et_point))
+}
+
+#[allow(dead_code)]
+#[allow(clippy::too_many_arguments)]
+fn add_point(store: &mut Store, et_point: EntityType, device: EntityId, name: &str, register_type: i64, address: i64, data_type: i64, scale: f64, target: EntityId, target_field: &str) -> Result<EntityId> {
+    let point = store.create_entity(et_point, Some(device), name)?;
+    let values = [
+        ("RegisterType", Value::Choice(register_type)),
+        ("RegisterAddress", Value::Int(address)),
+        ("DataType", Value::Choice(data_type)),
+        ("Scale", Value::Float(scale)),
+        (ft::TARGET, Value::EntityReference(Some(target))),
+        (ft::TARGET_FIELD, Value::String(target_field.into())),
+    ];
+    for (field, value) in values {
+        let field_type = store.get_field_type(field)?;
+        store.write(point, &[field_type], value, None, None, None, None)?;
+    }
+    Ok(point)
+}
+
+#[test]
+fn test_modbus_rtu_framing() -> Result<()> {
+    assert_eq!(crc16(&[0x01, 0x03, 0x00, 0x00, 0x00, 0x01]).to_le_bytes(), [0x84, 0x0a]);
+
+    let mut reply = vec![0x11, 0x03, 0x04, 0xff, 0xff, 0xff, 0xfe];
+    reply.extend_from_slice(&crc16(&reply).to_le_bytes());
+    let mut rtu = ModbusRtu::new(FakePort { written: Vec::new(), reply: Cursor::new(reply) });
+    assert_eq!(read_point(&mut rtu, 0x11, RegisterType::HoldingRegister, 0x6b, DataType::I32)?.ok(), Some(-2.0));
+
+    let mut request = vec![0x11, 0x03, 0x00, 0x6b, 0x00, 0x02];
+    request.extend_from_slice(&crc16(&request).to_le_bytes());
+    let FakePort { written, .. } = rtu.into_port();
+    assert_eq!(written, request);
+
+    // A corrupted frame is a link failure
+    let mut reply = vec![0x11, 0x03, 0x02, 0x00, 0x07];
+    reply.extend_from_slice(&(crc16(&reply) ^ 1).to_le_bytes());
+    let mut rtu = ModbusRtu::new(FakePort { written: Vec::new(), reply: Cursor::new(reply) });
+    assert!(rtu.request(0x11, &[0x03, 0x00, 0x00, 0x00, 0x01]).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_modbus_poller_writes_points() -> Result<()> {
+    let mut store = Store::new();
+    let (et_device, et_point) = modbus_schema(&mut store)?;
+    let et_pump = SchemaBuilder::object("Pump").float("Speed", 0.0).float("Temperature", 0.0).bool("Running", false).int("Faults", 0).apply(&mut store)?;
+    let pump = store.create_entity(et_pump, None, "P1")?;
+
+    let registers = HashMap::from([(0, 1234), (10, 0x422a), (11, 0x0000)]);
+    let address = serve_device(registers, vec![5]);
+    let device = store.create_entity(et_device, None, "PLC1")?;
+    store.write(device, &[store.get_field_type("Address")?], Value::String(format!("tcp://{}", address)), None, None, None, None)?;
+    add_point(&mut store, et_point, device, "Speed", 2, 0, 1, 0.1, pump, "Speed")?;
+    add_point(&mut store, et_point, device, "Temperature", 2, 10, 5, 1.0, pump, "Temperature")?;
+    let running = add_point(&mut store, et_point, device, "Running", 0, 5, 0, 1.0, pump, "Running")?;
+    add_point(&mut store, et_point, device, "Faults", 2, 99, 1, 1.0, pump, "Faults")?;
+    store.write(running, &[store.get_field_type("PollInterval")?], Value::Int(5000), None, None, None, None)?;
+
+    let mut poller = ModbusPoller::new();
+    let start = now();
+    assert_eq!(poller.tick(&mut store, start)?, 3);
+    assert_eq!(store.read(pump, &[store.get_field_type("Speed")?])?.0, Value::Float(123.4));
+    assert_eq!(store.read(pump, &[store.get_field_type("Temperature")?])?.0, Value::Float(42.5));
+    assert_eq!(store.read(pump, &[store.get_field_type("Running")?])?.0, Value::Bool(true));
+    // The exception only affects its point
+    let (health, _, _) = store.read(device, &[store.get_field_type(ft::HEALTH)?])?;
+    assert_eq!(health, Value::Choice(app::HealthStatus::Degraded.as_choice()));
+    let (message, _, _) = store.read(device, &[store.get_field_type(ft::HEALTH_MESSAGE)?])?;
+    assert!(message.as_string().is_some_and(|message| message.contains("exception 2")));
+
+    // Nothing is due yet, then all but the slower point are
+    assert_eq!(poller.tick(&mut store, start + Duration::milliseconds(500))?, 0);
+    assert_eq!(poller.tick(&mut store, start + Duration::milliseconds(1000))?, 2);
+    Ok(())
+}
+
+#[test]
+fn test_modbus_poller_backs_off() -> Result<()> {
+    let mut store = Store::new();
+    let (et_device, et_point) = modbus_schema(&mut store)?;
+    let et_tank = SchemaBuilder::object("Tank").float("Level", 0.0).apply(&mut store)?;
+    let tank = store.create_entity(et_tank, None, "T1")?;
+    let device = store.create_entity(et_device, None, "PLC2")?;
+    store.write(device, &[store.get_field_type("Address")?], Value::String("plc2:502".into()), None, None, None, None)?;
+    add_point(&mut store, et_point, device, "Level", 3, 0, 1, 1.0, tank, "Level")?;
+
+    let attempts = Arc::new(Mutex::new(Vec::new()));
+    let recorded = attempts.clone();
+    let mut poller = ModbusPoller::with_connector(Box::new(move |address: &str| {
+        recorded.lock().unwrap().push(address.to_string());
+        Err(Error::InvalidRequest("connection refused".to_string()))
+    }))
+    .with_backoff(Duration::seconds(1), Duration::seconds(3));
+
+    let ft_failed_attempts = store.get_field_type(ft::FAILED_ATTEMPTS)?;
+    let start = now();
+    let mut expected_failures = 0;
+    for (offset, retries) in [(0, true), (500, false), (1000, true), (2999, false), (3000, true), (5999, false), (6000, true)] {
+        poller.tick(&mut store, start + Duration::milliseconds(offset))?;
+        expected_failures += retries as i64;
+        assert_eq!(attempts.lock().unwrap().len() as i64, expected_failures, "at {}ms", offset);
+    }
+    // 1s, 2s, then capped at 3s
+    assert_eq!(poller.retry_at(device), Some(start + Duration::seconds(9)));
+    assert_eq!(store.read(device, &[ft_failed_attempts])?.0, Value::Int(4));
+    let (health, _, _) = store.read(device, &[store.get_field_type(ft::HEALTH)?])?;
+    assert_eq!(health, Value::Choice(app::HealthStatus::Unhealthy.as_choice()));
+    assert_eq!(attempts.lock().unwrap()[0], "plc2:502");
+    Ok(())
+}
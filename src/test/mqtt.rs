@@ -0,0 +1,147 @@
+#[allow(unused_imports)]
+use crate::*;
+#[allow(unused_imports)]
+use crate::testing::SchemaBuilder;
+#[allow(unused_imports)]
+use crate::bridge::mqtt::{BridgedField, MqttBridge, MqttClient, MqttMessage, MqttOptions};
+#[allow(unused_imports)]
+use std::io::{Read, Write};
+#[allow(unused_imports)]
+use std::net::{TcpListener, TcpStream};
+#[allow(unused_imports)]
+use std::sync::{Arc, Mutex};
+
+#[allow(dead_code)]
+fn read_packet(stream: &mut TcpStream) -> Option<(u8, Vec<u8>)> {
+    let mut header = [0u8; 1];
+    stream.read_exact(&mut header).ok()?;
+    let (mut length, mut shift) = (0usize, 0);
+    loop {
+        let mut byte = [0u8; 1];
+        stream.read_exact(&mut byte).ok()?;
+        length += ((byte[0] & 0x7f) as usize) << shift;
+        shift += 7;
+        if byte[0] & 0x80 == 0 {
+            break;
+        }
+    }
+    let mut body = vec![0u8; length];
+    stream.read_exact(&mut body).ok()?;
+    Some((header[0], body))
+}
+
+#[allow(dead_code)]
+fn publish_packet(topic: &str, payload: &[u8]) -> Vec<u8> {
+    let mut packet = vec![0x30, (2 + topic.len() + payload.len()) as u8];
+    packet.extend_from_slice(&(topic.len() as u16).to_be_bytes());
+    packet.extend_from_slice(topic.as_bytes());
+    packet.extend_from_slice(payload);
+    packet
+}
+
+/// Messages a client published, as (topic, payload, retained)
+#[allow(dead_code)]
+type Published = Arc<Mutex<Vec<(String, String, bool)>>>;
+
+/// A broker that accepts one client, answers its CONNECT, SUBSCRIBE and
+/// PINGREQ, sends it `inbound` once it has subscribed, and records what it
+/// publishes
+#[allow(dead_code)]
+fn serve_broker(inbound: Vec<(&'static str, &'static [u8])>) -> (String, Published) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    let published = Arc::new(Mutex::new(Vec::new()));
+    let state = published.clone();
+    std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        while let Some((header, body)) = read_packet(&mut stream) {
+            match header & 0xf0 {
+                0x10 => {
+                    // Protocol name, level 4, clean session, user name and password
+                    assert_eq!(&body[..8], b"\x00\x04MQTT\x04\xc2");
+                    stream.write_all(&[0x20, 2, 0, 0]).unwrap();
+                }
+                0x80 => {
+                    assert!(body.ends_with(b"\x00\x07plant/#\x00"));
+                    stream.write_all(&[0x90, 3, body[0], body[1], 0]).unwrap();
+                    for (topic, payload) in &inbound {
+                        stream.write_all(&publish_packet(topic, payload)).unwrap();
+                    }
+                }
+                0x30 => {
+                    let length = u16::from_be_bytes([body[0], body[1]]) as usize;
+                    let topic = String::from_utf8(body[2..2 + length].to_vec()).unwrap();
+                    let payload = String::from_utf8(body[2 + length..].to_vec()).unwrap();
+                    state.lock().unwrap().push((topic, payload, header & 0x01 == 1));
+                }
+                0xc0 => stream.write_all(&[0xd0, 0]).unwrap(),
+                _ => break,
+            }
+        }
+    });
+    (address, published)
+}
+
+#[test]
+fn test_mqtt_bridge_publishes_and_writes() -> Result<()> {
+    let mut store = Store::new();
+    let et_root = SchemaBuilder::object(et::ROOT).apply(&mut store)?;
+    let et_pump = SchemaBuilder::object("Pump")
+        .int("Speed", 0)
+        .int("Setpoint", 0)
+        .choice("Mode", &["Off", "Auto"])
+        .apply(&mut store)?;
+    let plant = store.create_entity(et_root, None, "Plant")?;
+    let pump = store.create_entity(et_pump, Some(plant), "P1")?;
+    let (ft_speed, ft_setpoint, ft_mode) = (store.get_field_type("Speed")?, store.get_field_type("Setpoint")?, store.get_field_type("Mode")?);
+
+    let bridge = MqttBridge::new("plant/")
+        .field(BridgedField::of_type(et_pump, ft_speed))
+        .field(BridgedField::of_entity(pump, ft_mode))
+        .field(BridgedField::of_type(et_pump, ft_setpoint).writable());
+    assert_eq!(bridge.topic(&store, pump, ft_speed)?, "plant/Plant/P1/Speed");
+    let queue = NotificationQueue::new();
+    for config in bridge.notify_configs() {
+        store.register_notification(config, queue.clone())?;
+    }
+
+    let (address, published) = serve_broker(vec![
+        ("plant/Plant/P1/Speed/set", b"99"),
+        ("plant/Plant/P1/Setpoint/set", b"1200"),
+        ("plant/Plant/P1/Setpoint", b"1"),
+    ]);
+    let options = MqttOptions::new(&address, "qlib-test").with_credentials("bridge", "secret");
+    let mut client = MqttClient::connect(&options)?;
+
+    bridge.start(&store, &mut client)?;
+    store.write(pump, &[ft_speed], Value::Int(1500), None, None, None, None)?;
+    store.write(pump, &[ft_mode], Value::Choice(1), None, None, None, None)?;
+
+    let timeout = std::time::Duration::from_millis(500);
+    // Speed isn't writable, so its set message is dropped
+    bridge.run_once(&mut store, &mut client, &queue, timeout)?;
+    assert_eq!(store.read(pump, &[ft_speed])?.0, Value::Int(1500));
+    // The setpoint is written, and the write published back
+    bridge.run_once(&mut store, &mut client, &queue, timeout)?;
+    assert_eq!(store.read(pump, &[ft_setpoint])?.0, Value::Int(1200));
+    let state_message = MqttMessage { topic: "plant/Plant/P1/Setpoint".to_string(), payload: b"1".to_vec() };
+    assert_eq!(bridge.handle_message(&mut store, &state_message)?, None);
+    bridge.run_once(&mut store, &mut client, &queue, std::time::Duration::ZERO)?;
+    client.disconnect()?;
+
+    std::thread::sleep(std::time::Duration::from_millis(100));
+    let published = published.lock().unwrap().clone();
+    let expected = [
+        ("plant/Plant/P1/Speed", "0"),
+        ("plant/Plant/P1/Mode", "\"Off\""),
+        ("plant/Plant/P1/Setpoint", "0"),
+        ("plant/Plant/P1/Speed", "1500"),
+        ("plant/Plant/P1/Mode", "\"Auto\""),
+        ("plant/Plant/P1/Setpoint", "1200"),
+    ];
+    assert_eq!(
+        published,
+        expected.iter().map(|(topic, payload)| (topic.to_string(), payload.to_string(), true)).collect::<Vec<_>>()
+    );
+    Ok(())
+}
@@ -0,0 +1,106 @@
+#[allow(unused_imports)]
+use crate::*;
+#[allow(unused_imports)]
+use crate::testing::SchemaBuilder;
+#[allow(unused_imports)]
+use crate::bridge::opcua::{DataChange, OpcUaConnection, OpcUaIngest, OpcUaMapping};
+#[allow(unused_imports)]
+use std::collections::VecDeque;
+
+/// A connection replaying scripted poll results; `connect` fails while
+/// `refuse` is set
+#[allow(dead_code)]
+#[derive(Default)]
+struct ScriptedConnection {
+    refuse: bool,
+    monitored: Vec<String>,
+    polls: VecDeque<Result<Vec<DataChange>>>,
+}
+
+impl OpcUaConnection for ScriptedConnection {
+    fn connect(&mut self, endpoint: &str) -> Result<()> {
+        match self.refuse {
+            true => Err(Error::InvalidRequest(format!("{} refused the connection", endpoint))),
+            false => Ok(()),
+        }
+    }
+
+    fn monitor(&mut self, node_ids: &[String]) -> Result<()> {
+        self.monitored = node_ids.to_vec();
+        Ok(())
+    }
+
+    fn poll(&mut self, _timeout: std::time::Duration) -> Result<Vec<DataChange>> {
+        self.polls.pop_front().unwrap_or(Ok(Vec::new()))
+    }
+}
+
+#[allow(dead_code)]
+fn change(node_id: &str, value: Value, status_code: u32) -> DataChange {
+    DataChange { node_id: node_id.to_string(), value, status_code, source_timestamp: None }
+}
+
+#[test]
+fn test_opcua_ingest_scales_and_reports_health() -> Result<()> {
+    let mut store = Store::new();
+    let et_plc = SchemaBuilder::object("Plc")
+        .choice(ft::HEALTH, &["Unknown", "Healthy", "Degraded", "Unhealthy"])
+        .string(ft::HEALTH_MESSAGE, "")
+        .apply(&mut store)?;
+    let et_pump = SchemaBuilder::object("Pump").float("Speed", 0.0).int("Starts", 0).bool("Running", false).apply(&mut store)?;
+    let plc = store.create_entity(et_plc, None, "PLC1")?;
+    let pump = store.create_entity(et_pump, None, "P1")?;
+    let (ft_speed, ft_starts, ft_running) = (store.get_field_type("Speed")?, store.get_field_type("Starts")?, store.get_field_type("Running")?);
+    let ft_health = store.get_field_type(ft::HEALTH)?;
+    let health = |store: &Store| store.read(plc, &[ft_health]).map(|(value, _, _)| app::HealthStatus::from_choice(value.as_choice().unwrap_or_default()));
+
+    let mut ingest = OpcUaIngest::new("opc.tcp://plc1:4840")
+        .with_health_entity(plc)
+        .map(OpcUaMapping::new("ns=2;s=P1.Speed", pump, ft_speed).with_scaling(0.1, 0.0).with_deadband(0.5))
+        .map(OpcUaMapping::new("ns=2;s=P1.Starts", pump, ft_starts))
+        .map(OpcUaMapping::new("ns=2;s=P1.Running", pump, ft_running));
+    let timeout = std::time::Duration::ZERO;
+
+    let mut connection = ScriptedConnection { refuse: true, ..Default::default() };
+    assert_eq!(ingest.tick(&mut store, &mut connection, timeout)?, 0);
+    assert_eq!(health(&store)?, app::HealthStatus::Unhealthy);
+
+    connection.refuse = false;
+    connection.polls.extend([
+        Ok(vec![
+            change("ns=2;s=P1.Speed", Value::Int(1000), 0),
+            change("ns=2;s=P1.Starts", Value::Float(2.6), 0),
+            change("ns=2;s=P1.Running", Value::Bool(true), 0),
+        ]),
+        // Within the deadband, then outside it
+        Ok(vec![change("ns=2;s=P1.Speed", Value::Int(1004), 0), change("ns=2;s=P1.Speed", Value::Int(1010), 0)]),
+        // BadCommunicationError isn't written
+        Ok(vec![change("ns=2;s=P1.Speed", Value::Int(0), 0x8005_0000)]),
+        Ok(vec![change("ns=2;s=P1.Speed", Value::Int(1200), 0)]),
+        Err(Error::InvalidRequest("secure channel closed".to_string())),
+    ]);
+
+    assert_eq!(ingest.tick(&mut store, &mut connection, timeout)?, 3);
+    assert_eq!(connection.monitored, vec!["ns=2;s=P1.Speed", "ns=2;s=P1.Starts", "ns=2;s=P1.Running"]);
+    assert_eq!(health(&store)?, app::HealthStatus::Healthy);
+    assert_eq!(store.read(pump, &[ft_speed])?.0, Value::Float(100.0));
+    assert_eq!(store.read(pump, &[ft_starts])?.0, Value::Int(3));
+    assert_eq!(store.read(pump, &[ft_running])?.0, Value::Bool(true));
+
+    assert_eq!(ingest.tick(&mut store, &mut connection, timeout)?, 1);
+    assert_eq!(store.read(pump, &[ft_speed])?.0, Value::Float(101.0));
+
+    assert_eq!(ingest.tick(&mut store, &mut connection, timeout)?, 0);
+    assert_eq!(health(&store)?, app::HealthStatus::Degraded);
+    assert_eq!(store.read(pump, &[ft_speed])?.0, Value::Float(101.0));
+
+    assert_eq!(ingest.tick(&mut store, &mut connection, timeout)?, 1);
+    assert_eq!(health(&store)?, app::HealthStatus::Healthy);
+
+    assert_eq!(ingest.tick(&mut store, &mut connection, timeout)?, 0);
+    assert!(!ingest.is_connected());
+    assert_eq!(health(&store)?, app::HealthStatus::Unhealthy);
+    let (message, _, _) = store.read(plc, &[store.get_field_type(ft::HEALTH_MESSAGE)?])?;
+    assert_eq!(message, Value::String("Lost connection to opc.tcp://plc1:4840: Invalid request: secure channel closed".into()));
+    Ok(())
+}
@@ -0,0 +1,1585 @@
+#[allow(unused_imports)]
+use crate::*;
+#[allow(unused_imports)]
+use std::io::{Read, Write};
+#[allow(unused_imports)]
+use std::net::TcpListener;
+
+// Accept one connection, wait for a command and answer it with `reply`.
+// The handle yields every byte the client sent.
+#[allow(dead_code)]
+fn serve_once(reply: Vec<u8>) -> Result<(String, std::thread::JoinHandle<Vec<u8>>)> {
+    serve_script(vec![reply])
+}
+
+// Like `serve_once`, but answers each request in turn with the next reply
+#[allow(dead_code)]
+fn serve_script(replies: Vec<Vec<u8>>) -> Result<(String, std::thread::JoinHandle<Vec<u8>>)> {
+    let listener = TcpListener::bind("127.0.0.1:0").map_err(|e| Error::StoreProxyError(e.to_string()))?;
+    let address = listener.local_addr().map_err(|e| Error::StoreProxyError(e.to_string()))?.to_string();
+    let handle = std::thread::spawn(move || {
+        let mut received = Vec::new();
+        if let Ok((mut stream, _)) = listener.accept() {
+            let mut request = [0u8; 4096];
+            for reply in replies {
+                if let Ok(n) = stream.read(&mut request) {
+                    received.extend_from_slice(&request[..n]);
+                }
+                let _ = stream.write_all(&reply);
+            }
+            // Hold the connection open until the client hangs up
+            while let Ok(n) = stream.read(&mut request) {
+                if n == 0 {
+                    break;
+                }
+                received.extend_from_slice(&request[..n]);
+            }
+        }
+        received
+    });
+    Ok((address, handle))
+}
+
+#[test]
+fn test_protocol_limits_check_frame() {
+    let limits = ProtocolLimits::new(1024);
+    assert!(limits.check_frame(1024).is_ok());
+    assert!(matches!(limits.check_frame(1025), Err(Error::FrameTooLarge(1025, 1024))));
+    assert_eq!(ProtocolLimits::default().max_message_size, MAX_MESSAGE_SIZE);
+}
+
+#[test]
+fn test_store_proxy_rejects_oversized_frames() -> Result<()> {
+    // A 4KiB bulk string is more than the client is willing to buffer
+    let mut reply = b"$4096\r\n".to_vec();
+    reply.extend(std::iter::repeat_n(b'x', 4096));
+    reply.extend_from_slice(b"\r\n");
+    let (address, server) = serve_once(reply)?;
+
+    let proxy = StoreProxy::connect_with_limits(&address, ProtocolLimits::new(1024))?;
+
+    // Outgoing commands are checked before anything is sent
+    let too_long = "x".repeat(2048);
+    assert!(matches!(proxy.get_entity_type(&too_long), Err(Error::FrameTooLarge(_, 1024))));
+
+    // Responses are abandoned once they grow past the limit
+    assert!(matches!(proxy.get_entity_type("Root"), Err(Error::FrameTooLarge(_, 1024))));
+
+    drop(proxy);
+    let _ = server.join();
+    Ok(())
+}
+
+#[test]
+fn test_protocol_error_frames_round_trip() {
+    use crate::data::resp::{error_from_frame, OwnedRespValue, ProtocolError};
+
+    let errors = vec![
+        ProtocolError::UnknownCommand("FROB".to_string()),
+        ProtocolError::ArityMismatch("GET".to_string(), 3, 2),
+        ProtocolError::MalformedFrame("Invalid RESP type marker".to_string()),
+        ProtocolError::FrameTooLarge(2048, 1024),
+        ProtocolError::Unsupported("SNAP is disabled".to_string()),
+        ProtocolError::Timeout(250),
+        ProtocolError::Cancelled(42),
+        ProtocolError::ShuttingDown,
+    ];
+    for error in errors {
+        let OwnedRespValue::Error(frame) = error.to_resp() else {
+            panic!("{:?} did not encode as an error frame", error);
+        };
+        assert!(frame.starts_with(error.code()));
+        assert_eq!(ProtocolError::from_frame(&frame), Some(error));
+    }
+
+    assert_eq!(error_from_frame("ERR_ARITY GET expected 3 got 2").to_string(), "Protocol error: ERR_ARITY GET expected 3 got 2");
+    assert!(matches!(error_from_frame("ERR_FRAME_TOO_LARGE size 2048 limit 1024"), Error::FrameTooLarge(2048, 1024)));
+    assert!(matches!(error_from_frame("ERR_TIMEOUT after 250 ms"), Error::Timeout(timeout) if timeout.as_millis() == 250));
+    assert!(matches!(error_from_frame("ERR_CANCELLED 42"), Error::Cancelled(42)));
+    assert_eq!(ProtocolError::from_error(&Error::Timeout(std::time::Duration::from_millis(250))), Some(ProtocolError::Timeout(250)));
+    // Frames without a known code are passed through as text
+    assert!(matches!(error_from_frame("Entity not found"), Error::StoreProxyError(_)));
+    assert!(ProtocolError::from_frame("ERR_ARITY GET").is_none());
+}
+
+#[test]
+fn test_decode_errors_are_typed() {
+    use crate::data::resp::{ProtocolError, ReadCommand, RespDecode, RespFromBytes, RespValue};
+
+    // GET with the field path missing
+    let (frame, _) = RespValue::from_bytes(b"*2\r\n$3\r\nGET\r\n:1\r\n").expect("complete frame");
+    assert!(matches!(
+        ReadCommand::decode(frame),
+        Err(Error::ProtocolError(ProtocolError::ArityMismatch(command, 3, 2))) if command == "GET"
+    ));
+
+    assert!(matches!(
+        RespValue::from_bytes(b"?bogus\r\n"),
+        Err(Error::ProtocolError(ProtocolError::MalformedFrame(_)))
+    ));
+    // Incomplete input isn't malformed; the caller should wait for more
+    assert!(matches!(RespValue::from_bytes(b"$5\r\nab"), Err(Error::InvalidRequest(_))));
+}
+
+#[test]
+fn test_store_proxy_converts_protocol_error_frames() -> Result<()> {
+    let (address, server) = serve_once(b"-ERR_ARITY GETTYPE expected 2 got 3\r\n".to_vec())?;
+
+    let proxy = StoreProxy::connect(&address)?;
+    assert!(matches!(
+        proxy.get_entity_type("Root"),
+        Err(Error::ProtocolError(crate::data::resp::ProtocolError::ArityMismatch(command, 2, 3))) if command == "GETTYPE"
+    ));
+
+    drop(proxy);
+    let _ = server.join();
+    Ok(())
+}
+
+#[test]
+fn test_store_error_frames_round_trip() {
+    use crate::data::resp::{error_from_frame, error_to_frame, OwnedRespValue};
+
+    let pump = EntityId::new(EntityType(3), 7);
+    let speed = FieldType(12);
+    let errors = vec![
+        Error::BadIndirection(pump, vec![speed], BadIndirectionReason::ArrayIndexOutOfBounds(4, 2)),
+        Error::EntityNotFound(pump),
+        Error::EntityTypeStrNotFound("Pump".to_string()),
+        Error::FieldTypeNotFound(pump, speed),
+        Error::InvalidFieldValue("line one\nline two".to_string()),
+        Error::UnsupportedAdjustBehavior(pump, speed, AdjustBehavior::Add),
+        Error::ValueTypeMismatch(pump, speed, Value::from_string("fast".to_string()), Value::Float(0.0)),
+        Error::StaleFencingToken(pump, 3, 5),
+        Error::EntityReferenced(pump, EntityId::new(EntityType(4), 1), speed),
+        Error::QuotaExceeded("operator".to_string(), "entities".to_string(), 1000),
+        Error::ChecksumMismatch(vec!["Pump".to_string(), "Tank".to_string()]),
+        Error::NotificationsDiscarded("hmi".to_string(), 18),
+        Error::AccountLocked,
+        Error::ConnectionLost,
+        Error::Timeout(std::time::Duration::from_millis(250)),
+    ];
+    for error in errors {
+        let OwnedRespValue::Error(frame) = error_to_frame(&error) else {
+            panic!("{:?} did not encode as an error frame", error);
+        };
+        assert!(frame.starts_with(error.code()));
+        assert!(!frame.contains('\n'));
+        let decoded = error_from_frame(&frame);
+        assert_eq!(decoded.code(), error.code());
+        assert_eq!(decoded.to_string(), error.to_string());
+    }
+
+    let OwnedRespValue::Error(frame) = error_to_frame(&Error::EntityNotFound(pump)) else { unreachable!() };
+    assert_eq!(frame, format!("ERR_ENTITY_NOT_FOUND [{}] Entity not found: {:?}", pump.0, pump));
+    // A known code with parameters that don't fit it stays text
+    assert!(matches!(error_from_frame("ERR_ENTITY_NOT_FOUND [\"Root\"]"), Error::StoreProxyError(_)));
+    assert!(matches!(error_from_frame("ERR Entity not found"), Error::StoreProxyError(_)));
+}
+
+#[test]
+fn test_store_proxy_converts_store_error_frames() -> Result<()> {
+    use crate::data::resp::{error_to_frame, RespToBytes};
+
+    let missing = EntityId::new(EntityType(1), 9);
+    let (address, server) = serve_script(vec![
+        error_to_frame(&Error::EntityNotFound(missing)).to_bytes(),
+        error_to_frame(&Error::EntityNameNotFound("Machines".to_string())).to_bytes(),
+    ])?;
+
+    let proxy = StoreProxy::connect(&address)?;
+    assert!(matches!(proxy.read(missing, &[FieldType(1)]), Err(Error::EntityNotFound(id)) if id == missing));
+    assert!(matches!(proxy.resolve_path("Root/Machines"), Err(Error::EntityNameNotFound(name)) if name == "Machines"));
+
+    drop(proxy);
+    let _ = server.join();
+    Ok(())
+}
+
+#[test]
+fn test_pipeline_execute_atomic() -> Result<()> {
+    use crate::data::resp::{IntegerResponse, OwnedRespValue, RespEncode, RespToBytes, ResolvePathResponse};
+
+    let machine = EntityId::new(EntityType(1), 7);
+    let mut reply = b"+OK\r\n+QUEUED\r\n+QUEUED\r\n+QUEUED\r\n".to_vec();
+    reply.extend(OwnedRespValue::Array(vec![
+        OwnedRespValue::SimpleString("OK".to_string()),
+        ResolvePathResponse { entity_id: machine }.encode(),
+        IntegerResponse { value: 12 }.encode(),
+    ]).to_bytes());
+    let (address, server) = serve_once(reply)?;
+
+    let proxy = StoreProxy::connect(&address)?;
+    let mut pipeline = proxy.pipeline();
+    pipeline.write_path("Root/Machines/M1/Speed", Value::Int(5), None, None, None, None)?;
+    pipeline.resolve_path("Root/Machines/M1")?;
+    pipeline.write_blob_append(machine, &[FieldType(3)], b"chunk".to_vec(), None)?;
+    let results = pipeline.execute_atomic()?;
+
+    assert_eq!(results.len(), 3);
+    let _: () = results.get(0)?;
+    assert_eq!(results.get::<EntityId>(1)?, machine);
+    assert_eq!(results.get::<usize>(2)?, 12);
+    assert!(results.get::<bool>(2).is_err());
+
+    drop(proxy);
+    let request = String::from_utf8_lossy(&server.join().expect("server thread")).to_string();
+    let multi = request.find("MULTI").expect("MULTI was sent");
+    let exec = request.find("EXEC").expect("EXEC was sent");
+    assert!(multi < request.find("WRITE_PATH").expect("command was sent"));
+    assert!(exec > request.find("APPEND_BLOB").expect("command was sent"));
+    Ok(())
+}
+
+#[test]
+fn test_pipeline_atomic_failure_reports_first_error() -> Result<()> {
+    use crate::data::resp::{ProtocolError, RespToBytes};
+
+    let mut reply = b"+OK\r\n+QUEUED\r\n".to_vec();
+    reply.extend(ProtocolError::UnknownCommand("FROB".to_string()).to_resp().to_bytes());
+    reply.extend_from_slice(b"-EXECABORT Transaction discarded because of previous errors\r\n");
+    let (address, server) = serve_once(reply)?;
+
+    let proxy = StoreProxy::connect(&address)?;
+    let mut pipeline = proxy.pipeline();
+    pipeline.get_entity_type("Root")?;
+    pipeline.machine_info()?;
+    assert!(matches!(
+        pipeline.execute_atomic(),
+        Err(Error::ProtocolError(ProtocolError::UnknownCommand(command))) if command == "FROB"
+    ));
+
+    drop(proxy);
+    let _ = server.join();
+    Ok(())
+}
+
+#[test]
+fn test_pipeline_unregister_keeps_shared_listen() -> Result<()> {
+    let (address, server) = serve_once(b"+OK\r\n+OK\r\n".to_vec())?;
+    let config = NotifyConfig::EntityType {
+        entity_type: EntityType(1),
+        field_type: FieldType(2),
+        trigger_on_change: true,
+        context: vec![],
+        filter: None,
+    };
+    let (first, _first_rx) = crossbeam::channel::unbounded();
+    let (second, _second_rx) = crossbeam::channel::unbounded();
+
+    let proxy = StoreProxy::connect(&address)?;
+    let mut pipeline = proxy.pipeline();
+    pipeline.register_notification(config.clone(), first.clone())?;
+    pipeline.register_notification(config.clone(), second)?;
+    let results = pipeline.execute()?;
+    let _: () = results.get(1)?;
+
+    // Another sender still listens, so this is answered without the server
+    let mut pipeline = proxy.pipeline();
+    pipeline.unregister_notification(&config, &first)?;
+    assert!(pipeline.execute()?.get::<bool>(0)?);
+
+    drop(proxy);
+    let request = String::from_utf8_lossy(&server.join().expect("server thread")).to_string();
+    assert_eq!(request.matches("LISTEN").count(), 2);
+    assert!(!request.contains("UNLISTEN"));
+    Ok(())
+}
+
+#[test]
+fn test_cached_store_proxy_invalidates_on_notification() -> Result<()> {
+    use crate::data::resp::{NotificationCommand, ReadResponse, RespEncode, RespToBytes};
+
+    let entity_id = EntityId::new(EntityType(1), 3);
+    let speed = FieldType(9);
+    let config = NotifyConfig::EntityId {
+        entity_id,
+        field_type: speed,
+        trigger_on_change: false,
+        context: vec![],
+        filter: None,
+    };
+    let info = |value: i64| NotifyInfo {
+        entity_id,
+        field_path: smallvec::smallvec![speed],
+        value: Some(Value::Int(value)),
+        timestamp: None,
+        writer_id: None,
+    };
+    let notification = Notification {
+        current: info(2),
+        previous: info(1),
+        context: Default::default(),
+        config_hash: hash_notify_config(&config),
+    };
+
+    // LISTEN and GET are answered together, then the field is written
+    let mut reply = b"+OK\r\n".to_vec();
+    reply.extend(ReadResponse { value: Value::Int(1), timestamp: epoch(), writer_id: None }.encode().to_bytes());
+    reply.extend(NotificationCommand {
+        notification,
+        _marker: std::marker::PhantomData,
+    }.encode().to_bytes());
+    let (address, server) = serve_once(reply)?;
+
+    let cached = CachedStoreProxy::connect(&address)?;
+    assert_eq!(cached.read(entity_id, &[speed])?.0, Value::Int(1));
+    assert_eq!(cached.read(entity_id, &[speed])?.0, Value::Int(1));
+    assert_eq!(cached.stats(), CacheStats { hits: 1, misses: 1, invalidations: 0 });
+    assert_eq!(cached.stats().hit_rate(), 0.5);
+
+    cached.process_notifications()?;
+    assert!(cached.is_empty());
+    assert_eq!(cached.stats().invalidations, 1);
+
+    drop(cached);
+    let request = String::from_utf8_lossy(&server.join().expect("server thread")).to_string();
+    // The second read never reached the server
+    assert_eq!(request.matches("GET").count(), 1);
+    Ok(())
+}
+
+#[test]
+fn test_cache_preload_and_write_through() -> Result<()> {
+    use crate::data::resp::{PaginatedEntityResponse, ReadResponse, RespEncode, RespToBytes};
+
+    let sensor_type = EntityType(4);
+    let (first, second) = (EntityId::new(sensor_type, 1), EntityId::new(sensor_type, 2));
+    let (name, reading) = (FieldType(1), FieldType(2));
+    let read_reply = |value: Value| ReadResponse { value, timestamp: epoch(), writer_id: None }.encode().to_bytes();
+
+    let mut values = read_reply(Value::String("north".to_string()));
+    values.extend(read_reply(Value::Float(1.5)));
+    values.extend(read_reply(Value::String("south".to_string())));
+    values.extend(read_reply(Value::Float(2.5)));
+    let (address, server) = serve_script(vec![
+        // LISTEN for each cached field
+        b"+OK\r\n".to_vec(),
+        b"+OK\r\n".to_vec(),
+        PaginatedEntityResponse { items: vec![first, second], total: 2, next_cursor: None, next_key: None }.encode().to_bytes(),
+        // Both fields of both entities in one pipeline
+        values,
+        // The write-through SET
+        b"+OK\r\n".to_vec(),
+    ])?;
+
+    let proxy = StoreProxy::connect(&address)?;
+    let (mut cache, _receiver) = Cache::new(&proxy, sensor_type, vec![name], vec![reading])?;
+    assert_eq!(cache.len(), 2);
+    assert_eq!(cache.iter_entities().count(), 2);
+
+    let mut readings: Vec<(EntityId, Value)> = cache.values_for_field(reading).map(|(id, value)| (id, value.clone())).collect();
+    readings.sort_by_key(|(id, _)| *id);
+    assert_eq!(readings, vec![(first, Value::Float(1.5)), (second, Value::Float(2.5))]);
+
+    // Writes are refused until write-through is enabled
+    assert!(cache.write(&proxy, first, name, Value::String("east".to_string())).is_err());
+    cache.set_write_through(true);
+    assert!(matches!(cache.write(&proxy, first, FieldType(99), Value::Int(1)), Err(Error::CacheFieldNotFound(_))));
+    cache.write(&proxy, first, name, Value::String("east".to_string()))?;
+
+    assert!(cache.get(vec![Value::String("north".to_string())]).is_none());
+    let east = cache.get_unique(vec![Value::String("east".to_string())]).expect("reindexed");
+    assert_eq!(east.get(&reading), Some(&Value::Float(1.5)));
+    assert!(cache.get_unique(vec![Value::String("south".to_string())]).is_some());
+
+    drop(proxy);
+    let request = String::from_utf8_lossy(&server.join().expect("server thread")).to_string();
+    assert_eq!(request.matches("FINDPAG").count(), 1);
+    assert_eq!(request.matches("SET").count(), 1);
+    Ok(())
+}
+
+#[test]
+fn test_bulk_type_lookup() -> Result<()> {
+    use crate::data::resp::{IntegerResponse, ProtocolError, RespEncode, RespToBytes, TypesBulkResponse};
+
+    let bulk = TypesBulkResponse {
+        entity_types: vec![Some(EntityType(3)), None],
+        field_types: vec![Some(FieldType(9))],
+    };
+    let mut names = vec![None; 13];
+    names[5] = Some(EntityType(2));
+    let et_reply = TypesBulkResponse { entity_types: names, field_types: vec![] };
+    let (address, server) = serve_script(vec![bulk.encode().to_bytes(), et_reply.encode().to_bytes()])?;
+
+    let proxy = StoreProxy::connect(&address)?;
+    let (entity_types, field_types) = proxy.get_types_bulk(&["Root", "Missing"], &["Name"])?;
+    assert_eq!(entity_types, vec![Some(EntityType(3)), None]);
+    assert_eq!(field_types, vec![Some(FieldType(9))]);
+
+    // ET::new resolves every known entity type in one request
+    let et = crate::et::ET::new(&proxy);
+    assert_eq!(et.root, Some(EntityType(2)));
+    assert_eq!(et.folder, None);
+
+    drop(proxy);
+    let request = String::from_utf8_lossy(&server.join().expect("server thread")).to_string();
+    assert_eq!(request.matches("GET_TYPES_BULK").count(), 2);
+    assert!(!request.contains("GETTYPE"));
+
+    // Servers without the command get one lookup per name instead
+    let unknown = ProtocolError::UnknownCommand("GET_TYPES_BULK".to_string()).to_resp().to_bytes();
+    let mut replies = vec![unknown];
+    replies.extend((0..13).map(|i| IntegerResponse { value: i }.encode().to_bytes()));
+    let (address, server) = serve_script(replies)?;
+
+    let proxy = StoreProxy::connect(&address)?;
+    let et = crate::et::ET::new(&proxy);
+    assert_eq!(et.fault_tolerance, Some(EntityType(0)));
+    assert_eq!(et.candidate, Some(EntityType(10)));
+    assert_eq!(et.backup_schedule, Some(EntityType(11)));
+    assert_eq!(et.trigger, Some(EntityType(12)));
+
+    drop(proxy);
+    let _ = server.join();
+    Ok(())
+}
+
+#[test]
+fn test_leader_guard_rejects_writes_locally() -> Result<()> {
+    use crate::app::{CandidateState, LeaderGuard};
+    use crate::data::resp::{RespEncode, RespToBytes, TypesBulkResponse};
+
+    let candidate_id = EntityId::new(EntityType(5), 1);
+    let output = EntityId::new(EntityType(6), 1);
+    let no_types = TypesBulkResponse { entity_types: vec![], field_types: vec![None; 46] };
+    let (address, server) = serve_script(vec![no_types.encode().to_bytes(), b"+OK\r\n".to_vec()])?;
+
+    let mut proxy = StoreProxy::connect(&address)?;
+    let mut candidate = CandidateState::new(&mut proxy, candidate_id);
+    {
+        let mut guard = LeaderGuard::new(&mut proxy, &mut candidate);
+        assert!(!guard.is_leader()?);
+        let result = guard.write(output, &[FieldType(9)], Value::Int(1), None, None, None, None);
+        assert!(matches!(result, Err(Error::NotLeader(id)) if id == candidate_id));
+        assert!(matches!(guard.delete_entity(output), Err(Error::NotLeader(_))));
+    }
+
+    candidate.is_leader = true;
+    LeaderGuard::new(&mut proxy, &mut candidate).write(output, &[FieldType(9)], Value::Int(2), None, None, None, None)?;
+
+    drop(proxy);
+    let request = String::from_utf8_lossy(&server.join().expect("server thread")).to_string();
+    // Only the leader's write reached the server
+    assert_eq!(request.matches("SET").count(), 1);
+    assert!(!request.contains("DEL"));
+    Ok(())
+}
+
+#[test]
+fn test_fencing_token_comes_from_leader_notification() -> Result<()> {
+    use crate::app::CandidateState;
+    use crate::data::resp::{ReadResponse, RespEncode, RespToBytes, TypesBulkResponse};
+    use std::collections::BTreeMap;
+
+    let candidate_id = EntityId::new(EntityType(5), 1);
+    let fault_tolerance_id = EntityId::new(EntityType(7), 1);
+    let (ft_current_leader, ft_leader_token) = (FieldType(31), FieldType(30));
+    let mut field_types = vec![None; 46];
+    field_types[9] = Some(ft_current_leader);
+    field_types[24] = Some(ft_leader_token);
+    let types = TypesBulkResponse { entity_types: vec![], field_types };
+    // By the time the candidate could read LeaderToken, another leader has taken over
+    let successor_token = ReadResponse { value: Value::Int(6), timestamp: epoch(), writer_id: None };
+    let (address, server) = serve_script(vec![types.encode().to_bytes(), successor_token.encode().to_bytes()])?;
+
+    let mut proxy = StoreProxy::connect(&address)?;
+    let mut candidate = CandidateState::new(&mut proxy, candidate_id);
+    let info = |field_type: FieldType, value: Value| NotifyInfo {
+        entity_id: fault_tolerance_id,
+        field_path: crate::sfield![field_type],
+        value: Some(value),
+        timestamp: Some(epoch()),
+        writer_id: None,
+    };
+    candidate.notify_ch.0.send(Notification {
+        current: info(ft_current_leader, Value::EntityReference(Some(candidate_id))),
+        previous: info(ft_current_leader, Value::EntityReference(None)),
+        context: BTreeMap::from([(vec![ft_leader_token], info(ft_leader_token, Value::Int(5)))]),
+        config_hash: 0,
+    }).expect("channel open");
+
+    candidate.tick(&mut proxy)?;
+    assert!(candidate.is_leader);
+    assert_eq!(candidate.fencing_token, Some((fault_tolerance_id, 5)));
+
+    drop(proxy);
+    let request = String::from_utf8_lossy(&server.join().expect("server thread")).to_string();
+    // Nothing but the type lookup was sent
+    assert!(!request.contains("$3\r\nGET\r\n"));
+    Ok(())
+}
+
+#[test]
+fn test_audit_query_round_trip() -> Result<()> {
+    use crate::data::resp::{AuditQueryCommand, AuditQueryResponse, RespDecode, RespEncode, RespFromBytes, RespToBytes};
+
+    let subject = EntityId::new(EntityType(1), 3);
+    let record = AuditRecord {
+        seq: 9,
+        timestamp: now(),
+        subject: Some(subject),
+        connection: Some("10.0.0.7:51234".to_string()),
+        write: WriteInfo::DeleteEntity {
+            entity_id: EntityId::new(EntityType(2), 5),
+            timestamp: now(),
+        },
+    };
+    let reply = AuditQueryResponse::from_records(std::slice::from_ref(&record))?.encode().to_bytes();
+    let (address, server) = serve_once(reply)?;
+
+    let query = AuditQuery { subject: Some(subject), limit: Some(10), ..Default::default() };
+    let proxy = StoreProxy::connect(&address)?;
+    assert_eq!(proxy.audit_query(&query)?, vec![record]);
+
+    drop(proxy);
+    let request = server.join().expect("server thread");
+    let (value, _) = crate::data::resp::RespValue::from_bytes(&request)?;
+    assert_eq!(AuditQueryCommand::decode(value)?.to_query(), query);
+    Ok(())
+}
+
+#[test]
+fn test_get_changes_round_trip() -> Result<()> {
+    use crate::data::resp::{ChangesResponse, GetChangesCommand, RespDecode, RespEncode, RespFromBytes, RespToBytes};
+
+    let pump = EntityId::new(EntityType(4), 1);
+    let writes = vec![WriteInfo::DeleteEntity { entity_id: pump, timestamp: now() }];
+    let reply = ChangesResponse::from_writes(&writes)?.encode().to_bytes();
+    let (address, server) = serve_once(reply)?;
+
+    let since = now();
+    let proxy = StoreProxy::connect(&address)?;
+    assert_eq!(proxy.get_changes(pump, since)?, writes);
+
+    drop(proxy);
+    let request = server.join().expect("server thread");
+    let (value, _) = crate::data::resp::RespValue::from_bytes(&request)?;
+    let command = GetChangesCommand::decode(value)?;
+    assert_eq!((command.entity_id, command.since), (pump, since));
+    Ok(())
+}
+
+#[test]
+fn test_find_referencing_round_trip() -> Result<()> {
+    use crate::data::resp::{FindReferencingCommand, ReferencingResponse, RespDecode, RespEncode, RespFromBytes, RespToBytes};
+
+    let pump = EntityId::new(EntityType(4), 1);
+    let referrers = vec![(EntityId::new(EntityType(5), 2), FieldType(7)), (EntityId::new(EntityType(5), 3), FieldType(8))];
+    let reply = ReferencingResponse {
+        entity_ids: referrers.iter().map(|(entity_id, _)| *entity_id).collect(),
+        field_types: referrers.iter().map(|(_, field_type)| *field_type).collect(),
+    };
+    let (address, server) = serve_once(reply.encode().to_bytes())?;
+
+    let proxy = StoreProxy::connect(&address)?;
+    assert_eq!(StoreTrait::find_referencing(&proxy, pump)?, referrers);
+
+    drop(proxy);
+    let request = server.join().expect("server thread");
+    let (value, _) = crate::data::resp::RespValue::from_bytes(&request)?;
+    assert_eq!(FindReferencingCommand::decode(value)?.entity_id, pump);
+    Ok(())
+}
+
+#[test]
+fn test_create_entity_with_fields_round_trip() -> Result<()> {
+    use crate::data::resp::{CreateEntityFullCommand, CreateEntityResponse, RespDecode, RespEncode, RespFromBytes, RespToBytes};
+
+    let et_pump = EntityType(4);
+    let pump = EntityId::new(et_pump, 1);
+    let fields = vec![(FieldType(7), Value::Int(1500)), (FieldType(8), Value::String("Auto".into()))];
+    let (address, server) = serve_once(CreateEntityResponse { entity_id: pump }.encode().to_bytes())?;
+
+    let proxy = StoreProxy::connect(&address)?;
+    assert_eq!(proxy.create_entity_with_fields(et_pump, None, "P1", fields.clone())?, pump);
+
+    drop(proxy);
+    let request = server.join().expect("server thread");
+    let (value, _) = crate::data::resp::RespValue::from_bytes(&request)?;
+    let command = CreateEntityFullCommand::decode(value)?;
+    assert_eq!((command.entity_type, command.name.as_str(), command.fields), (et_pump, "P1", fields));
+    Ok(())
+}
+
+#[test]
+fn test_instantiate_template_round_trip() -> Result<()> {
+    use crate::data::resp::{CreateEntityResponse, InstantiateTemplateCommand, RespDecode, RespEncode, RespFromBytes, RespToBytes};
+
+    let template = EntityId::new(EntityType(4), 1);
+    let press = EntityId::new(EntityType(4), 2);
+    let (address, server) = serve_once(CreateEntityResponse { entity_id: press }.encode().to_bytes())?;
+
+    let proxy = StoreProxy::connect(&address)?;
+    assert_eq!(proxy.instantiate_template(template, None, "Press1", vec![(FieldType(7), Value::Int(3))])?, press);
+
+    drop(proxy);
+    let request = server.join().expect("server thread");
+    let (value, _) = crate::data::resp::RespValue::from_bytes(&request)?;
+    let command = InstantiateTemplateCommand::decode(value)?;
+    assert_eq!((command.template_id, command.name.as_str(), command.overrides), (template, "Press1", vec![(FieldType(7), Value::Int(3))]));
+    Ok(())
+}
+
+#[test]
+fn test_get_or_create_and_upsert_round_trip() -> Result<()> {
+    use crate::data::resp::{BooleanResponse, GetOrCreateCommand, GetOrCreateResponse, RespDecode, RespEncode, RespFromBytes, RespToBytes, UpsertFieldCommand};
+
+    let et_folder = EntityType(4);
+    let folder = EntityId::new(et_folder, 1);
+    let (address, server) = serve_script(vec![
+        GetOrCreateResponse { entity_id: folder, created: false }.encode().to_bytes(),
+        BooleanResponse { result: true }.encode().to_bytes(),
+    ])?;
+
+    // The trait methods go to the server as single commands
+    let mut proxy = StoreProxy::connect(&address)?;
+    assert_eq!(StoreTrait::get_or_create(&mut proxy, et_folder, None, "Config")?, (folder, false));
+    assert!(StoreTrait::upsert_field(&mut proxy, folder, FieldType(7), Value::Int(3))?);
+
+    drop(proxy);
+    let request = server.join().expect("server thread");
+    let (value, rest) = crate::data::resp::RespValue::from_bytes(&request)?;
+    assert_eq!(GetOrCreateCommand::decode(value)?.name, "Config");
+    let (value, _) = crate::data::resp::RespValue::from_bytes(rest)?;
+    assert_eq!(UpsertFieldCommand::decode(value)?.value, Value::Int(3));
+    Ok(())
+}
+
+#[test]
+fn test_rename_entity_round_trip() -> Result<()> {
+    use crate::data::resp::{RenameEntityCommand, RespDecode, RespFromBytes};
+
+    let folder = EntityId::new(EntityType(4), 1);
+    let (address, server) = serve_once(b"+OK\r\n".to_vec())?;
+
+    let mut proxy = StoreProxy::connect(&address)?;
+    StoreTrait::rename_entity(&mut proxy, folder, "Pumps")?;
+
+    drop(proxy);
+    let request = server.join().expect("server thread");
+    let (value, _) = crate::data::resp::RespValue::from_bytes(&request)?;
+    let command = RenameEntityCommand::decode(value)?;
+    assert_eq!((command.entity_id, command.new_name.as_str()), (folder, "Pumps"));
+    Ok(())
+}
+
+#[test]
+fn test_sorted_page_round_trip() -> Result<()> {
+    use crate::data::resp::{FindEntitiesPaginatedCommand, PaginatedEntityResponse, RespDecode, RespEncode, RespFromBytes, RespToBytes};
+
+    let pump_type = EntityType(4);
+    let power = FieldType(9);
+    let (first, second) = (EntityId::new(pump_type, 1), EntityId::new(pump_type, 2));
+    let reply = PaginatedEntityResponse {
+        items: vec![first, second],
+        total: 3,
+        next_cursor: None,
+        next_key: Some((Value::Int(12), second)),
+    };
+    let (address, server) = serve_once(reply.encode().to_bytes())?;
+
+    let proxy = StoreProxy::connect(&address)?;
+    let mut opts = PageOpts::new(2, None).with_order_by(power, SortDirection::Descending);
+    opts.after = Some((Value::Int(30), EntityId::new(pump_type, 7)));
+    let page = proxy.find_entities_paginated(pump_type, Some(&opts), None)?;
+    assert_eq!(page.items, vec![first, second]);
+    assert_eq!(page.next_page(&opts).and_then(|next| next.after), Some((Value::Int(12), second)));
+
+    drop(proxy);
+    let request = server.join().expect("server thread");
+    let (value, _) = crate::data::resp::RespValue::from_bytes(&request)?;
+    let sent = FindEntitiesPaginatedCommand::decode(value)?.page_opts.expect("page opts");
+    assert_eq!(sent.order_by, Some((power, SortDirection::Descending)));
+    assert_eq!(sent.after, opts.after);
+    Ok(())
+}
+
+#[test]
+fn test_search_round_trip() -> Result<()> {
+    use crate::data::resp::{EntityListResponse, RespDecode, RespEncode, RespFromBytes, RespToBytes, SearchCommand};
+
+    let found = vec![EntityId::new(EntityType(4), 1), EntityId::new(EntityType(4), 3)];
+    let (address, server) = serve_once(EntityListResponse { entities: found.clone() }.encode().to_bytes())?;
+
+    let proxy = StoreProxy::connect(&address)?;
+    assert_eq!(proxy.search(EntityType(4), "\"water pump\" bas*")?, found);
+
+    drop(proxy);
+    let request = server.join().expect("server thread");
+    let (value, _) = crate::data::resp::RespValue::from_bytes(&request)?;
+    let command = SearchCommand::decode(value)?;
+    assert_eq!(command.entity_type, EntityType(4));
+    assert_eq!(command.query, "\"water pump\" bas*");
+    Ok(())
+}
+
+#[test]
+fn test_aggregate_round_trip() -> Result<()> {
+    use crate::data::resp::{AggregateCommand, AggregateResponse, RespDecode, RespEncode, RespFromBytes, RespToBytes};
+
+    let reply = AggregateResponse { value: Some(Value::Int(47)) };
+    let (address, server) = serve_once(reply.encode().to_bytes())?;
+
+    let proxy = StoreProxy::connect(&address)?;
+    let total = StoreTrait::aggregate(&proxy, EntityType(4), FieldType(9), AggregateOp::Sum, Some("Power > 10"))?;
+    assert_eq!(total, Some(Value::Int(47)));
+
+    drop(proxy);
+    let request = server.join().expect("server thread");
+    let (value, _) = crate::data::resp::RespValue::from_bytes(&request)?;
+    let command = AggregateCommand::decode(value)?;
+    assert_eq!(command.entity_type, EntityType(4));
+    assert_eq!(command.field_type, FieldType(9));
+    assert_eq!(command.op, AggregateOp::Sum);
+    assert_eq!(command.filter.as_deref(), Some("Power > 10"));
+
+    // A Min/Max over no entities comes back as null
+    let empty = AggregateResponse { value: None }.encode().to_bytes();
+    let (empty, _) = crate::data::resp::RespValue::from_bytes(&empty)?;
+    assert!(AggregateResponse::decode(empty)?.value.is_none());
+    Ok(())
+}
+
+#[test]
+fn test_async_notification_stream() -> Result<()> {
+    use crate::data::resp::{NotificationCommand, ReadResponse, RespEncode, RespToBytes};
+    use crate::data::AsyncStoreProxy;
+    use futures_util::StreamExt;
+
+    let entity_id = EntityId::new(EntityType(1), 3);
+    let speed = FieldType(9);
+    let config = NotifyConfig::EntityId {
+        entity_id,
+        field_type: speed,
+        trigger_on_change: false,
+        context: vec![],
+        filter: None,
+    };
+    let notify = |value: i64| {
+        let info = |value: i64| NotifyInfo {
+            entity_id,
+            field_path: smallvec::smallvec![speed],
+            value: Some(Value::Int(value)),
+            timestamp: None,
+            writer_id: None,
+        };
+        let notification = Notification {
+            current: info(value),
+            previous: info(value - 1),
+            context: Default::default(),
+            config_hash: hash_notify_config(&config),
+        };
+        NotificationCommand {
+            notification,
+            _marker: std::marker::PhantomData,
+        }.encode().to_bytes()
+    };
+
+    // Notifications are pushed right after LISTEN is acknowledged, before
+    // any other command, and again alongside the reply to a later GET
+    let mut listen_reply = b"+OK\r\n".to_vec();
+    listen_reply.extend(notify(1));
+    listen_reply.extend(notify(2));
+    let mut get_reply = notify(3);
+    get_reply.extend(ReadResponse { value: Value::Int(3), timestamp: epoch(), writer_id: None }.encode().to_bytes());
+    let (address, server) = serve_script(vec![listen_reply, get_reply, b"+OK\r\n".to_vec()])?;
+
+    let runtime = tokio::runtime::Runtime::new().map_err(|e| Error::StoreProxyError(e.to_string()))?;
+    runtime.block_on(async {
+        let proxy = AsyncStoreProxy::connect(&address).await?;
+        let mut stream = proxy.register_notification_stream(config.clone()).await?;
+        let value = |notification: Option<Notification>| notification.and_then(|n| n.current.value);
+
+        let timeout = std::time::Duration::from_secs(5);
+        let first = tokio::time::timeout(timeout, stream.next()).await.expect("first notification");
+        assert_eq!(value(first), Some(Value::Int(1)));
+        let second = tokio::time::timeout(timeout, stream.next()).await.expect("second notification");
+        assert_eq!(value(second), Some(Value::Int(2)));
+
+        // Commands still get through while the stream is open
+        assert_eq!(proxy.read(entity_id, &[speed]).await?.0, Value::Int(3));
+        let third = tokio::time::timeout(timeout, stream.next()).await.expect("third notification");
+        assert_eq!(value(third), Some(Value::Int(3)));
+        assert_eq!(stream.dropped(), 0);
+
+        proxy.unregister_notification_stream(stream).await?;
+        Ok::<_, Error>(())
+    })?;
+    drop(runtime);
+
+    let request = String::from_utf8_lossy(&server.join().expect("server thread")).to_string();
+    assert!(request.contains("UNLISTEN"));
+    Ok(())
+}
+
+#[test]
+fn test_wait_for_field_value() -> Result<()> {
+    use crate::data::resp::{NotificationCommand, ReadResponse, RespEncode, RespToBytes};
+    use std::time::Duration;
+
+    let entity_id = EntityId::new(EntityType(1), 3);
+    let state = FieldType(9);
+    let config = NotifyConfig::EntityId {
+        entity_id,
+        field_type: state,
+        trigger_on_change: true,
+        context: vec![],
+        filter: None,
+    };
+    let notify = |value: i64| {
+        let info = |value: i64| NotifyInfo {
+            entity_id,
+            field_path: smallvec::smallvec![state],
+            value: Some(Value::Int(value)),
+            timestamp: None,
+            writer_id: None,
+        };
+        NotificationCommand {
+            notification: Notification {
+                current: info(value),
+                previous: info(value - 1),
+                context: Default::default(),
+                config_hash: hash_notify_config(&config),
+            },
+            _marker: std::marker::PhantomData,
+        }.encode().to_bytes()
+    };
+    let read_reply = |value: i64| ReadResponse { value: Value::Int(value), timestamp: epoch(), writer_id: None }.encode().to_bytes();
+
+    // The field moves on twice after it is read, then the second wait never
+    // sees what it wants
+    let mut get_reply = read_reply(1);
+    get_reply.extend(notify(2));
+    get_reply.extend(notify(3));
+    let (address, server) = serve_script(vec![
+        b"+OK\r\n".to_vec(),
+        get_reply,
+        b"+OK\r\n".to_vec(),
+        b"+OK\r\n".to_vec(),
+        read_reply(3),
+        b"+OK\r\n".to_vec(),
+    ])?;
+
+    let proxy = StoreProxy::connect(&address)?;
+    let ready = |value: &Value| value.as_int().is_some_and(|value| value >= 3);
+    assert_eq!(proxy.wait_for(entity_id, state, ready, Duration::from_secs(5))?, Value::Int(3));
+    let stopped = |value: &Value| value.as_int() == Some(0);
+    assert!(matches!(proxy.wait_for(entity_id, state, stopped, Duration::from_millis(50)), Err(Error::Timeout(_))));
+
+    drop(proxy);
+    let request = String::from_utf8_lossy(&server.join().expect("server thread")).to_string();
+    // Registered before reading, and cleaned up after both waits
+    assert!(request.find("LISTEN").unwrap() < request.find("GET").unwrap());
+    assert_eq!(request.matches("UNLISTEN").count(), 2);
+    Ok(())
+}
+
+#[test]
+fn test_async_wait_for_field_value() -> Result<()> {
+    use crate::data::resp::{NotificationCommand, ReadResponse, RespEncode, RespToBytes};
+    use crate::data::AsyncStoreProxy;
+    use std::time::Duration;
+
+    let entity_id = EntityId::new(EntityType(1), 3);
+    let state = FieldType(9);
+    let config = NotifyConfig::EntityId {
+        entity_id,
+        field_type: state,
+        trigger_on_change: true,
+        context: vec![],
+        filter: None,
+    };
+    let info = |value: i64| NotifyInfo {
+        entity_id,
+        field_path: smallvec::smallvec![state],
+        value: Some(Value::Int(value)),
+        timestamp: None,
+        writer_id: None,
+    };
+    let mut get_reply = ReadResponse { value: Value::Int(1), timestamp: epoch(), writer_id: None }.encode().to_bytes();
+    get_reply.extend(NotificationCommand {
+        notification: Notification {
+            current: info(2),
+            previous: info(1),
+            context: Default::default(),
+            config_hash: hash_notify_config(&config),
+        },
+        _marker: std::marker::PhantomData,
+    }.encode().to_bytes());
+    let (address, server) = serve_script(vec![b"+OK\r\n".to_vec(), get_reply, b"+OK\r\n".to_vec()])?;
+
+    let runtime = tokio::runtime::Runtime::new().map_err(|e| Error::StoreProxyError(e.to_string()))?;
+    runtime.block_on(async {
+        let proxy = AsyncStoreProxy::connect(&address).await?;
+        let value = proxy.wait_for(entity_id, state, |value| value == &Value::Int(2), Duration::from_secs(5)).await?;
+        assert_eq!(value, Value::Int(2));
+        Ok::<_, Error>(())
+    })?;
+    drop(runtime);
+
+    let request = String::from_utf8_lossy(&server.join().expect("server thread")).to_string();
+    assert!(request.contains("UNLISTEN"));
+    Ok(())
+}
+
+#[test]
+fn test_resume_durable_subscription() -> Result<()> {
+    use crate::data::resp::{DurableNotificationCommand, RespEncode, RespToBytes};
+    use crate::data::AsyncStoreProxy;
+
+    let entity_id = EntityId::new(EntityType(1), 3);
+    let level = FieldType(4);
+    let config = NotifyConfig::EntityId {
+        entity_id,
+        field_type: level,
+        trigger_on_change: false,
+        context: vec![],
+        filter: None,
+    };
+    let info = |value: i64| NotifyInfo {
+        entity_id,
+        field_path: smallvec::smallvec![level],
+        value: Some(Value::Int(value)),
+        timestamp: None,
+        writer_id: None,
+    };
+    let pushed = |seq: u64| DurableNotificationCommand {
+        subscription_id: "tank-levels".to_string(),
+        seq,
+        notification: Notification {
+            current: info(seq as i64),
+            previous: info(seq as i64 - 1),
+            context: Default::default(),
+            config_hash: hash_notify_config(&config),
+        },
+        _marker: std::marker::PhantomData,
+    }.encode().to_bytes();
+
+    // RESUME is answered with the missed notifications, one of them
+    // repeated as if it also arrived live, then the last sequence number
+    let mut resume_reply = pushed(6);
+    resume_reply.extend(pushed(7));
+    resume_reply.extend(pushed(7));
+    resume_reply.extend_from_slice(b":7\r\n");
+    let (address, server) = serve_script(vec![resume_reply.clone(), b"+OK\r\n".to_vec()])?;
+
+    let proxy = StoreProxy::connect(&address)?;
+    let (sender, receiver) = crossbeam::channel::unbounded();
+    assert_eq!(proxy.resume_durable("tank-levels", 5, sender)?, 7);
+    let received: Vec<u64> = receiver.try_iter().map(|(seq, _)| seq).collect();
+    assert_eq!(received, vec![6, 7]);
+    proxy.unsubscribe_durable("tank-levels")?;
+    drop(proxy);
+
+    let request = String::from_utf8_lossy(&server.join().expect("server thread")).to_string();
+    assert!(request.contains("RESUME") && request.contains("tank-levels"));
+    assert!(request.contains("DUNLISTEN"));
+
+    let (address, server) = serve_once(resume_reply)?;
+    let runtime = tokio::runtime::Runtime::new().map_err(|e| Error::StoreProxyError(e.to_string()))?;
+    runtime.block_on(async {
+        let proxy = AsyncStoreProxy::connect(&address).await?;
+        let (last_seq, mut receiver) = proxy.resume_durable("tank-levels", 5).await?;
+        assert_eq!(last_seq, 7);
+        let mut received = Vec::new();
+        while let Ok((seq, notification)) = receiver.try_recv() {
+            assert_eq!(notification.current.value, Some(Value::Int(seq as i64)));
+            received.push(seq);
+        }
+        assert_eq!(received, vec![6, 7]);
+        Ok::<_, Error>(())
+    })?;
+    drop(runtime);
+    server.join().expect("server thread");
+    Ok(())
+}
+
+#[test]
+fn test_async_timeout_resyncs_connection() -> Result<()> {
+    use crate::data::resp::{PaginatedEntityResponse, ReadResponse, RespEncode, RespToBytes};
+    use crate::data::AsyncStoreProxy;
+    use std::time::Duration;
+
+    // The first FINDPAG is answered with a timeout error, the second only
+    // after the client has given up on it
+    let listener = TcpListener::bind("127.0.0.1:0").map_err(|e| Error::StoreProxyError(e.to_string()))?;
+    let address = listener.local_addr().map_err(|e| Error::StoreProxyError(e.to_string()))?.to_string();
+    let server = std::thread::spawn(move || {
+        let mut received = Vec::new();
+        let (mut stream, _) = listener.accept().expect("client connects");
+        let mut request = [0u8; 4096];
+        let late_reply = PaginatedEntityResponse { items: vec![], total: 0, next_cursor: None, next_key: None }.encode().to_bytes();
+        let read_value = ReadResponse { value: Value::Int(7), timestamp: epoch(), writer_id: None }.encode().to_bytes();
+        for (delay, reply) in [
+            (0, b"-ERR_TIMEOUT after 50 ms\r\n".to_vec()),
+            (800, late_reply),
+            (0, b"+OK\r\n".to_vec()),
+            (0, read_value),
+        ] {
+            let n = stream.read(&mut request).unwrap_or(0);
+            received.extend_from_slice(&request[..n]);
+            std::thread::sleep(Duration::from_millis(delay));
+            let _ = stream.write_all(&reply);
+        }
+        received
+    });
+
+    let runtime = tokio::runtime::Runtime::new().map_err(|e| Error::StoreProxyError(e.to_string()))?;
+    runtime.block_on(async {
+        let proxy = AsyncStoreProxy::connect(&address).await?;
+        let entity_type = EntityType(1);
+        let timeout = Some(Duration::from_millis(50));
+
+        // The server reports the timeout itself
+        let result = proxy.find_entities_paginated_with_timeout(entity_type, None, Some("Speed > 10"), timeout).await;
+        assert!(matches!(result, Err(Error::Timeout(t)) if t == Duration::from_millis(50)));
+
+        // The server doesn't answer in time, so the client gives up and cancels
+        let result = proxy.find_entities_paginated_with_timeout(entity_type, None, Some("Speed > 10"), timeout).await;
+        assert!(matches!(result, Err(Error::Timeout(_))));
+
+        // The late reply was skipped, so the next command gets its own answer
+        let read = proxy.read(EntityId::new(entity_type, 1), &[FieldType(2)]).await?;
+        assert_eq!(read.0, Value::Int(7));
+        Ok::<_, Error>(())
+    })?;
+    drop(runtime);
+
+    let request = String::from_utf8_lossy(&server.join().expect("server thread")).to_string();
+    assert_eq!(request.matches("FINDPAG").count(), 2);
+    assert!(request.contains("CANCEL"));
+    Ok(())
+}
+
+#[test]
+fn test_multiplexed_replies_out_of_order() -> Result<()> {
+    use crate::data::resp::{decode_tagged, encode_tagged, ReadCommand, ReadResponse, RespDecode, RespEncode, RespFromBytes, RespToBytes, RespValue};
+    use crate::data::AsyncStoreProxy;
+
+    let tagged = encode_tagged(42, b"+OK\r\n");
+    let (value, rest) = RespValue::from_bytes(&tagged)?;
+    assert!(rest.is_empty());
+    assert!(matches!(decode_tagged(&value), Some((42, RespValue::SimpleString("OK")))));
+    assert!(decode_tagged(&RespValue::SimpleString("OK")).is_none());
+
+    // Wait for two tagged reads, then answer the second one first. Each
+    // reply carries the id of the entity that was read.
+    let listener = TcpListener::bind("127.0.0.1:0").map_err(|e| Error::StoreProxyError(e.to_string()))?;
+    let address = listener.local_addr().map_err(|e| Error::StoreProxyError(e.to_string()))?.to_string();
+    let server = std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept().expect("client connects");
+        let mut buffer = Vec::new();
+        let mut request = [0u8; 4096];
+        let mut reads = Vec::new();
+        while reads.len() < 2 {
+            let n = stream.read(&mut request).unwrap_or(0);
+            assert!(n > 0, "client hung up early");
+            buffer.extend_from_slice(&request[..n]);
+            while let Ok((value, rest)) = RespValue::from_bytes(&buffer) {
+                let (correlation_id, command) = decode_tagged(&value).expect("command is tagged");
+                let command = ReadCommand::decode(command.clone()).expect("command is a read");
+                reads.push((correlation_id, command.entity_id.extract_id()));
+                let consumed = buffer.len() - rest.len();
+                buffer.drain(..consumed);
+            }
+        }
+        for (correlation_id, id) in reads.into_iter().rev() {
+            let reply = ReadResponse { value: Value::Int(id as i64), timestamp: epoch(), writer_id: None }.encode().to_bytes();
+            let _ = stream.write_all(&encode_tagged(correlation_id, &reply));
+        }
+    });
+
+    let runtime = tokio::runtime::Runtime::new().map_err(|e| Error::StoreProxyError(e.to_string()))?;
+    runtime.block_on(async {
+        let proxy = AsyncStoreProxy::connect_multiplexed(&address).await?;
+        assert!(proxy.is_multiplexed());
+        let entity_type = EntityType(1);
+
+        let (first, second) = tokio::join!(
+            proxy.read(EntityId::new(entity_type, 1), &[FieldType(2)]),
+            proxy.read(EntityId::new(entity_type, 2), &[FieldType(2)]),
+        );
+        assert_eq!(first?.0, Value::Int(1));
+        assert_eq!(second?.0, Value::Int(2));
+        Ok::<_, Error>(())
+    })?;
+    drop(runtime);
+
+    server.join().expect("server thread");
+    Ok(())
+}
+
+#[test]
+fn test_ids_encode_as_binary() -> Result<()> {
+    use crate::data::resp::{with_binary_ids, OwnedRespValue, ReadCommand, RespDecode, RespEncode, RespFromBytes, RespToBytes, RespValue, BINARY_ID_MARKER};
+
+    let entity_id = EntityId::new(EntityType(3), 0x3030_3030);
+    let field_path = vec![FieldType(1), FieldType(u64::MAX)];
+    let command = ReadCommand { entity_id, field_path: field_path.clone(), _marker: std::marker::PhantomData };
+
+    // Ids are integers unless the connection negotiated binary ids
+    assert!(matches!(entity_id.encode(), OwnedRespValue::Integer(id) if id as u64 == entity_id.0));
+    assert!(matches!(field_path.encode(), OwnedRespValue::Array(elements) if elements.len() == 2));
+    let bytes = command.encode().to_bytes();
+    let decoded = ReadCommand::decode(RespValue::from_bytes(&bytes)?.0)?;
+    assert_eq!((decoded.entity_id, &decoded.field_path), (entity_id, &field_path));
+
+    with_binary_ids(true, || -> Result<()> {
+        let OwnedRespValue::BulkString(data) = entity_id.encode() else {
+            panic!("entity id should encode as a bulk string");
+        };
+        assert_eq!(data[0], BINARY_ID_MARKER);
+        assert_eq!(&data[1..], &entity_id.0.to_le_bytes());
+        let OwnedRespValue::BulkString(data) = field_path.encode() else {
+            panic!("field path should encode as one bulk string");
+        };
+        assert_eq!(data.len(), 1 + 2 * 8);
+
+        let bytes = command.encode().to_bytes();
+        let decoded = ReadCommand::decode(RespValue::from_bytes(&bytes)?.0)?;
+        assert_eq!((decoded.entity_id, &decoded.field_path), (entity_id, &field_path));
+        assert_eq!(EntityType::decode(RespValue::from_bytes(&EntityType(7).encode().to_bytes())?.0)?, EntityType(7));
+        Ok(())
+    })?;
+    assert!(matches!(EntityType(7).encode(), OwnedRespValue::Integer(7)));
+
+    // Ids typed as text, e.g. from a terminal, still decode
+    assert_eq!(EntityId::decode(RespValue::BulkString(b"42"))?, EntityId(42));
+    assert_eq!(EntityId::decode(RespValue::Integer(42))?, EntityId(42));
+    assert_eq!(
+        Vec::<FieldType>::decode(RespValue::Array(vec![RespValue::BulkString(b"5"), RespValue::Integer(6)]))?,
+        vec![FieldType(5), FieldType(6)]
+    );
+
+    // Truncated binary is rejected rather than misread
+    assert!(matches!(EntityId::decode(RespValue::BulkString(b"#1234")), Err(Error::ProtocolError(_))));
+    assert!(matches!(Vec::<FieldType>::decode(RespValue::BulkString(b"#123456789")), Err(Error::ProtocolError(_))));
+    Ok(())
+}
+
+#[test]
+fn test_notification_encodes_as_nested_array() -> Result<()> {
+    use crate::data::resp::{NotificationCommand, OwnedRespValue, RespDecode, RespEncode, RespFromBytes, RespToBytes, RespValue};
+
+    let info = |field: u64, value: Option<Value>| NotifyInfo {
+        entity_id: EntityId::new(EntityType(2), 11),
+        field_path: smallvec::smallvec![FieldType(field)],
+        value,
+        timestamp: Some(epoch()),
+        writer_id: None,
+    };
+    let mut context = std::collections::BTreeMap::new();
+    context.insert(vec![FieldType(4), FieldType(5)], info(5, Some(Value::String("ctx".to_string()))));
+    let command = NotificationCommand {
+        notification: Notification {
+            current: info(1, Some(Value::Int(2))),
+            previous: info(1, None),
+            context,
+            config_hash: u64::MAX - 1,
+        },
+        _marker: std::marker::PhantomData,
+    };
+
+    // The notification is nested in the frame rather than wrapped in a string
+    let OwnedRespValue::Array(elements) = command.encode() else {
+        panic!("NOTIFY should encode as an array");
+    };
+    assert!(matches!(&elements[1], OwnedRespValue::Array(fields) if fields.len() == 4));
+
+    let check = |bytes: Vec<u8>| -> Result<()> {
+        let decoded = NotificationCommand::decode(RespValue::from_bytes(&bytes)?.0)?.notification;
+        assert_eq!(decoded.config_hash, u64::MAX - 1);
+        assert_eq!(decoded.current.value, Some(Value::Int(2)));
+        assert_eq!(decoded.previous.value, None);
+        assert_eq!(decoded.context[&vec![FieldType(4), FieldType(5)]].value, Some(Value::String("ctx".to_string())));
+        Ok(())
+    };
+    check(command.encode().to_bytes())?;
+    // JSON notifications from older servers still decode
+    check(command.encode_legacy()?.to_bytes())?;
+    Ok(())
+}
+
+#[test]
+fn test_respc_enum_dispatches_on_command_name() -> Result<()> {
+    use crate::data::resp::{OwnedRespValue, ProtocolError, ReadCommand, RespDecode, RespEncode, RespFromBytes, RespToBytes, RespValue, StoreCommand, WriteCommand};
+
+    let read = ReadCommand { entity_id: EntityId::new(EntityType(1), 5), field_path: vec![FieldType(2)], _marker: std::marker::PhantomData };
+    let write = WriteCommand {
+        entity_id: EntityId::new(EntityType(1), 5),
+        field_path: vec![FieldType(2)],
+        value: Value::Int(9),
+        writer_id: None,
+        write_time: None,
+        push_condition: None,
+        adjust_behavior: None,
+        _marker: std::marker::PhantomData,
+    };
+
+    // Wrapped commands are sent exactly as the command itself
+    let bytes = read.encode().to_bytes();
+    assert_eq!(StoreCommand::Read(read.clone()).encode().to_bytes(), bytes);
+    let StoreCommand::Read(decoded) = StoreCommand::decode(RespValue::from_bytes(&bytes)?.0)? else {
+        panic!("GET should decode as a read");
+    };
+    assert_eq!(decoded.entity_id, read.entity_id);
+    let decoded = StoreCommand::decode(RespValue::from_bytes(&write.encode().to_bytes())?.0)?;
+    assert_eq!(decoded.command_name(), "SET");
+    assert!(matches!(decoded, StoreCommand::Write(command) if command.value == Value::Int(9)));
+
+    let unknown = RespValue::Array(vec![RespValue::BulkString(b"FROB")]);
+    assert!(matches!(
+        StoreCommand::decode(unknown),
+        Err(Error::ProtocolError(ProtocolError::UnknownCommand(command))) if command == "FROB"
+    ));
+
+    // Variants can also name their own commands
+    #[crate::respc]
+    #[derive(Debug, Clone)]
+    enum TestCommand<'a> {
+        Read(ReadCommand<'a>),
+        #[respc(name = "PING")]
+        Ping,
+        #[respc(name = "ECHO")]
+        Echo { message: String, _marker: std::marker::PhantomData<&'a ()> },
+        #[respc(name = "ADD")]
+        Add(i64, i64),
+    }
+
+    for command in [
+        TestCommand::Ping,
+        TestCommand::Echo { message: "hi".to_string(), _marker: std::marker::PhantomData },
+        TestCommand::Add(2, -3),
+    ] {
+        let bytes = command.encode().to_bytes();
+        let decoded = TestCommand::decode(RespValue::from_bytes(&bytes)?.0)?;
+        assert_eq!(decoded.command_name(), command.command_name());
+        assert_eq!(decoded.encode().to_bytes(), bytes);
+    }
+    assert!(matches!(
+        TestCommand::decode(RespValue::from_bytes(&TestCommand::Add(2, -3).encode().to_bytes())?.0)?,
+        TestCommand::Add(2, -3)
+    ));
+    let OwnedRespValue::Array(elements) = TestCommand::Add(2, -3).encode() else {
+        panic!("commands encode as arrays");
+    };
+    assert_eq!(elements[0], OwnedRespValue::BulkString(b"ADD".to_vec()));
+    assert_eq!(TestCommand::Ping.command_name(), "PING");
+    assert!(matches!(TestCommand::decode(RespValue::from_bytes(&read.encode().to_bytes())?.0)?, TestCommand::Read(_)));
+
+    let short = RespValue::Array(vec![RespValue::BulkString(b"ADD"), RespValue::Integer(1)]);
+    assert!(matches!(
+        TestCommand::decode(short),
+        Err(Error::ProtocolError(ProtocolError::ArityMismatch(command, 3, 2))) if command == "ADD"
+    ));
+    Ok(())
+}
+
+#[test]
+fn test_resp_field_attributes() -> Result<()> {
+    use crate::data::resp::{OwnedRespValue, RespDecode, RespEncode, RespFromBytes, RespToBytes, RespValue};
+
+    #[derive(Debug, Clone, crate::RespEncode, crate::RespDecode)]
+    struct Settings {
+        #[resp(rename = "name")]
+        label: String,
+        #[resp(default)]
+        retries: i64,
+        #[resp(skip)]
+        cached: Option<String>,
+        #[resp(default)]
+        tags: Vec<String>,
+    }
+
+    #[derive(Debug, Clone, crate::RespEncode, crate::RespDecode)]
+    struct OldSettings {
+        name: String,
+    }
+
+    let settings = Settings { label: "pump".to_string(), retries: 3, cached: Some("x".to_string()), tags: vec!["a".to_string()] };
+    let OwnedRespValue::Array(elements) = settings.encode() else {
+        panic!("structs encode as arrays");
+    };
+    // Renamed fields use the wire name, skipped fields are left out
+    assert_eq!(elements.len(), 6);
+    assert_eq!(elements[0], OwnedRespValue::BulkString(b"name".to_vec()));
+    assert!(!elements.contains(&OwnedRespValue::BulkString(b"cached".to_vec())));
+
+    let bytes = settings.encode().to_bytes();
+    let decoded = Settings::decode(RespValue::from_bytes(&bytes)?.0)?;
+    assert_eq!((decoded.label.as_str(), decoded.retries, decoded.cached, decoded.tags), ("pump", 3, None, vec!["a".to_string()]));
+
+    // A peer without the defaulted fields still decodes, and fields it
+    // doesn't know about are ignored
+    let old = OldSettings { name: "valve".to_string() }.encode().to_bytes();
+    let decoded = Settings::decode(RespValue::from_bytes(&old)?.0)?;
+    assert_eq!((decoded.label.as_str(), decoded.retries, decoded.tags.len()), ("valve", 0, 0));
+    assert_eq!(OldSettings::decode(RespValue::from_bytes(&bytes)?.0)?.name, "pump");
+
+    // Fields without a default are still required
+    let missing = RespValue::Array(vec![RespValue::BulkString(b"retries"), RespValue::Integer(1)]);
+    assert!(matches!(Settings::decode(missing), Err(Error::InvalidRequest(message)) if message.contains("'name'")));
+
+    #[derive(Debug, Clone, crate::RespEncode, crate::RespDecode)]
+    struct Pair(i64, #[resp(skip)] Option<i64>, #[resp(default)] i64);
+
+    let decoded = Pair::decode(RespValue::Array(vec![RespValue::Integer(7)]))?;
+    assert_eq!((decoded.0, decoded.1, decoded.2), (7, None, 0));
+    let OwnedRespValue::Array(elements) = Pair(1, Some(2), 3).encode() else {
+        panic!("tuple structs encode as arrays");
+    };
+    assert_eq!(elements, vec![OwnedRespValue::Integer(1), OwnedRespValue::Integer(3)]);
+    Ok(())
+}
+
+#[test]
+fn test_parse_i64_bytes_matches_std() -> Result<()> {
+    use crate::data::resp::{parse_i64_bytes, RespFromBytes, RespValue};
+
+    let mut inputs: Vec<String> = [
+        "0", "-0", "7", "-7", "12345678", "123456789", "-1234567890123456", "00000000000000000042",
+        "9223372036854775807", "9223372036854775808", "-9223372036854775808", "-9223372036854775809",
+        "99999999999999999999", "", "-", "--1", "12a45678", "1234567:", "1234 678", "12345678/",
+    ].iter().map(|s| s.to_string()).collect();
+    let mut n: i64 = 1;
+    while let Some(next) = n.checked_mul(7) {
+        inputs.push(n.to_string());
+        inputs.push((-n).to_string());
+        n = next + 3;
+    }
+
+    for input in &inputs {
+        assert_eq!(parse_i64_bytes(input.as_bytes()), input.parse::<i64>().ok(), "parsing {:?}", input);
+    }
+
+    // A lone \r inside a line doesn't end it
+    let (value, rest) = RespValue::from_bytes(b"+a\rb\r\n:-42\r\n")?;
+    assert!(matches!(value, RespValue::SimpleString("a\rb")));
+    assert!(matches!(RespValue::from_bytes(rest)?.0, RespValue::Integer(-42)));
+    assert!(RespValue::from_bytes(b"$3\r\nabc\r").is_err());
+    assert!(matches!(RespValue::from_bytes(b":12x\r\n"), Err(Error::ProtocolError(_))));
+    Ok(())
+}
+
+#[test]
+fn test_buffer_pool_reuses_buffers() -> Result<()> {
+    use crate::data::buffer_pool::{pooled_buffers, take_buffer, MAX_POOLED_CAPACITY};
+    use crate::data::resp::{ReadCommand, RespEncode, RespToBytes};
+
+    let command = ReadCommand {
+        entity_id: EntityId::new(EntityType(1), 5),
+        field_path: vec![FieldType(2)],
+        _marker: std::marker::PhantomData,
+    };
+
+    // Encoding into a buffer gives the same bytes as encoding on its own
+    let mut buffer = take_buffer();
+    command.encode_into(&mut buffer);
+    assert_eq!(&buffer[..], &command.encode().to_bytes()[..]);
+    let address = buffer.as_ptr();
+
+    let before = pooled_buffers();
+    drop(buffer);
+    assert_eq!(pooled_buffers(), before + 1);
+
+    // The next buffer is the same allocation, emptied
+    let buffer = take_buffer();
+    assert!(buffer.is_empty());
+    assert_eq!(buffer.as_ptr(), address);
+    assert_eq!(pooled_buffers(), before);
+
+    // A buffer whose bytes are kept doesn't go back
+    let kept = buffer.into_vec();
+    assert_eq!(kept.as_ptr(), address);
+    assert_eq!(pooled_buffers(), before);
+
+    // Oversized buffers are freed instead of pooled
+    let mut large = take_buffer();
+    large.reserve(MAX_POOLED_CAPACITY + 1);
+    let before = pooled_buffers();
+    drop(large);
+    assert_eq!(pooled_buffers(), before);
+    Ok(())
+}
+
+#[test]
+fn test_read_series_round_trip() -> Result<()> {
+    use crate::data::resp::{ReadSeriesCommand, RespDecode, RespEncode, RespFromBytes, RespToBytes, SeriesResponse};
+
+    let sampled_at = secs_to_timestamp(1_700_000_000);
+    let samples = vec![(sampled_at, Value::Float(1.5)), (sampled_at + Duration::seconds(10), Value::Float(2.5))];
+    let (address, server) = serve_once(SeriesResponse { samples: samples.clone() }.encode().to_bytes())?;
+
+    let proxy = StoreProxy::connect(&address)?;
+    let entity_id = EntityId::new(EntityType(4), 1);
+    assert_eq!(proxy.read_series(entity_id, FieldType(9), Some(sampled_at), None, Some(Duration::minutes(1)))?, samples);
+
+    drop(proxy);
+    let request = server.join().expect("server thread");
+    let (value, _) = crate::data::resp::RespValue::from_bytes(&request)?;
+    let command = ReadSeriesCommand::decode(value)?;
+    assert_eq!(command.entity_id, entity_id);
+    assert_eq!(command.field_type, FieldType(9));
+    assert_eq!(command.from, Some(sampled_at));
+    assert_eq!(command.to, None);
+    assert_eq!(command.downsample, Some(Duration::minutes(1)));
+    Ok(())
+}
+
+#[test]
+fn test_verify_round_trip() -> Result<()> {
+    use crate::data::resp::{ChecksumResponse, RespDecode, RespEncode, RespFromBytes, RespToBytes, VerifyCommand};
+
+    let checksum = Store::new().checksum();
+    let (address, server) = serve_once(ChecksumResponse::from_checksum(&checksum)?.encode().to_bytes())?;
+
+    let proxy = StoreProxy::connect(&address)?;
+    assert_eq!(proxy.verify()?, checksum);
+
+    drop(proxy);
+    let request = server.join().expect("server thread");
+    let (value, _) = crate::data::resp::RespValue::from_bytes(&request)?;
+    VerifyCommand::decode(value)?;
+    Ok(())
+}
+
+#[test]
+fn test_stats_round_trip() -> Result<()> {
+    use crate::data::resp::{RespDecode, RespEncode, RespFromBytes, RespToBytes, StatsCommand, StatsResponse};
+
+    let mut store = Store::new();
+    let mut schema = EntitySchema::<Single, String, String>::new("Pump".to_string(), vec![]);
+    schema.fields.insert("Name".to_string(), FieldSchema::String {
+        field_type: "Name".to_string(),
+        default_value: String::new(),
+        rank: 0,
+        storage_scope: StorageScope::Configuration,
+        validator: None,
+        metadata: Default::default(),
+    });
+    store.update_schema(schema)?;
+    store.create_entity(store.get_entity_type("Pump")?, None, "P1")?;
+    let stats = store.stats();
+
+    let (address, server) = serve_once(StatsResponse { stats: stats.to_map() }.encode().to_bytes())?;
+    let proxy = StoreProxy::connect(&address)?;
+    assert_eq!(StoreTrait::stats(&proxy)?, stats);
+
+    drop(proxy);
+    let request = server.join().expect("server thread");
+    let (value, _) = crate::data::resp::RespValue::from_bytes(&request)?;
+    StatsCommand::decode(value)?;
+    Ok(())
+}
+
+#[test]
+fn test_slowlog_round_trip() -> Result<()> {
+    use crate::data::resp::{RespDecode, RespEncode, RespFromBytes, RespToBytes, SlowLogCommand, SlowLogResponse};
+
+    let entries = vec![SlowLogEntry {
+        id: 7,
+        timestamp: secs_to_timestamp(1_700_000_000),
+        duration_micros: 25_000,
+        args: vec!["FIND".to_string(), "Object".to_string()],
+        client: Some("10.0.0.5:4000".to_string()),
+    }];
+    let (address, server) = serve_once(SlowLogResponse::from_entries(&entries)?.encode().to_bytes())?;
+
+    let proxy = StoreProxy::connect(&address)?;
+    assert_eq!(proxy.slowlog_get(Some(10))?, entries);
+
+    drop(proxy);
+    let request = server.join().expect("server thread");
+    let (value, _) = crate::data::resp::RespValue::from_bytes(&request)?;
+    let command = SlowLogCommand::decode(value)?;
+    assert_eq!(command.action, SlowLogAction::Get);
+    assert_eq!(command.count, Some(10));
+    Ok(())
+}
+
+#[test]
+fn test_store_proxy_close_sends_quit() -> Result<()> {
+    use crate::data::resp::{QuitCommand, RespDecode, RespFromBytes};
+
+    let (address, server) = serve_script(vec![b"+OK\r\n".to_vec()])?;
+    StoreProxy::connect(&address)?.close()?;
+
+    let request = server.join().expect("server thread");
+    let (value, _) = crate::data::resp::RespValue::from_bytes(&request)?;
+    QuitCommand::decode(value)?;
+    Ok(())
+}
+
+#[test]
+fn test_schema_validator_and_merge_policy_survive_store_proxy() -> Result<()> {
+    use crate::data::resp::{RespDecode, RespFromBytes, UpdateSchemaCommand};
+    use crate::testing::SchemaBuilder;
+
+    let schema = SchemaBuilder::object("Pump")
+        .field(FieldSchema::Int {
+            field_type: "Speed".to_string(),
+            default_value: 0,
+            rank: 3,
+            storage_scope: StorageScope::Configuration,
+            merge_policy: MergePolicy::Counter,
+            validator: Some("new >= 0".to_string()),
+            metadata: Default::default(),
+        })
+        .build();
+
+    let (address, server) = serve_once(b"+OK\r\n".to_vec())?;
+    let proxy = StoreProxy::connect(&address)?;
+    proxy.update_schema(schema)?;
+    drop(proxy);
+
+    // Apply the schema the server received to a store
+    let request = server.join().expect("server thread");
+    let (value, _) = crate::data::resp::RespValue::from_bytes(&request)?;
+    let mut store = Store::new();
+    let received = UpdateSchemaCommand::decode(value)?.schema.to_entity_schema(&store)?;
+    store.update_schema(received)?;
+
+    let ft_speed = store.get_field_type("Speed")?;
+    let et_pump = store.get_entity_type("Pump")?;
+    assert_eq!(store.get_complete_entity_schema(et_pump)?.fields[&ft_speed].merge_policy(), MergePolicy::Counter);
+
+    let pump = store.create_entity(et_pump, None, "P1")?;
+    store.write(pump, &[ft_speed], Value::Int(10), None, None, None, None)?;
+    assert!(matches!(
+        store.write(pump, &[ft_speed], Value::Int(-1), None, None, None, None),
+        Err(Error::InvalidFieldValue(..))
+    ));
+    Ok(())
+}
+
+#[test]
+fn test_proxy_sends_binary_ids_after_codec_negotiation() -> Result<()> {
+    use crate::data::codec::Codec;
+    use crate::data::resp::{BooleanResponse, RespEncode, RespToBytes, BINARY_ID_MARKER};
+
+    let exists = BooleanResponse { result: true }.encode().to_bytes();
+    let (address, server) = serve_script(vec![exists.clone(), b"+OK\r\n".to_vec(), exists])?;
+    let proxy = StoreProxy::connect(&address)?;
+    let entity_id = EntityId::new(EntityType(3), 0x3030_3030);
+
+    assert!(proxy.entity_exists(entity_id));
+    assert!(proxy.negotiate_codec(Codec::Rkyv).is_err());
+    assert_eq!(proxy.codec(), Codec::Resp);
+    proxy.negotiate_codec(Codec::RespBinaryIds)?;
+    assert_eq!(proxy.codec(), Codec::RespBinaryIds);
+    assert!(proxy.entity_exists(entity_id));
+    drop(proxy);
+
+    let received = server.join().expect("server thread");
+    let decimal = format!(":{}\r\n", entity_id.0).into_bytes();
+    let mut binary = vec![BINARY_ID_MARKER];
+    binary.extend_from_slice(&entity_id.0.to_le_bytes());
+    let codec_at = received.windows(15).position(|window| window == b"resp-binary-ids").expect("CODEC request");
+    let (before, after) = received.split_at(codec_at);
+    assert!(before.windows(decimal.len()).any(|window| window == decimal.as_slice()));
+    assert!(!before.windows(binary.len()).any(|window| window == binary.as_slice()));
+    assert!(after.windows(binary.len()).any(|window| window == binary.as_slice()));
+    Ok(())
+}
@@ -0,0 +1,314 @@
+use crate::*;
+use crate::testing::SchemaBuilder;
+
+#[allow(dead_code)]
+fn create_test_store() -> Result<Store> {
+    let mut store = Store::new();
+    SchemaBuilder::object("Object")
+        .merge_policy("Children", MergePolicy::SetUnion)
+        .int("Counter", 0)
+        .runtime("Counter")
+        .merge_policy("Counter", MergePolicy::Counter)
+        .apply(&mut store)?;
+
+    Ok(store)
+}
+
+#[test]
+fn test_handshake_requests_sync_from_older_peer() {
+    let mut older = PeerReplicator::new("machine-a", 100);
+    let mut newer = PeerReplicator::new("machine-b", 200);
+
+    let (response, sync_request) = newer.handle_handshake(&older.handshake());
+    assert!(sync_request.is_some());
+    let response = response.expect("handshake response");
+    assert!(response.is_response);
+
+    let (response, sync_request) = older.handle_handshake(&response);
+    assert!(response.is_none());
+    assert!(sync_request.is_none());
+
+    assert!(older.peer("machine-b").unwrap().handshake_complete);
+    assert!(newer.peer("machine-a").unwrap().handshake_complete);
+}
+
+#[test]
+fn test_full_sync_and_streamed_writes() -> Result<()> {
+    let mut source = create_test_store()?;
+    let et_object = source.get_entity_type("Object")?;
+    let ft_counter = source.get_field_type("Counter")?;
+    let object_id = source.create_entity(et_object, None, "Pump")?;
+    source.write_queue.clear();
+
+    let source_replicator = PeerReplicator::new("machine-a", 100);
+    let mut target_replicator = PeerReplicator::new("machine-b", 200);
+    target_replicator.handle_handshake(&source_replicator.handshake());
+
+    let mut target = Store::new();
+    let response = source_replicator.full_sync_response(&source)?;
+    target_replicator.apply_full_sync("machine-a", &mut target, &response)?;
+    assert!(target.entity_exists(object_id));
+    assert!(target_replicator.peer("machine-a").unwrap().synced);
+
+    source.write(object_id, &[ft_counter], Value::Int(5), None, None, None, Some(AdjustBehavior::Add))?;
+    let batch = source_replicator.drain_sync_writes(&mut source)?.expect("pending writes");
+    assert!(source.write_queue.is_empty());
+
+    target_replicator.apply_sync_writes("machine-a", &mut target, &batch)?;
+    let (value, _, _) = target.read(object_id, &[ft_counter])?;
+    assert_eq!(value, Value::Int(5));
+
+    // Replicated writes are not queued again for replication
+    assert!(!target_replicator.has_unsent_writes(&target));
+
+    Ok(())
+}
+
+#[test]
+fn test_replication_leaves_writes_for_other_readers() -> Result<()> {
+    let mut local = create_test_store()?;
+    let et_object = local.get_entity_type("Object")?;
+    let ft_counter = local.get_field_type("Counter")?;
+    let object_id = local.create_entity(et_object, None, "Pump")?;
+    let mut remote = Store::new();
+    remote.restore_snapshot(local.take_snapshot());
+    local.write_queue.clear();
+
+    let mut replicator = PeerReplicator::new("machine-a", 100);
+    replicator.attach(&mut local);
+    local.add_write_reader("wal");
+
+    local.write(object_id, &[ft_counter], Value::Int(1), None, None, None, Some(AdjustBehavior::Add))?;
+    replicator.drain_sync_writes(&mut local)?.expect("pending writes");
+    assert_eq!(local.unread_writes("wal").count(), 1);
+
+    // A local write made before a peer's batch arrives is still sent,
+    // the peer's own writes are not sent back
+    local.write(object_id, &[ft_counter], Value::Int(2), None, None, None, Some(AdjustBehavior::Add))?;
+    remote.write(object_id, &[ft_counter], Value::Int(3), None, None, None, Some(AdjustBehavior::Add))?;
+    let batch = PeerReplicator::new("machine-b", 200).drain_sync_writes(&mut remote)?.expect("pending writes");
+    replicator.apply_sync_writes("machine-b", &mut local, &batch)?;
+    local.write(object_id, &[ft_counter], Value::Int(4), None, None, None, Some(AdjustBehavior::Add))?;
+
+    let batch = replicator.drain_sync_writes(&mut local)?.expect("pending writes");
+    let sent: Vec<WriteInfo> = serde_json::from_str(&batch.requests_data).unwrap();
+    let deltas: Vec<_> = sent.iter().map(|write| match write {
+        WriteInfo::FieldUpdate { delta, .. } => delta.clone(),
+        _ => None,
+    }).collect();
+    assert_eq!(deltas, vec![Some(Value::Int(2)), Some(Value::Int(4))]);
+    assert!(!replicator.has_unsent_writes(&local));
+
+    // The peer's write is queued for persistence with the local ones
+    assert_eq!(local.unread_writes("wal").count(), 4);
+    let seq = local.write_seq();
+    local.mark_writes_read("wal", seq);
+    assert!(local.write_queue.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn test_older_remote_write_is_discarded() -> Result<()> {
+    let mut local = create_test_store()?;
+    let et_object = local.get_entity_type("Object")?;
+    let ft_counter = local.get_field_type("Counter")?;
+    let object_id = local.create_entity(et_object, None, "Pump")?;
+
+    let mut remote = Store::new();
+    remote.restore_snapshot(local.take_snapshot());
+
+    let stale_time = now();
+    remote.write(object_id, &[ft_counter], Value::Int(1), None, Some(stale_time), None, None)?;
+    local.write(object_id, &[ft_counter], Value::Int(2), None, None, None, None)?;
+
+    let mut replicator = PeerReplicator::new("machine-a", 100);
+    let batch = replicator.drain_sync_writes(&mut remote)?.expect("pending writes");
+    replicator.apply_sync_writes("machine-b", &mut local, &batch)?;
+
+    let (value, _, _) = local.read(object_id, &[ft_counter])?;
+    assert_eq!(value, Value::Int(2));
+
+    Ok(())
+}
+
+#[test]
+fn test_counter_and_set_union_merge_policies() -> Result<()> {
+    let mut store_a = create_test_store()?;
+    let et_object = store_a.get_entity_type("Object")?;
+    let ft_counter = store_a.get_field_type("Counter")?;
+    let ft_children = store_a.get_field_type("Children")?;
+    let parent_id = store_a.create_entity(et_object, None, "Parent")?;
+    let child_a = store_a.create_entity(et_object, None, "ChildA")?;
+    let child_b = store_a.create_entity(et_object, None, "ChildB")?;
+    store_a.write_queue.clear();
+
+    let mut store_b = Store::new();
+    store_b.restore_snapshot(store_a.take_snapshot());
+
+    // Both sides diverge while partitioned
+    store_a.write(parent_id, &[ft_counter], Value::Int(2), None, None, None, Some(AdjustBehavior::Add))?;
+    store_b.write(parent_id, &[ft_counter], Value::Int(3), None, None, None, Some(AdjustBehavior::Add))?;
+    store_a.write(parent_id, &[ft_children], Value::EntityList(vec![child_a]), None, None, None, None)?;
+    store_b.write(parent_id, &[ft_children], Value::EntityList(vec![child_b]), None, None, None, None)?;
+
+    let mut replicator = PeerReplicator::new("machine-a", 100);
+    let batch_a = replicator.drain_sync_writes(&mut store_a)?.expect("pending writes");
+    let batch_b = replicator.drain_sync_writes(&mut store_b)?.expect("pending writes");
+    replicator.apply_sync_writes("machine-b", &mut store_a, &batch_b)?;
+    replicator.apply_sync_writes("machine-a", &mut store_b, &batch_a)?;
+
+    for store in [&store_a, &store_b] {
+        let (counter, _, _) = store.read(parent_id, &[ft_counter])?;
+        assert_eq!(counter, Value::Int(5));
+
+        let (children, _, _) = store.read(parent_id, &[ft_children])?;
+        let mut children = children.as_entity_list().cloned().unwrap_or_default();
+        children.sort();
+        assert_eq!(children, vec![child_a, child_b]);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_failed_sync_batch_applies_nothing() -> Result<()> {
+    let mut local = create_test_store()?;
+    let et_object = local.get_entity_type("Object")?;
+    let ft_counter = local.get_field_type("Counter")?;
+    let ft_name = local.get_field_type("Name")?;
+    let object_id = local.create_entity(et_object, None, "Pump")?;
+    local.write_queue.clear();
+
+    let mut remote = Store::new();
+    remote.restore_snapshot(local.take_snapshot());
+    remote.write(object_id, &[ft_counter], Value::Int(5), None, None, None, Some(AdjustBehavior::Add))?;
+    remote.write(object_id, &[ft_name], Value::String("Bad".to_string()), None, None, None, None)?;
+
+    // The local schema rejects the second write of the batch
+    let strict_name = |validator: Option<&str>| FieldSchema::String {
+        field_type: "Name".to_string(),
+        default_value: "".to_string(),
+        rank: 0,
+        storage_scope: StorageScope::Configuration,
+        validator: validator.map(str::to_string),
+        metadata: Default::default(),
+    };
+    let schema = |validator| {
+        SchemaBuilder::object("Object")
+            .field(strict_name(validator))
+            .merge_policy("Children", MergePolicy::SetUnion)
+            .int("Counter", 0)
+            .runtime("Counter")
+            .merge_policy("Counter", MergePolicy::Counter)
+            .build()
+    };
+    local.update_schema(schema(Some("new != 'Bad'")))?;
+    local.write_queue.clear();
+
+    let mut replicator = PeerReplicator::new("machine-a", 100);
+    let batch = replicator.drain_sync_writes(&mut remote)?.expect("pending writes");
+    assert!(replicator.apply_sync_writes("machine-b", &mut local, &batch).is_err());
+    assert_eq!(local.read(object_id, &[ft_counter])?.0, Value::Int(0));
+    assert!(local.write_queue.is_empty());
+
+    // Resending the batch once it can be applied counts the delta once
+    local.update_schema(schema(None))?;
+    replicator.apply_sync_writes("machine-b", &mut local, &batch)?;
+    assert_eq!(local.read(object_id, &[ft_counter])?.0, Value::Int(5));
+    assert_eq!(local.read(object_id, &[ft_name])?.0, Value::String("Bad".to_string()));
+
+    // A batch delivered again is not counted again
+    replicator.apply_sync_writes("machine-b", &mut local, &batch)?;
+    assert_eq!(local.read(object_id, &[ft_counter])?.0, Value::Int(5));
+
+    Ok(())
+}
+
+#[test]
+fn test_hybrid_clock_orders_causally() {
+    let start = epoch() + Duration::seconds(100);
+    let mut clock = HybridClock::new();
+    let first = clock.now_at(start);
+    assert_eq!(first, HlcTimestamp::from_timestamp(start));
+
+    // The wall clock steps back, the hybrid clock doesn't
+    let second = clock.now_at(start - Duration::seconds(1));
+    assert_eq!((second.wall, second.logical), (first.wall, 1));
+
+    // A reading from a machine whose clock is ahead moves ours past it
+    let remote = HlcTimestamp::from_timestamp(start + Duration::seconds(5));
+    let received = clock.update_at(remote, start);
+    assert_eq!((received.wall, received.logical), (remote.wall, 1));
+    assert!(clock.now_at(start) > received);
+    assert_eq!(clock.now_at(start + Duration::seconds(6)), HlcTimestamp::from_timestamp(start + Duration::seconds(6)));
+}
+
+#[test]
+fn test_hlc_keeps_writes_in_causal_order_across_skew() -> Result<()> {
+    let mut local = create_test_store()?;
+    let et_object = local.get_entity_type("Object")?;
+    let ft_name = local.get_field_type("Name")?;
+    let object_id = local.create_entity(et_object, None, "Pump")?;
+    let mut remote = Store::new();
+    remote.restore_snapshot(local.take_snapshot());
+    local.write_queue.clear();
+
+    // The remote machine's clock runs a minute ahead
+    local.enable_hlc();
+    remote.enable_hlc().update(HlcTimestamp::from_timestamp(now() + Duration::seconds(60)));
+
+    let mut local_replicator = PeerReplicator::new("machine-a", 100);
+    let mut remote_replicator = PeerReplicator::new("machine-b", 200);
+    remote.write(object_id, &[ft_name], Value::from_string("remote".to_string()), None, None, None, None)?;
+    let batch = remote_replicator.drain_sync_writes(&mut remote)?.expect("pending writes");
+    local_replicator.apply_sync_writes("machine-b", &mut local, &batch)?;
+
+    // Written after seeing the remote write, so it must win everywhere
+    local.write(object_id, &[ft_name], Value::from_string("local".to_string()), None, None, None, None)?;
+    let remote_hlc = remote.hlc().unwrap().last();
+    let Some(WriteInfo::FieldUpdate { hlc: Some(local_hlc), write_time, .. }) = local.write_queue.back().cloned() else {
+        panic!("expected a stamped field update");
+    };
+    assert!(local_hlc > remote_hlc);
+    assert_eq!(write_time, Some(local_hlc.to_timestamp()));
+
+    let batch = local_replicator.drain_sync_writes(&mut local)?.expect("pending writes");
+    remote_replicator.apply_sync_writes("machine-a", &mut remote, &batch)?;
+    assert_eq!(remote.read(object_id, &[ft_name])?.0, Value::from_string("local".to_string()));
+    assert!(remote.hlc().unwrap().last() > local_hlc);
+    Ok(())
+}
+
+#[test]
+fn test_handshake_reports_clock_skew() -> Result<()> {
+    use crate::data::resp::{OwnedRespValue, PeerHandshakeCommand, RespDecode, RespEncode, RespFromBytes, RespToBytes, RespValue};
+
+    let mut local = PeerReplicator::new("machine-a", 100);
+    let remote = PeerReplicator::new("machine-b", 200);
+    let mut handshake = remote.handshake();
+    local.handle_handshake(&handshake);
+    assert!(local.peer("machine-b").unwrap().clock_skew.is_some());
+    assert!(local.skewed_peers().next().is_none());
+
+    handshake.clock = HlcTimestamp::from_timestamp(now() + Duration::seconds(5));
+    local.handle_handshake(&handshake);
+    let skewed: Vec<_> = local.skewed_peers().map(|peer| peer.machine_id.clone()).collect();
+    assert_eq!(skewed, vec!["machine-b".to_string()]);
+    assert!(local.peer("machine-b").unwrap().clock_skew.unwrap() > Duration::seconds(4));
+
+    // Older peers don't send their clock
+    let OwnedRespValue::Array(mut elements) = remote.handshake().encode() else {
+        panic!("handshake should encode as an array");
+    };
+    elements.pop();
+    let bytes = OwnedRespValue::Array(elements).to_bytes();
+    let (value, _) = RespValue::from_bytes(&bytes)?;
+    let old_handshake = PeerHandshakeCommand::decode(value)?;
+    assert_eq!(old_handshake.clock, HlcTimestamp::default());
+
+    let mut fresh = PeerReplicator::new("machine-c", 300);
+    fresh.handle_handshake(&old_handshake);
+    assert_eq!(fresh.peer("machine-b").unwrap().clock_skew, None);
+    Ok(())
+}
@@ -0,0 +1,95 @@
+#[allow(unused_imports)]
+use crate::*;
+#[allow(unused_imports)]
+use std::time::Duration;
+
+#[allow(dead_code)]
+fn no_wait() -> RetryPolicy {
+    RetryPolicy::new().with_backoff(Duration::ZERO, Duration::ZERO)
+}
+
+#[test]
+fn test_error_classification() {
+    let pump = EntityId::new(EntityType(1), 7);
+    assert!(Error::ConnectionLost.is_retryable());
+    assert!(Error::NotLeader(pump).is_retryable());
+    assert!(Error::ProtocolError(crate::data::resp::ProtocolError::ShuttingDown).is_retryable());
+    assert!(!Error::EntityNotFound(pump).is_retryable());
+    assert!(!Error::StoreProxyError("Server error: ?".to_string()).is_retryable());
+
+    assert!(Error::EntityAlreadyExists(pump).is_conflict());
+    assert!(Error::StaleFencingToken(pump, 1, 2).is_conflict());
+    assert!(!Error::RateLimited("hmi".to_string()).is_conflict());
+
+    assert!(Error::EntityNotFound(pump).is_not_found());
+    assert!(Error::FieldTypeStrNotFound("Speed".to_string()).is_not_found());
+    assert!(!Error::InvalidCredentials.is_not_found());
+}
+
+#[test]
+fn test_retry_policy_run() {
+    // Succeeds on the third attempt
+    let mut attempts = 0;
+    let result = no_wait().run(|| {
+        attempts += 1;
+        if attempts < 3 { Err(Error::ConnectionLost) } else { Ok(attempts) }
+    });
+    assert!(matches!(result, Ok(3)));
+
+    // Fatal errors are returned at once
+    let mut attempts = 0;
+    let result: Result<()> = no_wait().run(|| {
+        attempts += 1;
+        Err(Error::InvalidName)
+    });
+    assert!(matches!(result, Err(Error::InvalidName)));
+    assert_eq!(attempts, 1);
+
+    // The last error is returned once attempts run out
+    let mut attempts = 0;
+    let result: Result<()> = no_wait().with_max_attempts(4).run(|| {
+        attempts += 1;
+        Err(Error::RateLimited(attempts.to_string()))
+    });
+    assert!(matches!(result, Err(Error::RateLimited(client)) if client == "4"));
+
+    // A filter replaces the default classification
+    let policy = no_wait().retry_if(|error| error.is_conflict());
+    let mut attempts = 0;
+    let result: Result<()> = policy.run(|| {
+        attempts += 1;
+        Err(Error::ConnectionLost)
+    });
+    assert!(result.is_err());
+    assert_eq!(attempts, 1);
+    assert!(policy.should_retry(&Error::SubjectAlreadyExists, 1));
+    assert!(!policy.should_retry(&Error::SubjectAlreadyExists, policy.max_attempts()));
+}
+
+#[test]
+fn test_retry_policy_backoff() {
+    let policy = RetryPolicy::new().with_backoff(Duration::from_millis(100), Duration::from_secs(1)).with_jitter(0.0);
+    assert_eq!(policy.backoff(1), Duration::from_millis(100));
+    assert_eq!(policy.backoff(3), Duration::from_millis(400));
+    assert_eq!(policy.backoff(5), Duration::from_secs(1));
+    assert_eq!(policy.backoff(u32::MAX), Duration::from_secs(1));
+
+    let policy = policy.with_jitter(0.5);
+    for _ in 0..100 {
+        let backoff = policy.backoff(2);
+        assert!(backoff > Duration::from_millis(100) && backoff <= Duration::from_millis(200), "{:?}", backoff);
+    }
+}
+
+#[test]
+fn test_retry_policy_run_async() -> Result<()> {
+    let runtime = tokio::runtime::Runtime::new().map_err(|e| Error::ExecutionError(e.to_string()))?;
+    let attempts = std::cell::Cell::new(0);
+    let result = runtime.block_on(no_wait().run_async(|| {
+        attempts.set(attempts.get() + 1);
+        async { if attempts.get() < 2 { Err(Error::Timeout(Duration::from_millis(5))) } else { Ok("done") } }
+    }));
+    assert!(matches!(result, Ok("done")));
+    assert_eq!(attempts.get(), 2);
+    Ok(())
+}
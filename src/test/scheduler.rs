@@ -0,0 +1,111 @@
+use crate::*;
+use crate::testing::SchemaBuilder;
+use time::format_description::well_known::Rfc3339;
+
+#[allow(dead_code)]
+fn utc(s: &str) -> Timestamp {
+    Timestamp::parse(s, &Rfc3339).unwrap()
+}
+
+#[allow(dead_code)]
+fn create_test_store() -> Result<Store> {
+    let mut store = Store::new();
+    SchemaBuilder::object("ScheduledTask")
+        .string("Schedule", "")
+        .string("Action", "")
+        .string("TargetField", "")
+        .timestamp("LastRun")
+        .runtime("LastRun")
+        .choice("MissedRunPolicy", &["Skip", "RunOnce", "RunAll"])
+        .int("RunCount", 0)
+        .runtime("RunCount")
+        .apply(&mut store)?;
+
+    Ok(store)
+}
+
+#[test]
+fn test_cron_schedule_next_after() -> Result<()> {
+    let every_15_minutes = app::CronSchedule::parse("*/15 * * * *")?;
+    assert_eq!(
+        every_15_minutes.next_after(utc("2024-03-10T10:07:30Z")),
+        Some(utc("2024-03-10T10:15:00Z"))
+    );
+    // Occurrences are strictly after the given time
+    assert_eq!(
+        every_15_minutes.next_after(utc("2024-03-10T10:15:00Z")),
+        Some(utc("2024-03-10T10:30:00Z"))
+    );
+
+    let weekdays_at_nine = app::CronSchedule::parse("0 9 * * 1-5")?;
+    // 2024-03-09 is a Saturday
+    assert_eq!(
+        weekdays_at_nine.next_after(utc("2024-03-09T12:00:00Z")),
+        Some(utc("2024-03-11T09:00:00Z"))
+    );
+
+    let new_year = app::CronSchedule::parse("30 0 1 1 *")?;
+    assert_eq!(
+        new_year.next_after(utc("2024-03-09T12:00:00Z")),
+        Some(utc("2025-01-01T00:30:00Z"))
+    );
+
+    assert!(app::CronSchedule::parse("* * *").is_err());
+    assert!(app::CronSchedule::parse("61 * * * *").is_err());
+    assert!(app::CronSchedule::parse("*/0 * * * *").is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_scheduler_runs_due_tasks_with_catch_up_policy() -> Result<()> {
+    let mut store = create_test_store()?;
+    let et_task = store.get_entity_type("ScheduledTask")?;
+    let ft_schedule = store.get_field_type("Schedule")?;
+    let ft_action = store.get_field_type("Action")?;
+    let ft_target_field = store.get_field_type("TargetField")?;
+    let ft_policy = store.get_field_type("MissedRunPolicy")?;
+    let ft_run_count = store.get_field_type("RunCount")?;
+    let ft_last_run = store.get_field_type("LastRun")?;
+
+    let mut tasks = Vec::new();
+    for (name, policy) in [("Skip", 0), ("RunOnce", 1), ("RunAll", 2)] {
+        let task_id = store.create_entity(et_task, None, name)?;
+        store.write(task_id, &[ft_schedule], Value::String("0 * * * *".to_string()), None, None, None, None)?;
+        store.write(task_id, &[ft_action], Value::String("RunCount + 1".to_string()), None, None, None, None)?;
+        store.write(task_id, &[ft_target_field], Value::String("RunCount".to_string()), None, None, None, None)?;
+        store.write(task_id, &[ft_policy], Value::Choice(policy), None, None, None, None)?;
+        tasks.push(task_id);
+    }
+
+    let mut scheduler = app::Scheduler::new();
+
+    // The first tick only records the starting point
+    scheduler.tick(&mut store, utc("2024-03-10T10:30:00Z"))?;
+    for task_id in &tasks {
+        let (last_run, _, _) = store.read(*task_id, &[ft_last_run])?;
+        assert_eq!(last_run, Value::Timestamp(utc("2024-03-10T10:30:00Z")));
+    }
+
+    // One occurrence is due: every policy runs it
+    scheduler.tick(&mut store, utc("2024-03-10T11:00:30Z"))?;
+    for task_id in &tasks {
+        let (count, _, _) = store.read(*task_id, &[ft_run_count])?;
+        assert_eq!(count, Value::Int(1));
+    }
+
+    // Three occurrences were missed
+    scheduler.tick(&mut store, utc("2024-03-10T14:10:00Z"))?;
+    let counts: Vec<Value> = tasks
+        .iter()
+        .map(|task_id| store.read(*task_id, &[ft_run_count]).map(|(count, _, _)| count))
+        .collect::<Result<_>>()?;
+    assert_eq!(counts, vec![Value::Int(1), Value::Int(2), Value::Int(4)]);
+
+    for task_id in &tasks {
+        let (last_run, _, _) = store.read(*task_id, &[ft_last_run])?;
+        assert_eq!(last_run, Value::Timestamp(utc("2024-03-10T14:00:00Z")));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,33 @@
+#[allow(unused_imports)]
+use crate::*;
+#[allow(unused_imports)]
+use crate::app::ServiceGroup;
+
+#[test]
+fn test_service_group_startup_order() -> Result<()> {
+    let mut group = ServiceGroup::new(1000);
+    group.add_service("alarm-engine", true, &["modbus-poller", "historian"])?;
+    group.add_service("historian", false, &[])?;
+    group.add_service("modbus-poller", true, &["historian"])?;
+    group.add_service("web-ui", false, &[])?;
+
+    // Dependencies first, otherwise in the order added
+    assert_eq!(group.startup_order()?, vec!["historian", "modbus-poller", "alarm-engine", "web-ui"]);
+    assert!(matches!(group.add_service("historian", true, &[]), Err(Error::InvalidRequest(_))));
+    assert!(!group.is_leader("alarm-engine"));
+    Ok(())
+}
+
+#[test]
+fn test_service_group_rejects_bad_dependencies() -> Result<()> {
+    let mut group = ServiceGroup::new(1000);
+    group.add_service("alarm-engine", true, &["modbus-poller"])?;
+    assert!(matches!(group.startup_order(), Err(Error::InvalidRequest(_))));
+
+    group.add_service("modbus-poller", true, &["alarm-engine"])?;
+    let Err(Error::InvalidRequest(message)) = group.startup_order() else {
+        panic!("a dependency cycle should be rejected");
+    };
+    assert!(message.contains("alarm-engine") && message.contains("modbus-poller"));
+    Ok(())
+}
@@ -0,0 +1,107 @@
+use crate::*;
+use crate::sim::{SimConfig, Simulation};
+use crate::testing::SchemaBuilder;
+
+/// A three node simulation with one replicated pump, returning its id and
+/// the Speed field
+fn pump_simulation(seed: u64, config: SimConfig) -> Result<(Simulation, EntityId, FieldType)> {
+    let mut sim = Simulation::new(seed, 3, config)?;
+    let et_pump = sim.update_schema(SchemaBuilder::object("Pump").int("Speed", 0).build())?;
+    let ft_speed = sim.store(0).get_field_type("Speed")?;
+    sim.run_for(Duration::milliseconds(100))?;
+    let pump = sim.store_mut(0).create_entity(et_pump, None, "P1")?;
+    sim.run_for(Duration::milliseconds(100))?;
+    Ok((sim, pump, ft_speed))
+}
+
+#[test]
+fn test_simulation_replays_from_seed() -> Result<()> {
+    let config = SimConfig::default()
+        .with_drop_rate(0.2)
+        .with_duplicate_rate(0.2)
+        .with_in_order(false);
+    let run = |seed: u64| -> Result<(Vec<String>, Vec<Value>)> {
+        let (mut sim, pump, ft_speed) = pump_simulation(seed, config.clone())?;
+        for i in 0..20 {
+            sim.write(i % 3, pump, &[ft_speed], Value::Int(i as i64))?;
+            sim.run_for(Duration::milliseconds(3))?;
+        }
+        sim.run_for(Duration::milliseconds(100))?;
+        let speeds = (0..3)
+            .map(|node| sim.store(node).read(pump, &[ft_speed]).map(|(value, _, _)| value).unwrap_or(Value::Int(-1)))
+            .collect();
+        Ok((sim.trace().to_vec(), speeds))
+    };
+
+    assert_eq!(run(7)?, run(7)?);
+    assert_ne!(run(7)?.0, run(8)?.0);
+    Ok(())
+}
+
+#[test]
+fn test_simulation_converges_on_a_lossless_network() -> Result<()> {
+    let (mut sim, pump, ft_speed) = pump_simulation(1, SimConfig::default())?;
+    assert!((0..3).all(|node| sim.store(node).entity_exists(pump)));
+
+    for node in 0..3 {
+        sim.write(node, pump, &[ft_speed], Value::Int(node as i64 + 10))?;
+        sim.run_for(Duration::milliseconds(2))?;
+    }
+    assert!(sim.run_until_quiet(Duration::seconds(1))?);
+    assert_eq!(sim.read_all(pump, &[ft_speed])?, vec![Value::Int(12); 3]);
+    Ok(())
+}
+
+#[test]
+fn test_simulation_partition_and_heal() -> Result<()> {
+    let (mut sim, pump, ft_speed) = pump_simulation(2, SimConfig::default())?;
+
+    sim.isolate(2);
+    sim.write(0, pump, &[ft_speed], Value::Int(1500))?;
+    assert!(sim.run_until_quiet(Duration::seconds(1))?);
+    assert_eq!(sim.read_all(pump, &[ft_speed])?, vec![Value::Int(1500), Value::Int(1500), Value::Int(0)]);
+    assert!(sim.replicator(2).peers().next().is_none());
+
+    // The isolated node rejoins and takes a full sync from the older nodes
+    sim.heal();
+    assert!(sim.run_until_quiet(Duration::seconds(1))?);
+    assert_eq!(sim.read_all(pump, &[ft_speed])?, vec![Value::Int(1500); 3]);
+    assert!(sim.replicator(2).peer("node-0").is_some_and(|peer| peer.synced));
+    Ok(())
+}
+
+#[test]
+fn test_simulation_clock_skew_decides_last_writer() -> Result<()> {
+    let (mut sim, pump, ft_speed) = pump_simulation(3, SimConfig::default())?;
+
+    // Node 1 writes last, but its clock runs five seconds behind, so the
+    // earlier write from node 0 wins everywhere
+    sim.set_skew(1, Duration::seconds(-5));
+    sim.write(0, pump, &[ft_speed], Value::Int(1))?;
+    sim.run_for(Duration::milliseconds(50))?;
+    sim.write(1, pump, &[ft_speed], Value::Int(2))?;
+    assert!(sim.run_until_quiet(Duration::seconds(1))?);
+    assert_eq!(sim.read_all(pump, &[ft_speed])?, vec![Value::Int(1); 3]);
+    Ok(())
+}
+
+#[test]
+fn test_simulation_stamps_stores_with_virtual_time() -> Result<()> {
+    let (mut sim, pump, ft_speed) = pump_simulation(4, SimConfig::default())?;
+
+    // The pump was created on node 0 once 100ms of virtual time had passed,
+    // and the other nodes stamped it when the creation reached them
+    let (_, created_at, _) = sim.store(0).read(pump, &[ft_speed])?;
+    assert_eq!(created_at, sim.clock().start() + Duration::milliseconds(100));
+    for node in 1..3 {
+        let (_, received_at, _) = sim.store(node).read(pump, &[ft_speed])?;
+        assert!(created_at < received_at && received_at < sim.clock().now());
+    }
+
+    // Local writes are stamped with the writing node's skewed clock
+    sim.set_skew(1, Duration::seconds(5));
+    sim.store_mut(1).write(pump, &[ft_speed], Value::Int(7), None, None, None, None)?;
+    let (_, write_time, _) = sim.store(1).read(pump, &[ft_speed])?;
+    assert_eq!(write_time, sim.clock().now() + Duration::seconds(5));
+    Ok(())
+}
@@ -0,0 +1,46 @@
+#[allow(unused_imports)]
+use crate::*;
+#[allow(unused_imports)]
+use crate::testing::SchemaBuilder;
+
+#[allow(dead_code)]
+fn pump_store() -> Result<(Store, EntityId, FieldType)> {
+    let mut store = Store::new();
+    let et_pump = SchemaBuilder::object("Pump").int("Speed", 0).apply(&mut store)?;
+    let pump = store.create_entity(et_pump, None, "P1")?;
+    let ft_speed = store.get_field_type("Speed")?;
+    Ok((store, pump, ft_speed))
+}
+
+#[test]
+fn test_directory_sink_restores_by_time() -> Result<()> {
+    let (mut store, pump, ft_speed) = pump_store()?;
+    let dir = std::env::temp_dir().join(format!("qlib-sink-{}", uuid::Uuid::new_v4()));
+    let mut sink = DirectorySink::new(&dir);
+    assert!(list_snapshots(&mut sink)?.is_empty());
+    assert!(matches!(restore_from_sink(&mut store, &mut sink, None), Err(Error::InvalidRequest(_))));
+
+    store.write(pump, &[ft_speed], Value::Int(1000), None, None, None, None)?;
+    let first = upload_snapshot(&mut sink, &store.take_snapshot())?;
+    store.write(pump, &[ft_speed], Value::Int(2000), None, None, None, None)?;
+    let second = upload_snapshot(&mut sink, &store.take_snapshot())?;
+    // Other files in the directory aren't snapshots
+    sink.put("README", b"off-box copies of the plant store")?;
+
+    assert_eq!(list_snapshots(&mut sink)?, vec![first.clone(), second.clone()]);
+    assert_eq!(data::snapshot_sink::snapshot_key_time(&first.key), Some(first.created));
+    assert!(matches!(sink.put("../escape", b""), Err(Error::InvalidRequest(_))));
+
+    let mut restored = Store::new();
+    assert_eq!(restore_from_sink(&mut restored, &mut sink, Some(first.created))?, first);
+    assert_eq!(restored.read(pump, &[ft_speed])?.0, Value::Int(1000));
+    assert_eq!(restore_from_sink(&mut restored, &mut sink, None)?, second);
+    assert_eq!(restored.read(pump, &[ft_speed])?.0, Value::Int(2000));
+    assert!(restore_from_sink(&mut restored, &mut sink, Some(first.created - Duration::seconds(1))).is_err());
+
+    sink.delete(&second.key)?;
+    assert_eq!(list_snapshots(&mut sink)?, vec![first]);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+    Ok(())
+}
@@ -1,5 +1,7 @@
 use crate::*;
 use crate::data::StorageScope;
+#[allow(unused_imports)]
+use crate::data::limits;
 
 // Helper to create an entity schema with basic fields
 fn create_entity_schema_with_name(store: &mut Store, entity_type_name: &str) -> Result<()> {
@@ -13,6 +15,8 @@ fn create_entity_schema_with_name(store: &mut Store, entity_type_name: &str) ->
             default_value: "".to_string(),
             rank: 1,
             storage_scope: StorageScope::Configuration,
+            validator: None,
+            metadata: Default::default(),
         }
     );
 
@@ -23,6 +27,8 @@ fn create_entity_schema_with_name(store: &mut Store, entity_type_name: &str) ->
             default_value: None,
             rank: 2,
             storage_scope: StorageScope::Configuration,
+            validator: None,
+            metadata: Default::default(),
         }
     );
 
@@ -33,6 +39,9 @@ fn create_entity_schema_with_name(store: &mut Store, entity_type_name: &str) ->
             default_value: vec![],
             rank: 3,
             storage_scope: StorageScope::Configuration,
+            merge_policy: MergePolicy::LastWriterWins,
+            validator: None,
+            metadata: Default::default(),
         }
     );
 
@@ -270,6 +279,8 @@ fn test_find_entities_comprehensive() -> Result<()> {
             default_value: String::new(),
             rank: 0,
             storage_scope: StorageScope::Runtime,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     user_schema.fields.insert(
@@ -279,6 +290,8 @@ fn test_find_entities_comprehensive() -> Result<()> {
             default_value: None,
             rank: 1,
             storage_scope: StorageScope::Configuration,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     user_schema.fields.insert(
@@ -288,6 +301,9 @@ fn test_find_entities_comprehensive() -> Result<()> {
             default_value: Vec::new(),
             rank: 2,
             storage_scope: StorageScope::Configuration,
+            merge_policy: MergePolicy::LastWriterWins,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     store.update_schema(user_schema)?;
@@ -362,6 +378,8 @@ fn test_find_entities_pagination() -> Result<()> {
             default_value: String::new(),
             rank: 0,
             storage_scope: StorageScope::Runtime,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     user_schema.fields.insert(
@@ -371,6 +389,8 @@ fn test_find_entities_pagination() -> Result<()> {
             default_value: None,
             rank: 1,
             storage_scope: StorageScope::Configuration,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     user_schema.fields.insert(
@@ -380,6 +400,9 @@ fn test_find_entities_pagination() -> Result<()> {
             default_value: Vec::new(),
             rank: 2,
             storage_scope: StorageScope::Configuration,
+            merge_policy: MergePolicy::LastWriterWins,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     store.update_schema(user_schema)?;
@@ -457,6 +480,8 @@ fn test_find_entities_inheritance() -> Result<()> {
             default_value: String::new(),
             rank: 0,
             storage_scope: StorageScope::Runtime,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     animal_schema.fields.insert(
@@ -466,6 +491,8 @@ fn test_find_entities_inheritance() -> Result<()> {
             default_value: None,
             rank: 1,
             storage_scope: StorageScope::Runtime,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     animal_schema.fields.insert(
@@ -475,6 +502,9 @@ fn test_find_entities_inheritance() -> Result<()> {
             default_value: vec![],
             rank: 2,
             storage_scope: StorageScope::Runtime,
+            merge_policy: MergePolicy::LastWriterWins,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     store.update_schema(animal_schema)?;
@@ -488,6 +518,8 @@ fn test_find_entities_inheritance() -> Result<()> {
             default_value: String::new(),
             rank: 1,
             storage_scope: StorageScope::Runtime,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     store.update_schema(mammal_schema)?;
@@ -501,6 +533,8 @@ fn test_find_entities_inheritance() -> Result<()> {
             default_value: String::new(),
             rank: 2,
             storage_scope: StorageScope::Runtime,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     store.update_schema(dog_schema)?;
@@ -514,6 +548,8 @@ fn test_find_entities_inheritance() -> Result<()> {
             default_value: String::new(),
             rank: 2,
             storage_scope: StorageScope::Runtime,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     store.update_schema(cat_schema)?;
@@ -527,6 +563,8 @@ fn test_find_entities_inheritance() -> Result<()> {
             default_value: true,
             rank: 1,
             storage_scope: StorageScope::Runtime,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     store.update_schema(bird_schema)?;
@@ -625,6 +663,8 @@ fn test_find_entities_cel_edge_cases() -> Result<()> {
             default_value: String::new(),
             rank: 0,
             storage_scope: StorageScope::Runtime,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     user_schema.fields.insert(
@@ -634,6 +674,8 @@ fn test_find_entities_cel_edge_cases() -> Result<()> {
             default_value: None,
             rank: 1,
             storage_scope: StorageScope::Configuration,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     user_schema.fields.insert(
@@ -643,6 +685,9 @@ fn test_find_entities_cel_edge_cases() -> Result<()> {
             default_value: Vec::new(),
             rank: 2,
             storage_scope: StorageScope::Configuration,
+            merge_policy: MergePolicy::LastWriterWins,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     store.update_schema(user_schema)?;
@@ -685,6 +730,8 @@ fn test_complete_entity_schema_caching() -> Result<()> {
             default_value: String::new(),
             rank: 0,
             storage_scope: StorageScope::Runtime,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     base_schema.fields.insert(
@@ -694,6 +741,8 @@ fn test_complete_entity_schema_caching() -> Result<()> {
             default_value: None,
             rank: 1,
             storage_scope: StorageScope::Configuration,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     base_schema.fields.insert(
@@ -703,6 +752,9 @@ fn test_complete_entity_schema_caching() -> Result<()> {
             default_value: Vec::new(),
             rank: 2,
             storage_scope: StorageScope::Configuration,
+            merge_policy: MergePolicy::LastWriterWins,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     base_schema.fields.insert(
@@ -712,6 +764,8 @@ fn test_complete_entity_schema_caching() -> Result<()> {
             default_value: "base_default".to_string(),
             rank: 3,
             storage_scope: StorageScope::Runtime,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     store.update_schema(base_schema)?;
@@ -725,6 +779,8 @@ fn test_complete_entity_schema_caching() -> Result<()> {
             default_value: "derived_default".to_string(),
             rank: 1,
             storage_scope: StorageScope::Runtime,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     store.update_schema(derived_schema)?;
@@ -755,6 +811,8 @@ fn test_complete_entity_schema_caching() -> Result<()> {
             default_value: String::new(),
             rank: 0,
             storage_scope: StorageScope::Runtime,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     updated_base_schema.fields.insert(
@@ -764,6 +822,8 @@ fn test_complete_entity_schema_caching() -> Result<()> {
             default_value: None,
             rank: 1,
             storage_scope: StorageScope::Configuration,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     updated_base_schema.fields.insert(
@@ -773,6 +833,9 @@ fn test_complete_entity_schema_caching() -> Result<()> {
             default_value: Vec::new(),
             rank: 2,
             storage_scope: StorageScope::Configuration,
+            merge_policy: MergePolicy::LastWriterWins,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     updated_base_schema.fields.insert(
@@ -782,6 +845,8 @@ fn test_complete_entity_schema_caching() -> Result<()> {
             default_value: "updated_base_default".to_string(),
             rank: 3,
             storage_scope: StorageScope::Runtime,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     updated_base_schema.fields.insert(
@@ -791,6 +856,8 @@ fn test_complete_entity_schema_caching() -> Result<()> {
             default_value: "new_base_field".to_string(),
             rank: 4,
             storage_scope: StorageScope::Runtime,
+            validator: None,
+            metadata: Default::default(),
         }
     );
     
@@ -808,3 +875,2387 @@ fn test_complete_entity_schema_caching() -> Result<()> {
     
     Ok(())
 }
+
+#[test]
+fn test_field_validator_rejects_invalid_writes() -> Result<()> {
+    let mut store = setup_test_database()?;
+
+    let mut schema = EntitySchema::<Single, String, String>::new("Pump".to_string(), vec!["Folder".to_string()]);
+    schema.fields.insert(
+        "Speed".to_string(),
+        FieldSchema::Int {
+            field_type: "Speed".to_string(),
+            default_value: 0,
+            rank: 4,
+            storage_scope: StorageScope::Runtime,
+            merge_policy: MergePolicy::LastWriterWins,
+            validator: Some("new >= 0 && new <= 100 && new != old".to_string()),
+            metadata: Default::default(),
+        }
+    );
+    store.update_schema(schema)?;
+
+    let et_pump = store.get_entity_type("Pump")?;
+    let ft_speed = store.get_field_type("Speed")?;
+    let pump_id = store.create_entity(et_pump, None, "Pump1")?;
+
+    store.write(pump_id, &[ft_speed], Value::Int(50), None, None, None, None)?;
+
+    let result = store.write(pump_id, &[ft_speed], Value::Int(150), None, None, None, None);
+    assert!(matches!(result, Err(Error::InvalidFieldValue(_))));
+
+    // The validator sees the value after adjustment and can compare with the old value
+    let result = store.write(pump_id, &[ft_speed], Value::Int(0), None, None, None, Some(AdjustBehavior::Add));
+    assert!(matches!(result, Err(Error::InvalidFieldValue(_))));
+
+    let (speed, _, _) = store.read(pump_id, &[ft_speed])?;
+    assert_eq!(speed, Value::Int(50));
+
+    Ok(())
+}
+
+#[test]
+fn test_computed_field_evaluated_on_read() -> Result<()> {
+    let mut store = setup_test_database()?;
+
+    let mut schema = EntitySchema::<Single, String, String>::new("Pump".to_string(), vec!["Folder".to_string()]);
+    schema.fields.insert(
+        "Speed".to_string(),
+        FieldSchema::Int {
+            field_type: "Speed".to_string(),
+            default_value: 0,
+            rank: 4,
+            storage_scope: StorageScope::Runtime,
+            merge_policy: MergePolicy::LastWriterWins,
+            validator: None,
+            metadata: Default::default(),
+        }
+    );
+    schema.fields.insert(
+        "DoubleSpeed".to_string(),
+        FieldSchema::Computed {
+            field_type: "DoubleSpeed".to_string(),
+            expression: "Speed * 2".to_string(),
+            rank: 5,
+            storage_scope: StorageScope::Runtime,
+            metadata: Default::default(),
+        }
+    );
+    schema.fields.insert(
+        "Summary".to_string(),
+        FieldSchema::Computed {
+            field_type: "Summary".to_string(),
+            expression: "Name + ': ' + string(DoubleSpeed)".to_string(),
+            rank: 6,
+            storage_scope: StorageScope::Runtime,
+            metadata: Default::default(),
+        }
+    );
+    store.update_schema(schema)?;
+
+    let et_pump = store.get_entity_type("Pump")?;
+    let ft_speed = store.get_field_type("Speed")?;
+    let ft_double_speed = store.get_field_type("DoubleSpeed")?;
+    let ft_summary = store.get_field_type("Summary")?;
+    let pump_id = store.create_entity(et_pump, None, "Pump1")?;
+
+    store.write(pump_id, &[ft_speed], Value::Int(10), None, None, None, None)?;
+    let (value, _, _) = store.read(pump_id, &[ft_double_speed])?;
+    assert_eq!(value, Value::Int(20));
+    let (value, _, _) = store.read(pump_id, &[ft_summary])?;
+    assert_eq!(value, Value::String("Pump1: 20".to_string()));
+
+    // Writing a dependency invalidates the cached values, including nested computed fields
+    store.write(pump_id, &[ft_speed], Value::Int(15), None, None, None, None)?;
+    let (value, _, _) = store.read(pump_id, &[ft_double_speed])?;
+    assert_eq!(value, Value::Int(30));
+    let (value, _, _) = store.read(pump_id, &[ft_summary])?;
+    assert_eq!(value, Value::String("Pump1: 30".to_string()));
+
+    // Computed fields are read-only
+    let result = store.write(pump_id, &[ft_double_speed], Value::Int(1), None, None, None, None);
+    assert!(matches!(result, Err(Error::InvalidRequest(_))));
+
+    Ok(())
+}
+
+#[test]
+fn test_computed_field_aggregates_over_entity_list() -> Result<()> {
+    let mut store = Store::new();
+    let et_plant = crate::testing::SchemaBuilder::object("Plant")
+        .field(FieldSchema::Computed {
+            field_type: "TotalPower".to_string(),
+            expression: "Sum(Children->Power)".to_string(),
+            rank: 10,
+            storage_scope: StorageScope::Runtime,
+            metadata: Default::default(),
+        })
+        .field(FieldSchema::Computed {
+            field_type: "Units".to_string(),
+            expression: "Count(Children->Power)".to_string(),
+            rank: 11,
+            storage_scope: StorageScope::Runtime,
+            metadata: Default::default(),
+        })
+        .field(FieldSchema::Computed {
+            field_type: "PeakPower".to_string(),
+            expression: "Max(Children->Power)".to_string(),
+            rank: 12,
+            storage_scope: StorageScope::Runtime,
+            metadata: Default::default(),
+        })
+        .apply(&mut store)?;
+    let et_generator = crate::testing::SchemaBuilder::object("Generator").int("Power", 0).apply(&mut store)?;
+
+    let ft_power = store.get_field_type("Power")?;
+    let ft_total_power = store.get_field_type("TotalPower")?;
+    let ft_units = store.get_field_type("Units")?;
+    let ft_peak_power = store.get_field_type("PeakPower")?;
+
+    let plant_id = store.create_entity(et_plant, None, "Plant1")?;
+    assert_eq!(store.read(plant_id, &[ft_total_power])?.0, Value::Int(0));
+    assert_eq!(store.read(plant_id, &[ft_units])?.0, Value::Int(0));
+
+    let gen1 = store.create_entity(et_generator, Some(plant_id), "Gen1")?;
+    let gen2 = store.create_entity(et_generator, Some(plant_id), "Gen2")?;
+    store.write(gen1, &[ft_power], Value::Int(30), None, None, None, None)?;
+    store.write(gen2, &[ft_power], Value::Int(50), None, None, None, None)?;
+    assert_eq!(store.read(plant_id, &[ft_total_power])?.0, Value::Int(80));
+    assert_eq!(store.read(plant_id, &[ft_units])?.0, Value::Int(2));
+    assert_eq!(store.read(plant_id, &[ft_peak_power])?.0, Value::Int(50));
+
+    // Writing a child's field invalidates the cached aggregate
+    store.write(gen1, &[ft_power], Value::Int(70), None, None, None, None)?;
+    assert_eq!(store.read(plant_id, &[ft_total_power])?.0, Value::Int(120));
+    assert_eq!(store.read(plant_id, &[ft_peak_power])?.0, Value::Int(70));
+
+    // So do adding and removing children
+    let gen3 = store.create_entity(et_generator, Some(plant_id), "Gen3")?;
+    store.write(gen3, &[ft_power], Value::Int(5), None, None, None, None)?;
+    assert_eq!(store.read(plant_id, &[ft_total_power])?.0, Value::Int(125));
+    assert_eq!(store.read(plant_id, &[ft_units])?.0, Value::Int(3));
+    store.delete_entity(gen1)?;
+    assert_eq!(store.read(plant_id, &[ft_total_power])?.0, Value::Int(55));
+    assert_eq!(store.read(plant_id, &[ft_peak_power])?.0, Value::Int(50));
+
+    Ok(())
+}
+
+#[test]
+fn test_triggers_run_after_matching_writes() -> Result<()> {
+    let mut store = setup_test_database()?;
+
+    let mut schema = EntitySchema::<Single, String, String>::new("Pump".to_string(), vec!["Folder".to_string()]);
+    schema.fields.insert(
+        "Speed".to_string(),
+        FieldSchema::Int {
+            field_type: "Speed".to_string(),
+            default_value: 0,
+            rank: 4,
+            storage_scope: StorageScope::Runtime,
+            merge_policy: MergePolicy::LastWriterWins,
+            validator: None,
+            metadata: Default::default(),
+        }
+    );
+    schema.fields.insert(
+        "Status".to_string(),
+        FieldSchema::String {
+            field_type: "Status".to_string(),
+            default_value: "".to_string(),
+            rank: 5,
+            storage_scope: StorageScope::Runtime,
+            validator: None,
+            metadata: Default::default(),
+        }
+    );
+    store.update_schema(schema)?;
+    trigger_schema().apply(&mut store)?;
+
+    let et_pump = store.get_entity_type("Pump")?;
+    let ft_speed = store.get_field_type("Speed")?;
+    let ft_status = store.get_field_type("Status")?;
+    let pump_id = store.create_entity(et_pump, None, "Pump1")?;
+
+    let trigger_id = store.register_trigger(None, Trigger {
+        entity_type: et_pump,
+        field_type: ft_speed,
+        condition: Some("new > 50".to_string()),
+        action: TriggerAction::Write {
+            field_path: vec![ft_status],
+            expression: "Name + ' running fast at ' + string(new)".to_string(),
+        },
+    })?;
+
+    store.write(pump_id, &[ft_speed], Value::Int(10), None, None, None, None)?;
+    let (status, _, _) = store.read(pump_id, &[ft_status])?;
+    assert_eq!(status, Value::String("".to_string()));
+
+    store.write_queue.clear();
+    store.write(pump_id, &[ft_speed], Value::Int(80), None, None, None, None)?;
+    let (status, _, _) = store.read(pump_id, &[ft_status])?;
+    assert_eq!(status, Value::String("Pump1 running fast at 80".to_string()));
+
+    // The trigger's write is queued after the write that caused it
+    let triggered: Vec<_> = store.write_queue.iter().map(|write| match write {
+        WriteInfo::FieldUpdate { field_type, triggered_by, .. } => (*field_type, *triggered_by),
+        _ => panic!("unexpected write"),
+    }).collect();
+    assert_eq!(triggered, vec![(ft_speed, None), (ft_status, Some(trigger_id))]);
+
+    // A trigger that keeps re-triggering itself is stopped, and neither the
+    // write nor any of the writes it set off are applied
+    store.unregister_trigger(trigger_id)?;
+    store.register_trigger(None, Trigger {
+        entity_type: et_pump,
+        field_type: ft_speed,
+        condition: None,
+        action: TriggerAction::Write {
+            field_path: vec![ft_speed],
+            expression: "new + 1".to_string(),
+        },
+    })?;
+    store.write_queue.clear();
+    let result = store.write(pump_id, &[ft_speed], Value::Int(0), None, None, None, None);
+    assert!(matches!(result, Err(Error::ExecutionError(_))));
+    let (speed, _, _) = store.read(pump_id, &[ft_speed])?;
+    assert_eq!(speed, Value::Int(80));
+    assert!(store.write_queue.is_empty());
+
+    Ok(())
+}
+
+#[allow(dead_code)]
+fn trigger_schema() -> crate::testing::SchemaBuilder {
+    crate::testing::SchemaBuilder::object("Trigger")
+        .string("ResourceType", "")
+        .string("ResourceField", "")
+        .string("Condition", "")
+        .string("TargetField", "")
+        .string("Action", "")
+}
+
+#[test]
+fn test_triggers_are_stored_as_entities() -> Result<()> {
+    let mut store = Store::new();
+    crate::testing::SchemaBuilder::object("Pump").int("Speed", 0).int("Limit", 0).apply(&mut store)?;
+    let et_trigger = trigger_schema().apply(&mut store)?;
+
+    let et_pump = store.get_entity_type("Pump")?;
+    let ft_speed = store.get_field_type("Speed")?;
+    let ft_limit = store.get_field_type("Limit")?;
+
+    // A trigger is defined by creating a Trigger entity, as a remote client would
+    let field = |store: &Store, name: &str, value: &str| -> Result<(FieldType, Value)> {
+        Ok((store.get_field_type(name)?, Value::String(value.to_string())))
+    };
+    let fields = vec![
+        field(&store, "ResourceType", "Pump")?,
+        field(&store, "ResourceField", "Speed")?,
+        field(&store, "TargetField", "Limit")?,
+        field(&store, "Action", "new * 2")?,
+    ];
+    let trigger_id = store.create_entity_with_fields(et_trigger, None, "DoubleSpeed", fields)?;
+
+    let pump_id = store.create_entity(et_pump, None, "Pump1")?;
+    store.write(pump_id, &[ft_speed], Value::Int(10), None, None, None, None)?;
+    assert_eq!(store.read(pump_id, &[ft_limit])?.0, Value::Int(20));
+
+    // Changing the entity changes the trigger
+    let ft_action = store.get_field_type("Action")?;
+    store.write(trigger_id, &[ft_action], Value::String("new * 3".to_string()), None, None, None, None)?;
+    store.write(pump_id, &[ft_speed], Value::Int(10), None, None, None, None)?;
+    assert_eq!(store.read(pump_id, &[ft_limit])?.0, Value::Int(30));
+
+    // Triggers are part of snapshots
+    let mut restored = Store::new();
+    restored.restore_snapshot(store.take_snapshot());
+    restored.write(pump_id, &[ft_speed], Value::Int(5), None, None, None, None)?;
+    assert_eq!(restored.read(pump_id, &[ft_limit])?.0, Value::Int(15));
+
+    // Deleting the entity removes the trigger
+    store.delete_entity(trigger_id)?;
+    store.write(pump_id, &[ft_speed], Value::Int(1), None, None, None, None)?;
+    assert_eq!(store.read(pump_id, &[ft_limit])?.0, Value::Int(30));
+
+    // A write that makes a trigger match the field it wrote is undone along
+    // with the trigger's failed cascade
+    let fields = vec![
+        field(&store, "ResourceType", "Trigger")?,
+        field(&store, "TargetField", "Condition")?,
+        field(&store, "Action", "1 +")?,
+    ];
+    let broken_id = store.create_entity_with_fields(et_trigger, None, "Broken", fields)?;
+    let ft_resource_field = store.get_field_type("ResourceField")?;
+    let result = store.write(broken_id, &[ft_resource_field], Value::String("ResourceField".to_string()), None, None, None, None);
+    assert!(result.is_err());
+    assert_eq!(store.read(broken_id, &[ft_resource_field])?.0, Value::String("".to_string()));
+
+    Ok(())
+}
+
+#[test]
+fn test_rejected_write_adds_no_field() -> Result<()> {
+    let mut store = Store::new();
+    let et_pump = crate::testing::SchemaBuilder::object("Pump").int("Speed", 0).apply(&mut store)?;
+    let ft_name = store.get_field_type("Name")?;
+    let ft_speed = store.get_field_type("Speed")?;
+
+    // Nothing is stored for an entity that doesn't exist when the value is rejected
+    let missing = EntityId::new(et_pump, 42);
+    let result = store.write(missing, &[ft_name], Value::Int(1), None, None, None, None);
+    assert!(matches!(result, Err(Error::ValueTypeMismatch(..))));
+    assert!(!store.entity_exists(missing));
+    assert!(store.read(missing, &[ft_speed]).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_fenced_write_rejects_stale_leader() -> Result<()> {
+    let mut store = setup_test_database()?;
+
+    let mut schema = EntitySchema::<Single, String, String>::new("FaultTolerance".to_string(), vec!["Folder".to_string()]);
+    schema.fields.insert(
+        "CurrentLeader".to_string(),
+        FieldSchema::EntityReference {
+            field_type: "CurrentLeader".to_string(),
+            default_value: None,
+            rank: 4,
+            storage_scope: StorageScope::Runtime,
+            validator: None,
+            metadata: Default::default(),
+        }
+    );
+    schema.fields.insert(
+        "LeaderToken".to_string(),
+        FieldSchema::Int {
+            field_type: "LeaderToken".to_string(),
+            default_value: 0,
+            rank: 5,
+            storage_scope: StorageScope::Runtime,
+            merge_policy: MergePolicy::LastWriterWins,
+            validator: None,
+            metadata: Default::default(),
+        }
+    );
+    store.update_schema(schema)?;
+
+    let et_fault_tolerance = store.get_entity_type("FaultTolerance")?;
+    let et_folder = store.get_entity_type("Folder")?;
+    let ft_current_leader = store.get_field_type("CurrentLeader")?;
+    let ft_leader_token = store.get_field_type("LeaderToken")?;
+    let ft_name = store.get_field_type("Name")?;
+
+    let fault_tolerance_id = store.create_entity(et_fault_tolerance, None, "ServiceFT")?;
+    let service_a = store.create_entity(et_folder, None, "ServiceA")?;
+    let service_b = store.create_entity(et_folder, None, "ServiceB")?;
+    let target_id = store.create_entity(et_folder, None, "Target")?;
+
+    // Every change of leader issues a new token
+    store.write(fault_tolerance_id, &[ft_current_leader], Value::EntityReference(Some(service_a)), None, None, None, None)?;
+    let (token_a, _, _) = store.read(fault_tolerance_id, &[ft_leader_token])?;
+    assert_eq!(token_a, Value::Int(1));
+    let token_a = token_a.as_int().unwrap();
+
+    store.fenced_write(fault_tolerance_id, token_a, target_id, &[ft_name], Value::String("A".to_string()), Some(service_a), None, None, None)?;
+
+    // Re-assigning the same leader doesn't advance the token
+    store.write(fault_tolerance_id, &[ft_current_leader], Value::EntityReference(Some(service_a)), None, None, None, None)?;
+    store.write(fault_tolerance_id, &[ft_current_leader], Value::EntityReference(Some(service_b)), None, None, None, None)?;
+    let (token_b, _, _) = store.read(fault_tolerance_id, &[ft_leader_token])?;
+    assert_eq!(token_b, Value::Int(2));
+
+    let result = store.fenced_write(fault_tolerance_id, token_a, target_id, &[ft_name], Value::String("stale".to_string()), Some(service_a), None, None, None);
+    assert!(matches!(result, Err(Error::StaleFencingToken(_, 1, 2))));
+
+    let (name, _, _) = store.read(target_id, &[ft_name])?;
+    assert_eq!(name, Value::String("A".to_string()));
+
+    Ok(())
+}
+
+#[test]
+fn test_move_copy_and_delete_entity_tree() -> Result<()> {
+    let mut store = setup_test_database()?;
+
+    let mut schema = EntitySchema::<Single, String, String>::new("Gauge".to_string(), vec!["Folder".to_string()]);
+    schema.fields.insert(
+        "Level".to_string(),
+        FieldSchema::Int {
+            field_type: "Level".to_string(),
+            default_value: 0,
+            rank: 4,
+            storage_scope: StorageScope::Configuration,
+            merge_policy: MergePolicy::LastWriterWins,
+            validator: None,
+            metadata: Default::default(),
+        }
+    );
+    store.update_schema(schema)?;
+
+    let et_root = store.get_entity_type("Root")?;
+    let et_folder = store.get_entity_type("Folder")?;
+    let et_gauge = store.get_entity_type("Gauge")?;
+    let ft_children = store.get_field_type("Children")?;
+    let ft_parent = store.get_field_type("Parent")?;
+    let ft_name = store.get_field_type("Name")?;
+    let ft_level = store.get_field_type("Level")?;
+
+    let root_id = store.create_entity(et_root, None, "Root")?;
+    let folder_a = store.create_entity(et_folder, Some(root_id), "A")?;
+    let folder_b = store.create_entity(et_folder, Some(root_id), "B")?;
+    let gauge_id = store.create_entity(et_gauge, Some(folder_a), "Gauge")?;
+    store.write(gauge_id, &[ft_level], Value::Int(42), None, None, None, None)?;
+
+    // Move keeps both Children lists and the Parent reference in sync
+    store.move_entity(gauge_id, Some(folder_b))?;
+    assert_eq!(store.read(folder_a, &[ft_children])?.0, Value::EntityList(vec![]));
+    assert_eq!(store.read(folder_b, &[ft_children])?.0, Value::EntityList(vec![gauge_id]));
+    assert_eq!(store.read(gauge_id, &[ft_parent])?.0, Value::EntityReference(Some(folder_b)));
+
+    // An entity can't be moved or copied into its own subtree
+    assert!(matches!(store.move_entity(folder_b, Some(gauge_id)), Err(Error::InvalidRequest(_))));
+    assert!(matches!(store.copy_entity_tree(folder_b, Some(folder_b)), Err(Error::InvalidRequest(_))));
+
+    // Copy duplicates the whole subtree, including inherited and own fields
+    let copy_b = store.copy_entity_tree(folder_b, Some(folder_a))?;
+    assert_ne!(copy_b, folder_b);
+    assert_eq!(store.read(copy_b, &[ft_name])?.0, Value::String("B".to_string()));
+    assert_eq!(store.read(copy_b, &[ft_parent])?.0, Value::EntityReference(Some(folder_a)));
+    assert_eq!(store.read(folder_a, &[ft_children])?.0, Value::EntityList(vec![copy_b]));
+
+    let (copy_children, _, _) = store.read(copy_b, &[ft_children])?;
+    let copy_children = copy_children.as_entity_list().cloned().unwrap();
+    assert_eq!(copy_children.len(), 1);
+    let copy_gauge = copy_children[0];
+    assert_ne!(copy_gauge, gauge_id);
+    assert_eq!(store.read(copy_gauge, &[ft_level])?.0, Value::Int(42));
+    assert_eq!(store.read(copy_gauge, &[ft_parent])?.0, Value::EntityReference(Some(copy_b)));
+
+    // Deep delete removes descendants and detaches the root from its parent
+    store.delete_entity_tree(folder_b)?;
+    assert!(!store.entity_exists(folder_b));
+    assert!(!store.entity_exists(gauge_id));
+    assert!(store.entity_exists(copy_gauge));
+    assert_eq!(store.read(root_id, &[ft_children])?.0, Value::EntityList(vec![folder_a]));
+
+    Ok(())
+}
+
+#[test]
+fn test_read_and_write_by_path() -> Result<()> {
+    let mut store = setup_test_database()?;
+
+    let et_root = store.get_entity_type("Root")?;
+    let et_folder = store.get_entity_type("Folder")?;
+
+    let root_id = store.create_entity(et_root, None, "Root")?;
+    let machines_id = store.create_entity(et_folder, Some(root_id), "Machines")?;
+    let machine_id = store.create_entity(et_folder, Some(machines_id), "M1")?;
+
+    assert_eq!(store.resolve_path("Root/Machines/M1")?, machine_id);
+
+    let (name, _, _) = store.read_path("Root/Machines/M1/Name")?;
+    assert_eq!(name, Value::String("M1".to_string()));
+
+    // The field segment supports indirection
+    let (parent_name, _, _) = store.read_path("Root/Machines/M1/Parent->Name")?;
+    assert_eq!(parent_name, Value::String("Machines".to_string()));
+
+    store.write_path("Root/Machines/M1/Parent->Name", Value::String("Plant".to_string()), None, None, None, None)?;
+    assert_eq!(store.resolve_path("Root/Plant/M1")?, machine_id);
+
+    assert!(matches!(store.read_path("Root/Machines/M1/Name"), Err(Error::EntityNameNotFound(_))));
+    assert!(store.read_path("Root").is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_blob_range_reads_and_appends() -> Result<()> {
+    let mut store = setup_test_database()?;
+
+    let mut schema = EntitySchema::<Single, String, String>::new("Firmware".to_string(), vec![]);
+    schema.fields.insert(
+        "Image".to_string(),
+        FieldSchema::Blob {
+            field_type: "Image".to_string(),
+            default_value: vec![],
+            rank: 1,
+            storage_scope: StorageScope::Configuration,
+            validator: None,
+            metadata: Default::default(),
+        }
+    );
+    schema.fields.insert(
+        "Version".to_string(),
+        FieldSchema::String {
+            field_type: "Version".to_string(),
+            default_value: "".to_string(),
+            rank: 2,
+            storage_scope: StorageScope::Configuration,
+            validator: None,
+            metadata: Default::default(),
+        }
+    );
+    store.update_schema(schema)?;
+
+    let firmware_id = store.create_entity(store.get_entity_type("Firmware")?, None, "F1")?;
+    let ft_image = store.get_field_type("Image")?;
+    let ft_version = store.get_field_type("Version")?;
+
+    // A never-written blob reads as its empty default
+    assert_eq!(store.read_blob_range(firmware_id, &[ft_image], 0, 16)?, (vec![], 0));
+
+    let image: Vec<u8> = (0..=255).collect();
+    for chunk in image.chunks(100) {
+        store.write_blob_append(firmware_id, &[ft_image], chunk.to_vec(), None)?;
+    }
+    assert_eq!(store.read(firmware_id, &[ft_image])?.0, Value::Blob(image.clone()));
+
+    let mut reassembled = Vec::new();
+    let mut offset = 0;
+    loop {
+        let (chunk, total_len) = store.read_blob_range(firmware_id, &[ft_image], offset, 64)?;
+        assert_eq!(total_len, image.len());
+        if chunk.is_empty() {
+            break;
+        }
+        offset += chunk.len();
+        reassembled.extend(chunk);
+    }
+    assert_eq!(reassembled, image);
+
+    // Ranges are clipped at the end of the blob, but can't start past it
+    assert_eq!(store.read_blob_range(firmware_id, &[ft_image], 250, 64)?.0, image[250..].to_vec());
+    assert!(matches!(store.read_blob_range(firmware_id, &[ft_image], 257, 1), Err(Error::InvalidRequest(_))));
+
+    // Only blob fields can be read or appended to in chunks
+    assert!(matches!(store.read_blob_range(firmware_id, &[ft_version], 0, 1), Err(Error::ValueTypeMismatch(..))));
+    assert!(matches!(store.write_blob_append(firmware_id, &[ft_version], vec![1], None), Err(Error::ValueTypeMismatch(..))));
+
+    Ok(())
+}
+
+#[test]
+fn test_choice_names_from_schema_and_entity_list() -> Result<()> {
+    let mut store = setup_test_database()?;
+
+    let mut schema = EntitySchema::<Single, String, String>::new("Pump".to_string(), vec!["Folder".to_string()]);
+    schema.fields.insert(
+        "Direction".to_string(),
+        FieldSchema::Choice {
+            field_type: "Direction".to_string(),
+            default_value: 0,
+            rank: 4,
+            choices: vec!["Forward".to_string(), "Reverse".to_string()],
+            choices_source: None,
+            storage_scope: StorageScope::Configuration,
+            validator: None,
+            metadata: Default::default(),
+        }
+    );
+    schema.fields.insert(
+        "Mode".to_string(),
+        FieldSchema::Choice {
+            field_type: "Mode".to_string(),
+            default_value: 0,
+            rank: 5,
+            choices: vec![],
+            choices_source: Some("Root/Modes/Children".to_string()),
+            storage_scope: StorageScope::Configuration,
+            validator: None,
+            metadata: Default::default(),
+        }
+    );
+    store.update_schema(schema)?;
+
+    let et_root = store.get_entity_type("Root")?;
+    let et_folder = store.get_entity_type("Folder")?;
+    let et_pump = store.get_entity_type("Pump")?;
+    let ft_direction = store.get_field_type("Direction")?;
+    let ft_mode = store.get_field_type("Mode")?;
+    let ft_name = store.get_field_type("Name")?;
+
+    assert_eq!(store.resolve_choice(et_pump, ft_direction, 1)?, "Reverse");
+    assert_eq!(store.choice_value(et_pump, ft_direction, "Forward")?, 0);
+    assert!(matches!(store.resolve_choice(et_pump, ft_direction, 2), Err(Error::InvalidFieldValue(_))));
+    assert!(matches!(store.choice_value(et_pump, ft_direction, "Sideways"), Err(Error::InvalidFieldValue(_))));
+    assert!(matches!(store.get_choices(et_pump, ft_name), Err(Error::InvalidFieldType(_))));
+
+    // Data-driven choices follow the entities in the source list
+    let root_id = store.create_entity(et_root, None, "Root")?;
+    let modes_id = store.create_entity(et_folder, Some(root_id), "Modes")?;
+    store.create_entity(et_folder, Some(modes_id), "Auto")?;
+    let manual_id = store.create_entity(et_folder, Some(modes_id), "Manual")?;
+
+    assert_eq!(store.get_choices(et_pump, ft_mode)?, vec!["Auto".to_string(), "Manual".to_string()]);
+    assert_eq!(store.choice_value(et_pump, ft_mode, "Manual")?, 1);
+
+    store.write(manual_id, &[ft_name], Value::String("Hand".to_string()), None, None, None, None)?;
+    assert_eq!(store.resolve_choice(et_pump, ft_mode, 1)?, "Hand");
+
+    Ok(())
+}
+
+#[test]
+fn test_type_ids_survive_rebuild() -> Result<()> {
+    let mut original = Store::new();
+    create_entity_schema_with_name(&mut original, "Root")?;
+    create_entity_schema_with_name(&mut original, "Folder")?;
+    create_entity_schema_with_name(&mut original, "Sensor")?;
+
+    let mapping = original.export_type_ids();
+    let json = serde_json::to_string(&mapping).unwrap();
+    let mapping: TypeIdMapping = serde_json::from_str(&json).unwrap();
+
+    // Rebuilding the schemas in another order would reshuffle the entity
+    // type ids, unless the exported mapping is imported first
+    let mut rebuilt = Store::new();
+    rebuilt.import_type_ids(&mapping)?;
+    create_entity_schema_with_name(&mut rebuilt, "Sensor")?;
+    create_entity_schema_with_name(&mut rebuilt, "Root")?;
+    create_entity_schema_with_name(&mut rebuilt, "Folder")?;
+    assert_eq!(rebuilt.export_type_ids(), original.export_type_ids());
+    for name in ["Root", "Folder", "Sensor"] {
+        assert_eq!(rebuilt.get_entity_type(name)?, original.get_entity_type(name)?);
+    }
+
+    // A store that already assigned the ids differently refuses the mapping
+    let mut diverged = Store::new();
+    create_entity_schema_with_name(&mut diverged, "Sensor")?;
+    let before = diverged.export_type_ids();
+    assert!(matches!(diverged.import_type_ids(&mapping), Err(Error::TypeIdConflict(_, _))));
+    assert_eq!(diverged.export_type_ids(), before);
+
+    // ensure_ids hands out the ids without applying the schema
+    let mut schema = EntitySchema::<Single, String, String>::new("Pump".to_string(), vec!["Root".to_string()]);
+    schema.fields.insert(
+        "Speed".to_string(),
+        FieldSchema::Float {
+            field_type: "Speed".to_string(),
+            default_value: 0.0,
+            rank: 1,
+            storage_scope: StorageScope::Runtime,
+            merge_policy: MergePolicy::LastWriterWins,
+            validator: None,
+            metadata: Default::default(),
+        }
+    );
+    let (et_pump, field_types) = rebuilt.ensure_ids(&schema)?;
+    assert_eq!(rebuilt.get_entity_type("Pump")?, et_pump);
+    assert_eq!(rebuilt.get_field_type("Speed")?, field_types["Speed"]);
+    assert!(rebuilt.get_entity_schema(et_pump).is_err());
+    assert_eq!(rebuilt.ensure_ids(&schema)?.0, et_pump);
+
+    let orphan = EntitySchema::<Single, String, String>::new("Orphan".to_string(), vec!["Missing".to_string()]);
+    assert!(matches!(rebuilt.ensure_ids(&orphan), Err(Error::EntityTypeStrNotFound(_))));
+
+    Ok(())
+}
+
+#[test]
+fn test_audit_log_records_writes() -> Result<()> {
+    let mut store = Store::new();
+    create_entity_schema_with_name(&mut store, "Root")?;
+    let et_root = store.get_entity_type("Root")?;
+    let ft_name = store.get_field_type("Name")?;
+
+    // Nothing is recorded until auditing is enabled
+    let root_id = store.create_entity(et_root, None, "Root")?;
+    assert!(matches!(store.query_audit(&AuditQuery::default()), Err(Error::InvalidRequest(_))));
+
+    let (sender, receiver) = crossbeam::channel::unbounded();
+    store.enable_audit(2).add_sink(Box::new(sender));
+
+    let operator = EntityId::new(et_root, 42);
+    store.set_client_context(ClientContext {
+        subject: Some(operator),
+        connection: Some("10.0.0.7:51234".to_string()),
+        ..Default::default()
+    });
+    let child_id = store.create_entity(et_root, Some(root_id), "Child")?;
+    store.write(root_id, &[ft_name], Value::from_string("Renamed".to_string()), None, None, None, None)?;
+
+    // Without an authenticated subject the writer is recorded instead
+    let writer = EntityId::new(et_root, 7);
+    store.set_client_context(ClientContext::default());
+    store.write(child_id, &[ft_name], Value::from_string("Kid".to_string()), Some(writer), None, None, None)?;
+
+    // The buffer keeps the last two records, sinks get all of them
+    let records = store.query_audit(&AuditQuery::default())?;
+    assert_eq!(records.len(), 2);
+    assert_eq!(receiver.try_iter().count(), 3);
+    assert!(records[0].seq < records[1].seq);
+
+    let by_operator = store.query_audit(&AuditQuery { subject: Some(operator), ..Default::default() })?;
+    assert_eq!(by_operator.len(), 1);
+    assert_eq!(by_operator[0].entity_id(), Some(root_id));
+    assert_eq!(by_operator[0].connection.as_deref(), Some("10.0.0.7:51234"));
+
+    let by_entity = store.query_audit(&AuditQuery { entity_id: Some(child_id), ..Default::default() })?;
+    assert_eq!(by_entity.len(), 1);
+    assert_eq!(by_entity[0].subject, Some(writer));
+
+    let later = store.query_audit(&AuditQuery { since: Some(by_entity[0].timestamp + Duration::seconds(1)), ..Default::default() })?;
+    assert!(later.is_empty());
+    let latest = store.query_audit(&AuditQuery { limit: Some(1), ..Default::default() })?;
+    assert_eq!(latest, by_entity);
+
+    assert!(store.disable_audit().is_some());
+    assert!(store.audit_log().is_none());
+    Ok(())
+}
+
+#[test]
+fn test_get_changes_for_entity() -> Result<()> {
+    let mut store = Store::new();
+    create_entity_schema_with_name(&mut store, "Root")?;
+    let et_root = store.get_entity_type("Root")?;
+    let ft_name = store.get_field_type("Name")?;
+    let root_id = store.create_entity(et_root, None, "Root")?;
+    assert!(matches!(store.get_changes(root_id, epoch()), Err(Error::InvalidRequest(_))));
+
+    store.enable_audit(4);
+    let child_id = store.create_entity(et_root, Some(root_id), "Child")?;
+    store.write(child_id, &[ft_name], Value::from_string("Kid".to_string()), None, None, None, None)?;
+    store.write(root_id, &[ft_name], Value::from_string("Renamed".to_string()), None, None, None, None)?;
+    store.write(child_id, &[ft_name], Value::from_string("Teen".to_string()), None, None, None, None)?;
+
+    let changes = store.get_changes(child_id, epoch())?;
+    assert_eq!(changes.len(), 3);
+    assert!(matches!(&changes[0], WriteInfo::CreateEntity { created_entity_id, .. } if *created_entity_id == child_id));
+    let names: Vec<_> = changes[1..]
+        .iter()
+        .map(|write| match write {
+            WriteInfo::FieldUpdate { value, .. } => value.clone(),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(names, vec![Some(Value::from_string("Kid".to_string())), Some(Value::from_string("Teen".to_string()))]);
+
+    let records = store.query_audit(&AuditQuery::default())?;
+    assert!(store.get_changes(child_id, records[3].timestamp + Duration::seconds(1))?.is_empty());
+
+    // Once the log has dropped records, only times after them are answered
+    store.write(root_id, &[ft_name], Value::from_string("Again".to_string()), None, None, None, None)?;
+    assert!(matches!(store.get_changes(child_id, epoch()), Err(Error::InvalidRequest(_))));
+    let latest = store.query_audit(&AuditQuery::default())?;
+    assert_eq!(store.get_changes(root_id, latest[3].timestamp)?.len(), 1);
+    Ok(())
+}
+
+#[test]
+fn test_id_allocation_ranges_and_nodes() -> Result<()> {
+    let id = EntityId::on_node(EntityType(3), 5, 42);
+    assert_eq!((id.extract_type(), id.node_of(), id.local_id()), (EntityType(3), 5, 42));
+    assert_eq!(EntityId::new(EntityType(3), 42).node_of(), 0);
+
+    let mut north = Store::new();
+    let mut south = Store::new();
+    for store in [&mut north, &mut south] {
+        create_entity_schema_with_name(store, "Root")?;
+    }
+    let et_root = north.get_entity_type("Root")?;
+    north.set_id_allocator(IdAllocator::Node(1))?;
+    south.set_id_allocator(IdAllocator::Node(2))?;
+
+    // Both create entities while disconnected without minting the same ids
+    let north_ids = [north.create_entity(et_root, None, "A")?, north.create_entity(et_root, None, "B")?];
+    let south_id = south.create_entity(et_root, None, "C")?;
+    assert_eq!(north_ids.map(|id| (id.node_of(), id.local_id())), [(1, 1), (1, 2)]);
+    assert_eq!((south_id.node_of(), south_id.local_id()), (2, 1));
+
+    // Entities from another node don't move this node's counter
+    north.create_entity_with_id(et_root, None, &mut Some(south_id), "C")?;
+    assert_eq!(north.create_entity(et_root, None, "D")?.local_id(), 3);
+
+    let mut store = Store::new();
+    create_entity_schema_with_name(&mut store, "Root")?;
+    store.set_id_allocator(IdAllocator::Range(10..12))?;
+    assert_eq!(store.create_entity(et_root, None, "A")?.extract_id(), 10);
+    assert_eq!(store.create_entity(et_root, None, "B")?.extract_id(), 11);
+    assert!(matches!(store.create_entity(et_root, None, "C"), Err(Error::InvalidRequest(_))));
+
+    assert!(store.set_id_allocator(IdAllocator::Node(MAX_NODE_ID + 1)).is_err());
+    assert!(store.set_id_allocator(IdAllocator::Range(5..5)).is_err());
+    assert_eq!(store.id_allocator(), &IdAllocator::Range(10..12));
+    Ok(())
+}
+
+#[test]
+fn test_write_scope_enforced_for_clients() -> Result<()> {
+    let mut store = Store::new();
+    let mut schema = EntitySchema::<Single, String, String>::new("Object".to_string(), vec![]);
+    let scoped = [
+        ("Name", None),
+        ("Password", Some(WriteScope::OwnerOnly)),
+        ("Heartbeat", Some(WriteScope::ServiceOnly)),
+        ("CurrentLeader", Some(WriteScope::AdminOnly)),
+    ];
+    for (rank, (name, write_scope)) in scoped.into_iter().enumerate() {
+        let mut field_schema = FieldSchema::String {
+            field_type: name.to_string(),
+            default_value: "".to_string(),
+            rank: rank as i64,
+            storage_scope: StorageScope::Configuration,
+            validator: None,
+            metadata: Default::default(),
+        };
+        if let Some(write_scope) = write_scope {
+            field_schema = field_schema.with_write_scope(write_scope);
+        }
+        schema.fields.insert(name.to_string(), field_schema);
+    }
+    schema.fields.insert("Parent".to_string(), FieldSchema::EntityReference {
+        field_type: "Parent".to_string(),
+        default_value: None,
+        rank: 4,
+        storage_scope: StorageScope::Configuration,
+        validator: None,
+        metadata: Default::default(),
+    });
+    store.update_schema(schema)?;
+    for (name, parent) in [("User", "Object"), ("Service", "Object"), ("Worker", "Service")] {
+        store.update_schema(EntitySchema::<Single, String, String>::new(name.to_string(), vec![parent.to_string()]))?;
+    }
+
+    let alice = store.create_entity(store.get_entity_type("User")?, None, "alice")?;
+    let bob = store.create_entity(store.get_entity_type("User")?, None, "bob")?;
+    let worker = store.create_entity(store.get_entity_type("Worker")?, None, "worker")?;
+    let ft_name = store.get_field_type("Name")?;
+    let ft_password = store.get_field_type("Password")?;
+    let ft_heartbeat = store.get_field_type("Heartbeat")?;
+    let ft_current_leader = store.get_field_type("CurrentLeader")?;
+    let value = || Value::from_string("x".to_string());
+
+    // Without a client subject, e.g. local code, nothing is restricted
+    store.write(alice, &[ft_current_leader], value(), None, None, None, None)?;
+
+    store.set_client_context(ClientContext { subject: Some(alice), ..Default::default() });
+    store.write(bob, &[ft_name], value(), None, None, None, None)?;
+    store.write(alice, &[ft_password], value(), None, None, None, None)?;
+    assert!(matches!(
+        store.write(bob, &[ft_password], value(), None, None, None, None),
+        Err(Error::PermissionDenied(subject, entity, field)) if subject == alice && entity == bob && field == ft_password
+    ));
+    assert!(matches!(store.write(alice, &[ft_heartbeat], value(), None, None, None, None), Err(Error::PermissionDenied(..))));
+    assert!(matches!(store.write(alice, &[ft_current_leader], value(), None, None, None, None), Err(Error::PermissionDenied(..))));
+
+    // Derived service types count as services
+    store.set_client_context(ClientContext { subject: Some(worker), ..Default::default() });
+    store.write(alice, &[ft_heartbeat], value(), None, None, None, None)?;
+    assert!(matches!(store.write(alice, &[ft_current_leader], value(), None, None, None, None), Err(Error::PermissionDenied(..))));
+
+    store.set_client_context(ClientContext { subject: Some(bob), admin: true, ..Default::default() });
+    store.write(alice, &[ft_current_leader], value(), None, None, None, None)?;
+    store.write(alice, &[ft_password], value(), None, None, None, None)?;
+
+    // The scope travels with the schema
+    let schema = store.get_entity_schema(store.get_entity_type("Object")?)?;
+    assert_eq!(schema.fields[&ft_password].write_scope(), Some(WriteScope::OwnerOnly));
+    assert_eq!(schema.fields[&ft_name].write_scope(), None);
+    Ok(())
+}
+
+#[test]
+fn test_rate_limits_and_quotas() -> Result<()> {
+    let mut store = Store::new();
+    let mut schema = EntitySchema::<Single, String, String>::new("Object".to_string(), vec![]);
+    schema.fields.insert("Name".to_string(), FieldSchema::String {
+        field_type: "Name".to_string(),
+        default_value: "".to_string(),
+        rank: 0,
+        storage_scope: StorageScope::Configuration,
+        validator: None,
+        metadata: Default::default(),
+    });
+    schema.fields.insert("Parent".to_string(), FieldSchema::EntityReference {
+        field_type: "Parent".to_string(),
+        default_value: None,
+        rank: 1,
+        storage_scope: StorageScope::Configuration,
+        validator: None,
+        metadata: Default::default(),
+    });
+    for (rank, name) in [limits::RATE_LIMIT, limits::MAX_ENTITIES].into_iter().enumerate() {
+        schema.fields.insert(name.to_string(), FieldSchema::Int {
+            field_type: name.to_string(),
+            default_value: -1,
+            rank: rank as i64 + 2,
+            storage_scope: StorageScope::Configuration,
+            merge_policy: MergePolicy::LastWriterWins,
+            validator: None,
+            metadata: Default::default(),
+        });
+    }
+    store.update_schema(schema)?;
+    let et_object = store.get_entity_type("Object")?;
+    let ft_name = store.get_field_type("Name")?;
+    let ft_max_entities = store.get_field_type(limits::MAX_ENTITIES)?;
+    let ft_rate_limit = store.get_field_type(limits::RATE_LIMIT)?;
+
+    // Set up before limits are enabled, so nothing here is charged
+    let service = store.create_entity(et_object, None, "service")?;
+    let unlimited = store.create_entity(et_object, None, "unlimited")?;
+    store.write(service, &[ft_max_entities], Value::from_int(2), None, None, None, None)?;
+    store.write(service, &[ft_rate_limit], Value::from_int(1000), None, None, None, None)?;
+
+    store.enable_limits(Limits {
+        commands_per_second: Some(0.001),
+        burst: Some(3),
+        max_entities: Some(1),
+        max_notifications: Some(1),
+    });
+
+    // Unauthenticated clients are limited per connection with the defaults
+    store.set_client_context(ClientContext { connection: Some("10.0.0.7:51234".to_string()), ..Default::default() });
+    for _ in 0..3 {
+        store.check_rate_limit()?;
+    }
+    assert!(matches!(store.check_rate_limit(), Err(Error::RateLimited(client)) if client == "10.0.0.7:51234"));
+
+    // The subject's own fields override the defaults
+    store.set_client_context(ClientContext { subject: Some(service), ..Default::default() });
+    for _ in 0..3 {
+        store.check_rate_limit()?;
+    }
+    std::thread::sleep(std::time::Duration::from_millis(10));
+    for _ in 0..3 {
+        store.check_rate_limit()?;
+    }
+    store.create_entity(et_object, None, "a")?;
+    store.create_entity(et_object, None, "b")?;
+    assert!(matches!(
+        store.create_entity(et_object, None, "c"),
+        Err(Error::QuotaExceeded(_, quota, 2)) if quota == limits::MAX_ENTITIES
+    ));
+    assert_eq!(store.limit_enforcer().unwrap().usage(&LimitKey::Subject(service)).unwrap().entities_created, 2);
+
+    let config = NotifyConfig::EntityId { entity_id: service, field_type: ft_name, trigger_on_change: false, context: vec![], filter: None };
+    let queue = NotificationQueue::new();
+    store.register_notification(config.clone(), queue.clone())?;
+    assert!(matches!(store.register_notification(config.clone(), queue.clone()), Err(Error::QuotaExceeded(..))));
+    assert!(store.unregister_notification(&config, &queue));
+    store.register_notification(config.clone(), queue.clone())?;
+
+    // A negative value lifts the limit entirely
+    store.set_client_context(ClientContext { subject: Some(unlimited), ..Default::default() });
+    for _ in 0..10 {
+        store.check_rate_limit()?;
+    }
+    store.create_entity(et_object, None, "d")?;
+    store.create_entity(et_object, None, "e")?;
+
+    store.disable_limits();
+    store.set_client_context(ClientContext { connection: Some("10.0.0.7:51234".to_string()), ..Default::default() });
+    store.check_rate_limit()?;
+    Ok(())
+}
+
+#[test]
+fn test_notification_context_values() -> Result<()> {
+    let mut store = setup_test_database()?;
+    let et_folder = store.get_entity_type("Folder")?;
+    let ft_name = store.get_field_type("Name")?;
+    let ft_parent = store.get_field_type("Parent")?;
+    let ft_children = store.get_field_type("Children")?;
+
+    let plant = store.create_entity(et_folder, None, "Plant")?;
+    let pump = store.create_entity(et_folder, Some(plant), "Pump")?;
+    let operator = store.create_entity(et_folder, None, "Operator")?;
+    store.write(plant, &[ft_name], Value::from("Plant A"), Some(operator), None, None, None)?;
+    let queue = NotificationQueue::new();
+    store.register_notification(
+        NotifyConfig::EntityType {
+            entity_type: et_folder,
+            field_type: ft_name,
+            trigger_on_change: false,
+            context: vec![vec![ft_parent, ft_name], vec![ft_name], vec![ft_parent, ft_parent, ft_name]],
+            filter: None,
+        },
+        queue.clone(),
+    )?;
+
+    store.write(pump, &[ft_name], Value::from("Pump 1"), None, None, None, None)?;
+    let notification = queue.pop().expect("notification");
+    assert_eq!(notification.context.len(), 3);
+
+    // Indirect paths carry what they resolved to, including its writer
+    let parent_name = notification.context_info(&[ft_parent, ft_name]).expect("Parent->Name");
+    assert_eq!(parent_name.value, Some(Value::from("Plant A")));
+    assert_eq!(parent_name.writer_id, Some(operator));
+    assert_eq!(parent_name.timestamp, Some(store.read(plant, &[ft_name])?.1));
+    assert_eq!(notification.context_value_by_name(&store, "Parent->Name")?, Some(&Value::from("Plant A")));
+
+    // Context is read after the write it reports
+    assert_eq!(notification.context_value(&[ft_name]), Some(&Value::from("Pump 1")));
+
+    // A path that can't be resolved is present but empty
+    let grandparent = notification.context_info(&[ft_parent, ft_parent, ft_name]).expect("Parent->Parent->Name");
+    assert!(grandparent.value.is_none() && grandparent.timestamp.is_none() && grandparent.writer_id.is_none());
+    assert_eq!(notification.context_value(&[ft_children]), None);
+    Ok(())
+}
+
+#[test]
+fn test_notification_filter() -> Result<()> {
+    use crate::data::resp::{RespDecode, RespEncode, RespFromBytes, RespToBytes, RespValue};
+
+    let mut store = Store::new();
+    let et_sensor = crate::testing::SchemaBuilder::object("Sensor")
+        .float("Temperature", 0.0)
+        .apply(&mut store)?;
+    let ft_temperature = store.get_field_type("Temperature")?;
+    let plant = store.create_entity(et_sensor, None, "Plant")?;
+    let boiler = store.create_entity(et_sensor, Some(plant), "Boiler")?;
+
+    // Only crossing 80 upwards, and only for sensors under the plant
+    let queue = NotificationQueue::new();
+    let config = NotifyConfig::EntityType {
+        entity_type: et_sensor,
+        field_type: ft_temperature,
+        trigger_on_change: true,
+        context: vec![],
+        filter: Some("new > 80.0 && old <= 80.0 && Parent->Name == 'Plant'".to_string()),
+    };
+    store.register_notification(config.clone(), queue.clone())?;
+
+    for temperature in [75.0, 81.0, 85.0, 79.0, 82.0] {
+        store.write(boiler, &[ft_temperature], Value::Float(temperature), None, None, None, None)?;
+    }
+    store.write(plant, &[ft_temperature], Value::Float(90.0), None, None, None, None)?;
+    let crossings: Vec<Option<Value>> = std::iter::from_fn(|| queue.pop()).map(|notification| notification.current.value).collect();
+    assert_eq!(crossings, vec![Some(Value::Float(81.0)), Some(Value::Float(82.0))]);
+    assert_eq!(config.filter(), Some("new > 80.0 && old <= 80.0 && Parent->Name == 'Plant'"));
+
+    let broken = NotifyConfig::EntityId {
+        entity_id: boiler,
+        field_type: ft_temperature,
+        trigger_on_change: false,
+        context: vec![],
+        filter: Some("new >".to_string()),
+    };
+    assert!(matches!(store.register_notification(broken, NotificationQueue::new()), Err(Error::InvalidNotifyConfig(_))));
+
+    // Configs from peers that predate filters decode without one
+    let unfiltered = NotifyConfig::EntityId {
+        entity_id: boiler,
+        field_type: ft_temperature,
+        trigger_on_change: false,
+        context: vec![],
+        filter: None,
+    };
+    let bytes = unfiltered.encode().to_bytes();
+    let RespValue::Array(mut elements) = RespValue::from_bytes(&bytes)?.0 else {
+        panic!("config encodes as an array");
+    };
+    elements.pop();
+    assert_eq!(NotifyConfig::decode(RespValue::Array(elements))?, unfiltered);
+    Ok(())
+}
+
+#[test]
+fn test_bounded_notification_queues() -> Result<()> {
+    let mut store = Store::new();
+    let mut schema = EntitySchema::<Single, String, String>::new("Object".to_string(), vec![]);
+    for (rank, name) in ["Name", "Value"].into_iter().enumerate() {
+        schema.fields.insert(name.to_string(), FieldSchema::String {
+            field_type: name.to_string(),
+            default_value: "".to_string(),
+            rank: rank as i64,
+            storage_scope: StorageScope::Configuration,
+            validator: None,
+            metadata: Default::default(),
+        });
+    }
+    schema.fields.insert("Parent".to_string(), FieldSchema::EntityReference {
+        field_type: "Parent".to_string(),
+        default_value: None,
+        rank: 2,
+        storage_scope: StorageScope::Configuration,
+        validator: None,
+        metadata: Default::default(),
+    });
+    store.update_schema(schema)?;
+    let entity_id = store.create_entity(store.get_entity_type("Object")?, None, "pump")?;
+    let ft_value = store.get_field_type("Value")?;
+
+    let config = NotifyConfig::EntityId { entity_id, field_type: ft_value, trigger_on_change: false, context: vec![], filter: None };
+    let unbounded = NotificationQueue::new();
+    let drop_oldest = NotificationQueue::bounded(2, OverflowPolicy::DropOldest);
+    let drop_newest = NotificationQueue::bounded(2, OverflowPolicy::DropNewest);
+    let disconnect = NotificationQueue::bounded(2, OverflowPolicy::Disconnect);
+    for queue in [&unbounded, &drop_oldest, &drop_newest, &disconnect] {
+        store.register_notification(config.clone(), queue.clone())?;
+    }
+
+    for i in 0..5 {
+        store.write(entity_id, &[ft_value], Value::from_string(i.to_string()), None, None, None, None)?;
+    }
+    let value = |queue: &NotificationQueue| queue.pop().and_then(|n| n.current.value).and_then(|v| v.as_string().map(str::to_string));
+
+    assert_eq!(unbounded.lag(), 5);
+    assert_eq!(unbounded.dropped(), 0);
+
+    assert_eq!(drop_oldest.lag(), 2);
+    assert_eq!(drop_oldest.dropped(), 3);
+    assert_eq!(value(&drop_oldest).as_deref(), Some("3"));
+
+    assert_eq!(drop_newest.lag(), 2);
+    assert_eq!(drop_newest.dropped(), 3);
+    assert_eq!(value(&drop_newest).as_deref(), Some("0"));
+
+    // The third write overflowed the queue and the store let go of it
+    assert!(disconnect.is_disconnected());
+    assert_eq!(disconnect.lag(), 0);
+    assert_eq!(disconnect.dropped(), 3);
+    assert!(!store.unregister_notification(&config, &disconnect));
+
+    // Reading keeps a bounded queue flowing
+    drop_newest.pop();
+    store.write(entity_id, &[ft_value], Value::from_string("5".to_string()), None, None, None, None)?;
+    assert_eq!(value(&drop_newest).as_deref(), Some("5"));
+    Ok(())
+}
+
+#[test]
+fn test_deadline_aborts_filtered_find() -> Result<()> {
+    let mut store = Store::new();
+    let mut schema = EntitySchema::<Single, String, String>::new("Object".to_string(), vec![]);
+    schema.fields.insert("Name".to_string(), FieldSchema::String {
+        field_type: "Name".to_string(),
+        default_value: "".to_string(),
+        rank: 0,
+        storage_scope: StorageScope::Configuration,
+        validator: None,
+        metadata: Default::default(),
+    });
+    schema.fields.insert("Parent".to_string(), FieldSchema::EntityReference {
+        field_type: "Parent".to_string(),
+        default_value: None,
+        rank: 1,
+        storage_scope: StorageScope::Configuration,
+        validator: None,
+        metadata: Default::default(),
+    });
+    store.update_schema(schema)?;
+    let et_object = store.get_entity_type("Object")?;
+    for i in 0..10 {
+        store.create_entity(et_object, None, &format!("object{}", i))?;
+    }
+    let filter = Some("Name != 'object3'");
+
+    store.set_deadline(Deadline::after(std::time::Duration::ZERO));
+    assert!(matches!(store.find_entities(et_object, filter), Err(Error::Timeout(_))));
+    assert!(matches!(store.find_entities_exact(et_object, None, filter), Err(Error::Timeout(_))));
+    // Scans without a filter are cheap and aren't interrupted
+    assert_eq!(store.find_entities(et_object, None)?.len(), 10);
+
+    let registry = CancelRegistry::new();
+    store.set_deadline(registry.begin(Some(60_000), Some(7)));
+    assert_eq!(store.find_entities(et_object, filter)?.len(), 9);
+    assert!(registry.cancel(7));
+    assert!(matches!(store.find_entities(et_object, filter), Err(Error::Cancelled(7))));
+
+    registry.finish(7);
+    assert!(!registry.cancel(7));
+    assert!(registry.is_empty());
+    store.set_deadline(Deadline::none());
+    assert_eq!(store.find_entities(et_object, filter)?.len(), 9);
+    Ok(())
+}
+
+#[test]
+fn test_shared_store_across_threads() -> Result<()> {
+    let mut store = setup_test_database()?;
+    let et_folder = store.get_entity_type("Folder")?;
+    let ft_name = store.get_field_type("Name")?;
+    let folder = store.create_entity(et_folder, None, "Folder")?;
+    let queue = NotificationQueue::new();
+    store.register_notification(
+        NotifyConfig::EntityId { entity_id: folder, field_type: ft_name, trigger_on_change: true, context: vec![], filter: None },
+        queue.clone(),
+    )?;
+
+    let shared = std::sync::Arc::new(SharedStore::new(store));
+    let readers: Vec<_> = (0..4)
+        .map(|_| {
+            let shared = std::sync::Arc::clone(&shared);
+            std::thread::spawn(move || -> Result<()> {
+                for _ in 0..200 {
+                    let (value, _, _) = (&*shared).read(folder, &[ft_name])?;
+                    let Value::String(name) = value else {
+                        panic!("Name is a string field");
+                    };
+                    assert!(name == "Folder" || name.starts_with("Renamed"));
+                    assert_eq!((&*shared).find_entities(et_folder, None)?, vec![folder]);
+                    // Filters are evaluated by each reader's own executor
+                    assert_eq!((&*shared).find_entities(et_folder, Some("Name != ''"))?, vec![folder]);
+                }
+                Ok(())
+            })
+        })
+        .collect();
+
+    for i in 0..50 {
+        (&*shared).write(folder, &[ft_name], Value::String(format!("Renamed {}", i)), None, None, None, None)?;
+    }
+    for reader in readers {
+        reader.join().expect("reader thread panicked")?;
+    }
+
+    // Notifications queued under the write lock can be read from any thread
+    assert_eq!(queue.lag(), 50);
+    let (value, _, _) = (&*shared).read(folder, &[ft_name])?;
+    assert_eq!(value, Value::String("Renamed 49".to_string()));
+    // The trait can't lend a schema out of the lock, the owned variant copies it
+    assert!(StoreTrait::get_complete_entity_schema(&&*shared, et_folder).is_err());
+    assert!((&*shared).get_complete_entity_schema_owned(et_folder)?.fields.contains_key(&ft_name));
+    Ok(())
+}
+
+#[test]
+fn test_read_snapshot_is_isolated_from_writes() -> Result<()> {
+    let mut store = setup_test_database()?;
+    let et_folder = store.get_entity_type("Folder")?;
+    let ft_name = store.get_field_type("Name")?;
+    let folder = store.create_entity(et_folder, None, "Folder")?;
+
+    let snapshot = store.begin_read_snapshot();
+    store.write(folder, &[ft_name], Value::from("Renamed"), None, None, None, None)?;
+    let other = store.create_entity(et_folder, None, "Other")?;
+
+    // The view still sees the store as it was when it was taken
+    let (value, _, _) = snapshot.read(folder, &[ft_name])?;
+    assert_eq!(value, Value::from("Folder"));
+    assert_eq!(snapshot.find_entities(et_folder, Some("Name == 'Folder'"))?, vec![folder]);
+    assert!(!snapshot.entity_exists(other));
+    assert!(snapshot.take_snapshot().entities[&et_folder].contains(&folder));
+
+    // ... and can be read from another thread
+    let handle = std::thread::spawn(move || snapshot.find_entities(et_folder, None));
+    assert_eq!(handle.join().expect("reader thread panicked")?, vec![folder]);
+
+    let (value, _, _) = store.read(folder, &[ft_name])?;
+    assert_eq!(value, Value::from("Renamed"));
+    assert_eq!(store.find_entities(et_folder, None)?, vec![folder, other]);
+
+    let shared = SharedStore::new(store);
+    let snapshot = shared.begin_read_snapshot();
+    (&shared).write(folder, &[ft_name], Value::from("Again"), None, None, None, None)?;
+    assert_eq!(snapshot.read(folder, &[ft_name])?.0, Value::from("Renamed"));
+    Ok(())
+}
+
+#[test]
+fn test_soft_delete_and_restore_entity() -> Result<()> {
+    let mut store = setup_test_database()?;
+    let et_folder = store.get_entity_type("Folder")?;
+    let ft_name = store.get_field_type("Name")?;
+    let ft_children = store.get_field_type("Children")?;
+    let root = store.create_entity(et_folder, None, "Root")?;
+    let config = store.create_entity(et_folder, Some(root), "Config")?;
+    let nested = store.create_entity(et_folder, Some(config), "Nested")?;
+    store.write(nested, &[ft_name], Value::from("Renamed"), None, None, None, None)?;
+    store.write_queue.clear();
+
+    store.soft_delete_entity(config)?;
+    assert!(matches!(store.write_queue.pop_back(), Some(WriteInfo::SoftDeleteEntity { entity_id, .. }) if entity_id == config));
+    assert!(!store.entity_exists(config) && !store.entity_exists(nested));
+    assert!(store.is_soft_deleted(config) && store.is_soft_deleted(nested));
+    assert!(store.read(nested, &[ft_name]).is_err());
+    assert_eq!(store.find_entities(et_folder, None)?, vec![root]);
+    assert_eq!(store.read(root, &[ft_children])?.0, Value::EntityList(vec![]));
+
+    // Ids of deleted entities aren't handed out again
+    let other = store.create_entity(et_folder, None, "Other")?;
+    assert!(other != config && other != nested);
+
+    // The subtree comes back as it was, under its old parent
+    assert!(store.restore_entity(nested).is_err());
+    store.restore_entity(config)?;
+    assert!(!store.is_soft_deleted(nested));
+    assert_eq!(store.read(nested, &[ft_name])?.0, Value::from("Renamed"));
+    assert_eq!(store.read(root, &[ft_children])?.0, Value::EntityList(vec![config]));
+    assert_eq!(store.read(config, &[ft_children])?.0, Value::EntityList(vec![nested]));
+    assert_eq!(store.find_entities(et_folder, None)?, vec![root, config, nested, other]);
+
+    // Purging makes the deletion permanent
+    store.soft_delete_entity(config)?;
+    assert_eq!(store.deleted_entities().len(), 2);
+    assert!(store.purge_deleted(now() - Duration::hours(1)).is_empty());
+    assert_eq!(store.purge_deleted(now() + Duration::seconds(1)), vec![config, nested]);
+    assert!(store.deleted_entities().is_empty());
+    assert!(store.restore_entity(config).is_err());
+
+    // With a retention of zero, the next soft delete purges the previous one
+    store.set_tombstone_retention(Some(Duration::ZERO));
+    store.soft_delete_entity(other)?;
+    std::thread::sleep(std::time::Duration::from_millis(1));
+    store.soft_delete_entity(root)?;
+    assert_eq!(store.deleted_entities().into_iter().map(|(id, _)| id).collect::<Vec<_>>(), vec![root]);
+
+    // Deleting a soft deleted entity purges it
+    store.delete_entity(root)?;
+    assert!(!store.is_soft_deleted(root));
+    Ok(())
+}
+
+#[test]
+fn test_soft_delete_follows_delete_rules() -> Result<()> {
+    let mut store = setup_test_database()?;
+    add_link_schema(&mut store)?;
+    let et_folder = store.get_entity_type("Folder")?;
+    let et_link = store.get_entity_type("Link")?;
+    let ft_name = store.get_field_type("Name")?;
+    let ft_children = store.get_field_type("Children")?;
+    let ft_lock = store.get_field_type("Lock")?;
+    let name_schema = store.get_field_schema(et_folder, ft_name)?;
+    store.set_field_schema(et_folder, ft_name, name_schema.with_unique_among_siblings(true))?;
+
+    let root = store.create_entity(et_folder, None, "Root")?;
+    let config = store.create_entity(et_folder, Some(root), "Config")?;
+    let nested = store.create_entity(et_folder, Some(config), "Nested")?;
+    let link = store.create_entity(et_link, None, "Link")?;
+    store.write(link, &[ft_lock], Value::EntityReference(Some(nested)), None, None, None, None)?;
+    assert_eq!(store.find_entities(et_folder, Some("Name == 'Config'"))?, vec![config]);
+
+    // A Restrict reference into the subtree blocks a soft delete too
+    assert!(matches!(store.soft_delete_entity(config), Err(Error::EntityReferenced(id, by, _)) if id == nested && by == link));
+    assert!(store.entity_exists(nested));
+    store.write(link, &[ft_lock], Value::EntityReference(None), None, None, None, None)?;
+
+    // Watchers of the parent's children hear about it, and finds by value
+    // stay right without rebuilding their index
+    let queue = NotificationQueue::new();
+    store.register_notification(
+        NotifyConfig::EntityId { entity_id: root, field_type: ft_children, trigger_on_change: true, context: vec![], filter: None },
+        queue.clone(),
+    )?;
+    store.soft_delete_entity(config)?;
+    let notification = queue.pop().expect("soft delete notification");
+    assert_eq!(notification.current.value, Some(Value::EntityList(vec![])));
+    assert_eq!(notification.previous.value, Some(Value::EntityList(vec![config])));
+    assert!(store.find_entities(et_folder, Some("Name == 'Config'"))?.is_empty());
+    assert!(store.find_entities(et_folder, Some("Name == 'Nested'"))?.is_empty());
+
+    // A sibling that took the name in the meantime blocks the restore
+    let taken = store.create_entity(et_folder, Some(root), "Config")?;
+    queue.pop();
+    assert!(matches!(store.restore_entity(config), Err(Error::EntityAlreadyExists(id)) if id == taken));
+    assert!(store.is_soft_deleted(config));
+    store.rename_entity(taken, "Config 2")?;
+    queue.pop();
+
+    store.restore_entity(config)?;
+    let notification = queue.pop().expect("restore notification");
+    assert_eq!(notification.current.value, Some(Value::EntityList(vec![taken, config])));
+    assert_eq!(store.find_entities(et_folder, Some("Name == 'Config'"))?, vec![config]);
+    assert_eq!(store.find_entities(et_folder, Some("Name == 'Nested'"))?, vec![nested]);
+    Ok(())
+}
+
+// A "Link" type with one reference field per OnDelete rule
+#[allow(dead_code)]
+fn add_link_schema(store: &mut Store) -> Result<()> {
+    let mut schema = EntitySchema::<Single, String, String>::new("Link".to_string(), vec!["Folder".to_string()]);
+    let reference = |name: &str, rank: i64, on_delete: OnDelete| {
+        FieldSchema::EntityReference {
+            field_type: name.to_string(),
+            default_value: None,
+            rank,
+            storage_scope: StorageScope::Configuration,
+            validator: None,
+            metadata: Default::default(),
+        }
+        .with_on_delete(on_delete)
+    };
+    schema.fields.insert("Target".to_string(), reference("Target", 4, OnDelete::Nullify));
+    schema.fields.insert("Owner".to_string(), reference("Owner", 5, OnDelete::Cascade));
+    schema.fields.insert("Lock".to_string(), reference("Lock", 6, OnDelete::Restrict));
+    schema.fields.insert("Members".to_string(), FieldSchema::EntityList {
+        field_type: "Members".to_string(),
+        default_value: vec![],
+        rank: 7,
+        storage_scope: StorageScope::Configuration,
+        merge_policy: MergePolicy::LastWriterWins,
+        validator: None,
+        metadata: Default::default(),
+    }.with_on_delete(OnDelete::Nullify));
+    store.update_schema(schema)
+}
+
+#[test]
+fn test_on_delete_rules_for_references() -> Result<()> {
+    let mut store = setup_test_database()?;
+    add_link_schema(&mut store)?;
+
+    let et_folder = store.get_entity_type("Folder")?;
+    let et_link = store.get_entity_type("Link")?;
+    let ft_target = store.get_field_type("Target")?;
+    let ft_owner = store.get_field_type("Owner")?;
+    let ft_lock = store.get_field_type("Lock")?;
+    let ft_members = store.get_field_type("Members")?;
+    assert_eq!(store.get_field_schema(et_link, ft_lock)?.on_delete(), Some(OnDelete::Restrict));
+
+    let folder = store.create_entity(et_folder, None, "Folder")?;
+    let child = store.create_entity(et_folder, Some(folder), "Child")?;
+    let keep = store.create_entity(et_folder, None, "Keep")?;
+    let link = store.create_entity(et_link, None, "Link")?;
+    store.write(link, &[ft_target], Value::EntityReference(Some(child)), None, None, None, None)?;
+    store.write(link, &[ft_members], Value::EntityList(vec![keep, child]), None, None, None, None)?;
+    let owned = store.create_entity(et_link, None, "Owned")?;
+    store.write(owned, &[ft_owner], Value::EntityReference(Some(folder)), None, None, None, None)?;
+
+    // A Restrict reference into the subtree blocks the delete, even one
+    // coming from an entity the delete would cascade to
+    let locked = store.create_entity(et_link, None, "Locked")?;
+    store.write(locked, &[ft_lock], Value::EntityReference(Some(owned)), None, None, None, None)?;
+    assert!(matches!(store.delete_entity(folder), Err(Error::EntityReferenced(id, by, _)) if id == owned && by == locked));
+    assert!(store.entity_exists(child));
+    assert_eq!(store.read(link, &[ft_target])?.0, Value::EntityReference(Some(child)));
+
+    store.write(locked, &[ft_lock], Value::EntityReference(None), None, None, None, None)?;
+    store.delete_entity(folder)?;
+    assert!(!store.entity_exists(child));
+    assert!(!store.entity_exists(owned));
+    assert_eq!(store.read(link, &[ft_target])?.0, Value::EntityReference(None));
+    assert_eq!(store.read(link, &[ft_members])?.0, Value::EntityList(vec![keep]));
+    assert!(store.find_dangling_references()?.is_empty());
+
+    // References left dangling some other way are reported
+    let ft_children = store.get_field_type("Children")?;
+    store.write(keep, &[ft_children], Value::EntityList(vec![child]), None, None, None, None)?;
+    assert_eq!(
+        store.find_dangling_references()?,
+        vec![DanglingReference { entity_id: keep, field_type: ft_children, target: child }]
+    );
+    Ok(())
+}
+
+#[test]
+fn test_failed_delete_undoes_nullified_references() -> Result<()> {
+    let mut store = setup_test_database()?;
+    add_link_schema(&mut store)?;
+    trigger_schema().apply(&mut store)?;
+    let et_folder = store.get_entity_type("Folder")?;
+    let et_link = store.get_entity_type("Link")?;
+    let ft_target = store.get_field_type("Target")?;
+    let ft_owner = store.get_field_type("Owner")?;
+    let ft_lock = store.get_field_type("Lock")?;
+    let ft_members = store.get_field_type("Members")?;
+
+    let folder = store.create_entity(et_folder, None, "Folder")?;
+    let link = store.create_entity(et_link, None, "Link")?;
+    store.write(link, &[ft_target], Value::EntityReference(Some(folder)), None, None, None, None)?;
+    let owned = store.create_entity(et_link, None, "Owned")?;
+    store.write(owned, &[ft_owner], Value::EntityReference(Some(folder)), None, None, None, None)?;
+    let locked = store.create_entity(et_link, None, "Locked")?;
+    store.write(locked, &[ft_lock], Value::EntityReference(Some(owned)), None, None, None, None)?;
+
+    let queue = NotificationQueue::new();
+    store.register_notification(
+        NotifyConfig::EntityId { entity_id: link, field_type: ft_target, trigger_on_change: false, context: vec![], filter: None },
+        queue.clone(),
+    )?;
+    store.write_queue.clear();
+
+    // Link's Target is nullified before the cascade to Owned finds the
+    // Restrict reference from Locked
+    assert!(matches!(store.delete_entity(folder), Err(Error::EntityReferenced(id, by, _)) if id == owned && by == locked));
+    assert_eq!(store.read(link, &[ft_target])?.0, Value::EntityReference(Some(folder)));
+    assert!(store.entity_exists(folder) && store.entity_exists(owned));
+    assert!(store.write_queue.is_empty());
+    assert!(queue.pop().is_none());
+
+    // The same goes for a trigger on the nullified field that fails
+    store.write(locked, &[ft_lock], Value::EntityReference(None), None, None, None, None)?;
+    store.register_trigger(None, Trigger {
+        entity_type: et_link,
+        field_type: ft_target,
+        condition: None,
+        action: TriggerAction::Write { field_path: vec![ft_members], expression: "'not a list'".to_string() },
+    })?;
+    store.write_queue.clear();
+    assert!(store.delete_entity(folder).is_err());
+    assert_eq!(store.read(link, &[ft_target])?.0, Value::EntityReference(Some(folder)));
+    assert!(store.entity_exists(folder) && store.entity_exists(owned));
+    assert!(store.write_queue.is_empty());
+    Ok(())
+}
+
+#[test]
+fn test_find_referencing_follows_changes() -> Result<()> {
+    let mut store = setup_test_database()?;
+    let mut schema = EntitySchema::<Single, String, String>::new("Valve".to_string(), vec!["Folder".to_string()]);
+    schema.fields.insert("Pump".to_string(), FieldSchema::EntityReference {
+        field_type: "Pump".to_string(),
+        default_value: None,
+        rank: 4,
+        storage_scope: StorageScope::Configuration,
+        validator: None,
+        metadata: Default::default(),
+    });
+    store.update_schema(schema)?;
+
+    let et_folder = store.get_entity_type("Folder")?;
+    let et_valve = store.get_entity_type("Valve")?;
+    let ft_pump = store.get_field_type("Pump")?;
+    let ft_parent = store.get_field_type("Parent")?;
+    let ft_children = store.get_field_type("Children")?;
+
+    let folder = store.create_entity(et_folder, None, "Pumps")?;
+    let pump = store.create_entity(et_folder, Some(folder), "Pump")?;
+    let spare = store.create_entity(et_folder, Some(folder), "Spare")?;
+    let valve = store.create_entity(et_valve, None, "Valve")?;
+
+    // The tree is indexed like any other reference
+    assert_eq!(store.find_referencing(pump), vec![(folder, ft_children)]);
+    assert_eq!(store.find_referencing(folder), vec![(pump, ft_parent), (spare, ft_parent)]);
+
+    store.write(valve, &[ft_pump], Value::EntityReference(Some(pump)), None, None, None, None)?;
+    assert_eq!(store.find_referencing(pump), vec![(folder, ft_children), (valve, ft_pump)]);
+
+    // Pointing elsewhere moves the entry
+    store.write(valve, &[ft_pump], Value::EntityReference(Some(spare)), None, None, None, None)?;
+    assert_eq!(store.find_referencing(pump), vec![(folder, ft_children)]);
+    assert_eq!(store.find_referencing(spare), vec![(folder, ft_children), (valve, ft_pump)]);
+
+    // Soft deleting the referrer hides its references until it is restored
+    store.soft_delete_entity(valve)?;
+    assert_eq!(store.find_referencing(spare), vec![(folder, ft_children)]);
+    store.restore_entity(valve)?;
+    assert_eq!(store.find_referencing(spare), vec![(folder, ft_children), (valve, ft_pump)]);
+
+    // References to a deleted entity stay visible until the referrer changes
+    store.delete_entity(spare)?;
+    assert_eq!(store.find_referencing(spare), vec![(valve, ft_pump)]);
+    assert_eq!(store.find_referencing(folder), vec![(pump, ft_parent)]);
+
+    // The index is rebuilt from a restored snapshot
+    let mut restored = Store::new();
+    restored.restore_snapshot(store.take_snapshot());
+    assert_eq!(restored.find_referencing(spare), vec![(valve, ft_pump)]);
+    assert_eq!(restored.find_referencing(pump), vec![(folder, ft_children)]);
+    Ok(())
+}
+
+#[test]
+fn test_aggregate_fields() -> Result<()> {
+    let mut store = setup_test_database()?;
+    let mut schema = EntitySchema::<Single, String, String>::new("Pump".to_string(), vec!["Folder".to_string()]);
+    schema.fields.insert("Power".to_string(), FieldSchema::Int {
+        field_type: "Power".to_string(),
+        default_value: 0,
+        rank: 4,
+        storage_scope: StorageScope::Runtime,
+        merge_policy: MergePolicy::LastWriterWins,
+        validator: None,
+        metadata: Default::default(),
+    });
+    store.update_schema(schema)?;
+
+    let et_pump = store.get_entity_type("Pump")?;
+    let ft_power = store.get_field_type("Power")?;
+    let ft_name = store.get_field_type("Name")?;
+
+    // Nothing to aggregate yet
+    assert_eq!(store.aggregate(et_pump, ft_power, AggregateOp::Count, None)?, Some(Value::Int(0)));
+    assert_eq!(store.aggregate(et_pump, ft_power, AggregateOp::Sum, None)?, Some(Value::Int(0)));
+    assert_eq!(store.aggregate(et_pump, ft_power, AggregateOp::Max, None)?, None);
+
+    for (name, power) in [("P1", 30), ("P2", 5), ("P3", 12)] {
+        let pump = store.create_entity(et_pump, None, name)?;
+        store.write(pump, &[ft_power], Value::Int(power), None, None, None, None)?;
+    }
+
+    assert_eq!(store.aggregate(et_pump, ft_power, AggregateOp::Count, None)?, Some(Value::Int(3)));
+    assert_eq!(store.aggregate(et_pump, ft_power, AggregateOp::Sum, None)?, Some(Value::Int(47)));
+    assert_eq!(store.aggregate(et_pump, ft_power, AggregateOp::Min, None)?, Some(Value::Int(5)));
+    assert_eq!(store.aggregate(et_pump, ft_power, AggregateOp::Max, None)?, Some(Value::Int(30)));
+    assert_eq!(store.aggregate(et_pump, ft_power, AggregateOp::Sum, Some("Power > 10"))?, Some(Value::Int(42)));
+    assert_eq!(store.aggregate(et_pump, ft_name, AggregateOp::Max, None)?, Some(Value::String("P3".to_string())));
+
+    // Strings can be compared but not added
+    assert!(store.aggregate(et_pump, ft_name, AggregateOp::Sum, None).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_sorted_pagination_is_stable() -> Result<()> {
+    let mut store = setup_test_database()?;
+    let mut schema = EntitySchema::<Single, String, String>::new("Pump".to_string(), vec!["Folder".to_string()]);
+    schema.fields.insert("Power".to_string(), FieldSchema::Int {
+        field_type: "Power".to_string(),
+        default_value: 0,
+        rank: 4,
+        storage_scope: StorageScope::Runtime,
+        merge_policy: MergePolicy::LastWriterWins,
+        validator: None,
+        metadata: Default::default(),
+    });
+    store.update_schema(schema)?;
+
+    let et_pump = store.get_entity_type("Pump")?;
+    let ft_power = store.get_field_type("Power")?;
+    let ft_children = store.get_field_type("Children")?;
+
+    let mut pumps = Vec::new();
+    for (name, power) in [("P1", 30), ("P2", 5), ("P3", 12), ("P4", 12), ("P5", 50)] {
+        let pump = store.create_entity(et_pump, None, name)?;
+        store.write(pump, &[ft_power], Value::Int(power), None, None, None, None)?;
+        pumps.push(pump);
+    }
+
+    // Highest power first, equal values ordered by id
+    let opts = PageOpts::new(2, None).with_order_by(ft_power, SortDirection::Descending);
+    let first = store.find_entities_paginated(et_pump, Some(&opts), None)?;
+    assert_eq!(first.items, vec![pumps[4], pumps[0]]);
+    assert_eq!(first.total, 5);
+    assert_eq!(first.next_key, Some((Value::Int(30), pumps[0])));
+
+    // Changes between pages don't shift the next one
+    store.delete_entity(pumps[4])?;
+    let p6 = store.create_entity(et_pump, None, "P6")?;
+    store.write(p6, &[ft_power], Value::Int(100), None, None, None, None)?;
+
+    let opts = first.next_page(&opts).expect("more pages");
+    let second = store.find_entities_paginated(et_pump, Some(&opts), None)?;
+    assert_eq!(second.items, vec![pumps[3], pumps[2]]);
+
+    let opts = second.next_page(&opts).expect("more pages");
+    let third = store.find_entities_paginated(et_pump, Some(&opts), None)?;
+    assert_eq!(third.items, vec![pumps[1]]);
+    assert!(third.next_page(&opts).is_none());
+
+    // Filters and exact queries sort the same way
+    let opts = PageOpts::new(10, None).with_order_by(ft_power, SortDirection::Ascending);
+    let filtered = store.find_entities_exact(et_pump, Some(&opts), Some("Power > 10"))?;
+    assert_eq!(filtered.items, vec![pumps[2], pumps[3], pumps[0], p6]);
+    assert!(filtered.next_key.is_none());
+
+    // Lists have no order to sort by
+    let opts = PageOpts::new(10, None).with_order_by(ft_children, SortDirection::Ascending);
+    assert!(store.find_entities_paginated(et_pump, Some(&opts), None).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_live_query_tracks_matches() -> Result<()> {
+    let mut store = setup_test_database()?;
+    let mut schema = EntitySchema::<Single, String, String>::new("Pump".to_string(), vec!["Folder".to_string()]);
+    schema.fields.insert("Power".to_string(), FieldSchema::Int {
+        field_type: "Power".to_string(),
+        default_value: 0,
+        rank: 4,
+        storage_scope: StorageScope::Runtime,
+        merge_policy: MergePolicy::LastWriterWins,
+        validator: None,
+        metadata: Default::default(),
+    });
+    store.update_schema(schema)?;
+
+    let et_pump = store.get_entity_type("Pump")?;
+    let ft_power = store.get_field_type("Power")?;
+    let ft_name = store.get_field_type("Name")?;
+
+    let p1 = store.create_entity(et_pump, None, "P1")?;
+    let p2 = store.create_entity(et_pump, None, "P2")?;
+    store.write(p1, &[ft_power], Value::Int(20), None, None, None, None)?;
+
+    let mut query = LiveQuery::new(&store, et_pump, Some("Power > 10"), &[ft_name])?;
+    assert_eq!(query.entities().collect::<Vec<_>>(), vec![p1]);
+
+    let queue = NotificationQueue::new();
+    for config in query.notify_configs() {
+        store.register_notification(config, queue.clone())?;
+    }
+    // Someone else's registration on the same queue
+    store.register_notification(NotifyConfig::EntityId { entity_id: p2, field_type: ft_power, trigger_on_change: false, context: vec![], filter: None }, queue.clone())?;
+
+    let mut events = Vec::new();
+    let mut drain = |store: &Store, query: &mut LiveQuery| {
+        while let Some(notification) = queue.pop() {
+            events.extend(query.apply(store, &notification));
+        }
+    };
+
+    store.write(p2, &[ft_power], Value::Int(15), None, None, None, None)?;
+    store.write(p1, &[ft_power], Value::Int(5), None, None, None, None)?;
+    store.write(p2, &[ft_name], Value::String("Main".to_string()), None, None, None, None)?;
+    drain(&store, &mut query);
+    assert_eq!(events, vec![
+        LiveQueryEvent::Added(p2),
+        LiveQueryEvent::Removed(p1),
+        LiveQueryEvent::Updated { entity_id: p2, field_type: ft_name, value: Some(Value::String("Main".to_string())) },
+    ]);
+    assert_eq!(query.entities().collect::<Vec<_>>(), vec![p2]);
+
+    // Creates and deletes are picked up by a refresh
+    let p3 = store.create_entity(et_pump, None, "P3")?;
+    store.write(p3, &[ft_power], Value::Int(11), None, None, None, None)?;
+    store.delete_entity(p2)?;
+    assert_eq!(query.refresh(&store)?, vec![LiveQueryEvent::Removed(p2), LiveQueryEvent::Added(p3)]);
+    assert_eq!(query.len(), 1);
+    Ok(())
+}
+
+#[cfg(feature = "search")]
+#[test]
+fn test_search_searchable_fields() -> Result<()> {
+    let mut store = setup_test_database()?;
+    let mut schema = EntitySchema::<Single, String, String>::new("Pump".to_string(), vec!["Folder".to_string()]);
+    schema.fields.insert("Description".to_string(), FieldSchema::String {
+        field_type: "Description".to_string(),
+        default_value: "".to_string(),
+        rank: 4,
+        storage_scope: StorageScope::Configuration,
+        validator: None,
+        metadata: Default::default(),
+    }.with_searchable(true));
+    store.update_schema(schema)?;
+
+    let et_pump = store.get_entity_type("Pump")?;
+    let et_folder = store.get_entity_type("Folder")?;
+    let ft_description = store.get_field_type("Description")?;
+
+    let p1 = store.create_entity(et_pump, None, "P1")?;
+    let p2 = store.create_entity(et_pump, None, "P2")?;
+    let folder = store.create_entity(et_folder, None, "Cooling water")?;
+    store.write(p1, &[ft_description], Value::String("Cooling water pump, north basin".to_string()), None, None, None, None)?;
+    store.write(p2, &[ft_description], Value::String("Spare pump for the water tower".to_string()), None, None, None, None)?;
+
+    assert_eq!(store.search(et_pump, "water")?, vec![p1, p2]);
+    assert_eq!(store.search(et_pump, "WATER north")?, vec![p1]);
+    assert_eq!(store.search(et_pump, "bas*")?, vec![p1]);
+    assert_eq!(store.search(et_pump, "\"water pump\"")?, vec![p1]);
+    assert_eq!(store.search(et_pump, "\"pump water\"")?, Vec::<EntityId>::new());
+
+    // Only searchable fields are indexed (not the folder's Name)
+    assert_eq!(store.search(et_folder, "cooling")?, vec![p1]);
+    assert!(!store.search(et_folder, "cooling")?.contains(&folder));
+
+    // Writes and deletes keep the index current
+    store.write(p1, &[ft_description], Value::String("Drain pump".to_string()), None, None, None, None)?;
+    assert_eq!(store.search(et_pump, "water")?, vec![p2]);
+    assert_eq!(store.search(et_pump, "drain")?, vec![p1]);
+    store.delete_entity(p2)?;
+    assert_eq!(store.search(et_pump, "pump")?, vec![p1]);
+
+    // A restored store is indexed from its snapshot
+    let mut restored = Store::new();
+    restored.restore_snapshot(store.take_snapshot());
+    assert_eq!(restored.search(et_pump, "drain")?, vec![p1]);
+
+    assert!(store.search(et_pump, "  ").is_err());
+    assert!(store.search(et_pump, "\"water").is_err());
+    Ok(())
+}
+
+#[test]
+fn test_series_keeps_bounded_history() -> Result<()> {
+    let mut store = setup_test_database()?;
+    let mut schema = EntitySchema::<Single, String, String>::new("Meter".to_string(), vec!["Folder".to_string()]);
+    schema.fields.insert("Flow".to_string(), FieldSchema::Series {
+        field_type: "Flow".to_string(),
+        sample_type: SampleType::Float,
+        capacity: 4,
+        retention: None,
+        rank: 4,
+        storage_scope: StorageScope::Runtime,
+        validator: None,
+        metadata: Default::default(),
+    });
+    schema.fields.insert("Speed".to_string(), FieldSchema::Series {
+        field_type: "Speed".to_string(),
+        sample_type: SampleType::Int,
+        capacity: 100,
+        retention: Some(Duration::seconds(15)),
+        rank: 5,
+        storage_scope: StorageScope::Runtime,
+        validator: None,
+        metadata: Default::default(),
+    });
+    store.update_schema(schema)?;
+
+    let et_meter = store.get_entity_type("Meter")?;
+    let ft_flow = store.get_field_type("Flow")?;
+    let ft_speed = store.get_field_type("Speed")?;
+    let ft_name = store.get_field_type("Name")?;
+    let meter = store.create_entity(et_meter, None, "M1")?;
+
+    // Nothing is recorded until the first write
+    assert_eq!(store.read(meter, &[ft_flow])?.0, Value::Float(0.0));
+    assert!(store.read_series(meter, ft_flow, None, None, None)?.is_empty());
+
+    let start = now() + Duration::hours(1);
+    let at = |secs: i64| start + Duration::seconds(secs);
+    for (i, flow) in [1.0, 2.0, 3.0, 4.0, 5.0].into_iter().enumerate() {
+        let time = at(i as i64 * 10);
+        store.write(meter, &[ft_flow], Value::Float(flow), None, Some(time), None, None)?;
+        store.write(meter, &[ft_speed], Value::Int(i as i64), None, Some(time), None, None)?;
+    }
+
+    // Reads give the latest sample, the series the last `capacity` ones
+    assert_eq!(store.read(meter, &[ft_flow])?.0, Value::Float(5.0));
+    let history = store.read_series(meter, ft_flow, None, None, None)?;
+    assert_eq!(history, vec![
+        (at(10), Value::Float(2.0)),
+        (at(20), Value::Float(3.0)),
+        (at(30), Value::Float(4.0)),
+        (at(40), Value::Float(5.0)),
+    ]);
+    assert_eq!(store.read_series(meter, ft_flow, Some(at(20)), Some(at(30)), None)?.len(), 2);
+
+    // Downsampling averages each interval, reported at its start
+    assert_eq!(store.read_series(meter, ft_flow, Some(at(10)), None, Some(Duration::seconds(20)))?, vec![
+        (at(10), Value::Float(2.5)),
+        (at(30), Value::Float(4.5)),
+    ]);
+    assert!(store.read_series(meter, ft_flow, None, None, Some(Duration::ZERO)).is_err());
+
+    // Retention drops samples older than that before the latest
+    assert_eq!(store.read_series(meter, ft_speed, None, None, None)?, vec![
+        (at(30), Value::Int(3)),
+        (at(40), Value::Int(4)),
+    ]);
+
+    // Samples must have the series' type, and only series have a history
+    assert!(store.write(meter, &[ft_flow], Value::Int(6), None, None, None, None).is_err());
+    assert!(store.read_series(meter, ft_name, None, None, None).is_err());
+
+    // The history is part of a snapshot
+    let mut restored = Store::new();
+    restored.restore_snapshot(store.take_snapshot());
+    assert_eq!(restored.read_series(meter, ft_flow, None, None, None)?, history);
+
+    store.delete_entity(meter)?;
+    assert!(store.read_series(meter, ft_flow, None, None, None).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_group_commit_batches_writes() -> Result<()> {
+    use std::time::{Duration as StdDuration, Instant};
+
+    let mut store = setup_test_database()?;
+    let et_folder = store.get_entity_type("Folder")?;
+    let ft_name = store.get_field_type("Name")?;
+    let folder = store.create_entity(et_folder, None, "F")?;
+    store.write_queue.clear();
+
+    let queue = NotificationQueue::new();
+    store.register_notification(
+        NotifyConfig::EntityId { entity_id: folder, field_type: ft_name, trigger_on_change: false, context: vec![], filter: None },
+        queue.clone(),
+    )?;
+
+    let mut group = GroupCommit::new(StdDuration::from_micros(500)).with_max_writes(10);
+    group.attach(&mut store);
+    let start = Instant::now();
+    assert!(!group.is_due(&store, start));
+    assert_eq!(group.time_left(start), None);
+
+    // Writes within the window wait, along with their notifications
+    store.write(folder, &[ft_name], Value::from("a"), None, None, None, None)?;
+    assert!(!group.is_due(&store, start));
+    store.write(folder, &[ft_name], Value::from("b"), None, None, None, None)?;
+    assert!(!group.is_due(&store, start + StdDuration::from_micros(200)));
+    assert_eq!(group.time_left(start + StdDuration::from_micros(200)), Some(StdDuration::from_micros(300)));
+    assert_eq!(queue.lag(), 0);
+    assert_eq!(store.held_notifications(), 2);
+
+    // A failed append keeps the batch for the next try
+    assert!(group.is_due(&store, start + StdDuration::from_micros(500)));
+    let failed = group.commit(&mut store, |_| Err(Error::InvalidRequest("disk full".to_string())));
+    assert!(failed.is_err());
+    assert_eq!(store.unread_writes(GROUP_COMMIT_WRITE_READER).count(), 2);
+    assert_eq!(queue.lag(), 0);
+
+    let mut appends = Vec::new();
+    assert_eq!(group.commit(&mut store, |writes| {
+        appends.push(writes.len());
+        Ok(())
+    })?, 2);
+    assert_eq!(appends, vec![2]);
+    assert_eq!(queue.lag(), 2);
+    assert!(!group.is_due(&store, start + StdDuration::from_micros(600)));
+
+    // A full batch is due straight away
+    for i in 0..10 {
+        store.write(folder, &[ft_name], Value::from(format!("n{}", i)), None, None, None, None)?;
+    }
+    assert!(group.is_due(&store, start + StdDuration::from_micros(601)));
+    assert_eq!(group.commit(&mut store, |_| Ok(()))?, 10);
+
+    store.release_notifications();
+    store.write(folder, &[ft_name], Value::from("c"), None, None, None, None)?;
+    assert_eq!(queue.lag(), 13);
+    Ok(())
+}
+
+#[test]
+fn test_group_commit_and_replication_share_the_write_queue() -> Result<()> {
+    use std::time::{Duration as StdDuration, Instant};
+
+    let mut store = setup_test_database()?;
+    let et_folder = store.get_entity_type("Folder")?;
+    let ft_name = store.get_field_type("Name")?;
+    let folder = store.create_entity(et_folder, None, "F")?;
+    let mut peer = Store::new();
+    peer.restore_snapshot(store.take_snapshot());
+    store.write_queue.clear();
+
+    let mut group = GroupCommit::new(StdDuration::ZERO);
+    group.attach(&mut store);
+    let mut replicator = PeerReplicator::new("machine-a", 100);
+    replicator.attach(&mut store);
+
+    // Replicating first doesn't take the writes from persistence
+    store.write(folder, &[ft_name], Value::from("a"), None, None, None, None)?;
+    replicator.drain_sync_writes(&mut store)?.expect("pending writes");
+    assert!(group.is_due(&store, Instant::now()));
+    let mut persisted = Vec::new();
+    group.commit(&mut store, |writes| {
+        persisted.extend_from_slice(writes);
+        Ok(())
+    })?;
+    assert_eq!(persisted.len(), 1);
+
+    // Nor does persisting first take them from replication
+    store.write(folder, &[ft_name], Value::from("b"), None, None, None, None)?;
+    assert_eq!(group.commit(&mut store, |_| Ok(()))?, 1);
+    assert!(replicator.has_unsent_writes(&store));
+    replicator.drain_sync_writes(&mut store)?.expect("pending writes");
+    assert!(store.write_queue.is_empty());
+
+    // Writes applied from a peer are persisted, not sent back
+    peer.write(folder, &[ft_name], Value::from("c"), None, None, None, None)?;
+    let batch = PeerReplicator::new("machine-b", 200).drain_sync_writes(&mut peer)?.expect("pending writes");
+    replicator.apply_sync_writes("machine-b", &mut store, &batch)?;
+    assert!(!replicator.has_unsent_writes(&store));
+    let mut persisted = Vec::new();
+    group.commit(&mut store, |writes| {
+        persisted.extend_from_slice(writes);
+        Ok(())
+    })?;
+    assert!(matches!(&persisted[..], [WriteInfo::FieldUpdate { value: Some(value), .. }] if *value == Value::from("c")));
+    assert!(store.write_queue.is_empty());
+    Ok(())
+}
+
+#[test]
+fn test_snapshot_checksum_verifies_restore() -> Result<()> {
+    let mut store = setup_test_database()?;
+    let et_folder = store.get_entity_type("Folder")?;
+    let ft_name = store.get_field_type("Name")?;
+    let folder = store.create_entity(et_folder, None, "Docs")?;
+    store.create_entity(et_folder, Some(folder), "Reports")?;
+
+    let snapshot = store.take_snapshot();
+    let checksum = verify_snapshot(&snapshot)?;
+    assert_eq!(snapshot.checksum.as_ref(), Some(&checksum));
+    assert_eq!(store.checksum(), checksum);
+    assert!(checksum.entity_types.contains_key("Folder"));
+
+    // A restore reproduces the checksum
+    let mut restored = Store::new();
+    restored.restore_snapshot(snapshot.clone());
+    assert_eq!(restored.checksum(), checksum);
+
+    // Corrupted content fails verification, naming the entity type
+    let mut corrupted = snapshot.clone();
+    corrupted.fields.get_mut(&folder).unwrap().get_mut(&ft_name).unwrap().value = Value::from_string("Tampered".to_string());
+    match verify_snapshot(&corrupted) {
+        Err(Error::ChecksumMismatch(entity_types)) => assert_eq!(entity_types, vec!["Folder".to_string()]),
+        other => panic!("Expected a checksum mismatch, got {:?}", other),
+    }
+
+    // Any write changes the checksum of that entity type only
+    store.write(folder, &[ft_name], Value::from_string("Documents".to_string()), None, None, None, None)?;
+    assert_eq!(store.checksum().mismatches(&checksum), vec!["Folder".to_string()]);
+
+    // Without a checksum there is nothing to verify against
+    let unchecked = Snapshot { checksum: None, ..snapshot };
+    assert_eq!(verify_snapshot(&unchecked)?, checksum);
+    Ok(())
+}
+
+#[test]
+fn test_store_stats() -> Result<()> {
+    let mut store = setup_test_database()?;
+    let et_folder = store.get_entity_type("Folder")?;
+    let ft_name = store.get_field_type("Name")?;
+
+    let before = StoreTrait::stats(&store)?;
+    assert_eq!(before.entity_counts.get("Folder"), Some(&0));
+    assert_eq!(before.entity_type_names, store.get_entity_types()?.len());
+
+    let folder = store.create_entity(et_folder, None, "Docs")?;
+    store.create_entity(et_folder, Some(folder), "Reports")?;
+    store.write(folder, &[ft_name], Value::from_string("A much longer name for the folder".to_string()), None, None, None, None)?;
+    store.register_notification(
+        NotifyConfig::EntityId { entity_id: folder, field_type: ft_name, trigger_on_change: true, context: vec![], filter: None },
+        NotificationQueue::new(),
+    )?;
+    store.register_notification(
+        NotifyConfig::EntityType { entity_type: et_folder, field_type: ft_name, trigger_on_change: true, context: vec![], filter: None },
+        NotificationQueue::new(),
+    )?;
+
+    let stats = store.stats();
+    assert_eq!(stats.entity_counts.get("Folder"), Some(&2));
+    assert_eq!(stats.entity_count(), before.entity_count() + 2);
+    assert!(stats.field_count > before.field_count);
+    assert!(stats.memory_estimate > before.memory_estimate);
+    assert_eq!((stats.entity_notifications, stats.type_notifications), (1, 1));
+    assert_eq!(stats.field_type_names, before.field_type_names);
+
+    // The map sent for STATS reads back the same
+    assert_eq!(StoreStats::from_map(&stats.to_map())?, stats);
+    Ok(())
+}
+
+#[test]
+fn test_slowlog_keeps_slow_commands() -> Result<()> {
+    use crate::data::resp::{RespFromBytes, RespValue};
+    use std::time::Duration as StdDuration;
+
+    let mut store = Store::new();
+    assert_eq!(store.slowlog().threshold_micros(), Some(DEFAULT_SLOWLOG_THRESHOLD_MICROS));
+    store.slowlog_mut().set_threshold_micros(Some(1_000));
+    store.slowlog_mut().set_capacity(2);
+    store.set_client_context(ClientContext { connection: Some("10.0.0.5:4000".to_string()), ..Default::default() });
+
+    let long_name = "x".repeat(200);
+    let frame = format!("*3\r\n$4\r\nFIND\r\n$6\r\nObject\r\n${}\r\n{}\r\n", long_name.len(), long_name);
+    let (request, _) = RespValue::from_bytes(frame.as_bytes())?;
+
+    // Fast commands aren't logged
+    assert!(!store.record_slow_command(&request, StdDuration::from_micros(999)));
+    assert!(store.slowlog().is_empty());
+
+    for micros in [1_000, 2_000, 3_000] {
+        assert!(store.record_slow_command(&request, StdDuration::from_micros(micros)));
+    }
+
+    // Newest first, bounded by the capacity
+    let entries = store.slowlog().get(None);
+    assert_eq!(entries.iter().map(|entry| (entry.id, entry.duration_micros)).collect::<Vec<_>>(), vec![(2, 3_000), (1, 2_000)]);
+    assert_eq!(store.slowlog().get(Some(1)).len(), 1);
+    assert_eq!(entries[0].client.as_deref(), Some("10.0.0.5:4000"));
+    assert_eq!(&entries[0].args[..2], &["FIND".to_string(), "Object".to_string()]);
+    assert_eq!(entries[0].args[2], format!("{}... (72 more chars)", "x".repeat(128)));
+
+    // A reset drops the entries but ids keep counting
+    store.slowlog_mut().reset();
+    assert!(store.slowlog().is_empty());
+    store.record_slow_command(&request, StdDuration::from_millis(5));
+    assert_eq!(store.slowlog().get(None)[0].id, 3);
+
+    // Without a threshold nothing is logged
+    store.slowlog_mut().set_threshold_micros(None);
+    assert!(!store.record_slow_command(&request, StdDuration::from_secs(1)));
+    Ok(())
+}
+
+#[test]
+fn test_drain_refuses_new_commands_and_waits() -> Result<()> {
+    use crate::data::resp::ProtocolError;
+    use std::time::Duration as StdDuration;
+
+    let drain = Drain::new();
+    let guard = drain.begin()?;
+    assert_eq!(drain.in_flight(), 1);
+
+    drain.start_draining();
+    assert!(drain.is_draining());
+    assert!(matches!(drain.begin(), Err(Error::ProtocolError(ProtocolError::ShuttingDown))));
+    // The running command keeps the drain from finishing
+    assert!(!drain.wait_idle(StdDuration::from_millis(10)));
+
+    let waiter = {
+        let drain = drain.clone();
+        std::thread::spawn(move || drain.wait_idle(StdDuration::from_secs(5)))
+    };
+    drop(guard);
+    assert!(waiter.join().expect("waiter thread"));
+    assert_eq!(drain.in_flight(), 0);
+    Ok(())
+}
+
+#[test]
+fn test_create_entity_with_fields_is_all_or_nothing() -> Result<()> {
+    let mut store = setup_test_database()?;
+
+    let mut schema = EntitySchema::<Single, String, String>::new("Pump".to_string(), vec!["Folder".to_string()]);
+    schema.fields.insert(
+        "Speed".to_string(),
+        FieldSchema::Int {
+            field_type: "Speed".to_string(),
+            default_value: 0,
+            rank: 4,
+            storage_scope: StorageScope::Runtime,
+            merge_policy: MergePolicy::LastWriterWins,
+            validator: Some("new <= 100".to_string()),
+            metadata: Default::default(),
+        }
+    );
+    store.update_schema(schema)?;
+
+    let et_pump = store.get_entity_type("Pump")?;
+    let ft_speed = store.get_field_type("Speed")?;
+    let ft_parent = store.get_field_type("Parent")?;
+    let queue = NotificationQueue::new();
+    store.register_notification(
+        NotifyConfig::EntityType { entity_type: et_pump, field_type: ft_speed, trigger_on_change: false, context: vec![], filter: None },
+        queue.clone(),
+    )?;
+    store.write_queue.clear();
+
+    let pump = store.create_entity_with_fields(et_pump, None, "Pump1", vec![(ft_speed, Value::Int(50))])?;
+    assert_eq!(store.read(pump, &[ft_speed])?.0, Value::Int(50));
+    assert_eq!(queue.pop().and_then(|notification| notification.current.value), Some(Value::Int(50)));
+    assert!(matches!(store.write_queue.front(), Some(WriteInfo::CreateEntity { .. })));
+    assert!(matches!(store.write_queue.back(), Some(WriteInfo::FieldUpdate { .. })));
+
+    // A rejected value leaves nothing behind: no entity, no writes to
+    // replicate and no notifications
+    store.write_queue.clear();
+    let rejected = [
+        vec![(ft_speed, Value::Int(50)), (ft_speed, Value::Int(150))],
+        vec![(ft_speed, Value::String("fast".into()))],
+        vec![(ft_parent, Value::EntityReference(None))],
+    ];
+    for fields in rejected {
+        assert!(store.create_entity_with_fields(et_pump, None, "Pump2", fields).is_err());
+    }
+    assert_eq!(store.find_entities(et_pump, None)?, vec![pump]);
+    assert!(store.write_queue.is_empty());
+    assert!(queue.pop().is_none());
+    assert_eq!(store.held_notifications(), 0);
+    Ok(())
+}
+
+#[test]
+fn test_create_entity_with_fields_undoes_trigger_writes() -> Result<()> {
+    use crate::testing::SchemaBuilder;
+
+    let mut store = Store::new();
+    let et_pump = SchemaBuilder::object("Pump")
+        .int("Speed", 0)
+        .int("Level", 0)
+        .string("Status", "idle")
+        .apply(&mut store)?;
+    trigger_schema().apply(&mut store)?;
+    let ft_speed = store.get_field_type("Speed")?;
+    let ft_level = store.get_field_type("Level")?;
+    let ft_status = store.get_field_type("Status")?;
+    let ft_parent = store.get_field_type(ft::PARENT)?;
+    let station = store.create_entity(et_pump, None, "Station")?;
+
+    // Setting the speed marks the parent busy, setting the level never settles
+    store.register_trigger(None, Trigger {
+        entity_type: et_pump,
+        field_type: ft_speed,
+        condition: None,
+        action: TriggerAction::Write { field_path: vec![ft_parent, ft_status], expression: "'busy'".to_string() },
+    })?;
+    store.register_trigger(None, Trigger {
+        entity_type: et_pump,
+        field_type: ft_level,
+        condition: None,
+        action: TriggerAction::Write { field_path: vec![ft_level], expression: "new + 1".to_string() },
+    })?;
+    store.write_queue.clear();
+
+    let fields = vec![(ft_speed, Value::Int(5)), (ft_level, Value::Int(0))];
+    assert!(store.create_entity_with_fields(et_pump, Some(station), "Pump1", fields).is_err());
+    assert_eq!(store.read(station, &[ft_status])?.0, Value::from("idle"));
+    assert_eq!(store.find_entities(et_pump, None)?, vec![station]);
+    assert!(store.write_queue.is_empty());
+    Ok(())
+}
+
+#[test]
+fn test_instantiate_template_copies_subtree() -> Result<()> {
+    use crate::testing::SchemaBuilder;
+
+    let mut store = Store::new();
+    let et_pump = SchemaBuilder::object("Pump").int("Speed", 0).apply(&mut store)?;
+    let et_machine = SchemaBuilder::object("Machine")
+        .bool(ft::IS_TEMPLATE, false)
+        .string("Model", "")
+        .entity_reference("MainPump")
+        .apply(&mut store)?;
+    let ft_speed = store.get_field_type("Speed")?;
+    let ft_model = store.get_field_type("Model")?;
+    let ft_main_pump = store.get_field_type("MainPump")?;
+    let ft_children = store.get_field_type(ft::CHILDREN)?;
+
+    let template = store.create_entity_with_fields(et_machine, None, "PressTemplate", vec![(ft_model, Value::String("P-100".into()))])?;
+    let feed_pump = store.create_entity_with_fields(et_pump, Some(template), "FeedPump", vec![(ft_speed, Value::Int(1500))])?;
+    store.create_entity(et_pump, Some(template), "CoolantPump")?;
+    store.write(template, &[ft_main_pump], Value::EntityReference(Some(feed_pump)), None, None, None, None)?;
+
+    // Only designated templates can be instantiated
+    assert!(matches!(store.instantiate_template(template, None, "Press1", vec![]), Err(Error::InvalidRequest(_))));
+    store.mark_template(template, true)?;
+    assert!(store.is_template(template)?);
+    assert!(!store.is_template(feed_pump)?);
+
+    let press = store.instantiate_template(template, None, "Press1", vec![(ft_model, Value::String("P-100X".into()))])?;
+    assert!(!store.is_template(press)?);
+    assert_eq!(crate::path(&store, press)?, "Press1");
+    assert_eq!(store.read(press, &[ft_model])?.0, Value::String("P-100X".into()));
+
+    // The children are copies, and the reference into the template now
+    // points at the copied pump
+    let children = store.read(press, &[ft_children])?.0.as_entity_list().cloned().unwrap_or_default();
+    assert_eq!(children.len(), 2);
+    assert!(!children.contains(&feed_pump));
+    let (main_pump, _, _) = store.read(press, &[ft_main_pump])?;
+    assert_eq!(main_pump, Value::EntityReference(Some(children[0])));
+    assert_eq!(crate::path(&store, children[0])?, "Press1/FeedPump");
+    assert_eq!(store.read(children[0], &[ft_speed])?.0, Value::Int(1500));
+
+    // A rejected override leaves no partial copy behind
+    let entities = store.find_entities(et_pump, None)?.len();
+    assert!(store.instantiate_template(template, None, "Press2", vec![(ft_model, Value::Int(2))]).is_err());
+    assert_eq!(store.find_entities(et_pump, None)?.len(), entities);
+    assert_eq!(store.find_entities(et_machine, None)?.len(), 2);
+    Ok(())
+}
+
+#[test]
+fn test_instantiate_template_undoes_failed_triggers() -> Result<()> {
+    use crate::testing::SchemaBuilder;
+
+    let mut store = Store::new();
+    let et_pump = SchemaBuilder::object("Pump").int("Speed", 0).apply(&mut store)?;
+    let et_machine = SchemaBuilder::object("Machine")
+        .bool(ft::IS_TEMPLATE, false)
+        .string("Model", "")
+        .string("Status", "idle")
+        .apply(&mut store)?;
+    trigger_schema().apply(&mut store)?;
+    let ft_speed = store.get_field_type("Speed")?;
+    let ft_model = store.get_field_type("Model")?;
+    let ft_status = store.get_field_type("Status")?;
+    let ft_parent = store.get_field_type(ft::PARENT)?;
+
+    let plant = store.create_entity(et_machine, None, "Plant")?;
+    let template = store.create_entity_with_fields(et_machine, None, "PressTemplate", vec![(ft_model, Value::String("P-100".into()))])?;
+    store.create_entity_with_fields(et_pump, Some(template), "FeedPump", vec![(ft_speed, Value::Int(1500))])?;
+    store.mark_template(template, true)?;
+
+    // Copying the model marks the new machine's parent busy, then copying
+    // the pump's speed sets off a trigger that never settles
+    store.register_trigger(None, Trigger {
+        entity_type: et_machine,
+        field_type: ft_model,
+        condition: None,
+        action: TriggerAction::Write { field_path: vec![ft_parent, ft_status], expression: "'busy'".to_string() },
+    })?;
+    store.register_trigger(None, Trigger {
+        entity_type: et_pump,
+        field_type: ft_speed,
+        condition: None,
+        action: TriggerAction::Write { field_path: vec![ft_speed], expression: "new + 1".to_string() },
+    })?;
+    store.write_queue.clear();
+
+    assert!(matches!(store.instantiate_template(template, Some(plant), "Press1", vec![]), Err(Error::ExecutionError(_))));
+    assert_eq!(store.read(plant, &[ft_status])?.0, Value::from("idle"));
+    assert_eq!(store.find_entities(et_machine, None)?, vec![plant, template]);
+    assert_eq!(store.find_entities(et_pump, None)?.len(), 1);
+    assert!(store.write_queue.is_empty());
+    Ok(())
+}
+
+#[test]
+fn test_get_or_create_and_upsert_field() -> Result<()> {
+    let mut store = setup_test_database()?;
+    let et_folder = store.get_entity_type("Folder")?;
+    let et_user = store.get_entity_type("User")?;
+    let ft_name = store.get_field_type("Name")?;
+
+    let (root, created) = store.get_or_create(et_folder, None, "Config")?;
+    assert!(created);
+    assert_eq!(store.get_or_create(et_folder, None, "Config")?, (root, false));
+
+    let (services, created) = store.get_or_create(et_folder, Some(root), "Services")?;
+    assert!(created);
+    assert_eq!(store.get_or_create(et_folder, Some(root), "Services")?, (services, false));
+    // A sibling of another type can't take the same path
+    assert!(matches!(store.get_or_create(et_user, Some(root), "Services"), Err(Error::EntityAlreadyExists(id)) if id == services));
+
+    // Services starting together agree on one entity
+    let shared = std::sync::Arc::new(SharedStore::new(store));
+    let services: Vec<_> = (0..4)
+        .map(|_| {
+            let shared = std::sync::Arc::clone(&shared);
+            std::thread::spawn(move || {
+                let mut store = &*shared;
+                store.get_or_create(et_folder, Some(root), "Pumps")
+            })
+        })
+        .collect();
+    let results: Vec<(EntityId, bool)> = services.into_iter().map(|service| service.join().expect("service thread panicked")).collect::<Result<_>>()?;
+    assert!(results.iter().all(|(entity_id, _)| *entity_id == results[0].0));
+    assert_eq!(results.iter().filter(|(_, created)| *created).count(), 1);
+
+    // Rewriting the value a field already holds is a no-op
+    let mut store = &*shared;
+    let pumps = results[0].0;
+    shared.write_guard().write_queue.clear();
+    assert!(!store.upsert_field(pumps, ft_name, Value::from("Pumps"))?);
+    assert!(shared.write_guard().write_queue.is_empty());
+    assert!(store.upsert_field(pumps, ft_name, Value::from("Pumps 2"))?);
+    assert_eq!(store.read(pumps, &[ft_name])?.0, Value::from("Pumps 2"));
+    Ok(())
+}
+
+#[test]
+fn test_rename_entity_with_unique_names() -> Result<()> {
+    let mut store = setup_test_database()?;
+    let et_folder = store.get_entity_type("Folder")?;
+    let ft_name = store.get_field_type("Name")?;
+    let ft_children = store.get_field_type("Children")?;
+    let name_schema = store.get_field_schema(et_folder, ft_name)?;
+    store.set_field_schema(et_folder, ft_name, name_schema.with_unique_among_siblings(true))?;
+
+    let root = store.create_entity(et_folder, None, "Root")?;
+    let other = store.create_entity(et_folder, None, "Other")?;
+    let pumps = store.create_entity(et_folder, Some(root), "Pumps")?;
+    let valves = store.create_entity(et_folder, Some(root), "Valves")?;
+    let queue = NotificationQueue::new();
+    store.register_notification(
+        NotifyConfig::EntityId { entity_id: root, field_type: ft_children, trigger_on_change: true, context: vec![], filter: None },
+        queue.clone(),
+    )?;
+
+    // Watchers of the parent's children hear about the rename
+    store.rename_entity(pumps, "Motors")?;
+    assert_eq!(store.read(pumps, &[ft_name])?.0, Value::from("Motors"));
+    let notification = queue.pop().expect("rename notification");
+    assert_eq!(notification.current.entity_id, pumps);
+    assert_eq!(notification.current.value, Some(Value::from("Motors")));
+    assert_eq!(notification.previous.value, Some(Value::from("Pumps")));
+    store.rename_entity(pumps, "Motors")?;
+    assert!(queue.pop().is_none());
+
+    // Taken names are rejected on rename, create and move alike
+    assert!(matches!(store.rename_entity(pumps, "Valves"), Err(Error::EntityAlreadyExists(id)) if id == valves));
+    assert!(matches!(store.create_entity(et_folder, Some(root), "Motors"), Err(Error::EntityAlreadyExists(id)) if id == pumps));
+    let moved = store.create_entity(et_folder, Some(other), "Valves")?;
+    assert!(matches!(store.move_entity(moved, Some(root)), Err(Error::EntityAlreadyExists(id)) if id == valves));
+    assert_eq!(store.read(root, &[ft_children])?.0, Value::EntityList(vec![pumps, valves]));
+
+    // Names only have to be unique among siblings
+    store.rename_entity(moved, "Motors")?;
+    store.move_entity(moved, Some(pumps))?;
+    Ok(())
+}
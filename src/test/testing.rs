@@ -0,0 +1,98 @@
+#[allow(unused_imports)]
+use crate::*;
+#[allow(unused_imports)]
+use crate::testing::{MockMethod, MockStore, SchemaBuilder, TestCluster};
+
+#[test]
+fn test_mock_store_scripts_answers_and_failures() -> Result<()> {
+    let mut mock = MockStore::new();
+    let et_pump = SchemaBuilder::object("Pump").int("Speed", 0).apply(mock.store_mut())?;
+    let ft_speed = mock.get_field_type("Speed")?;
+    let pump = mock.create_entity(et_pump, None, "P1")?;
+
+    // Unscripted calls reach the backing store
+    mock.write(pump, &[ft_speed], Value::Int(10), None, None, None, None)?;
+    assert_eq!(mock.read(pump, &[ft_speed])?.0, Value::Int(10));
+
+    mock.respond_read(pump, &[ft_speed], Value::Int(1500));
+    assert_eq!(mock.read(pump, &[ft_speed])?.0, Value::Int(1500));
+    mock.respond_find(et_pump, vec![]);
+    assert!(mock.find_entities(et_pump, None)?.is_empty());
+
+    // Queued failures come first, then the standing one
+    mock.fail_next(MockMethod::Write, Error::Cancelled(1));
+    mock.fail(MockMethod::Write, Error::Cancelled(2));
+    let write = |mock: &mut MockStore| mock.write(pump, &[ft_speed], Value::Int(20), None, None, None, None);
+    assert!(matches!(write(&mut mock), Err(Error::Cancelled(1))));
+    assert!(matches!(write(&mut mock), Err(Error::Cancelled(2))));
+
+    mock.clear_script();
+    write(&mut mock)?;
+    assert_eq!(mock.read(pump, &[ft_speed])?.0, Value::Int(20));
+    assert_eq!(mock.find_entities(et_pump, None)?, vec![pump]);
+
+    assert_eq!(mock.call_count(MockMethod::Write), 4);
+    assert_eq!(mock.calls().last(), Some(&MockMethod::FindEntities));
+    mock.clear_calls();
+    assert!(mock.calls().is_empty());
+
+    mock.set_latency(MockMethod::EntityExists, std::time::Duration::from_millis(20));
+    let started = std::time::Instant::now();
+    assert!(mock.entity_exists(pump));
+    assert!(started.elapsed() >= std::time::Duration::from_millis(20));
+    Ok(())
+}
+
+#[test]
+fn test_schema_builder_ranks_fields_in_order() -> Result<()> {
+    let schema = SchemaBuilder::object("Pump")
+        .inherits("Device")
+        .int("Speed", 5)
+        .choice("Mode", &["Auto", "Manual"])
+        .build();
+
+    assert_eq!(schema.entity_type, "Pump");
+    assert_eq!(schema.inherit, vec!["Device".to_string()]);
+    let ranks: Vec<i64> = ["Name", "Parent", "Children", "Speed", "Mode"]
+        .iter()
+        .map(|name| schema.fields[*name].rank())
+        .collect();
+    assert_eq!(ranks, vec![0, 1, 2, 3, 4]);
+    assert_eq!(schema.fields["Speed"].default_value(), Value::Int(5));
+
+    let mut store = Store::new();
+    SchemaBuilder::object("Device").apply(&mut store)?;
+    let et_pump = SchemaBuilder::object("Pump").inherits("Device").int("Speed", 5).apply(&mut store)?;
+    let ft_speed = store.get_field_type("Speed")?;
+    let pump = store.create_entity(et_pump, None, "P1")?;
+    assert_eq!(store.read(pump, &[ft_speed])?.0, Value::Int(5));
+    Ok(())
+}
+
+#[test]
+fn test_cluster_replicates_and_heals() -> Result<()> {
+    let mut cluster = TestCluster::new(3)?;
+    assert_eq!(cluster.len(), 3);
+    assert!(cluster.replicator(2).peer("node-0").is_some_and(|peer| peer.synced));
+
+    let et_pump = cluster.update_schema(SchemaBuilder::object("Pump").int("Speed", 0).build())?;
+    let pump = cluster.store_mut(0).create_entity(et_pump, None, "P1")?;
+    assert_eq!(cluster.sync()?, 3);
+    let ft_speed = cluster.store(2).get_field_type("Speed")?;
+    assert!(cluster.store(2).entity_exists(pump));
+
+    // A partitioned node misses writes until it is healed
+    cluster.partition(2);
+    assert!(!cluster.is_connected(2));
+    cluster.store_mut(1).write(pump, &[ft_speed], Value::Int(7), None, None, None, None)?;
+    cluster.sync()?;
+    assert_eq!(cluster.store(0).read(pump, &[ft_speed])?.0, Value::Int(7));
+    assert_eq!(cluster.store(2).read(pump, &[ft_speed])?.0, Value::Int(0));
+
+    // On healing, the younger node takes a full sync from the older ones
+    cluster.heal(2)?;
+    assert!(cluster.is_connected(2));
+    assert_eq!(cluster.store(2).read(pump, &[ft_speed])?.0, Value::Int(7));
+    assert_eq!(cluster.sync()?, 0);
+    Ok(())
+}
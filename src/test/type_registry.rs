@@ -0,0 +1,88 @@
+use crate::*;
+use crate::testing::SchemaBuilder;
+
+#[allow(dead_code)]
+fn create_test_store() -> Result<Store> {
+    let mut store = Store::new();
+    SchemaBuilder::new("Pump")
+        .int("Speed", 0)
+        .runtime("Speed")
+        .int("RPMSetpoint", 0)
+        .runtime("RPMSetpoint")
+        .apply(&mut store)?;
+
+    Ok(store)
+}
+
+#[allow(dead_code)]
+#[derive(FieldTypes)]
+struct PumpFields {
+    speed: FieldType,
+    #[field_type(name = "RPMSetpoint")]
+    setpoint: FieldType,
+    flow_rate: Option<FieldType>,
+}
+
+#[allow(dead_code)]
+#[derive(FieldTypes)]
+struct StrictPumpFields {
+    speed: FieldType,
+    flow_rate: FieldType,
+}
+
+#[test]
+fn test_field_types_derive() -> Result<()> {
+    let store = create_test_store()?;
+
+    assert_eq!(PumpFields::NAMES, &["Speed", "RPMSetpoint", "FlowRate"]);
+
+    let fields = PumpFields::resolve(&store)?;
+    assert_eq!(fields.speed, store.get_field_type("Speed")?);
+    assert_eq!(fields.setpoint, store.get_field_type("RPMSetpoint")?);
+    assert_eq!(fields.flow_rate, None);
+
+    assert!(matches!(StrictPumpFields::resolve(&store), Err(Error::FieldTypeStrNotFound(name)) if name == "FlowRate"));
+    Ok(())
+}
+
+#[test]
+fn test_type_registry_caches_resolved_names() -> Result<()> {
+    let mut store = create_test_store()?;
+    let registry = TypeRegistry::new();
+    assert!(registry.is_empty());
+
+    assert_eq!(registry.get(&store, "Speed")?, store.get_field_type("Speed")?);
+    assert_eq!(registry.entity_type(&store, "Pump")?, store.get_entity_type("Pump")?);
+    assert_eq!(registry.len(), 2);
+
+    // Unknown names aren't cached, so they resolve once the schema exists
+    assert!(registry.get(&store, "FlowRate").is_err());
+    assert_eq!(registry.len(), 2);
+
+    let fields: PumpFields = registry.field_set(&store)?;
+    assert_eq!(fields.setpoint, store.get_field_type("RPMSetpoint")?);
+    assert_eq!(fields.flow_rate, None);
+    assert_eq!(registry.len(), 3);
+
+    let mut schema = EntitySchema::<Single, String, String>::new("Pump".to_string(), vec![]);
+    schema.fields.insert(
+        "FlowRate".to_string(),
+        FieldSchema::Float {
+            field_type: "FlowRate".to_string(),
+            default_value: 0.0,
+            rank: 2,
+            storage_scope: StorageScope::Runtime,
+            merge_policy: MergePolicy::LastWriterWins,
+            validator: None,
+            metadata: Default::default(),
+        }
+    );
+    store.update_schema(schema)?;
+    let fields: StrictPumpFields = registry.field_set(&store)?;
+    assert_eq!(fields.flow_rate, store.get_field_type("FlowRate")?);
+
+    registry.clear();
+    registry.preload(&store, &["Pump", "Missing"], &["Speed"])?;
+    assert_eq!(registry.len(), 2);
+    Ok(())
+}
@@ -0,0 +1,307 @@
+#[allow(unused_imports)]
+use crate::*;
+#[allow(unused_imports)]
+use std::collections::BTreeMap;
+
+#[test]
+fn test_value_conversions() -> Result<()> {
+    let entity_id = EntityId(42);
+
+    assert_eq!(Value::from(5), Value::Int(5));
+    assert_eq!(Value::from(1.5), Value::Float(1.5));
+    assert_eq!(Value::from("pump"), Value::String("pump".to_string()));
+    assert_eq!(Value::from(entity_id), Value::EntityReference(Some(entity_id)));
+    assert_eq!(Value::from(vec![entity_id]), Value::EntityList(vec![entity_id]));
+
+    assert_eq!(Value::from(5).try_as::<i64>()?, 5);
+    assert_eq!(Value::from("pump").try_as::<String>()?, "pump");
+    assert_eq!(Value::from(entity_id).try_as::<EntityId>()?, entity_id);
+    assert_eq!(Value::EntityReference(None).try_as::<Option<EntityId>>()?, None);
+    let speed: f64 = (&Value::Float(2.5)).try_into()?;
+    assert_eq!(speed, 2.5);
+
+    assert_eq!(Value::from("pump").as_str(), Some("pump"));
+    assert_eq!(Value::from(entity_id).as_entity_ref(), Some(entity_id));
+    assert_eq!(Value::EntityReference(None).as_entity_ref(), None);
+
+    // Mismatched types report what was found and what was expected
+    assert!(matches!(Value::from("pump").try_as::<i64>(), Err(Error::BadValueCast(Value::String(_), Value::Int(_)))));
+    assert!(matches!(Value::EntityReference(None).try_as::<EntityId>(), Err(Error::BadValueCast(_, _))));
+
+    Ok(())
+}
+
+#[allow(dead_code)]
+fn resp_round_trip(value: &Value) -> Result<Value> {
+    use crate::data::resp::{RespDecode, RespEncode, RespParser, RespToBytes};
+
+    let bytes = value.encode().to_bytes();
+    let (parsed, _) = RespParser::parse_value(&bytes)?;
+    Value::decode(parsed)
+}
+
+#[test]
+fn test_decimal_and_duration_values() -> Result<()> {
+    let price: Decimal = "12.50".parse()?;
+    assert_eq!(price.mantissa(), 1250);
+    assert_eq!(price.scale(), 2);
+    assert_eq!(price.to_string(), "12.50");
+    assert_eq!("-0.05".parse::<Decimal>()?.to_string(), "-0.05");
+    assert!("1.2.3".parse::<Decimal>().is_err());
+
+    // Equality is numeric, not representational
+    assert_eq!(price, "12.5".parse()?);
+    assert_eq!(Value::Decimal(price), Value::Decimal(Decimal::new(125, 1)?));
+    assert!(price < "12.51".parse()?);
+    assert_eq!(price.checked_add(&"0.005".parse()?), Some("12.505".parse()?));
+    assert_eq!(price.rescale(0)?, Decimal::from_int(13));
+
+    // Wire encoding keeps every digit
+    let exact: Decimal = "123456789.123456789".parse()?;
+    assert_eq!(resp_round_trip(&Value::Decimal(exact))?.try_as::<Decimal>()?.to_string(), "123456789.123456789");
+    let timeout = Duration::milliseconds(1500);
+    assert_eq!(resp_round_trip(&Value::Duration(timeout))?, Value::Duration(timeout));
+    // Existing variants keep their discriminants
+    assert_eq!(resp_round_trip(&Value::Timestamp(epoch()))?, Value::Timestamp(epoch()));
+
+    assert_eq!(value_to_json_value(&Value::Decimal(price), None), serde_json::json!("12.50"));
+    assert_eq!(value_to_json_value(&Value::Duration(timeout), None), serde_json::json!(1.5));
+    assert_eq!(value_to_json_value(&Value::Duration(Duration::seconds(30)), None), serde_json::json!(30));
+
+    Ok(())
+}
+
+/// Store with an Object base type (Name/Parent/Children) and a Root entity
+#[allow(dead_code)]
+fn setup_object_store() -> Result<(Store, EntityId)> {
+    let mut store = Store::new();
+
+    let mut schema = EntitySchema::<Single, String, String>::new("Object".to_string(), vec![]);
+    schema.fields.insert("Name".to_string(), FieldSchema::String {
+        field_type: "Name".to_string(),
+        default_value: String::new(),
+        rank: 0,
+        storage_scope: StorageScope::Configuration,
+        validator: None,
+        metadata: Default::default(),
+    });
+    schema.fields.insert("Parent".to_string(), FieldSchema::EntityReference {
+        field_type: "Parent".to_string(),
+        default_value: None,
+        rank: 1,
+        storage_scope: StorageScope::Configuration,
+        validator: None,
+        metadata: Default::default(),
+    });
+    schema.fields.insert("Children".to_string(), FieldSchema::EntityList {
+        field_type: "Children".to_string(),
+        default_value: Vec::new(),
+        rank: 2,
+        storage_scope: StorageScope::Configuration,
+        merge_policy: MergePolicy::LastWriterWins,
+        validator: None,
+        metadata: Default::default(),
+    });
+    store.update_schema(schema)?;
+    store.update_schema(EntitySchema::<Single, String, String>::new("Root".to_string(), vec!["Object".to_string()]))?;
+
+    let root = store.create_entity(store.get_entity_type("Root")?, None, "Root")?;
+    Ok((store, root))
+}
+
+#[test]
+fn test_decimal_and_duration_fields() -> Result<()> {
+    let (mut store, root) = setup_object_store()?;
+
+    let mut schema = EntitySchema::<Single, String, String>::new("Meter".to_string(), vec!["Object".to_string()]);
+    schema.fields.insert("Cost".to_string(), FieldSchema::Decimal {
+        field_type: "Cost".to_string(),
+        default_value: "0.00".parse()?,
+        rank: 3,
+        storage_scope: StorageScope::Configuration,
+        merge_policy: MergePolicy::LastWriterWins,
+        validator: None,
+        metadata: Default::default(),
+    });
+    schema.fields.insert("Runtime".to_string(), FieldSchema::Duration {
+        field_type: "Runtime".to_string(),
+        default_value: Duration::ZERO,
+        rank: 4,
+        storage_scope: StorageScope::Configuration,
+        merge_policy: MergePolicy::LastWriterWins,
+        validator: None,
+        metadata: Default::default(),
+    });
+    store.update_schema(schema)?;
+
+    let meter = store.create_entity(store.get_entity_type("Meter")?, Some(root), "M1")?;
+    let cost = store.get_field_type("Cost")?;
+    let runtime = store.get_field_type("Runtime")?;
+
+    // 0.1 + 0.2 adds up exactly
+    store.write(meter, &[cost], Value::Decimal("0.1".parse()?), None, None, None, Some(AdjustBehavior::Add))?;
+    store.write(meter, &[cost], Value::Decimal("0.2".parse()?), None, None, None, Some(AdjustBehavior::Add))?;
+    assert_eq!(store.read(meter, &[cost])?.0.try_as::<Decimal>()?.to_string(), "0.30");
+
+    store.write(meter, &[runtime], Value::Duration(Duration::minutes(5)), None, None, None, Some(AdjustBehavior::Add))?;
+    store.write(meter, &[runtime], Value::Duration(Duration::seconds(30)), None, None, None, Some(AdjustBehavior::Subtract))?;
+    assert_eq!(store.read(meter, &[runtime])?.0, Value::Duration(Duration::seconds(270)));
+
+    // CEL sees durations natively and decimals as doubles
+    let mut executor = crate::expr::CelExecutor::new();
+    let result = executor.execute("Runtime > duration('4m') && Cost > 0.25", meter, &store)?;
+    assert_eq!(result, cel::Value::Bool(true));
+    let result = executor.execute("Runtime + duration('30s')", meter, &store)?;
+    assert_eq!(crate::expr::cel_value_to_value(result)?, Value::Duration(Duration::minutes(5)));
+
+    // The schema and values survive a JSON round trip
+    let meter_schema = store.get_entity_schema(store.get_entity_type("Meter")?)?;
+    let restored_schema = JsonEntitySchema::from_entity_schema(&meter_schema, &store).to_entity_schema(&store)?;
+    assert_eq!(restored_schema.fields.get(&cost), meter_schema.fields.get(&cost));
+    assert_eq!(restored_schema.fields.get(&runtime), meter_schema.fields.get(&runtime));
+
+    let mut exported = export_subtree(&mut store, meter)?;
+    exported.fields.insert("Name".to_string(), serde_json::json!("M2"));
+    import_subtree(&mut store, &exported, Some(root), false)?;
+    let copy = path_to_entity_id(&store, "Root/M2")?;
+    assert_eq!(store.read(copy, &[cost])?.0, Value::Decimal("0.3".parse()?));
+    assert_eq!(store.read(copy, &[runtime])?.0, Value::Duration(Duration::seconds(270)));
+
+    Ok(())
+}
+
+#[test]
+fn test_string_list_and_map_fields() -> Result<()> {
+    let (mut store, root) = setup_object_store()?;
+
+    let mut schema = EntitySchema::<Single, String, String>::new("Drive".to_string(), vec!["Object".to_string()]);
+    schema.fields.insert("Tags".to_string(), FieldSchema::StringList {
+        field_type: "Tags".to_string(),
+        default_value: vec!["new".to_string()],
+        rank: 3,
+        storage_scope: StorageScope::Configuration,
+        merge_policy: MergePolicy::SetUnion,
+        validator: None,
+        metadata: Default::default(),
+    });
+    schema.fields.insert("Settings".to_string(), FieldSchema::Map {
+        field_type: "Settings".to_string(),
+        default_value: BTreeMap::new(),
+        rank: 4,
+        storage_scope: StorageScope::Configuration,
+        validator: None,
+        metadata: Default::default(),
+    });
+    store.update_schema(schema)?;
+
+    let drive = store.create_entity(store.get_entity_type("Drive")?, Some(root), "D1")?;
+    let tags = store.get_field_type("Tags")?;
+    let settings = store.get_field_type("Settings")?;
+
+    let queue = NotificationQueue::new();
+    store.register_notification(NotifyConfig::EntityId {
+        entity_id: drive,
+        field_type: settings,
+        trigger_on_change: true,
+        context: vec![],
+        filter: None,
+    }, queue.clone())?;
+
+    store.write(drive, &[tags], Value::from(vec!["pump".to_string(), "new".to_string()]), None, None, None, Some(AdjustBehavior::Add))?;
+    store.write(drive, &[tags], Value::from(vec!["new".to_string()]), None, None, None, Some(AdjustBehavior::Subtract))?;
+    assert_eq!(store.read(drive, &[tags])?.0, Value::StringList(vec!["pump".to_string()]));
+
+    let mut limits = BTreeMap::new();
+    limits.insert("max".to_string(), Value::Decimal("12.5".parse()?));
+    let mut entries = BTreeMap::new();
+    entries.insert("mode".to_string(), Value::from("auto"));
+    entries.insert("gain".to_string(), Value::Float(1.5));
+    entries.insert("limits".to_string(), Value::Map(limits));
+    store.write(drive, &[settings], Value::Map(entries.clone()), None, None, None, Some(AdjustBehavior::Add))?;
+
+    let mut removed = BTreeMap::new();
+    removed.insert("gain".to_string(), Value::Bool(true));
+    store.write(drive, &[settings], Value::Map(removed), None, None, None, Some(AdjustBehavior::Subtract))?;
+    entries.remove("gain");
+    assert_eq!(store.read(drive, &[settings])?.0, Value::Map(entries.clone()));
+
+    // Both writes changed the map, so both were notified with the full value
+    let first = queue.pop().expect("notification for the first write");
+    assert_eq!(first.previous.value, Some(Value::Map(BTreeMap::new())));
+    let second = queue.pop().expect("notification for the second write");
+    assert_eq!(second.current.value, Some(Value::Map(entries.clone())));
+    let json = serde_json::to_string(&second).map_err(|e| Error::InvalidRequest(e.to_string()))?;
+    let decoded: Notification = serde_json::from_str(&json).map_err(|e| Error::InvalidRequest(e.to_string()))?;
+    assert_eq!(decoded.current.value, Some(Value::Map(entries.clone())));
+
+    // Nested values survive the wire
+    assert_eq!(resp_round_trip(&Value::Map(entries.clone()))?, Value::Map(entries.clone()));
+
+    // Plain JSON where the type is unambiguous, tagged otherwise
+    let json = value_to_json_value(&Value::Map(entries.clone()), None);
+    assert_eq!(json, serde_json::json!({"mode": "auto", "limits": {"max": {"Decimal": "12.5"}}}));
+    let settings_schema = store.get_entity_schema(store.get_entity_type("Drive")?)?.fields[&settings].clone();
+    assert_eq!(json_value_to_value(&json, &settings_schema)?, Value::Map(entries.clone()));
+
+    // CEL sees lists and maps
+    let mut executor = crate::expr::CelExecutor::new();
+    let result = executor.execute("'pump' in Tags && Settings.mode == 'auto' && Settings.limits.max > 12.0", drive, &store)?;
+    assert_eq!(result, cel::Value::Bool(true));
+
+    Ok(())
+}
+
+#[test]
+fn test_field_metadata() -> Result<()> {
+    let (mut store, root) = setup_object_store()?;
+
+    let speed_metadata = FieldMetadata {
+        unit: Some("rpm".to_string()),
+        min: Some(0.0),
+        max: Some(100.0),
+        precision: Some(1),
+        label: Some("Speed".to_string()),
+        description: Some("Shaft speed".to_string()),
+        write_scope: None,
+        on_delete: None,
+        searchable: false,
+        unique_among_siblings: false,
+    };
+    let mut schema = EntitySchema::<Single, String, String>::new("Motor".to_string(), vec!["Object".to_string()]);
+    schema.fields.insert("Speed".to_string(), FieldSchema::Float {
+        field_type: "Speed".to_string(),
+        default_value: 0.0,
+        rank: 3,
+        storage_scope: StorageScope::Configuration,
+        merge_policy: MergePolicy::LastWriterWins,
+        validator: None,
+        metadata: Default::default(),
+    }.with_metadata(speed_metadata.clone()));
+    store.update_schema(schema)?;
+
+    let motor = store.create_entity(store.get_entity_type("Motor")?, Some(root), "M1")?;
+    let speed = store.get_field_type("Speed")?;
+
+    store.write(motor, &[speed], Value::Float(90.0), None, None, None, None)?;
+    assert!(matches!(
+        store.write(motor, &[speed], Value::Float(-1.0), None, None, None, None),
+        Err(Error::InvalidFieldValue(_))
+    ));
+    // The bound applies to the adjusted value, not the delta
+    assert!(matches!(
+        store.write(motor, &[speed], Value::Float(20.0), None, None, None, Some(AdjustBehavior::Add)),
+        Err(Error::InvalidFieldValue(_))
+    ));
+    assert_eq!(store.read(motor, &[speed])?.0, Value::Float(90.0));
+
+    // Metadata survives the wire and JSON schema formats
+    let motor_schema = store.get_entity_schema(store.get_entity_type("Motor")?)?;
+    let field_schema = motor_schema.fields.get(&speed).expect("Speed field");
+    assert_eq!(field_schema.metadata(), &speed_metadata);
+    let resp = FieldSchemaResp::from_field_schema(field_schema, &store);
+    assert_eq!(resp.to_field_schema().metadata(), &speed_metadata);
+    let restored_schema = JsonEntitySchema::from_entity_schema(&motor_schema, &store).to_entity_schema(&store)?;
+    assert_eq!(restored_schema.fields.get(&speed), Some(field_schema));
+
+    Ok(())
+}